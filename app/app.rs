@@ -1,24 +1,32 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use futures::{StreamExt, TryFutureExt};
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use plain_bitassets::{
     bip300301::{bitcoin, MainClient},
-    format_deposit_address,
+    format_deposit_address, heed,
     miner::{self, Miner},
     node::{self, Node, THIS_SIDECHAIN},
     types::{
-        self, BitcoinOutputContent, Body, FilledOutput, OutPoint, Output,
-        Transaction,
+        self, BitcoinOutputContent, Body, FilledOutput, Network, OutPoint,
+        Output, Transaction,
     },
+    util,
     wallet::{self, Wallet},
 };
-use tokio::{spawn, sync::RwLock as TokioRwLock, task::JoinHandle};
+use tokio::{
+    spawn, sync::RwLock as TokioRwLock, task::JoinHandle,
+    time::MissedTickBehavior,
+};
 use tokio_util::task::LocalPoolHandle;
 
-use crate::cli::Config;
+use crate::{audit_log::AuditLog, cli::Config};
 
-fn update_wallet(node: &Node, wallet: &Wallet) -> Result<(), Error> {
+fn update_wallet(
+    node: &Node,
+    wallet: &Wallet,
+    deposit_maturity: u32,
+) -> Result<(), Error> {
     let addresses = wallet.get_addresses()?;
     let unconfirmed_utxos =
         node.get_unconfirmed_utxos_by_addresses(&addresses)?;
@@ -39,6 +47,9 @@ fn update_wallet(node: &Node, wallet: &Wallet) -> Result<(), Error> {
     wallet.put_utxos(&utxos)?;
     wallet.put_unconfirmed_utxos(&unconfirmed_utxos)?;
     wallet.spend_utxos(&spent)?;
+    let immature_deposits =
+        node.get_immature_deposits(&addresses, deposit_maturity)?;
+    wallet.put_immature_deposits(&immature_deposits)?;
     Ok(())
 }
 
@@ -48,8 +59,9 @@ fn update(
     utxos: &mut HashMap<OutPoint, FilledOutput>,
     unconfirmed_utxos: &mut HashMap<OutPoint, Output>,
     wallet: &Wallet,
+    deposit_maturity: u32,
 ) -> Result<(), Error> {
-    let () = update_wallet(node, wallet)?;
+    let () = update_wallet(node, wallet, deposit_maturity)?;
     *utxos = wallet.get_utxos()?;
     *unconfirmed_utxos = wallet.get_unconfirmed_utxos()?;
     Ok(())
@@ -57,6 +69,7 @@ fn update(
 
 #[derive(Clone)]
 pub struct App {
+    pub audit_log: AuditLog,
     pub node: Arc<Node>,
     pub wallet: Wallet,
     pub miner: Arc<TokioRwLock<Miner>>,
@@ -64,19 +77,33 @@ pub struct App {
     pub unconfirmed_utxos: Arc<RwLock<HashMap<OutPoint, Output>>>,
     pub runtime: Arc<tokio::runtime::Runtime>,
     task: Arc<JoinHandle<()>>,
+    /// Background task that mines blocks automatically, if enabled
+    auto_mine_task: Arc<Mutex<Option<JoinHandle<()>>>>,
     pub local_pool: LocalPoolHandle,
+    pub network: Network,
+    /// Number of mainchain confirmations required before the wallet will
+    /// spend a deposit
+    deposit_maturity: u32,
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("audit log error")]
+    AuditLog(#[from] heed::Error),
+    #[error("auto-mining is only supported on regtest")]
+    AutoMineUnsupported,
     #[error("drivechain error")]
     Drivechain(#[from] bip300301::Error),
+    #[error("instant mining is only supported on regtest")]
+    InstantMiningUnsupported,
     #[error("io error")]
     Io(#[from] std::io::Error),
     #[error("jsonrpsee error")]
     Jsonrpsee(#[from] jsonrpsee::core::Error),
     #[error("miner error: {0}")]
     Miner(#[from] miner::Error),
+    #[error(transparent)]
+    NetworkDataPath(#[from] util::NetworkDataPathError),
     #[error("node error")]
     Node(#[from] node::Error),
     #[error(transparent)]
@@ -91,6 +118,7 @@ impl App {
         utxos: Arc<RwLock<HashMap<OutPoint, FilledOutput>>>,
         unconfirmed_utxos: Arc<RwLock<HashMap<OutPoint, Output>>>,
         wallet: Wallet,
+        deposit_maturity: u32,
     ) -> Result<(), Error> {
         let mut state_changes = node.watch_state();
         while let Some(()) = state_changes.next().await {
@@ -99,6 +127,7 @@ impl App {
                 &mut utxos.write(),
                 &mut unconfirmed_utxos.write(),
                 &wallet,
+                deposit_maturity,
             )?;
         }
         Ok(())
@@ -109,14 +138,20 @@ impl App {
         utxos: Arc<RwLock<HashMap<OutPoint, FilledOutput>>>,
         unconfirmed_utxos: Arc<RwLock<HashMap<OutPoint, Output>>>,
         wallet: Wallet,
+        deposit_maturity: u32,
     ) -> JoinHandle<()> {
         spawn(
-            Self::task(node, utxos, unconfirmed_utxos, wallet).unwrap_or_else(
-                |err| {
-                    let err = anyhow::Error::from(err);
-                    tracing::error!("{err:#}")
-                },
-            ),
+            Self::task(
+                node,
+                utxos,
+                unconfirmed_utxos,
+                wallet,
+                deposit_maturity,
+            )
+            .unwrap_or_else(|err| {
+                let err = anyhow::Error::from(err);
+                tracing::error!("{err:#}")
+            }),
         )
     }
 
@@ -126,10 +161,21 @@ impl App {
         let runtime = tokio::runtime::Builder::new_multi_thread()
             .enable_all()
             .build()?;
-        let wallet = Wallet::new(&config.datadir.join("wallet.mdb"))?;
+        let wallet_path = util::network_data_path(
+            &config.datadir,
+            config.network,
+            "wallet.mdb",
+        )?;
+        let wallet = Wallet::new(&wallet_path)?;
+        let audit_log_path = util::network_data_path(
+            &config.datadir,
+            config.network,
+            "audit_log.mdb",
+        )?;
+        let audit_log = AuditLog::new(&audit_log_path)?;
         if let Some(seed_phrase_path) = &config.mnemonic_seed_phrase_path {
             let mnemonic = std::fs::read_to_string(seed_phrase_path)?;
-            let () = wallet.set_seed_from_mnemonic(mnemonic.as_str())?;
+            let () = wallet.set_seed_from_mnemonic(mnemonic.as_str(), "")?;
         }
         let miner = Miner::new(
             THIS_SIDECHAIN,
@@ -141,12 +187,16 @@ impl App {
         let local_pool = LocalPoolHandle::new(1);
         let node = Node::new(
             config.net_addr,
+            config.peer_limits,
             &config.datadir,
             config.main_addr,
             config.network,
             &config.main_password,
             &config.main_user,
             local_pool.clone(),
+            config.index_address_activity,
+            config.mempool_limits,
+            &config.genesis,
             #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
             config.zmq_addr,
         )?;
@@ -170,9 +220,11 @@ impl App {
             utxos.clone(),
             unconfirmed_utxos.clone(),
             wallet.clone(),
+            config.deposit_maturity,
         );
         drop(rt_guard);
         Ok(Self {
+            audit_log,
             node,
             wallet,
             miner: Arc::new(TokioRwLock::new(miner)),
@@ -180,7 +232,10 @@ impl App {
             utxos,
             runtime: Arc::new(runtime),
             task: Arc::new(task),
+            auto_mine_task: Arc::new(Mutex::new(None)),
             local_pool,
+            network: config.network,
+            deposit_maturity: config.deposit_maturity,
         })
     }
 
@@ -191,12 +246,36 @@ impl App {
             &mut self.utxos.write(),
             &mut self.unconfirmed_utxos.write(),
             &self.wallet,
+            self.deposit_maturity,
+        )
+    }
+
+    /// Update (unconfirmed) utxos & wallet, without waiting for the node
+    /// state to change
+    pub fn refresh(&self) -> Result<(), Error> {
+        update(
+            &self.node,
+            &mut self.utxos.write(),
+            &mut self.unconfirmed_utxos.write(),
+            &self.wallet,
+            self.deposit_maturity,
         )
     }
 
     pub fn sign_and_send(&self, tx: Transaction) -> Result<(), Error> {
+        self.sign_and_send_with_expiry(tx, None)
+    }
+
+    /// Sign and send a tx, which is dropped from the mempool if it is not
+    /// included in a block at or below the expiry height
+    pub fn sign_and_send_with_expiry(
+        &self,
+        tx: Transaction,
+        expiry_height: Option<u32>,
+    ) -> Result<(), Error> {
         let authorized_transaction = self.wallet.authorize(tx)?;
-        self.node.submit_transaction(authorized_transaction)?;
+        self.node
+            .submit_transaction(authorized_transaction, expiry_height)?;
         let () = self.update()?;
         Ok(())
     }
@@ -225,11 +304,19 @@ impl App {
     const EMPTY_BLOCK_BMM_BRIBE: bip300301::bitcoin::Amount =
         bip300301::bitcoin::Amount::from_sat(1000);
 
+    /** Mine a sidechain block.
+     *  If `instant` is set, the mainchain block that includes the BMM request
+     *  is generated immediately, rather than waiting for it to be mined
+     *  externally. This is only supported on regtest. */
     pub async fn mine(
         &self,
         fee: Option<bip300301::bitcoin::Amount>,
+        instant: bool,
     ) -> Result<(), Error> {
         const NUM_TRANSACTIONS: usize = 1000;
+        if instant && self.network != Network::Regtest {
+            return Err(Error::InstantMiningUnsupported);
+        }
         let (txs, tx_fees) = self.node.get_transactions(NUM_TRANSACTIONS)?;
         let coinbase = match tx_fees {
             0 => vec![],
@@ -250,10 +337,12 @@ impl App {
             .drivechain
             .get_mainchain_tip()
             .await?;
+        let timestamp = self.node.next_block_timestamp(prev_main_hash)?;
         let header = types::Header {
             merkle_root: body.compute_merkle_root(),
             prev_side_hash,
             prev_main_hash,
+            timestamp,
         };
         let bribe = fee.unwrap_or_else(|| {
             if tx_fees > 0 {
@@ -266,6 +355,10 @@ impl App {
         miner_write
             .attempt_bmm(bribe.to_sat(), 0, header, body)
             .await?;
+        if instant {
+            tracing::trace!("generating mainchain block...");
+            let () = miner_write.generate().await?;
+        }
         tracing::trace!("confirming bmm...");
         if let Some((main_hash, header, body)) =
             miner_write.confirm_bmm().await?
@@ -280,6 +373,64 @@ impl App {
         Ok(())
     }
 
+    /// Interval at which the mempool is checked when auto-mining without a
+    /// fixed interval
+    const AUTO_MINE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    /** Mine blocks in the background, generating the mainchain block that
+     *  includes each BMM request immediately. This is only supported on
+     *  regtest.
+     *  If `interval` is set, a block is mined at that interval. Otherwise,
+     *  a block is mined whenever the mempool is non-empty.
+     *  Replaces any existing auto-mine task.
+     *  `interval` must be nonzero. */
+    pub fn start_auto_mine(
+        &self,
+        interval: Option<Duration>,
+    ) -> Result<(), Error> {
+        if self.network != Network::Regtest {
+            return Err(Error::AutoMineUnsupported);
+        }
+        let app = self.clone();
+        let task = self.local_pool.spawn_pinned(move || async move {
+            let mut ticks = tokio::time::interval(
+                interval.unwrap_or(Self::AUTO_MINE_POLL_INTERVAL),
+            );
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                if interval.is_none() {
+                    match app.node.mempool_info() {
+                        Ok(mempool_info) if mempool_info.num_txs == 0 => {
+                            continue;
+                        }
+                        Ok(_) => (),
+                        Err(err) => {
+                            let err = anyhow::Error::from(err);
+                            tracing::error!("auto-mine error: {err:#}");
+                            continue;
+                        }
+                    }
+                }
+                if let Err(err) = app.mine(None, true).await {
+                    let err = anyhow::Error::from(err);
+                    tracing::error!("auto-mine error: {err:#}");
+                }
+            }
+        });
+        if let Some(prev_task) = self.auto_mine_task.lock().replace(task) {
+            prev_task.abort();
+        }
+        Ok(())
+    }
+
+    /// Stop mining blocks in the background, if auto-mining is enabled
+    pub fn stop_auto_mine(&self) {
+        if let Some(task) = self.auto_mine_task.lock().take() {
+            task.abort();
+        }
+    }
+
     pub fn deposit(
         &mut self,
         amount: bitcoin::Amount,
@@ -304,10 +455,31 @@ impl App {
             Ok(())
         })
     }
+
+    /** Get the wallet's deposits that are not yet spendable, with the number
+     *  of mainchain confirmations remaining until each is spendable,
+     *  ordered by outpoint. */
+    pub fn get_maturing_deposits(&self) -> Result<Vec<(OutPoint, u32)>, Error> {
+        let addresses = self.wallet.get_addresses()?;
+        let immature_deposits = self
+            .node
+            .get_immature_deposits(&addresses, self.deposit_maturity)?;
+        let mut res: Vec<_> = immature_deposits
+            .into_iter()
+            .map(|(outpoint, confirmations)| {
+                (outpoint, self.deposit_maturity - confirmations)
+            })
+            .collect();
+        res.sort_unstable();
+        Ok(res)
+    }
 }
 
 impl Drop for App {
     fn drop(&mut self) {
-        self.task.abort()
+        // Clones share the task, so it is only aborted with the last clone
+        if Arc::strong_count(&self.task) == 1 {
+            self.task.abort()
+        }
     }
 }
@@ -0,0 +1,60 @@
+//! Append-only log of mutating RPC calls
+
+use std::path::Path;
+
+use plain_bitassets::heed::{
+    self,
+    byteorder::BigEndian,
+    types::{SerdeBincode, U64},
+};
+use plain_bitassets_app_rpc_api::RpcAuditLogEntry;
+
+#[derive(Clone)]
+pub struct AuditLog {
+    env: heed::Env,
+    /// Entries, keyed by sequence number
+    entries: heed::Database<U64<BigEndian>, SerdeBincode<RpcAuditLogEntry>>,
+}
+
+impl AuditLog {
+    pub const NUM_DBS: u32 = 1;
+
+    pub fn new(path: &Path) -> Result<Self, heed::Error> {
+        std::fs::create_dir_all(path)?;
+        let env = unsafe {
+            heed::EnvOpenOptions::new()
+                .map_size(100 * 1024 * 1024) // 100MB
+                .max_dbs(Self::NUM_DBS)
+                .open(path)?
+        };
+        let mut rwtxn = env.write_txn()?;
+        let entries = env.create_database(&mut rwtxn, Some("rpc_audit_log"))?;
+        rwtxn.commit()?;
+        Ok(Self { env, entries })
+    }
+
+    /// Append an entry to the log
+    pub fn append(&self, entry: &RpcAuditLogEntry) -> Result<(), heed::Error> {
+        let mut rwtxn = self.env.write_txn()?;
+        let seq = match self.entries.last(&rwtxn)? {
+            Some((last_seq, _)) => last_seq + 1,
+            None => 0,
+        };
+        self.entries.put(&mut rwtxn, &seq, entry)?;
+        rwtxn.commit()?;
+        Ok(())
+    }
+
+    /// Get up to `limit` of the most recent entries, most recent first
+    pub fn get_latest(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<RpcAuditLogEntry>, heed::Error> {
+        let rotxn = self.env.read_txn()?;
+        self.entries
+            .rev_iter(&rotxn)?
+            .take(limit)
+            .map(|item| item.map(|(_, entry)| entry))
+            .collect()
+    }
+}
@@ -5,8 +5,12 @@ use std::{
     sync::LazyLock,
 };
 
+use anyhow::Context as _;
 use clap::{Arg, Parser};
-use plain_bitassets::{node::THIS_SIDECHAIN, types::Network};
+use plain_bitassets::{
+    mempool::MemPoolLimits, net::PeerLimits, node::THIS_SIDECHAIN,
+    state::Genesis, types::Network,
+};
 
 const fn ipv4_socket_addr(ipv4_octets: [u8; 4], port: u16) -> SocketAddr {
     let [a, b, c, d] = ipv4_octets;
@@ -35,6 +39,9 @@ const DEFAULT_NET_ADDR: SocketAddr =
 const DEFAULT_RPC_ADDR: SocketAddr =
     ipv4_socket_addr([127, 0, 0, 1], 6000 + THIS_SIDECHAIN as u16);
 
+/// Default max size of RPC request and response bodies, in bytes (10 MiB)
+const DEFAULT_RPC_MAX_BODY_SIZE: u32 = 10 * 1024 * 1024;
+
 #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
 const DEFAULT_ZMQ_ADDR: SocketAddr =
     ipv4_socket_addr([127, 0, 0, 1], 28000 + THIS_SIDECHAIN as u16);
@@ -88,7 +95,11 @@ impl clap::Args for DatadirArg {
                 .value_parser(clap::builder::PathBufValueParser::new())
                 .long("datadir")
                 .short('d')
-                .help("Data directory for storing blockchain and wallet data");
+                .help(
+                    "Data directory for storing blockchain and wallet data. \
+                     Data for each network is stored in a separate \
+                     subdirectory.",
+                );
             match DEFAULT_DATA_DIR.deref() {
                 None => arg.required(true),
                 Some(datadir) => {
@@ -106,12 +117,30 @@ impl clap::Args for DatadirArg {
 #[derive(Clone, Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 pub(super) struct Cli {
-    /// Data directory for storing blockchain and wallet data
+    /// Data directory for storing blockchain and wallet data.
+    /// Data for each network is stored in a separate subdirectory.
     #[command(flatten)]
     datadir: DatadirArg,
+    /// Number of mainchain confirmations that a deposit must have before
+    /// the wallet will spend it. Deposits are credited as soon as they are
+    /// processed, but may be invalidated by a mainchain reorg.
+    #[arg(default_value_t = 1, long)]
+    deposit_maturity: u32,
+    /// Path to a JSON file specifying BitAssets and balances to allocate
+    /// when starting a new chain, and chain parameters such as the AMM
+    /// protocol fee and the AMM pool creators.
+    /// All nodes on a chain must use the same genesis file. If not set,
+    /// nothing is allocated at genesis, and default chain parameters are
+    /// used.
+    #[arg(long)]
+    genesis_file: Option<PathBuf>,
     /// If specified, the gui will not launch.
     #[arg(long)]
     headless: bool,
+    /// If specified, maintain an index of txs that spent from or paid to
+    /// each address. Requires additional disk space.
+    #[arg(long)]
+    index_address_activity: bool,
     /// Directory in which to store log files.
     /// Defaults to `<DATADIR>/logs/v<VERSION>`, where `<DATADIR>` is
     /// BitAssets's data directory, and `<VERSION>` is the BitAssets app version.
@@ -122,6 +151,23 @@ pub(super) struct Cli {
     /// Log level
     #[arg(default_value_t = tracing::Level::DEBUG, long)]
     log_level: tracing::Level,
+    /// Max number of connections accepted from peers.
+    /// Once reached, a peer that has never sent a new tip or tx is
+    /// disconnected to accept a new connection, if there is one.
+    /// Otherwise, the new connection is refused.
+    #[arg(default_value_t = PeerLimits::DEFAULT_MAX_INBOUND, long)]
+    max_inbound_peers: usize,
+    /// Max number of connections made to peers
+    #[arg(default_value_t = PeerLimits::DEFAULT_MAX_OUTBOUND, long)]
+    max_outbound_peers: usize,
+    /// Max total size of txs in the mempool, in bytes.
+    /// Once exceeded, txs with the lowest fee rates are evicted.
+    #[arg(default_value_t = MemPoolLimits::DEFAULT_MAX_BYTES, long)]
+    mempool_max_bytes: u64,
+    /// Max number of txs in the mempool.
+    /// Once exceeded, txs with the lowest fee rates are evicted.
+    #[arg(default_value_t = MemPoolLimits::DEFAULT_MAX_TXS, long)]
+    mempool_max_txs: u64,
     /// Socket address to connect to mainchain node RPC server
     #[arg(default_value_t = DEFAULT_MAIN_ADDR, long, short)]
     main_addr: SocketAddr,
@@ -137,6 +183,14 @@ pub(super) struct Cli {
     /// Socket address to host the RPC server
     #[arg(default_value_t = DEFAULT_RPC_ADDR, long, short)]
     rpc_addr: SocketAddr,
+    /// Max size of RPC request bodies, in bytes.
+    /// Requests larger than this are rejected.
+    #[arg(default_value_t = DEFAULT_RPC_MAX_BODY_SIZE, long)]
+    rpc_max_request_size: u32,
+    /// Max size of RPC response bodies, in bytes.
+    /// Requests with responses larger than this fail with an error.
+    #[arg(default_value_t = DEFAULT_RPC_MAX_BODY_SIZE, long)]
+    rpc_max_response_size: u32,
     /// Mainchain node RPC user
     #[arg(default_value_t = DEFAULT_MAIN_USER.to_owned(), long, short)]
     user_main: String,
@@ -152,7 +206,12 @@ pub(super) struct Cli {
 #[derive(Clone, Debug)]
 pub struct Config {
     pub datadir: PathBuf,
+    /// Number of mainchain confirmations required before the wallet will
+    /// spend a deposit
+    pub deposit_maturity: u32,
+    pub genesis: Genesis,
     pub headless: bool,
+    pub index_address_activity: bool,
     /// If None, logging to file should be disabled.
     pub log_dir: Option<PathBuf>,
     pub log_level: tracing::Level,
@@ -160,9 +219,13 @@ pub struct Config {
     pub main_password: String,
     pub mnemonic_seed_phrase_path: Option<PathBuf>,
     pub main_user: String,
+    pub mempool_limits: MemPoolLimits,
     pub net_addr: SocketAddr,
     pub network: Network,
+    pub peer_limits: PeerLimits,
     pub rpc_addr: SocketAddr,
+    pub rpc_max_request_size: u32,
+    pub rpc_max_response_size: u32,
     #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
     pub zmq_addr: SocketAddr,
 }
@@ -185,18 +248,52 @@ impl Cli {
                 }
             }
         };
+        let genesis = match self.genesis_file {
+            None => Genesis::default(),
+            Some(genesis_file) => {
+                let genesis_json = std::fs::read_to_string(&genesis_file)
+                    .with_context(|| {
+                        format!(
+                            "failed to read genesis file `{}`",
+                            genesis_file.display()
+                        )
+                    })?;
+                let genesis: Genesis = serde_json::from_str(&genesis_json)
+                    .with_context(|| {
+                        format!(
+                            "failed to parse genesis file `{}`",
+                            genesis_file.display()
+                        )
+                    })?;
+                let () = genesis.validate()?;
+                genesis
+            }
+        };
         Ok(Config {
             datadir: self.datadir.0,
+            deposit_maturity: self.deposit_maturity,
+            genesis,
             headless: self.headless,
+            index_address_activity: self.index_address_activity,
             log_dir,
             log_level: self.log_level,
             main_addr: self.main_addr,
             main_password: self.password_main,
             main_user: self.user_main,
+            mempool_limits: MemPoolLimits {
+                max_txs: self.mempool_max_txs,
+                max_bytes: self.mempool_max_bytes,
+            },
             mnemonic_seed_phrase_path: self.mnemonic_seed_phrase_path,
             net_addr: self.net_addr,
             network: self.network,
+            peer_limits: PeerLimits {
+                max_inbound: self.max_inbound_peers,
+                max_outbound: self.max_outbound_peers,
+            },
             rpc_addr: self.rpc_addr,
+            rpc_max_request_size: self.rpc_max_request_size,
+            rpc_max_response_size: self.rpc_max_response_size,
             #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
             zmq_addr: self.zmq_addr,
         })
@@ -3,18 +3,26 @@ use std::collections::{BTreeMap, HashMap};
 use eframe::egui;
 use hex::FromHex;
 use plain_bitassets::{
+    node,
     state::BitAssetSeqId,
     types::{hashes::BitAssetId, BitAssetData},
 };
 
 use crate::{
     app::App,
-    gui::util::{InnerResponseExt, UiExt},
+    gui::util::{InnerResponseExt, RefreshSignal, UiExt},
 };
 
-#[derive(Debug, Default)]
+type BitAssets = (
+    HashMap<BitAssetSeqId, BitAssetId>,
+    BTreeMap<BitAssetId, BitAssetData>,
+);
+
 pub(super) struct AllBitAssets {
     query: String,
+    refresh_signal: RefreshSignal,
+    /// None if not yet loaded
+    bitassets: Option<Result<BitAssets, node::Error>>,
 }
 
 fn show_bitasset_data(
@@ -90,25 +98,36 @@ fn show_bitasset_with_data(
 }
 
 impl AllBitAssets {
+    /// MUST be run from within a tokio runtime
+    pub fn new(app: &App) -> Self {
+        Self {
+            query: String::new(),
+            refresh_signal: RefreshSignal::new(app),
+            bitassets: None,
+        }
+    }
+
+    fn get_bitassets(app: &App) -> Result<BitAssets, node::Error> {
+        let bitassets = app.node.bitassets()?;
+        Ok(bitassets
+            .into_iter()
+            .map(|(seq_id, bitasset_id, bitasset_data)| {
+                ((seq_id, bitasset_id), (bitasset_id, bitasset_data))
+            })
+            .unzip())
+    }
+
     pub fn show(&mut self, app: &mut App, ui: &mut egui::Ui) {
+        if self.refresh_signal.poll(app, ui.ctx()) {
+            self.bitassets = Some(Self::get_bitassets(app));
+        }
         egui::CentralPanel::default().show_inside(ui, |ui| {
-            match app.node.bitassets() {
-                Err(node_err) => {
+            match &self.bitassets {
+                None => (),
+                Some(Err(node_err)) => {
                     ui.monospace_selectable_multiline(node_err.to_string());
                 }
-                Ok(bitassets) => {
-                    let (seq_id_to_bitasset_id, bitassets): (
-                        HashMap<_, _>,
-                        BTreeMap<_, _>,
-                    ) = bitassets
-                        .into_iter()
-                        .map(|(seq_id, bitasset_id, bitasset_data)| {
-                            (
-                                (seq_id, bitasset_id),
-                                (bitasset_id, bitasset_data),
-                            )
-                        })
-                        .unzip();
+                Some(Ok((seq_id_to_bitasset_id, bitassets))) => {
                     ui.horizontal(|ui| {
                         let query_edit =
                             egui::TextEdit::singleline(&mut self.query)
@@ -117,12 +136,12 @@ impl AllBitAssets {
                         ui.add(query_edit);
                     });
                     if self.query.is_empty() {
-                        bitassets.into_iter().for_each(
+                        bitassets.iter().for_each(
                             |(bitasset_id, bitasset_data)| {
                                 show_bitasset_with_data(
                                     ui,
-                                    &bitasset_id,
-                                    &bitasset_data,
+                                    bitasset_id,
+                                    bitasset_data,
                                 );
                             },
                         )
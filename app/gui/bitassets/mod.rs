@@ -22,7 +22,6 @@ enum Tab {
     DutchAuctionExplorer,
 }
 
-#[derive(Default)]
 pub struct BitAssets {
     all_bitassets: AllBitAssets,
     dutch_auction_explorer: DutchAuctionExplorer,
@@ -31,6 +30,16 @@ pub struct BitAssets {
 }
 
 impl BitAssets {
+    /// MUST be run from within a tokio runtime
+    pub fn new(app: &App) -> Self {
+        Self {
+            all_bitassets: AllBitAssets::new(app),
+            dutch_auction_explorer: DutchAuctionExplorer::default(),
+            reserve_register: ReserveRegister::default(),
+            tab: Tab::default(),
+        }
+    }
+
     pub fn show(&mut self, app: &mut App, ui: &mut egui::Ui) {
         egui::TopBottomPanel::top("bitassets_tabs").show(ui.ctx(), |ui| {
             ui.horizontal(|ui| {
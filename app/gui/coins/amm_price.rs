@@ -0,0 +1,62 @@
+use eframe::egui;
+
+use plain_bitassets::{state::AmmPair, types::AssetId};
+
+use crate::{
+    app::App,
+    gui::util::{borsh_deserialize_hex, UiExt},
+};
+
+/// Show whether an AMM pool exists for an asset pair, and the current price
+/// of the base asset, in units of the quote asset.
+/// Nothing is shown unless both assets are valid and distinct.
+pub fn show_amm_price(
+    app: &App,
+    ui: &mut egui::Ui,
+    (base, base_descriptor): (&str, &str),
+    (quote, quote_descriptor): (&str, &str),
+) {
+    let (Ok(base), Ok(quote)) = (
+        borsh_deserialize_hex::<AssetId>(base),
+        borsh_deserialize_hex::<AssetId>(quote),
+    ) else {
+        return;
+    };
+    if base == quote {
+        return;
+    }
+    let amm_pair = AmmPair::new(base, quote);
+    let amm_pool_state = match app.node.try_get_amm_pool_state(amm_pair) {
+        Ok(amm_pool_state) => amm_pool_state,
+        Err(err) => {
+            ui.monospace_selectable_multiline(format!("{err:#}"));
+            return;
+        }
+    };
+    if amm_pool_state.is_none() {
+        ui.monospace(
+            "No pool exists for this pair. \
+             Minting a position will create one.",
+        );
+        return;
+    }
+    match app.node.try_get_amm_price(base, quote) {
+        Ok(Some(price)) => {
+            ui.monospace_selectable_singleline(
+                false,
+                format!(
+                    "Price: 1 {base_descriptor} = \
+                     {price:.8} {quote_descriptor}"
+                ),
+            );
+        }
+        Ok(None) => {
+            ui.monospace(
+                "A pool exists for this pair, but it has no liquidity.",
+            );
+        }
+        Err(err) => {
+            ui.monospace_selectable_multiline(format!("{err:#}"));
+        }
+    }
+}
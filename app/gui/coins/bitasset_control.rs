@@ -0,0 +1,157 @@
+//! Operations on BitAsset control coins
+
+use bip300301::bitcoin;
+use eframe::egui;
+use hex::FromHex;
+use plain_bitassets::types::{Address, BitAssetId};
+
+use crate::{app::App, gui::shortcuts};
+
+fn show_input(
+    ui: &mut egui::Ui,
+    label: &str,
+    hint_text: &str,
+    text_buffer: &mut String,
+) {
+    ui.horizontal(|ui| {
+        let label = ui.label(label);
+        let edit = egui::TextEdit::singleline(text_buffer)
+            .hint_text(hint_text)
+            .desired_width(250.);
+        ui.add(edit).labelled_by(label.id);
+    });
+}
+
+/// Send a BitAsset control coin and BitAsset units to separate addresses
+#[derive(Debug, Default)]
+pub struct SplitControl {
+    bitasset_id: String,
+    control_dest: String,
+    units_dest: String,
+    units_amount: String,
+    fee: String,
+}
+
+impl SplitControl {
+    fn split_bitasset_control(
+        app: &App,
+        bitasset_id: BitAssetId,
+        control_dest: Address,
+        units_dest: Address,
+        units_amount: u64,
+        fee: bitcoin::Amount,
+    ) -> anyhow::Result<()> {
+        let mut tx = app.wallet.create_regular_transaction(fee.to_sat())?;
+        let () = app.wallet.split_bitasset_control(
+            &mut tx,
+            bitasset_id,
+            control_dest,
+            units_dest,
+            units_amount,
+        )?;
+        app.sign_and_send(tx)?;
+        Ok(())
+    }
+
+    pub fn show(&mut self, app: &App, ui: &mut egui::Ui) {
+        ui.heading("Split Control Coin");
+        show_input(ui, "BitAsset", "BitAsset ID (hex)", &mut self.bitasset_id);
+        show_input(
+            ui,
+            "Control coin to",
+            "destination address",
+            &mut self.control_dest,
+        );
+        show_input(ui, "Units to", "destination address", &mut self.units_dest);
+        show_input(ui, "Units", "amount", &mut self.units_amount);
+        show_input(ui, "Fee (BTC)", "fee", &mut self.fee);
+        let bitasset_id = BitAssetId::from_hex(&self.bitasset_id).ok();
+        let control_dest: Option<Address> = self.control_dest.parse().ok();
+        let units_dest: Option<Address> = self.units_dest.parse().ok();
+        let units_amount: Option<u64> = self.units_amount.parse().ok();
+        let fee = bitcoin::Amount::from_str_in(
+            &self.fee,
+            bitcoin::Denomination::Bitcoin,
+        )
+        .ok();
+        let enabled = bitasset_id.is_some()
+            && control_dest.is_some()
+            && units_dest.is_some()
+            && units_amount.is_some()
+            && fee.is_some();
+        if ui
+            .add_enabled(enabled, egui::Button::new("split"))
+            .clicked()
+            || shortcuts::submit_pressed(ui, enabled)
+        {
+            if let Err(err) = Self::split_bitasset_control(
+                app,
+                bitasset_id.expect("should not happen"),
+                control_dest.expect("should not happen"),
+                units_dest.expect("should not happen"),
+                units_amount.expect("should not happen"),
+                fee.expect("should not happen"),
+            ) {
+                tracing::error!("{err:#}");
+            } else {
+                *self = Self::default();
+            }
+        }
+    }
+}
+
+/// Send a BitAsset control coin to another address, transferring authority
+/// to mint and update the BitAsset
+#[derive(Debug, Default)]
+pub struct TransferControl {
+    bitasset_id: String,
+    dest: String,
+    fee: String,
+}
+
+impl TransferControl {
+    fn transfer_bitasset_control(
+        app: &App,
+        bitasset_id: BitAssetId,
+        dest: Address,
+        fee: bitcoin::Amount,
+    ) -> anyhow::Result<()> {
+        let mut tx = app.wallet.create_regular_transaction(fee.to_sat())?;
+        let () =
+            app.wallet
+                .transfer_bitasset_control(&mut tx, bitasset_id, dest)?;
+        app.sign_and_send(tx)?;
+        Ok(())
+    }
+
+    pub fn show(&mut self, app: &App, ui: &mut egui::Ui) {
+        ui.heading("Transfer Control Coin");
+        show_input(ui, "BitAsset", "BitAsset ID (hex)", &mut self.bitasset_id);
+        show_input(ui, "To", "destination address", &mut self.dest);
+        show_input(ui, "Fee (BTC)", "fee", &mut self.fee);
+        let bitasset_id = BitAssetId::from_hex(&self.bitasset_id).ok();
+        let dest: Option<Address> = self.dest.parse().ok();
+        let fee = bitcoin::Amount::from_str_in(
+            &self.fee,
+            bitcoin::Denomination::Bitcoin,
+        )
+        .ok();
+        let enabled = bitasset_id.is_some() && dest.is_some() && fee.is_some();
+        if ui
+            .add_enabled(enabled, egui::Button::new("transfer"))
+            .clicked()
+            || shortcuts::submit_pressed(ui, enabled)
+        {
+            if let Err(err) = Self::transfer_bitasset_control(
+                app,
+                bitasset_id.expect("should not happen"),
+                dest.expect("should not happen"),
+                fee.expect("should not happen"),
+            ) {
+                tracing::error!("{err:#}");
+            } else {
+                *self = Self::default();
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::{HashMap, HashSet};
+
+use eframe::egui;
+use plain_bitassets::{
+    bip300301::bitcoin,
+    types::{AssetId, BlockHash, FilledOutput, OutPoint, Transaction},
+};
+
+use crate::{app::App, gui::util::UiExt};
+
+/// Manual coin control. UTXOs can be selected as inputs for the
+/// transaction in the transaction builder, and reserved so that they are
+/// never selected automatically.
+#[derive(Debug, Default)]
+pub struct CoinControl {
+    /// Cached confirmation counts, valid for the tip at which they were
+    /// computed
+    confirmations: HashMap<OutPoint, Option<u32>>,
+    confirmations_tip: Option<BlockHash>,
+}
+
+impl CoinControl {
+    /// Compute the number of confirmations for a UTXO, if known.
+    /// Confirmations are only known for outputs of regular transactions.
+    fn compute_confirmations(
+        app: &App,
+        tip: BlockHash,
+        tip_height: u32,
+        outpoint: &OutPoint,
+    ) -> anyhow::Result<Option<u32>> {
+        let OutPoint::Regular { txid, vout: _ } = outpoint else {
+            return Ok(None);
+        };
+        for block_hash in app.node.get_tx_inclusions(*txid)?.into_keys() {
+            if app.node.is_descendant(block_hash, tip)? {
+                let height = app.node.get_height(block_hash)?;
+                return Ok(Some(tip_height - height));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get the number of confirmations for a UTXO, if known
+    fn confirmations(
+        &mut self,
+        app: &App,
+        outpoint: &OutPoint,
+    ) -> anyhow::Result<Option<u32>> {
+        let tip = app.node.get_tip()?;
+        if self.confirmations_tip != Some(tip) {
+            self.confirmations.clear();
+            self.confirmations_tip = Some(tip);
+        }
+        if let Some(confirmations) = self.confirmations.get(outpoint) {
+            return Ok(*confirmations);
+        }
+        let tip_height = app.node.get_tip_height()?;
+        let confirmations =
+            Self::compute_confirmations(app, tip, tip_height, outpoint)?;
+        self.confirmations.insert(*outpoint, confirmations);
+        Ok(confirmations)
+    }
+
+    fn show_asset_value(ui: &mut egui::Ui, output: &FilledOutput) {
+        match output.asset_value() {
+            None => {
+                ui.monospace_selectable_singleline(false, "-");
+                ui.monospace_selectable_singleline(false, "-");
+            }
+            Some((AssetId::Bitcoin, value)) => {
+                ui.monospace_selectable_singleline(false, "Bitcoin");
+                ui.monospace_selectable_singleline(
+                    false,
+                    format!("₿{}", bitcoin::Amount::from_sat(value)),
+                );
+            }
+            Some((AssetId::BitAsset(bitasset_id), value)) => {
+                ui.monospace_selectable_singleline(
+                    true,
+                    format!("BitAsset {}", hex::encode(bitasset_id.0)),
+                );
+                ui.monospace_selectable_singleline(false, format!("{value}"));
+            }
+            Some((AssetId::BitAssetControl(bitasset_id), value)) => {
+                ui.monospace_selectable_singleline(
+                    true,
+                    format!("BitAsset Control {}", hex::encode(bitasset_id.0)),
+                );
+                ui.monospace_selectable_singleline(false, format!("{value}"));
+            }
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        app: &mut App,
+        ui: &mut egui::Ui,
+        tx: &mut Transaction,
+    ) -> anyhow::Result<()> {
+        ui.heading("Coin Control");
+        ui.label(
+            "Select UTXOs to spend in the transaction builder. \
+             Locked UTXOs are reserved, and are never selected \
+             automatically when creating transactions.",
+        );
+        let reserved_utxos = app.wallet.get_reserved_utxos()?;
+        let mut utxos: Vec<_> = app
+            .utxos
+            .read()
+            .iter()
+            .map(|(outpoint, output)| (*outpoint, output.clone()))
+            .collect();
+        utxos.sort_by_key(|(outpoint, _)| format!("{outpoint}"));
+        let selected: HashSet<_> = tx.inputs.iter().copied().collect();
+        ui.separator();
+        ui.monospace(format!(
+            "Selected: {} / {} ({} locked)",
+            selected.len(),
+            utxos.len(),
+            reserved_utxos.len()
+        ));
+        ui.separator();
+        let mut toggle_selected = None;
+        let mut toggle_reserved = None;
+        egui::ScrollArea::vertical()
+            .show(ui, |ui| {
+                egui::Grid::new("coin_control")
+                    .striped(true)
+                    .num_columns(6)
+                    .show(ui, |ui| -> anyhow::Result<()> {
+                        ui.monospace_selectable_singleline(false, "Spend");
+                        ui.monospace_selectable_singleline(false, "Outpoint");
+                        ui.monospace_selectable_singleline(false, "Asset");
+                        ui.monospace_selectable_singleline(false, "Amount");
+                        ui.monospace_selectable_singleline(
+                            false,
+                            "Confirmations",
+                        );
+                        ui.monospace_selectable_singleline(false, "Lock");
+                        ui.end_row();
+                        for (outpoint, output) in &utxos {
+                            let reserved = reserved_utxos.contains(outpoint);
+                            let mut is_selected = selected.contains(outpoint);
+                            if ui
+                                .add_enabled(
+                                    !reserved,
+                                    egui::Checkbox::without_text(
+                                        &mut is_selected,
+                                    ),
+                                )
+                                .changed()
+                            {
+                                toggle_selected = Some(*outpoint);
+                            }
+                            ui.monospace_selectable_singleline(
+                                true,
+                                format!("{outpoint}"),
+                            );
+                            Self::show_asset_value(ui, output);
+                            let confirmations =
+                                match self.confirmations(app, outpoint)? {
+                                    Some(confirmations) => {
+                                        format!("{confirmations}")
+                                    }
+                                    None => "-".to_owned(),
+                                };
+                            ui.monospace_selectable_singleline(
+                                false,
+                                confirmations,
+                            );
+                            let lock_button =
+                                if reserved { "unlock" } else { "lock" };
+                            if ui.button(lock_button).clicked() {
+                                toggle_reserved = Some((*outpoint, reserved));
+                            }
+                            ui.end_row();
+                        }
+                        Ok(())
+                    })
+                    .inner
+            })
+            .inner?;
+        if let Some(outpoint) = toggle_selected {
+            if selected.contains(&outpoint) {
+                tx.inputs.retain(|input| *input != outpoint);
+            } else {
+                tx.inputs.push(outpoint);
+            }
+        }
+        match toggle_reserved {
+            Some((outpoint, true)) => {
+                app.wallet.unreserve_utxo(&outpoint)?;
+            }
+            Some((outpoint, false)) => {
+                // Reserved UTXOs may not be selected
+                tx.inputs.retain(|input| *input != outpoint);
+                app.wallet.reserve_utxo(&outpoint)?;
+            }
+            None => (),
+        }
+        Ok(())
+    }
+}
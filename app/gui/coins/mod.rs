@@ -3,6 +3,9 @@ use strum::{EnumIter, IntoEnumIterator};
 
 use crate::app::App;
 
+mod amm_price;
+mod bitasset_control;
+mod coin_control;
 mod my_bitassets;
 mod transfer_receive;
 mod tx_builder;
@@ -10,6 +13,7 @@ pub(super) mod tx_creator;
 mod utxo_creator;
 mod utxo_selector;
 
+use coin_control::CoinControl;
 use my_bitassets::MyBitAssets;
 use transfer_receive::TransferReceive;
 use tx_builder::TxBuilder;
@@ -21,11 +25,14 @@ enum Tab {
     TransferReceive,
     #[strum(to_string = "Transaction Builder")]
     TransactionBuilder,
+    #[strum(to_string = "Coin Control")]
+    CoinControl,
     #[strum(to_string = "My BitAssets")]
     MyBitAssets,
 }
 
 pub struct Coins {
+    coin_control: CoinControl,
     my_bitassets: MyBitAssets,
     tab: Tab,
     transfer_receive: TransferReceive,
@@ -35,7 +42,8 @@ pub struct Coins {
 impl Coins {
     pub fn new(app: &App) -> Self {
         Self {
-            my_bitassets: MyBitAssets,
+            coin_control: CoinControl::default(),
+            my_bitassets: MyBitAssets::default(),
             tab: Tab::default(),
             transfer_receive: TransferReceive::new(app),
             tx_builder: TxBuilder::default(),
@@ -62,6 +70,12 @@ impl Coins {
             Tab::TransactionBuilder => {
                 let () = self.tx_builder.show(app, ui).unwrap();
             }
+            Tab::CoinControl => {
+                let () = self
+                    .coin_control
+                    .show(app, ui, &mut self.tx_builder.base_tx)
+                    .unwrap();
+            }
             Tab::MyBitAssets => {
                 self.my_bitassets.show(app, ui);
             }
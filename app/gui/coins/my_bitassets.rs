@@ -3,10 +3,14 @@ use itertools::{Either, Itertools};
 
 use plain_bitassets::types::FilledOutput;
 
+use super::bitasset_control::{SplitControl, TransferControl};
 use crate::{app::App, gui::util::UiExt};
 
 #[derive(Debug, Default)]
-pub struct MyBitAssets;
+pub struct MyBitAssets {
+    split_control: SplitControl,
+    transfer_control: TransferControl,
+}
 
 impl MyBitAssets {
     pub fn show_reservations(&mut self, app: &mut App, ui: &mut egui::Ui) {
@@ -160,5 +164,10 @@ impl MyBitAssets {
     pub fn show(&mut self, app: &mut App, ui: &mut egui::Ui) {
         let _reservations_response = self.show_reservations(app, ui);
         let _bitassets_response = self.show_bitassets(app, ui);
+        egui::CentralPanel::default().show_inside(ui, |ui| {
+            self.split_control.show(app, ui);
+            ui.separator();
+            self.transfer_control.show(app, ui);
+        });
     }
 }
@@ -2,7 +2,10 @@ use bip300301::bitcoin;
 use eframe::egui;
 use plain_bitassets::types::Address;
 
-use crate::{app::App, gui::util::UiExt};
+use crate::{
+    app::App,
+    gui::{shortcuts, util::UiExt},
+};
 
 #[derive(Debug, Default)]
 struct Transfer {
@@ -31,29 +34,32 @@ impl Transfer {
     fn show(&mut self, app: &App, ui: &mut egui::Ui) {
         ui.add_sized((250., 10.), |ui: &mut egui::Ui| {
             ui.horizontal(|ui| {
+                let dest_label = ui.label("To");
                 let dest_edit = egui::TextEdit::singleline(&mut self.dest)
                     .hint_text("destination address")
                     .desired_width(150.);
-                ui.add(dest_edit);
+                ui.add(dest_edit).labelled_by(dest_label.id);
             })
             .response
         });
         ui.add_sized((110., 10.), |ui: &mut egui::Ui| {
             ui.horizontal(|ui| {
+                let amount_label = ui.label("Amount");
                 let amount_edit = egui::TextEdit::singleline(&mut self.amount)
                     .hint_text("amount")
                     .desired_width(80.);
-                ui.add(amount_edit);
+                ui.add(amount_edit).labelled_by(amount_label.id);
                 ui.label("BTC");
             })
             .response
         });
         ui.add_sized((110., 10.), |ui: &mut egui::Ui| {
             ui.horizontal(|ui| {
+                let fee_label = ui.label("Fee");
                 let fee_edit = egui::TextEdit::singleline(&mut self.fee)
                     .hint_text("fee")
                     .desired_width(80.);
-                ui.add(fee_edit);
+                ui.add(fee_edit).labelled_by(fee_label.id);
                 ui.label("BTC");
             })
             .response
@@ -67,12 +73,11 @@ impl Transfer {
             &self.fee,
             bitcoin::Denomination::Bitcoin,
         );
+        let enabled = dest.is_some() && amount.is_ok() && fee.is_ok();
         if ui
-            .add_enabled(
-                dest.is_some() && amount.is_ok() && fee.is_ok(),
-                egui::Button::new("transfer"),
-            )
+            .add_enabled(enabled, egui::Button::new("transfer"))
             .clicked()
+            || shortcuts::submit_pressed(ui, enabled)
         {
             if let Err(err) = create_transfer(
                 app,
@@ -20,7 +20,7 @@ use crate::{app::App, gui::util::UiExt};
 #[derive(Debug, Default)]
 pub struct TxBuilder {
     // regular tx without extra data or special inputs/outputs
-    base_tx: Transaction,
+    pub(super) base_tx: Transaction,
     tx_creator: TxCreator,
     utxo_creator: UtxoCreator,
     utxo_selector: UtxoSelector,
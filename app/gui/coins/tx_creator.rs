@@ -18,9 +18,13 @@ use plain_bitassets::{
     },
 };
 
+use super::amm_price::show_amm_price;
 use crate::{
     app::App,
-    gui::util::{borsh_deserialize_hex, InnerResponseExt},
+    gui::{
+        shortcuts,
+        util::{borsh_deserialize_hex, InnerResponseExt},
+    },
 };
 
 // struct representing the outcome of trying to set an Option<T> from a String
@@ -275,8 +279,14 @@ impl TxCreator {
                 .node
                 .get_amm_pool_state(amm_pair)
                 .map_err(anyhow::Error::new)?;
+            // Pool reserves are in the canonical order for the AMM pair
+            let (pool_amount0, pool_amount1) = if asset0 <= asset1 {
+                (amount0, amount1)
+            } else {
+                (amount1, amount0)
+            };
             let next_amm_pool_state = amm_pool_state
-                .mint(amount0, amount1)
+                .mint(pool_amount0, pool_amount1)
                 .map_err(anyhow::Error::new)?;
             next_amm_pool_state.outstanding_lp_tokens
                 - amm_pool_state.outstanding_lp_tokens
@@ -313,12 +323,15 @@ impl TxCreator {
             u64::from_str(&dex_swap.amount_receive).map_err(|err| {
                 anyhow::anyhow!("Failed to parse receive amount: {err}")
             })?;
+        let amm_protocol_fee = app.node.amm_protocol_fee();
         let () = app.wallet.amm_swap(
             &mut tx,
             asset_spend,
             asset_receive,
             amount_spend,
             amount_receive,
+            amm_protocol_fee.fee(amount_spend),
+            amm_protocol_fee.address,
         )?;
         Ok(tx)
     }
@@ -775,6 +788,28 @@ impl TxCreator {
             }
         };
         let tx_data_changed = tx_data_ui.is_some_and(|resp| resp.changed());
+        match &self.tx_type {
+            TxType::DexBurn(DexBurn { asset0, asset1, .. })
+            | TxType::DexMint(DexMint { asset0, asset1, .. }) => {
+                show_amm_price(
+                    app,
+                    ui,
+                    (asset0, "Asset 0"),
+                    (asset1, "Asset 1"),
+                )
+            }
+            TxType::DexSwap(DexSwap {
+                asset_spend,
+                asset_receive,
+                ..
+            }) => show_amm_price(
+                app,
+                ui,
+                (asset_spend, "Spend Asset"),
+                (asset_receive, "Receive Asset"),
+            ),
+            _ => (),
+        }
         // if base txid has changed, store the new txid
         let base_txid = base_tx.txid();
         let base_txid_changed = base_txid != self.base_txid;
@@ -805,7 +840,9 @@ impl TxCreator {
             let fee = self.bitcoin_value_in - self.bitcoin_value_out;
             let fee = bitcoin::Amount::from_sat(fee);
             ui.monospace(format!("fee:  {fee}"));
-            if ui.button("sign and send").clicked() {
+            if ui.button("sign and send").clicked()
+                || shortcuts::submit_pressed(ui, true)
+            {
                 let () = app.sign_and_send(final_tx.clone())?;
                 *base_tx = Transaction::default();
                 self.final_tx = None;
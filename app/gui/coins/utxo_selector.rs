@@ -95,12 +95,18 @@ impl UtxoSelector {
         asset_id: AssetId,
     ) {
         let selected: HashSet<_> = tx.inputs.iter().cloned().collect();
+        // Reserved UTXOs can only be spent via coin control
+        let reserved = app
+            .wallet
+            .get_reserved_utxos()
+            .expect("failed to retrieve reserved UTXOs");
         let mut unconfirmed_utxos: Vec<(OutPoint, BitcoinOutput)> = {
             app.unconfirmed_utxos
                 .read()
                 .iter()
                 .filter_map(|(outpoint, output)| {
                     if !selected.contains(outpoint)
+                        && !reserved.contains(outpoint)
                         && asset_id == AssetId::Bitcoin
                     {
                         let output =
@@ -119,6 +125,7 @@ impl UtxoSelector {
                 .iter()
                 .filter_map(|(outpoint, output)| {
                     if !selected.contains(outpoint)
+                        && !reserved.contains(outpoint)
                         && output.asset_value().is_some_and(
                             |(output_asset_id, _)| output_asset_id == asset_id,
                         )
@@ -14,6 +14,7 @@ use eframe::egui::{
 
 use crate::{
     app::App,
+    gui::debug_report::debug_report,
     line_buffer::{LineBuffer, LineBufferWriter},
 };
 
@@ -90,6 +91,19 @@ impl ConsoleLogs {
     }
 
     pub fn show(&mut self, app: &App, ui: &mut egui::Ui) {
+        TopBottomPanel::top("debug_report").show_inside(ui, |ui| {
+            if ui
+                .button("Copy debug report")
+                .on_hover_text(
+                    "Copy version, network, and recent error information \
+                     to the clipboard, for including in bug reports",
+                )
+                .clicked()
+            {
+                let report = debug_report(app, &self.line_buffer);
+                ui.output_mut(|po| po.copied_text = report);
+            }
+        });
         ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
             let line_buffer_read = self.line_buffer.as_str();
             let mut logs: &str = &line_buffer_read;
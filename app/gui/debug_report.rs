@@ -0,0 +1,99 @@
+//! Debug reports, for attaching to bug reports
+
+use std::fmt::Write as _;
+
+use plain_bitassets_app_rpc_api::RpcAuditLogEntry;
+
+use crate::{app::App, line_buffer::LineBuffer};
+
+/// Max number of recent error log lines to include
+const RECENT_ERRORS_LIMIT: usize = 20;
+
+/// Max number of audit log entries to search for a failed RPC request
+const AUDIT_LOG_SEARCH_LIMIT: usize = 100;
+
+const REDACTED: &str = "<redacted>";
+
+fn recent_errors(logs: &LineBuffer) -> Vec<String> {
+    let logs = logs.as_str();
+    let mut errors: Vec<_> = logs
+        .lines()
+        .rev()
+        .filter(|line| line.contains(" ERROR "))
+        .take(RECENT_ERRORS_LIMIT)
+        .map(str::to_owned)
+        .collect();
+    errors.reverse();
+    errors
+}
+
+fn last_failed_rpc_request(
+    app: &App,
+) -> anyhow::Result<Option<RpcAuditLogEntry>> {
+    let entries = app.audit_log.get_latest(AUDIT_LOG_SEARCH_LIMIT)?;
+    Ok(entries.into_iter().find(|entry| entry.error.is_some()))
+}
+
+/// Write a field of the report. Errors encountered while collecting
+/// information for the field are included in the report, rather than
+/// failing the whole report.
+fn write_field<T, F>(report: &mut String, name: &str, f: F)
+where
+    T: std::fmt::Display,
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    let _ = match f() {
+        Ok(value) => writeln!(report, "{name}: {value}"),
+        Err(err) => writeln!(report, "{name}: error: {err:#}"),
+    };
+}
+
+/// Assemble a plaintext debug report.
+/// The wallet seed and mnemonic are never included. If the mnemonic is
+/// available, any occurrence of it (eg. in logs) is redacted.
+pub fn debug_report(app: &App, logs: &LineBuffer) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "# Plain BitAssets debug report");
+    write_field(&mut report, "Version", || Ok(env!("CARGO_PKG_VERSION")));
+    write_field(&mut report, "Network", || Ok(app.network.data_dir_name()));
+    write_field(&mut report, "Tip height", || {
+        Ok(app.node.get_tip_height()?)
+    });
+    write_field(&mut report, "Wallet addresses", || {
+        Ok(app.wallet.get_num_addresses()?)
+    });
+    let _ = writeln!(report, "\n## Last failed RPC request");
+    match last_failed_rpc_request(app) {
+        Ok(Some(entry)) => {
+            let _ = writeln!(report, "Timestamp: {}", entry.timestamp);
+            let _ = writeln!(report, "Method: {}", entry.method);
+            let _ = writeln!(report, "Params digest: {}", entry.params_digest);
+            let _ = writeln!(
+                report,
+                "Error: {}",
+                entry.error.as_deref().unwrap_or_default()
+            );
+        }
+        Ok(None) => {
+            let _ = writeln!(report, "None");
+        }
+        Err(err) => {
+            let _ = writeln!(report, "error: {err:#}");
+        }
+    }
+    let _ = writeln!(report, "\n## Recent errors");
+    let recent_errors = recent_errors(logs);
+    if recent_errors.is_empty() {
+        let _ = writeln!(report, "None");
+    }
+    for line in recent_errors {
+        let _ = writeln!(report, "{line}");
+    }
+    match app.wallet.get_mnemonic() {
+        Ok(mnemonic) if !mnemonic.trim().is_empty() => {
+            report = report.replace(mnemonic.trim(), REDACTED);
+        }
+        Ok(_) | Err(_) => (),
+    }
+    report
+}
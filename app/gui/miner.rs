@@ -40,7 +40,7 @@ impl Miner {
                 let running = self.running.clone();
                 || async move {
                     tracing::debug!("Mining...");
-                    let mining_result = app.mine(None).await;
+                    let mining_result = app.mine(None, false).await;
                     running.store(false, atomic::Ordering::SeqCst);
                     if let Err(err) = mining_result {
                         tracing::error!("{:#}", anyhow::Error::new(err))
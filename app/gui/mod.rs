@@ -1,4 +1,4 @@
-use std::{net::SocketAddr, task::Poll};
+use std::{net::SocketAddr, path::PathBuf, task::Poll};
 
 use eframe::egui::{self, Color32, RichText};
 use plain_bitassets::{util::Watchable, wallet::Wallet};
@@ -10,11 +10,14 @@ mod activity;
 mod bitassets;
 mod coins;
 mod console_logs;
+mod debug_report;
 mod encrypt_message;
 mod fonts;
 mod miner;
 mod parent_chain;
+mod preferences;
 mod seed;
+mod shortcuts;
 mod util;
 
 use activity::Activity;
@@ -25,8 +28,12 @@ use encrypt_message::EncryptMessage;
 use fonts::FONT_DEFINITIONS;
 use miner::Miner;
 use parent_chain::ParentChain;
+use preferences::PreferencesPanel;
 use seed::SetSeed;
-use util::{show_btc_amount_from_sats, UiExt, BITCOIN_LOGO_FA, BITCOIN_ORANGE};
+use util::{
+    show_btc_amount_from_sats, RefreshSignal, UiExt, BITCOIN_LOGO_FA,
+    BITCOIN_ORANGE,
+};
 
 struct BottomPanel {
     wallet_updated: PromiseStream<<Wallet as Watchable<()>>::WatchStream>,
@@ -135,8 +142,13 @@ pub struct EguiApp {
     encrypt_message: EncryptMessage,
     miner: Miner,
     parent_chain: ParentChain,
+    preferences: PreferencesPanel,
     set_seed: SetSeed,
+    show_shortcuts_help: bool,
     tab: Tab,
+    /// Fallback for refreshing wallet data, which is otherwise refreshed
+    /// when the node state changes
+    wallet_refresh: RefreshSignal,
 }
 
 #[derive(Default, EnumIter, Eq, PartialEq, strum::Display)]
@@ -154,12 +166,15 @@ enum Tab {
     Activity,
     #[strum(to_string = "Console / Logs")]
     ConsoleLogs,
+    #[strum(to_string = "Preferences")]
+    Preferences,
 }
 
 impl EguiApp {
     pub fn new(
         app: App,
         cc: &eframe::CreationContext<'_>,
+        datadir: PathBuf,
         logs_capture: LineBuffer,
         rpc_addr: SocketAddr,
     ) -> Self {
@@ -184,8 +199,11 @@ impl EguiApp {
 
         cc.egui_ctx.set_style(style);
 
+        let preferences = PreferencesPanel::new(datadir);
+        let () = preferences.preferences().store_in_ctx(&cc.egui_ctx);
         let activity = Activity::new(&app);
         let rt_guard = app.runtime.enter();
+        let bitassets = BitAssets::new(&app);
         let bottom_panel = BottomPanel::new(&app.wallet);
         drop(rt_guard);
         let coins = Coins::new(&app);
@@ -194,32 +212,77 @@ impl EguiApp {
         Self {
             activity,
             app,
-            bitassets: BitAssets::default(),
+            bitassets,
             bottom_panel,
             coins,
             console_logs,
             encrypt_message: EncryptMessage::new(),
             miner: Miner::default(),
             parent_chain,
+            preferences,
             set_seed: SetSeed::default(),
+            show_shortcuts_help: false,
             tab: Tab::default(),
+            wallet_refresh: RefreshSignal::interval_only(),
         }
     }
+
+    /// Handle top-level keyboard shortcuts
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if shortcuts::toggle_help_pressed(ctx) {
+            self.show_shortcuts_help = !self.show_shortcuts_help;
+        }
+        ctx.input_mut(|input| {
+            for (tab_idx, tab_variant) in Tab::iter().enumerate() {
+                if let Some(shortcut) = shortcuts::switch_tab(tab_idx)
+                    && input.consume_shortcut(&shortcut)
+                {
+                    self.tab = tab_variant;
+                }
+            }
+        });
+        if ctx.input_mut(|input| input.consume_shortcut(&shortcuts::REFRESH))
+            || self.wallet_refresh.poll(&self.app, ctx)
+        {
+            if let Err(err) = self.app.refresh() {
+                let err = anyhow::Error::from(err);
+                tracing::error!("Failed to refresh wallet: {err:#}");
+            }
+            self.bottom_panel.update(&self.app);
+        }
+        let tab_names: Vec<_> =
+            Tab::iter().map(|tab| tab.to_string()).collect();
+        shortcuts::show_help(
+            ctx,
+            &mut self.show_shortcuts_help,
+            tab_names.iter().map(String::as_str),
+        );
+    }
 }
 
 impl eframe::App for EguiApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         if self.app.wallet.has_seed().unwrap_or(false) {
+            self.handle_shortcuts(ctx);
             egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
                 ui.horizontal(|ui| {
-                    Tab::iter().for_each(|tab_variant| {
-                        let tab_name = tab_variant.to_string();
-                        ui.selectable_value(
-                            &mut self.tab,
-                            tab_variant,
-                            tab_name,
-                        );
-                    })
+                    Tab::iter().enumerate().for_each(
+                        |(tab_idx, tab_variant)| {
+                            let tab_name = tab_variant.to_string();
+                            let resp = ui.selectable_value(
+                                &mut self.tab,
+                                tab_variant,
+                                tab_name,
+                            );
+                            if let Some(shortcut) =
+                                shortcuts::switch_tab(tab_idx)
+                            {
+                                resp.on_hover_text(
+                                    ctx.format_shortcut(&shortcut),
+                                );
+                            }
+                        },
+                    )
                 });
             });
             egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
@@ -244,6 +307,9 @@ impl eframe::App for EguiApp {
                 Tab::ConsoleLogs => {
                     self.console_logs.show(&self.app, ui);
                 }
+                Tab::Preferences => {
+                    self.preferences.show(ui);
+                }
             });
         } else {
             egui::CentralPanel::default().show(ctx, |_ui| {
@@ -0,0 +1,109 @@
+use bip300301::{bitcoin, DepositInfo};
+use eframe::egui;
+
+use crate::{app::App, gui::util::UiExt};
+
+struct Inner {
+    /// Deposits to wallet addresses, most recent first, paired with the
+    /// sidechain height at which each was applied, if applied
+    deposits: Vec<(DepositInfo, Option<u32>)>,
+    sidechain_height: u32,
+}
+
+/// Recent deposits to wallet addresses, and whether they have been credited
+pub(super) struct Deposits(anyhow::Result<Inner>);
+
+impl Deposits {
+    /// Number of recent deposit blocks to show deposits from
+    const DEPOSIT_BLOCKS_LIMIT: usize = 10;
+
+    fn get_deposits(app: &App) -> anyhow::Result<Inner> {
+        let addresses = app.wallet.get_addresses()?;
+        let main_tip = app
+            .runtime
+            .block_on(app.node.drivechain().get_mainchain_tip())
+            .inspect_err(|err| {
+                tracing::warn!("Failed to get mainchain tip: {err:#}")
+            })
+            .ok();
+        let deposits = app.node.get_deposits_to_addresses(
+            &addresses,
+            main_tip,
+            Self::DEPOSIT_BLOCKS_LIMIT,
+        )?;
+        let sidechain_height = app.node.get_tip_height()?;
+        Ok(Inner {
+            deposits,
+            sidechain_height,
+        })
+    }
+
+    pub fn new(app: &App) -> Self {
+        let inner = Self::get_deposits(app)
+            .inspect_err(|err| tracing::error!("{err:#}"));
+        Self(inner)
+    }
+
+    fn refresh_deposits(&mut self, app: &App) {
+        self.0 = Self::get_deposits(app)
+            .inspect_err(|err| tracing::error!("{err:#}"));
+    }
+
+    pub fn show(&mut self, app: &mut App, ui: &mut egui::Ui) {
+        if ui.button("Refresh").clicked() {
+            let () = self.refresh_deposits(app);
+        }
+        let inner = match self.0.as_ref() {
+            Ok(inner) => inner,
+            Err(err) => {
+                ui.monospace_selectable_multiline(format!("{err:#}"));
+                return;
+            }
+        };
+        if inner.deposits.is_empty() {
+            ui.monospace("No recent deposits to wallet addresses");
+            return;
+        }
+        egui::Grid::new("deposits")
+            .striped(true)
+            .num_columns(4)
+            .show(ui, |ui| {
+                ui.monospace_selectable_singleline(false, "Outpoint");
+                ui.monospace_selectable_singleline(false, "Address");
+                ui.monospace_selectable_singleline(false, "Value");
+                ui.monospace_selectable_singleline(false, "Status");
+                ui.end_row();
+                for (deposit, applied_height) in &inner.deposits {
+                    ui.monospace_selectable_singleline(
+                        true,
+                        format!("{}", deposit.outpoint),
+                    );
+                    ui.monospace_selectable_singleline(
+                        true,
+                        deposit.output.address.clone(),
+                    );
+                    ui.monospace_selectable_singleline(
+                        false,
+                        format!(
+                            "₿{}",
+                            bitcoin::Amount::from_sat(deposit.output.value)
+                        ),
+                    );
+                    let status = match applied_height {
+                        Some(applied_height) => {
+                            let confirmations = inner
+                                .sidechain_height
+                                .saturating_sub(*applied_height);
+                            format!(
+                                "credited at height {applied_height} \
+                                 ({confirmations} confirmations)"
+                            )
+                        }
+                        None => "pending (awaiting sidechain block)".to_owned(),
+                    };
+                    ui.monospace_selectable_singleline(false, status);
+                    ui.end_row();
+                }
+            });
+    }
+}
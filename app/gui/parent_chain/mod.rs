@@ -3,9 +3,11 @@ use strum::{EnumIter, IntoEnumIterator};
 
 use crate::app::App;
 
+mod deposits;
 mod info;
 mod transfer;
 
+use deposits::Deposits;
 use info::Info;
 use transfer::Transfer;
 
@@ -14,11 +16,14 @@ enum Tab {
     #[default]
     #[strum(to_string = "Transfer")]
     Transfer,
+    #[strum(to_string = "Deposits")]
+    Deposits,
     #[strum(to_string = "Info")]
     Info,
 }
 
 pub struct ParentChain {
+    deposits: Deposits,
     info: Info,
     tab: Tab,
     transfer: Transfer,
@@ -26,8 +31,10 @@ pub struct ParentChain {
 
 impl ParentChain {
     pub fn new(app: &App) -> Self {
+        let deposits = Deposits::new(app);
         let info = Info::new(app);
         Self {
+            deposits,
             info,
             tab: Tab::default(),
             transfer: Transfer::default(),
@@ -47,6 +54,9 @@ impl ParentChain {
             Tab::Transfer => {
                 self.transfer.show(app, ui);
             }
+            Tab::Deposits => {
+                self.deposits.show(app, ui);
+            }
             Tab::Info => {
                 self.info.show(app, ui);
             }
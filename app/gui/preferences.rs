@@ -0,0 +1,141 @@
+//! GUI preferences, persisted as JSON in the data directory
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::gui::util::UiExt;
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Interval at which data is refreshed, if it has not already been
+    /// refreshed due to a change in the node state
+    pub refresh_interval_secs: u64,
+}
+
+impl Preferences {
+    const FILE_NAME: &'static str = "gui_preferences.json";
+    const MIN_REFRESH_INTERVAL_SECS: u64 = 1;
+    const MAX_REFRESH_INTERVAL_SECS: u64 = 3600;
+
+    fn path(datadir: &Path) -> PathBuf {
+        datadir.join(Self::FILE_NAME)
+    }
+
+    /// Load preferences from the data directory, or use the defaults if
+    /// they do not exist or cannot be read
+    pub fn load(datadir: &Path) -> Self {
+        let path = Self::path(datadir);
+        if !path.exists() {
+            return Self::default();
+        }
+        let res = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                serde_json::from_str(&json).map_err(anyhow::Error::from)
+            });
+        match res {
+            Ok(preferences) => preferences,
+            Err(err) => {
+                tracing::error!(
+                    "Failed to load GUI preferences from {}: {err:#}",
+                    path.display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Save preferences to the data directory
+    pub fn save(&self, datadir: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(Self::path(datadir), json)?;
+        Ok(())
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        Duration::from_secs(self.refresh_interval_secs)
+    }
+
+    fn refresh_interval_id() -> egui::Id {
+        egui::Id::new("refresh_interval")
+    }
+
+    /// Make the preferences available to all widgets via the egui context
+    pub fn store_in_ctx(&self, ctx: &egui::Context) {
+        ctx.data_mut(|data| {
+            data.insert_temp(
+                Self::refresh_interval_id(),
+                self.refresh_interval(),
+            )
+        })
+    }
+
+    /// Get the refresh interval stored in the egui context
+    pub fn refresh_interval_from_ctx(ctx: &egui::Context) -> Duration {
+        ctx.data(|data| data.get_temp(Self::refresh_interval_id()))
+            .unwrap_or_else(|| Self::default().refresh_interval())
+    }
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            refresh_interval_secs: 10,
+        }
+    }
+}
+
+/// Preferences panel
+pub struct PreferencesPanel {
+    datadir: PathBuf,
+    preferences: Preferences,
+}
+
+impl PreferencesPanel {
+    pub fn new(datadir: PathBuf) -> Self {
+        let preferences = Preferences::load(&datadir);
+        Self {
+            datadir,
+            preferences,
+        }
+    }
+
+    pub fn preferences(&self) -> &Preferences {
+        &self.preferences
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Preferences");
+        let resp = ui
+            .horizontal(|ui| {
+                let label = ui.label("Refresh interval (seconds)");
+                ui.add(
+                    egui::DragValue::new(
+                        &mut self.preferences.refresh_interval_secs,
+                    )
+                    .clamp_range(
+                        Preferences::MIN_REFRESH_INTERVAL_SECS
+                            ..=Preferences::MAX_REFRESH_INTERVAL_SECS,
+                    ),
+                )
+                .labelled_by(label.id)
+            })
+            .inner;
+        ui.monospace_selectable_multiline(
+            "Data is refreshed whenever the node state changes, \
+             and at this interval otherwise.",
+        );
+        if resp.changed() {
+            let () = self.preferences.store_in_ctx(ui.ctx());
+            if let Err(err) = self.preferences.save(&self.datadir) {
+                tracing::error!("Failed to save GUI preferences: {err:#}");
+            }
+        }
+    }
+}
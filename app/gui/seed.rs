@@ -1,4 +1,4 @@
-use crate::app::App;
+use crate::{app::App, gui::shortcuts};
 use eframe::egui;
 
 pub struct SetSeed {
@@ -18,10 +18,11 @@ impl Default for SetSeed {
 impl SetSeed {
     pub fn show(&mut self, app: &App, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
+            let seed_label = ui.label("Seed");
             let seed_edit = egui::TextEdit::singleline(&mut self.seed)
                 .hint_text("seed")
                 .clip_text(false);
-            ui.add(seed_edit);
+            ui.add(seed_edit).labelled_by(seed_label.id);
             if ui.button("generate").clicked() {
                 let mnemonic = bip39::Mnemonic::new(
                     bip39::MnemonicType::Words12,
@@ -30,19 +31,27 @@ impl SetSeed {
                 self.seed = mnemonic.phrase().into();
             }
         });
-        let passphrase_edit = egui::TextEdit::singleline(&mut self.passphrase)
-            .hint_text("passphrase")
-            .password(true)
-            .clip_text(false);
-        ui.add(passphrase_edit);
+        ui.horizontal(|ui| {
+            let passphrase_label = ui.label("Passphrase");
+            let passphrase_edit =
+                egui::TextEdit::singleline(&mut self.passphrase)
+                    .hint_text("passphrase")
+                    .password(true)
+                    .clip_text(false);
+            ui.add(passphrase_edit).labelled_by(passphrase_label.id);
+        });
         let mnemonic =
             bip39::Mnemonic::from_phrase(&self.seed, bip39::Language::English);
         if ui
             .add_enabled(mnemonic.is_ok(), egui::Button::new("set"))
             .clicked()
+            || shortcuts::submit_pressed(ui, mnemonic.is_ok())
         {
             app.wallet
-                .set_seed_from_mnemonic(self.seed.as_str())
+                .set_seed_from_mnemonic(
+                    self.seed.as_str(),
+                    self.passphrase.as_str(),
+                )
                 .expect("failed to set HD wallet seed");
         }
     }
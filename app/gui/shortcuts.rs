@@ -0,0 +1,102 @@
+//! Keyboard shortcuts
+
+use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
+
+use crate::gui::util::UiExt;
+
+/// Refresh wallet data and balance
+pub const REFRESH: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::NONE, Key::F5);
+
+/// Submit the visible form, eg. sign and send a transaction
+pub const SUBMIT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND, Key::Enter);
+
+/// Keys used to switch to each of the top-level tabs, in order
+pub const TAB_KEYS: [Key; 9] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+
+/// Shortcut used to switch to the top-level tab at the specified index
+pub fn switch_tab(tab_idx: usize) -> Option<KeyboardShortcut> {
+    TAB_KEYS
+        .get(tab_idx)
+        .map(|key| KeyboardShortcut::new(Modifiers::COMMAND, *key))
+}
+
+/// Returns `true` if the help overlay should be toggled.
+/// `?` is ignored while a text input has focus.
+pub fn toggle_help_pressed(ctx: &egui::Context) -> bool {
+    !ctx.wants_keyboard_input()
+        && ctx.input(|input| {
+            input.events.iter().any(
+                |event| matches!(event, egui::Event::Text(text) if text == "?"),
+            )
+        })
+}
+
+/// Returns `true` if the submit shortcut was pressed, consuming it.
+/// `enabled` should be the same as the enabled state of the submit button,
+/// so that the shortcut is not consumed if it would have no effect.
+pub fn submit_pressed(ui: &egui::Ui, enabled: bool) -> bool {
+    enabled && ui.input_mut(|input| input.consume_shortcut(&SUBMIT))
+}
+
+/// Help overlay listing the available shortcuts
+pub fn show_help<'a, TabNames>(
+    ctx: &egui::Context,
+    open: &mut bool,
+    tab_names: TabNames,
+) where
+    TabNames: IntoIterator<Item = &'a str>,
+{
+    egui::Window::new("Keyboard Shortcuts")
+        .open(open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("keyboard_shortcuts")
+                .striped(true)
+                .num_columns(2)
+                .show(ui, |ui| {
+                    for (tab_idx, tab_name) in tab_names.into_iter().enumerate()
+                    {
+                        let Some(shortcut) = switch_tab(tab_idx) else {
+                            break;
+                        };
+                        ui.monospace(ctx.format_shortcut(&shortcut));
+                        ui.monospace_selectable_singleline(
+                            false,
+                            format!("Switch to {tab_name}"),
+                        );
+                        ui.end_row();
+                    }
+                    ui.monospace(ctx.format_shortcut(&SUBMIT));
+                    ui.monospace_selectable_singleline(
+                        false,
+                        "Submit form / sign and send transaction",
+                    );
+                    ui.end_row();
+                    ui.monospace(ctx.format_shortcut(&REFRESH));
+                    ui.monospace_selectable_singleline(
+                        false,
+                        "Refresh wallet data",
+                    );
+                    ui.end_row();
+                    ui.monospace("?");
+                    ui.monospace_selectable_singleline(
+                        false,
+                        "Show / hide this help",
+                    );
+                    ui.end_row();
+                });
+        });
+}
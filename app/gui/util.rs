@@ -1,10 +1,13 @@
-use std::borrow::Borrow;
+use std::{borrow::Borrow, task::Poll, time::Instant};
 
 use bip300301::bitcoin;
 use borsh::BorshDeserialize;
 use eframe::egui::{self, Color32, InnerResponse, Response, Ui};
+use futures::{stream::BoxStream, StreamExt};
 use libes::{auth::HmacSha256, enc::Aes256Gcm, key::X25519};
 
+use crate::{app::App, gui::preferences::Preferences, util::PromiseStream};
+
 /// ecies crypto scheme over x25519
 pub type Ecies = libes::Ecies<X25519, Aes256Gcm, HmacSha256>;
 
@@ -32,6 +35,60 @@ pub fn show_btc_amount_from_sats(sats: u64) -> String {
     show_btc_amount(bitcoin::Amount::from_sat(sats))
 }
 
+/// Signals when data should be refreshed: whenever the node state changes,
+/// or if the refresh interval has elapsed since the last refresh
+pub struct RefreshSignal {
+    /// None if only the refresh interval should be used
+    state_changed: Option<PromiseStream<BoxStream<'static, ()>>>,
+    last_refresh: Option<Instant>,
+}
+
+impl RefreshSignal {
+    /// MUST be run from within a tokio runtime
+    pub fn new(app: &App) -> Self {
+        let state_changed = PromiseStream::from(app.node.watch_state().boxed());
+        Self {
+            state_changed: Some(state_changed),
+            last_refresh: None,
+        }
+    }
+
+    /// Signal only when the refresh interval has elapsed, for data that is
+    /// already updated when the node state changes
+    pub fn interval_only() -> Self {
+        Self {
+            state_changed: None,
+            last_refresh: None,
+        }
+    }
+
+    /// Returns `true` if data should be refreshed.
+    /// Also schedules a repaint, so that data is refreshed at the refresh
+    /// interval even if there is no user input.
+    pub fn poll(&mut self, app: &App, ctx: &egui::Context) -> bool {
+        let state_changed = match &mut self.state_changed {
+            Some(state_changed) => {
+                let _rt_guard = app.runtime.enter();
+                matches!(state_changed.poll_next(), Some(Poll::Ready(())))
+            }
+            None => false,
+        };
+        let refresh_interval = Preferences::refresh_interval_from_ctx(ctx);
+        let now = Instant::now();
+        let interval_elapsed = match self.last_refresh {
+            Some(last_refresh) => now - last_refresh >= refresh_interval,
+            None => true,
+        };
+        ctx.request_repaint_after(refresh_interval);
+        if state_changed || interval_elapsed {
+            self.last_refresh = Some(now);
+            true
+        } else {
+            false
+        }
+    }
+}
+
 // extension for InnerResponse<Response> and InnerResponse<Option<Response>>
 pub trait InnerResponseExt {
     fn join(self) -> Response;
@@ -9,6 +9,7 @@ use tracing_subscriber::{
 };
 
 mod app;
+mod audit_log;
 mod cli;
 mod gui;
 mod line_buffer;
@@ -97,7 +98,19 @@ fn main() -> anyhow::Result<()> {
     // spawn rpc server
     app.runtime.spawn({
         let app = app.clone();
-        async move { rpc_server::run_server(app, config.rpc_addr).await.unwrap() }
+        let rpc_addr = config.rpc_addr;
+        let max_request_size = config.rpc_max_request_size;
+        let max_response_size = config.rpc_max_response_size;
+        async move {
+            rpc_server::run_server(
+                app,
+                rpc_addr,
+                max_request_size,
+                max_response_size,
+            )
+            .await
+            .unwrap()
+        }
     });
 
     if config.headless {
@@ -119,6 +132,7 @@ fn main() -> anyhow::Result<()> {
                 Box::new(gui::EguiApp::new(
                     app,
                     cc,
+                    config.datadir,
                     line_buffer,
                     config.rpc_addr,
                 ))
@@ -1,139 +1,260 @@
-use std::{cmp::Ordering, net::SocketAddr};
+use std::{
+    borrow::Cow,
+    collections::{hash_map, BTreeMap, HashMap},
+    net::SocketAddr,
+    pin::pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use bip300301::bitcoin;
 use fraction::Fraction;
+use futures::StreamExt;
 use jsonrpsee::{
-    core::{async_trait, RpcResult},
+    core::{async_trait, RpcResult, SubscriptionResult},
     server::Server,
     types::ErrorObject,
+    PendingSubscriptionSink, SubscriptionMessage,
 };
 
+use parking_lot::Mutex;
 use plain_bitassets::{
+    mempool::MemPoolInfo,
     node,
-    state::{self, AmmPair, AmmPoolState, BitAssetSeqId, DutchAuctionState},
+    state::{
+        self, AmmPair, AmmPoolState, AtomicSwapState, BitAssetSeqId,
+        DutchAuctionState, OrderState, UtxoSort, WithdrawalEstimate,
+    },
     types::{
-        Address, AssetId, BitAssetData, BitAssetId, Block, BlockHash,
-        DutchAuctionId, DutchAuctionParams, FilledOutputContent, PointedOutput,
-        Transaction, Txid,
+        Address, AssetId, AtomicSwapId, AtomicSwapParams,
+        AuthorizedTransaction, BitAssetData, BitAssetId, BitAssetKey, Block,
+        BlockHash, DutchAuctionId, DutchAuctionParams, FilledOutput,
+        FilledOutputContent, Hash, Header, InPoint, OrderId, OrderParams,
+        OutPoint, PointedOutput, ReservationPrep, Transaction, TxStatus, Txid,
+        WithdrawalBundleInfo, WithdrawalBundleRecord,
     },
     wallet,
 };
-use plain_bitassets_app_rpc_api::{RpcServer, TxInfo};
+use plain_bitassets_app_rpc_api::{
+    compression::CompressionLayer, AmmSwapEstimate, AssetBalances, Balance,
+    BitAssetRegistration, DepositBlock, DiagnosticCheck, DiagnosticStatus,
+    Diagnostics, ErrorCode, ErrorData, LpTokenBalance, NodeInfo, Peer,
+    RpcAuditLogEntry, RpcServer, RpcSubscriptionsServer, TransferOutput,
+    TxInfo,
+};
+use serde::Serialize;
+use tower::ServiceBuilder;
 
 use crate::app::{self, App};
 
+#[derive(Clone)]
 pub struct RpcServerImpl {
     app: App,
+    /// Time at which the mnemonic was last revealed
+    last_mnemonic_reveal: Arc<Mutex<Option<Instant>>>,
+}
+
+fn structured_err(
+    err_msg: impl Into<String>,
+    data: ErrorData,
+) -> ErrorObject<'static> {
+    ErrorObject::owned(data.kind.code(), err_msg.into(), Some(data))
 }
 
 fn custom_err(err_msg: impl Into<String>) -> ErrorObject<'static> {
-    ErrorObject::owned(-1, err_msg.into(), Option::<()>::None)
+    structured_err(err_msg, ErrorData::new(ErrorCode::Other))
 }
 
 fn convert_app_err(err: app::Error) -> ErrorObject<'static> {
-    let err = anyhow::anyhow!(err);
-    tracing::error!("{err:#}");
-    custom_err(err.to_string())
+    match err {
+        app::Error::Node(err) => convert_node_err(err),
+        app::Error::Wallet(err) => convert_wallet_err(err),
+        err => {
+            let err = anyhow::anyhow!(err);
+            tracing::error!("{err:#}");
+            custom_err(err.to_string())
+        }
+    }
 }
 
 fn convert_node_err(err: node::Error) -> ErrorObject<'static> {
+    let data = ErrorData::from(&err);
     let err = anyhow::anyhow!(err);
     tracing::error!("{err:#}");
-    custom_err(err.to_string())
+    let err_msg = match data.kind {
+        // Include the full error chain, which describes the offending
+        // input or output
+        ErrorCode::TxRejected => format!("{err:#}"),
+        _ => err.to_string(),
+    };
+    structured_err(err_msg, data)
 }
 
 fn convert_wallet_err(err: wallet::Error) -> ErrorObject<'static> {
+    let data = ErrorData::new(ErrorCode::from(&err));
     let err = anyhow::anyhow!(err);
-    tracing::error!("{err:#}");
-    custom_err(err.to_string())
+    if !matches!(data.kind, ErrorCode::NoWalletSeed | ErrorCode::WalletLocked) {
+        tracing::error!("{err:#}");
+    }
+    structured_err(err.to_string(), data)
 }
 
-#[async_trait]
-impl RpcServer for RpcServerImpl {
-    async fn amm_burn(
+fn decode_hash(hex: &str) -> RpcResult<Hash> {
+    <Hash as hex::FromHex>::from_hex(hex)
+        .map_err(|err| custom_err(err.to_string()))
+}
+
+impl RpcServerImpl {
+    /// Minimum interval between calls to `reveal_mnemonic`
+    const MNEMONIC_REVEAL_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Max time that `create_token` waits for a reservation to be confirmed
+    const CREATE_TOKEN_RESERVATION_TIMEOUT: Duration =
+        Duration::from_secs(60 * 60);
+
+    /// Default time that `wait_for_block` waits for the block count to
+    /// increase
+    const WAIT_FOR_BLOCK_DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Max time that `wait_for_block` waits for the block count to increase
+    const WAIT_FOR_BLOCK_MAX_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Max number of txids that can be requested by `get_transactions_batch`
+    const MAX_TRANSACTIONS_BATCH_SIZE: usize = 1000;
+
+    /// Max number of headers returned by `get_headers`
+    const GET_HEADERS_MAX_COUNT: u32 = 2000;
+
+    /// Max number of blocks below the tip that `get_balance_at_height` will
+    /// unwind
+    const GET_BALANCE_AT_HEIGHT_MAX_DEPTH: u32 = 10_000;
+
+    fn new(app: App) -> Self {
+        Self {
+            app,
+            last_mnemonic_reveal: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Currently connected peers, ordered by address
+    fn peers(&self) -> Vec<Peer> {
+        self.app
+            .node
+            .list_peers()
+            .into_iter()
+            .map(|(address, direction)| Peer {
+                address,
+                direction,
+                score: self.app.node.peer_score(address),
+            })
+            .collect()
+    }
+
+    /// Record a mutating RPC call in the audit log, returning the result
+    /// unchanged. Failure to write to the audit log is logged, but does not
+    /// fail the call, since the call's effects have already been applied.
+    fn audit<T>(
         &self,
-        asset0: AssetId,
-        asset1: AssetId,
-        lp_token_amount: u64,
-    ) -> RpcResult<Txid> {
-        let amm_pair = AmmPair::new(asset0, asset1);
-        let amm_pool_state = self.get_amm_pool_state(asset0, asset1).await?;
-        let next_amm_pool_state = amm_pool_state
-            .burn(lp_token_amount)
-            .map_err(|err| convert_node_err(err.into()))?;
-        let amount0 = amm_pool_state.reserve0 - next_amm_pool_state.reserve0;
-        let amount1 = amm_pool_state.reserve1 - next_amm_pool_state.reserve1;
-        let mut tx = Transaction::default();
-        let () = self
-            .app
-            .wallet
-            .amm_burn(
-                &mut tx,
-                amm_pair.asset0(),
-                amm_pair.asset1(),
-                amount0,
-                amount1,
-                lp_token_amount,
-            )
-            .map_err(convert_wallet_err)?;
-        let txid = tx.txid();
-        let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
-        Ok(txid)
+        method: &str,
+        params: serde_json::Value,
+        res: RpcResult<T>,
+    ) -> RpcResult<T>
+    where
+        T: Serialize,
+    {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let params_digest =
+            hex::encode(blake3::hash(params.to_string().as_bytes()).as_bytes());
+        let (result, error) = match &res {
+            Ok(value) => (serde_json::to_string(value).ok(), None),
+            Err(err) => (None, Some(err.message().to_owned())),
+        };
+        let entry = RpcAuditLogEntry {
+            timestamp,
+            method: method.to_owned(),
+            params_digest,
+            result,
+            error,
+        };
+        if let Err(err) = self.app.audit_log.append(&entry) {
+            let err = anyhow::Error::from(err);
+            tracing::error!("failed to write to RPC audit log: {err:#}");
+        }
+        res
     }
 
-    async fn amm_mint(
+    /// Returns a [`wallet::Error::NoWalletSeed`] error if the wallet does not
+    /// have a seed
+    fn require_wallet_seed(&self) -> RpcResult<()> {
+        if self.app.wallet.has_seed().map_err(convert_wallet_err)? {
+            Ok(())
+        } else {
+            Err(convert_wallet_err(wallet::Error::NoWalletSeed))
+        }
+    }
+
+    /// Returns the current state of the pool for an AMM swap
+    fn amm_swap_pool_state(
         &self,
-        asset0: AssetId,
-        asset1: AssetId,
-        amount0: u64,
-        amount1: u64,
-    ) -> RpcResult<Txid> {
-        let amm_pool_state = self.get_amm_pool_state(asset0, asset1).await?;
-        let next_amm_pool_state = amm_pool_state
-            .mint(amount0, amount1)
-            .map_err(|err| convert_node_err(err.into()))?;
-        let lp_token_mint = next_amm_pool_state.outstanding_lp_tokens
-            - amm_pool_state.outstanding_lp_tokens;
-        let mut tx = Transaction::default();
-        let () = self
+        asset_spend: AssetId,
+        asset_receive: AssetId,
+    ) -> RpcResult<AmmPoolState> {
+        if asset_spend == asset_receive {
+            let err = node::Error::State(state::Error::InvalidAmmSwap);
+            return Err(convert_node_err(err));
+        }
+        let amm_pair = AmmPair::new(asset_spend, asset_receive);
+        let amm_pool_state = self
             .app
-            .wallet
-            .amm_mint(&mut tx, asset0, asset1, amount0, amount1, lp_token_mint)
-            .map_err(convert_wallet_err)?;
-        let txid = tx.txid();
-        let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
-        Ok(txid)
+            .node
+            .get_amm_pool_state(amm_pair)
+            .map_err(convert_node_err)?;
+        if amm_pool_state.reserve0 == 0 || amm_pool_state.reserve1 == 0 {
+            return Err(custom_err("AMM pool reserves must be nonzero"));
+        }
+        Ok(amm_pool_state)
     }
 
-    async fn amm_swap(
+    /// Returns the current state of the pool for an AMM swap, and the amount
+    /// of `asset_receive` that would be received by the swap
+    fn amm_swap_amount_receive(
         &self,
         asset_spend: AssetId,
         asset_receive: AssetId,
         amount_spend: u64,
-    ) -> RpcResult<u64> {
-        let pair = match asset_spend.cmp(&asset_receive) {
-            Ordering::Less => (asset_spend, asset_receive),
-            Ordering::Equal => {
-                let err = node::Error::State(state::Error::InvalidAmmSwap);
-                return Err(convert_node_err(err));
-            }
-            Ordering::Greater => (asset_receive, asset_spend),
-        };
-        let amm_pool_state = self.get_amm_pool_state(pair.0, pair.1).await?;
+    ) -> RpcResult<(AmmPoolState, u64)> {
+        let amm_pool_state =
+            self.amm_swap_pool_state(asset_spend, asset_receive)?;
         let amount_receive = (if asset_spend < asset_receive {
             amm_pool_state.swap_asset0_for_asset1(amount_spend).map(
                 |new_amm_pool_state| {
-                    new_amm_pool_state.reserve1 - amm_pool_state.reserve1
+                    amm_pool_state.reserve1 - new_amm_pool_state.reserve1
                 },
             )
         } else {
             amm_pool_state.swap_asset1_for_asset0(amount_spend).map(
                 |new_amm_pool_state| {
-                    new_amm_pool_state.reserve0 - amm_pool_state.reserve0
+                    amm_pool_state.reserve0 - new_amm_pool_state.reserve0
                 },
             )
         })
         .map_err(|err| convert_node_err(err.into()))?;
+        Ok((amm_pool_state, amount_receive))
+    }
+
+    /// Build, sign, and submit an AMM swap tx, paying the protocol fee
+    fn submit_amm_swap(
+        &self,
+        asset_spend: AssetId,
+        asset_receive: AssetId,
+        amount_spend: u64,
+        amount_receive: u64,
+        expiry_height: Option<u32>,
+    ) -> RpcResult<()> {
+        let amm_protocol_fee = self.app.node.amm_protocol_fee();
         let mut tx = Transaction::default();
         let () = self
             .app
@@ -144,15 +265,402 @@ impl RpcServer for RpcServerImpl {
                 asset_receive,
                 amount_spend,
                 amount_receive,
+                amm_protocol_fee.fee(amount_spend),
+                amm_protocol_fee.address,
             )
             .map_err(convert_wallet_err)?;
         let authorized_tx =
             self.app.wallet.authorize(tx).map_err(convert_wallet_err)?;
         self.app
             .node
-            .submit_transaction(authorized_tx)
+            .submit_transaction(authorized_tx, expiry_height)
             .map_err(convert_node_err)?;
-        Ok(amount_receive)
+        Ok(())
+    }
+
+    /// Try to register a BitAsset, returning `None` if the wallet does not
+    /// have a confirmed reservation for it
+    fn try_register_bitasset(
+        &self,
+        plain_name: &str,
+        bitasset_data: &BitAssetData,
+        initial_supply: u64,
+    ) -> RpcResult<Option<Txid>> {
+        let mut tx = Transaction::default();
+        match self.app.wallet.register_bitasset(
+            &mut tx,
+            plain_name,
+            Cow::Borrowed(bitasset_data),
+            initial_supply,
+        ) {
+            Ok(()) => (),
+            Err(wallet::Error::NoBitassetReservation { .. }) => {
+                return Ok(None)
+            }
+            Err(err) => return Err(convert_wallet_err(err)),
+        }
+        let txid = tx.txid();
+        let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+        Ok(Some(txid))
+    }
+
+    /// Claim (if the preimage is provided) or refund an atomic swap
+    fn release_atomic_swap(
+        &self,
+        swap_id: AtomicSwapId,
+        preimage: Option<Hash>,
+    ) -> RpcResult<Txid> {
+        let height =
+            self.app.node.get_tip_height().map_err(convert_node_err)?;
+        let swap_state = self
+            .app
+            .node
+            .get_atomic_swap_state(swap_id)
+            .map_err(convert_node_err)?;
+        let _: AtomicSwapState = swap_state
+            .release(Txid::default(), preimage, height)
+            .map_err(|err| convert_node_err(state::Error::from(err).into()))?;
+        let mut tx = Transaction::default();
+        let () = self
+            .app
+            .wallet
+            .atomic_swap_release(
+                &mut tx,
+                swap_id,
+                preimage,
+                swap_state.asset,
+                swap_state.amount,
+                swap_state.release_address(preimage.as_ref()),
+            )
+            .map_err(convert_wallet_err)?;
+        let txid = tx.txid();
+        let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+        Ok(txid)
+    }
+}
+
+#[async_trait]
+impl RpcServer for RpcServerImpl {
+    async fn amm_burn(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        lp_token_amount: u64,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "asset0": asset0,
+            "asset1": asset1,
+            "lp_token_amount": lp_token_amount,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let amm_pair = AmmPair::new(asset0, asset1);
+            let amm_pool_state =
+                self.get_amm_pool_state(asset0, asset1).await?;
+            let next_amm_pool_state = amm_pool_state
+                .burn(lp_token_amount)
+                .map_err(|err| convert_node_err(err.into()))?;
+            let amount0 =
+                amm_pool_state.reserve0 - next_amm_pool_state.reserve0;
+            let amount1 =
+                amm_pool_state.reserve1 - next_amm_pool_state.reserve1;
+            let mut tx = Transaction::default();
+            let () = self
+                .app
+                .wallet
+                .amm_burn(
+                    &mut tx,
+                    amm_pair.asset0(),
+                    amm_pair.asset1(),
+                    amount0,
+                    amount1,
+                    lp_token_amount,
+                )
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("amm_burn", params, res)
+    }
+
+    async fn amm_mint(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        amount0: u64,
+        amount1: u64,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "asset0": asset0,
+            "asset1": asset1,
+            "amount0": amount0,
+            "amount1": amount1,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let amm_pool_state =
+                self.get_amm_pool_state(asset0, asset1).await?;
+            // Pool reserves are in the canonical order for the AMM pair
+            let (pool_amount0, pool_amount1) = if asset0 <= asset1 {
+                (amount0, amount1)
+            } else {
+                (amount1, amount0)
+            };
+            let next_amm_pool_state = amm_pool_state
+                .mint(pool_amount0, pool_amount1)
+                .map_err(|err| convert_node_err(err.into()))?;
+            let lp_token_mint = next_amm_pool_state.outstanding_lp_tokens
+                - amm_pool_state.outstanding_lp_tokens;
+            let mut tx = Transaction::default();
+            let () = self
+                .app
+                .wallet
+                .amm_mint(
+                    &mut tx,
+                    asset0,
+                    asset1,
+                    amount0,
+                    amount1,
+                    lp_token_mint,
+                )
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("amm_mint", params, res)
+    }
+
+    async fn amm_pools(
+        &self,
+    ) -> RpcResult<Vec<(AssetId, AssetId, AmmPoolState)>> {
+        self.app.node.amm_pools().map_err(convert_node_err)
+    }
+
+    async fn amm_swap(
+        &self,
+        asset_spend: AssetId,
+        asset_receive: AssetId,
+        amount_spend: u64,
+        min_amount_receive: Option<u64>,
+        expiry_height: Option<u32>,
+    ) -> RpcResult<u64> {
+        let params = serde_json::json!({
+            "asset_spend": asset_spend,
+            "asset_receive": asset_receive,
+            "amount_spend": amount_spend,
+            "min_amount_receive": min_amount_receive,
+            "expiry_height": expiry_height,
+        });
+        let res: RpcResult<u64> = async {
+            let () = self.require_wallet_seed()?;
+            let (_, amount_receive) = self.amm_swap_amount_receive(
+                asset_spend,
+                asset_receive,
+                amount_spend,
+            )?;
+            if let Some(min_amount_receive) = min_amount_receive
+                && amount_receive < min_amount_receive
+            {
+                let err = state::Error::AmmSwapBelowMinReceive {
+                    amount_receive,
+                    min_amount_receive,
+                };
+                return Err(convert_node_err(err.into()));
+            }
+            let () = self.submit_amm_swap(
+                asset_spend,
+                asset_receive,
+                amount_spend,
+                amount_receive,
+                expiry_height,
+            )?;
+            Ok(amount_receive)
+        }
+        .await;
+        self.audit("amm_swap", params, res)
+    }
+
+    async fn amm_swap_estimate(
+        &self,
+        asset_spend: AssetId,
+        asset_receive: AssetId,
+        amount_spend: u64,
+    ) -> RpcResult<AmmSwapEstimate> {
+        let (amm_pool_state, amount_receive) = self.amm_swap_amount_receive(
+            asset_spend,
+            asset_receive,
+            amount_spend,
+        )?;
+        let (reserve_spend, reserve_receive) = if asset_spend < asset_receive {
+            (amm_pool_state.reserve0, amm_pool_state.reserve1)
+        } else {
+            (amm_pool_state.reserve1, amm_pool_state.reserve0)
+        };
+        // Price impact is the relative difference between the current pool
+        // price and the effective price of the swap
+        let price_impact = if amount_spend == 0 {
+            Fraction::from(0)
+        } else {
+            let pool_price = reserve_receive as f64 / reserve_spend as f64;
+            let effective_price = amount_receive as f64 / amount_spend as f64;
+            Fraction::from(1. - (effective_price / pool_price))
+        };
+        Ok(AmmSwapEstimate {
+            amount_receive,
+            price_impact,
+        })
+    }
+
+    async fn amm_swap_exact_output(
+        &self,
+        asset_spend: AssetId,
+        asset_receive: AssetId,
+        amount_receive: u64,
+    ) -> RpcResult<u64> {
+        let params = serde_json::json!({
+            "asset_spend": asset_spend,
+            "asset_receive": asset_receive,
+            "amount_receive": amount_receive,
+        });
+        let res: RpcResult<u64> = async {
+            let () = self.require_wallet_seed()?;
+            let amm_pool_state =
+                self.amm_swap_pool_state(asset_spend, asset_receive)?;
+            let amount_spend = if asset_spend < asset_receive {
+                amm_pool_state.required_spend_asset0_for_asset1(amount_receive)
+            } else {
+                amm_pool_state.required_spend_asset1_for_asset0(amount_receive)
+            }
+            .map_err(|err| convert_node_err(err.into()))?;
+            /* The tx must commit to the exact amount received by the swap,
+             * which can exceed `amount_receive` due to rounding */
+            let (_, swap_amount_receive) = self.amm_swap_amount_receive(
+                asset_spend,
+                asset_receive,
+                amount_spend,
+            )?;
+            let () = self.submit_amm_swap(
+                asset_spend,
+                asset_receive,
+                amount_spend,
+                swap_amount_receive,
+                None,
+            )?;
+            Ok(amount_spend)
+        }
+        .await;
+        self.audit("amm_swap_exact_output", params, res)
+    }
+
+    async fn asset_balances(&self) -> RpcResult<AssetBalances> {
+        let () = self.require_wallet_seed()?;
+        let confirmed_utxos =
+            self.app.wallet.get_utxos().map_err(convert_wallet_err)?;
+        let unconfirmed_utxos = self
+            .app
+            .wallet
+            .get_unconfirmed_utxos()
+            .map_err(convert_wallet_err)?;
+        // Fill unconfirmed outputs using the mempool txs that created them.
+        // Outputs of txs that cannot be filled are not counted.
+        let mut filled_unconfirmed_txs =
+            HashMap::<Txid, Option<Vec<FilledOutput>>>::new();
+        let mut filled_unconfirmed_utxos = Vec::new();
+        for outpoint in unconfirmed_utxos.into_keys() {
+            let OutPoint::Regular { txid, vout } = outpoint else {
+                continue;
+            };
+            let filled_outputs = match filled_unconfirmed_txs.entry(txid) {
+                hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                hash_map::Entry::Vacant(entry) => {
+                    let filled_outputs = self
+                        .app
+                        .node
+                        .try_get_filled_transaction(txid)
+                        .map_err(convert_node_err)?
+                        .and_then(|(filled_tx, _)| {
+                            filled_tx.transaction.filled_outputs()
+                        });
+                    entry.insert(filled_outputs)
+                }
+            };
+            if let Some(output) = filled_outputs
+                .as_ref()
+                .and_then(|outputs| outputs.get(vout as usize))
+            {
+                filled_unconfirmed_utxos.push(output.clone());
+            }
+        }
+        let mut assets = BTreeMap::<AssetId, Balance>::new();
+        let mut lp_tokens = BTreeMap::<(AssetId, AssetId), Balance>::new();
+        let mut dutch_auction_receipts =
+            BTreeMap::<DutchAuctionId, Balance>::new();
+        let utxos =
+            confirmed_utxos.values().map(|output| (true, output)).chain(
+                filled_unconfirmed_utxos
+                    .iter()
+                    .map(|output| (false, output)),
+            );
+        for (confirmed, output) in utxos {
+            let (balance, amount) =
+                if let Some((asset, value)) = output.asset_value() {
+                    (assets.entry(asset).or_default(), value)
+                } else if let Some((asset0, asset1, amount)) =
+                    output.lp_token_amount()
+                {
+                    (lp_tokens.entry((asset0, asset1)).or_default(), amount)
+                } else if let Some(auction_id) =
+                    output.content.dutch_auction_receipt()
+                {
+                    (dutch_auction_receipts.entry(auction_id).or_default(), 1)
+                } else {
+                    continue;
+                };
+            if confirmed {
+                balance.confirmed += amount;
+            } else {
+                balance.unconfirmed += amount;
+            }
+        }
+        let lp_tokens = lp_tokens
+            .into_iter()
+            .map(|((asset0, asset1), balance)| LpTokenBalance {
+                asset0,
+                asset1,
+                balance,
+            })
+            .collect();
+        Ok(AssetBalances {
+            assets,
+            lp_tokens,
+            dutch_auction_receipts,
+        })
+    }
+
+    async fn atomic_swaps(
+        &self,
+    ) -> RpcResult<Vec<(AtomicSwapId, AtomicSwapState)>> {
+        self.app.node.atomic_swaps().map_err(convert_node_err)
+    }
+
+    async fn auto_mine(&self, interval_secs: Option<u64>) -> RpcResult<()> {
+        let params = serde_json::json!({
+            "interval_secs": interval_secs,
+        });
+        let res: RpcResult<()> = async {
+            let () = self.require_wallet_seed()?;
+            if interval_secs == Some(0) {
+                return Err(custom_err("`interval_secs` must be nonzero"));
+            }
+            let interval = interval_secs.map(Duration::from_secs);
+            self.app.start_auto_mine(interval).map_err(convert_app_err)
+        }
+        .await;
+        self.audit("auto_mine", params, res)
     }
 
     async fn bitassets(
@@ -162,108 +670,410 @@ impl RpcServer for RpcServerImpl {
     }
 
     async fn bitcoin_balance(&self) -> RpcResult<u64> {
+        let () = self.require_wallet_seed()?;
         self.app
             .wallet
             .get_bitcoin_balance()
             .map_err(convert_wallet_err)
     }
 
+    async fn build_transfer(
+        &self,
+        outputs: Vec<TransferOutput>,
+        fee: u64,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "outputs": outputs,
+            "fee": fee,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let outputs: Vec<_> = outputs
+                .into_iter()
+                .map(|output| (output.address, output.asset, output.amount))
+                .collect();
+            let tx = self
+                .app
+                .wallet
+                .create_multi_asset_transfer(&outputs, fee)
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("build_transfer", params, res)
+    }
+
+    async fn cancel_order(&self, order_id: OrderId) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "order_id": order_id,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let order_state = self
+                .app
+                .node
+                .get_order_state(order_id)
+                .map_err(convert_node_err)?;
+            let mut tx = Transaction::default();
+            let () = self
+                .app
+                .wallet
+                .order_cancel(
+                    &mut tx,
+                    order_id,
+                    order_state.sell_asset,
+                    order_state.buy_asset,
+                    order_state.sell_amount_remaining.latest().data,
+                    order_state.buy_amount_received.latest().data,
+                )
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("cancel_order", params, res)
+    }
+
+    async fn cancel_withdrawal(
+        &self,
+        outpoint: OutPoint,
+        fee_sats: u64,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "outpoint": outpoint,
+            "fee_sats": fee_sats,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let dest = self
+                .app
+                .wallet
+                .get_new_address()
+                .map_err(convert_wallet_err)?;
+            let tx = self
+                .app
+                .node
+                .cancel_withdrawal(outpoint, dest, fee_sats)
+                .map_err(convert_node_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("cancel_withdrawal", params, res)
+    }
+
+    async fn claim_atomic_swap(
+        &self,
+        swap_id: AtomicSwapId,
+        preimage: String,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "swap_id": swap_id,
+            "preimage": preimage,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let preimage = decode_hash(&preimage)?;
+            self.release_atomic_swap(swap_id, Some(preimage))
+        }
+        .await;
+        self.audit("claim_atomic_swap", params, res)
+    }
+
+    async fn complete_registration(
+        &self,
+        prep: ReservationPrep,
+        initial_supply: u64,
+        bitasset_data: BitAssetData,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "prep": prep,
+            "initial_supply": initial_supply,
+            "bitasset_data": bitasset_data,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let mut tx = Transaction::default();
+            let () = self
+                .app
+                .wallet
+                .complete_registration(
+                    &mut tx,
+                    &prep,
+                    bitasset_data,
+                    initial_supply,
+                )
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("complete_registration", params, res)
+    }
+
     async fn connect_peer(&self, addr: SocketAddr) -> RpcResult<()> {
-        self.app.node.connect_peer(addr).map_err(convert_node_err)
+        let params = serde_json::json!({
+            "addr": addr,
+        });
+        let res: RpcResult<()> = async {
+            self.app.node.connect_peer(addr).map_err(convert_node_err)
+        }
+        .await;
+        self.audit("connect_peer", params, res)
+    }
+
+    async fn count_utxos(&self) -> RpcResult<u64> {
+        self.app.node.count_utxos().map_err(convert_node_err)
+    }
+
+    async fn count_utxos_by_asset(&self, asset_id: AssetId) -> RpcResult<u64> {
+        self.app
+            .node
+            .count_utxos_by_asset(asset_id)
+            .map_err(convert_node_err)
+    }
+
+    async fn create_atomic_swap(
+        &self,
+        asset: AssetId,
+        amount: u64,
+        recipient: Address,
+        hash_lock: String,
+        timeout: u32,
+    ) -> RpcResult<AtomicSwapId> {
+        let params = serde_json::json!({
+            "asset": asset,
+            "amount": amount,
+            "recipient": recipient,
+            "hash_lock": hash_lock,
+            "timeout": timeout,
+        });
+        let res: RpcResult<AtomicSwapId> = async {
+            let () = self.require_wallet_seed()?;
+            let hash_lock = decode_hash(&hash_lock)?;
+            let refund_address = self
+                .app
+                .wallet
+                .get_new_address()
+                .map_err(convert_wallet_err)?;
+            let atomic_swap_params = AtomicSwapParams {
+                asset,
+                amount,
+                recipient,
+                refund_address,
+                hash_lock,
+                timeout,
+            };
+            let mut tx = Transaction::default();
+            let () = self
+                .app
+                .wallet
+                .atomic_swap_lock(&mut tx, atomic_swap_params)
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(AtomicSwapId(txid))
+        }
+        .await;
+        self.audit("create_atomic_swap", params, res)
+    }
+
+    async fn create_token(
+        &self,
+        plain_name: String,
+        initial_supply: u64,
+        bitasset_data: BitAssetData,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "plain_name": plain_name,
+            "initial_supply": initial_supply,
+            "bitasset_data": bitasset_data,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let bitasset_id = BitAssetId::from_plain_name(&plain_name);
+            if self
+                .app
+                .node
+                .try_get_current_bitasset_data(&bitasset_id)
+                .map_err(convert_node_err)?
+                .is_some()
+            {
+                return Err(custom_err(format!(
+                    "BitAsset `{plain_name}` is already registered"
+                )));
+            }
+            if let Some(txid) = self.try_register_bitasset(
+                &plain_name,
+                &bitasset_data,
+                initial_supply,
+            )? {
+                return Ok(txid);
+            }
+            // Subscribe before sending the reservation, so that the
+            // confirmation cannot be missed
+            let mut state_changes = pin!(self.app.node.watch_state());
+            let mut tx = Transaction::default();
+            let _: ReservationPrep = self
+                .app
+                .wallet
+                .reserve_bitasset(&mut tx, &plain_name)
+                .map_err(convert_wallet_err)?;
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            let register = async {
+                while let Some(()) = state_changes.next().await {
+                    let () = self.app.refresh().map_err(convert_app_err)?;
+                    if let Some(txid) = self.try_register_bitasset(
+                        &plain_name,
+                        &bitasset_data,
+                        initial_supply,
+                    )? {
+                        return Ok(txid);
+                    }
+                }
+                Err(custom_err("node state stream ended"))
+            };
+            tokio::time::timeout(
+                Self::CREATE_TOKEN_RESERVATION_TIMEOUT,
+                register,
+            )
+            .await
+            .map_err(|_| {
+                custom_err(format!(
+                    "timed out waiting for the reservation for `{plain_name}` \
+                     to be confirmed; call `create_token` again to register \
+                     it once the reservation is confirmed"
+                ))
+            })?
+        }
+        .await;
+        self.audit("create_token", params, res)
     }
 
     async fn dutch_auction_bid(
         &self,
         auction_id: DutchAuctionId,
         bid_size: u64,
+        expiry_height: Option<u32>,
     ) -> RpcResult<u64> {
-        let height = self.getblockcount().await?;
-        let auction_state = self
-            .app
-            .node
-            .get_dutch_auction_state(auction_id)
-            .map_err(convert_node_err)?;
-        let next_auction_state = auction_state
-            .bid(Txid::default(), bid_size, height)
-            .map_err(|err| convert_node_err(err.into()))?;
-        let receive_quantity =
-            auction_state.base_amount_remaining.latest().data
-                - next_auction_state.base_amount_remaining.latest().data;
-        let mut tx = Transaction::default();
-        let () = self
-            .app
-            .wallet
-            .dutch_auction_bid(
-                &mut tx,
-                auction_id,
-                auction_state.base_asset,
-                auction_state.quote_asset,
-                bid_size,
-                receive_quantity,
-            )
-            .map_err(convert_wallet_err)?;
-        let authorized_tx =
-            self.app.wallet.authorize(tx).map_err(convert_wallet_err)?;
-        self.app
-            .node
-            .submit_transaction(authorized_tx)
-            .map_err(convert_node_err)?;
-        Ok(receive_quantity)
+        let params = serde_json::json!({
+            "auction_id": auction_id,
+            "bid_size": bid_size,
+            "expiry_height": expiry_height,
+        });
+        let res: RpcResult<u64> = async {
+            let () = self.require_wallet_seed()?;
+            let height = self.getblockcount().await?;
+            let auction_state = self
+                .app
+                .node
+                .get_dutch_auction_state(auction_id)
+                .map_err(convert_node_err)?;
+            let next_auction_state = auction_state
+                .bid(Txid::default(), bid_size, height)
+                .map_err(|err| convert_node_err(err.into()))?;
+            let receive_quantity =
+                auction_state.base_amount_remaining.latest().data
+                    - next_auction_state.base_amount_remaining.latest().data;
+            let mut tx = Transaction::default();
+            let () = self
+                .app
+                .wallet
+                .dutch_auction_bid(
+                    &mut tx,
+                    auction_id,
+                    auction_state.base_asset,
+                    auction_state.quote_asset,
+                    bid_size,
+                    receive_quantity,
+                )
+                .map_err(convert_wallet_err)?;
+            let authorized_tx =
+                self.app.wallet.authorize(tx).map_err(convert_wallet_err)?;
+            self.app
+                .node
+                .submit_transaction(authorized_tx, expiry_height)
+                .map_err(convert_node_err)?;
+            Ok(receive_quantity)
+        }
+        .await;
+        self.audit("dutch_auction_bid", params, res)
     }
 
     async fn dutch_auction_collect(
         &self,
         auction_id: DutchAuctionId,
     ) -> RpcResult<(u64, u64)> {
-        let height = self.getblockcount().await?;
-        let auction_state = self
-            .app
-            .node
-            .get_dutch_auction_state(auction_id)
-            .map_err(convert_node_err)?;
-        if height <= auction_state.start_block + auction_state.duration {
-            let err = state::DutchAuctionCollectError::AuctionNotFinished;
-            let err = node::Error::State(err.into());
-            return Err(convert_node_err(err));
-        }
-        let mut tx = Transaction::default();
-        let () = self
-            .app
-            .wallet
-            .dutch_auction_collect(
-                &mut tx,
-                auction_id,
-                auction_state.base_asset,
-                auction_state.quote_asset,
+        let params = serde_json::json!({
+            "auction_id": auction_id,
+        });
+        let res: RpcResult<(u64, u64)> = async {
+            let () = self.require_wallet_seed()?;
+            let height = self.getblockcount().await?;
+            let auction_state = self
+                .app
+                .node
+                .get_dutch_auction_state(auction_id)
+                .map_err(convert_node_err)?;
+            if height <= auction_state.start_block + auction_state.duration {
+                let err = state::DutchAuctionCollectError::AuctionNotFinished;
+                let err = node::Error::State(err.into());
+                return Err(convert_node_err(err));
+            }
+            let mut tx = Transaction::default();
+            let () = self
+                .app
+                .wallet
+                .dutch_auction_collect(
+                    &mut tx,
+                    auction_id,
+                    auction_state.base_asset,
+                    auction_state.quote_asset,
+                    auction_state.base_amount_remaining.latest().data,
+                    auction_state.quote_amount.latest().data,
+                )
+                .map_err(convert_wallet_err)?;
+            let authorized_tx =
+                self.app.wallet.authorize(tx).map_err(convert_wallet_err)?;
+            self.app
+                .node
+                .submit_transaction(authorized_tx, None)
+                .map_err(convert_node_err)?;
+            Ok((
                 auction_state.base_amount_remaining.latest().data,
                 auction_state.quote_amount.latest().data,
-            )
-            .map_err(convert_wallet_err)?;
-        let authorized_tx =
-            self.app.wallet.authorize(tx).map_err(convert_wallet_err)?;
-        self.app
-            .node
-            .submit_transaction(authorized_tx)
-            .map_err(convert_node_err)?;
-        Ok((
-            auction_state.base_amount_remaining.latest().data,
-            auction_state.quote_amount.latest().data,
-        ))
+            ))
+        }
+        .await;
+        self.audit("dutch_auction_collect", params, res)
     }
 
     async fn dutch_auction_create(
         &self,
         dutch_auction_params: DutchAuctionParams,
     ) -> RpcResult<Txid> {
-        let mut tx = Transaction::default();
-        let () = self
-            .app
-            .wallet
-            .dutch_auction_create(&mut tx, dutch_auction_params)
-            .map_err(convert_wallet_err)?;
-        let txid = tx.txid();
-        let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
-        Ok(txid)
+        let params = serde_json::json!({
+            "dutch_auction_params": dutch_auction_params,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let mut tx = Transaction::default();
+            let () = self
+                .app
+                .wallet
+                .dutch_auction_create(&mut tx, dutch_auction_params)
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("dutch_auction_create", params, res)
     }
 
     async fn dutch_auctions(
@@ -272,6 +1082,109 @@ impl RpcServer for RpcServerImpl {
         self.app.node.dutch_auctions().map_err(convert_node_err)
     }
 
+    async fn encrypt_wallet(&self, passphrase: String) -> RpcResult<()> {
+        let params = serde_json::json!({
+            "passphrase": "<redacted>",
+        });
+        let res: RpcResult<()> = async {
+            self.app
+                .wallet
+                .encrypt(&passphrase)
+                .map_err(convert_wallet_err)
+        }
+        .await;
+        self.audit("encrypt_wallet", params, res)
+    }
+
+    async fn estimate_withdrawal(
+        &self,
+        mainchain_address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+        amount_sats: u64,
+        mainchain_fee_sats: u64,
+    ) -> RpcResult<WithdrawalEstimate> {
+        self.app
+            .node
+            .estimate_withdrawal(
+                mainchain_address,
+                amount_sats,
+                mainchain_fee_sats,
+            )
+            .map_err(convert_node_err)
+    }
+
+    async fn fill_order(
+        &self,
+        order_id: OrderId,
+        amount: u64,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "order_id": order_id,
+            "amount": amount,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let height = self.getblockcount().await?;
+            let order_state = self
+                .app
+                .node
+                .get_order_state(order_id)
+                .map_err(convert_node_err)?;
+            let next_order_state =
+                order_state.fill(Txid::default(), amount, height).map_err(
+                    |err| convert_node_err(state::Error::from(err).into()),
+                )?;
+            let cost = next_order_state.buy_amount_received.latest().data
+                - order_state.buy_amount_received.latest().data;
+            let mut tx = Transaction::default();
+            let () = self
+                .app
+                .wallet
+                .order_fill(
+                    &mut tx,
+                    order_id,
+                    order_state.sell_asset,
+                    order_state.buy_asset,
+                    amount,
+                    cost,
+                )
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("fill_order", params, res)
+    }
+
+    async fn estimate_impermanent_loss(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        entry_reserve0: u64,
+        entry_reserve1: u64,
+    ) -> RpcResult<Fraction> {
+        let amm_pair = AmmPair::new(asset0, asset1);
+        self.app
+            .node
+            .estimate_impermanent_loss(amm_pair, entry_reserve0, entry_reserve1)
+            .map_err(convert_node_err)?
+            .ok_or_else(|| {
+                custom_err(
+                    "entry reserves and current reserves must be nonzero",
+                )
+            })
+    }
+
+    async fn find_bitassets_by_key(
+        &self,
+        key: BitAssetKey,
+    ) -> RpcResult<Vec<BitAssetId>> {
+        self.app
+            .node
+            .find_bitassets_by_key(&key)
+            .map_err(convert_node_err)
+    }
+
     async fn format_deposit_address(
         &self,
         address: Address,
@@ -291,45 +1204,270 @@ impl RpcServer for RpcServerImpl {
         Ok(mnemonic.to_string())
     }
 
-    async fn get_amm_pool_state(
+    async fn get_address_history(
+        &self,
+        address: Address,
+        from_height: Option<u32>,
+    ) -> RpcResult<Vec<Txid>> {
+        self.app
+            .node
+            .get_address_history(&address, from_height.unwrap_or_default())
+            .map_err(convert_node_err)
+    }
+
+    async fn get_amm_pool_history(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        from_height: u32,
+        to_height: u32,
+    ) -> RpcResult<Vec<(u32, AmmPoolState)>> {
+        let amm_pair = AmmPair::new(asset0, asset1);
+        self.app
+            .node
+            .get_amm_pool_history(amm_pair, from_height, to_height)
+            .map_err(convert_node_err)
+    }
+
+    async fn get_amm_pool_state(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+    ) -> RpcResult<AmmPoolState> {
+        let amm_pair = AmmPair::new(asset0, asset1);
+        self.app
+            .node
+            .get_amm_pool_state(amm_pair)
+            .map_err(convert_node_err)
+    }
+
+    async fn get_amm_pool_state_at_height(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        height: u32,
+    ) -> RpcResult<Option<AmmPoolState>> {
+        let amm_pair = AmmPair::new(asset0, asset1);
+        self.app
+            .node
+            .get_amm_pool_state_at_height(amm_pair, height)
+            .map_err(convert_node_err)
+    }
+
+    async fn get_amm_price(
+        &self,
+        base: AssetId,
+        quote: AssetId,
+    ) -> RpcResult<Option<Fraction>> {
+        self.app
+            .node
+            .try_get_amm_price(base, quote)
+            .map_err(convert_node_err)
+    }
+
+    async fn get_amm_price_impact(
+        &self,
+        base: AssetId,
+        quote: AssetId,
+        amount_spend: u64,
+    ) -> RpcResult<Option<Fraction>> {
+        self.app
+            .node
+            .try_get_amm_price_impact(base, quote, amount_spend)
+            .map_err(convert_node_err)
+    }
+
+    async fn get_balance_at_height(
+        &self,
+        address: Address,
+        height: u32,
+    ) -> RpcResult<u64> {
+        let tip_height =
+            self.app.node.get_tip_height().map_err(convert_node_err)?;
+        if tip_height.saturating_sub(height)
+            > Self::GET_BALANCE_AT_HEIGHT_MAX_DEPTH
+        {
+            return Err(custom_err(format!(
+                "height {height} is more than {} blocks below the tip",
+                Self::GET_BALANCE_AT_HEIGHT_MAX_DEPTH
+            )));
+        }
+        self.app
+            .node
+            .get_balance_at_height(&address, height)
+            .map_err(convert_node_err)
+    }
+
+    async fn get_bitasset_market_cap(
+        &self,
+        bitasset_id: BitAssetId,
+    ) -> RpcResult<Option<u64>> {
+        self.app
+            .node
+            .try_get_bitasset_market_cap(&bitasset_id)
+            .map_err(convert_node_err)
+    }
+
+    async fn get_bitasset_txids(
+        &self,
+        bitasset_id: BitAssetId,
+    ) -> RpcResult<Vec<Txid>> {
+        self.app
+            .node
+            .get_bitasset_txids(&bitasset_id)
+            .map_err(convert_node_err)
+    }
+
+    async fn get_block(&self, block_hash: BlockHash) -> RpcResult<Block> {
+        let block = self
+            .app
+            .node
+            .get_block(block_hash)
+            .expect("This error should have been handled properly.");
+        Ok(block)
+    }
+
+    async fn get_block_commitment(
+        &self,
+        header: Header,
+    ) -> RpcResult<bitcoin::BlockHash> {
+        Ok(header.bmm_critical_hash())
+    }
+
+    async fn get_block_inputs(
+        &self,
+        block_hash: BlockHash,
+    ) -> RpcResult<Vec<OutPoint>> {
+        let body = self
+            .app
+            .node
+            .get_body(block_hash)
+            .map_err(convert_node_err)?;
+        Ok(body.get_inputs())
+    }
+
+    async fn get_block_outputs(
+        &self,
+        block_hash: BlockHash,
+    ) -> RpcResult<Vec<PointedOutput>> {
+        let body = self
+            .app
+            .node
+            .get_body(block_hash)
+            .map_err(convert_node_err)?;
+        let mut outputs: Vec<_> = body
+            .get_outputs()
+            .into_iter()
+            .map(|(outpoint, output)| PointedOutput { outpoint, output })
+            .collect();
+        outputs.sort_unstable_by_key(|output| output.outpoint);
+        Ok(outputs)
+    }
+
+    async fn get_block_receive_time(
         &self,
-        asset0: AssetId,
-        asset1: AssetId,
-    ) -> RpcResult<AmmPoolState> {
-        let amm_pair = AmmPair::new(asset0, asset1);
+        block_hash: BlockHash,
+    ) -> RpcResult<Option<u64>> {
         self.app
             .node
-            .get_amm_pool_state(amm_pair)
+            .try_get_block_receive_time(block_hash)
             .map_err(convert_node_err)
     }
 
-    async fn get_amm_price(
+    async fn get_block_transactions(
         &self,
-        base: AssetId,
-        quote: AssetId,
-    ) -> RpcResult<Option<Fraction>> {
-        self.app
+        block_hash: BlockHash,
+    ) -> RpcResult<Vec<(Txid, AuthorizedTransaction)>> {
+        let body = self
+            .app
             .node
-            .try_get_amm_price(base, quote)
-            .map_err(convert_node_err)
+            .get_body(block_hash)
+            .map_err(convert_node_err)?;
+        let txs = body
+            .authorized_transactions()
+            .into_iter()
+            .map(|tx| (tx.transaction.txid(), tx))
+            .collect();
+        Ok(txs)
     }
 
-    async fn get_block(&self, block_hash: BlockHash) -> RpcResult<Block> {
-        let block = self
+    async fn get_deposit_blocks(&self) -> RpcResult<Vec<DepositBlock>> {
+        let deposit_blocks = self
             .app
             .node
-            .get_block(block_hash)
-            .expect("This error should have been handled properly.");
-        Ok(block)
+            .get_deposit_blocks()
+            .map_err(convert_node_err)?;
+        let res = deposit_blocks
+            .into_iter()
+            .map(|(main_block_hash, sidechain_height)| DepositBlock {
+                main_block_hash,
+                sidechain_height,
+            })
+            .collect();
+        Ok(res)
+    }
+
+    async fn get_headers(
+        &self,
+        start_height: u32,
+        count: u32,
+    ) -> RpcResult<Vec<Header>> {
+        let count = count.min(Self::GET_HEADERS_MAX_COUNT);
+        self.app
+            .node
+            .get_headers(start_height, count)
+            .map_err(convert_node_err)
+    }
+
+    async fn get_lp_token_id(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+    ) -> RpcResult<(AssetId, AssetId)> {
+        let pair = AmmPair::new(asset0, asset1);
+        Ok((pair.asset0(), pair.asset1()))
     }
 
     async fn get_new_address(&self) -> RpcResult<Address> {
+        let () = self.require_wallet_seed()?;
         self.app
             .wallet
             .get_new_address()
             .map_err(convert_wallet_err)
     }
 
+    async fn get_node_info(&self) -> RpcResult<NodeInfo> {
+        let peer_counts = self.app.node.peer_counts();
+        let peer_limits = self.app.node.peer_limits();
+        Ok(NodeInfo {
+            num_inbound_peers: peer_counts.inbound,
+            num_outbound_peers: peer_counts.outbound,
+            max_inbound_peers: peer_limits.max_inbound,
+            max_outbound_peers: peer_limits.max_outbound,
+            peers: self.peers(),
+        })
+    }
+
+    async fn get_rpc_audit_log(
+        &self,
+        limit: usize,
+    ) -> RpcResult<Vec<RpcAuditLogEntry>> {
+        self.app
+            .audit_log
+            .get_latest(limit)
+            .map_err(|err| convert_app_err(err.into()))
+    }
+
+    async fn get_spent_by(
+        &self,
+        outpoint: OutPoint,
+    ) -> RpcResult<Option<InPoint>> {
+        self.app
+            .node
+            .get_spent_by(&outpoint)
+            .map_err(convert_node_err)
+    }
+
     async fn get_transaction(
         &self,
         txid: Txid,
@@ -374,7 +1512,33 @@ impl RpcServer for RpcServerImpl {
         Ok(Some(res))
     }
 
+    async fn get_transaction_status(&self, txid: Txid) -> RpcResult<TxStatus> {
+        self.app
+            .node
+            .get_transaction_status(txid)
+            .map_err(convert_node_err)
+    }
+
+    async fn get_transactions_batch(
+        &self,
+        txids: Vec<Txid>,
+    ) -> RpcResult<Vec<Option<Transaction>>> {
+        if txids.len() > Self::MAX_TRANSACTIONS_BATCH_SIZE {
+            let err_msg = format!(
+                "requested {} txids, but at most {} may be requested",
+                txids.len(),
+                Self::MAX_TRANSACTIONS_BATCH_SIZE
+            );
+            return Err(custom_err(err_msg));
+        }
+        self.app
+            .node
+            .try_get_transactions(&txids)
+            .map_err(convert_node_err)
+    }
+
     async fn get_wallet_addresses(&self) -> RpcResult<Vec<Address>> {
+        let () = self.require_wallet_seed()?;
         let addrs = self
             .app
             .wallet
@@ -388,22 +1552,83 @@ impl RpcServer for RpcServerImpl {
     async fn get_wallet_utxos(
         &self,
     ) -> RpcResult<Vec<PointedOutput<FilledOutputContent>>> {
+        let () = self.require_wallet_seed()?;
         let utxos = self.app.wallet.get_utxos().map_err(convert_wallet_err)?;
-        let utxos = utxos
+        let mut utxos: Vec<_> = utxos
             .into_iter()
             .map(|(outpoint, output)| PointedOutput { outpoint, output })
             .collect();
+        utxos.sort_unstable_by_key(|utxo| utxo.outpoint);
         Ok(utxos)
     }
 
+    async fn get_withdrawal_bundle_for_utxo(
+        &self,
+        outpoint: OutPoint,
+    ) -> RpcResult<Option<WithdrawalBundleInfo>> {
+        self.app
+            .node
+            .try_get_withdrawal_bundle_for_utxo(outpoint)
+            .map_err(convert_node_err)
+    }
+
     async fn getblockcount(&self) -> RpcResult<u32> {
         self.app.node.get_tip_height().map_err(convert_node_err)
     }
 
+    async fn list_maturing_deposits(&self) -> RpcResult<Vec<(OutPoint, u32)>> {
+        self.app.get_maturing_deposits().map_err(convert_app_err)
+    }
+
+    async fn list_orders(&self) -> RpcResult<Vec<(OrderId, OrderState)>> {
+        self.app.node.orders().map_err(convert_node_err)
+    }
+
+    async fn list_peers(&self) -> RpcResult<Vec<Peer>> {
+        Ok(self.peers())
+    }
+
     async fn list_utxos(
         &self,
     ) -> RpcResult<Vec<PointedOutput<FilledOutputContent>>> {
         let utxos = self.app.node.get_all_utxos().map_err(convert_node_err)?;
+        let mut res: Vec<_> = utxos
+            .into_iter()
+            .map(|(outpoint, output)| PointedOutput { outpoint, output })
+            .collect();
+        res.sort_unstable_by_key(|utxo| utxo.outpoint);
+        Ok(res)
+    }
+
+    async fn list_utxos_by_asset(
+        &self,
+        asset_id: AssetId,
+    ) -> RpcResult<Vec<PointedOutput<FilledOutputContent>>> {
+        let utxos = self
+            .app
+            .node
+            .get_utxos_by_asset(asset_id)
+            .map_err(convert_node_err)?;
+        let mut res: Vec<_> = utxos
+            .into_iter()
+            .map(|(outpoint, output)| PointedOutput { outpoint, output })
+            .collect();
+        res.sort_unstable_by_key(|utxo| utxo.outpoint);
+        Ok(res)
+    }
+
+    async fn list_utxos_paginated(
+        &self,
+        offset: usize,
+        limit: usize,
+        asset: Option<AssetId>,
+        sort: UtxoSort,
+    ) -> RpcResult<Vec<PointedOutput<FilledOutputContent>>> {
+        let utxos = self
+            .app
+            .node
+            .get_utxos_paginated(offset, limit, asset, sort)
+            .map_err(convert_node_err)?;
         let res = utxos
             .into_iter()
             .map(|(outpoint, output)| PointedOutput { outpoint, output })
@@ -411,21 +1636,91 @@ impl RpcServer for RpcServerImpl {
         Ok(res)
     }
 
-    async fn mine(&self, fee: Option<u64>) -> RpcResult<()> {
-        let fee = fee.map(bip300301::bitcoin::Amount::from_sat);
-        self.app.local_pool.spawn_pinned({
-            let app = self.app.clone();
-            move || async move { app.mine(fee).await.map_err(convert_app_err) }
-        }).await.unwrap()
+    async fn list_withdrawal_bundles(
+        &self,
+    ) -> RpcResult<Vec<WithdrawalBundleRecord>> {
+        self.app
+            .node
+            .list_withdrawal_bundles()
+            .map_err(convert_node_err)
+    }
+
+    async fn lock_wallet(&self) -> RpcResult<()> {
+        let res = self.app.wallet.lock().map_err(convert_wallet_err);
+        self.audit("lock_wallet", serde_json::json!({}), res)
+    }
+
+    async fn mempool_info(&self) -> RpcResult<MemPoolInfo> {
+        self.app.node.mempool_info().map_err(convert_node_err)
+    }
+
+    async fn mine(
+        &self,
+        fee: Option<u64>,
+        instant: Option<bool>,
+    ) -> RpcResult<()> {
+        let params = serde_json::json!({
+            "fee": fee,
+            "instant": instant,
+        });
+        let res: RpcResult<()> = async {
+            let () = self.require_wallet_seed()?;
+            let fee = fee.map(bip300301::bitcoin::Amount::from_sat);
+            let instant = instant.unwrap_or(false);
+            self.app
+                .local_pool
+                .spawn_pinned({
+                    let app = self.app.clone();
+                    move || async move {
+                        app.mine(fee, instant).await.map_err(convert_app_err)
+                    }
+                })
+                .await
+                .unwrap()
+        }
+        .await;
+        self.audit("mine", params, res)
+    }
+
+    async fn mint_bitasset(
+        &self,
+        bitasset_id: BitAssetId,
+        amount: u64,
+        fee: u64,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "bitasset_id": bitasset_id,
+            "amount": amount,
+            "fee": fee,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let mut tx = self
+                .app
+                .wallet
+                .create_regular_transaction(fee)
+                .map_err(convert_wallet_err)?;
+            let () = self
+                .app
+                .wallet
+                .mint_bitasset(&mut tx, bitasset_id, amount)
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("mint_bitasset", params, res)
     }
 
     async fn my_unconfirmed_utxos(&self) -> RpcResult<Vec<PointedOutput>> {
+        let () = self.require_wallet_seed()?;
         let addresses = self
             .app
             .wallet
             .get_addresses()
             .map_err(convert_wallet_err)?;
-        let utxos = self
+        let mut utxos: Vec<_> = self
             .app
             .node
             .get_unconfirmed_utxos_by_addresses(&addresses)
@@ -433,13 +1728,15 @@ impl RpcServer for RpcServerImpl {
             .into_iter()
             .map(|(outpoint, output)| PointedOutput { outpoint, output })
             .collect();
+        utxos.sort_unstable_by_key(|utxo| utxo.outpoint);
         Ok(utxos)
     }
 
     async fn my_utxos(
         &self,
     ) -> RpcResult<Vec<PointedOutput<FilledOutputContent>>> {
-        let utxos = self
+        let () = self.require_wallet_seed()?;
+        let mut utxos: Vec<_> = self
             .app
             .wallet
             .get_utxos()
@@ -447,6 +1744,7 @@ impl RpcServer for RpcServerImpl {
             .into_iter()
             .map(|(outpoint, output)| PointedOutput { outpoint, output })
             .collect();
+        utxos.sort_unstable_by_key(|utxo| utxo.outpoint);
         Ok(utxos)
     }
 
@@ -456,22 +1754,311 @@ impl RpcServer for RpcServerImpl {
         Ok(res)
     }
 
+    async fn openrpc_schema(&self) -> RpcResult<serde_json::Value> {
+        Ok(plain_bitassets_app_rpc_api::openrpc_schema())
+    }
+
+    async fn place_order(
+        &self,
+        sell_asset: AssetId,
+        buy_asset: AssetId,
+        amount: u64,
+        price: u64,
+    ) -> RpcResult<OrderId> {
+        let params = serde_json::json!({
+            "sell_asset": sell_asset,
+            "buy_asset": buy_asset,
+            "amount": amount,
+            "price": price,
+        });
+        let res: RpcResult<OrderId> = async {
+            let () = self.require_wallet_seed()?;
+            let order_params = OrderParams {
+                sell_asset,
+                sell_amount: amount,
+                buy_asset,
+                price,
+            };
+            let mut tx = Transaction::default();
+            let () = self
+                .app
+                .wallet
+                .order_place(&mut tx, order_params)
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(OrderId(txid))
+        }
+        .await;
+        self.audit("place_order", params, res)
+    }
+
+    async fn prepare_reservation(
+        &self,
+        plain_name: String,
+    ) -> RpcResult<ReservationPrep> {
+        let params = serde_json::json!({
+            "plain_name": plain_name,
+        });
+        let res: RpcResult<ReservationPrep> = async {
+            let () = self.require_wallet_seed()?;
+            let mut tx = Transaction::default();
+            let prep = self
+                .app
+                .wallet
+                .reserve_bitasset(&mut tx, &plain_name)
+                .map_err(convert_wallet_err)?;
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(prep)
+        }
+        .await;
+        self.audit("prepare_reservation", params, res)
+    }
+
+    async fn preview_registration(
+        &self,
+        plain_name: String,
+    ) -> RpcResult<(BitAssetId, bool)> {
+        self.app
+            .node
+            .preview_registration(&plain_name)
+            .map_err(convert_node_err)
+    }
+
+    async fn refund_atomic_swap(
+        &self,
+        swap_id: AtomicSwapId,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "swap_id": swap_id,
+        });
+        let res: RpcResult<Txid> =
+            async { self.release_atomic_swap(swap_id, None) }.await;
+        self.audit("refund_atomic_swap", params, res)
+    }
+
+    async fn register_bitassets_bulk(
+        &self,
+        registrations: Vec<BitAssetRegistration>,
+    ) -> RpcResult<Vec<Txid>> {
+        let params = serde_json::json!({
+            "registrations": registrations,
+        });
+        let res: RpcResult<Vec<Txid>> = async {
+            let () = self.require_wallet_seed()?;
+            let mut txids = Vec::with_capacity(registrations.len());
+            for registration in registrations {
+                let BitAssetRegistration {
+                    plain_name,
+                    initial_supply,
+                    bitasset_data,
+                } = registration;
+                let mut tx = Transaction::default();
+                let _registered: bool = self
+                    .app
+                    .wallet
+                    .register_or_reserve_bitasset(
+                        &mut tx,
+                        &plain_name,
+                        Cow::Owned(bitasset_data),
+                        initial_supply,
+                    )
+                    .map_err(convert_wallet_err)?;
+                txids.push(tx.txid());
+                let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            }
+            Ok(txids)
+        }
+        .await;
+        self.audit("register_bitassets_bulk", params, res)
+    }
+
+    async fn reindex(&self) -> RpcResult<()> {
+        let params = serde_json::json!({});
+        let res: RpcResult<()> =
+            async { self.app.node.reindex().map_err(convert_node_err) }.await;
+        self.audit("reindex", params, res)
+    }
+
     async fn reserve_bitasset(&self, plain_name: String) -> RpcResult<Txid> {
-        let mut tx = Transaction::default();
-        let () = match self.app.wallet.reserve_bitasset(&mut tx, &plain_name) {
-            Ok(()) => (),
-            Err(err) => return Err(convert_wallet_err(err)),
+        let params = serde_json::json!({
+            "plain_name": plain_name,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let mut tx = Transaction::default();
+            let _: ReservationPrep =
+                match self.app.wallet.reserve_bitasset(&mut tx, &plain_name) {
+                    Ok(prep) => prep,
+                    Err(err) => return Err(convert_wallet_err(err)),
+                };
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("reserve_bitasset", params, res)
+    }
+
+    async fn reveal_mnemonic(&self) -> RpcResult<String> {
+        let res: RpcResult<String> = {
+            let mut last_reveal = self.last_mnemonic_reveal.lock();
+            match *last_reveal {
+                Some(last_reveal_time)
+                    if last_reveal_time.elapsed()
+                        < Self::MNEMONIC_REVEAL_INTERVAL =>
+                {
+                    let wait = Self::MNEMONIC_REVEAL_INTERVAL
+                        .saturating_sub(last_reveal_time.elapsed());
+                    Err(custom_err(format!(
+                        "mnemonic was revealed too recently; try again in {}s",
+                        wait.as_secs() + 1
+                    )))
+                }
+                _ => {
+                    let res = self
+                        .app
+                        .wallet
+                        .get_mnemonic()
+                        .map_err(convert_wallet_err);
+                    if res.is_ok() {
+                        *last_reveal = Some(Instant::now());
+                    }
+                    res
+                }
+            }
         };
-        let txid = tx.txid();
-        let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
-        Ok(txid)
+        // Do not record the mnemonic in the audit log
+        let _: RpcResult<&str> = self.audit(
+            "reveal_mnemonic",
+            serde_json::json!({}),
+            res.as_ref().map(|_| "<redacted>").map_err(Clone::clone),
+        );
+        res
     }
 
-    async fn set_seed_from_mnemonic(&self, mnemonic: String) -> RpcResult<()> {
-        self.app
-            .wallet
-            .set_seed_from_mnemonic(mnemonic.as_str())
-            .map_err(convert_wallet_err)
+    async fn run_diagnostics(&self) -> RpcResult<Diagnostics> {
+        /// Fraction of the DB map size above which to warn
+        const DB_USAGE_WARN: f64 = 0.75;
+        /// Fraction of the DB map size above which to fail
+        const DB_USAGE_FAIL: f64 = 0.9;
+        fn check(
+            name: &str,
+            status: DiagnosticStatus,
+            message: impl Into<String>,
+        ) -> DiagnosticCheck {
+            DiagnosticCheck {
+                name: name.to_owned(),
+                status,
+                message: message.into(),
+            }
+        }
+        /// Pass if there are no issues, otherwise report the issues with
+        /// the specified status
+        fn check_issues(
+            name: &str,
+            issues: Result<Vec<String>, node::Error>,
+            issue_status: DiagnosticStatus,
+        ) -> DiagnosticCheck {
+            match issues {
+                Ok(issues) if issues.is_empty() => {
+                    check(name, DiagnosticStatus::Pass, "ok")
+                }
+                Ok(issues) => check(name, issue_status, issues.join("; ")),
+                Err(err) => check(
+                    name,
+                    DiagnosticStatus::Fail,
+                    format!("{:#}", anyhow::Error::from(err)),
+                ),
+            }
+        }
+        let state_consistency = check_issues(
+            "state_consistency",
+            self.app.node.verify_state_consistency(),
+            DiagnosticStatus::Fail,
+        );
+        let mainchain_connectivity =
+            match self.app.node.get_best_parentchain_hash().await {
+                Ok(block_hash) => check(
+                    "mainchain_connectivity",
+                    DiagnosticStatus::Pass,
+                    format!("mainchain tip is {block_hash}"),
+                ),
+                Err(err) => check(
+                    "mainchain_connectivity",
+                    DiagnosticStatus::Fail,
+                    format!("{:#}", anyhow::Error::from(err)),
+                ),
+            };
+        let peers = {
+            let num_peers = self.app.node.num_active_peers();
+            let status = if num_peers == 0 {
+                DiagnosticStatus::Warn
+            } else {
+                DiagnosticStatus::Pass
+            };
+            check("peers", status, format!("{num_peers} connected peers"))
+        };
+        let mempool = check_issues(
+            "mempool",
+            self.app.node.verify_mempool(),
+            DiagnosticStatus::Warn,
+        );
+        let db_space = match self.app.node.db_usage() {
+            Ok((used, map_size)) => {
+                let usage = used as f64 / map_size as f64;
+                let status = if usage > DB_USAGE_FAIL {
+                    DiagnosticStatus::Fail
+                } else if usage > DB_USAGE_WARN {
+                    DiagnosticStatus::Warn
+                } else {
+                    DiagnosticStatus::Pass
+                };
+                check(
+                    "db_space",
+                    status,
+                    format!(
+                        "{used} of {map_size} bytes used ({:.1}%)",
+                        usage * 100.
+                    ),
+                )
+            }
+            Err(err) => check(
+                "db_space",
+                DiagnosticStatus::Fail,
+                format!("{:#}", anyhow::Error::from(err)),
+            ),
+        };
+        let checks = vec![
+            state_consistency,
+            mainchain_connectivity,
+            peers,
+            mempool,
+            db_space,
+        ];
+        Ok(Diagnostics { checks })
+    }
+
+    async fn set_seed_from_mnemonic(
+        &self,
+        mnemonic: String,
+        passphrase: Option<String>,
+    ) -> RpcResult<()> {
+        let params = serde_json::json!({
+            "mnemonic": "<redacted>",
+            "passphrase": passphrase.as_ref().map(|_| "<redacted>"),
+        });
+        let res: RpcResult<()> = async {
+            self.app
+                .wallet
+                .set_seed_from_mnemonic(
+                    mnemonic.as_str(),
+                    passphrase.as_deref().unwrap_or_default(),
+                )
+                .map_err(convert_wallet_err)
+        }
+        .await;
+        self.audit("set_seed_from_mnemonic", params, res)
     }
 
     async fn sidechain_wealth(&self) -> RpcResult<bitcoin::Amount> {
@@ -481,33 +2068,221 @@ impl RpcServer for RpcServerImpl {
             .map_err(convert_node_err)
     }
 
+    async fn split_bitasset_control(
+        &self,
+        bitasset_id: BitAssetId,
+        control_dest: Address,
+        units_dest: Address,
+        units_amount: u64,
+        fee: u64,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "bitasset_id": bitasset_id,
+            "control_dest": control_dest,
+            "units_dest": units_dest,
+            "units_amount": units_amount,
+            "fee": fee,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let mut tx = self
+                .app
+                .wallet
+                .create_regular_transaction(fee)
+                .map_err(convert_wallet_err)?;
+            let () = self
+                .app
+                .wallet
+                .split_bitasset_control(
+                    &mut tx,
+                    bitasset_id,
+                    control_dest,
+                    units_dest,
+                    units_amount,
+                )
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("split_bitasset_control", params, res)
+    }
+
     async fn stop(&self) {
+        let _ = self.audit("stop", serde_json::json!({}), Ok(()));
         std::process::exit(0);
     }
 
+    async fn stop_auto_mine(&self) -> RpcResult<()> {
+        let () = self.app.stop_auto_mine();
+        self.audit("stop_auto_mine", serde_json::json!({}), Ok(()))
+    }
+
     async fn transfer(
         &self,
         dest: Address,
         value: u64,
         fee: u64,
         memo: Option<String>,
+        expiry_height: Option<u32>,
     ) -> RpcResult<Txid> {
-        let memo = match memo {
-            None => None,
-            Some(memo) => {
-                let hex = hex::decode(memo)
-                    .map_err(|err| custom_err(err.to_string()))?;
-                Some(hex)
+        let params = serde_json::json!({
+            "dest": dest,
+            "value": value,
+            "fee": fee,
+            "memo": memo,
+            "expiry_height": expiry_height,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let memo = match memo {
+                None => None,
+                Some(memo) => {
+                    let hex = hex::decode(memo)
+                        .map_err(|err| custom_err(err.to_string()))?;
+                    Some(hex)
+                }
+            };
+            let tx = self
+                .app
+                .wallet
+                .create_transfer(dest, value, fee, memo)
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self
+                .app
+                .sign_and_send_with_expiry(tx, expiry_height)
+                .map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("transfer", params, res)
+    }
+
+    async fn transfer_bitasset_control(
+        &self,
+        bitasset_id: BitAssetId,
+        dest: Address,
+        fee: u64,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "bitasset_id": bitasset_id,
+            "dest": dest,
+            "fee": fee,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let mut tx = self
+                .app
+                .wallet
+                .create_regular_transaction(fee)
+                .map_err(convert_wallet_err)?;
+            let () = self
+                .app
+                .wallet
+                .transfer_bitasset_control(&mut tx, bitasset_id, dest)
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("transfer_bitasset_control", params, res)
+    }
+
+    async fn transfer_lp_token(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        dest: Address,
+        amount: u64,
+        fee: u64,
+    ) -> RpcResult<Txid> {
+        let params = serde_json::json!({
+            "asset0": asset0,
+            "asset1": asset1,
+            "dest": dest,
+            "amount": amount,
+            "fee": fee,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let amm_pair = AmmPair::new(asset0, asset1);
+            let mut tx = self
+                .app
+                .wallet
+                .create_regular_transaction(fee)
+                .map_err(convert_wallet_err)?;
+            let () = self
+                .app
+                .wallet
+                .amm_lp_token_transfer(
+                    &mut tx,
+                    amm_pair.asset0(),
+                    amm_pair.asset1(),
+                    dest,
+                    amount,
+                )
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("transfer_lp_token", params, res)
+    }
+
+    async fn unlock_wallet(
+        &self,
+        passphrase: String,
+        timeout_secs: Option<u64>,
+    ) -> RpcResult<()> {
+        let params = serde_json::json!({
+            "passphrase": "<redacted>",
+            "timeout_secs": timeout_secs,
+        });
+        let res: RpcResult<()> = async {
+            let timeout = timeout_secs.map_or(
+                wallet::Wallet::DEFAULT_UNLOCK_TIMEOUT,
+                std::time::Duration::from_secs,
+            );
+            self.app
+                .wallet
+                .unlock(&passphrase, timeout)
+                .map_err(convert_wallet_err)
+        }
+        .await;
+        self.audit("unlock_wallet", params, res)
+    }
+
+    async fn wait_for_block(
+        &self,
+        current_height: u32,
+        timeout_secs: Option<u64>,
+    ) -> RpcResult<u32> {
+        let timeout = timeout_secs
+            .map_or(Self::WAIT_FOR_BLOCK_DEFAULT_TIMEOUT, Duration::from_secs)
+            .min(Self::WAIT_FOR_BLOCK_MAX_TIMEOUT);
+        // Subscribe before checking the tip, so that tip changes cannot be
+        // missed
+        let mut state_changes = pin!(self.app.node.watch_state());
+        let wait = async {
+            loop {
+                let height =
+                    self.app.node.get_tip_height().map_err(convert_node_err)?;
+                if height > current_height {
+                    return Ok(height);
+                }
+                if state_changes.next().await.is_none() {
+                    return Err(custom_err("node state stream ended"));
+                }
             }
         };
-        let tx = self
-            .app
-            .wallet
-            .create_transfer(dest, value, fee, memo)
-            .map_err(convert_wallet_err)?;
-        let txid = tx.txid();
-        let () = self.app.sign_and_send(tx).map_err(convert_app_err)?;
-        Ok(txid)
+        match tokio::time::timeout(timeout, wait).await {
+            Ok(res) => res,
+            Err(_) => self.app.node.get_tip_height().map_err(convert_node_err),
+        }
     }
 
     async fn withdraw(
@@ -517,30 +2292,175 @@ impl RpcServer for RpcServerImpl {
         fee_sats: u64,
         mainchain_fee_sats: u64,
     ) -> RpcResult<Txid> {
-        let tx = self
-            .app
-            .wallet
-            .create_withdrawal(
-                mainchain_address,
-                amount_sats,
-                mainchain_fee_sats,
-                fee_sats,
-            )
-            .map_err(convert_wallet_err)?;
-        let txid = tx.txid();
-        self.app.sign_and_send(tx).map_err(convert_app_err)?;
-        Ok(txid)
+        let params = serde_json::json!({
+            "mainchain_address":
+                mainchain_address.clone().assume_checked().to_string(),
+            "amount_sats": amount_sats,
+            "fee_sats": fee_sats,
+            "mainchain_fee_sats": mainchain_fee_sats,
+        });
+        let res: RpcResult<Txid> = async {
+            let () = self.require_wallet_seed()?;
+            let tx = self
+                .app
+                .wallet
+                .create_withdrawal(
+                    mainchain_address,
+                    amount_sats,
+                    mainchain_fee_sats,
+                    fee_sats,
+                )
+                .map_err(convert_wallet_err)?;
+            let txid = tx.txid();
+            self.app.sign_and_send(tx).map_err(convert_app_err)?;
+            Ok(txid)
+        }
+        .await;
+        self.audit("withdraw", params, res)
+    }
+}
+
+#[async_trait]
+impl RpcSubscriptionsServer for RpcServerImpl {
+    async fn subscribe_amm_pool(
+        &self,
+        pending: PendingSubscriptionSink,
+        asset0: AssetId,
+        asset1: AssetId,
+    ) -> SubscriptionResult {
+        let amm_pair = AmmPair::new(asset0, asset1);
+        let mut amm_pool_states =
+            pin!(self.app.node.watch_amm_pool(amm_pair)?);
+        let sink = pending.accept().await?;
+        loop {
+            tokio::select! {
+                () = sink.closed() => break,
+                amm_pool_state = amm_pool_states.next() => {
+                    let Some(amm_pool_state) = amm_pool_state else {
+                        break;
+                    };
+                    let msg = SubscriptionMessage::from_json(&amm_pool_state?)?;
+                    if sink.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn subscribe_auction(
+        &self,
+        pending: PendingSubscriptionSink,
+        auction_id: DutchAuctionId,
+    ) -> SubscriptionResult {
+        // Ends once the auction has ended
+        let mut prices =
+            pin!(self.app.node.watch_dutch_auction_price(auction_id));
+        let sink = pending.accept().await?;
+        loop {
+            tokio::select! {
+                () = sink.closed() => break,
+                price = prices.next() => {
+                    let Some(price) = price else {
+                        break;
+                    };
+                    let msg = SubscriptionMessage::from_json(&price?)?;
+                    if sink.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn subscribe_block_events(
+        &self,
+        pending: PendingSubscriptionSink,
+    ) -> SubscriptionResult {
+        let mut block_events = pin!(self.app.node.block_events());
+        let sink = pending.accept().await?;
+        loop {
+            tokio::select! {
+                () = sink.closed() => break,
+                block_event = block_events.next() => {
+                    let block_event = match block_event {
+                        Some(Ok(block_event)) => block_event,
+                        Some(Err(err)) => {
+                            /* Events cannot be skipped without breaking the
+                             * ordering guarantee, so close the subscription
+                             * instead. */
+                            return Err(format!(
+                                "{err}; resubscribe and resync from the tip"
+                            )
+                            .into());
+                        }
+                        None => break,
+                    };
+                    let msg = SubscriptionMessage::from_json(&block_event)?;
+                    if sink.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn subscribe_reorgs(
+        &self,
+        pending: PendingSubscriptionSink,
+    ) -> SubscriptionResult {
+        let mut reorgs = pin!(self.app.node.watch_reorgs());
+        let sink = pending.accept().await?;
+        loop {
+            tokio::select! {
+                () = sink.closed() => break,
+                reorg = reorgs.next() => {
+                    let reorg = match reorg {
+                        Some(Ok(reorg)) => reorg,
+                        Some(Err(err)) => {
+                            /* A skipped reorg would leave the subscriber
+                             * with an incomplete set of affected blocks. */
+                            return Err(format!(
+                                "{err}; resubscribe and resync from the tip"
+                            )
+                            .into());
+                        }
+                        None => break,
+                    };
+                    let msg = SubscriptionMessage::from_json(&reorg)?;
+                    if sink.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 }
 
+/// `max_request_size` and `max_response_size` are the max sizes of request
+/// and response bodies, in bytes.
 pub async fn run_server(
     app: App,
     rpc_addr: SocketAddr,
+    max_request_size: u32,
+    max_response_size: u32,
 ) -> anyhow::Result<SocketAddr> {
-    let server = Server::builder().build(rpc_addr).await?;
+    let server = Server::builder()
+        .set_middleware(ServiceBuilder::new().layer(CompressionLayer))
+        .max_request_body_size(max_request_size)
+        .max_response_body_size(max_response_size)
+        .build(rpc_addr)
+        .await?;
 
     let addr = server.local_addr()?;
-    let handle = server.start(RpcServerImpl { app }.into_rpc());
+    let rpc_server = RpcServerImpl::new(app);
+    let mut module = RpcServer::into_rpc(rpc_server.clone());
+    module.merge(RpcSubscriptionsServer::into_rpc(rpc_server))?;
+    let handle = server.start(module);
 
     // In this example we don't care about doing shutdown so let's it run forever.
     // You may use the `ServerHandle` to shut it down or manage it yourself.
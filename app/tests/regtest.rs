@@ -1,6 +1,7 @@
 #![feature(impl_trait_in_assoc_type)]
 
 use std::{
+    collections::HashSet,
     ffi::OsString,
     net::{SocketAddr, TcpListener},
     ops::Deref,
@@ -11,16 +12,33 @@ use bip300301::{
     bitcoin::{Address as BitcoinAddress, Amount as BitcoinAmount},
     MainClient,
 };
-use futures::TryFutureExt;
-use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use fraction::Fraction;
+use futures::{StreamExt as _, TryFutureExt};
+use jsonrpsee::{
+    http_client::{HttpClient, HttpClientBuilder},
+    ws_client::WsClientBuilder,
+};
 use tempfile::tempdir;
 // Shadows #[test]
 use test_log::test;
 
-use plain_bitassets_app_rpc_api::RpcClient as BitAssetsClient;
+use plain_bitassets::{
+    state::UtxoSort,
+    types::{
+        Address, AssetId, BitAssetData, BitAssetId, BlockEvent,
+        FilledOutputContent, OutPoint, PointedOutput, TxStatus, Txid,
+    },
+};
+use plain_bitassets_app_rpc_api::{
+    BitAssetRegistration, DiagnosticStatus, ErrorCode, ErrorData,
+    RpcClient as BitAssetsClient, RpcSubscriptionsClient, TransferOutput,
+    NO_WALLET_SEED_ERROR_CODE, WALLET_LOCKED_ERROR_CODE,
+};
 
 const RPC_PASS: &str = "integrationtest";
 const RPC_USER: &str = "integrationtest";
+/// Max size of RPC request bodies for bitassetsd, in bytes (1 MiB)
+const BITASSETSD_RPC_MAX_REQUEST_SIZE: u32 = 1024 * 1024;
 
 #[repr(transparent)]
 struct BitAssetsdClient(HttpClient);
@@ -70,6 +88,9 @@ impl Deref for MainchaindClient {
     }
 }
 
+/// Mainchain confirmations required before the wallet will spend a deposit
+const DEPOSIT_MATURITY: u32 = 2;
+
 fn bitassetsd(
     data_dir: &Path,
     mainchaind_addr: SocketAddr,
@@ -81,12 +102,17 @@ fn bitassetsd(
         .arg(data_dir)
         .args(
             [
-                &["--headless"][..],
+                &["--deposit-maturity", &format!("{DEPOSIT_MATURITY}")][..],
+                &["--headless"],
                 &["--log-level", "DEBUG"],
                 &["--main-addr", &format!("{mainchaind_addr}")],
                 &["--password-main", RPC_PASS],
                 &["--user-main", RPC_USER],
                 &["--rpc-addr", &format!("{rpc_addr}")],
+                &[
+                    "--rpc-max-request-size",
+                    &format!("{BITASSETSD_RPC_MAX_REQUEST_SIZE}"),
+                ],
             ]
             .concat(),
         )
@@ -146,11 +172,13 @@ async fn mine_bitassets_block(
 ) -> anyhow::Result<()> {
     let block_count_before =
         BitAssetsClient::getblockcount(&**bitassetsd_client).await?;
-    let ((), ()) =
-        futures::try_join!(bitassetsd_client.mine(fee).err_into(), async {
+    let ((), ()) = futures::try_join!(
+        bitassetsd_client.mine(fee, None).err_into(),
+        async {
             tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
             mine_mainchain_blocks(mainchaind_client, mainchain_addr, 1).await
-        })?;
+        }
+    )?;
     let block_count_after =
         BitAssetsClient::getblockcount(&**bitassetsd_client).await?;
     let blocks_mined = block_count_after - block_count_before;
@@ -198,6 +226,80 @@ async fn regtest_test() -> anyhow::Result<()> {
     // Wait 10s to accomodate startup
     tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
 
+    /* Check RPC request size limit */
+    {
+        use jsonrpsee::{core::client::ClientT, rpc_params};
+        // Client limit must exceed the server limit, so that the request is
+        // sent to the server
+        let client = HttpClientBuilder::default()
+            .max_request_size(2 * BITASSETSD_RPC_MAX_REQUEST_SIZE)
+            .build(format!("http://{bitassets_socketaddr}"))?;
+        let padding = "0".repeat(BITASSETSD_RPC_MAX_REQUEST_SIZE as usize);
+        let res = client
+            .request::<u32, _>("getblockcount", rpc_params![padding])
+            .await;
+        match res {
+            Ok(_) => anyhow::bail!(
+                "Expected an over-limit RPC request to be rejected"
+            ),
+            Err(err) => {
+                tracing::debug!(%err, "over-limit RPC request rejected")
+            }
+        }
+        // Requests within the limit are still served
+        let _: u32 =
+            BitAssetsClient::getblockcount(&**bitassetsd_client).await?;
+    }
+
+    /* Check RPC response compression */
+    {
+        use hyper::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
+        use plain_bitassets_app_rpc_api::compression::Encoding;
+        const REQUEST: &str = r#"{
+            "jsonrpc": "2.0", "id": 0, "method": "openapi_schema", "params": []
+        }"#;
+        let client = hyper::Client::new();
+        let send_request = |encoding: Option<Encoding>| {
+            let mut request =
+                hyper::Request::post(format!("http://{bitassets_socketaddr}"))
+                    .header(CONTENT_TYPE, "application/json");
+            if let Some(encoding) = encoding {
+                request = request.header(ACCEPT_ENCODING, encoding.as_str());
+            }
+            anyhow::Ok(client.request(request.body(REQUEST.into())?))
+        };
+        let response = send_request(None)?.await?;
+        anyhow::ensure!(
+            !response.headers().contains_key(CONTENT_ENCODING),
+            "Expected an uncompressed response"
+        );
+        let payload = hyper::body::to_bytes(response.into_body()).await?;
+        for encoding in [Encoding::Gzip, Encoding::Deflate] {
+            let response = send_request(Some(encoding))?.await?;
+            anyhow::ensure!(
+                response
+                    .headers()
+                    .get(CONTENT_ENCODING)
+                    .is_some_and(|value| value == encoding.as_str()),
+                "Expected a {} compressed response",
+                encoding.as_str()
+            );
+            let compressed =
+                hyper::body::to_bytes(response.into_body()).await?;
+            anyhow::ensure!(
+                encoding.decode(&compressed, payload.len())? == payload,
+                "Expected {} compressed response to decode to the same \
+                 payload",
+                encoding.as_str()
+            );
+            anyhow::ensure!(
+                encoding.decode(&compressed, payload.len() - 1).is_err(),
+                "Expected {} compressed response to exceed a smaller limit",
+                encoding.as_str()
+            );
+        }
+    }
+
     /* Initialize Mainchain */
 
     let mainchain_addr = mainchaind_client
@@ -288,12 +390,27 @@ async fn regtest_test() -> anyhow::Result<()> {
     }
 
     /* Initialize BitAssets */
+    // Check that wallet-dependent methods fail without a seed
+    match bitassetsd_client.get_new_address().await {
+        Err(jsonrpsee::core::Error::Call(err))
+            if err.code() == NO_WALLET_SEED_ERROR_CODE => {}
+        res => anyhow::bail!(
+            "Expected no wallet seed error from `get_new_address`, \
+             but got {res:?}"
+        ),
+    }
     // Set a mnemonic seed
     {
         let mnemonic_seed = bitassetsd_client.generate_mnemonic().await?;
         let () = bitassetsd_client
-            .set_seed_from_mnemonic(mnemonic_seed)
+            .set_seed_from_mnemonic(mnemonic_seed.clone(), None)
             .await?;
+        // Check that the mnemonic can be revealed
+        let revealed_mnemonic = bitassetsd_client.reveal_mnemonic().await?;
+        anyhow::ensure!(
+            revealed_mnemonic == mnemonic_seed,
+            "Revealed mnemonic does not match the mnemonic that was set"
+        );
     }
     // Generate addresses
     let bitassets_addr = bitassetsd_client.get_new_address().await?;
@@ -306,6 +423,15 @@ async fn regtest_test() -> anyhow::Result<()> {
             BitAssetsClient::getblockcount(&*bitassetsd_client).await?;
         assert_eq!(block_count, 0, "Initial BitAssets block count should be 0");
     }
+    // Check that no diagnostic checks fail on a fresh node.
+    // Warnings are expected, since a fresh node has no peers.
+    {
+        let diagnostics = bitassetsd_client.run_diagnostics().await?;
+        anyhow::ensure!(
+            diagnostics.status() != DiagnosticStatus::Fail,
+            "Expected no failed diagnostic checks, but got {diagnostics:#?}"
+        );
+    }
     // Mine a block
     let () = mine_bitassets_block(
         &bitassetsd_client,
@@ -366,6 +492,923 @@ async fn regtest_test() -> anyhow::Result<()> {
         let balance = bitassetsd_client.bitcoin_balance().await?;
         anyhow::ensure!(balance > 0, "Expected positive balance");
     }
+    // The deposit has one mainchain confirmation, so is not yet spendable
+    {
+        let maturing_deposits =
+            bitassetsd_client.list_maturing_deposits().await?;
+        anyhow::ensure!(
+            matches!(
+                maturing_deposits.as_slice(),
+                [(OutPoint::Deposit(_), remaining)]
+                    if *remaining == DEPOSIT_MATURITY - 1
+            ),
+            "Expected a deposit with {} confirmation(s) remaining, but got \
+             {maturing_deposits:?}",
+            DEPOSIT_MATURITY - 1
+        );
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+        let maturing_deposits =
+            bitassetsd_client.list_maturing_deposits().await?;
+        anyhow::ensure!(
+            maturing_deposits.is_empty(),
+            "Expected the deposit to be spendable, but got \
+             {maturing_deposits:?}"
+        );
+    }
+    // Verify that the deposit block was recorded
+    {
+        let deposit_blocks = bitassetsd_client.get_deposit_blocks().await?;
+        anyhow::ensure!(
+            !deposit_blocks.is_empty(),
+            "Expected at least 1 deposit block"
+        );
+    }
+    // Check that paginating UTXOs covers the full UTXO set, without gaps or
+    // duplicates
+    for sort in [UtxoSort::Outpoint, UtxoSort::Value] {
+        let utxos: HashSet<_> = bitassetsd_client
+            .list_utxos()
+            .await?
+            .into_iter()
+            .map(|utxo| utxo.outpoint)
+            .collect();
+        let mut paginated_utxos = Vec::new();
+        loop {
+            let page = bitassetsd_client
+                .list_utxos_paginated(paginated_utxos.len(), 1, None, sort)
+                .await?;
+            if page.is_empty() {
+                break;
+            }
+            paginated_utxos.extend(page.into_iter().map(|utxo| utxo.outpoint));
+        }
+        anyhow::ensure!(
+            paginated_utxos.len() == utxos.len()
+                && paginated_utxos.iter().collect::<HashSet<_>>()
+                    == utxos.iter().collect(),
+            "Paginated UTXOs do not match the UTXO set"
+        );
+    }
+    // Check that UTXO counts match the UTXO set after mining
+    {
+        let utxos = bitassetsd_client.list_utxos().await?;
+        let count = bitassetsd_client.count_utxos().await?;
+        anyhow::ensure!(
+            count == utxos.len() as u64,
+            "Expected {} UTXOs, but counted {count}",
+            utxos.len()
+        );
+        let bitcoin_utxos = utxos
+            .iter()
+            .filter(|utxo| utxo.output.content.is_bitcoin())
+            .count();
+        let bitcoin_count = bitassetsd_client
+            .count_utxos_by_asset(AssetId::Bitcoin)
+            .await?;
+        anyhow::ensure!(
+            bitcoin_count == bitcoin_utxos as u64,
+            "Expected {bitcoin_utxos} Bitcoin UTXOs, but counted \
+             {bitcoin_count}"
+        );
+    }
+
+    /* BitAsset registration */
+
+    const BITASSET_NAME: &str = "integrationtest";
+    const BITASSET_INITIAL_SUPPLY: u64 = 1_000;
+    let bitasset_id = BitAssetId(blake3::hash(BITASSET_NAME.as_bytes()).into());
+    let registration = BitAssetRegistration {
+        plain_name: BITASSET_NAME.to_owned(),
+        initial_supply: BITASSET_INITIAL_SUPPLY,
+        bitasset_data: BitAssetData::default(),
+    };
+    // The first call reserves the BitAsset, and the second registers it once
+    // the reservation is confirmed
+    for _ in 0..2 {
+        let _txids = bitassetsd_client
+            .register_bitassets_bulk(vec![registration.clone()])
+            .await?;
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+    }
+    {
+        let bitassets = bitassetsd_client.bitassets().await?;
+        anyhow::ensure!(
+            bitassets.iter().any(|(_, id, _)| *id == bitasset_id),
+            "Expected BitAsset `{BITASSET_NAME}` to be registered"
+        );
+    }
+
+    /* Create a token in one step */
+    {
+        const TOKEN_NAME: &str = "integrationtesttoken";
+        const TOKEN_INITIAL_SUPPLY: u64 = 21_000_000;
+        let token_id = BitAssetId(blake3::hash(TOKEN_NAME.as_bytes()).into());
+        // `create_token` waits for the reservation to be confirmed, so mine
+        // a block once the reservation has been sent
+        let (_txid, ()) = futures::try_join!(
+            bitassetsd_client
+                .create_token(
+                    TOKEN_NAME.to_owned(),
+                    TOKEN_INITIAL_SUPPLY,
+                    BitAssetData::default()
+                )
+                .err_into(),
+            async {
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                mine_bitassets_block(
+                    &bitassetsd_client,
+                    &mainchaind_client,
+                    &mainchain_addr,
+                    Some(DEFAULT_TX_FEE.to_sat()),
+                )
+                .await
+            }
+        )?;
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+        let bitassets = bitassetsd_client.bitassets().await?;
+        anyhow::ensure!(
+            bitassets.iter().any(|(_, id, _)| *id == token_id),
+            "Expected token `{TOKEN_NAME}` to be registered"
+        );
+        let token_supply: u64 = bitassetsd_client
+            .list_utxos()
+            .await?
+            .iter()
+            .filter_map(|utxo| utxo.output.content.bitasset_value())
+            .filter(|(id, _)| *id == token_id)
+            .map(|(_, value)| value)
+            .sum();
+        anyhow::ensure!(
+            token_supply == TOKEN_INITIAL_SUPPLY,
+            "Expected a supply of {TOKEN_INITIAL_SUPPLY}, but got \
+             {token_supply}"
+        );
+    }
+
+    /* AMM LP token identifiers */
+    {
+        let token_id =
+            BitAssetId(blake3::hash("integrationtesttoken".as_bytes()).into());
+        // Specify the pair in non-canonical order, to check that the LP
+        // token id does not depend on the order
+        let (asset0, asset1) = if AssetId::Bitcoin < AssetId::BitAsset(token_id)
+        {
+            (AssetId::BitAsset(token_id), AssetId::Bitcoin)
+        } else {
+            (AssetId::Bitcoin, AssetId::BitAsset(token_id))
+        };
+        let lp_token_id =
+            bitassetsd_client.get_lp_token_id(asset0, asset1).await?;
+        anyhow::ensure!(
+            lp_token_id
+                == bitassetsd_client.get_lp_token_id(asset1, asset0).await?,
+            "Expected LP token id to be the same for either order of assets"
+        );
+        let _txid = bitassetsd_client
+            .amm_mint(asset0, asset1, 10_000, 10_000)
+            .await?;
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+        let utxos = bitassetsd_client.list_utxos().await?;
+        let lp_token_ids: Vec<_> = utxos
+            .iter()
+            .filter_map(|utxo| match utxo.output.content {
+                FilledOutputContent::AmmLpToken { asset0, asset1, .. } => {
+                    Some((asset0, asset1))
+                }
+                _ => None,
+            })
+            .collect();
+        anyhow::ensure!(
+            !lp_token_ids.is_empty()
+                && lp_token_ids.iter().all(|id| *id == lp_token_id),
+            "Expected minted LP tokens to have id {lp_token_id:?}, but got \
+             {lp_token_ids:?}"
+        );
+        // The new pool is listed, with its assets in ascending order
+        let amm_pools = bitassetsd_client.amm_pools().await?;
+        anyhow::ensure!(
+            amm_pools
+                .iter()
+                .any(|(asset0, asset1, _)| lp_token_id == (*asset0, *asset1)),
+            "Expected pool {lp_token_id:?} to be listed, but got {amm_pools:?}"
+        );
+        // Wallet balances include the LP tokens and the token
+        let balances = bitassetsd_client.asset_balances().await?;
+        anyhow::ensure!(
+            balances.lp_tokens.iter().any(|lp_token_balance| {
+                (lp_token_balance.asset0, lp_token_balance.asset1)
+                    == lp_token_id
+                    && lp_token_balance.balance.confirmed > 0
+            }),
+            "Expected a confirmed balance of LP token {lp_token_id:?}, but \
+             got {balances:?}"
+        );
+        anyhow::ensure!(
+            balances
+                .assets
+                .get(&AssetId::BitAsset(token_id))
+                .is_some_and(|balance| balance.confirmed > 0),
+            "Expected a confirmed balance of {token_id:?}, but got \
+             {balances:?}"
+        );
+    }
+
+    /* Estimate AMM swap */
+    {
+        const AMOUNT_SPEND: u64 = 1_000;
+        let token_id =
+            BitAssetId(blake3::hash("integrationtesttoken".as_bytes()).into());
+        let estimate = bitassetsd_client
+            .amm_swap_estimate(
+                AssetId::Bitcoin,
+                AssetId::BitAsset(token_id),
+                AMOUNT_SPEND,
+            )
+            .await?;
+        // The pool has equal reserves, so fewer units are received than
+        // are spent
+        anyhow::ensure!(
+            estimate.amount_receive > 0
+                && estimate.amount_receive < AMOUNT_SPEND,
+            "Expected to receive between 0 and {AMOUNT_SPEND}, but got {}",
+            estimate.amount_receive
+        );
+        anyhow::ensure!(
+            estimate.price_impact > Fraction::from(0)
+                && estimate.price_impact < Fraction::from(1),
+            "Expected a price impact between 0 and 1, but got {}",
+            estimate.price_impact
+        );
+        let price_impact = bitassetsd_client
+            .get_amm_price_impact(
+                AssetId::Bitcoin,
+                AssetId::BitAsset(token_id),
+                AMOUNT_SPEND,
+            )
+            .await?;
+        anyhow::ensure!(
+            price_impact == Some(estimate.price_impact),
+            "Expected a price impact of {}, but got {price_impact:?}",
+            estimate.price_impact
+        );
+        // Swaps that would receive less than the minimum are not submitted
+        anyhow::ensure!(
+            bitassetsd_client
+                .amm_swap(
+                    AssetId::Bitcoin,
+                    AssetId::BitAsset(token_id),
+                    AMOUNT_SPEND,
+                    Some(estimate.amount_receive + 1),
+                    None,
+                )
+                .await
+                .is_err(),
+            "Expected a swap below the minimum amount to receive to fail"
+        );
+        // Estimating a swap against a nonexistent pool fails
+        let missing_token_id = BitAssetId([0x45; 32]);
+        anyhow::ensure!(
+            bitassetsd_client
+                .amm_swap_estimate(
+                    AssetId::Bitcoin,
+                    AssetId::BitAsset(missing_token_id),
+                    AMOUNT_SPEND,
+                )
+                .await
+                .is_err(),
+            "Expected estimating a swap against a nonexistent pool to fail"
+        );
+        // Spending `AMOUNT_SPEND` is enough to receive the estimated amount
+        let amount_spend = bitassetsd_client
+            .amm_swap_exact_output(
+                AssetId::Bitcoin,
+                AssetId::BitAsset(token_id),
+                estimate.amount_receive,
+            )
+            .await?;
+        anyhow::ensure!(
+            amount_spend <= AMOUNT_SPEND,
+            "Expected to spend at most {AMOUNT_SPEND} to receive {}, but \
+             spent {amount_spend}",
+            estimate.amount_receive
+        );
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+        // Swaps for more than the pool reserves fail
+        anyhow::ensure!(
+            bitassetsd_client
+                .amm_swap_exact_output(
+                    AssetId::Bitcoin,
+                    AssetId::BitAsset(token_id),
+                    u64::MAX,
+                )
+                .await
+                .is_err(),
+            "Expected a swap for more than the pool reserves to fail"
+        );
+    }
+
+    /* Transfer Bitcoin and a BitAsset in one tx */
+    {
+        const BITCOIN_AMOUNT: u64 = 5_000;
+        const TOKEN_AMOUNT: u64 = 500;
+        let token_id =
+            BitAssetId(blake3::hash("integrationtesttoken".as_bytes()).into());
+        let bitcoin_recipient = Address::from([0x43; 20]);
+        let token_recipient = Address::from([0x44; 20]);
+        let txid = bitassetsd_client
+            .build_transfer(
+                vec![
+                    TransferOutput {
+                        address: bitcoin_recipient,
+                        asset: AssetId::Bitcoin,
+                        amount: BITCOIN_AMOUNT,
+                    },
+                    TransferOutput {
+                        address: token_recipient,
+                        asset: AssetId::BitAsset(token_id),
+                        amount: TOKEN_AMOUNT,
+                    },
+                ],
+                DEFAULT_TX_FEE.to_sat(),
+            )
+            .await?;
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+        let is_created_by_tx = |outpoint: &OutPoint| {
+            matches!(outpoint, OutPoint::Regular { txid: utxo_txid, .. }
+                if *utxo_txid == txid)
+        };
+        let utxos = bitassetsd_client.list_utxos().await?;
+        anyhow::ensure!(
+            utxos.iter().any(|utxo| {
+                is_created_by_tx(&utxo.outpoint)
+                    && utxo.output.address == bitcoin_recipient
+                    && utxo.output.content.asset_value()
+                        == Some((AssetId::Bitcoin, BITCOIN_AMOUNT))
+            }),
+            "Expected {BITCOIN_AMOUNT} sats to be sent to {bitcoin_recipient}"
+        );
+        anyhow::ensure!(
+            utxos.iter().any(|utxo| {
+                is_created_by_tx(&utxo.outpoint)
+                    && utxo.output.address == token_recipient
+                    && utxo.output.content.bitasset_value()
+                        == Some((token_id, TOKEN_AMOUNT))
+            }),
+            "Expected {TOKEN_AMOUNT} units to be sent to {token_recipient}"
+        );
+        let token_utxos = bitassetsd_client
+            .list_utxos_by_asset(AssetId::BitAsset(token_id))
+            .await?;
+        anyhow::ensure!(
+            token_utxos.iter().all(|utxo| {
+                utxo.output.content.bitasset_value().map(|(id, _)| id)
+                    == Some(token_id)
+            }) && token_utxos.iter().any(|utxo| {
+                is_created_by_tx(&utxo.outpoint)
+                    && utxo.output.address == token_recipient
+            }),
+            "Expected only token UTXOs, including the one sent to \
+             {token_recipient}"
+        );
+        let bitcoin_utxos = bitassetsd_client
+            .list_utxos_by_asset(AssetId::Bitcoin)
+            .await?;
+        anyhow::ensure!(
+            bitcoin_utxos
+                == utxos
+                    .iter()
+                    .filter(|utxo| utxo.output.content.is_bitcoin())
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            "Expected the Bitcoin UTXOs listed by asset to match `list_utxos`"
+        );
+        // Check the txs reported for the block that included the transfer
+        let Some(txin) = bitassetsd_client
+            .get_transaction_info(txid)
+            .await?
+            .and_then(|tx_info| tx_info.txin)
+        else {
+            anyhow::bail!("Expected {txid} to be included in a block")
+        };
+        let block_txs = bitassetsd_client
+            .get_block_transactions(txin.block_hash)
+            .await?;
+        anyhow::ensure!(
+            block_txs
+                .iter()
+                .all(|(txid, tx)| *txid == tx.transaction.txid()),
+            "Expected txids to match the txs in the block"
+        );
+        anyhow::ensure!(
+            block_txs.get(txin.idx as usize).map(|(txid, _)| *txid)
+                == Some(txid),
+            "Expected {txid} at index {} in the block txs",
+            txin.idx
+        );
+        // Connected blocks should have a recorded receive time
+        anyhow::ensure!(
+            bitassetsd_client
+                .get_block_receive_time(txin.block_hash)
+                .await?
+                .is_some(),
+            "Expected a receive time for block {}",
+            txin.block_hash
+        );
+        // Check the status of the confirmed transfer
+        let block_height =
+            bitassetsd_client.get_block(txin.block_hash).await?.height;
+        let status = bitassetsd_client.get_transaction_status(txid).await?;
+        anyhow::ensure!(
+            matches!(
+                status,
+                TxStatus::Confirmed { height, .. } if height == block_height
+            ),
+            "Expected {txid} to be confirmed at height {block_height}, \
+             but got {status:?}"
+        );
+        let unknown_txid = Txid([0xff; 32]);
+        let status = bitassetsd_client
+            .get_transaction_status(unknown_txid)
+            .await?;
+        anyhow::ensure!(
+            status == TxStatus::Unknown,
+            "Expected unknown status for {unknown_txid}, but got {status:?}"
+        );
+        // Fetch existing and missing txs in one call
+        let txs = bitassetsd_client
+            .get_transactions_batch(vec![txid, unknown_txid, txid])
+            .await?;
+        anyhow::ensure!(
+            matches!(
+                txs.as_slice(),
+                [Some(tx0), None, Some(tx2)]
+                    if tx0.txid() == txid && tx2.txid() == txid
+            ),
+            "Expected batch of [{txid}, missing, {txid}], but got {txs:?}"
+        );
+    }
+
+    /* Block events */
+    {
+        let ws_client = WsClientBuilder::default()
+            .build(format!("ws://{bitassets_socketaddr}"))
+            .await?;
+        let mut block_events = ws_client.subscribe_block_events().await?;
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            None,
+        )
+        .await?;
+        let block_count =
+            BitAssetsClient::getblockcount(&**bitassetsd_client).await?;
+        let block_event = tokio::time::timeout(
+            tokio::time::Duration::from_secs(10),
+            block_events.next(),
+        )
+        .await?;
+        match block_event {
+            Some(Ok(BlockEvent::Connected(block)))
+                if block.height == block_count => {}
+            res => anyhow::bail!(
+                "Expected a connected block event at height {block_count}, \
+                 but got {res:?}"
+            ),
+        }
+        let () = block_events.unsubscribe().await?;
+    }
+
+    /* Wait for block */
+    {
+        let block_count =
+            BitAssetsClient::getblockcount(&**bitassetsd_client).await?;
+        // Returns immediately if the block count already exceeds the height
+        let res = tokio::time::timeout(
+            tokio::time::Duration::from_secs(5),
+            bitassetsd_client.wait_for_block(block_count - 1, Some(60)),
+        )
+        .await??;
+        anyhow::ensure!(
+            res == block_count,
+            "Expected wait_for_block to return {block_count} immediately, \
+             but got {res}"
+        );
+        // Returns the current block count on timeout
+        let res = bitassetsd_client
+            .wait_for_block(block_count, Some(1))
+            .await?;
+        anyhow::ensure!(
+            res == block_count,
+            "Expected wait_for_block to time out with {block_count}, \
+             but got {res}"
+        );
+        // Returns once a block is mined
+        let (res, ()) = futures::try_join!(
+            bitassetsd_client
+                .wait_for_block(block_count, Some(60))
+                .err_into(),
+            mine_bitassets_block(
+                &bitassetsd_client,
+                &mainchaind_client,
+                &mainchain_addr,
+                None,
+            ),
+        )?;
+        anyhow::ensure!(
+            res == block_count + 1,
+            "Expected wait_for_block to return {}, but got {res}",
+            block_count + 1
+        );
+    }
+
+    /* Headers */
+    {
+        let block_count =
+            BitAssetsClient::getblockcount(&**bitassetsd_client).await?;
+        let headers = bitassetsd_client.get_headers(1, u32::MAX).await?;
+        anyhow::ensure!(
+            headers.len() == block_count as usize,
+            "Expected {block_count} headers, but got {}",
+            headers.len()
+        );
+        anyhow::ensure!(
+            headers
+                .windows(2)
+                .all(|pair| pair[1].prev_side_hash == pair[0].hash()),
+            "Expected headers to be linked by `prev_side_hash`"
+        );
+        let tip_headers =
+            bitassetsd_client.get_headers(block_count, 10).await?;
+        anyhow::ensure!(
+            tip_headers.as_slice() == &headers[headers.len() - 1..],
+            "Expected only the tip header from the tip height"
+        );
+    }
+
+    /* Balance at height */
+    {
+        const AMOUNT: u64 = 1_000;
+        let recipient = Address::from([0x45; 20]);
+        let height =
+            BitAssetsClient::getblockcount(&**bitassetsd_client).await?;
+        let balance = bitassetsd_client.bitcoin_balance().await?;
+        let _txid = bitassetsd_client
+            .transfer(recipient, AMOUNT, DEFAULT_TX_FEE.to_sat(), None, None)
+            .await?;
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+        let mut historical_balance = 0;
+        let mut tip_balance = 0;
+        for address in bitassetsd_client.get_wallet_addresses().await? {
+            historical_balance += bitassetsd_client
+                .get_balance_at_height(address, height)
+                .await?;
+            tip_balance += bitassetsd_client
+                .get_balance_at_height(address, height + 1)
+                .await?;
+        }
+        // The historical balance is unaffected by the spend
+        anyhow::ensure!(
+            historical_balance == balance,
+            "Expected a balance of {balance} at height {height}, but got \
+             {historical_balance}"
+        );
+        let live_balance = bitassetsd_client.bitcoin_balance().await?;
+        anyhow::ensure!(
+            tip_balance == live_balance && live_balance != balance,
+            "Expected a balance of {live_balance} at the tip, but got \
+             {tip_balance}"
+        );
+        anyhow::ensure!(
+            bitassetsd_client
+                .get_balance_at_height(recipient, height)
+                .await?
+                == 0
+                && bitassetsd_client
+                    .get_balance_at_height(recipient, height + 1)
+                    .await?
+                    == AMOUNT,
+            "Expected {recipient} to receive {AMOUNT} at height {}",
+            height + 1
+        );
+        // Heights above the tip are rejected
+        anyhow::ensure!(
+            bitassetsd_client
+                .get_balance_at_height(recipient, height + 2)
+                .await
+                .is_err(),
+            "Expected a height above the tip to be rejected"
+        );
+    }
+
+    /* Split BitAsset control coin */
+    {
+        const UNITS_AMOUNT: u64 = 400;
+        let control_dest = bitassetsd_client.get_new_address().await?;
+        let units_dest = bitassetsd_client.get_new_address().await?;
+        let _txid = bitassetsd_client
+            .split_bitasset_control(
+                bitasset_id,
+                control_dest,
+                units_dest,
+                UNITS_AMOUNT,
+                DEFAULT_TX_FEE.to_sat(),
+            )
+            .await?;
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+        let utxos = bitassetsd_client.list_utxos().await?;
+        anyhow::ensure!(
+            utxos.iter().any(|utxo| {
+                utxo.output.address == control_dest
+                    && utxo.output.content.is_bitasset_control()
+                    && utxo.output.content.get_bitasset() == Some(bitasset_id)
+            }),
+            "Expected control coin to be sent to {control_dest}"
+        );
+        anyhow::ensure!(
+            utxos.iter().any(|utxo| {
+                utxo.output.address == units_dest
+                    && utxo.output.content.bitasset_value()
+                        == Some((bitasset_id, UNITS_AMOUNT))
+            }),
+            "Expected {UNITS_AMOUNT} units to be sent to {units_dest}"
+        );
+    }
+
+    /* Transfer BitAsset control coin */
+    {
+        const MINT_AMOUNT: u64 = 100;
+        // Transferring to a wallet address makes the wallet the new owner,
+        // which can then mint
+        let new_owner_addr = bitassetsd_client.get_new_address().await?;
+        let _txid = bitassetsd_client
+            .transfer_bitasset_control(
+                bitasset_id,
+                new_owner_addr,
+                DEFAULT_TX_FEE.to_sat(),
+            )
+            .await?;
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+        let _txid = bitassetsd_client
+            .mint_bitasset(bitasset_id, MINT_AMOUNT, DEFAULT_TX_FEE.to_sat())
+            .await?;
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+        let total_supply: u64 = bitassetsd_client
+            .list_utxos()
+            .await?
+            .iter()
+            .filter_map(|utxo| utxo.output.content.bitasset_value())
+            .filter(|(id, _)| *id == bitasset_id)
+            .map(|(_, value)| value)
+            .sum();
+        anyhow::ensure!(
+            total_supply == BITASSET_INITIAL_SUPPLY + MINT_AMOUNT,
+            "Expected a total supply of {}, but got {total_supply}",
+            BITASSET_INITIAL_SUPPLY + MINT_AMOUNT
+        );
+        // After transferring to an address outside of the wallet, the wallet
+        // can no longer mint
+        let external_addr = Address::from([0x42; 20]);
+        let _txid = bitassetsd_client
+            .transfer_bitasset_control(
+                bitasset_id,
+                external_addr,
+                DEFAULT_TX_FEE.to_sat(),
+            )
+            .await?;
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+        let utxos = bitassetsd_client.list_utxos().await?;
+        anyhow::ensure!(
+            utxos.iter().any(|utxo| {
+                utxo.output.address == external_addr
+                    && utxo.output.content.is_bitasset_control()
+                    && utxo.output.content.get_bitasset() == Some(bitasset_id)
+            }),
+            "Expected control coin to be sent to {external_addr}"
+        );
+        if let Ok(txid) = bitassetsd_client
+            .mint_bitasset(bitasset_id, MINT_AMOUNT, DEFAULT_TX_FEE.to_sat())
+            .await
+        {
+            anyhow::bail!(
+                "Expected minting to fail after transferring the control \
+                 coin, but sent {txid}"
+            )
+        }
+    }
+
+    /* UTXO list ordering */
+    {
+        let utxo_outpoints =
+            |utxos: &[PointedOutput<FilledOutputContent>]| {
+                utxos.iter().map(|utxo| utxo.outpoint).collect::<Vec<_>>()
+            };
+        let utxos = utxo_outpoints(&bitassetsd_client.list_utxos().await?);
+        anyhow::ensure!(
+            utxos.is_sorted(),
+            "Expected UTXOs to be ordered by outpoint"
+        );
+        anyhow::ensure!(
+            utxos == utxo_outpoints(&bitassetsd_client.list_utxos().await?),
+            "Expected UTXOs to be listed in the same order by each call"
+        );
+        let my_utxos = utxo_outpoints(&bitassetsd_client.my_utxos().await?);
+        anyhow::ensure!(
+            my_utxos.is_sorted(),
+            "Expected owned UTXOs to be ordered by outpoint"
+        );
+        anyhow::ensure!(
+            my_utxos == utxo_outpoints(&bitassetsd_client.my_utxos().await?),
+            "Expected owned UTXOs to be listed in the same order by each call"
+        );
+    }
+
+    /* Auto-mine */
+    {
+        let block_count =
+            BitAssetsClient::getblockcount(&**bitassetsd_client).await?;
+        let () = bitassetsd_client.auto_mine(None).await?;
+        let txid = bitassetsd_client
+            .transfer(bitassets_addr, 1000, DEFAULT_TX_FEE.to_sat(), None, None)
+            .await?;
+        // The tx is mined without calling `mine`
+        let new_block_count = bitassetsd_client
+            .wait_for_block(block_count, Some(60))
+            .await?;
+        let () = bitassetsd_client.stop_auto_mine().await?;
+        anyhow::ensure!(
+            new_block_count > block_count,
+            "Expected a block to be auto-mined after {block_count}"
+        );
+        let status = bitassetsd_client.get_transaction_status(txid).await?;
+        anyhow::ensure!(
+            matches!(status, TxStatus::Confirmed { .. }),
+            "Expected {txid} to be auto-mined, but got {status:?}"
+        );
+    }
+
+    /* Mempool expiry */
+    {
+        let height =
+            BitAssetsClient::getblockcount(&**bitassetsd_client).await?;
+        anyhow::ensure!(height > 0, "Expected at least one block");
+        match bitassetsd_client
+            .transfer(
+                bitassets_addr,
+                1000,
+                DEFAULT_TX_FEE.to_sat(),
+                None,
+                Some(height - 1),
+            )
+            .await
+        {
+            Err(jsonrpsee::core::Error::Call(err))
+                if err.code() == ErrorCode::TxExpired.code() =>
+            {
+                // Check that the error data identifies the error
+                let data: Option<ErrorData> = err
+                    .data()
+                    .map(|data| serde_json::from_str(data.get()))
+                    .transpose()?;
+                anyhow::ensure!(
+                    data.map(|data| data.kind) == Some(ErrorCode::TxExpired),
+                    "Expected tx expired error data"
+                );
+            }
+            res => anyhow::bail!(
+                "Expected a transfer with an expiry height below the next \
+                 block height to fail with a tx expired error, but got \
+                 {res:?}"
+            ),
+        }
+    }
+
+    /* Audit log */
+    {
+        let txid = bitassetsd_client
+            .transfer(bitassets_addr, 1000, DEFAULT_TX_FEE.to_sat(), None, None)
+            .await?;
+        // The transfer is the most recent mutating call, preceded by the
+        // expired transfer
+        let entries = bitassetsd_client.get_rpc_audit_log(2).await?;
+        let [transfer_entry, expired_entry] = entries.as_slice() else {
+            anyhow::bail!("Expected 2 audit log entries, but got {entries:?}")
+        };
+        anyhow::ensure!(
+            transfer_entry.method == "transfer"
+                && transfer_entry.result == Some(serde_json::to_string(&txid)?)
+                && transfer_entry.error.is_none(),
+            "Expected an audit log entry for transfer {txid}, but got \
+             {transfer_entry:?}"
+        );
+        anyhow::ensure!(
+            expired_entry.method == "transfer"
+                && expired_entry.result.is_none()
+                && expired_entry.error.is_some()
+                && expired_entry.params_digest != transfer_entry.params_digest,
+            "Expected an audit log entry for the expired transfer, but got \
+             {expired_entry:?}"
+        );
+        let () = mine_bitassets_block(
+            &bitassetsd_client,
+            &mainchaind_client,
+            &mainchain_addr,
+            Some(DEFAULT_TX_FEE.to_sat()),
+        )
+        .await?;
+    }
+
+    /* Wallet encryption */
+
+    const WALLET_PASSPHRASE: &str = "integrationtest";
+    let () = bitassetsd_client
+        .encrypt_wallet(WALLET_PASSPHRASE.to_owned())
+        .await?;
+    // Check that signing fails while the wallet is locked
+    match bitassetsd_client
+        .transfer(bitassets_addr, 1000, DEFAULT_TX_FEE.to_sat(), None, None)
+        .await
+    {
+        Err(jsonrpsee::core::Error::Call(err))
+            if err.code() == WALLET_LOCKED_ERROR_CODE => {}
+        res => anyhow::bail!(
+            "Expected wallet locked error from `transfer`, but got {res:?}"
+        ),
+    }
+    // Check that signing succeeds once the wallet is unlocked
+    let () = bitassetsd_client
+        .unlock_wallet(WALLET_PASSPHRASE.to_owned(), None)
+        .await?;
+    let _txid = bitassetsd_client
+        .transfer(bitassets_addr, 1000, DEFAULT_TX_FEE.to_sat(), None, None)
+        .await?;
+    let () = bitassetsd_client.lock_wallet().await?;
 
     /* Clean up */
     {
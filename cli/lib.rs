@@ -1,13 +1,24 @@
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
 
 use bip300301::bitcoin;
 use clap::{Parser, Subcommand};
-use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use jsonrpsee::http_client::HttpClientBuilder;
 use plain_bitassets::{
     node::THIS_SIDECHAIN,
-    types::{Address, AssetId, BlockHash, DutchAuctionId, DutchAuctionParams},
+    state::UtxoSort,
+    types::{
+        Address, AssetId, AtomicSwapId, BitAssetData, BitAssetId, BlockHash,
+        DutchAuctionId, DutchAuctionParams, OrderId, OutPoint, Txid,
+    },
+};
+use plain_bitassets_app_rpc_api::{
+    compression::DecompressionLayer, RpcClient, NO_WALLET_SEED_ERROR_CODE,
+    WALLET_LOCKED_ERROR_CODE,
 };
-use plain_bitassets_app_rpc_api::RpcClient;
+use tower::ServiceBuilder;
 
 #[derive(Clone, Debug, Subcommand)]
 #[command(arg_required_else_help(true))]
@@ -29,6 +40,8 @@ pub enum Command {
         #[arg(long)]
         amount1: u64,
     },
+    /// List all AMM pools
+    AmmPools,
     /// Returns the amount of `asset_receive` to receive
     AmmSwap {
         #[arg(long)]
@@ -37,19 +50,132 @@ pub enum Command {
         asset_receive: AssetId,
         #[arg(long)]
         amount_spend: u64,
+        /// Do not submit the swap if the amount to receive is less than this
+        #[arg(long = "min-receive")]
+        min_amount_receive: Option<u64>,
+        /// Drop the swap tx from the mempool if it is not included in a
+        /// block at or below this height
+        #[arg(long)]
+        expiry_height: Option<u32>,
+    },
+    /// Estimate the amount of `asset_receive` to receive, and the price
+    /// impact of an AMM swap, without submitting a tx
+    AmmSwapEstimate {
+        #[arg(long)]
+        asset_spend: AssetId,
+        #[arg(long)]
+        asset_receive: AssetId,
+        #[arg(long)]
+        amount_spend: u64,
+    },
+    /// Swap to receive an exact amount of `asset_receive`.
+    /// Returns the amount of `asset_spend` to spend
+    AmmSwapExactOutput {
+        #[arg(long)]
+        asset_spend: AssetId,
+        #[arg(long)]
+        asset_receive: AssetId,
+        #[arg(long)]
+        amount_receive: u64,
+    },
+    /// Get the balances of all assets held by the wallet, with confirmed and
+    /// unconfirmed subtotals
+    AssetBalances,
+    /// List all atomic swaps
+    AtomicSwaps,
+    /// Mine blocks automatically, for local development on regtest.
+    /// If `interval-secs` is set, a block is mined at that interval.
+    /// Otherwise, a block is mined whenever the mempool is non-empty.
+    AutoMine {
+        #[arg(long)]
+        interval_secs: Option<u64>,
     },
     /// List all BitAssets
     Bitassets,
     /// Get Bitcoin balance in sats
     BitcoinBalance,
+    /// Transfer several assets, eg. Bitcoin and BitAssets, to several
+    /// recipients in a single tx
+    BuildTransfer {
+        /// Path to a JSON file containing an array of outputs, each with
+        /// `address`, `asset`, and `amount` fields
+        outputs_file: PathBuf,
+        #[arg(long)]
+        fee_sats: u64,
+    },
+    /// Cancel an order, redeeming the unfilled remainder and any proceeds
+    CancelOrder { order_id: OrderId },
+    /// Cancel a withdrawal that has not yet been collected into a withdrawal
+    /// bundle, returning the funds to the wallet.
+    /// The outpoint should be specified as
+    /// `"<regular|coinbase|deposit> <id> <vout>"`.
+    CancelWithdrawal {
+        outpoint: OutPoint,
+        #[arg(long)]
+        fee_sats: u64,
+    },
+    /// Claim the asset locked in an atomic swap, by revealing the
+    /// hex-encoded preimage
+    ClaimAtomicSwap {
+        #[arg(long)]
+        swap_id: AtomicSwapId,
+        #[arg(long)]
+        preimage: String,
+    },
+    /// Register a BitAsset by revealing the nonce from a reservation prep,
+    /// once the reservation tx from `prepare-reservation` is confirmed
+    CompleteRegistration {
+        /// Reservation prep returned by `prepare-reservation`, as JSON
+        prep: String,
+        #[arg(long)]
+        initial_supply: u64,
+        /// Path to a JSON file containing the BitAsset data.
+        /// If not specified, no BitAsset data is set.
+        #[arg(long)]
+        bitasset_data_file: Option<PathBuf>,
+    },
     /// Connect to a peer
     ConnectPeer { addr: SocketAddr },
+    /// Count all UTXOs
+    CountUtxos,
+    /// Count UTXOs containing the specified asset
+    CountUtxosByAsset { asset_id: AssetId },
+    /// Lock an asset in an atomic swap, claimable by the recipient by
+    /// revealing the preimage of the hex-encoded BLAKE3 hash lock,
+    /// or refundable from the timeout block height onwards
+    CreateAtomicSwap {
+        #[arg(long)]
+        asset: AssetId,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long)]
+        recipient: Address,
+        #[arg(long)]
+        hash_lock: String,
+        #[arg(long)]
+        timeout: u32,
+    },
+    /// Reserve and register a BitAsset with an initial supply, in one step.
+    /// Waits for the reservation to be confirmed, if necessary.
+    CreateToken {
+        plain_name: String,
+        #[arg(long)]
+        initial_supply: u64,
+        /// Path to a JSON file containing the BitAsset data.
+        /// If not specified, no BitAsset data is set.
+        #[arg(long)]
+        bitasset_data_file: Option<PathBuf>,
+    },
     /// Returns the amount of the base asset to receive
     DutchAuctionBid {
         #[arg(long)]
         auction_id: DutchAuctionId,
         #[arg(long)]
         bid_size: u64,
+        /// Drop the bid tx from the mempool if it is not included in a
+        /// block at or below this height
+        #[arg(long)]
+        expiry_height: Option<u32>,
     },
     /// Create a dutch auction
     DutchAuctionCreate {
@@ -60,30 +186,201 @@ pub enum Command {
     DutchAuctionCollect { auction_id: DutchAuctionId },
     /// List all Dutch auctions
     DutchAuctions,
+    /// Encrypt the wallet seed with a passphrase
+    EncryptWallet { passphrase: String },
+    /// Estimate the impermanent loss of an LP position in the specified
+    /// pool that was entered at the specified reserves, relative to holding
+    /// the deposited assets
+    EstimateImpermanentLoss {
+        asset0: AssetId,
+        asset1: AssetId,
+        #[arg(long)]
+        entry_reserve0: u64,
+        #[arg(long)]
+        entry_reserve1: u64,
+    },
+    /// Estimate the effect of a withdrawal to the specified mainchain
+    /// address on the next withdrawal bundle
+    EstimateWithdrawal {
+        mainchain_address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+        #[arg(long)]
+        amount_sats: u64,
+        #[arg(long)]
+        mainchain_fee_sats: u64,
+    },
+    /// Fill an order, purchasing the specified amount of the sell asset
+    /// at the order price
+    FillOrder {
+        #[arg(long)]
+        order_id: OrderId,
+        #[arg(long)]
+        amount: u64,
+    },
+    /// Find BitAssets for which the specified key is currently set as the
+    /// signing or encryption pubkey
+    FindBitassetsByKey {
+        /// Signing or encryption pubkey, as JSON
+        key: String,
+    },
     /// Format a deposit address
     FormatDepositAddress { address: Address },
-    /// Generate a mnemonic seed phrase
+    /// Generate a mnemonic seed phrase.
+    /// The mnemonic may optionally be protected with a BIP39 passphrase,
+    /// by setting `--passphrase` when calling `set-seed-from-mnemonic`.
     GenerateMnemonic,
+    /// Get the txids of confirmed txs that spent from or paid to the
+    /// specified address. Requires the address activity index to be enabled.
+    GetAddressHistory {
+        address: Address,
+        /// Only include txs confirmed in blocks at or above this height.
+        /// The address activity index must have been enabled since before
+        /// this height.
+        #[arg(long)]
+        from_height: Option<u32>,
+    },
+    /// Get the state of the specified AMM pool at the end of each block in
+    /// the specified height range in which the pool state changed
+    GetAmmPoolHistory {
+        asset0: AssetId,
+        asset1: AssetId,
+        #[arg(long)]
+        from_height: u32,
+        #[arg(long)]
+        to_height: u32,
+    },
     /// Get the state of the specified AMM pool
     GetAmmPoolState { asset0: AssetId, asset1: AssetId },
+    /// Get the state of the specified AMM pool at the end of the block at
+    /// the specified height
+    GetAmmPoolStateAtHeight {
+        asset0: AssetId,
+        asset1: AssetId,
+        height: u32,
+    },
     /// Get the current price for the specified pair
     GetAmmPrice { base: AssetId, quote: AssetId },
+    /// Get the relative difference between the current price for the
+    /// specified pair, and the effective price of a swap spending
+    /// `amount_spend` of `base` to receive `quote`
+    GetAmmPriceImpact {
+        base: AssetId,
+        quote: AssetId,
+        amount_spend: u64,
+    },
+    /// Get the Bitcoin balance of an address in sats at the end of the block
+    /// at the specified height
+    GetBalanceAtHeight { address: Address, height: u32 },
+    /// Get the market cap of a BitAsset in sats, implied by the price in the
+    /// BitAsset-Bitcoin AMM pool
+    GetBitassetMarketCap { bitasset_id: BitAssetId },
+    /// Get the txids of confirmed txs that spent or created BitAsset outputs
+    /// or control coins for the specified BitAsset
+    GetBitassetTxids { bitasset_id: BitAssetId },
     /// Get block data
     GetBlock { block_hash: BlockHash },
+    /// Get the BMM critical hash that must be committed to on the mainchain
+    /// for a block with the specified header
+    GetBlockCommitment {
+        /// Block header, as JSON
+        header: String,
+    },
+    /// Get the outpoints spent by the specified block
+    GetBlockInputs { block_hash: BlockHash },
+    /// Get the outputs created by the specified block
+    GetBlockOutputs { block_hash: BlockHash },
+    /// Get the local time (unix seconds) at which the specified block was
+    /// first received
+    GetBlockReceiveTime { block_hash: BlockHash },
+    /// Get the authorized txs in the specified block, with their txids
+    GetBlockTransactions { block_hash: BlockHash },
     /// Get the current block count
     GetBlockcount,
+    /// Get mainchain deposit blocks, in the order that they were applied
+    GetDepositBlocks,
+    /// Get up to `count` headers in the current chain, starting with the
+    /// header at `start_height`
+    GetHeaders {
+        start_height: u32,
+        #[arg(long)]
+        count: u32,
+    },
+    /// Get the identifier for the LP token of the AMM pool for the specified
+    /// pair
+    GetLpTokenId { asset0: AssetId, asset1: AssetId },
     /// Get a new address
     GetNewAddress,
+    /// Get the number of connected peers, and the configured peer limits
+    GetNodeInfo,
+    /// Get the most recent mutating RPC calls, most recent first
+    GetRpcAuditLog {
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+    /// Get the input that spent the specified outpoint, if it has been spent.
+    /// The outpoint should be specified as
+    /// `"<regular|coinbase|deposit> <id> <vout>"`.
+    GetSpentBy { outpoint: OutPoint },
+    /// Get the status of a transaction, relative to the current tip
+    GetTransactionStatus { txid: Txid },
+    /// Get several transactions from the archive or mempool
+    GetTransactionsBatch { txids: Vec<Txid> },
     /// Get wallet addresses, sorted by base58 encoding
     GetWalletAddresses,
     /// Get wallet UTXOs
     GetWalletUtxos,
+    /// Get the latest withdrawal bundle that a withdrawal output was spent
+    /// into, and the status of the bundle.
+    /// The outpoint should be specified as
+    /// `"<regular|coinbase|deposit> <id> <vout>"`.
+    GetWithdrawalBundleForUtxo { outpoint: OutPoint },
+    /// List the wallet's deposits that are not yet spendable, with the
+    /// number of mainchain confirmations remaining until each is
+    ListMaturingDeposits,
+    /// List all orders
+    ListOrders,
+    /// List connected peers
+    ListPeers,
     /// List all UTXOs
     ListUtxos,
+    /// List all UTXOs holding the specified asset
+    ListUtxosByAsset { asset_id: AssetId },
+    /// List a page of UTXOs
+    ListUtxosPaginated {
+        /// Number of UTXOs to skip
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Maximum number of UTXOs to list
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+        /// Only list UTXOs containing this asset
+        #[arg(long)]
+        asset: Option<AssetId>,
+        /// Sort by `outpoint` or `value` [default: outpoint]
+        #[arg(long)]
+        sort: Option<UtxoSort>,
+    },
+    /// List all withdrawal bundles, with their latest status
+    ListWithdrawalBundles,
+    /// Lock an encrypted wallet
+    LockWallet,
+    /// Get the current size and capacity of the mempool
+    MempoolInfo,
     /// Attempt to mine a sidechain block
     Mine {
         #[arg(long)]
         fee_sats: Option<u64>,
+        /// Generate the mainchain block that includes the BMM request
+        /// immediately. Only supported on regtest.
+        #[arg(long)]
+        instant: bool,
+    },
+    /// Mint units of a BitAsset
+    MintBitasset {
+        bitasset_id: BitAssetId,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long)]
+        fee_sats: u64,
     },
     /// List unconfirmed owned UTXOs
     MyUnconfirmedUtxos,
@@ -92,14 +389,72 @@ pub enum Command {
     /// Show OpenAPI schema
     #[command(name = "openapi-schema")]
     OpenApiSchema,
+    /// Show OpenRPC schema
+    #[command(name = "openrpc-schema")]
+    OpenRpcSchema,
+    /// Place a limit order, offering `amount` of `sell_asset` at `price`
+    /// units of `buy_asset` per unit of `sell_asset`
+    PlaceOrder {
+        #[arg(long)]
+        sell_asset: AssetId,
+        #[arg(long)]
+        buy_asset: AssetId,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long)]
+        price: u64,
+    },
+    /// Reserve a BitAsset, returning the commitment broadcast in the
+    /// reservation tx, and the nonce to retain. The nonce is revealed when
+    /// registering with `complete-registration`.
+    PrepareReservation { plain_name: String },
+    /// Compute the BitAsset ID for the specified plaintext name, and whether
+    /// the name is available to register
+    PreviewRegistration { plain_name: String },
+    /// Refund the asset locked in an atomic swap, after the timeout
+    RefundAtomicSwap { swap_id: AtomicSwapId },
+    /// Register multiple BitAssets, eg. to seed a test environment.
+    /// BitAssets without a confirmed reservation are reserved instead, and can
+    /// be registered by running this again once the reservations are
+    /// confirmed.
+    RegisterBitassetsBulk {
+        /// Path to a JSON file containing an array of registrations, each with
+        /// `plain_name`, `initial_supply`, and `bitasset_data` fields
+        registrations_file: PathBuf,
+    },
+    /// Rebuild derived indices (tx inclusions and BMM results) from the
+    /// archived blocks, without re-validating the UTXO set
+    Reindex,
     /// Reserve a BitAsset
     ReserveBitasset { plaintext_name: String },
+    /// Reveal the mnemonic seed phrase for the wallet
+    RevealMnemonic,
+    /// Run health checks on the node, and print a report
+    RunDiagnostics,
     /// Set the wallet seed from a mnemonic seed phrase
-    SetSeedFromMnemonic { mnemonic: String },
+    SetSeedFromMnemonic {
+        mnemonic: String,
+        /// BIP39 passphrase, if the mnemonic is passphrase-protected
+        #[arg(long)]
+        passphrase: Option<String>,
+    },
     /// Get total sidechain wealth
     SidechainWealth,
+    /// Send the control coin for a BitAsset, and BitAsset units, to
+    /// separate addresses
+    SplitBitassetControl {
+        bitasset_id: BitAssetId,
+        control_dest: Address,
+        units_dest: Address,
+        #[arg(long)]
+        units_amount: u64,
+        #[arg(long)]
+        fee_sats: u64,
+    },
     /// Stop the node
     Stop,
+    /// Stop mining blocks automatically
+    StopAutoMine,
     /// Transfer funds to the specified address
     Transfer {
         dest: Address,
@@ -107,6 +462,44 @@ pub enum Command {
         value_sats: u64,
         #[arg(long)]
         fee_sats: u64,
+        /// Drop the tx from the mempool if it is not included in a block at
+        /// or below this height
+        #[arg(long)]
+        expiry_height: Option<u32>,
+    },
+    /// Transfer the control coin for a BitAsset to the specified address
+    TransferBitassetControl {
+        bitasset_id: BitAssetId,
+        dest: Address,
+        #[arg(long)]
+        fee_sats: u64,
+    },
+    /// Transfer AMM LP tokens for the specified pair to the specified address
+    TransferLpToken {
+        asset0: AssetId,
+        asset1: AssetId,
+        dest: Address,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long)]
+        fee_sats: u64,
+    },
+    /// Unlock an encrypted wallet
+    UnlockWallet {
+        passphrase: String,
+        /// Automatically lock the wallet again after this many seconds
+        /// [default: 300, max: 604800]
+        #[arg(long)]
+        timeout_secs: Option<u64>,
+    },
+    /// Wait for the block count to exceed the specified height, and print
+    /// the new block count
+    WaitForBlock {
+        current_height: u32,
+        /// Print the current block count if it does not increase within
+        /// this many seconds [default: 30, max: 60]
+        #[arg(long)]
+        timeout_secs: Option<u64>,
     },
     /// Initiate a withdrawal to the specified mainchain address
     Withdraw {
@@ -136,9 +529,41 @@ pub struct Cli {
     pub command: Command,
 }
 
+/// Hint printed if a wallet-dependent command fails because the wallet does
+/// not have a seed
+const NO_WALLET_SEED_HINT: &str = "the wallet does not have a seed. \
+    Generate a mnemonic with `generate-mnemonic`, \
+    and set it with `set-seed-from-mnemonic <MNEMONIC>`";
+
+/// Hint printed if a wallet-dependent command fails because the wallet is
+/// locked
+const WALLET_LOCKED_HINT: &str =
+    "the wallet is locked. Unlock it with `unlock-wallet <PASSPHRASE>`";
+
 impl Cli {
     pub async fn run(self) -> anyhow::Result<String> {
-        let rpc_client: HttpClient = HttpClientBuilder::default()
+        self.run_command().await.map_err(|err| {
+            match err.downcast_ref::<jsonrpsee::core::Error>() {
+                Some(jsonrpsee::core::Error::Call(err_obj))
+                    if err_obj.code() == NO_WALLET_SEED_ERROR_CODE =>
+                {
+                    err.context(NO_WALLET_SEED_HINT)
+                }
+                Some(jsonrpsee::core::Error::Call(err_obj))
+                    if err_obj.code() == WALLET_LOCKED_ERROR_CODE =>
+                {
+                    err.context(WALLET_LOCKED_HINT)
+                }
+                _ => err,
+            }
+        })
+    }
+
+    async fn run_command(self) -> anyhow::Result<String> {
+        let rpc_client = HttpClientBuilder::default()
+            .set_http_middleware(
+                ServiceBuilder::new().layer(DecompressionLayer::default()),
+            )
             .build(format!("http://{}", self.rpc_addr))?;
         let res = match self.command {
             Command::AmmBurn {
@@ -162,16 +587,64 @@ impl Cli {
                     .await?;
                 format!("{txid}")
             }
+            Command::AmmPools => {
+                let amm_pools = rpc_client.amm_pools().await?;
+                serde_json::to_string_pretty(&amm_pools)?
+            }
             Command::AmmSwap {
                 asset_spend,
                 asset_receive,
                 amount_spend,
+                min_amount_receive,
+                expiry_height,
             } => {
                 let amount = rpc_client
-                    .amm_swap(asset_spend, asset_receive, amount_spend)
+                    .amm_swap(
+                        asset_spend,
+                        asset_receive,
+                        amount_spend,
+                        min_amount_receive,
+                        expiry_height,
+                    )
                     .await?;
                 format!("{amount}")
             }
+            Command::AmmSwapEstimate {
+                asset_spend,
+                asset_receive,
+                amount_spend,
+            } => {
+                let estimate = rpc_client
+                    .amm_swap_estimate(asset_spend, asset_receive, amount_spend)
+                    .await?;
+                serde_json::to_string_pretty(&estimate)?
+            }
+            Command::AmmSwapExactOutput {
+                asset_spend,
+                asset_receive,
+                amount_receive,
+            } => {
+                let amount = rpc_client
+                    .amm_swap_exact_output(
+                        asset_spend,
+                        asset_receive,
+                        amount_receive,
+                    )
+                    .await?;
+                format!("{amount}")
+            }
+            Command::AssetBalances => {
+                let balances = rpc_client.asset_balances().await?;
+                serde_json::to_string_pretty(&balances)?
+            }
+            Command::AtomicSwaps => {
+                let swaps = rpc_client.atomic_swaps().await?;
+                serde_json::to_string_pretty(&swaps)?
+            }
+            Command::AutoMine { interval_secs } => {
+                let () = rpc_client.auto_mine(interval_secs).await?;
+                String::default()
+            }
             Command::Bitassets => {
                 let bitassets = rpc_client.bitassets().await?;
                 serde_json::to_string_pretty(&bitassets)?
@@ -180,16 +653,100 @@ impl Cli {
                 let balance = rpc_client.bitcoin_balance().await?;
                 format!("{balance}")
             }
+            Command::BuildTransfer {
+                outputs_file,
+                fee_sats,
+            } => {
+                let outputs = std::fs::read_to_string(outputs_file)?;
+                let outputs = serde_json::from_str(&outputs)?;
+                let txid = rpc_client.build_transfer(outputs, fee_sats).await?;
+                format!("{txid}")
+            }
+            Command::CancelOrder { order_id } => {
+                let txid = rpc_client.cancel_order(order_id).await?;
+                format!("{txid}")
+            }
+            Command::CancelWithdrawal { outpoint, fee_sats } => {
+                let txid =
+                    rpc_client.cancel_withdrawal(outpoint, fee_sats).await?;
+                format!("{txid}")
+            }
+            Command::ClaimAtomicSwap { swap_id, preimage } => {
+                let txid =
+                    rpc_client.claim_atomic_swap(swap_id, preimage).await?;
+                format!("{txid}")
+            }
+            Command::CompleteRegistration {
+                prep,
+                initial_supply,
+                bitasset_data_file,
+            } => {
+                let prep = serde_json::from_str(&prep)?;
+                let bitasset_data = match bitasset_data_file {
+                    Some(bitasset_data_file) => {
+                        let bitasset_data =
+                            std::fs::read_to_string(bitasset_data_file)?;
+                        serde_json::from_str(&bitasset_data)?
+                    }
+                    None => BitAssetData::default(),
+                };
+                let txid = rpc_client
+                    .complete_registration(prep, initial_supply, bitasset_data)
+                    .await?;
+                format!("{txid}")
+            }
             Command::ConnectPeer { addr } => {
                 let () = rpc_client.connect_peer(addr).await?;
                 String::default()
             }
+            Command::CountUtxos => {
+                let count = rpc_client.count_utxos().await?;
+                format!("{count}")
+            }
+            Command::CountUtxosByAsset { asset_id } => {
+                let count = rpc_client.count_utxos_by_asset(asset_id).await?;
+                format!("{count}")
+            }
+            Command::CreateAtomicSwap {
+                asset,
+                amount,
+                recipient,
+                hash_lock,
+                timeout,
+            } => {
+                let swap_id = rpc_client
+                    .create_atomic_swap(
+                        asset, amount, recipient, hash_lock, timeout,
+                    )
+                    .await?;
+                format!("{swap_id}")
+            }
+            Command::CreateToken {
+                plain_name,
+                initial_supply,
+                bitasset_data_file,
+            } => {
+                let bitasset_data = match bitasset_data_file {
+                    Some(bitasset_data_file) => {
+                        let bitasset_data =
+                            std::fs::read_to_string(bitasset_data_file)?;
+                        serde_json::from_str(&bitasset_data)?
+                    }
+                    None => BitAssetData::default(),
+                };
+                let txid = rpc_client
+                    .create_token(plain_name, initial_supply, bitasset_data)
+                    .await?;
+                format!("{txid}")
+            }
             Command::DutchAuctionBid {
                 auction_id,
                 bid_size,
+                expiry_height,
             } => {
-                let amount =
-                    rpc_client.dutch_auction_bid(auction_id, bid_size).await?;
+                let amount = rpc_client
+                    .dutch_auction_bid(auction_id, bid_size, expiry_height)
+                    .await?;
                 format!("{amount}")
             }
             Command::DutchAuctionCreate { params } => {
@@ -209,31 +766,194 @@ impl Cli {
                 let auctions = rpc_client.dutch_auctions().await?;
                 serde_json::to_string_pretty(&auctions)?
             }
+            Command::EncryptWallet { passphrase } => {
+                let () = rpc_client.encrypt_wallet(passphrase).await?;
+                String::default()
+            }
+            Command::EstimateImpermanentLoss {
+                asset0,
+                asset1,
+                entry_reserve0,
+                entry_reserve1,
+            } => {
+                let impermanent_loss = rpc_client
+                    .estimate_impermanent_loss(
+                        asset0,
+                        asset1,
+                        entry_reserve0,
+                        entry_reserve1,
+                    )
+                    .await?;
+                format!("{impermanent_loss}")
+            }
+            Command::EstimateWithdrawal {
+                mainchain_address,
+                amount_sats,
+                mainchain_fee_sats,
+            } => {
+                let estimate = rpc_client
+                    .estimate_withdrawal(
+                        mainchain_address,
+                        amount_sats,
+                        mainchain_fee_sats,
+                    )
+                    .await?;
+                serde_json::to_string_pretty(&estimate)?
+            }
+            Command::FillOrder { order_id, amount } => {
+                let txid = rpc_client.fill_order(order_id, amount).await?;
+                format!("{txid}")
+            }
+            Command::FindBitassetsByKey { key } => {
+                let key = serde_json::from_str(&key)?;
+                let bitasset_ids =
+                    rpc_client.find_bitassets_by_key(key).await?;
+                serde_json::to_string_pretty(&bitasset_ids)?
+            }
             Command::FormatDepositAddress { address } => {
                 rpc_client.format_deposit_address(address).await?
             }
             Command::GenerateMnemonic => rpc_client.generate_mnemonic().await?,
+            Command::GetAddressHistory {
+                address,
+                from_height,
+            } => {
+                let txids = rpc_client
+                    .get_address_history(address, from_height)
+                    .await?;
+                serde_json::to_string_pretty(&txids)?
+            }
+            Command::GetAmmPoolHistory {
+                asset0,
+                asset1,
+                from_height,
+                to_height,
+            } => {
+                let history = rpc_client
+                    .get_amm_pool_history(
+                        asset0,
+                        asset1,
+                        from_height,
+                        to_height,
+                    )
+                    .await?;
+                serde_json::to_string_pretty(&history)?
+            }
             Command::GetAmmPoolState { asset0, asset1 } => {
                 let state =
                     rpc_client.get_amm_pool_state(asset0, asset1).await?;
                 serde_json::to_string_pretty(&state)?
             }
+            Command::GetAmmPoolStateAtHeight {
+                asset0,
+                asset1,
+                height,
+            } => {
+                let state = rpc_client
+                    .get_amm_pool_state_at_height(asset0, asset1, height)
+                    .await?;
+                serde_json::to_string_pretty(&state)?
+            }
             Command::GetAmmPrice { base, quote } => {
                 let price = rpc_client.get_amm_price(base, quote).await?;
                 serde_json::to_string_pretty(&price)?
             }
+            Command::GetAmmPriceImpact {
+                base,
+                quote,
+                amount_spend,
+            } => {
+                let price_impact = rpc_client
+                    .get_amm_price_impact(base, quote, amount_spend)
+                    .await?;
+                serde_json::to_string_pretty(&price_impact)?
+            }
+            Command::GetBalanceAtHeight { address, height } => {
+                let balance =
+                    rpc_client.get_balance_at_height(address, height).await?;
+                format!("{balance}")
+            }
+            Command::GetBitassetMarketCap { bitasset_id } => {
+                let market_cap =
+                    rpc_client.get_bitasset_market_cap(bitasset_id).await?;
+                serde_json::to_string_pretty(&market_cap)?
+            }
+            Command::GetBitassetTxids { bitasset_id } => {
+                let txids = rpc_client.get_bitasset_txids(bitasset_id).await?;
+                serde_json::to_string_pretty(&txids)?
+            }
             Command::GetBlock { block_hash } => {
                 let block = rpc_client.get_block(block_hash).await?;
                 serde_json::to_string_pretty(&block)?
             }
+            Command::GetBlockCommitment { header } => {
+                let header = serde_json::from_str(&header)?;
+                let critical_hash =
+                    rpc_client.get_block_commitment(header).await?;
+                format!("{critical_hash}")
+            }
+            Command::GetBlockInputs { block_hash } => {
+                let inputs = rpc_client.get_block_inputs(block_hash).await?;
+                serde_json::to_string_pretty(&inputs)?
+            }
+            Command::GetBlockOutputs { block_hash } => {
+                let outputs = rpc_client.get_block_outputs(block_hash).await?;
+                serde_json::to_string_pretty(&outputs)?
+            }
+            Command::GetBlockReceiveTime { block_hash } => {
+                let receive_time =
+                    rpc_client.get_block_receive_time(block_hash).await?;
+                serde_json::to_string_pretty(&receive_time)?
+            }
+            Command::GetBlockTransactions { block_hash } => {
+                let txs = rpc_client.get_block_transactions(block_hash).await?;
+                serde_json::to_string_pretty(&txs)?
+            }
             Command::GetBlockcount => {
                 let blockcount = rpc_client.getblockcount().await?;
                 format!("{blockcount}")
             }
+            Command::GetDepositBlocks => {
+                let deposit_blocks = rpc_client.get_deposit_blocks().await?;
+                serde_json::to_string_pretty(&deposit_blocks)?
+            }
+            Command::GetHeaders {
+                start_height,
+                count,
+            } => {
+                let headers =
+                    rpc_client.get_headers(start_height, count).await?;
+                serde_json::to_string_pretty(&headers)?
+            }
+            Command::GetLpTokenId { asset0, asset1 } => {
+                let lp_token_id =
+                    rpc_client.get_lp_token_id(asset0, asset1).await?;
+                serde_json::to_string_pretty(&lp_token_id)?
+            }
             Command::GetNewAddress => {
                 let address = rpc_client.get_new_address().await?;
                 format!("{address}")
             }
+            Command::GetNodeInfo => {
+                let node_info = rpc_client.get_node_info().await?;
+                serde_json::to_string_pretty(&node_info)?
+            }
+            Command::GetRpcAuditLog { limit } => {
+                let entries = rpc_client.get_rpc_audit_log(limit).await?;
+                serde_json::to_string_pretty(&entries)?
+            }
+            Command::GetSpentBy { outpoint } => {
+                let inpoint = rpc_client.get_spent_by(outpoint).await?;
+                serde_json::to_string_pretty(&inpoint)?
+            }
+            Command::GetTransactionStatus { txid } => {
+                let status = rpc_client.get_transaction_status(txid).await?;
+                serde_json::to_string_pretty(&status)?
+            }
+            Command::GetTransactionsBatch { txids } => {
+                let txs = rpc_client.get_transactions_batch(txids).await?;
+                serde_json::to_string_pretty(&txs)?
+            }
             Command::GetWalletAddresses => {
                 let addresses = rpc_client.get_wallet_addresses().await?;
                 serde_json::to_string_pretty(&addresses)?
@@ -242,14 +962,73 @@ impl Cli {
                 let utxos = rpc_client.get_wallet_utxos().await?;
                 serde_json::to_string_pretty(&utxos)?
             }
+            Command::GetWithdrawalBundleForUtxo { outpoint } => {
+                let bundle_info =
+                    rpc_client.get_withdrawal_bundle_for_utxo(outpoint).await?;
+                serde_json::to_string_pretty(&bundle_info)?
+            }
+            Command::ListMaturingDeposits => {
+                let deposits = rpc_client.list_maturing_deposits().await?;
+                serde_json::to_string_pretty(&deposits)?
+            }
+            Command::ListOrders => {
+                let orders = rpc_client.list_orders().await?;
+                serde_json::to_string_pretty(&orders)?
+            }
+            Command::ListPeers => {
+                let peers = rpc_client.list_peers().await?;
+                serde_json::to_string_pretty(&peers)?
+            }
             Command::ListUtxos => {
                 let utxos = rpc_client.list_utxos().await?;
                 serde_json::to_string_pretty(&utxos)?
             }
-            Command::Mine { fee_sats } => {
-                let () = rpc_client.mine(fee_sats).await?;
+            Command::ListUtxosByAsset { asset_id } => {
+                let utxos = rpc_client.list_utxos_by_asset(asset_id).await?;
+                serde_json::to_string_pretty(&utxos)?
+            }
+            Command::ListUtxosPaginated {
+                offset,
+                limit,
+                asset,
+                sort,
+            } => {
+                let utxos = rpc_client
+                    .list_utxos_paginated(
+                        offset,
+                        limit,
+                        asset,
+                        sort.unwrap_or_default(),
+                    )
+                    .await?;
+                serde_json::to_string_pretty(&utxos)?
+            }
+            Command::ListWithdrawalBundles => {
+                let bundles = rpc_client.list_withdrawal_bundles().await?;
+                serde_json::to_string_pretty(&bundles)?
+            }
+            Command::LockWallet => {
+                let () = rpc_client.lock_wallet().await?;
+                String::default()
+            }
+            Command::MempoolInfo => {
+                let mempool_info = rpc_client.mempool_info().await?;
+                serde_json::to_string_pretty(&mempool_info)?
+            }
+            Command::Mine { fee_sats, instant } => {
+                let () = rpc_client.mine(fee_sats, Some(instant)).await?;
                 String::default()
             }
+            Command::MintBitasset {
+                bitasset_id,
+                amount,
+                fee_sats,
+            } => {
+                let txid = rpc_client
+                    .mint_bitasset(bitasset_id, amount, fee_sats)
+                    .await?;
+                format!("{txid}")
+            }
             Command::MyUnconfirmedUtxos => {
                 let utxos = rpc_client.my_unconfirmed_utxos().await?;
                 serde_json::to_string_pretty(&utxos)?
@@ -263,32 +1042,147 @@ impl Cli {
                     <plain_bitassets_app_rpc_api::RpcDoc as utoipa::OpenApi>::openapi();
                 openapi.to_pretty_json()?
             }
+            Command::OpenRpcSchema => {
+                let openrpc = plain_bitassets_app_rpc_api::openrpc_schema();
+                serde_json::to_string_pretty(&openrpc)?
+            }
+            Command::PlaceOrder {
+                sell_asset,
+                buy_asset,
+                amount,
+                price,
+            } => {
+                let order_id = rpc_client
+                    .place_order(sell_asset, buy_asset, amount, price)
+                    .await?;
+                format!("{order_id}")
+            }
+            Command::PrepareReservation { plain_name } => {
+                let prep = rpc_client.prepare_reservation(plain_name).await?;
+                serde_json::to_string_pretty(&prep)?
+            }
+            Command::PreviewRegistration { plain_name } => {
+                let (bitasset_id, available) =
+                    rpc_client.preview_registration(plain_name).await?;
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "bitasset_id": bitasset_id,
+                    "available": available,
+                }))?
+            }
+            Command::RefundAtomicSwap { swap_id } => {
+                let txid = rpc_client.refund_atomic_swap(swap_id).await?;
+                format!("{txid}")
+            }
+            Command::RegisterBitassetsBulk { registrations_file } => {
+                let registrations =
+                    std::fs::read_to_string(registrations_file)?;
+                let registrations = serde_json::from_str(&registrations)?;
+                let txids =
+                    rpc_client.register_bitassets_bulk(registrations).await?;
+                serde_json::to_string_pretty(&txids)?
+            }
+            Command::Reindex => {
+                let () = rpc_client.reindex().await?;
+                String::default()
+            }
             Command::ReserveBitasset { plaintext_name } => {
                 let txid = rpc_client.reserve_bitasset(plaintext_name).await?;
                 format!("{txid}")
             }
-            Command::SetSeedFromMnemonic { mnemonic } => {
-                let () = rpc_client.set_seed_from_mnemonic(mnemonic).await?;
+            Command::RevealMnemonic => rpc_client.reveal_mnemonic().await?,
+            Command::RunDiagnostics => {
+                let diagnostics = rpc_client.run_diagnostics().await?;
+                serde_json::to_string_pretty(&diagnostics)?
+            }
+            Command::SetSeedFromMnemonic {
+                mnemonic,
+                passphrase,
+            } => {
+                let () = rpc_client
+                    .set_seed_from_mnemonic(mnemonic, passphrase)
+                    .await?;
                 String::default()
             }
             Command::SidechainWealth => {
                 let sidechain_wealth = rpc_client.sidechain_wealth().await?;
                 format!("{sidechain_wealth}")
             }
+            Command::SplitBitassetControl {
+                bitasset_id,
+                control_dest,
+                units_dest,
+                units_amount,
+                fee_sats,
+            } => {
+                let txid = rpc_client
+                    .split_bitasset_control(
+                        bitasset_id,
+                        control_dest,
+                        units_dest,
+                        units_amount,
+                        fee_sats,
+                    )
+                    .await?;
+                format!("{txid}")
+            }
             Command::Stop => {
                 let () = rpc_client.stop().await?;
                 String::default()
             }
+            Command::StopAutoMine => {
+                let () = rpc_client.stop_auto_mine().await?;
+                String::default()
+            }
             Command::Transfer {
                 dest,
                 value_sats,
                 fee_sats,
+                expiry_height,
             } => {
                 let txid = rpc_client
-                    .transfer(dest, value_sats, fee_sats, None)
+                    .transfer(dest, value_sats, fee_sats, None, expiry_height)
                     .await?;
                 format!("{txid}")
             }
+            Command::TransferBitassetControl {
+                bitasset_id,
+                dest,
+                fee_sats,
+            } => {
+                let txid = rpc_client
+                    .transfer_bitasset_control(bitasset_id, dest, fee_sats)
+                    .await?;
+                format!("{txid}")
+            }
+            Command::TransferLpToken {
+                asset0,
+                asset1,
+                dest,
+                amount,
+                fee_sats,
+            } => {
+                let txid = rpc_client
+                    .transfer_lp_token(asset0, asset1, dest, amount, fee_sats)
+                    .await?;
+                format!("{txid}")
+            }
+            Command::UnlockWallet {
+                passphrase,
+                timeout_secs,
+            } => {
+                let () =
+                    rpc_client.unlock_wallet(passphrase, timeout_secs).await?;
+                String::default()
+            }
+            Command::WaitForBlock {
+                current_height,
+                timeout_secs,
+            } => {
+                let block_count = rpc_client
+                    .wait_for_block(current_height, timeout_secs)
+                    .await?;
+                format!("{block_count}")
+            }
             Command::Withdraw {
                 mainchain_address,
                 amount_sats,
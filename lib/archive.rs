@@ -1,6 +1,7 @@
 use std::{
     cmp::Ordering,
     collections::{BTreeMap, HashMap, HashSet},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use bip300301::{
@@ -12,12 +13,73 @@ use heed::{types::SerdeBincode, Database, RoTxn, RwTxn};
 
 use crate::types::{Block, BlockHash, BmmResult, Body, Header, Tip, Txid};
 
+/// Number of blocks, ending with the parent block, over which the median
+/// time past is computed
+pub const MEDIAN_TIME_PAST_WINDOW: usize = 11;
+
+/// Max number of seconds by which a block's timestamp may exceed the time of
+/// the mainchain block that it extends
+pub const MAX_FUTURE_BLOCK_TIME: u64 = 2 * 60 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidTimestampError {
+    #[error(
+        "timestamp {timestamp} is not later than the median time past \
+         ({median_time_past})"
+    )]
+    NotAfterMedianTimePast {
+        timestamp: u64,
+        median_time_past: u64,
+    },
+    #[error(
+        "timestamp {timestamp} is more than {}s later than the mainchain \
+         block time ({main_block_time})",
+        MAX_FUTURE_BLOCK_TIME
+    )]
+    TooFarInFuture {
+        timestamp: u64,
+        main_block_time: u64,
+    },
+}
+
+/** Check a header timestamp against the median time past of the parent
+ *  block, and against the time of the mainchain block that the header
+ *  extends.
+ *  `median_time_past` should be `None` if the header has no parent, and
+ *  `main_block_time` should be `None` if the header does not extend a
+ *  mainchain block. */
+pub fn check_header_timestamp(
+    timestamp: u64,
+    median_time_past: Option<u64>,
+    main_block_time: Option<u64>,
+) -> Result<(), InvalidTimestampError> {
+    if let Some(median_time_past) = median_time_past
+        && timestamp <= median_time_past
+    {
+        return Err(InvalidTimestampError::NotAfterMedianTimePast {
+            timestamp,
+            median_time_past,
+        });
+    }
+    if let Some(main_block_time) = main_block_time
+        && timestamp > main_block_time + MAX_FUTURE_BLOCK_TIME
+    {
+        return Err(InvalidTimestampError::TooFarInFuture {
+            timestamp,
+            main_block_time,
+        });
+    }
+    Ok(())
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("invalid mainchain block hash for deposit")]
     DepositInvalidMainBlockHash,
     #[error("heed error")]
     Heed(#[from] heed::Error),
+    #[error("invalid header timestamp")]
+    InvalidTimestamp(#[from] InvalidTimestampError),
     #[error("invalid previous side hash")]
     InvalidPrevSideHash,
     #[error("invalid merkle root")]
@@ -67,6 +129,9 @@ pub struct Archive {
         SerdeBincode<BlockHash>,
         SerdeBincode<HashMap<bitcoin::BlockHash, BmmResult>>,
     >,
+    /// Local wall-clock time (unix seconds) at which each block body was
+    /// first stored
+    block_receive_times: Database<SerdeBincode<BlockHash>, SerdeBincode<u64>>,
     bodies: Database<SerdeBincode<BlockHash>, SerdeBincode<Body>>,
     /// Deposits by mainchain block, sorted first-to-last in each block
     deposits: Database<
@@ -129,7 +194,7 @@ pub struct Archive {
 }
 
 impl Archive {
-    pub const NUM_DBS: u32 = 14;
+    pub const NUM_DBS: u32 = 15;
 
     pub fn new(env: &heed::Env) -> Result<Self, Error> {
         let mut rwtxn = env.write_txn()?;
@@ -137,6 +202,8 @@ impl Archive {
             env.create_database(&mut rwtxn, Some("hash_to_height"))?;
         let bmm_results =
             env.create_database(&mut rwtxn, Some("bmm_results"))?;
+        let block_receive_times =
+            env.create_database(&mut rwtxn, Some("block_receive_times"))?;
         let bodies = env.create_database(&mut rwtxn, Some("bodies"))?;
         let deposits = env.create_database(&mut rwtxn, Some("deposits"))?;
         let exponential_ancestors =
@@ -177,6 +244,7 @@ impl Archive {
         Ok(Self {
             block_hash_to_height,
             bmm_results,
+            block_receive_times,
             bodies,
             deposits,
             exponential_ancestors,
@@ -288,6 +356,18 @@ impl Archive {
             .ok_or(Error::NoDepositsInfo(block_hash))
     }
 
+    /// Get the local wall-clock time (unix seconds) at which a block was
+    /// first received.
+    /// Returns [`None`] if the block body has not been stored.
+    pub fn try_get_block_receive_time(
+        &self,
+        rotxn: &RoTxn,
+        block_hash: BlockHash,
+    ) -> Result<Option<u64>, Error> {
+        let receive_time = self.block_receive_times.get(rotxn, &block_hash)?;
+        Ok(receive_time)
+    }
+
     pub fn try_get_header(
         &self,
         rotxn: &RoTxn,
@@ -633,6 +713,13 @@ impl Archive {
     ) -> Result<(), Error> {
         let _header = self.get_header(rwtxn, block_hash)?;
         self.bodies.put(rwtxn, &block_hash, body)?;
+        if self.block_receive_times.get(rwtxn, &block_hash)?.is_none() {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            self.block_receive_times.put(rwtxn, &block_hash, &now)?;
+        }
         body.transactions
             .iter()
             .enumerate()
@@ -663,10 +750,11 @@ impl Archive {
         Ok(())
     }
 
-    /// Store a header.
+    /// Store a header, if the header timestamp is valid.
     ///
     /// The following predicates MUST be met before calling this function:
     /// * Ancestor headers MUST be stored
+    /// * The mainchain header for `header.prev_main_hash` MUST be stored
     /// * BMM commitments MUST be stored for mainchain header where
     ///   `main_header.prev_blockhash == header.prev_main_hash`
     pub fn put_header(
@@ -679,6 +767,7 @@ impl Archive {
         else {
             return Err(Error::InvalidPrevSideHash);
         };
+        let () = self.validate_header_timestamp(rwtxn, header)?;
         let height = prev_height + 1;
         let block_hash = header.hash();
         self.block_hash_to_height.put(rwtxn, &block_hash, &height)?;
@@ -724,63 +813,74 @@ impl Archive {
             &exponential_ancestors,
         )?;
         // Populate BMM verifications
-        {
-            let mut bmm_results = self.get_bmm_results(rwtxn, block_hash)?;
-            let parent_bmm_results =
-                self.get_bmm_results(rwtxn, header.prev_side_hash)?;
-            let main_blocks =
-                self.get_main_successors(rwtxn, header.prev_main_hash)?;
-            for main_block in main_blocks {
-                let Some(commitment) =
-                    self.get_main_bmm_commitment(rwtxn, main_block)?
-                else {
-                    tracing::trace!(%block_hash, "Failed BMM @ {main_block}: missing commitment");
-                    bmm_results.insert(main_block, BmmResult::Failed);
-                    continue;
-                };
-                if commitment != block_hash {
-                    tracing::trace!(%block_hash, "Failed BMM @ {main_block}: commitment to other block ({commitment})");
-                    bmm_results.insert(main_block, BmmResult::Failed);
-                    continue;
-                }
-                let main_header = self.get_main_header(rwtxn, main_block)?;
-                if header.prev_main_hash != main_header.prev_blockhash {
-                    tracing::trace!(%block_hash, "Failed BMM @ {main_block}: should be impossible?");
-                    bmm_results.insert(main_block, BmmResult::Failed);
-                    continue;
-                }
-                if header.prev_side_hash == BlockHash::default() {
-                    tracing::trace!(%block_hash, "Verified BMM @ {main_block}: no parent");
-                    bmm_results.insert(main_block, BmmResult::Verified);
-                    continue;
-                }
-                // Check if there is a valid BMM commitment to the parent in the
-                // main ancestry
-                let main_ancestry_contains_valid_bmm_commitment_to_parent =
-                    parent_bmm_results
-                        .iter()
-                        .map(Ok)
-                        .transpose_into_fallible()
-                        .any(|(bmm_block, bmm_result)| {
-                            let parent_verified = *bmm_result
-                                == BmmResult::Verified
-                                && self.is_main_descendant(
-                                    rwtxn, *bmm_block, main_block,
-                                )?;
-                            Result::<bool, Error>::Ok(parent_verified)
-                        })?;
-                if main_ancestry_contains_valid_bmm_commitment_to_parent {
-                    tracing::trace!(%block_hash, "Verified BMM @ {main_block}: verified parent");
-                    bmm_results.insert(main_block, BmmResult::Verified);
-                    continue;
-                } else {
-                    tracing::trace!(%block_hash, "Failed BMM @ {main_block}: no valid BMM commitment to parent in main ancestry");
-                    bmm_results.insert(main_block, BmmResult::Failed);
-                    continue;
-                }
+        let () = self.put_bmm_results(rwtxn, header)?;
+        Ok(())
+    }
+
+    /// Populate BMM results for a header, from the BMM commitments in
+    /// mainchain blocks that extend the header's `prev_main_hash`.
+    /// BMM results for the parent block MUST be stored.
+    fn put_bmm_results(
+        &self,
+        rwtxn: &mut RwTxn,
+        header: &Header,
+    ) -> Result<(), Error> {
+        let block_hash = header.hash();
+        let mut bmm_results = self.get_bmm_results(rwtxn, block_hash)?;
+        let parent_bmm_results =
+            self.get_bmm_results(rwtxn, header.prev_side_hash)?;
+        let main_blocks =
+            self.get_main_successors(rwtxn, header.prev_main_hash)?;
+        for main_block in main_blocks {
+            let Some(commitment) =
+                self.get_main_bmm_commitment(rwtxn, main_block)?
+            else {
+                tracing::trace!(%block_hash, "Failed BMM @ {main_block}: missing commitment");
+                bmm_results.insert(main_block, BmmResult::Failed);
+                continue;
+            };
+            if commitment != block_hash {
+                tracing::trace!(%block_hash, "Failed BMM @ {main_block}: commitment to other block ({commitment})");
+                bmm_results.insert(main_block, BmmResult::Failed);
+                continue;
+            }
+            let main_header = self.get_main_header(rwtxn, main_block)?;
+            if header.prev_main_hash != main_header.prev_blockhash {
+                tracing::trace!(%block_hash, "Failed BMM @ {main_block}: should be impossible?");
+                bmm_results.insert(main_block, BmmResult::Failed);
+                continue;
+            }
+            if header.prev_side_hash == BlockHash::default() {
+                tracing::trace!(%block_hash, "Verified BMM @ {main_block}: no parent");
+                bmm_results.insert(main_block, BmmResult::Verified);
+                continue;
+            }
+            // Check if there is a valid BMM commitment to the parent in the
+            // main ancestry
+            let main_ancestry_contains_valid_bmm_commitment_to_parent =
+                parent_bmm_results
+                    .iter()
+                    .map(Ok)
+                    .transpose_into_fallible()
+                    .any(|(bmm_block, bmm_result)| {
+                        let parent_verified = *bmm_result
+                            == BmmResult::Verified
+                            && self.is_main_descendant(
+                                rwtxn, *bmm_block, main_block,
+                            )?;
+                        Result::<bool, Error>::Ok(parent_verified)
+                    })?;
+            if main_ancestry_contains_valid_bmm_commitment_to_parent {
+                tracing::trace!(%block_hash, "Verified BMM @ {main_block}: verified parent");
+                bmm_results.insert(main_block, BmmResult::Verified);
+                continue;
+            } else {
+                tracing::trace!(%block_hash, "Failed BMM @ {main_block}: no valid BMM commitment to parent in main ancestry");
+                bmm_results.insert(main_block, BmmResult::Failed);
+                continue;
             }
-            self.bmm_results.put(rwtxn, &block_hash, &bmm_results)?;
         }
+        self.bmm_results.put(rwtxn, &block_hash, &bmm_results)?;
         Ok(())
     }
 
@@ -909,6 +1009,115 @@ impl Archive {
         Ok(())
     }
 
+    /** Rebuild derived indices (tx inclusions and BMM results) from the
+     *  stored headers, bodies, and mainchain BMM commitments. */
+    pub fn reindex(&self, rwtxn: &mut RwTxn) -> Result<(), Error> {
+        // Rebuild tx inclusions
+        self.txid_to_inclusions.clear(rwtxn)?;
+        let bodies: Vec<(BlockHash, Body)> =
+            self.bodies.iter(rwtxn)?.collect::<Result<_, _>>()?;
+        for (block_hash, body) in bodies {
+            for (txin, tx) in body.transactions.iter().enumerate() {
+                let txid = tx.txid();
+                let mut inclusions = self.get_tx_inclusions(rwtxn, txid)?;
+                inclusions.insert(block_hash, txin as u32);
+                self.txid_to_inclusions.put(rwtxn, &txid, &inclusions)?;
+            }
+        }
+        // Rebuild BMM results, such that parents are processed before
+        // children
+        self.bmm_results.clear(rwtxn)?;
+        let mut block_heights: Vec<(BlockHash, u32)> = self
+            .block_hash_to_height
+            .iter(rwtxn)?
+            .collect::<Result<_, _>>()?;
+        block_heights.sort_by_key(|(_, height)| *height);
+        for (block_hash, _) in block_heights {
+            let header = self.get_header(rwtxn, block_hash)?;
+            let () = self.put_bmm_results(rwtxn, &header)?;
+        }
+        /* Any remaining BMM commitments to known blocks are in mainchain
+         * blocks that do not extend the block's `prev_main_hash` */
+        let main_bmm_commitments: Vec<(bitcoin::BlockHash, Option<BlockHash>)> =
+            self.main_bmm_commitments
+                .iter(rwtxn)?
+                .collect::<Result<_, _>>()?;
+        for (main_hash, commitment) in main_bmm_commitments {
+            let Some(commitment) = commitment else {
+                continue;
+            };
+            if self.try_get_header(rwtxn, commitment)?.is_none() {
+                continue;
+            }
+            let mut bmm_results = self.get_bmm_results(rwtxn, commitment)?;
+            if !bmm_results.contains_key(&main_hash) {
+                bmm_results.insert(main_hash, BmmResult::Failed);
+                self.bmm_results.put(rwtxn, &commitment, &bmm_results)?;
+            }
+        }
+        Ok(())
+    }
+
+    /** Median of the timestamps of the specified block and its ancestors,
+     *  over the last [`MEDIAN_TIME_PAST_WINDOW`] blocks.
+     *  Returns `None` for the zero block hash. */
+    pub fn get_median_time_past(
+        &self,
+        rotxn: &RoTxn,
+        block_hash: BlockHash,
+    ) -> Result<Option<u64>, Error> {
+        let mut timestamps: Vec<u64> = self
+            .ancestors(rotxn, block_hash)
+            .take(MEDIAN_TIME_PAST_WINDOW)
+            .map(|block_hash| {
+                let header = self.get_header(rotxn, block_hash)?;
+                Ok(header.timestamp)
+            })
+            .collect()?;
+        if timestamps.is_empty() {
+            return Ok(None);
+        }
+        timestamps.sort_unstable();
+        Ok(Some(timestamps[timestamps.len() / 2]))
+    }
+
+    /** Time of the specified mainchain block, if it is known.
+     *  Returns `None` for the zero block hash. */
+    pub fn try_get_main_block_time(
+        &self,
+        rotxn: &RoTxn,
+        block_hash: bitcoin::BlockHash,
+    ) -> Result<Option<u64>, Error> {
+        let main_header = self.try_get_main_header(rotxn, block_hash)?;
+        Ok(main_header.map(|main_header| main_header.time as u64))
+    }
+
+    /// Check a header's timestamp, as in [`check_header_timestamp`].
+    /// Ancestor headers MUST be stored.
+    pub fn validate_header_timestamp(
+        &self,
+        rotxn: &RoTxn,
+        header: &Header,
+    ) -> Result<(), Error> {
+        let median_time_past =
+            self.get_median_time_past(rotxn, header.prev_side_hash)?;
+        let main_block_time =
+            if header.prev_main_hash == bitcoin::BlockHash::all_zeros() {
+                None
+            } else {
+                Some(
+                    self.try_get_main_block_time(rotxn, header.prev_main_hash)?
+                        .ok_or(Error::NoMainHeader(header.prev_main_hash))?,
+                )
+            };
+        let () = check_header_timestamp(
+            header.timestamp,
+            median_time_past,
+            main_block_time,
+        )?;
+        Ok(())
+    }
+
     /// Return a fallible iterator over ancestors of a block,
     /// starting with the specified block's header
     pub fn ancestors<'a>(
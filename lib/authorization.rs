@@ -1,5 +1,8 @@
 use borsh::BorshSerialize;
-use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
+use rayon::iter::{
+    IndexedParallelIterator as _, IntoParallelRefIterator as _,
+    ParallelIterator as _,
+};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -17,6 +20,12 @@ pub enum Error {
     BorshSerialize(#[from] borsh::io::Error),
     #[error("ed25519_dalek error")]
     DalekError(#[from] SignatureError),
+    #[error("invalid signature for authorization {index}")]
+    InvalidSignature {
+        /// Index of the authorization with an invalid signature
+        index: usize,
+        source: SignatureError,
+    },
     #[error(
         "wrong key for address: address = {address},
              hash(verifying_key) = {hash_verifying_key}"
@@ -95,6 +104,27 @@ pub fn get_address(verifying_key: &VerifyingKey) -> Address {
     Address(output)
 }
 
+/// Verify each signature individually, after batch verification failed, in
+/// order to identify the first invalid signature.
+/// If no invalid signature is found, the batch verification error is
+/// returned.
+fn find_invalid_signature(
+    pairs: &[(&Authorization, &[u8])],
+    batch_err: SignatureError,
+) -> Error {
+    pairs
+        .par_iter()
+        .enumerate()
+        .find_map_first(|(index, (authorization, message))| {
+            authorization
+                .verifying_key
+                .verify(message, &authorization.signature)
+                .err()
+                .map(|source| Error::InvalidSignature { index, source })
+        })
+        .unwrap_or(Error::DalekError(batch_err))
+}
+
 struct Package<'a> {
     messages: Vec<&'a [u8]>,
     signatures: Vec<Signature>,
@@ -119,7 +149,13 @@ pub fn verify_authorized_transaction(
                  }| (verifying_key, signature),
             )
             .unzip();
-    ed25519_dalek::verify_batch(&messages, &signatures, &verifying_keys)?;
+    if let Err(err) =
+        ed25519_dalek::verify_batch(&messages, &signatures, &verifying_keys)
+    {
+        let pairs: Vec<_> =
+            transaction.authorizations.iter().zip(messages).collect();
+        return Err(find_invalid_signature(&pairs, err));
+    }
     Ok(())
 }
 
@@ -175,7 +211,7 @@ pub fn verify_authorizations(body: &Body) -> Result<(), Error> {
         packages.iter().map(|p| p.signatures.len()).sum::<usize>(),
         body.authorizations.len()
     );
-    packages
+    let res = packages
         .par_iter()
         .map(
             |Package {
@@ -190,7 +226,10 @@ pub fn verify_authorizations(body: &Body) -> Result<(), Error> {
                 )
             },
         )
-        .collect::<Result<(), SignatureError>>()?;
+        .collect::<Result<(), SignatureError>>();
+    if let Err(err) = res {
+        return Err(find_invalid_signature(&pairs, err));
+    }
     Ok(())
 }
 
@@ -0,0 +1,112 @@
+//! Benchmark connecting a block, with and without re-using the filled txs
+//! from validation.
+//! Run with `cargo bench -p plain_bitassets`.
+
+#![feature(test)]
+
+extern crate test;
+
+use plain_bitassets::{
+    bip300301::bitcoin::{self, hashes::Hash as _},
+    state::{Genesis, GenesisBitAsset},
+    testutil::{TestKey, TestState},
+    types::{
+        AssetId, AuthorizedTransaction, BitAssetData, Body, Header, Output,
+        OutputContent, Transaction,
+    },
+};
+use test::Bencher;
+
+/// Number of txs in the benchmarked block
+const NUM_TXS: usize = 256;
+
+/// Number of inputs in each tx
+const INPUTS_PER_TX: usize = 4;
+
+/** State with `NUM_TXS * INPUTS_PER_TX` genesis BitAsset UTXOs, and a block
+ *  containing `NUM_TXS` txs spending them. */
+fn setup(name: &str) -> (TestState, Header, Body) {
+    let key = TestKey::from_seed(1);
+    let genesis = Genesis {
+        bitassets: vec![GenesisBitAsset {
+            plain_name: "asset".to_owned(),
+            bitasset_data: BitAssetData::default(),
+            control_address: key.address,
+            balances: vec![(key.address, 1_000); NUM_TXS * INPUTS_PER_TX],
+        }],
+        ..Default::default()
+    };
+    let test_state = TestState::new(name, &genesis).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    let mut utxos: Vec<_> = test_state
+        .state
+        .get_utxos(&rotxn)
+        .unwrap()
+        .into_iter()
+        .filter(|(_, output)| {
+            matches!(output.asset_value(), Some((AssetId::BitAsset(_), _)))
+        })
+        .map(|(outpoint, _)| outpoint)
+        .collect();
+    utxos.sort();
+    let txs: Vec<AuthorizedTransaction> = utxos
+        .chunks(INPUTS_PER_TX)
+        .map(|inputs| {
+            let output = Output::new(
+                key.address,
+                OutputContent::BitAsset(1_000 * inputs.len() as u64),
+            );
+            let tx = Transaction::new(inputs.to_vec(), vec![output]);
+            key.authorize(tx).unwrap()
+        })
+        .collect();
+    assert_eq!(txs.len(), NUM_TXS);
+    let body = Body::new(txs, Vec::new());
+    let header = Header {
+        merkle_root: body.compute_merkle_root(),
+        prev_side_hash: test_state.state.get_tip(&rotxn).unwrap(),
+        prev_main_hash: bitcoin::BlockHash::all_zeros(),
+        timestamp: 0,
+    };
+    drop(rotxn);
+    (test_state, header, body)
+}
+
+/// Validate and connect the block, filling txs again when connecting.
+/// The write txn is aborted after each iteration.
+#[bench]
+fn validate_connect_block(b: &mut Bencher) {
+    let (test_state, header, body) = setup("bench_validate_connect_block");
+    b.iter(|| {
+        let mut rwtxn = test_state.env.write_txn().unwrap();
+        let _ = test_state
+            .state
+            .validate_block(&rwtxn, &header, &body)
+            .unwrap();
+        let () = test_state
+            .state
+            .connect_block(&mut rwtxn, &header, &body)
+            .unwrap();
+        rwtxn.abort();
+    })
+}
+
+/// Validate and connect the block, re-using the filled txs from validation.
+/// The write txn is aborted after each iteration.
+#[bench]
+fn validate_connect_filled_block(b: &mut Bencher) {
+    let (test_state, header, body) =
+        setup("bench_validate_connect_filled_block");
+    b.iter(|| {
+        let mut rwtxn = test_state.env.write_txn().unwrap();
+        let (_fees, filled_txs) = test_state
+            .state
+            .validate_block(&rwtxn, &header, &body)
+            .unwrap();
+        let () = test_state
+            .state
+            .connect_filled_block(&mut rwtxn, &header, &body, filled_txs)
+            .unwrap();
+        rwtxn.abort();
+    })
+}
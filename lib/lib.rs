@@ -10,6 +10,8 @@ pub mod miner;
 pub mod net;
 pub mod node;
 pub mod state;
+#[cfg(feature = "test-util")]
+pub mod testutil;
 pub mod types;
 pub mod util;
 pub mod wallet;
@@ -1,21 +1,136 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
-use heed::{types::SerdeBincode, Database, RoTxn, RwTxn};
+use heed::{
+    types::{Bytes, SerdeBincode, Unit},
+    Database, RoTxn, RwTxn,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-use crate::types::{
-    Address, AuthorizedTransaction, InPoint, OutPoint, Output, Txid,
+use crate::{
+    types::{Address, AuthorizedTransaction, InPoint, OutPoint, Output, Txid},
+    util::UnitKey,
 };
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("bincode error")]
+    Bincode(#[from] bincode::Error),
+    #[error(
+        "mempool is full, and the fee rate of tx {txid} is too low to evict \
+         other txs"
+    )]
+    FeeRateTooLow { txid: Txid },
     #[error("heed error")]
     Heed(#[from] heed::Error),
+    #[error(
+        "tx {txid} pays a fee of {fee}, but must pay more than the total fee \
+         of {replaced_fee} paid by the txs that it replaces, including their \
+         descendants"
+    )]
+    InsufficientReplacementFee {
+        txid: Txid,
+        fee: u64,
+        replaced_fee: u64,
+    },
+    #[error(
+        "tx {txid} must pay a higher fee rate than tx {conflicting_txid}, \
+         which it replaces"
+    )]
+    InsufficientReplacementFeeRate { txid: Txid, conflicting_txid: Txid },
     #[error("Missing transaction {0}")]
     MissingTransaction(Txid),
     #[error("can't add transaction, utxo double spent")]
     UtxoDoubleSpent,
 }
 
+impl Error {
+    /// Returns `true` if the error is due to a conflict with a tx that is
+    /// already in the mempool
+    pub fn is_conflict(&self) -> bool {
+        matches!(
+            self,
+            Self::InsufficientReplacementFee { .. }
+                | Self::InsufficientReplacementFeeRate { .. }
+                | Self::UtxoDoubleSpent
+        )
+    }
+}
+
+/// Limits on the size of the mempool.
+/// Once a limit is exceeded, txs with the lowest fee rates are evicted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MemPoolLimits {
+    /// Max number of txs in the mempool
+    pub max_txs: u64,
+    /// Max total serialized size of txs in the mempool, in bytes
+    pub max_bytes: u64,
+}
+
+impl MemPoolLimits {
+    pub const DEFAULT_MAX_TXS: u64 = 100_000;
+    pub const DEFAULT_MAX_BYTES: u64 = 300 * 1024 * 1024;
+}
+
+impl Default for MemPoolLimits {
+    fn default() -> Self {
+        Self {
+            max_txs: Self::DEFAULT_MAX_TXS,
+            max_bytes: Self::DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+/// Current size and capacity of the mempool
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ToSchema,
+)]
+pub struct MemPoolInfo {
+    /// Number of txs in the mempool
+    pub num_txs: u64,
+    /// Total serialized size of txs in the mempool, in bytes
+    pub bytes: u64,
+    /// Max number of txs in the mempool
+    pub max_txs: u64,
+    /// Max total serialized size of txs in the mempool, in bytes
+    pub max_bytes: u64,
+}
+
+/// Fee and serialized size of a tx in the mempool
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct FeeAndSize {
+    /// Fee in sats
+    fee: u64,
+    /// Serialized size, in bytes
+    size: u64,
+}
+
+impl FeeAndSize {
+    /// Compare fee rates, without division
+    fn cmp_fee_rate(&self, other: &Self) -> std::cmp::Ordering {
+        let fee_rate = self.fee as u128 * other.size as u128;
+        let other_fee_rate = other.fee as u128 * self.size as u128;
+        fee_rate.cmp(&other_fee_rate)
+    }
+
+    /// Key for the fee rate index. Keys sort by fee rate, as a big-endian
+    /// fixed-point number with 64 fractional bits, and then by txid.
+    fn fee_rate_key(&self, txid: Txid) -> [u8; 48] {
+        let fee_rate = ((self.fee as u128) << 64) / self.size.max(1) as u128;
+        let mut key = [0; 48];
+        key[..16].copy_from_slice(&fee_rate.to_be_bytes());
+        key[16..].copy_from_slice(&txid.0);
+        key
+    }
+}
+
+/// Number and total serialized size of txs in the mempool
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+struct Totals {
+    num_txs: u64,
+    bytes: u64,
+}
+
 #[derive(Clone)]
 pub struct MemPool {
     pub transactions:
@@ -24,12 +139,24 @@ pub struct MemPool {
     /// Associates relevant txs to each address
     address_to_txs:
         Database<SerdeBincode<Address>, SerdeBincode<HashSet<Txid>>>,
+    /// Last block height at which each tx can be included in a block.
+    /// Txs without an expiry height do not expire.
+    expiry_heights: Database<SerdeBincode<Txid>, SerdeBincode<u32>>,
+    /// Fee and serialized size of each tx
+    fees_and_sizes: Database<SerdeBincode<Txid>, SerdeBincode<FeeAndSize>>,
+    /// Txs ordered by fee rate, lowest first.
+    /// See [`FeeAndSize::fee_rate_key`].
+    fee_rate_index: Database<Bytes, Unit>,
+    /// Running totals, so that the size of the mempool can be checked
+    /// without scanning every tx
+    totals: Database<SerdeBincode<UnitKey>, SerdeBincode<Totals>>,
+    limits: MemPoolLimits,
 }
 
 impl MemPool {
-    pub const NUM_DBS: u32 = 3;
+    pub const NUM_DBS: u32 = 7;
 
-    pub fn new(env: &heed::Env) -> Result<Self, Error> {
+    pub fn new(env: &heed::Env, limits: MemPoolLimits) -> Result<Self, Error> {
         let mut rwtxn = env.write_txn()?;
         let transactions =
             env.create_database(&mut rwtxn, Some("transactions"))?;
@@ -37,12 +164,82 @@ impl MemPool {
             env.create_database(&mut rwtxn, Some("spent_utxos"))?;
         let address_to_txs =
             env.create_database(&mut rwtxn, Some("address_to_txs"))?;
-        rwtxn.commit()?;
-        Ok(Self {
+        let expiry_heights =
+            env.create_database(&mut rwtxn, Some("mempool_expiry_heights"))?;
+        let fees_and_sizes =
+            env.create_database(&mut rwtxn, Some("mempool_fees_and_sizes"))?;
+        let fee_rate_index =
+            env.create_database(&mut rwtxn, Some("mempool_fee_rate_index"))?;
+        let totals = env.create_database(&mut rwtxn, Some("mempool_totals"))?;
+        let mempool = Self {
             transactions,
             spent_utxos,
             address_to_txs,
-        })
+            expiry_heights,
+            fees_and_sizes,
+            fee_rate_index,
+            totals,
+            limits,
+        };
+        // Mempools created before the totals and fee rate index were
+        // maintained must be indexed once
+        if mempool.totals.get(&rwtxn, &UnitKey)?.is_none() {
+            let () = mempool.rebuild_index(&mut rwtxn)?;
+        }
+        rwtxn.commit()?;
+        Ok(mempool)
+    }
+
+    /// Rebuild the totals and fee rate index from the stored txs
+    fn rebuild_index(&self, rwtxn: &mut RwTxn) -> Result<(), Error> {
+        self.fee_rate_index.clear(rwtxn)?;
+        self.totals.put(rwtxn, &UnitKey, &Totals::default())?;
+        let txs = self.take_all(rwtxn)?;
+        for tx in txs {
+            let txid = tx.transaction.txid();
+            let fee_and_size = self.get_fee_and_size(rwtxn, txid, &tx)?;
+            let () = self.index_tx(rwtxn, txid, fee_and_size)?;
+        }
+        Ok(())
+    }
+
+    fn get_totals(&self, rotxn: &RoTxn) -> Result<Totals, Error> {
+        let totals = self.totals.get(rotxn, &UnitKey)?.unwrap_or_default();
+        Ok(totals)
+    }
+
+    /// Add a tx to the fee rate index and totals
+    fn index_tx(
+        &self,
+        rwtxn: &mut RwTxn,
+        txid: Txid,
+        fee_and_size: FeeAndSize,
+    ) -> Result<(), Error> {
+        let key = fee_and_size.fee_rate_key(txid);
+        self.fee_rate_index.put(rwtxn, &key, &())?;
+        let mut totals = self.get_totals(rwtxn)?;
+        totals.num_txs += 1;
+        totals.bytes += fee_and_size.size;
+        self.totals.put(rwtxn, &UnitKey, &totals)?;
+        Ok(())
+    }
+
+    /// Remove a tx from the fee rate index and totals
+    fn unindex_tx(
+        &self,
+        rwtxn: &mut RwTxn,
+        txid: Txid,
+        fee_and_size: FeeAndSize,
+    ) -> Result<(), Error> {
+        let key = fee_and_size.fee_rate_key(txid);
+        if !self.fee_rate_index.delete(rwtxn, &key)? {
+            return Ok(());
+        }
+        let mut totals = self.get_totals(rwtxn)?;
+        totals.num_txs -= 1;
+        totals.bytes -= fee_and_size.size;
+        self.totals.put(rwtxn, &UnitKey, &totals)?;
+        Ok(())
     }
 
     /// Stores STXOs, checking for double spends
@@ -142,13 +339,27 @@ impl MemPool {
         })
     }
 
+    /** Add a tx paying the specified fee to the mempool.
+     *  If the tx spends any inputs that are spent by txs already in the
+     *  mempool, the tx replaces those txs, and any of their descendants.
+     *  The tx must pay a strictly higher fee rate than each tx that it
+     *  conflicts with, and a strictly higher fee than the total fee paid by
+     *  all of the txs that it replaces, including descendants.
+     *  If an expiry height is specified, the tx is dropped once it can no
+     *  longer be included in a block at or below the expiry height.
+     *  If the mempool limits are exceeded, txs are evicted until they are
+     *  not. See [`Self::evict`].
+     *  Returns the txids of evicted txs, which may include the added tx. */
     pub fn put(
         &self,
         rwtxn: &mut RwTxn,
         transaction: &AuthorizedTransaction,
-    ) -> Result<(), Error> {
+        fee: u64,
+        expiry_height: Option<u32>,
+    ) -> Result<Vec<Txid>, Error> {
         let txid = transaction.transaction.txid();
         tracing::debug!("adding transaction {txid} to mempool");
+        let () = self.replace_conflicts(rwtxn, transaction, fee)?;
         let stxos = {
             let txid = transaction.transaction.txid();
             transaction.transaction.inputs.iter().enumerate().map(
@@ -165,17 +376,237 @@ impl MemPool {
         };
         let () = self.put_stxos(rwtxn, stxos)?;
         self.transactions.put(rwtxn, &txid, transaction)?;
+        if let Some(expiry_height) = expiry_height {
+            self.expiry_heights.put(rwtxn, &txid, &expiry_height)?;
+        }
+        let fee_and_size = FeeAndSize {
+            fee,
+            size: bincode::serialized_size(transaction)?,
+        };
+        self.fees_and_sizes.put(rwtxn, &txid, &fee_and_size)?;
+        let () = self.index_tx(rwtxn, txid, fee_and_size)?;
         let () = self.assoc_tx_with_relevant_addresses(rwtxn, transaction)?;
+        self.evict(rwtxn)
+    }
+
+    /** Delete txs that spend any of the same inputs as the tx, and their
+     *  descendants, if the tx pays a strictly higher fee rate than each
+     *  conflicting tx, and a strictly higher fee than the total fee paid by
+     *  the conflicting txs and their descendants. */
+    fn replace_conflicts(
+        &self,
+        rwtxn: &mut RwTxn,
+        transaction: &AuthorizedTransaction,
+        fee: u64,
+    ) -> Result<(), Error> {
+        let mut conflicting_txids = HashSet::new();
+        for input in &transaction.transaction.inputs {
+            if let Some(InPoint::Regular { txid, .. }) =
+                self.spent_utxos.get(rwtxn, input)?
+            {
+                conflicting_txids.insert(txid);
+            }
+        }
+        if conflicting_txids.is_empty() {
+            return Ok(());
+        }
+        let txid = transaction.transaction.txid();
+        let fee_and_size = FeeAndSize {
+            fee,
+            size: bincode::serialized_size(transaction)?,
+        };
+        for conflicting_txid in &conflicting_txids {
+            let conflicting_tx = self
+                .transactions
+                .get(rwtxn, conflicting_txid)?
+                .ok_or(Error::MissingTransaction(*conflicting_txid))?;
+            let conflicting_fee_and_size = self.get_fee_and_size(
+                rwtxn,
+                *conflicting_txid,
+                &conflicting_tx,
+            )?;
+            if !fee_and_size.cmp_fee_rate(&conflicting_fee_and_size).is_gt() {
+                return Err(Error::InsufficientReplacementFeeRate {
+                    txid,
+                    conflicting_txid: *conflicting_txid,
+                });
+            }
+        }
+        let mut replaced_fee: u64 = 0;
+        for replaced_txid in
+            self.descendants(rwtxn, conflicting_txids.iter().copied())?
+        {
+            let replaced_tx = self
+                .transactions
+                .get(rwtxn, &replaced_txid)?
+                .ok_or(Error::MissingTransaction(replaced_txid))?;
+            let FeeAndSize { fee, size: _ } =
+                self.get_fee_and_size(rwtxn, replaced_txid, &replaced_tx)?;
+            replaced_fee = replaced_fee.saturating_add(fee);
+        }
+        if fee <= replaced_fee {
+            return Err(Error::InsufficientReplacementFee {
+                txid,
+                fee,
+                replaced_fee,
+            });
+        }
+        for conflicting_txid in conflicting_txids {
+            tracing::debug!(
+                %txid,
+                replaced_txid = %conflicting_txid,
+                "replacing tx in mempool"
+            );
+            let () = self.delete(rwtxn, conflicting_txid)?;
+        }
         Ok(())
     }
 
+    /// The specified txs, and all txs in the mempool that spend their
+    /// outputs, directly or indirectly
+    fn descendants<Iter>(
+        &self,
+        rotxn: &RoTxn,
+        txids: Iter,
+    ) -> Result<HashSet<Txid>, Error>
+    where
+        Iter: IntoIterator<Item = Txid>,
+    {
+        let mut descendants = HashSet::new();
+        let mut pending: VecDeque<Txid> = txids.into_iter().collect();
+        while let Some(txid) = pending.pop_front() {
+            if !descendants.insert(txid) {
+                continue;
+            }
+            let tx = self
+                .transactions
+                .get(rotxn, &txid)?
+                .ok_or(Error::MissingTransaction(txid))?;
+            for vout in 0..tx.transaction.outputs.len() {
+                let outpoint = OutPoint::Regular {
+                    txid,
+                    vout: vout as u32,
+                };
+                if let Some(InPoint::Regular {
+                    txid: child_txid, ..
+                }) = self.spent_utxos.get(rotxn, &outpoint)?
+                {
+                    pending.push_back(child_txid);
+                }
+            }
+        }
+        Ok(descendants)
+    }
+
+    /// Fee and size of a tx in the mempool.
+    /// Txs that were added before fees were recorded are assumed to pay no
+    /// fee.
+    fn get_fee_and_size(
+        &self,
+        rotxn: &RoTxn,
+        txid: Txid,
+        tx: &AuthorizedTransaction,
+    ) -> Result<FeeAndSize, Error> {
+        match self.fees_and_sizes.get(rotxn, &txid)? {
+            Some(fee_and_size) => Ok(fee_and_size),
+            None => Ok(FeeAndSize {
+                fee: 0,
+                size: bincode::serialized_size(tx)?,
+            }),
+        }
+    }
+
+    /// Returns `true` if an output of the tx is spent by another tx in the
+    /// mempool
+    fn has_children(
+        &self,
+        rotxn: &RoTxn,
+        txid: Txid,
+        tx: &AuthorizedTransaction,
+    ) -> Result<bool, Error> {
+        for vout in 0..tx.transaction.outputs.len() {
+            let outpoint = OutPoint::Regular {
+                txid,
+                vout: vout as u32,
+            };
+            if self.spent_utxos.get(rotxn, &outpoint)?.is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /** Evict txs until the mempool limits are no longer exceeded.
+     *  The tx with the lowest fee rate, among txs whose outputs are not spent
+     *  by other txs in the mempool, is evicted first. A tx is therefore
+     *  never evicted while a tx that depends on it remains in the mempool.
+     *  Txs with equal fee rates are evicted in txid order.
+     *  Returns the txids of evicted txs. */
+    fn evict(&self, rwtxn: &mut RwTxn) -> Result<Vec<Txid>, Error> {
+        let mut evicted = Vec::new();
+        loop {
+            let Totals { num_txs, bytes } = self.get_totals(rwtxn)?;
+            if num_txs <= self.limits.max_txs && bytes <= self.limits.max_bytes
+            {
+                break;
+            }
+            let mut lowest = None;
+            for item in self.fee_rate_index.iter(rwtxn)? {
+                let (key, ()) = item?;
+                let txid = Txid(key[16..].try_into().unwrap());
+                let tx = self
+                    .transactions
+                    .get(rwtxn, &txid)?
+                    .ok_or(Error::MissingTransaction(txid))?;
+                if !self.has_children(rwtxn, txid, &tx)? {
+                    lowest = Some(txid);
+                    break;
+                }
+            }
+            // The mempool is acyclic, so there is at least one tx without
+            // children, unless the mempool is empty
+            let Some(txid) = lowest else {
+                break;
+            };
+            tracing::debug!(%txid, "evicting tx from mempool");
+            let () = self.delete(rwtxn, txid)?;
+            evicted.push(txid);
+        }
+        Ok(evicted)
+    }
+
+    /// Current size and capacity of the mempool
+    pub fn info(&self, rotxn: &RoTxn) -> Result<MemPoolInfo, Error> {
+        let Totals { num_txs, bytes } = self.get_totals(rotxn)?;
+        Ok(MemPoolInfo {
+            num_txs,
+            bytes,
+            max_txs: self.limits.max_txs,
+            max_bytes: self.limits.max_bytes,
+        })
+    }
+
+    /// Last block height at which the tx can be included in a block, if any
+    pub fn try_get_expiry_height(
+        &self,
+        rotxn: &RoTxn,
+        txid: &Txid,
+    ) -> Result<Option<u32>, Error> {
+        let expiry_height = self.expiry_heights.get(rotxn, txid)?;
+        Ok(expiry_height)
+    }
+
     pub fn delete(&self, rwtxn: &mut RwTxn, txid: Txid) -> Result<(), Error> {
         let mut pending_deletes = VecDeque::from([txid]);
         while let Some(txid) = pending_deletes.pop_front() {
             if let Some(tx) = self.transactions.get(rwtxn, &txid)? {
                 let () = self.delete_stxos(rwtxn, &tx.transaction.inputs)?;
                 let () = self.unassoc_tx_with_relevant_addresses(rwtxn, &tx)?;
+                let fee_and_size = self.get_fee_and_size(rwtxn, txid, &tx)?;
+                let () = self.unindex_tx(rwtxn, txid, fee_and_size)?;
                 self.transactions.delete(rwtxn, &txid)?;
+                self.expiry_heights.delete(rwtxn, &txid)?;
+                self.fees_and_sizes.delete(rwtxn, &txid)?;
                 for vout in 0..tx.transaction.outputs.len() {
                     let outpoint = OutPoint::Regular {
                         txid,
@@ -1,7 +1,6 @@
 use std::{net::SocketAddr, str::FromStr as _, time::Duration};
 
 use bip300301::{bitcoin, Drivechain, MainClient as _};
-use bitcoin::hashes::Hash as _;
 
 use crate::types::*;
 
@@ -54,8 +53,7 @@ impl Miner {
         body: Body,
     ) -> Result<bitcoin::Txid, Error> {
         let str_hash_prev = header.prev_main_hash.to_string();
-        let critical_hash: [u8; 32] = header.hash().into();
-        let critical_hash = bitcoin::BlockHash::from_byte_array(critical_hash);
+        let critical_hash = header.bmm_critical_hash();
         let amount = bitcoin::Amount::from_sat(amount);
         let prev_bytes = &str_hash_prev[str_hash_prev.len() - 8..];
         let value = self
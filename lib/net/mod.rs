@@ -1,18 +1,18 @@
 use std::{
-    collections::{hash_map, HashMap, HashSet},
-    net::SocketAddr,
+    collections::{hash_map, HashMap, HashSet, VecDeque},
+    net::{IpAddr, SocketAddr},
     sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 
 use fallible_iterator::{FallibleIterator, IteratorExt};
 use futures::{channel::mpsc, StreamExt};
-use heed::{
-    types::{SerdeBincode, Unit},
-    Database,
-};
+use heed::{types::SerdeBincode, Database};
 use parking_lot::RwLock;
 use quinn::{ClientConfig, Endpoint, ServerConfig};
+use serde::{Deserialize, Serialize};
 use tokio_stream::StreamNotifyClose;
+use utoipa::ToSchema;
 
 use crate::{
     archive::Archive,
@@ -29,8 +29,8 @@ use peer::{
 };
 pub use peer::{
     ConnectionError as PeerConnectionError, Info as PeerConnectionInfo,
-    InternalMessage as PeerConnectionMessage, PeerStateId,
-    Request as PeerRequest, Response as PeerResponse,
+    InternalMessage as PeerConnectionMessage, Misbehavior as PeerMisbehavior,
+    PeerStateId, Request as PeerRequest, Response as PeerResponse,
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -53,6 +53,8 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error("peer connection not found for {0}")]
     MissingPeerConnection(SocketAddr),
+    #[error("peer at {0} is banned")]
+    PeerBanned(SocketAddr),
     #[error("peer connection")]
     PeerConnection(#[from] PeerConnectionError),
     #[error("quinn rustls error")]
@@ -65,6 +67,8 @@ pub enum Error {
     SendDatagram(#[from] quinn::SendDatagramError),
     #[error("server endpoint closed")]
     ServerEndpointClosed,
+    #[error("outbound peer limit ({max}) reached")]
+    TooManyOutboundPeers { max: usize },
     #[error("write error")]
     Write(#[from] quinn::WriteError),
 }
@@ -95,6 +99,82 @@ const fn seed_node_addrs(network: Network) -> &'static [SocketAddr] {
     }
 }
 
+/// Limits on the number of peer connections
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PeerLimits {
+    /// Max number of connections accepted from peers
+    pub max_inbound: usize,
+    /// Max number of connections made to peers
+    pub max_outbound: usize,
+}
+
+impl PeerLimits {
+    pub const DEFAULT_MAX_INBOUND: usize = 64;
+    pub const DEFAULT_MAX_OUTBOUND: usize = 16;
+}
+
+impl Default for PeerLimits {
+    fn default() -> Self {
+        Self {
+            max_inbound: Self::DEFAULT_MAX_INBOUND,
+            max_outbound: Self::DEFAULT_MAX_OUTBOUND,
+        }
+    }
+}
+
+/// Score of a peer that has not misbehaved. Each protocol violation reduces
+/// the score by the violation's penalty, and peers with a score of zero are
+/// disconnected and banned.
+pub const INITIAL_PEER_SCORE: u32 = 100;
+
+/// Duration for which a peer is banned, once its score reaches zero
+const PEER_BAN_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Max number of addresses in the address book. Once full, the address
+/// that was least recently connected to is evicted.
+pub const MAX_KNOWN_PEERS: usize = 1000;
+
+/// Max number of unverified addresses, learned from peers, that are kept
+/// as candidates for outbound connections. Once full, the oldest candidate
+/// is evicted.
+const MAX_PEER_ADDR_CANDIDATES: usize = 1000;
+
+/// Seconds since the unix epoch
+fn unix_time_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Number of active peer connections in each direction
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PeerCounts {
+    pub inbound: usize,
+    pub outbound: usize,
+}
+
+/// Whether a peer connection was accepted from, or made to, the peer
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionDirection {
+    Inbound,
+    Outbound,
+}
+
+struct ActivePeer {
+    connection_handle: PeerConnectionHandle,
+    direction: ConnectionDirection,
+    connected_at: Instant,
+    /// `false` until an outbound connection is established
+    established: bool,
+    /// Last time that the peer sent a new tip or tx
+    last_useful: Option<Instant>,
+    /// Address on which the peer accepts connections, if known
+    listen_addr: Option<SocketAddr>,
+}
+
 // State.
 // Archive.
 
@@ -113,12 +193,24 @@ pub struct Net {
     pub client: Endpoint,
     pub server: Endpoint,
     archive: Archive,
+    network: Network,
     state: State,
-    active_peers: Arc<RwLock<HashMap<SocketAddr, PeerConnectionHandle>>>,
+    active_peers: Arc<RwLock<HashMap<SocketAddr, ActivePeer>>>,
+    peer_limits: PeerLimits,
+    /// Scores of peers that have misbehaved, by IP address.
+    /// Peers that are not present have the initial score.
+    peer_scores: Arc<RwLock<HashMap<IpAddr, u32>>>,
+    /// Banned peers, and the time at which each ban expires
+    banned_peers: Arc<RwLock<HashMap<IpAddr, Instant>>>,
     // None indicates that the stream has ended
     peer_info_tx:
         mpsc::UnboundedSender<(SocketAddr, Option<PeerConnectionInfo>)>,
-    known_peers: Database<SerdeBincode<SocketAddr>, Unit>,
+    /// Address book of peers that this node has connected to, and the time
+    /// of the last connection to each, in seconds since the unix epoch.
+    /// Seed nodes are always present.
+    known_peers: Database<SerdeBincode<SocketAddr>, SerdeBincode<u64>>,
+    /// Unverified addresses learned from peers, oldest first
+    peer_addr_candidates: Arc<RwLock<VecDeque<SocketAddr>>>,
 }
 
 impl Net {
@@ -127,22 +219,333 @@ impl Net {
     fn add_active_peer(
         &self,
         addr: SocketAddr,
-        peer_connection_handle: PeerConnectionHandle,
+        connection_handle: PeerConnectionHandle,
+        direction: ConnectionDirection,
     ) -> Result<(), Error> {
         let mut active_peers_write = self.active_peers.write();
         match active_peers_write.entry(addr) {
             hash_map::Entry::Occupied(_) => Err(Error::AlreadyConnected(addr)),
             hash_map::Entry::Vacant(active_peer_entry) => {
-                active_peer_entry.insert(peer_connection_handle);
+                let listen_addr = match direction {
+                    ConnectionDirection::Inbound => None,
+                    ConnectionDirection::Outbound => Some(addr),
+                };
+                active_peer_entry.insert(ActivePeer {
+                    connection_handle,
+                    direction,
+                    connected_at: Instant::now(),
+                    established: direction == ConnectionDirection::Inbound,
+                    last_useful: None,
+                    listen_addr,
+                });
                 Ok(())
             }
         }
     }
 
+    /// Number of currently connected peers
+    pub fn num_active_peers(&self) -> usize {
+        self.active_peers.read().len()
+    }
+
+    /// Addresses of currently connected peers, and the direction of each
+    /// connection, ordered by address
+    pub fn active_peers(&self) -> Vec<(SocketAddr, ConnectionDirection)> {
+        let mut active_peers: Vec<_> = self
+            .active_peers
+            .read()
+            .iter()
+            .map(|(addr, active_peer)| (*addr, active_peer.direction))
+            .collect();
+        active_peers.sort_unstable_by_key(|(addr, _)| *addr);
+        active_peers
+    }
+
+    /// Number of currently connected peers in each direction
+    pub fn peer_counts(&self) -> PeerCounts {
+        let active_peers_read = self.active_peers.read();
+        let inbound = active_peers_read
+            .values()
+            .filter(|peer| peer.direction == ConnectionDirection::Inbound)
+            .count();
+        PeerCounts {
+            inbound,
+            outbound: active_peers_read.len() - inbound,
+        }
+    }
+
+    pub fn peer_limits(&self) -> PeerLimits {
+        self.peer_limits
+    }
+
+    /// Addresses in the address book
+    pub fn known_peers(
+        &self,
+        rotxn: &heed::RoTxn,
+    ) -> Result<Vec<SocketAddr>, Error> {
+        let known_peers = self
+            .known_peers
+            .iter(rotxn)?
+            .map(|res| res.map(|(addr, _last_connected)| addr))
+            .collect::<Result<_, _>>()?;
+        Ok(known_peers)
+    }
+
+    /// Evict the least recently connected peers from the address book,
+    /// until there is space for a new address. Seed nodes are not evicted.
+    fn evict_known_peers(&self, rwtxn: &mut heed::RwTxn) -> Result<(), Error> {
+        let seed_node_addrs = seed_node_addrs(self.network);
+        while self.known_peers.len(rwtxn)? as usize >= MAX_KNOWN_PEERS {
+            let oldest = self
+                .known_peers
+                .iter(rwtxn)?
+                .transpose_into_fallible()
+                .filter(|(addr, _)| Ok(!seed_node_addrs.contains(addr)))
+                .min_by_key(|(_, last_connected)| Ok(*last_connected))?;
+            let Some((oldest_addr, _)) = oldest else {
+                break;
+            };
+            self.known_peers.delete(rwtxn, &oldest_addr)?;
+        }
+        Ok(())
+    }
+
+    /// Share addresses with all active peers, other than `addr`
+    fn relay_peer_addrs(&self, addr: SocketAddr, addrs: Vec<SocketAddr>) {
+        for (active_addr, active_peer) in self.active_peers.read().iter() {
+            if *active_addr == addr {
+                continue;
+            }
+            let message = PeerConnectionMessage::PeerAddrs(addrs.clone());
+            if let Err(_send_err) = active_peer
+                .connection_handle
+                .internal_message_tx
+                .unbounded_send(message)
+            {
+                tracing::warn!(
+                    "Failed to push peer addresses to peer at {active_addr}"
+                )
+            }
+        }
+    }
+
+    /** Handle an established outbound connection.
+     *  The peer is added to the address book, evicting the least recently
+     *  connected peer if necessary. If the peer was not previously known,
+     *  the address is shared with all other active peers. */
+    pub fn handle_peer_connected(
+        &self,
+        env: &heed::Env,
+        addr: SocketAddr,
+    ) -> Result<(), Error> {
+        match self.active_peers.write().get_mut(&addr) {
+            Some(active_peer)
+                if active_peer.direction == ConnectionDirection::Outbound =>
+            {
+                active_peer.established = true;
+            }
+            _ => return Ok(()),
+        }
+        let mut rwtxn = env.write_txn()?;
+        let newly_known = self.known_peers.get(&rwtxn, &addr)?.is_none();
+        if newly_known {
+            let () = self.evict_known_peers(&mut rwtxn)?;
+        }
+        self.known_peers.put(&mut rwtxn, &addr, &unix_time_secs())?;
+        rwtxn.commit()?;
+        if newly_known {
+            tracing::debug!(%addr, "Added peer to address book");
+            self.relay_peer_addrs(addr, vec![addr]);
+        }
+        Ok(())
+    }
+
+    /// Remove a peer from the address book, if an outbound connection to
+    /// the peer could not be established. Seed nodes are not removed.
+    pub fn forget_unreachable_peer(
+        &self,
+        env: &heed::Env,
+        addr: SocketAddr,
+    ) -> Result<(), Error> {
+        let unreachable =
+            self.active_peers
+                .read()
+                .get(&addr)
+                .is_some_and(|active_peer| {
+                    active_peer.direction == ConnectionDirection::Outbound
+                        && !active_peer.established
+                });
+        if !unreachable || seed_node_addrs(self.network).contains(&addr) {
+            return Ok(());
+        }
+        let mut rwtxn = env.write_txn()?;
+        if self.known_peers.delete(&mut rwtxn, &addr)? {
+            tracing::debug!(%addr, "Removed unreachable peer");
+        }
+        rwtxn.commit()?;
+        Ok(())
+    }
+
+    /// Returns `true` if the address is one on which this node accepts
+    /// connections
+    fn is_own_addr(&self, addr: SocketAddr) -> bool {
+        let Ok(local_addr) = self.server.local_addr() else {
+            return false;
+        };
+        addr == local_addr
+            || (local_addr.ip().is_unspecified()
+                && addr.ip().is_loopback()
+                && addr.port() == local_addr.port())
+    }
+
+    /** Handle peer addresses shared by a peer.
+     *  Shared addresses are unverified, so they are not added to the
+     *  address book or shared with other peers until a connection to them
+     *  is established. New addresses are added to a bounded set of
+     *  candidates, evicting the oldest, and candidates are then dialed, up
+     *  to the outbound peer limit. */
+    pub fn handle_peer_addrs(
+        &self,
+        env: &heed::Env,
+        addr: SocketAddr,
+        listen_addr: SocketAddr,
+        addrs: Vec<SocketAddr>,
+    ) -> Result<(), Error> {
+        if let Some(active_peer) = self.active_peers.write().get_mut(&addr) {
+            active_peer.listen_addr = Some(listen_addr);
+        }
+        let mut new_addrs = Vec::new();
+        {
+            let rotxn = env.read_txn()?;
+            let mut candidates_write = self.peer_addr_candidates.write();
+            for peer_addr in std::iter::once(listen_addr).chain(addrs) {
+                if peer_addr.ip().is_unspecified()
+                    || peer_addr.port() == 0
+                    || self.is_own_addr(peer_addr)
+                    || candidates_write.contains(&peer_addr)
+                    || self.known_peers.get(&rotxn, &peer_addr)?.is_some()
+                {
+                    continue;
+                }
+                candidates_write.push_back(peer_addr);
+                new_addrs.push(peer_addr);
+            }
+            let num_evicted = candidates_write
+                .len()
+                .saturating_sub(MAX_PEER_ADDR_CANDIDATES);
+            candidates_write.drain(..num_evicted);
+        }
+        if new_addrs.is_empty() {
+            return Ok(());
+        }
+        tracing::debug!(%addr, ?new_addrs, "Learned new peer addresses");
+        while self.peer_counts().outbound < self.peer_limits.max_outbound {
+            let Some(peer_addr) = self.peer_addr_candidates.write().pop_front()
+            else {
+                break;
+            };
+            let connected = self.active_peers.read().iter().any(
+                |(active_addr, active_peer)| {
+                    *active_addr == peer_addr
+                        || active_peer.listen_addr == Some(peer_addr)
+                },
+            );
+            if connected || self.is_banned(peer_addr) {
+                continue;
+            }
+            if let Err(err) = self.connect_peer(env.clone(), peer_addr) {
+                let err = anyhow::Error::from(err);
+                tracing::debug!(%peer_addr, "Failed to connect to peer: {err:#}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Current score of the peer, from zero to [`INITIAL_PEER_SCORE`]
+    pub fn peer_score(&self, addr: SocketAddr) -> u32 {
+        self.peer_scores
+            .read()
+            .get(&addr.ip())
+            .copied()
+            .unwrap_or(INITIAL_PEER_SCORE)
+    }
+
+    /// Returns `true` if the peer is currently banned
+    pub fn is_banned(&self, addr: SocketAddr) -> bool {
+        self.banned_peers
+            .read()
+            .get(&addr.ip())
+            .is_some_and(|ban_expiry| *ban_expiry > Instant::now())
+    }
+
+    /** Reduce the score of a peer that violated the protocol.
+     *  Scores are tracked by IP address, so that they persist if the peer
+     *  reconnects. If the score reaches zero, all connections to the peer
+     *  are closed, and the peer is banned for [`PEER_BAN_DURATION`]. */
+    pub fn penalize_peer(
+        &self,
+        addr: SocketAddr,
+        misbehavior: PeerMisbehavior,
+    ) {
+        let score = {
+            let mut peer_scores_write = self.peer_scores.write();
+            let score = peer_scores_write
+                .entry(addr.ip())
+                .or_insert(INITIAL_PEER_SCORE);
+            *score = score.saturating_sub(misbehavior.penalty());
+            *score
+        };
+        tracing::warn!(%addr, ?misbehavior, %score, "Penalized peer");
+        if score != 0 {
+            return;
+        }
+        tracing::warn!(%addr, "Banning peer");
+        self.banned_peers
+            .write()
+            .insert(addr.ip(), Instant::now() + PEER_BAN_DURATION);
+        // Peers start with the initial score once the ban expires
+        self.peer_scores.write().remove(&addr.ip());
+        let banned_addrs: Vec<_> = self
+            .active_peers
+            .read()
+            .keys()
+            .filter(|active_addr| active_addr.ip() == addr.ip())
+            .copied()
+            .collect();
+        for banned_addr in banned_addrs {
+            self.remove_active_peer(banned_addr);
+        }
+    }
+
+    /// Record that a peer sent a new tip or tx. Only peers that have never
+    /// done so can be evicted when the inbound peer limit is reached.
+    pub fn record_useful_peer(&self, addr: SocketAddr) {
+        if let Some(active_peer) = self.active_peers.write().get_mut(&addr) {
+            active_peer.last_useful = Some(Instant::now());
+        }
+    }
+
+    /** Select an inbound peer to evict, in order to accept a new inbound
+     *  connection. Only peers that have never sent a new tip or tx are
+     *  evicted, so that a flood of new connections cannot displace useful
+     *  peers. Of those, the peer that has been connected for longest is
+     *  selected. */
+    fn select_inbound_peer_to_evict(&self) -> Option<SocketAddr> {
+        self.active_peers
+            .read()
+            .iter()
+            .filter(|(_, peer)| {
+                peer.direction == ConnectionDirection::Inbound
+                    && peer.last_useful.is_none()
+            })
+            .min_by_key(|(_, peer)| peer.connected_at)
+            .map(|(addr, _)| *addr)
+    }
+
     pub fn remove_active_peer(&self, addr: SocketAddr) {
         let mut active_peers_write = self.active_peers.write();
-        if let Some(peer_connection) = active_peers_write.remove(&addr) {
-            drop(peer_connection);
+        if let Some(active_peer) = active_peers_write.remove(&addr) {
+            drop(active_peer);
             tracing::info!("Disconnected from peer at {addr}")
         }
     }
@@ -155,13 +558,22 @@ impl Net {
         if self.active_peers.read().contains_key(&addr) {
             return Err(Error::AlreadyConnected(addr));
         }
-        let mut rwtxn = env.write_txn()?;
-        self.known_peers.put(&mut rwtxn, &addr, &())?;
-        rwtxn.commit()?;
+        if self.is_banned(addr) {
+            return Err(Error::PeerBanned(addr));
+        }
+        if self.peer_counts().outbound >= self.peer_limits.max_outbound {
+            return Err(Error::TooManyOutboundPeers {
+                max: self.peer_limits.max_outbound,
+            });
+        }
+        // The peer is added to the address book once the connection is
+        // established
         let connection_ctxt = PeerConnectionCtxt {
             env,
             archive: self.archive.clone(),
             state: self.state.clone(),
+            known_peers: self.known_peers,
+            listen_port: self.server.local_addr()?.port(),
         };
         let (connection_handle, info_rx) =
             peer::connect(self.client.clone(), addr, connection_ctxt);
@@ -175,7 +587,11 @@ impl Net {
                 }
             }
         });
-        self.add_active_peer(addr, connection_handle)?;
+        self.add_active_peer(
+            addr,
+            connection_handle,
+            ConnectionDirection::Outbound,
+        )?;
         Ok(())
     }
 
@@ -185,19 +601,20 @@ impl Net {
         network: Network,
         state: State,
         bind_addr: SocketAddr,
+        peer_limits: PeerLimits,
     ) -> Result<(Self, PeerInfoRx), Error> {
         let (server, _) = make_server_endpoint(bind_addr)?;
         let client = make_client_endpoint("0.0.0.0:0".parse()?)?;
         let active_peers = Arc::new(RwLock::new(HashMap::new()));
         let mut rwtxn = env.write_txn()?;
         let known_peers =
-            match env.open_database(&rwtxn, Some("known_peers"))? {
+            match env.open_database(&rwtxn, Some("peer_addr_book"))? {
                 Some(known_peers) => known_peers,
                 None => {
-                    let known_peers =
-                        env.create_database(&mut rwtxn, Some("known_peers"))?;
+                    let known_peers = env
+                        .create_database(&mut rwtxn, Some("peer_addr_book"))?;
                     for seed_node_addr in seed_node_addrs(network) {
-                        known_peers.put(&mut rwtxn, seed_node_addr, &())?;
+                        known_peers.put(&mut rwtxn, seed_node_addr, &0)?;
                     }
                     known_peers
                 }
@@ -208,13 +625,19 @@ impl Net {
             server,
             client,
             archive,
+            network,
             state,
             active_peers,
+            peer_limits,
+            peer_scores: Arc::new(RwLock::new(HashMap::new())),
+            banned_peers: Arc::new(RwLock::new(HashMap::new())),
             peer_info_tx,
             known_peers,
+            peer_addr_candidates: Arc::new(RwLock::new(VecDeque::new())),
         };
+        // Most recently connected peers are dialed first
         #[allow(clippy::let_and_return)]
-        let known_peers: Vec<_> = {
+        let mut known_peers: Vec<(SocketAddr, u64)> = {
             let rotxn = env.read_txn()?;
             let known_peers = net
                 .known_peers
@@ -223,9 +646,15 @@ impl Net {
                 .collect()?;
             known_peers
         };
-        let () = known_peers.into_iter().try_for_each(|(peer_addr, _)| {
-            net.connect_peer(env.clone(), peer_addr)
-        })?;
+        known_peers.sort_by_key(|(_, last_connected)| {
+            std::cmp::Reverse(*last_connected)
+        });
+        let () = known_peers
+            .into_iter()
+            .take(peer_limits.max_outbound)
+            .try_for_each(|(peer_addr, _)| {
+                net.connect_peer(env.clone(), peer_addr)
+            })?;
         Ok((net, peer_info_rx))
     }
 
@@ -247,14 +676,42 @@ impl Net {
         if connection.0.close_reason().is_some() {
             return Ok(());
         }
+        if self.is_banned(addr) {
+            tracing::info!("refusing connection from banned peer at {addr}");
+            connection.0.close(quinn::VarInt::from_u32(3), b"banned");
+            return Ok(());
+        }
+        if self.peer_counts().inbound >= self.peer_limits.max_inbound {
+            match self.select_inbound_peer_to_evict() {
+                Some(evict_addr) => {
+                    tracing::info!(
+                        "inbound peer limit reached, evicting peer at \
+                         {evict_addr} to accept connection from {addr}"
+                    );
+                    self.remove_active_peer(evict_addr);
+                }
+                None => {
+                    tracing::info!(
+                        "inbound peer limit reached, refusing connection \
+                         from {addr}"
+                    );
+                    connection
+                        .0
+                        .close(quinn::VarInt::from_u32(2), b"too many peers");
+                    return Ok(());
+                }
+            }
+        }
         tracing::info!("connected to peer at {addr}");
-        let mut rwtxn = env.write_txn()?;
-        self.known_peers.put(&mut rwtxn, &addr, &())?;
-        rwtxn.commit()?;
+        // The address of an inbound connection is not one on which the peer
+        // accepts connections, so it is not added to the address book.
+        // Peers share their listening address in peer addrs messages.
         let connection_ctxt = PeerConnectionCtxt {
             env,
             archive: self.archive.clone(),
             state: self.state.clone(),
+            known_peers: self.known_peers,
+            listen_port: self.server.local_addr()?.port(),
         };
         let (connection_handle, info_rx) =
             peer::handle(connection_ctxt, connection);
@@ -268,7 +725,11 @@ impl Net {
                 }
             }
         });
-        self.add_active_peer(addr, connection_handle)?;
+        self.add_active_peer(
+            addr,
+            connection_handle,
+            ConnectionDirection::Inbound,
+        )?;
         Ok(())
     }
 
@@ -279,10 +740,11 @@ impl Net {
         addr: SocketAddr,
     ) -> Result<(), Error> {
         let active_peers_read = self.active_peers.read();
-        let Some(peer_connection_handle) = active_peers_read.get(&addr) else {
+        let Some(active_peer) = active_peers_read.get(&addr) else {
             return Err(Error::MissingPeerConnection(addr));
         };
-        if let Err(send_err) = peer_connection_handle
+        if let Err(send_err) = active_peer
+            .connection_handle
             .internal_message_tx
             .unbounded_send(message)
         {
@@ -300,11 +762,11 @@ impl Net {
     ) {
         let active_peers_read = self.active_peers.read();
         for addr in peers {
-            let Some(peer_connection_handle) = active_peers_read.get(addr)
-            else {
+            let Some(active_peer) = active_peers_read.get(addr) else {
                 continue;
             };
-            if let Err(_send_err) = peer_connection_handle
+            if let Err(_send_err) = active_peer
+                .connection_handle
                 .internal_message_tx
                 .unbounded_send(request.clone().into())
             {
@@ -325,11 +787,12 @@ impl Net {
             .read()
             .iter()
             .filter(|(addr, _)| !exclude.contains(addr))
-            .for_each(|(addr, peer_connection_handle)| {
+            .for_each(|(addr, active_peer)| {
                 let request = PeerRequest::PushTransaction {
                     transaction: tx.clone(),
                 };
-                if let Err(_send_err) = peer_connection_handle
+                if let Err(_send_err) = active_peer
+                    .connection_handle
                     .internal_message_tx
                     .unbounded_send(request.into())
                 {
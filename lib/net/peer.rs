@@ -9,13 +9,14 @@ use bip300301::bitcoin::{self, hashes::Hash as _, Work};
 use borsh::BorshSerialize;
 use fallible_iterator::FallibleIterator;
 use futures::{channel::mpsc, stream, StreamExt, TryFutureExt, TryStreamExt};
+use heed::{types::SerdeBincode, Database};
 use quinn::{Endpoint, SendStream};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::{
     spawn,
     task::{JoinHandle, JoinSet},
-    time::{interval, timeout, Duration},
+    time::{interval, timeout, Duration, Instant},
 };
 use tokio_stream::wrappers::IntervalStream;
 
@@ -44,6 +45,36 @@ pub enum BanReason {
     IncorrectTotalWork { tip: Tip, total_work: Option<Work> },
 }
 
+/// Protocol violation by a peer, for which the peer's score is reduced
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Misbehavior {
+    /// Peer sent a tip that failed BMM verification, or claimed incorrect
+    /// total work for a tip
+    BadBmm,
+    /// Peer sent a block that failed validation
+    InvalidBlock,
+    /// Peer sent an invalid response to a request
+    InvalidResponse,
+    /// Peer sent a tx that failed validation
+    InvalidTransaction,
+    /// Peer sent a message that could not be decoded
+    MalformedMessage,
+    /// Peer shared peer addresses faster than the rate limit allows
+    PeerAddrsFlood,
+}
+
+impl Misbehavior {
+    /// Amount by which the peer's score is reduced
+    pub const fn penalty(self) -> u32 {
+        match self {
+            Self::BadBmm => 100,
+            Self::InvalidBlock => 50,
+            Self::InvalidResponse | Self::MalformedMessage => 20,
+            Self::InvalidTransaction | Self::PeerAddrsFlood => 10,
+        }
+    }
+}
+
 #[must_use]
 #[derive(Debug, Error)]
 pub enum ConnectionError {
@@ -77,6 +108,20 @@ pub enum ConnectionError {
     Write(#[from] quinn::WriteError),
 }
 
+impl ConnectionError {
+    /// The protocol violation by the peer that caused the error, if any
+    pub fn misbehavior(&self) -> Option<Misbehavior> {
+        match self {
+            Self::Bincode(_) => Some(Misbehavior::MalformedMessage),
+            Self::PeerBan(
+                BanReason::BmmVerificationFailed(_)
+                | BanReason::IncorrectTotalWork { .. },
+            ) => Some(Misbehavior::BadBmm),
+            _ => None,
+        }
+    }
+}
+
 impl From<mpsc::TrySendError<Info>> for ConnectionError {
     fn from(_: mpsc::TrySendError<Info>) -> Self {
         Self::SendInfo
@@ -108,6 +153,17 @@ where
     borsh::BorshSerialize::serialize(&work.map(BorshWrapper), writer)
 }
 
+fn borsh_serialize_socket_addrs<W>(
+    addrs: &[SocketAddr],
+    writer: &mut W,
+) -> borsh::io::Result<()>
+where
+    W: borsh::io::Write,
+{
+    let addrs: Vec<String> = addrs.iter().map(ToString::to_string).collect();
+    borsh::BorshSerialize::serialize(&addrs, writer)
+}
+
 #[derive(
     BorshSerialize, Clone, Copy, Debug, Default, Deserialize, Serialize,
 )]
@@ -191,12 +247,21 @@ pub enum Request {
     PushTransaction {
         transaction: AuthorizedTransaction,
     },
+    /// Share known peer addresses
+    PeerAddrs {
+        /// Port on which the sender accepts connections
+        listen_port: u16,
+        #[borsh(serialize_with = "borsh_serialize_socket_addrs")]
+        addrs: Vec<SocketAddr>,
+    },
 }
 
 /// Info to send to the net task / node
 #[must_use]
 #[derive(Debug)]
 pub enum Info {
+    /// Connection to the peer was established
+    Connected,
     Error(ConnectionError),
     /// Need BMM verification for the specified tip
     NeedBmmVerification {
@@ -208,9 +273,21 @@ pub enum Info {
         main_hash: bitcoin::BlockHash,
         peer_state_id: PeerStateId,
     },
+    /// Peer violated the protocol, without ending the connection
+    Misbehavior(Misbehavior),
     /// New tip ready (body and header exist in archive, BMM verified)
     NewTipReady(Tip),
-    NewTransaction(AuthorizedTransaction),
+    /// New valid tx, and the fee that it pays
+    NewTransaction {
+        tx: AuthorizedTransaction,
+        fee: u64,
+    },
+    /// Peer shared known peer addresses
+    PeerAddrs {
+        /// Address on which the peer accepts connections
+        listen_addr: SocketAddr,
+        addrs: Vec<SocketAddr>,
+    },
     Response(Box<(Response, Request)>),
 }
 
@@ -253,6 +330,8 @@ pub enum InternalMessage {
     Headers(PeerStateId),
     /// Indicates that all requested missing block bodies are now available
     BodiesAvailable(PeerStateId),
+    /// Share the specified peer addresses with the peer
+    PeerAddrs(Vec<SocketAddr>),
 }
 
 impl From<Request> for InternalMessage {
@@ -281,6 +360,17 @@ impl Connection {
     // 256B limit per tx ack (response size is ~192)
     pub const READ_TX_ACK_LIMIT: usize = 256;
 
+    pub const PEER_ADDRS_SEND_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Max number of addresses in a single peer addrs message
+    pub const MAX_PEER_ADDRS: usize = 1000;
+
+    /// Max number of addresses that a peer may share per
+    /// [`Self::PEER_ADDRS_SEND_INTERVAL`], before further peer addrs
+    /// messages are rate limited.
+    /// Allows for a full peer addrs message, as well as relayed addresses.
+    pub const PEER_ADDRS_RATE_LIMIT: usize = 2 * Self::MAX_PEER_ADDRS;
+
     pub const fn read_response_limit(req: &Request) -> usize {
         match req {
             Request::GetBlock { .. } => Self::READ_BLOCK_LIMIT,
@@ -289,7 +379,7 @@ impl Connection {
                 ..
             } => *height as usize * Self::READ_HEADER_LIMIT,
             // Should have no response, so limit zero
-            Request::Heartbeat(_) => 0,
+            Request::Heartbeat(_) | Request::PeerAddrs { .. } => 0,
             Request::PushTransaction { .. } => Self::READ_TX_ACK_LIMIT,
             // Should never happen, so limit zero
             Request::GetHeaders { height: None, .. } => 0,
@@ -338,10 +428,55 @@ impl Connection {
     }
 }
 
+/** Token bucket limiting the rate at which a peer may share addresses.
+ *  Each shared address consumes a token, and tokens are refilled at a rate
+ *  of [`Connection::PEER_ADDRS_RATE_LIMIT`] per
+ *  [`Connection::PEER_ADDRS_SEND_INTERVAL`], up to
+ *  [`Connection::PEER_ADDRS_RATE_LIMIT`]. */
+struct PeerAddrsRateLimit {
+    tokens: usize,
+    last_refill: Instant,
+}
+
+impl PeerAddrsRateLimit {
+    fn new() -> Self {
+        Self {
+            tokens: Connection::PEER_ADDRS_RATE_LIMIT,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Consume `n` tokens, if available.
+    /// Returns `false` if the rate limit is exceeded.
+    fn try_consume(&mut self, n: usize) -> bool {
+        let now = Instant::now();
+        let refill = now.duration_since(self.last_refill).as_millis()
+            * Connection::PEER_ADDRS_RATE_LIMIT as u128
+            / Connection::PEER_ADDRS_SEND_INTERVAL.as_millis();
+        if refill > 0 {
+            self.tokens = (self.tokens as u128 + refill)
+                .min(Connection::PEER_ADDRS_RATE_LIMIT as u128)
+                as usize;
+            self.last_refill = now;
+        }
+        match self.tokens.checked_sub(n) {
+            Some(tokens) => {
+                self.tokens = tokens;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
 pub struct ConnectionContext {
     pub env: heed::Env,
     pub archive: Archive,
     pub state: State,
+    /// Address book, shared with the peer
+    pub known_peers: Database<SerdeBincode<SocketAddr>, SerdeBincode<u64>>,
+    /// Port on which this node accepts connections
+    pub listen_port: u16,
 }
 
 struct ConnectionTask {
@@ -931,25 +1066,78 @@ impl ConnectionTask {
         };
         match validate_tx_result {
             Err(err) => {
+                let err = anyhow::Error::from(err);
+                tracing::warn!(%txid, "Rejected tx from peer: {err:#}");
                 Self::send_response(
                     response_tx,
                     Response::TransactionRejected(txid),
                 )
                 .await?;
-                Err(ConnectionError::from(err))
+                info_tx.unbounded_send(Info::Misbehavior(
+                    Misbehavior::InvalidTransaction,
+                ))?;
+                Ok(())
             }
-            Ok(_) => {
+            Ok(fee) => {
                 Self::send_response(
                     response_tx,
                     Response::TransactionAccepted(txid),
                 )
                 .await?;
-                info_tx.unbounded_send(Info::NewTransaction(tx))?;
+                info_tx.unbounded_send(Info::NewTransaction { tx, fee })?;
                 Ok(())
             }
         }
     }
 
+    /// Notify the net task of the addresses shared by the peer, including
+    /// the address on which the peer accepts connections.
+    /// Messages that exceed the peer's rate limit are ignored.
+    fn handle_peer_addrs(
+        info_tx: &mpsc::UnboundedSender<Info>,
+        peer_addrs_rate_limit: &mut PeerAddrsRateLimit,
+        peer_addr: SocketAddr,
+        listen_port: u16,
+        addrs: Vec<SocketAddr>,
+    ) -> Result<(), ConnectionError> {
+        if addrs.len() > Connection::MAX_PEER_ADDRS {
+            info_tx.unbounded_send(Info::Misbehavior(
+                Misbehavior::MalformedMessage,
+            ))?;
+            return Ok(());
+        }
+        // Empty messages count towards the limit, so that they are also
+        // rate limited
+        if !peer_addrs_rate_limit.try_consume(addrs.len().max(1)) {
+            tracing::debug!(%peer_addr, "Peer addrs rate limit exceeded");
+            info_tx.unbounded_send(Info::Misbehavior(
+                Misbehavior::PeerAddrsFlood,
+            ))?;
+            return Ok(());
+        }
+        let info = Info::PeerAddrs {
+            listen_addr: SocketAddr::new(peer_addr.ip(), listen_port),
+            addrs,
+        };
+        info_tx.unbounded_send(info)?;
+        Ok(())
+    }
+
+    /// Known peer addresses to share with the peer
+    fn known_peer_addrs(
+        ctxt: &ConnectionContext,
+    ) -> Result<Vec<SocketAddr>, ConnectionError> {
+        let rotxn = ctxt.env.read_txn()?;
+        let addrs = ctxt
+            .known_peers
+            .iter(&rotxn)?
+            .take(Connection::MAX_PEER_ADDRS)
+            .map(|res| res.map(|(addr, _last_connected)| addr))
+            .collect::<Result<_, _>>()?;
+        Ok(addrs)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn handle_request(
         ctxt: &ConnectionContext,
         info_tx: &mpsc::UnboundedSender<Info>,
@@ -957,6 +1145,8 @@ impl ConnectionTask {
         peer_state: &mut Option<PeerStateId>,
         // Map associating peer state hashes to peer state
         peer_states: &mut HashMap<PeerStateId, PeerState>,
+        peer_addrs_rate_limit: &mut PeerAddrsRateLimit,
+        peer_addr: SocketAddr,
         response_tx: SendStream,
         request: Request,
     ) -> Result<(), ConnectionError> {
@@ -992,6 +1182,15 @@ impl ConnectionTask {
                 Self::handle_push_tx(ctxt, info_tx, response_tx, transaction)
                     .await
             }
+            Request::PeerAddrs { listen_port, addrs } => {
+                Self::handle_peer_addrs(
+                    info_tx,
+                    peer_addrs_rate_limit,
+                    peer_addr,
+                    listen_port,
+                    addrs,
+                )
+            }
         }
     }
 
@@ -1002,6 +1201,8 @@ impl ConnectionTask {
             InternalMessage(InternalMessage),
             /// Signals that a heartbeat message should be sent to the peer
             Heartbeat,
+            /// Signals that known peer addresses should be sent to the peer
+            PeerAddrs,
             Request((Request, SendStream)),
             Response(Result<Response, ConnectionError>, Request),
         }
@@ -1011,6 +1212,9 @@ impl ConnectionTask {
         let heartbeat_stream =
             IntervalStream::new(interval(Connection::HEARTBEAT_SEND_INTERVAL))
                 .map(|_| Ok(MailboxItem::Heartbeat));
+        let peer_addrs_stream =
+            IntervalStream::new(interval(Connection::PEER_ADDRS_SEND_INTERVAL))
+                .map(|_| Ok(MailboxItem::PeerAddrs));
         let request_stream = stream::try_unfold((), {
             let conn = self.connection.clone();
             move |()| {
@@ -1034,6 +1238,7 @@ impl ConnectionTask {
         let mut mailbox_stream = stream::select_all([
             internal_message_stream.boxed(),
             heartbeat_stream.boxed(),
+            peer_addrs_stream.boxed(),
             request_stream.boxed(),
             response_stream.boxed(),
         ]);
@@ -1043,6 +1248,7 @@ impl ConnectionTask {
         let mut peer_state = Option::<PeerStateId>::None;
         // known peer states
         let mut peer_states = HashMap::<PeerStateId, PeerState>::new();
+        let mut peer_addrs_rate_limit = PeerAddrsRateLimit::new();
         // Do not repeat requests
         let mut pending_request_hashes = HashSet::<Hash>::new();
         while let Some(mailbox_item) = mailbox_stream.try_next().await? {
@@ -1164,6 +1370,45 @@ impl ConnectionTask {
                         }
                     });
                 }
+                MailboxItem::InternalMessage(InternalMessage::PeerAddrs(
+                    mut addrs,
+                )) => {
+                    addrs.truncate(Connection::MAX_PEER_ADDRS);
+                    let peer_addrs_msg = Request::PeerAddrs {
+                        listen_port: self.ctxt.listen_port,
+                        addrs,
+                    };
+                    task_set.spawn({
+                        let connection = self.connection.clone();
+                        let response_tx = response_tx.clone();
+                        async move {
+                            Self::send_request(
+                                &connection,
+                                &response_tx,
+                                peer_addrs_msg,
+                            )
+                            .await;
+                        }
+                    });
+                }
+                MailboxItem::PeerAddrs => {
+                    let peer_addrs_msg = Request::PeerAddrs {
+                        listen_port: self.ctxt.listen_port,
+                        addrs: Self::known_peer_addrs(&self.ctxt)?,
+                    };
+                    task_set.spawn({
+                        let connection = self.connection.clone();
+                        let response_tx = response_tx.clone();
+                        async move {
+                            Self::send_request(
+                                &connection,
+                                &response_tx,
+                                peer_addrs_msg,
+                            )
+                            .await;
+                        }
+                    });
+                }
                 MailboxItem::Request((request, response_tx)) => {
                     let () = Self::handle_request(
                         &self.ctxt,
@@ -1171,6 +1416,8 @@ impl ConnectionTask {
                         &self.internal_message_tx,
                         &mut peer_state,
                         &mut peer_states,
+                        &mut peer_addrs_rate_limit,
+                        self.connection.addr(),
                         response_tx,
                         request,
                     )
@@ -1254,6 +1501,7 @@ pub fn connect(
         let internal_message_tx = internal_message_tx.clone();
         move || async move {
             let connection = Connection::new(&endpoint, addr).await?;
+            info_tx.unbounded_send(Info::Connected)?;
             let connection_task = ConnectionTask {
                 connection,
                 ctxt,
@@ -5,30 +5,38 @@ use std::{
     fmt::Debug,
     net::SocketAddr,
     path::Path,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use bip300301::{bitcoin, DepositInfo};
 use fallible_iterator::FallibleIterator;
 use fraction::Fraction;
-use futures::Stream;
+use futures::{Stream, StreamExt as _};
+use tokio_stream::wrappers::{
+    errors::BroadcastStreamRecvError, BroadcastStream,
+};
 use tokio_util::task::LocalPoolHandle;
 
 use crate::{
     archive::{self, Archive},
-    mempool::{self, MemPool},
+    mempool::{self, MemPool, MemPoolInfo, MemPoolLimits},
     net::{self, Net},
     state::{
-        self, AmmPair, AmmPoolState, BitAssetSeqId, DutchAuctionBidError,
-        DutchAuctionState, State,
+        self, AmmPair, AmmPoolState, AmmProtocolFee, AtomicSwapReleaseError,
+        AtomicSwapState, BitAssetSeqId, DutchAuctionBidError,
+        DutchAuctionState, Genesis, OrderFillError, OrderState, State,
+        UtxoSort, WithdrawalEstimate,
     },
     types::{
-        Address, AssetId, Authorized, AuthorizedTransaction, BitAssetData,
-        BitAssetId, Block, BlockHash, BmmResult, Body, DutchAuctionId,
-        FilledOutput, FilledTransaction, GetBitcoinValue, Header, InPoint,
-        Network, OutPoint, Output, SpentOutput, Tip, Transaction, TxIn, Txid,
-        WithdrawalBundle,
+        Address, AssetId, AtomicSwapId, Authorized, AuthorizedTransaction,
+        BitAssetData, BitAssetId, BitAssetKey, BitcoinOutputContent, Block,
+        BlockEvent, BlockHash, BmmResult, Body, DutchAuctionId, FilledOutput,
+        FilledTransaction, GetBitcoinValue, Header, InPoint, M6id, MerkleProof,
+        Network, OrderId, OutPoint, Output, OutputContent, Reorg, SpentOutput,
+        Tip, Transaction, TxIn, TxStatus, Txid, WithdrawalBundle,
+        WithdrawalBundleInfo, WithdrawalBundleRecord,
     },
-    util::Watchable,
+    util::{self, Watchable},
 };
 
 mod mainchain_task;
@@ -53,12 +61,18 @@ pub enum Error {
     Drivechain(#[from] bip300301::Error),
     #[error("heed error")]
     Heed(#[from] heed::Error),
+    #[error("height {height} is above the current tip height {tip_height}")]
+    HeightAboveTip { height: u32, tip_height: u32 },
     #[error("quinn error")]
     Io(#[from] std::io::Error),
     #[error("error requesting mainchain ancestors")]
     MainchainAncestors(anyhow::Error),
     #[error("mempool error")]
     MemPool(#[from] mempool::Error),
+    #[error(transparent)]
+    NetworkDataPath(#[from] util::NetworkDataPathError),
+    #[error("{outpoint} is not a withdrawal output")]
+    NotWithdrawal { outpoint: OutPoint },
     #[error("net error")]
     Net(#[from] net::Error),
     #[error("net task error")]
@@ -71,10 +85,21 @@ pub enum Error {
     SendMainchainTaskRequest,
     #[error("state error")]
     State(#[from] state::Error),
+    #[error(
+        "tx {txid} expired at height {expiry_height} (next block height is \
+         {height})"
+    )]
+    TxExpired {
+        txid: Txid,
+        expiry_height: u32,
+        height: u32,
+    },
     #[error("Utreexo error: {0}")]
     Utreexo(String),
     #[error("Verify BMM error")]
     VerifyBmm(anyhow::Error),
+    #[error("withdrawal {outpoint} is already in withdrawal bundle {m6id}")]
+    WithdrawalInBundle { outpoint: OutPoint, m6id: M6id },
     #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
     #[error("ZMQ error")]
     Zmq(#[from] async_zmq::Error),
@@ -160,16 +185,20 @@ impl Node {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         bind_addr: SocketAddr,
+        peer_limits: net::PeerLimits,
         datadir: &Path,
         main_addr: SocketAddr,
         network: Network,
         password: &str,
         user: &str,
         local_pool: LocalPoolHandle,
+        index_address_activity: bool,
+        mempool_limits: MemPoolLimits,
+        genesis: &Genesis,
         #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
         zmq_addr: SocketAddr,
     ) -> Result<Self, Error> {
-        let env_path = datadir.join("data.mdb");
+        let env_path = util::network_data_path(datadir, network, "data.mdb")?;
         // let _ = std::fs::remove_dir_all(&env_path);
         std::fs::create_dir_all(&env_path)?;
         let env = unsafe {
@@ -183,11 +212,11 @@ impl Node {
                 )
                 .open(env_path)?
         };
-        let state = State::new(&env)?;
+        let state = State::new(&env, index_address_activity, genesis)?;
         #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
         let zmq_pub_handler = Arc::new(ZmqPubHandler::new(zmq_addr)?);
         let archive = Archive::new(&env)?;
-        let mempool = MemPool::new(&env)?;
+        let mempool = MemPool::new(&env, mempool_limits)?;
         let drivechain = bip300301::Drivechain::new(
             THIS_SIDECHAIN,
             main_addr,
@@ -200,8 +229,14 @@ impl Node {
                 archive.clone(),
                 drivechain.clone(),
             );
-        let (net, peer_info_rx) =
-            Net::new(&env, archive.clone(), network, state.clone(), bind_addr)?;
+        let (net, peer_info_rx) = Net::new(
+            &env,
+            archive.clone(),
+            network,
+            state.clone(),
+            bind_addr,
+            peer_limits,
+        )?;
 
         let net_task = NetTaskHandle::new(
             local_pool.clone(),
@@ -236,6 +271,11 @@ impl Node {
         &self.drivechain
     }
 
+    /// Protocol fee charged on AMM swaps
+    pub fn amm_protocol_fee(&self) -> AmmProtocolFee {
+        self.state.amm_protocol_fee
+    }
+
     pub async fn get_best_parentchain_hash(
         &self,
     ) -> Result<bitcoin::BlockHash, Error> {
@@ -259,6 +299,57 @@ impl Node {
         Ok(self.state.get_tip(&rotxn)?)
     }
 
+    /** Timestamp for a new block extending the current tip, that extends
+     *  the specified mainchain block.
+     *  This is the current time, if it is valid. Otherwise, it is the
+     *  closest valid time, if the mainchain block is known. */
+    pub fn next_block_timestamp(
+        &self,
+        prev_main_hash: bitcoin::BlockHash,
+    ) -> Result<u64, Error> {
+        let rotxn = self.env.read_txn()?;
+        let tip = self.state.get_tip(&rotxn)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut timestamp = now;
+        if let Some(median_time_past) =
+            self.archive.get_median_time_past(&rotxn, tip)?
+        {
+            timestamp = timestamp.max(median_time_past + 1);
+        }
+        if let Some(main_block_time) = self
+            .archive
+            .try_get_main_block_time(&rotxn, prev_main_hash)?
+        {
+            timestamp =
+                timestamp.min(main_block_time + archive::MAX_FUTURE_BLOCK_TIME);
+        }
+        Ok(timestamp)
+    }
+
+    /** Estimate the impermanent loss of an LP position in the specified
+     *  pool that was entered at the specified reserves, relative to holding
+     *  the deposited assets.
+     *  Returns `None` if the pool does not exist, or if any of the entry
+     *  reserves or current reserves are zero.
+     *  See [`AmmPoolState::impermanent_loss`]. */
+    pub fn estimate_impermanent_loss(
+        &self,
+        pair: AmmPair,
+        entry_reserve0: u64,
+        entry_reserve1: u64,
+    ) -> Result<Option<Fraction>, Error> {
+        let Some(amm_pool_state) = self.try_get_amm_pool_state(pair)? else {
+            return Ok(None);
+        };
+        let impermanent_loss = amm_pool_state
+            .impermanent_loss(entry_reserve0, entry_reserve1)
+            .map(Fraction::from);
+        Ok(impermanent_loss)
+    }
+
     pub fn try_get_amm_price(
         &self,
         base: AssetId,
@@ -285,6 +376,66 @@ impl Node {
         }
     }
 
+    /** Relative difference between the current price for the specified
+     *  pair, and the effective price of a swap spending `amount_spend` of
+     *  `base` to receive `quote`.
+     *  A price impact of `0.01` means that the swap receives 1% less than
+     *  it would at the current price. The swap fee is included.
+     *  Returns `None` if the pool does not exist or is empty. */
+    pub fn try_get_amm_price_impact(
+        &self,
+        base: AssetId,
+        quote: AssetId,
+        amount_spend: u64,
+    ) -> Result<Option<Fraction>, Error> {
+        let amm_pair = AmmPair::new(base, quote);
+        let Some(amm_pool_state) = self.try_get_amm_pool_state(amm_pair)?
+        else {
+            return Ok(None);
+        };
+        let (reserve_spend, reserve_receive) = if base < quote {
+            (amm_pool_state.reserve0, amm_pool_state.reserve1)
+        } else {
+            (amm_pool_state.reserve1, amm_pool_state.reserve0)
+        };
+        if reserve_spend == 0 || reserve_receive == 0 {
+            return Ok(None);
+        }
+        if amount_spend == 0 {
+            return Ok(Some(Fraction::from(0)));
+        }
+        let amount_receive = if base < quote {
+            let new_amm_pool_state =
+                amm_pool_state.swap_asset0_for_asset1(amount_spend)?;
+            amm_pool_state.reserve1 - new_amm_pool_state.reserve1
+        } else {
+            let new_amm_pool_state =
+                amm_pool_state.swap_asset1_for_asset0(amount_spend)?;
+            amm_pool_state.reserve0 - new_amm_pool_state.reserve0
+        };
+        let spot_price = reserve_receive as f64 / reserve_spend as f64;
+        let effective_price = amount_receive as f64 / amount_spend as f64;
+        Ok(Some(Fraction::from(1. - (effective_price / spot_price))))
+    }
+
+    /// List all AMM pools, with the assets in each pair in ascending order
+    pub fn amm_pools(
+        &self,
+    ) -> Result<Vec<(AssetId, AssetId, AmmPoolState)>, Error> {
+        let txn = self.env.read_txn()?;
+        let res = self
+            .state
+            .amm_pools
+            .iter(&txn)?
+            .map(|res| {
+                res.map(|(amm_pair, amm_pool_state)| {
+                    (amm_pair.asset0(), amm_pair.asset1(), amm_pool_state)
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(res)
+    }
+
     pub fn try_get_amm_pool_state(
         &self,
         pair: AmmPair,
@@ -307,6 +458,73 @@ impl Node {
         Ok(res)
     }
 
+    /** Returns the state of an AMM pool at the end of each block in the
+     *  specified (inclusive) height range in which the pool state changed */
+    pub fn get_amm_pool_history(
+        &self,
+        pair: AmmPair,
+        from_height: u32,
+        to_height: u32,
+    ) -> Result<Vec<(u32, AmmPoolState)>, Error> {
+        let txn = self.env.read_txn()?;
+        let res = self.state.get_amm_pool_history(
+            &txn,
+            pair,
+            from_height,
+            to_height,
+        )?;
+        Ok(res)
+    }
+
+    /** Returns the state of an AMM pool at the end of the block at the
+     *  specified height, or `None` if the pool did not exist at that
+     *  height */
+    pub fn get_amm_pool_state_at_height(
+        &self,
+        pair: AmmPair,
+        height: u32,
+    ) -> Result<Option<AmmPoolState>, Error> {
+        let txn = self.env.read_txn()?;
+        let res = self
+            .state
+            .try_get_amm_pool_state_at_block_height(&txn, pair, height)?;
+        Ok(res)
+    }
+
+    /// List all atomic swaps and their current state
+    pub fn atomic_swaps(
+        &self,
+    ) -> Result<Vec<(AtomicSwapId, AtomicSwapState)>, Error> {
+        let txn = self.env.read_txn()?;
+        let res = self
+            .state
+            .atomic_swaps
+            .iter(&txn)?
+            .collect::<Result<_, _>>()?;
+        Ok(res)
+    }
+
+    pub fn try_get_atomic_swap_state(
+        &self,
+        swap_id: AtomicSwapId,
+    ) -> Result<Option<AtomicSwapState>, Error> {
+        let txn = self.env.read_txn()?;
+        let res = self.state.atomic_swaps.get(&txn, &swap_id)?;
+        Ok(res)
+    }
+
+    pub fn get_atomic_swap_state(
+        &self,
+        swap_id: AtomicSwapId,
+    ) -> Result<AtomicSwapState, Error> {
+        self.try_get_atomic_swap_state(swap_id)
+            .and_then(|atomic_swap_state| {
+                atomic_swap_state.ok_or_else(|| {
+                    Error::State(AtomicSwapReleaseError::MissingSwap.into())
+                })
+            })
+    }
+
     /// List all BitAssets and their current data
     pub fn bitassets(
         &self,
@@ -382,6 +600,33 @@ impl Node {
         )
     }
 
+    /// List all orders and their current state
+    pub fn orders(&self) -> Result<Vec<(OrderId, OrderState)>, Error> {
+        let txn = self.env.read_txn()?;
+        let res = self.state.orders.iter(&txn)?.collect::<Result<_, _>>()?;
+        Ok(res)
+    }
+
+    pub fn try_get_order_state(
+        &self,
+        order_id: OrderId,
+    ) -> Result<Option<OrderState>, Error> {
+        let txn = self.env.read_txn()?;
+        let res = self.state.orders.get(&txn, &order_id)?;
+        Ok(res)
+    }
+
+    pub fn get_order_state(
+        &self,
+        order_id: OrderId,
+    ) -> Result<OrderState, Error> {
+        self.try_get_order_state(order_id).and_then(|order_state| {
+            order_state.ok_or_else(|| {
+                Error::State(OrderFillError::MissingOrder.into())
+            })
+        })
+    }
+
     pub fn try_get_height(
         &self,
         block_hash: BlockHash,
@@ -395,6 +640,33 @@ impl Node {
         Ok(self.archive.get_height(&rotxn, block_hash)?)
     }
 
+    /** Get the txids of txs that spent from or paid to the specified
+     *  address, confirmed in blocks at or above `from_height`, in the order
+     *  that they were confirmed.
+     *  Returns an error if the address activity index is disabled, or was
+     *  enabled after the block at `from_height`. */
+    pub fn get_address_history(
+        &self,
+        address: &Address,
+        from_height: u32,
+    ) -> Result<Vec<Txid>, Error> {
+        let rotxn = self.env.read_txn()?;
+        Ok(self
+            .state
+            .get_address_history(&rotxn, address, from_height)?)
+    }
+
+    /** Get the txids of confirmed txs that spent or created BitAsset
+     *  outputs or control coins for the specified BitAsset, in the order
+     *  that they were confirmed. */
+    pub fn get_bitasset_txids(
+        &self,
+        bitasset: &BitAssetId,
+    ) -> Result<Vec<Txid>, Error> {
+        let rotxn = self.env.read_txn()?;
+        Ok(self.state.get_bitasset_txids(&rotxn, bitasset)?)
+    }
+
     /// Get blocks in which a tx was included, and tx index within those blocks
     pub fn get_tx_inclusions(
         &self,
@@ -448,14 +720,76 @@ impl Node {
         Ok(self.state.get_current_bitasset_data(&txn, bitasset)?)
     }
 
+    /// Compute the BitAsset ID for a plaintext name, and whether the name
+    /// is available to register
+    pub fn preview_registration(
+        &self,
+        plain_name: &str,
+    ) -> Result<(BitAssetId, bool), Error> {
+        let rotxn = self.env.read_txn()?;
+        Ok(self.state.preview_registration(&rotxn, plain_name)?)
+    }
+
+    /// BitAssets for which the key is currently set as the signing or
+    /// encryption pubkey
+    pub fn find_bitassets_by_key(
+        &self,
+        key: &BitAssetKey,
+    ) -> Result<Vec<BitAssetId>, Error> {
+        let txn = self.env.read_txn()?;
+        Ok(self.state.find_bitassets_by_key(&txn, key)?)
+    }
+
+    /// Current total supply of a BitAsset, if it exists
+    pub fn try_get_bitasset_total_supply(
+        &self,
+        bitasset: &BitAssetId,
+    ) -> Result<Option<u64>, Error> {
+        let txn = self.env.read_txn()?;
+        Ok(self.state.try_get_bitasset_total_supply(&txn, bitasset)?)
+    }
+
+    /** Market cap of a BitAsset in sats, implied by the price in the
+     *  BitAsset-Bitcoin AMM pool.
+     *  Returns `None` if the BitAsset does not exist, or if there is no
+     *  BitAsset-Bitcoin pool with nonzero reserves. */
+    pub fn try_get_bitasset_market_cap(
+        &self,
+        bitasset: &BitAssetId,
+    ) -> Result<Option<u64>, Error> {
+        let txn = self.env.read_txn()?;
+        Ok(self.state.try_get_bitasset_market_cap(&txn, bitasset)?)
+    }
+
+    /** Validate a tx and add it to the mempool, and broadcast it to peers.
+     *  If an expiry height is specified, the tx is dropped from the mempool
+     *  if it is not included in a block at or below the expiry height.
+     *  The expiry height is not shared with peers. */
     pub fn submit_transaction(
         &self,
         transaction: AuthorizedTransaction,
+        expiry_height: Option<u32>,
     ) -> Result<(), Error> {
         {
             let mut txn = self.env.write_txn()?;
-            self.state.validate_transaction(&txn, &transaction)?;
-            self.mempool.put(&mut txn, &transaction)?;
+            if let Some(expiry_height) = expiry_height {
+                let height = self.state.get_height(&txn)?;
+                if height > expiry_height {
+                    return Err(Error::TxExpired {
+                        txid: transaction.transaction.txid(),
+                        expiry_height,
+                        height,
+                    });
+                }
+            }
+            let fee = self.state.validate_transaction(&txn, &transaction)?;
+            let evicted =
+                self.mempool
+                    .put(&mut txn, &transaction, fee, expiry_height)?;
+            let txid = transaction.transaction.txid();
+            if evicted.contains(&txid) {
+                return Err(mempool::Error::FeeRateTooLow { txid }.into());
+            }
             txn.commit()?;
         }
         self.net.push_tx(Default::default(), transaction);
@@ -469,6 +803,48 @@ impl Node {
         self.state.get_utxos(&rotxn).map_err(Error::from)
     }
 
+    /// Get all UTXOs holding the specified asset
+    pub fn get_utxos_by_asset(
+        &self,
+        asset: AssetId,
+    ) -> Result<HashMap<OutPoint, FilledOutput>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let mut utxos = self.state.get_utxos(&rotxn)?;
+        utxos.retain(|_, output| {
+            output
+                .asset_value()
+                .is_some_and(|(output_asset, _)| output_asset == asset)
+        });
+        Ok(utxos)
+    }
+
+    pub fn count_utxos(&self) -> Result<u64, Error> {
+        let rotxn = self.env.read_txn()?;
+        let count = self.state.count_utxos(&rotxn)?;
+        Ok(count)
+    }
+
+    pub fn count_utxos_by_asset(&self, asset: AssetId) -> Result<u64, Error> {
+        let rotxn = self.env.read_txn()?;
+        let count = self.state.count_utxos_by_asset(&rotxn, asset)?;
+        Ok(count)
+    }
+
+    /// Get a page of UTXOs. See [`State::get_utxos_paginated`].
+    pub fn get_utxos_paginated(
+        &self,
+        offset: usize,
+        limit: usize,
+        asset: Option<AssetId>,
+        sort: UtxoSort,
+    ) -> Result<Vec<(OutPoint, FilledOutput)>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let utxos = self
+            .state
+            .get_utxos_paginated(&rotxn, offset, limit, asset, sort)?;
+        Ok(utxos)
+    }
+
     pub fn get_spent_utxos(
         &self,
         outpoints: &[OutPoint],
@@ -483,6 +859,15 @@ impl Node {
         Ok(spent)
     }
 
+    /// Get the inpoint that spent the specified outpoint, if it was spent
+    pub fn get_spent_by(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<Option<InPoint>, Error> {
+        let rotxn = self.env.read_txn()?;
+        Ok(self.state.get_spent_by(&rotxn, outpoint)?)
+    }
+
     pub fn get_unconfirmed_spent_utxos<'a, OutPoints>(
         &self,
         outpoints: OutPoints,
@@ -525,6 +910,87 @@ impl Node {
         Ok(utxos)
     }
 
+    /** Get the Bitcoin balance of an address as of the end of the block at
+     *  the specified height in the current chain, by unwinding the blocks
+     *  and deposits above that height from the current UTXO set.
+     *  Withdrawal outputs are not included in the balance.
+     *  The cost is linear in the depth of the specified height. */
+    pub fn get_balance_at_height(
+        &self,
+        address: &Address,
+        height: u32,
+    ) -> Result<u64, Error> {
+        let rotxn = self.env.read_txn()?;
+        let tip = self.state.get_tip(&rotxn)?;
+        let tip_height = self.state.get_height(&rotxn)?;
+        if height > tip_height {
+            return Err(Error::HeightAboveTip { height, tip_height });
+        }
+        let addresses = HashSet::from([*address]);
+        let live: u64 = self
+            .state
+            .get_utxos_by_addresses(&rotxn, &addresses)?
+            .values()
+            .filter(|output| output.is_bitcoin())
+            .map(GetBitcoinValue::get_bitcoin_value)
+            .sum();
+        // Value received and spent above the specified height
+        let mut received: u64 = 0;
+        let mut spent: u64 = 0;
+        let mut ancestors = self
+            .archive
+            .ancestors(&rotxn, tip)
+            .take((tip_height - height) as usize);
+        while let Some(block_hash) = ancestors.next()? {
+            let body = self.archive.get_body(&rotxn, block_hash)?;
+            received += body
+                .coinbase
+                .iter()
+                .chain(body.transactions.iter().flat_map(|tx| &tx.outputs))
+                .filter(|output| {
+                    output.address == *address && output.is_bitcoin()
+                })
+                .map(GetBitcoinValue::get_bitcoin_value)
+                .sum::<u64>();
+            for outpoint in body.transactions.iter().flat_map(|tx| &tx.inputs) {
+                if let Some(SpentOutput { output, .. }) =
+                    self.state.stxos.get(&rotxn, outpoint)?
+                    && output.address == *address
+                    && output.is_bitcoin()
+                {
+                    spent += output.get_bitcoin_value();
+                }
+            }
+        }
+        // Deposits are recorded with the height of the block before the one
+        // in which they were applied
+        let deposit_blocks = self.state.get_deposit_blocks(&rotxn)?;
+        let mut deposits = Vec::new();
+        for (idx, (main_block_hash, applied_height)) in
+            deposit_blocks.iter().enumerate().rev()
+        {
+            if *applied_height < height {
+                break;
+            }
+            let prev_deposit_block_hash = idx
+                .checked_sub(1)
+                .map(|prev_idx| deposit_blocks[prev_idx].0);
+            let () = self.collect_deposits_to_addresses(
+                &rotxn,
+                &addresses,
+                *main_block_hash,
+                prev_deposit_block_hash,
+                Some(*applied_height),
+                &mut deposits,
+            )?;
+        }
+        received += deposits
+            .iter()
+            .map(|(deposit, _)| deposit.output.value)
+            .sum::<u64>();
+        Ok(live + spent - received)
+    }
+
     pub fn try_get_header(
         &self,
         block_hash: BlockHash,
@@ -557,6 +1023,33 @@ impl Node {
         }
     }
 
+    /** Get up to `count` headers in the current chain, in ascending height
+     *  order, starting with the header at `start_height`.
+     *  Fewer headers are returned if the tip is reached. The first block is
+     *  at height 1. */
+    pub fn get_headers(
+        &self,
+        start_height: u32,
+        count: u32,
+    ) -> Result<Vec<Header>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let tip = self.state.get_tip(&rotxn)?;
+        let tip_height = self.state.get_height(&rotxn)?;
+        if count == 0 || start_height > tip_height {
+            return Ok(Vec::new());
+        }
+        let end_height = start_height.saturating_add(count - 1).min(tip_height);
+        let mut headers: Vec<Header> = self
+            .archive
+            .ancestors(&rotxn, tip)
+            .skip((tip_height - end_height) as usize)
+            .take((end_height - start_height + 1) as usize)
+            .map(|block_hash| self.archive.get_header(&rotxn, block_hash))
+            .collect()?;
+        headers.reverse();
+        Ok(headers)
+    }
+
     pub fn try_get_body(
         &self,
         block_hash: BlockHash,
@@ -575,6 +1068,18 @@ impl Node {
         Ok(self.archive.get_block(&rotxn, block_hash)?)
     }
 
+    /// Get the local wall-clock time (unix seconds) at which a block was
+    /// first received
+    pub fn try_get_block_receive_time(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<u64>, Error> {
+        let rotxn = self.env.read_txn()?;
+        Ok(self
+            .archive
+            .try_get_block_receive_time(&rotxn, block_hash)?)
+    }
+
     pub fn get_all_transactions(
         &self,
     ) -> Result<Vec<AuthorizedTransaction>, Error> {
@@ -583,7 +1088,152 @@ impl Node {
         Ok(transactions)
     }
 
+    /// Current size and capacity of the mempool
+    pub fn mempool_info(&self) -> Result<MemPoolInfo, Error> {
+        let rotxn = self.env.read_txn()?;
+        Ok(self.mempool.info(&rotxn)?)
+    }
+
     /// Get total sidechain wealth in Bitcoin
+    /// Get mainchain deposit blocks, and the sidechain height at which each
+    /// was applied, in the order that they were applied
+    pub fn get_deposit_blocks(
+        &self,
+    ) -> Result<Vec<(bitcoin::BlockHash, u32)>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let deposit_blocks = self.state.get_deposit_blocks(&rotxn)?;
+        Ok(deposit_blocks)
+    }
+
+    /// Collect deposits to the specified addresses from `main_block_hash`
+    /// and its ancestors, most recent first, stopping at `stop_block_hash`
+    /// or at the first block for which deposits are unknown
+    fn collect_deposits_to_addresses(
+        &self,
+        rotxn: &heed::RoTxn,
+        addresses: &HashSet<Address>,
+        main_block_hash: bitcoin::BlockHash,
+        stop_block_hash: Option<bitcoin::BlockHash>,
+        applied_height: Option<u32>,
+        res: &mut Vec<(DepositInfo, Option<u32>)>,
+    ) -> Result<(), Error> {
+        let mut main_ancestors =
+            self.archive.main_ancestors(rotxn, main_block_hash);
+        while let Some(main_block_hash) = main_ancestors.next()?
+            && Some(main_block_hash) != stop_block_hash
+            && let Some(deposits) =
+                self.archive.try_get_deposits(rotxn, main_block_hash)?
+        {
+            res.extend(
+                deposits
+                    .into_iter()
+                    .rev()
+                    .filter(|deposit| {
+                        deposit
+                            .output
+                            .address
+                            .parse()
+                            .is_ok_and(|address| addresses.contains(&address))
+                    })
+                    .map(|deposit| (deposit, applied_height)),
+            );
+        }
+        Ok(())
+    }
+
+    /** Get deposits to the specified addresses, most recent first.
+     *  Deposits are included from mainchain blocks up to and including the
+     *  `limit` most recent deposit blocks, and from mainchain blocks after
+     *  the last deposit block, up to the specified mainchain tip.
+     *  Each deposit is paired with the sidechain height at which it was
+     *  applied, or `None` if it has not yet been applied. */
+    pub fn get_deposits_to_addresses(
+        &self,
+        addresses: &HashSet<Address>,
+        main_tip: Option<bitcoin::BlockHash>,
+        limit: usize,
+    ) -> Result<Vec<(DepositInfo, Option<u32>)>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let deposit_blocks = self.state.get_deposit_blocks(&rotxn)?;
+        let mut res = Vec::new();
+        let last_deposit_block_hash =
+            deposit_blocks.last().map(|(block_hash, _)| *block_hash);
+        if let Some(main_tip) = main_tip
+            && self
+                .archive
+                .try_get_main_header(&rotxn, main_tip)?
+                .is_some()
+        {
+            let () = self.collect_deposits_to_addresses(
+                &rotxn,
+                addresses,
+                main_tip,
+                last_deposit_block_hash,
+                None,
+                &mut res,
+            )?;
+        }
+        for (idx, (main_block_hash, applied_height)) in
+            deposit_blocks.iter().enumerate().rev().take(limit)
+        {
+            let prev_deposit_block_hash = idx
+                .checked_sub(1)
+                .map(|prev_idx| deposit_blocks[prev_idx].0);
+            let () = self.collect_deposits_to_addresses(
+                &rotxn,
+                addresses,
+                *main_block_hash,
+                prev_deposit_block_hash,
+                Some(*applied_height),
+                &mut res,
+            )?;
+        }
+        Ok(res)
+    }
+
+    /** Get unspent deposits to the specified addresses that have fewer than
+     *  `maturity` mainchain confirmations, with the number of confirmations
+     *  of each. Confirmations are counted relative to the mainchain block
+     *  that the current tip extends. */
+    pub fn get_immature_deposits(
+        &self,
+        addresses: &HashSet<Address>,
+        maturity: u32,
+    ) -> Result<HashMap<OutPoint, u32>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let tip = self.state.get_tip(&rotxn)?;
+        let mut res = HashMap::new();
+        if tip == BlockHash::default() {
+            return Ok(res);
+        }
+        let main_tip = self.archive.get_header(&rotxn, tip)?.prev_main_hash;
+        let mut main_ancestors = self
+            .archive
+            .main_ancestors(&rotxn, main_tip)
+            .take(maturity.saturating_sub(1) as usize);
+        let mut confirmations = 1;
+        while let Some(main_block_hash) = main_ancestors.next()? {
+            let deposits = self
+                .archive
+                .try_get_deposits(&rotxn, main_block_hash)?
+                .unwrap_or_default();
+            for deposit in deposits {
+                let outpoint = OutPoint::Deposit(deposit.outpoint);
+                if deposit
+                    .output
+                    .address
+                    .parse()
+                    .is_ok_and(|address| addresses.contains(&address))
+                    && self.state.utxos.get(&rotxn, &outpoint)?.is_some()
+                {
+                    res.insert(outpoint, confirmations);
+                }
+            }
+            confirmations += 1;
+        }
+        Ok(res)
+    }
+
     pub fn get_sidechain_wealth(&self) -> Result<bitcoin::Amount, Error> {
         let txn = self.env.read_txn()?;
         Ok(self.state.sidechain_wealth(&txn)?)
@@ -594,68 +1244,46 @@ impl Node {
         number: usize,
     ) -> Result<(Vec<Authorized<FilledTransaction>>, u64), Error> {
         let mut rwtxn = self.env.write_txn()?;
-        let transactions = self.mempool.take(&rwtxn, number)?;
-        let mut fee: u64 = 0;
-        let mut returned_transactions = vec![];
-        let mut spent_utxos = HashSet::new();
-        for transaction in transactions {
-            let inputs: HashSet<_> =
-                transaction.transaction.inputs.iter().copied().collect();
-            if !spent_utxos.is_disjoint(&inputs) {
-                println!("UTXO double spent");
-                self.mempool
-                    .delete(&mut rwtxn, transaction.transaction.txid())?;
-                continue;
-            }
-            if self
-                .state
-                .validate_transaction(&rwtxn, &transaction)
-                .is_err()
+        let height = self.state.get_height(&rwtxn)?;
+        let mut transactions = Vec::new();
+        for transaction in self.mempool.take_all(&rwtxn)? {
+            let txid = transaction.transaction.txid();
+            if let Some(expiry_height) =
+                self.mempool.try_get_expiry_height(&rwtxn, &txid)?
+                && height > expiry_height
             {
-                self.mempool
-                    .delete(&mut rwtxn, transaction.transaction.txid())?;
+                tracing::debug!(%txid, %expiry_height, "Dropping expired tx");
+                self.mempool.delete(&mut rwtxn, txid)?;
                 continue;
             }
-            let filled_transaction = self
-                .state
-                .fill_authorized_transaction(&rwtxn, transaction)?;
-            let value_in: u64 = filled_transaction
-                .transaction
-                .spent_utxos
-                .iter()
-                .map(GetBitcoinValue::get_bitcoin_value)
-                .sum();
-            let value_out: u64 = filled_transaction
-                .transaction
-                .transaction
-                .outputs
-                .iter()
-                .map(GetBitcoinValue::get_bitcoin_value)
-                .sum();
-            fee += value_in - value_out;
-            spent_utxos.extend(filled_transaction.transaction.inputs());
-            returned_transactions.push(filled_transaction);
+            transactions.push(transaction);
+        }
+        let selection =
+            self.state
+                .select_transactions(&rwtxn, transactions, number)?;
+        for txid in selection.rejected {
+            tracing::debug!(%txid, "Dropping invalid or double-spending tx");
+            self.mempool.delete(&mut rwtxn, txid)?;
         }
         rwtxn.commit()?;
-        Ok((returned_transactions, fee))
+        Ok((selection.txs, selection.fees))
     }
 
-    /// get a transaction from the archive or mempool, if it exists
-    pub fn try_get_transaction(
+    fn try_get_transaction_(
         &self,
+        rotxn: &heed::RoTxn,
         txid: Txid,
     ) -> Result<Option<Transaction>, Error> {
-        let rotxn = self.env.read_txn()?;
         if let Some((block_hash, txin)) = self
             .archive
-            .get_tx_inclusions(&rotxn, txid)?
+            .get_tx_inclusions(rotxn, txid)?
             .first_key_value()
         {
-            let body = self.archive.get_body(&rotxn, *block_hash)?;
+            let body = self.archive.get_body(rotxn, *block_hash)?;
             let tx = body.transactions.into_iter().nth(*txin as usize).unwrap();
             Ok(Some(tx))
         } else if let Some(auth_tx) =
-            self.mempool.transactions.get(&rotxn, &txid)?
+            self.mempool.transactions.get(rotxn, &txid)?
         {
             Ok(Some(auth_tx.transaction))
         } else {
@@ -663,6 +1291,29 @@ impl Node {
         }
     }
 
+    /// get a transaction from the archive or mempool, if it exists
+    pub fn try_get_transaction(
+        &self,
+        txid: Txid,
+    ) -> Result<Option<Transaction>, Error> {
+        let rotxn = self.env.read_txn()?;
+        self.try_get_transaction_(&rotxn, txid)
+    }
+
+    /// Get transactions from the archive or mempool, if they exist, in the
+    /// same order as the requested txids. All txs are read using the same
+    /// read txn.
+    pub fn try_get_transactions(
+        &self,
+        txids: &[Txid],
+    ) -> Result<Vec<Option<Transaction>>, Error> {
+        let rotxn = self.env.read_txn()?;
+        txids
+            .iter()
+            .map(|txid| self.try_get_transaction_(&rotxn, *txid))
+            .collect()
+    }
+
     /// get a filled transaction from the archive/state or mempool,
     /// and the tx index, if the transaction exists
     /// and can be filled with the current state.
@@ -707,6 +1358,132 @@ impl Node {
         }
     }
 
+    /** Get a proof of inclusion of a tx against the merkle root of the block
+     *  in the current chain that includes it.
+     *  Returns `None` if the tx is not included in the current chain. */
+    pub fn get_merkle_proof(
+        &self,
+        txid: Txid,
+    ) -> Result<Option<(BlockHash, MerkleProof)>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let tip = self.state.get_tip(&rotxn)?;
+        let inclusions = self.archive.get_tx_inclusions(&rotxn, txid)?;
+        let Some(block_hash) =
+            inclusions.keys().copied().try_find(|block_hash| {
+                self.archive.is_descendant(&rotxn, *block_hash, tip)
+            })?
+        else {
+            return Ok(None);
+        };
+        let body = self.archive.get_body(&rotxn, block_hash)?;
+        Ok(body.merkle_proof(txid).map(|proof| (block_hash, proof)))
+    }
+
+    /// Get the status of a tx, relative to the current tip
+    pub fn get_transaction_status(
+        &self,
+        txid: Txid,
+    ) -> Result<TxStatus, Error> {
+        let rotxn = self.env.read_txn()?;
+        let tip = self.state.get_tip(&rotxn)?;
+        let inclusions = self.archive.get_tx_inclusions(&rotxn, txid)?;
+        let included_in_chain =
+            inclusions.keys().copied().try_find(|block_hash| {
+                self.archive.is_descendant(&rotxn, *block_hash, tip)
+            })?;
+        if let Some(block_hash) = included_in_chain {
+            let tip_height = self.state.get_height(&rotxn)?;
+            let height = self.archive.get_height(&rotxn, block_hash)?;
+            return Ok(TxStatus::Confirmed {
+                height,
+                confirmations: tip_height - height,
+            });
+        }
+        if self.mempool.transactions.get(&rotxn, &txid)?.is_some() {
+            Ok(TxStatus::InMempool)
+        } else if inclusions.is_empty() {
+            Ok(TxStatus::Unknown)
+        } else {
+            Ok(TxStatus::Failed)
+        }
+    }
+
+    /// Estimate the effect of a withdrawal on the next withdrawal bundle
+    pub fn estimate_withdrawal(
+        &self,
+        main_address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+        value: u64,
+        main_fee: u64,
+    ) -> Result<WithdrawalEstimate, Error> {
+        let rotxn = self.env.read_txn()?;
+        let estimate = self.state.estimate_withdrawal(
+            &rotxn,
+            main_address,
+            value,
+            main_fee,
+        )?;
+        Ok(estimate)
+    }
+
+    /** Create a tx that cancels a withdrawal, by spending the withdrawal
+     *  output to `dest`, less `fee`.
+     *  Returns an error if the withdrawal has already been collected into a
+     *  withdrawal bundle.
+     *  The tx is not signed or submitted. */
+    pub fn cancel_withdrawal(
+        &self,
+        outpoint: OutPoint,
+        dest: Address,
+        fee: u64,
+    ) -> Result<Transaction, Error> {
+        let rotxn = self.env.read_txn()?;
+        let Some(output) = self.state.utxos.get(&rotxn, &outpoint)? else {
+            if let Some(SpentOutput {
+                inpoint: InPoint::Withdrawal { m6id },
+                ..
+            }) = self.state.stxos.get(&rotxn, &outpoint)?
+            {
+                return Err(Error::WithdrawalInBundle { outpoint, m6id });
+            }
+            return Err(state::Error::NoUtxo { outpoint }.into());
+        };
+        if !output.content.is_withdrawal() {
+            return Err(Error::NotWithdrawal { outpoint });
+        }
+        let value = output
+            .get_bitcoin_value()
+            .checked_sub(fee)
+            .ok_or(state::Error::NotEnoughValueIn)?;
+        let output = Output::new(
+            dest,
+            OutputContent::Value(BitcoinOutputContent(value)),
+        );
+        Ok(Transaction::new(vec![outpoint], vec![output]))
+    }
+
+    /// List all withdrawal bundles, oldest first, followed by the pending
+    /// bundle if there is one
+    pub fn list_withdrawal_bundles(
+        &self,
+    ) -> Result<Vec<WithdrawalBundleRecord>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let res = self.state.list_withdrawal_bundles(&rotxn)?;
+        Ok(res)
+    }
+
+    /// Get the latest withdrawal bundle that a withdrawal output was spent
+    /// into, and the status of the bundle
+    pub fn try_get_withdrawal_bundle_for_utxo(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<Option<WithdrawalBundleInfo>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let res = self
+            .state
+            .try_get_withdrawal_bundle_for_utxo(&rotxn, &outpoint)?;
+        Ok(res)
+    }
+
     pub fn get_pending_withdrawal_bundle(
         &self,
     ) -> Result<Option<WithdrawalBundle>, Error> {
@@ -718,6 +1495,15 @@ impl Node {
         Ok(bundle)
     }
 
+    /** Rebuild derived archive indices (tx inclusions and BMM results) from
+     *  the archived blocks, without re-validating the UTXO set. */
+    pub fn reindex(&self) -> Result<(), Error> {
+        let mut rwtxn = self.env.write_txn()?;
+        let () = self.archive.reindex(&mut rwtxn)?;
+        rwtxn.commit()?;
+        Ok(())
+    }
+
     pub fn remove_from_mempool(&self, txid: Txid) -> Result<(), Error> {
         let mut rwtxn = self.env.write_txn()?;
         let () = self.mempool.delete(&mut rwtxn, txid)?;
@@ -725,6 +1511,107 @@ impl Node {
         Ok(())
     }
 
+    /// Number of currently connected peers
+    pub fn num_active_peers(&self) -> usize {
+        self.net.num_active_peers()
+    }
+
+    /// Addresses of currently connected peers, and the direction of each
+    /// connection, ordered by address
+    pub fn list_peers(&self) -> Vec<(SocketAddr, net::ConnectionDirection)> {
+        self.net.active_peers()
+    }
+
+    /// Number of currently connected peers in each direction
+    pub fn peer_counts(&self) -> net::PeerCounts {
+        self.net.peer_counts()
+    }
+
+    pub fn peer_limits(&self) -> net::PeerLimits {
+        self.net.peer_limits()
+    }
+
+    /// Current score of the peer, from zero to [`net::INITIAL_PEER_SCORE`].
+    /// Peers are banned once their score reaches zero.
+    pub fn peer_score(&self, addr: SocketAddr) -> u32 {
+        self.net.peer_score(addr)
+    }
+
+    /// Returns the number of bytes in use by the node DB, and the maximum
+    /// size of the node DB.
+    pub fn db_usage(&self) -> Result<(u64, u64), Error> {
+        let used = self.env.non_free_pages_size()?;
+        let map_size = self.env.info().map_size as u64;
+        Ok((used, map_size))
+    }
+
+    /** Check that the state is consistent with the archive, and that no
+     *  output is both unspent and spent.
+     *  Returns a description of each inconsistency found. */
+    pub fn verify_state_consistency(&self) -> Result<Vec<String>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let mut inconsistencies = Vec::new();
+        let tip = self.state.get_tip(&rotxn)?;
+        let height = self.state.get_height(&rotxn)?;
+        if tip != BlockHash::default() {
+            if self.archive.try_get_header(&rotxn, tip)?.is_none() {
+                inconsistencies.push(format!("tip {tip} missing from archive"));
+            }
+            match self.archive.try_get_height(&rotxn, tip)? {
+                Some(archive_height) if archive_height != height => {
+                    inconsistencies.push(format!(
+                        "tip {tip} has height {archive_height} in archive, \
+                         but height {height} in state"
+                    ))
+                }
+                Some(_) => (),
+                None => inconsistencies
+                    .push(format!("height of tip {tip} missing from archive")),
+            }
+        }
+        let () = self.state.utxos.iter(&rotxn)?.try_for_each(|item| {
+            let (outpoint, _) = item?;
+            if self.state.stxos.get(&rotxn, &outpoint)?.is_some() {
+                inconsistencies
+                    .push(format!("{outpoint} is both unspent and spent"));
+            }
+            Ok::<_, Error>(())
+        })?;
+        Ok(inconsistencies)
+    }
+
+    /** Check that every input spent by a mempool tx is either a confirmed
+     *  UTXO, or an output of another mempool tx.
+     *  Returns a description of each invalid input found. */
+    pub fn verify_mempool(&self) -> Result<Vec<String>, Error> {
+        let rotxn = self.env.read_txn()?;
+        let txs = self.mempool.take_all(&rotxn)?;
+        let mempool_txids: HashSet<Txid> =
+            txs.iter().map(|tx| tx.transaction.txid()).collect();
+        let mut invalid_inputs = Vec::new();
+        for tx in &txs {
+            let txid = tx.transaction.txid();
+            for input in &tx.transaction.inputs {
+                let spends_mempool_output = match input {
+                    OutPoint::Regular { txid, .. } => {
+                        mempool_txids.contains(txid)
+                    }
+                    OutPoint::Coinbase { .. } | OutPoint::Deposit(_) => false,
+                };
+                if spends_mempool_output {
+                    continue;
+                }
+                if self.state.utxos.get(&rotxn, input)?.is_none() {
+                    invalid_inputs.push(format!(
+                        "mempool tx {txid} spends missing or spent UTXO \
+                         {input}"
+                    ));
+                }
+            }
+        }
+        Ok(invalid_inputs)
+    }
+
     pub fn connect_peer(&self, addr: SocketAddr) -> Result<(), Error> {
         self.net
             .connect_peer(self.env.clone(), addr)
@@ -868,4 +1755,46 @@ impl Node {
     pub fn watch_state(&self) -> impl Stream<Item = ()> {
         self.state.watch()
     }
+
+    /// Get a stream of the states of an AMM pool, whenever the pool state
+    /// changes. See [`State::watch_amm_pool`].
+    pub fn watch_amm_pool(
+        &self,
+        amm_pair: AmmPair,
+    ) -> Result<impl Stream<Item = Result<AmmPoolState, Error>>, Error> {
+        let stream = self.state.watch_amm_pool(&self.env, amm_pair)?;
+        Ok(stream.map(|res| res.map_err(Error::from)))
+    }
+
+    /// Get a stream of the price of a Dutch auction, until the auction ends.
+    /// See [`State::watch_dutch_auction_price`].
+    pub fn watch_dutch_auction_price(
+        &self,
+        auction_id: DutchAuctionId,
+    ) -> impl Stream<Item = Result<u64, Error>> {
+        self.state
+            .watch_dutch_auction_price(&self.env, auction_id)
+            .map(|res| res.map_err(Error::from))
+    }
+
+    /// Get a stream of blocks connected to and disconnected from the tip,
+    /// in the order that they were connected and disconnected.
+    /// If the stream falls too far behind, it yields an error with the
+    /// number of skipped events.
+    pub fn block_events(
+        &self,
+    ) -> impl Stream<Item = Result<BlockEvent, BroadcastStreamRecvError>> {
+        BroadcastStream::new(self.net_task.subscribe_block_events())
+    }
+
+    /// Get a stream of reorgs that disconnect blocks from the tip.
+    /// Unlike [`Self::watch_state`], blocks that extend the tip without
+    /// disconnecting any blocks are not reported.
+    /// If the stream falls too far behind, it yields an error with the
+    /// number of skipped reorgs.
+    pub fn watch_reorgs(
+        &self,
+    ) -> impl Stream<Item = Result<Reorg, BroadcastStreamRecvError>> {
+        BroadcastStream::new(self.net_task.subscribe_reorgs())
+    }
 }
@@ -17,7 +17,7 @@ use futures::{
 };
 use heed::RwTxn;
 use thiserror::Error;
-use tokio::task::JoinHandle;
+use tokio::{sync::broadcast, task::JoinHandle};
 use tokio_stream::StreamNotifyClose;
 use tokio_util::task::LocalPoolHandle;
 
@@ -27,10 +27,12 @@ use crate::{
     mempool::{self, MemPool},
     net::{
         self, Net, PeerConnectionInfo, PeerConnectionMessage, PeerInfoRx,
-        PeerRequest, PeerResponse, PeerStateId,
+        PeerMisbehavior, PeerRequest, PeerResponse, PeerStateId,
     },
     state::{self, State},
-    types::{BlockHash, BmmResult, Body, Header, Tip},
+    types::{
+        Block, BlockEvent, BlockHash, BmmResult, Body, Header, Reorg, Tip,
+    },
 };
 
 #[derive(Debug, Error)]
@@ -43,6 +45,11 @@ pub enum Error {
     ForwardMainchainTaskRequest,
     #[error("heed error")]
     Heed(#[from] heed::Error),
+    #[error("invalid block {block_hash}")]
+    InvalidBlock {
+        block_hash: BlockHash,
+        source: state::Error,
+    },
     #[error("mempool error")]
     MemPool(#[from] mempool::Error),
     #[error("Net error")]
@@ -106,14 +113,15 @@ async fn connect_tip_(
         .get_two_way_peg_data(header.prev_main_hash, last_deposit_block_hash)
         .await?;
     let block_hash = header.hash();
-    let _fees: u64 = state.validate_block(rwtxn, header, body)?;
+    let (_fees, filled_txs) = state
+        .validate_block(rwtxn, header, body)
+        .map_err(|source| Error::InvalidBlock { block_hash, source })?;
+    let () = state.connect_filled_block(rwtxn, header, body, filled_txs)?;
     if tracing::enabled!(tracing::Level::DEBUG) {
         let merkle_root = body.compute_merkle_root();
         let height = state.get_height(rwtxn)?;
         tracing::debug!(%height, %merkle_root, %block_hash,
                             "connected body")
-    } else {
-        let () = state.connect_block(rwtxn, header, body)?;
     }
     let () = state.connect_two_way_peg_data(rwtxn, &two_way_peg_data)?;
     let () = archive.put_header(rwtxn, header)?;
@@ -151,10 +159,23 @@ async fn disconnect_tip_(
             .get_two_way_peg_data(tip_header.prev_main_hash, start_block_hash)
             .await?
     };
+    // Fees must be computed before the spent UTXOs are restored
+    let fees: Vec<u64> = tip_body
+        .transactions
+        .iter()
+        .map(|tx| {
+            let filled_tx =
+                state.fill_transaction_from_stxos(rwtxn, tx.clone())?;
+            Ok::<_, Error>(filled_tx.bitcoin_fee().unwrap_or(0))
+        })
+        .collect::<Result<_, _>>()?;
     let () = state.disconnect_two_way_peg_data(rwtxn, &two_way_peg_data)?;
     let () = state.disconnect_tip(rwtxn, &tip_header, &tip_body)?;
-    for transaction in tip_body.authorized_transactions().iter().rev() {
-        mempool.put(rwtxn, transaction)?;
+    for (transaction, fee) in
+        tip_body.authorized_transactions().iter().zip(fees).rev()
+    {
+        // Txs evicted due to mempool limits are dropped
+        let _evicted = mempool.put(rwtxn, transaction, fee, None)?;
     }
     Ok(())
 }
@@ -169,6 +190,8 @@ async fn reorg_to_tip(
     drivechain: &bip300301::Drivechain,
     mempool: &MemPool,
     state: &State,
+    block_events_tx: &broadcast::Sender<BlockEvent>,
+    reorgs_tx: &broadcast::Sender<Reorg>,
     #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
     zmq_pub_handler: &ZmqPubHandler,
     new_tip: Tip,
@@ -200,6 +223,12 @@ async fn reorg_to_tip(
             Ok((header, body))
         })
         .collect()?;
+    // Blocks to disconnect, starting from the tip
+    let blocks_to_disconnect: Vec<Block> = archive
+        .ancestors(&rwtxn, tip_hash)
+        .take_while(|block_hash| Ok(*block_hash != common_ancestor))
+        .map(|block_hash| Ok(archive.get_block(&rwtxn, block_hash)?))
+        .collect()?;
     // Disconnect tip until common ancestor is reached
     let common_ancestor_height = archive.get_height(&rwtxn, common_ancestor)?;
     for _ in 0..tip_height - common_ancestor_height {
@@ -220,6 +249,37 @@ async fn reorg_to_tip(
     assert_eq!(tip, new_tip.block_hash);
     rwtxn.commit()?;
     tracing::info!("synced to tip: {}", new_tip.block_hash);
+    // Extending the tip without disconnecting any blocks is not a reorg
+    if !blocks_to_disconnect.is_empty() {
+        let reorg = Reorg {
+            disconnected: blocks_to_disconnect
+                .iter()
+                .map(|block| block.header.hash())
+                .collect(),
+            connected: blocks_to_apply
+                .iter()
+                .rev()
+                .map(|(header, _)| header.hash())
+                .collect(),
+            common_ancestor,
+        };
+        let _: Result<_, _> = reorgs_tx.send(reorg);
+    }
+    /* Block events are sent in the order that blocks were disconnected and
+     * connected. Sending only fails if there are no subscribers. */
+    for block in blocks_to_disconnect {
+        let _: Result<_, _> =
+            block_events_tx.send(BlockEvent::Disconnected(block));
+    }
+    for (idx, (header, body)) in blocks_to_apply.iter().rev().enumerate() {
+        let block = Block {
+            header: header.clone(),
+            body: body.clone(),
+            height: common_ancestor_height + idx as u32 + 1,
+        };
+        let _: Result<_, _> =
+            block_events_tx.send(BlockEvent::Connected(block));
+    }
     #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
     {
         for (idx, (header, _body)) in
@@ -247,6 +307,8 @@ struct NetTaskContext {
     mempool: MemPool,
     net: Net,
     state: State,
+    block_events_tx: broadcast::Sender<BlockEvent>,
+    reorgs_tx: broadcast::Sender<Reorg>,
     #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
     zmq_pub_handler: Arc<ZmqPubHandler>,
 }
@@ -318,7 +380,9 @@ impl NetTask {
                 if header.hash() != block_hash {
                     // Invalid response
                     tracing::warn!(%addr, ?req, ?resp,"Invalid response from peer; unexpected block hash");
-                    let () = ctxt.net.remove_active_peer(addr);
+                    let () = ctxt
+                        .net
+                        .penalize_peer(addr, PeerMisbehavior::InvalidResponse);
                     return Ok(());
                 }
                 {
@@ -436,13 +500,17 @@ impl NetTask {
                 // check that the end header is as requested
                 let Some(end_header) = headers.last() else {
                     tracing::warn!(%addr, ?req, "Invalid response from peer; missing end header");
-                    let () = ctxt.net.remove_active_peer(addr);
+                    let () = ctxt
+                        .net
+                        .penalize_peer(addr, PeerMisbehavior::InvalidResponse);
                     return Ok(());
                 };
                 let end_header_hash = end_header.hash();
                 if end_header_hash != end {
                     tracing::warn!(%addr, ?req, ?end_header,"Invalid response from peer; unexpected end header");
-                    let () = ctxt.net.remove_active_peer(addr);
+                    let () = ctxt
+                        .net
+                        .penalize_peer(addr, PeerMisbehavior::InvalidResponse);
                     return Ok(());
                 }
                 // Must be at least one header due to previous check
@@ -452,7 +520,9 @@ impl NetTask {
                     || start_hash == BlockHash::default())
                 {
                     tracing::warn!(%addr, ?req, ?start_hash, "Invalid response from peer; invalid start hash");
-                    let () = ctxt.net.remove_active_peer(addr);
+                    let () = ctxt
+                        .net
+                        .penalize_peer(addr, PeerMisbehavior::InvalidResponse);
                     return Ok(());
                 }
                 // check that the end header height is as expected
@@ -462,7 +532,10 @@ impl NetTask {
                         ctxt.archive.get_height(&rotxn, start_hash)?;
                     if start_height + headers.len() as u32 != height {
                         tracing::warn!(%addr, ?req, ?start_hash, "Invalid response from peer; invalid end height");
-                        let () = ctxt.net.remove_active_peer(addr);
+                        let () = ctxt.net.penalize_peer(
+                            addr,
+                            PeerMisbehavior::InvalidResponse,
+                        );
                         return Ok(());
                     }
                 }
@@ -471,7 +544,10 @@ impl NetTask {
                 for header in &headers {
                     if header.prev_side_hash != prev_side_hash {
                         tracing::warn!(%addr, ?req, ?headers,"Invalid response from peer; non-sequential headers");
-                        let () = ctxt.net.remove_active_peer(addr);
+                        let () = ctxt.net.penalize_peer(
+                            addr,
+                            PeerMisbehavior::InvalidResponse,
+                        );
                         return Ok(());
                     }
                     prev_side_hash = header.hash();
@@ -491,7 +567,19 @@ impl NetTask {
                                 .try_get_header(&rwtxn, header.prev_side_hash)?
                                 .is_some()
                         {
-                            ctxt.archive.put_header(&mut rwtxn, header)?;
+                            match ctxt.archive.put_header(&mut rwtxn, header) {
+                                Ok(()) => (),
+                                Err(archive::Error::InvalidTimestamp(err)) => {
+                                    let err = anyhow::Error::from(err);
+                                    tracing::warn!(%addr, %block_hash, "Invalid response from peer; {err:#}");
+                                    let () = ctxt.net.penalize_peer(
+                                        addr,
+                                        PeerMisbehavior::InvalidBlock,
+                                    );
+                                    break;
+                                }
+                                Err(err) => return Err(err.into()),
+                            }
                         } else {
                             break;
                         }
@@ -524,12 +612,15 @@ impl NetTask {
                 req @ (PeerRequest::GetBlock { .. }
                 | PeerRequest::GetHeaders { .. }
                 | PeerRequest::Heartbeat(_)
+                | PeerRequest::PeerAddrs { .. }
                 | PeerRequest::PushTransaction { .. }),
                 resp,
             ) => {
                 // Invalid response
                 tracing::warn!(%addr, ?req, ?resp,"Invalid response from peer");
-                let () = ctxt.net.remove_active_peer(addr);
+                let () = ctxt
+                    .net
+                    .penalize_peer(addr, PeerMisbehavior::InvalidResponse);
                 Ok(())
             }
         }
@@ -671,13 +762,15 @@ impl NetTask {
                         }
                     }
                 }
-                MailboxItem::NewTipReady(new_tip, _addr, resp_tx) => {
-                    let reorg_applied = reorg_to_tip(
+                MailboxItem::NewTipReady(new_tip, addr, resp_tx) => {
+                    let reorg_res = reorg_to_tip(
                         &self.ctxt.env,
                         &self.ctxt.archive,
                         &self.ctxt.drivechain,
                         &self.ctxt.mempool,
                         &self.ctxt.state,
+                        &self.ctxt.block_events_tx,
+                        &self.ctxt.reorgs_tx,
                         #[cfg(all(
                             not(target_os = "windows"),
                             feature = "zmq"
@@ -685,7 +778,22 @@ impl NetTask {
                         &self.ctxt.zmq_pub_handler,
                         new_tip,
                     )
-                    .await?;
+                    .await;
+                    let reorg_applied = match (reorg_res, addr) {
+                        (
+                            Err(Error::InvalidBlock { block_hash, source }),
+                            Some(addr),
+                        ) => {
+                            let err = anyhow::Error::from(source);
+                            tracing::warn!(%addr, %block_hash, "Invalid block from peer: {err:#}");
+                            let () = self.ctxt.net.penalize_peer(
+                                addr,
+                                PeerMisbehavior::InvalidBlock,
+                            );
+                            false
+                        }
+                        (reorg_res, _) => reorg_res?,
+                    };
                     if let Some(resp_tx) = resp_tx {
                         let () = resp_tx
                             .send(reorg_applied)
@@ -703,11 +811,32 @@ impl NetTask {
                 }
                 MailboxItem::PeerInfo(Some((addr, Some(peer_info)))) => {
                     match peer_info {
+                        PeerConnectionInfo::Connected => {
+                            let () = self
+                                .ctxt
+                                .net
+                                .handle_peer_connected(&self.ctxt.env, addr)?;
+                        }
                         PeerConnectionInfo::Error(err) => {
+                            let misbehavior = err.misbehavior();
                             let err = anyhow::anyhow!(err);
                             tracing::error!(%addr, err = format!("{err:#}"), "Peer connection error");
+                            if let Some(misbehavior) = misbehavior {
+                                let () = self
+                                    .ctxt
+                                    .net
+                                    .penalize_peer(addr, misbehavior);
+                            }
+                            let () = self.ctxt.net.forget_unreachable_peer(
+                                &self.ctxt.env,
+                                addr,
+                            )?;
                             let () = self.ctxt.net.remove_active_peer(addr);
                         }
+                        PeerConnectionInfo::Misbehavior(misbehavior) => {
+                            let () =
+                                self.ctxt.net.penalize_peer(addr, misbehavior);
+                        }
                         PeerConnectionInfo::NeedBmmVerification {
                             main_hash,
                             peer_state_id,
@@ -737,19 +866,59 @@ impl NetTask {
                                 })?;
                         }
                         PeerConnectionInfo::NewTipReady(new_tip) => {
+                            let () = self.ctxt.net.record_useful_peer(addr);
                             self.new_tip_ready_tx
                                 .unbounded_send((new_tip, Some(addr), None))
                                 .map_err(|_| Error::SendNewTipReady)?;
                         }
-                        PeerConnectionInfo::NewTransaction(new_tx) => {
+                        PeerConnectionInfo::NewTransaction {
+                            tx: new_tx,
+                            fee,
+                        } => {
+                            let txid = new_tx.transaction.txid();
                             let mut rwtxn = self.ctxt.env.write_txn()?;
-                            self.ctxt.mempool.put(&mut rwtxn, &new_tx)?;
-                            rwtxn.commit()?;
-                            // broadcast
-                            let () = self
+                            let evicted = match self
                                 .ctxt
-                                .net
-                                .push_tx(HashSet::from_iter([addr]), new_tx);
+                                .mempool
+                                .put(&mut rwtxn, &new_tx, fee, None)
+                            {
+                                Ok(evicted) => evicted,
+                                // Conflicting txs are not relayed
+                                Err(err) if err.is_conflict() => {
+                                    let err = anyhow::Error::from(err);
+                                    tracing::debug!(
+                                        %txid,
+                                        "Not relaying tx: {err:#}"
+                                    );
+                                    continue;
+                                }
+                                Err(err) => return Err(err.into()),
+                            };
+                            rwtxn.commit()?;
+                            let () = self.ctxt.net.record_useful_peer(addr);
+                            // Do not broadcast txs that were evicted
+                            if evicted.contains(&txid) {
+                                tracing::debug!(
+                                    %txid,
+                                    "Mempool full, not relaying tx"
+                                );
+                            } else {
+                                let () = self.ctxt.net.push_tx(
+                                    HashSet::from_iter([addr]),
+                                    new_tx,
+                                );
+                            }
+                        }
+                        PeerConnectionInfo::PeerAddrs {
+                            listen_addr,
+                            addrs,
+                        } => {
+                            let () = self.ctxt.net.handle_peer_addrs(
+                                &self.ctxt.env,
+                                addr,
+                                listen_addr,
+                                addrs,
+                            )?;
                         }
                         PeerConnectionInfo::Response(boxed) => {
                             let (resp, req) = *boxed;
@@ -783,9 +952,18 @@ pub(super) struct NetTaskHandle {
     /// An optional oneshot sender can be used receive the result of attempting
     /// to reorg to the new tip, on the corresponding oneshot receiver.
     new_tip_ready_tx: UnboundedSender<NewTipReadyMessage>,
+    /// Send blocks connected to and disconnected from the tip
+    block_events_tx: broadcast::Sender<BlockEvent>,
+    /// Send blocks changed by reorgs
+    reorgs_tx: broadcast::Sender<Reorg>,
 }
 
 impl NetTaskHandle {
+    /// Max number of block events buffered for each subscriber
+    const BLOCK_EVENTS_CAPACITY: usize = 256;
+    /// Max number of reorgs buffered for each subscriber
+    const REORGS_CAPACITY: usize = 64;
+
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         local_pool: LocalPoolHandle,
@@ -801,6 +979,9 @@ impl NetTaskHandle {
         #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
         zmq_pub_handler: Arc<ZmqPubHandler>,
     ) -> Self {
+        let (block_events_tx, _) =
+            broadcast::channel(Self::BLOCK_EVENTS_CAPACITY);
+        let (reorgs_tx, _) = broadcast::channel(Self::REORGS_CAPACITY);
         let ctxt = NetTaskContext {
             env,
             archive,
@@ -809,6 +990,8 @@ impl NetTaskHandle {
             mempool,
             net,
             state,
+            block_events_tx: block_events_tx.clone(),
+            reorgs_tx: reorgs_tx.clone(),
             #[cfg(all(not(target_os = "windows"), feature = "zmq"))]
             zmq_pub_handler,
         };
@@ -835,9 +1018,21 @@ impl NetTaskHandle {
         NetTaskHandle {
             task: Arc::new(task),
             new_tip_ready_tx,
+            block_events_tx,
+            reorgs_tx,
         }
     }
 
+    /// Subscribe to blocks connected to and disconnected from the tip
+    pub fn subscribe_block_events(&self) -> broadcast::Receiver<BlockEvent> {
+        self.block_events_tx.subscribe()
+    }
+
+    /// Subscribe to reorgs that disconnect blocks from the tip
+    pub fn subscribe_reorgs(&self) -> broadcast::Receiver<Reorg> {
+        self.reorgs_tx.subscribe()
+    }
+
     /// Push a tip that is ready to reorg to.
     #[allow(dead_code)]
     pub fn new_tip_ready(&self, new_tip: Tip) -> Result<(), Error> {
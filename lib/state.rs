@@ -8,8 +8,11 @@ use bip300301::{
     bitcoin::{self, transaction::Version as BitcoinTxVersion},
     TwoWayPegData, WithdrawalBundleStatus,
 };
-use futures::Stream;
-use heed::{types::SerdeBincode, Database, RoTxn, RwTxn};
+use futures::{future, Stream, StreamExt as _};
+use heed::{
+    types::{Bytes, DecodeIgnore, SerdeBincode},
+    Database, RoTxn, RwTxn,
+};
 use itertools::Itertools;
 use nonempty::{nonempty, NonEmpty};
 use serde::{Deserialize, Serialize};
@@ -19,13 +22,16 @@ use crate::{
     authorization::{Authorization, VerifyingKey},
     types::{
         self, hashes, Address, AggregatedWithdrawal, AmmBurn, AmmMint, AmmSwap,
-        AssetId, Authorized, AuthorizedTransaction, BitAssetDataUpdates,
-        BitAssetId, BitcoinOutputContent, BlockHash, Body, DutchAuctionBid,
+        AssetId, AtomicSwapId, AtomicSwapParams, AtomicSwapRelease, Authorized,
+        AuthorizedTransaction, BitAssetDataUpdates, BitAssetId, BitAssetKey,
+        BitcoinOutputContent, BlockHash, Body, DutchAuctionBid,
         DutchAuctionCollect, DutchAuctionId, DutchAuctionParams,
         EncryptionPubKey, FilledOutput, FilledOutputContent, FilledTransaction,
-        GetAddress as _, GetBitcoinValue as _, Hash, Header, InPoint,
-        MerkleRoot, OutPoint, OutputContent, SpentOutput, Transaction, TxData,
-        Txid, Update, Verify as _, WithdrawalBundle,
+        GetAddress as _, GetBitcoinValue as _, Hash, Header, InPoint, M6id,
+        MerkleRoot, OrderCancel, OrderFill, OrderId, OrderParams, OutPoint,
+        OutputContent, SpentOutput, Transaction, TxData, Txid, Update,
+        Verify as _, WithdrawalBundle, WithdrawalBundleInfo,
+        WithdrawalBundleRecord, WithdrawalStatus,
     },
     util::{EnvExt, UnitKey, Watchable, WatchableDb},
 };
@@ -276,6 +282,11 @@ impl BitAssetData {
         })
     }
 
+    /// Current total supply
+    pub fn total_supply(&self) -> u64 {
+        self.total_supply.latest().data
+    }
+
     /// get the current bitasset data
     pub fn current(&self) -> types::BitAssetData {
         types::BitAssetData {
@@ -288,6 +299,38 @@ impl BitAssetData {
     }
 }
 
+/// Errors when locking an asset in an atomic swap
+#[derive(Debug, thiserror::Error)]
+pub enum AtomicSwapLockError {
+    #[error("Tx expired; Atomic swap timeout block already exists")]
+    Expired,
+    #[error("Invalid tx; Atomic swap amount cannot be `0`")]
+    ZeroAmount,
+}
+
+/// Errors when claiming or refunding an atomic swap
+#[derive(Debug, thiserror::Error)]
+pub enum AtomicSwapReleaseError {
+    #[error("Atomic swap has timed out, and can no longer be claimed")]
+    Expired,
+    #[error("Incorrect asset specified")]
+    IncorrectAsset,
+    #[error("Amount must be exactly equal to the locked amount")]
+    IncorrectAmount,
+    #[error("Insufficient outputs to {address}")]
+    InsufficientOutputs { address: Address },
+    #[error("Preimage does not match the hash lock")]
+    InvalidPreimage,
+    #[error("Invalid TxData")]
+    InvalidTxData,
+    #[error("Atomic swap not found")]
+    MissingSwap,
+    #[error("Atomic swap has not timed out yet, and cannot be refunded")]
+    NotExpired,
+    #[error("Atomic swap has already been claimed or refunded")]
+    NotLocked,
+}
+
 /// Errors when bidding on a Dutch auction
 #[derive(Debug, thiserror::Error)]
 pub enum DutchAuctionBidError {
@@ -348,6 +391,95 @@ pub enum DutchAuctionCollectError {
     MissingAuction,
 }
 
+/// Errors when cancelling an order
+#[derive(Debug, thiserror::Error)]
+pub enum OrderCancelError {
+    #[error("Incorrect offered asset")]
+    IncorrectOfferedAsset,
+    #[error(
+        "Offered asset amount must be exactly equal to the amount remaining"
+    )]
+    IncorrectOfferedAssetAmount,
+    #[error("Incorrect receive asset specified")]
+    IncorrectReceiveAsset,
+    #[error(
+        "Receive asset amount must be exactly equal to the amount received"
+    )]
+    IncorrectReceiveAssetAmount,
+    #[error("Invalid TxData")]
+    InvalidTxData,
+    #[error("Order not found")]
+    MissingOrder,
+}
+
+/// Errors when filling an order
+#[derive(Debug, thiserror::Error)]
+pub enum OrderFillError {
+    #[error("Incorrect receive asset specified")]
+    IncorrectReceiveAsset,
+    #[error("Incorrect spend asset")]
+    IncorrectSpendAsset,
+    #[error("Tx can only be applied at the order price")]
+    InvalidPrice,
+    #[error("Invalid TxData")]
+    InvalidTxData,
+    #[error("Order not found")]
+    MissingOrder,
+    #[error("Fill quantity is more than remains in the order")]
+    QuantityTooLarge,
+    #[error("Fill quantity cannot be `0`")]
+    ZeroQuantity,
+}
+
+/// Errors when placing an order
+#[derive(Debug, thiserror::Error)]
+pub enum OrderPlaceError {
+    #[error("Invalid tx; Order total value overflows")]
+    Overflow,
+    #[error("Invalid tx; Sell asset and buy asset must differ")]
+    SameAsset,
+    #[error("Invalid tx; Order amount cannot be `0`")]
+    ZeroAmount,
+    #[error("Invalid tx; Order price cannot be `0`")]
+    ZeroPrice,
+}
+
+/// Errors when validating a genesis config
+#[derive(Debug, thiserror::Error)]
+pub enum GenesisError {
+    #[error(
+        "AMM protocol fee of {fee_bps} basis points exceeds the maximum \
+         of {max} basis points",
+        max = AmmProtocolFee::MAX_FEE_BPS
+    )]
+    AmmProtocolFeeTooHigh { fee_bps: u16 },
+    #[error(
+        "AMM max price impact of {max_price_impact_bps} basis points \
+         exceeds the maximum of {max} basis points",
+        max = AmmPoolState::MAX_PRICE_IMPACT_BPS
+    )]
+    AmmMaxPriceImpactTooHigh { max_price_impact_bps: u16 },
+    #[error("BitAsset `{plain_name}` is allocated more than once")]
+    DuplicateBitAsset { plain_name: String },
+    #[error(
+        "genesis config does not match the genesis config with which the \
+         state was created. Restore the original genesis config, or resync \
+         with a new data directory"
+    )]
+    Mismatch,
+    #[error("BitAsset `{plain_name}` has no balances allocated")]
+    NoBalances { plain_name: String },
+    #[error("total supply of BitAsset `{plain_name}` overflows")]
+    SupplyOverflow { plain_name: String },
+    #[error(
+        "BitAsset `{plain_name}` has a zero balance allocated to {address}"
+    )]
+    ZeroBalance {
+        plain_name: String,
+        address: Address,
+    },
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum InvalidHeaderError {
     #[error("expected block hash {expected}, but computed {computed}")]
@@ -362,8 +494,55 @@ pub enum InvalidHeaderError {
     },
 }
 
+/// Stage of transaction validation at which a tx was rejected
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TxValidationStage {
+    /// Looking up the UTXOs spent by the tx
+    Fill,
+    /// Checking that the tx is signed by the owners of the spent UTXOs
+    Authorization,
+    /// Checking the number of tx inputs and outputs
+    Size,
+    /// Checking BitAsset reservation inputs and outputs
+    Reservations,
+    /// Checking BitAsset and BitAsset control coin inputs and outputs
+    BitAssets,
+    /// Checking that AMM LP tokens are conserved
+    LpTokens,
+    /// Checking that the AMM pools for AMM swaps and burns exist
+    AmmPools,
+    /// Checking that the Bitcoin value in covers the value out
+    Fee,
+}
+
+impl std::fmt::Display for TxValidationStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stage = match self {
+            Self::Fill => "fill",
+            Self::Authorization => "authorization",
+            Self::Size => "size",
+            Self::Reservations => "reservations",
+            Self::BitAssets => "BitAssets",
+            Self::LpTokens => "LP tokens",
+            Self::AmmPools => "AMM pools",
+            Self::Fee => "fee",
+        };
+        f.write_str(stage)
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("address activity index is disabled")]
+    AddressActivityIndexDisabled,
+    #[error(
+        "address activity index starts at height {start_height}, so history \
+         from height {from_height} is incomplete"
+    )]
+    AddressActivityIndexIncomplete { from_height: u32, start_height: u32 },
     #[error("failed to verify authorization")]
     AuthorizationError,
     #[error("AMM burn overflow")]
@@ -376,12 +555,37 @@ pub enum Error {
     AmmLpTokenUnderflow,
     #[error("AMM pool invariant")]
     AmmPoolInvariant,
+    #[error(
+        "AMM swap price impact exceeds the maximum of \
+         {max_price_impact_bps} basis points"
+    )]
+    AmmPriceImpactExceeded { max_price_impact_bps: u16 },
+    #[error(
+        "AMM swap would receive {amount_receive}, which is less than the \
+         minimum of {min_amount_receive}"
+    )]
+    AmmSwapBelowMinReceive {
+        amount_receive: u64,
+        min_amount_receive: u64,
+    },
+    #[error("not permitted to create AMM pool for {asset0}-{asset1}")]
+    AmmPoolCreationNotPermitted { asset0: AssetId, asset1: AssetId },
+    #[error(transparent)]
+    AtomicSwapLock(#[from] AtomicSwapLockError),
+    #[error(transparent)]
+    AtomicSwapRelease(#[from] AtomicSwapReleaseError),
     #[error("bad coinbase output content")]
     BadCoinbaseOutputContent,
     #[error("bitasset {name_hash:?} already registered")]
     BitAssetAlreadyRegistered { name_hash: Hash },
+    #[error("bincode error")]
+    Bincode(#[from] bincode::Error),
     #[error("bundle too heavy {weight} > {max_weight}")]
     BundleTooHeavy { weight: u64, max_weight: u64 },
+    #[error("invalid body: duplicate merkle tree nodes (CVE-2012-2459)")]
+    DuplicateMerkleNodes,
+    #[error("invalid body: duplicate tx {txid}")]
+    DuplicateTxid { txid: Txid },
     #[error(transparent)]
     DutchAuctionBid(#[from] DutchAuctionBidError),
     #[error(transparent)]
@@ -390,8 +594,14 @@ pub enum Error {
     DutchAuctionCollect(#[from] DutchAuctionCollectError),
     #[error("failed to fill tx output contents: invalid transaction")]
     FillTxOutputContentsFailed,
+    #[error("expected filled tx {expected}, but received {received}")]
+    FilledTxMismatch { expected: Txid, received: Txid },
+    #[error("expected {expected} filled txs, but received {received}")]
+    FilledTxsMismatch { expected: usize, received: usize },
     #[error("heed error")]
     Heed(#[from] heed::Error),
+    #[error("Insufficient AMM protocol fee paid to {address}: expected {fee}")]
+    InsufficientAmmProtocolFee { address: Address, fee: u64 },
     #[error("Insufficient liquidity")]
     InsufficientLiquidity,
     #[error("Invalid AMM burn")]
@@ -400,6 +610,10 @@ pub enum Error {
     InvalidAmmMint,
     #[error("Invalid AMM swap")]
     InvalidAmmSwap,
+    #[error("Invalid atomic swap lock")]
+    InvalidAtomicSwapLock,
+    #[error("Invalid atomic swap release")]
+    InvalidAtomicSwapRelease,
     #[error("invalid body: expected merkle root {expected}, but computed {computed}")]
     InvalidBody {
         expected: MerkleRoot,
@@ -409,14 +623,26 @@ pub enum Error {
     InvalidDutchAuctionBid,
     #[error("Invalid Dutch auction collect")]
     InvalidDutchAuctionCollect,
+    #[error("invalid genesis: {0}")]
+    InvalidGenesis(#[from] GenesisError),
+    #[error("Invalid order cancel")]
+    InvalidOrderCancel,
+    #[error("Invalid order fill")]
+    InvalidOrderFill,
+    #[error("Invalid order placement")]
+    InvalidOrderPlace,
     #[error("invalid header: {0}")]
     InvalidHeader(InvalidHeaderError),
     #[error(
         "The last output in a BitAsset registration tx must be a control coin"
     )]
     LastOutputNotControlCoin,
+    #[error("LP token outputs do not match LP token inputs")]
+    LpTokensNotConserved,
     #[error("missing AMM pool state for {asset0}-{asset1}")]
     MissingAmmPoolState { asset0: AssetId, asset1: AssetId },
+    #[error("missing atomic swap {0}")]
+    MissingAtomicSwap(AtomicSwapId),
     #[error("missing BitAsset {bitasset:?}")]
     MissingBitAsset { bitasset: BitAssetId },
     #[error(
@@ -427,8 +653,17 @@ pub enum Error {
     MissingBitAssetInput { name_hash: Hash },
     #[error("missing Dutch auction {0}")]
     MissingDutchAuction(DutchAuctionId),
+    #[error("missing order {0}")]
+    MissingOrder(OrderId),
     #[error("missing BitAsset reservation {txid}")]
     MissingReservation { txid: Txid },
+    #[error(
+        "BitAsset {bitasset:?} updated more than {max_updates} time(s) in a single block"
+    )]
+    MultipleBitAssetUpdates {
+        bitasset: BitAssetId,
+        max_updates: usize,
+    },
     #[error("no BitAssets to mint")]
     NoBitAssetsToMint,
     #[error("no BitAssets to update")]
@@ -443,12 +678,40 @@ pub enum Error {
     NoStxo { outpoint: OutPoint },
     #[error("utxo {outpoint} doesn't exist")]
     NoUtxo { outpoint: OutPoint },
+    #[error(transparent)]
+    OrderCancel(#[from] OrderCancelError),
+    #[error(transparent)]
+    OrderFill(#[from] OrderFillError),
+    #[error(transparent)]
+    OrderPlace(#[from] OrderPlaceError),
+    #[error(
+        "revealed nonce for BitAsset {name_hash:?} implies reservation \
+         commitment {implied_commitment:?}, which does not match any spent \
+         reservation"
+    )]
+    ReservationCommitmentMismatch {
+        name_hash: Hash,
+        implied_commitment: Hash,
+    },
     #[error("Failed to revert AMM mint")]
     RevertAmmMint,
     #[error("Failed to revert AMM swap")]
     RevertAmmSwap,
+    #[error("Failed to revert atomic swap release")]
+    RevertAtomicSwapRelease,
     #[error("Failed to revert Dutch Auction collect")]
     RevertDutchAuctionCollect,
+    #[error("Failed to revert order cancel")]
+    RevertOrderCancel,
+    #[error(
+        "data directory has schema version {}, but version {expected} is \
+         required. Resync required: remove the data directory, and restart",
+        .stored.map_or_else(
+            || "none".to_owned(),
+            |stored| stored.to_string()
+        )
+    )]
+    SchemaVersionMismatch { stored: Option<u32>, expected: u32 },
     #[error(
         "The second-last output in a BitAsset registration tx \
              must be the BitAsset mint, \
@@ -463,8 +726,20 @@ pub enum Error {
     TooFewBitAssetsToDutchAuctionCreate,
     #[error("Too few BitAsset control coin outputs")]
     TooFewBitAssetControlOutputs,
+    #[error("too many tx inputs: {n_inputs} > {max_inputs}")]
+    TooManyInputs { n_inputs: usize, max_inputs: usize },
+    #[error("too many tx outputs: {n_outputs} > {max_outputs}")]
+    TooManyOutputs {
+        n_outputs: usize,
+        max_outputs: usize,
+    },
     #[error("Mint would cause total supply to overflow")]
     TotalSupplyOverflow,
+    #[error("transaction rejected at {stage} validation stage")]
+    TxValidation {
+        stage: TxValidationStage,
+        source: Box<Error>,
+    },
     #[error("Reverting Mint would cause total supply to underflow")]
     TotalSupplyUnderflow,
     #[error(
@@ -488,8 +763,260 @@ pub enum Error {
     },
     #[error("utxo double spent")]
     UtxoDoubleSpent,
-    #[error("wrong public key for address")]
-    WrongPubKeyForAddress,
+    #[error("wrong public key for address of input {input}")]
+    WrongPubKeyForAddress { input: OutPoint },
+}
+
+impl Error {
+    /// Attach the tx validation stage at which the error occurred.
+    /// Database errors are not the result of an invalid tx, and are
+    /// returned unchanged.
+    fn at_tx_validation_stage(self, stage: TxValidationStage) -> Self {
+        match self {
+            Self::Heed(_) => self,
+            err => Self::TxValidation {
+                stage,
+                source: Box::new(err),
+            },
+        }
+    }
+}
+
+/** Protocol fee charged on AMM swaps, in addition to the LP fee.
+ *  The fee is paid in the spend asset, and must be sent to the fee address
+ *  by the swap tx. Fees sent to [`Address::BURN`] are burned.
+ *  Swaps that do not pay the fee are invalid, so the fee is a chain
+ *  parameter, set in the [`Genesis`] config. */
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct AmmProtocolFee {
+    /// Fee, in basis points of the amount spent
+    pub fee_bps: u16,
+    /// Address to which the protocol fee must be paid
+    pub address: Address,
+}
+
+impl AmmProtocolFee {
+    /// Maximum fee, in basis points
+    pub const MAX_FEE_BPS: u16 = 10_000;
+
+    /// Protocol fee for a swap spending the specified amount
+    pub fn fee(&self, amount_spend: u64) -> u64 {
+        /* Truncation to `u64` is safe, as
+         * `fee_bps <= MAX_FEE_BPS` */
+        ((amount_spend as u128 * self.fee_bps as u128)
+            / Self::MAX_FEE_BPS as u128) as u64
+    }
+}
+
+impl Default for AmmProtocolFee {
+    fn default() -> Self {
+        Self {
+            fee_bps: 0,
+            address: Address::BURN,
+        }
+    }
+}
+
+/// Limits on the number of inputs and outputs in a tx.
+/// Txs that exceed these limits are invalid, so the limits are chain
+/// parameters, set in the [`Genesis`] config.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct TxLimits {
+    /// Max number of inputs in a tx
+    pub max_inputs: usize,
+    /// Max number of outputs in a tx
+    pub max_outputs: usize,
+}
+
+impl TxLimits {
+    pub const DEFAULT_MAX_INPUTS: usize = 1024;
+    pub const DEFAULT_MAX_OUTPUTS: usize = 1024;
+}
+
+impl Default for TxLimits {
+    fn default() -> Self {
+        Self {
+            max_inputs: Self::DEFAULT_MAX_INPUTS,
+            max_outputs: Self::DEFAULT_MAX_OUTPUTS,
+        }
+    }
+}
+
+/// Txs selected for inclusion in a block, by
+/// [`State::select_transactions`]
+#[derive(Debug, Default)]
+pub struct TxSelection {
+    /// Selected txs, highest fee rate first
+    pub txs: Vec<Authorized<FilledTransaction>>,
+    /// Total fees paid by the selected txs
+    pub fees: u64,
+    /// Txs that are invalid, or that spend a UTXO spent by a selected tx
+    /// with a higher fee rate
+    pub rejected: Vec<Txid>,
+}
+
+/// Estimated effect of a withdrawal on the next withdrawal bundle
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, ToSchema)]
+pub struct WithdrawalEstimate {
+    /// Weight added to the bundle by the withdrawal. This is 0 if the
+    /// withdrawal is aggregated with a pending withdrawal to the same
+    /// mainchain address.
+    pub weight: u64,
+    /// Projected weight of the next bundle, if the withdrawal is made
+    pub bundle_weight: u64,
+    /// Number of bundle outputs for pending withdrawals, before the
+    /// withdrawal
+    pub pending_bundle_outputs: usize,
+    /// Maximum number of outputs in a bundle
+    pub max_bundle_outputs: usize,
+    /// `true` if the withdrawal would be included in the next bundle, given
+    /// the pending withdrawals and their mainchain fees
+    pub fits_in_bundle: bool,
+}
+
+/// A BitAsset allocated at genesis
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GenesisBitAsset {
+    pub plain_name: String,
+    #[serde(default)]
+    pub bitasset_data: types::BitAssetData,
+    /// Address to which the BitAsset control coin is allocated
+    pub control_address: Address,
+    /// Balances of the BitAsset allocated to each address
+    pub balances: Vec<(Address, u64)>,
+}
+
+/// Allocations applied to the state of a new chain, before the first block,
+/// and chain parameters.
+/// All nodes on a chain must use the same genesis config.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Genesis {
+    #[serde(default)]
+    pub bitassets: Vec<GenesisBitAsset>,
+    /// Max price impact of a single AMM swap, in basis points.
+    /// If `None`, price impact is not limited.
+    #[serde(default)]
+    pub amm_max_price_impact_bps: Option<u16>,
+    /// Addresses permitted to create new AMM pools.
+    /// If empty, any address can create new AMM pools.
+    #[serde(default)]
+    pub amm_pool_creators: Vec<Address>,
+    /// Protocol fee charged on AMM swaps
+    #[serde(default)]
+    pub amm_protocol_fee: AmmProtocolFee,
+    /// Limits on the number of inputs and outputs in a tx
+    #[serde(default)]
+    pub tx_limits: TxLimits,
+}
+
+impl Genesis {
+    /// Check that the genesis config is internally consistent
+    pub fn validate(&self) -> Result<(), GenesisError> {
+        if self.amm_protocol_fee.fee_bps > AmmProtocolFee::MAX_FEE_BPS {
+            return Err(GenesisError::AmmProtocolFeeTooHigh {
+                fee_bps: self.amm_protocol_fee.fee_bps,
+            });
+        }
+        if let Some(max_price_impact_bps) = self.amm_max_price_impact_bps
+            && max_price_impact_bps > AmmPoolState::MAX_PRICE_IMPACT_BPS
+        {
+            return Err(GenesisError::AmmMaxPriceImpactTooHigh {
+                max_price_impact_bps,
+            });
+        }
+        let mut bitasset_ids = HashSet::new();
+        for genesis_bitasset in &self.bitassets {
+            let plain_name = &genesis_bitasset.plain_name;
+            if !bitasset_ids.insert(genesis_bitasset.bitasset_id()) {
+                return Err(GenesisError::DuplicateBitAsset {
+                    plain_name: plain_name.clone(),
+                });
+            }
+            if genesis_bitasset.balances.is_empty() {
+                return Err(GenesisError::NoBalances {
+                    plain_name: plain_name.clone(),
+                });
+            }
+            if let Some((address, _)) = genesis_bitasset
+                .balances
+                .iter()
+                .find(|(_, amount)| *amount == 0)
+            {
+                return Err(GenesisError::ZeroBalance {
+                    plain_name: plain_name.clone(),
+                    address: *address,
+                });
+            }
+            if genesis_bitasset.total_supply().is_none() {
+                return Err(GenesisError::SupplyOverflow {
+                    plain_name: plain_name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Hash of the genesis config, including chain parameters. Recorded in
+    /// the state when genesis allocations are applied, so that a state
+    /// cannot be reopened with a different genesis config.
+    pub fn hash(&self) -> Hash {
+        hashes::hash(self)
+    }
+
+    /// Txid used for the outputs created by genesis allocations, and for
+    /// BitAssets registered by genesis allocations.
+    /// This is not the txid of any tx.
+    pub fn txid(&self) -> Txid {
+        Txid(hashes::hash(&("genesis", self)))
+    }
+
+    /// Outputs created by genesis allocations.
+    /// For each BitAsset, the balances are followed by the control coin.
+    pub fn outputs(&self) -> Vec<(OutPoint, FilledOutput)> {
+        let txid = self.txid();
+        self.bitassets
+            .iter()
+            .flat_map(|genesis_bitasset| {
+                let bitasset_id = genesis_bitasset.bitasset_id();
+                genesis_bitasset
+                    .balances
+                    .iter()
+                    .map(move |(address, amount)| {
+                        FilledOutput::new(
+                            *address,
+                            FilledOutputContent::BitAsset(bitasset_id, *amount),
+                        )
+                    })
+                    .chain(std::iter::once(FilledOutput::new(
+                        genesis_bitasset.control_address,
+                        FilledOutputContent::BitAssetControl(bitasset_id),
+                    )))
+            })
+            .enumerate()
+            .map(|(vout, output)| {
+                let outpoint = OutPoint::Regular {
+                    txid,
+                    vout: vout as u32,
+                };
+                (outpoint, output)
+            })
+            .collect()
+    }
+}
+
+impl GenesisBitAsset {
+    pub fn bitasset_id(&self) -> BitAssetId {
+        BitAssetId(blake3::hash(self.plain_name.as_bytes()).into())
+    }
+
+    /// Sum of the allocated balances, or `None` if it overflows
+    pub fn total_supply(&self) -> Option<u64> {
+        self.balances
+            .iter()
+            .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+    }
 }
 
 /// Ordered pair of [`AssetId`]s
@@ -527,16 +1054,34 @@ pub struct AmmPoolState {
     pub reserve1: u64,
     /// Total amount of outstanding LP tokens
     pub outstanding_lp_tokens: u64,
+    /// Total swap fees paid to the pool in the first asset.
+    /// Fee counters are for reporting only, and saturate rather than
+    /// invalidating swaps.
+    pub cumulative_fees0: u64,
+    /// Total swap fees paid to the pool in the second asset
+    pub cumulative_fees1: u64,
     /// tx that created the pool
     creation_txid: Txid,
 }
 
 impl AmmPoolState {
-    fn new(txid: Txid) -> Self {
+    /** Fee charged on swaps, as a fraction of the amount spent (0.3%).
+     *  The fee is charged once per swap, on the amount spent, and remains
+     *  in the pool, accruing to LPs. The amount received is not subject to
+     *  any further fee. The AMM protocol fee, if any, is charged separately
+     *  and does not enter the pool. */
+    pub const SWAP_FEE_NUMERATOR: u64 = 3;
+    pub const SWAP_FEE_DENOMINATOR: u64 = 1000;
+    /// Maximum price impact cap, in basis points
+    pub const MAX_PRICE_IMPACT_BPS: u16 = 10_000;
+
+    pub(crate) fn new(txid: Txid) -> Self {
         Self {
             reserve0: 0,
             reserve1: 0,
             outstanding_lp_tokens: 0,
+            cumulative_fees0: 0,
+            cumulative_fees1: 0,
             creation_txid: txid,
         }
     }
@@ -553,6 +1098,8 @@ impl AmmPoolState {
             reserve0,
             reserve1,
             outstanding_lp_tokens,
+            cumulative_fees0,
+            cumulative_fees1,
             creation_txid,
         } = self;
         let new_reserve0 =
@@ -567,6 +1114,8 @@ impl AmmPoolState {
                 reserve0: new_reserve0,
                 reserve1: new_reserve1,
                 outstanding_lp_tokens: new_outstanding_lp_tokens,
+                cumulative_fees0: *cumulative_fees0,
+                cumulative_fees1: *cumulative_fees1,
                 creation_txid: *creation_txid,
             })
         } else {
@@ -590,6 +1139,8 @@ impl AmmPoolState {
                 reserve0: new_reserve0,
                 reserve1: new_reserve1,
                 outstanding_lp_tokens: new_outstanding_lp_tokens,
+                cumulative_fees0: *cumulative_fees0,
+                cumulative_fees1: *cumulative_fees1,
                 creation_txid: *creation_txid,
             })
         }
@@ -618,6 +1169,8 @@ impl AmmPoolState {
             reserve0: new_reserve0,
             reserve1: new_reserve1,
             outstanding_lp_tokens: new_outstanding_lp_tokens,
+            cumulative_fees0: self.cumulative_fees0,
+            cumulative_fees1: self.cumulative_fees1,
             creation_txid: self.creation_txid,
         };
         if *self == new_state.mint(amount0, amount1)? {
@@ -633,6 +1186,8 @@ impl AmmPoolState {
             reserve0,
             reserve1,
             outstanding_lp_tokens,
+            cumulative_fees0,
+            cumulative_fees1,
             creation_txid,
         } = self;
         if *outstanding_lp_tokens == 0 {
@@ -661,6 +1216,8 @@ impl AmmPoolState {
             reserve0: new_reserve0,
             reserve1: new_reserve1,
             outstanding_lp_tokens: new_outstanding_lp_tokens,
+            cumulative_fees0: *cumulative_fees0,
+            cumulative_fees1: *cumulative_fees1,
             creation_txid: *creation_txid,
         })
     }
@@ -676,6 +1233,8 @@ impl AmmPoolState {
             reserve0,
             reserve1,
             outstanding_lp_tokens,
+            cumulative_fees0,
+            cumulative_fees1,
             creation_txid,
         } = self;
         let new_outstanding_lp_tokens = outstanding_lp_tokens
@@ -691,52 +1250,120 @@ impl AmmPoolState {
             reserve0: new_reserve0,
             reserve1: new_reserve1,
             outstanding_lp_tokens: new_outstanding_lp_tokens,
+            cumulative_fees0: *cumulative_fees0,
+            cumulative_fees1: *cumulative_fees1,
             creation_txid: *creation_txid,
         })
     }
 
-    /// Returns the new pool state after a swap
-    pub fn swap_asset0_for_asset1(
-        &self,
+    /** Returns the amount received, and the fee paid, when spending
+     *  `amount_spend` against the specified reserves.
+     *  The fee is deducted from the amount spent, and only the remainder
+     *  is used to compute the amount received from the reserve product.
+     *  The fee remains in the pool, since the spend reserve increases by
+     *  the full amount spent. */
+    fn swap_amounts(
+        reserve_spend: u64,
+        reserve_receive: u64,
         amount_spend: u64,
-    ) -> Result<Self, Error> {
-        let AmmPoolState {
-            reserve0,
-            reserve1,
-            outstanding_lp_tokens,
-            creation_txid,
-        } = self;
-        let reserve_product: u128 = *reserve0 as u128 * *reserve1 as u128;
-        let spend_after_fee = ((amount_spend as u128 * 997) / 1000) as u64;
-        let _spend_fee = amount_spend
-            .checked_sub(spend_after_fee)
-            .ok_or(Error::InvalidAmmSwap)?;
-
+    ) -> Result<(u64, u64), Error> {
+        let reserve_product: u128 =
+            reserve_spend as u128 * reserve_receive as u128;
+        let spend_after_fee: u64 = ((amount_spend as u128
+            * (Self::SWAP_FEE_DENOMINATOR - Self::SWAP_FEE_NUMERATOR) as u128)
+            / Self::SWAP_FEE_DENOMINATOR as u128)
+            as u64;
+        let fee = amount_spend - spend_after_fee;
         // used for computing product for swap price
-        let effective_spend_asset_reserve = reserve0 + spend_after_fee;
-        let new_receive_asset_reserve_before_fee: u64 = reserve_product
+        let effective_spend_asset_reserve = reserve_spend
+            .checked_add(spend_after_fee)
+            .ok_or(Error::InvalidAmmSwap)?;
+        if effective_spend_asset_reserve == 0 {
+            return Err(Error::InsufficientLiquidity);
+        }
+        let new_receive_asset_reserve: u64 = reserve_product
             .div_ceil(effective_spend_asset_reserve as u128)
             .try_into()
             .map_err(|_| Error::InvalidAmmSwap)?;
-        let amount_receive_before_fee: u64 = reserve1
-            .checked_sub(new_receive_asset_reserve_before_fee)
-            .ok_or(Error::InvalidAmmSwap)?;
-        let amount_receive_after_fee =
-            ((amount_receive_before_fee as u128 * 997) / 1000) as u64;
-        let _receive_fee = amount_receive_before_fee
-            .checked_sub(amount_receive_before_fee)
+        let amount_receive = reserve_receive
+            .checked_sub(new_receive_asset_reserve)
             .ok_or(Error::InvalidAmmSwap)?;
-        let (new_reserve0, new_reserve1) = {
-            let new_reserve1 = reserve1
-                .checked_sub(amount_receive_after_fee)
-                .ok_or(Error::InsufficientLiquidity)?;
-            (reserve0 + amount_spend, new_reserve1)
-        };
-        Ok(AmmPoolState {
-            reserve0: new_reserve0,
-            reserve1: new_reserve1,
-            outstanding_lp_tokens: *outstanding_lp_tokens,
-            creation_txid: *creation_txid,
+        Ok((amount_receive, fee))
+    }
+
+    /** Impermanent loss of an LP position that was entered at the specified
+     *  reserves, relative to holding the deposited assets.
+     *  Returns `None` if any of the entry reserves or current reserves are
+     *  zero.
+     *
+     *  If `r` is the ratio of the current price to the entry price, then
+     *  the impermanent loss is `2 * sqrt(r) / (1 + r) - 1`, which is never
+     *  positive.
+     *  Writing `r = a / b`, where
+     *  `a = reserve1 * entry_reserve0` and `b = reserve0 * entry_reserve1`,
+     *  this is equal to `2 * sqrt(a * b) / (a + b) - 1`. */
+    pub fn impermanent_loss(
+        &self,
+        entry_reserve0: u64,
+        entry_reserve1: u64,
+    ) -> Option<f64> {
+        if [self.reserve0, self.reserve1, entry_reserve0, entry_reserve1]
+            .contains(&0)
+        {
+            return None;
+        }
+        let a = self.reserve1 as f64 * entry_reserve0 as f64;
+        let b = self.reserve0 as f64 * entry_reserve1 as f64;
+        Some((2. * a.sqrt() * b.sqrt() / (a + b)) - 1.)
+    }
+
+    /** `true` if a swap spending `amount_spend` against the specified
+     *  reserves, and receiving `amount_receive`, has a price impact greater
+     *  than `max_price_impact_bps` basis points.
+     *  Price impact is the relative difference between the pool price
+     *  before the swap, and the effective price of the swap, including the
+     *  swap fee. Computed exactly, so that all nodes agree. */
+    pub fn price_impact_exceeds(
+        reserve_spend: u64,
+        reserve_receive: u64,
+        amount_spend: u64,
+        amount_receive: u64,
+        max_price_impact_bps: u16,
+    ) -> bool {
+        use num::BigUint;
+        let max_bps = Self::MAX_PRICE_IMPACT_BPS;
+        let max_price_impact_bps = max_price_impact_bps.min(max_bps);
+        /* effective price / pool price
+         *   = (amount_receive * reserve_spend)
+         *   / (amount_spend * reserve_receive),
+         * which must not be less than `1 - (max_price_impact_bps / 10_000)` */
+        let lhs = BigUint::from(amount_receive)
+            * BigUint::from(reserve_spend)
+            * BigUint::from(max_bps);
+        let rhs = BigUint::from(amount_spend)
+            * BigUint::from(reserve_receive)
+            * BigUint::from(max_bps - max_price_impact_bps);
+        lhs < rhs
+    }
+
+    /// Returns the new pool state after a swap
+    pub fn swap_asset0_for_asset1(
+        &self,
+        amount_spend: u64,
+    ) -> Result<Self, Error> {
+        let (amount_receive, fee) =
+            Self::swap_amounts(self.reserve0, self.reserve1, amount_spend)?;
+        Ok(Self {
+            reserve0: self
+                .reserve0
+                .checked_add(amount_spend)
+                .ok_or(Error::InvalidAmmSwap)?,
+            reserve1: self
+                .reserve1
+                .checked_sub(amount_receive)
+                .ok_or(Error::InsufficientLiquidity)?,
+            cumulative_fees0: self.cumulative_fees0.saturating_add(fee),
+            ..*self
         })
     }
 
@@ -745,45 +1372,81 @@ impl AmmPoolState {
         &self,
         amount_spend: u64,
     ) -> Result<Self, Error> {
-        let AmmPoolState {
-            reserve0,
-            reserve1,
-            outstanding_lp_tokens,
-            creation_txid,
-        } = self;
-        let reserve_product: u128 = *reserve0 as u128 * *reserve1 as u128;
-        let spend_after_fee = ((amount_spend as u128 * 997) / 1000) as u64;
-        let _spend_fee = amount_spend
-            .checked_sub(spend_after_fee)
-            .ok_or(Error::InvalidAmmSwap)?;
-        // used for computing product for swap price
-        let effective_spend_asset_reserve = reserve1 + spend_after_fee;
-        let new_receive_asset_reserve_before_fee: u64 = reserve_product
-            .div_ceil(effective_spend_asset_reserve as u128)
-            .try_into()
-            .map_err(|_| Error::InvalidAmmSwap)?;
-        let amount_receive_before_fee: u64 = reserve0
-            .checked_sub(new_receive_asset_reserve_before_fee)
-            .ok_or(Error::InvalidAmmSwap)?;
-        let amount_receive_after_fee =
-            ((amount_receive_before_fee as u128 * 997) / 1000) as u64;
-        let _receive_fee = amount_receive_before_fee
-            .checked_sub(amount_receive_before_fee)
-            .ok_or(Error::InvalidAmmSwap)?;
-        let (new_reserve0, new_reserve1) = {
-            let new_reserve0 = reserve0
-                .checked_sub(amount_receive_after_fee)
-                .ok_or(Error::InsufficientLiquidity)?;
-            (new_reserve0, reserve1 + amount_spend)
-        };
-        Ok(AmmPoolState {
-            reserve0: new_reserve0,
-            reserve1: new_reserve1,
-            outstanding_lp_tokens: *outstanding_lp_tokens,
-            creation_txid: *creation_txid,
+        let (amount_receive, fee) =
+            Self::swap_amounts(self.reserve1, self.reserve0, amount_spend)?;
+        Ok(Self {
+            reserve0: self
+                .reserve0
+                .checked_sub(amount_receive)
+                .ok_or(Error::InsufficientLiquidity)?,
+            reserve1: self
+                .reserve1
+                .checked_add(amount_spend)
+                .ok_or(Error::InvalidAmmSwap)?,
+            cumulative_fees1: self.cumulative_fees1.saturating_add(fee),
+            ..*self
         })
     }
 
+    /** Returns the minimum amount of the spend asset that must be spent in a
+     *  swap to receive at least `amount_receive` of the receive asset,
+     *  given the reserves of each asset. This inverts the swap formula,
+     *  including the fee deducted from the amount spent. */
+    fn required_spend_for_output(
+        reserve_spend: u64,
+        reserve_receive: u64,
+        amount_receive: u64,
+    ) -> Result<u64, Error> {
+        if reserve_spend == 0 || reserve_receive == 0 {
+            return Err(Error::InsufficientLiquidity);
+        }
+        let reserve_product: u128 =
+            reserve_spend as u128 * reserve_receive as u128;
+        if amount_receive >= reserve_receive {
+            return Err(Error::InsufficientLiquidity);
+        }
+        let new_receive_asset_reserve: u128 =
+            (reserve_receive - amount_receive) as u128;
+        // minimum effective reserve for the receive reserve to decrease by
+        // at least `amount_receive`
+        let effective_spend_asset_reserve: u128 =
+            reserve_product.div_ceil(new_receive_asset_reserve);
+        let spend_after_fee =
+            effective_spend_asset_reserve.saturating_sub(reserve_spend as u128);
+        (spend_after_fee * Self::SWAP_FEE_DENOMINATOR as u128)
+            .div_ceil(
+                (Self::SWAP_FEE_DENOMINATOR - Self::SWAP_FEE_NUMERATOR) as u128,
+            )
+            .try_into()
+            .map_err(|_| Error::InvalidAmmSwap)
+    }
+
+    /** Returns the minimum amount of asset 0 that must be spent to receive
+     *  at least `amount_receive` of asset 1 */
+    pub fn required_spend_asset0_for_asset1(
+        &self,
+        amount_receive: u64,
+    ) -> Result<u64, Error> {
+        Self::required_spend_for_output(
+            self.reserve0,
+            self.reserve1,
+            amount_receive,
+        )
+    }
+
+    /** Returns the minimum amount of asset 1 that must be spent to receive
+     *  at least `amount_receive` of asset 0 */
+    pub fn required_spend_asset1_for_asset0(
+        &self,
+        amount_receive: u64,
+    ) -> Result<u64, Error> {
+        Self::required_spend_for_output(
+            self.reserve1,
+            self.reserve0,
+            amount_receive,
+        )
+    }
+
     /// Returns the pool state after reverting a swap
     fn revert_swap(&self, swap: AmmSwap) -> Result<Self, Error> {
         let amm_pair = AmmPair::new(swap.asset_receive, swap.asset_spend);
@@ -796,35 +1459,130 @@ impl AmmPoolState {
             new_reserve0 = self.reserve0 - swap.amount_spend;
             new_reserve1 = self.reserve1 + swap.amount_receive;
         }
-        let new_state = Self {
+        // reverted state, without fees, so that applying the swap again
+        // yields the fees paid in this swap alone
+        let reverted_state = Self {
             reserve0: new_reserve0,
             reserve1: new_reserve1,
             outstanding_lp_tokens: self.outstanding_lp_tokens,
+            cumulative_fees0: 0,
+            cumulative_fees1: 0,
             creation_txid: self.creation_txid,
         };
         // apply the swap again to see if the reverted state is correct
         let check_state = if swap.asset_spend == amm_pair.asset0() {
-            new_state.swap_asset0_for_asset1(swap.amount_spend)?
+            reverted_state.swap_asset0_for_asset1(swap.amount_spend)?
         } else {
-            new_state.swap_asset1_for_asset0(swap.amount_spend)?
+            reverted_state.swap_asset1_for_asset0(swap.amount_spend)?
         };
-        if check_state == *self {
-            Ok(new_state)
-        } else {
-            Err(Error::RevertAmmSwap)
+        let (swap_fees0, swap_fees1) =
+            (check_state.cumulative_fees0, check_state.cumulative_fees1);
+        let check_state = Self {
+            cumulative_fees0: self.cumulative_fees0,
+            cumulative_fees1: self.cumulative_fees1,
+            ..check_state
+        };
+        if check_state != *self {
+            return Err(Error::RevertAmmSwap);
         }
+        Ok(Self {
+            cumulative_fees0: self.cumulative_fees0.saturating_sub(swap_fees0),
+            cumulative_fees1: self.cumulative_fees1.saturating_sub(swap_fees1),
+            ..reverted_state
+        })
     }
 }
 
-/// Parameters of a Dutch Auction
+/// Status of an atomic swap
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum AtomicSwapStatus {
+    /// The asset is locked, and can be claimed or refunded
+    Locked,
+    /// The asset was claimed by revealing the preimage
+    Claimed { preimage: Hash },
+    /// The asset was refunded after the timeout
+    Refunded,
+}
+
+/// State of an atomic swap
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct DutchAuctionState {
-    /// Block height at which the auction starts
-    pub start_block: u32,
-    /// Block height at the most recent bid
-    pub most_recent_bid_block: RollBack<u32>,
-    /// Auction duration, in blocks
-    pub duration: u32,
+pub struct AtomicSwapState {
+    /// The locked asset
+    pub asset: AssetId,
+    /// The amount of the locked asset
+    pub amount: u64,
+    /// Address that can claim the locked asset by revealing the preimage
+    pub recipient: Address,
+    /// Address to refund the locked asset to, after the timeout
+    pub refund_address: Address,
+    /// BLAKE3 hash of the preimage that must be revealed to claim
+    pub hash_lock: Hash,
+    /// Block height from which the swap can no longer be claimed,
+    /// and can be refunded
+    pub timeout: u32,
+    /// Current status of the swap
+    pub status: RollBack<AtomicSwapStatus>,
+}
+
+impl AtomicSwapState {
+    /// Returns the new atomic swap state after a claim or refund
+    pub fn release(
+        &self,
+        txid: Txid,
+        preimage: Option<Hash>,
+        height: u32,
+    ) -> Result<Self, AtomicSwapReleaseError> {
+        if self.status.latest().data != AtomicSwapStatus::Locked {
+            do yeet AtomicSwapReleaseError::NotLocked
+        }
+        let new_status = match preimage {
+            Some(preimage) => {
+                if height >= self.timeout {
+                    do yeet AtomicSwapReleaseError::Expired
+                }
+                // WARNING: This comparison MUST be done in constant time.
+                // `blake3::Hash` handles this; DO NOT compare as byte arrays
+                if blake3::hash(&preimage) != self.hash_lock {
+                    do yeet AtomicSwapReleaseError::InvalidPreimage
+                }
+                AtomicSwapStatus::Claimed { preimage }
+            }
+            None => {
+                if height < self.timeout {
+                    do yeet AtomicSwapReleaseError::NotExpired
+                }
+                AtomicSwapStatus::Refunded
+            }
+        };
+        let mut status = self.status.clone();
+        status.push(new_status, txid, height);
+        Ok(Self {
+            status,
+            ..self.clone()
+        })
+    }
+
+    /** Address that the released asset must be sent to.
+     *  The asset is sent to the recipient if the swap is claimed,
+     *  and to the refund address if the swap is refunded. */
+    pub fn release_address(&self, preimage: Option<&Hash>) -> Address {
+        if preimage.is_some() {
+            self.recipient
+        } else {
+            self.refund_address
+        }
+    }
+}
+
+/// Parameters of a Dutch Auction
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DutchAuctionState {
+    /// Block height at which the auction starts
+    pub start_block: u32,
+    /// Block height at the most recent bid
+    pub most_recent_bid_block: RollBack<u32>,
+    /// Auction duration, in blocks
+    pub duration: u32,
     /// The asset to be auctioned
     pub base_asset: AssetId,
     /// The initial amount of base asset to be auctioned
@@ -846,22 +1604,25 @@ pub struct DutchAuctionState {
 }
 
 impl DutchAuctionState {
-    /// Returns the new auction state after a bid
-    pub fn bid(
+    /// Block height at which the auction ends
+    pub fn end_block(&self) -> u32 {
+        self.start_block.saturating_add(self.duration - 1)
+    }
+
+    /// Returns the price of the auction at the specified block height
+    pub fn current_price(
         &self,
-        txid: Txid,
-        bid_amount: u64,
         height: u32,
-    ) -> Result<Self, Error> {
+    ) -> Result<u64, DutchAuctionBidError> {
         let DutchAuctionState {
             start_block,
             most_recent_bid_block,
-            duration,
+            duration: _,
             base_asset: _,
             initial_base_amount: _,
-            base_amount_remaining,
+            base_amount_remaining: _,
             quote_asset: _,
-            quote_amount,
+            quote_amount: _,
             initial_price: _,
             price_after_most_recent_bid,
             initial_end_price: _,
@@ -872,7 +1633,7 @@ impl DutchAuctionState {
         };
         // Blocks elapsed since last bid
         let elapsed_blocks = height - most_recent_bid_block.latest().data;
-        let end_block = start_block.saturating_add(*duration - 1);
+        let end_block = self.end_block();
         if height > end_block {
             do yeet DutchAuctionBidError::AuctionEnded
         };
@@ -905,6 +1666,31 @@ impl DutchAuctionState {
             };
             price_after_most_recent_bid.latest().data - price_decrease
         };
+        Ok(price)
+    }
+
+    /// Returns the new auction state after a bid
+    pub fn bid(
+        &self,
+        txid: Txid,
+        bid_amount: u64,
+        height: u32,
+    ) -> Result<Self, Error> {
+        let DutchAuctionState {
+            start_block: _,
+            most_recent_bid_block,
+            duration: _,
+            base_asset: _,
+            initial_base_amount: _,
+            base_amount_remaining,
+            quote_asset: _,
+            quote_amount,
+            initial_price: _,
+            price_after_most_recent_bid,
+            initial_end_price: _,
+            end_price_after_most_recent_bid,
+        } = self;
+        let price = self.current_price(height)?;
         if price == 0 {
             do yeet DutchAuctionBidError::InvalidPrice
         };
@@ -990,16 +1776,145 @@ impl DutchAuctionState {
     }
 }
 
+/// State of a limit order
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OrderState {
+    /// The asset to be sold
+    pub sell_asset: AssetId,
+    /// The initial amount of the sell asset offered
+    pub initial_sell_amount: u64,
+    /// The remaining amount of the sell asset offered
+    pub sell_amount_remaining: RollBack<u64>,
+    /// The asset to be received in exchange
+    pub buy_asset: AssetId,
+    /// The amount of the buy asset that has been received
+    pub buy_amount_received: RollBack<u64>,
+    /// Price, in units of the buy asset per unit of the sell asset
+    pub price: u64,
+}
+
+impl OrderState {
+    /** Returns the amount of the buy asset required to fill the specified
+     *  quantity of the sell asset */
+    pub fn cost(&self, quantity: u64) -> Option<u64> {
+        quantity.checked_mul(self.price)
+    }
+
+    /// Returns the new order state after a fill
+    pub fn fill(
+        &self,
+        txid: Txid,
+        quantity: u64,
+        height: u32,
+    ) -> Result<Self, OrderFillError> {
+        if quantity == 0 {
+            do yeet OrderFillError::ZeroQuantity
+        }
+        let sell_amount_remaining = self.sell_amount_remaining.latest().data;
+        if quantity > sell_amount_remaining {
+            do yeet OrderFillError::QuantityTooLarge
+        }
+        // Cannot overflow, as the total order value is checked on placement
+        let cost = self.cost(quantity).ok_or(OrderFillError::InvalidPrice)?;
+        let mut new_sell_amount_remaining = self.sell_amount_remaining.clone();
+        new_sell_amount_remaining.push(
+            sell_amount_remaining - quantity,
+            txid,
+            height,
+        );
+        let mut buy_amount_received = self.buy_amount_received.clone();
+        buy_amount_received.push(
+            buy_amount_received.latest().data + cost,
+            txid,
+            height,
+        );
+        Ok(Self {
+            sell_amount_remaining: new_sell_amount_remaining,
+            buy_amount_received,
+            ..self.clone()
+        })
+    }
+
+    /// Returns the order state after reverting a fill
+    fn revert_fill(&self, txid: Txid) -> Self {
+        let mut sell_amount_remaining = self.sell_amount_remaining.clone();
+        assert!(sell_amount_remaining
+            .pop()
+            .is_some_and(|ts| ts.txid == txid));
+        let mut buy_amount_received = self.buy_amount_received.clone();
+        assert!(buy_amount_received.pop().is_some_and(|ts| ts.txid == txid));
+        Self {
+            sell_amount_remaining,
+            buy_amount_received,
+            ..self.clone()
+        }
+    }
+}
+
+/// Sort order for paginated UTXO queries
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum UtxoSort {
+    /// Sort by serialized outpoint, which is the order in which UTXOs are
+    /// stored. This does not require loading the entire UTXO set.
+    #[default]
+    Outpoint,
+    /// Sort by value, largest first, with ties broken by outpoint.
+    /// This requires loading all matching UTXOs in order to sort them.
+    Value,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid UTXO sort `{0}` (expected `outpoint` or `value`)")]
+pub struct ParseUtxoSortError(String);
+
+impl std::str::FromStr for UtxoSort {
+    type Err = ParseUtxoSortError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "outpoint" => Ok(Self::Outpoint),
+            "value" => Ok(Self::Value),
+            _ => Err(ParseUtxoSortError(s.to_owned())),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct State {
     /// Current tip
     tip: WatchableDb<SerdeBincode<UnitKey>, SerdeBincode<BlockHash>>,
     /// Current height
     height: Database<SerdeBincode<UnitKey>, SerdeBincode<u32>>,
+    /// Hash of the genesis config with which the state was created
+    genesis_hash: Database<SerdeBincode<UnitKey>, SerdeBincode<Hash>>,
+    /// Associates addresses, block heights, and positions in the block with
+    /// the txids of txs that spent from or paid to the address.
+    /// See [`Self::address_activity_key`].
+    /// If `None`, the address activity index is disabled.
+    pub address_activity: Option<Database<Bytes, SerdeBincode<Txid>>>,
+    /// Height of the first block recorded in the address activity index.
+    /// Not present if the index is disabled.
+    address_activity_start_height:
+        Database<SerdeBincode<UnitKey>, SerdeBincode<u32>>,
     /// Associates ordered pairs of BitAssets to their AMM pool states
     pub amm_pools: Database<SerdeBincode<AmmPair>, SerdeBincode<AmmPoolState>>,
+    /// Associates ordered pairs of BitAssets to the history of their AMM
+    /// pool states
+    pub amm_pool_history:
+        Database<SerdeBincode<AmmPair>, SerdeBincode<RollBack<AmmPoolState>>>,
+    /// Associates atomic swap IDs with atomic swap state
+    pub atomic_swaps:
+        Database<SerdeBincode<AtomicSwapId>, SerdeBincode<AtomicSwapState>>,
     /// Associates tx hashes with BitAsset reservation commitments
     pub bitasset_reservations: Database<SerdeBincode<Txid>, SerdeBincode<Hash>>,
+    /// Associates BitAsset IDs (name hashes) with the txids of txs that
+    /// spent or created BitAsset outputs or control coins for the BitAsset,
+    /// in the order that they were confirmed.
+    pub bitasset_activity:
+        Database<SerdeBincode<BitAssetId>, SerdeBincode<Vec<Txid>>>,
     /// Associates BitAsset sequence numbers with BitAsset IDs (name hashes)
     pub bitasset_seq_to_bitasset:
         Database<SerdeBincode<BitAssetSeqId>, SerdeBincode<BitAssetId>>,
@@ -1012,6 +1927,8 @@ pub struct State {
     /// Associates Dutch auction sequence numbers with auction state
     pub dutch_auctions:
         Database<SerdeBincode<DutchAuctionId>, SerdeBincode<DutchAuctionState>>,
+    /// Associates order IDs with order state
+    pub orders: Database<SerdeBincode<OrderId>, SerdeBincode<OrderState>>,
     pub utxos: Database<SerdeBincode<OutPoint>, SerdeBincode<FilledOutput>>,
     pub stxos: Database<SerdeBincode<OutPoint>, SerdeBincode<SpentOutput>>,
     /// Pending withdrawal bundle and block height
@@ -1025,17 +1942,88 @@ pub struct State {
     /// deposit blocks and the height at which they were applied, keyed sequentially
     pub deposit_blocks:
         Database<SerdeBincode<u32>, SerdeBincode<(bitcoin::BlockHash, u32)>>,
+    /// Addresses permitted to create new AMM pools, set in the genesis
+    /// config. If `None`, any address can create new AMM pools.
+    pub amm_pool_creators: Option<HashSet<Address>>,
+    /// Protocol fee charged on AMM swaps, set in the genesis config
+    pub amm_protocol_fee: AmmProtocolFee,
+    /// Max price impact of a single AMM swap, in basis points, set in the
+    /// genesis config. If `None`, price impact is not limited.
+    pub amm_max_price_impact_bps: Option<u16>,
+    /// Limits on the number of inputs and outputs in a tx, set in the
+    /// genesis config
+    pub tx_limits: TxLimits,
 }
 
 impl State {
-    pub const NUM_DBS: u32 = 13;
+    /// Weight of a withdrawal bundle with 0 outputs
+    pub const BUNDLE_0_WEIGHT: u64 = 504;
+    /// Weight of a single withdrawal bundle output
+    pub const BUNDLE_OUTPUT_WEIGHT: u64 = 128;
+    /// Maximum number of outputs in a withdrawal bundle.
+    /// Turns out to be 3121.
+    pub const MAX_BUNDLE_OUTPUTS: usize =
+        ((bitcoin::policy::MAX_STANDARD_TX_WEIGHT as u64
+            - Self::BUNDLE_0_WEIGHT)
+            / Self::BUNDLE_OUTPUT_WEIGHT) as usize;
+    /// Maximum number of updates to a single BitAsset in a block
+    pub const MAX_BITASSET_UPDATES_PER_BLOCK: usize = 1;
+    pub const NUM_DBS: u32 = 21;
+    /** Version of the encoding of data stored in the data directory.
+     *  Must be incremented whenever stored data is encoded incompatibly,
+     *  so that nodes refuse to open data directories that require a resync.
+     *  Version 1 added AMM pool fee counters and header timestamps. */
+    pub const SCHEMA_VERSION: u32 = 1;
     pub const WITHDRAWAL_BUNDLE_FAILURE_GAP: u32 = 5;
 
-    pub fn new(env: &heed::Env) -> Result<Self, Error> {
+    pub fn new(
+        env: &heed::Env,
+        index_address_activity: bool,
+        genesis: &Genesis,
+    ) -> Result<Self, Error> {
+        let () = genesis.validate()?;
         let mut rwtxn = env.write_txn()?;
+        let () = Self::check_schema_version(env, &mut rwtxn)?;
         let tip = env.create_watchable_db(&mut rwtxn, "tip")?;
         let height = env.create_database(&mut rwtxn, Some("height"))?;
+        let genesis_hash =
+            env.create_database(&mut rwtxn, Some("genesis_hash"))?;
+        let address_activity_start_height = env.create_database(
+            &mut rwtxn,
+            Some("address_activity_start_height"),
+        )?;
+        let address_activity = if index_address_activity {
+            let address_activity: Database<Bytes, SerdeBincode<Txid>> =
+                env.create_database(&mut rwtxn, Some("address_activity"))?;
+            if address_activity_start_height
+                .get(&rwtxn, &UnitKey)?
+                .is_none()
+            {
+                /* The index is newly enabled. Entries recorded while the
+                 * index was previously enabled may have been reorged out,
+                 * so they are removed, and blocks are indexed from the
+                 * next block onwards. */
+                address_activity.clear(&mut rwtxn)?;
+                let start_height =
+                    height.get(&rwtxn, &UnitKey)?.unwrap_or_default();
+                address_activity_start_height.put(
+                    &mut rwtxn,
+                    &UnitKey,
+                    &start_height,
+                )?;
+            }
+            Some(address_activity)
+        } else {
+            address_activity_start_height.delete(&mut rwtxn, &UnitKey)?;
+            None
+        };
         let amm_pools = env.create_database(&mut rwtxn, Some("amm_pools"))?;
+        let amm_pool_history =
+            env.create_database(&mut rwtxn, Some("amm_pool_history"))?;
+        let atomic_swaps =
+            env.create_database(&mut rwtxn, Some("atomic_swaps"))?;
+        let bitasset_activity =
+            env.create_database(&mut rwtxn, Some("bitasset_activity"))?;
         let bitasset_reservations =
             env.create_database(&mut rwtxn, Some("bitasset_reservations"))?;
         let bitasset_seq_to_bitasset =
@@ -1045,6 +2033,7 @@ impl State {
         let bitassets = env.create_database(&mut rwtxn, Some("bitassets"))?;
         let dutch_auctions =
             env.create_database(&mut rwtxn, Some("dutch_auctions"))?;
+        let orders = env.create_database(&mut rwtxn, Some("orders"))?;
         let utxos = env.create_database(&mut rwtxn, Some("utxos"))?;
         let stxos = env.create_database(&mut rwtxn, Some("stxos"))?;
         let pending_withdrawal_bundle =
@@ -1053,22 +2042,112 @@ impl State {
             env.create_database(&mut rwtxn, Some("withdrawal_bundles"))?;
         let deposit_blocks =
             env.create_database(&mut rwtxn, Some("deposit_blocks"))?;
-        rwtxn.commit()?;
-        Ok(Self {
+        let state = Self {
             tip,
             height,
+            genesis_hash,
+            address_activity,
+            address_activity_start_height,
             amm_pools,
+            amm_pool_history,
+            atomic_swaps,
+            bitasset_activity,
             bitasset_reservations,
             bitasset_seq_to_bitasset,
             bitasset_to_bitasset_seq,
             bitassets,
             dutch_auctions,
+            orders,
             utxos,
             stxos,
             pending_withdrawal_bundle,
             withdrawal_bundles,
             deposit_blocks,
-        })
+            amm_pool_creators: (!genesis.amm_pool_creators.is_empty())
+                .then(|| genesis.amm_pool_creators.iter().copied().collect()),
+            amm_protocol_fee: genesis.amm_protocol_fee,
+            amm_max_price_impact_bps: genesis.amm_max_price_impact_bps,
+            tx_limits: genesis.tx_limits,
+        };
+        if state.is_empty(&rwtxn)? {
+            let () = state.apply_genesis(&mut rwtxn, genesis)?;
+        }
+        if state.genesis_hash.get(&rwtxn, &UnitKey)? != Some(genesis.hash()) {
+            return Err(GenesisError::Mismatch.into());
+        }
+        rwtxn.commit()?;
+        Ok(state)
+    }
+
+    /** Check the schema version of the data directory, recording it if
+     *  the data directory is new. Data directories created before the
+     *  schema version was recorded are identified by the tip db, and are
+     *  incompatible. */
+    fn check_schema_version(
+        env: &heed::Env,
+        rwtxn: &mut RwTxn,
+    ) -> Result<(), Error> {
+        let is_new = env
+            .open_database::<DecodeIgnore, DecodeIgnore>(rwtxn, Some("tip"))?
+            .is_none();
+        let schema_version: Database<SerdeBincode<UnitKey>, SerdeBincode<u32>> =
+            env.create_database(rwtxn, Some("schema_version"))?;
+        match schema_version.get(rwtxn, &UnitKey)? {
+            Some(Self::SCHEMA_VERSION) => Ok(()),
+            None if is_new => {
+                schema_version.put(rwtxn, &UnitKey, &Self::SCHEMA_VERSION)?;
+                Ok(())
+            }
+            stored => Err(Error::SchemaVersionMismatch {
+                stored,
+                expected: Self::SCHEMA_VERSION,
+            }),
+        }
+    }
+
+    /// `true` if no blocks have been connected, and no genesis allocations
+    /// have been applied
+    fn is_empty(&self, rotxn: &RoTxn) -> Result<bool, Error> {
+        Ok(self.get_tip(rotxn)? == BlockHash::default()
+            && self.utxos.is_empty(rotxn)?
+            && self.bitassets.is_empty(rotxn)?)
+    }
+
+    /// Register the BitAssets allocated at genesis, and create the genesis
+    /// outputs. Genesis allocations are not recorded in the BitAsset or
+    /// address activity indexes, as they do not correspond to a tx.
+    fn apply_genesis(
+        &self,
+        rwtxn: &mut RwTxn,
+        genesis: &Genesis,
+    ) -> Result<(), Error> {
+        let txid = genesis.txid();
+        for genesis_bitasset in &genesis.bitassets {
+            let bitasset_id = genesis_bitasset.bitasset_id();
+            let seq = self.next_bitasset_seq(rwtxn)?;
+            self.bitasset_seq_to_bitasset
+                .put(rwtxn, &seq, &bitasset_id)?;
+            self.bitasset_to_bitasset_seq
+                .put(rwtxn, &bitasset_id, &seq)?;
+            let total_supply =
+                genesis_bitasset.total_supply().ok_or_else(|| {
+                    GenesisError::SupplyOverflow {
+                        plain_name: genesis_bitasset.plain_name.clone(),
+                    }
+                })?;
+            let bitasset_data = BitAssetData::init(
+                genesis_bitasset.bitasset_data.clone(),
+                total_supply,
+                txid,
+                0,
+            );
+            self.bitassets.put(rwtxn, &bitasset_id, &bitasset_data)?;
+        }
+        for (outpoint, output) in genesis.outputs() {
+            self.utxos.put(rwtxn, &outpoint, &output)?;
+        }
+        self.genesis_hash.put(rwtxn, &UnitKey, &genesis.hash())?;
+        Ok(())
     }
 
     pub fn get_tip(&self, rotxn: &RoTxn) -> Result<BlockHash, Error> {
@@ -1081,6 +2160,193 @@ impl State {
         Ok(height)
     }
 
+    /** Returns the txids of txs that spent from or paid to the specified
+     *  address, confirmed in blocks at or above `from_height`, in the order
+     *  that they were confirmed.
+     *  Returns an error if the address activity index is disabled, or if
+     *  the index was enabled after the block at `from_height`. */
+    pub fn get_address_history(
+        &self,
+        rotxn: &RoTxn,
+        address: &Address,
+        from_height: u32,
+    ) -> Result<Vec<Txid>, Error> {
+        let address_activity = self
+            .address_activity
+            .ok_or(Error::AddressActivityIndexDisabled)?;
+        let start_height = self
+            .address_activity_start_height
+            .get(rotxn, &UnitKey)?
+            .unwrap_or_default();
+        if from_height < start_height {
+            return Err(Error::AddressActivityIndexIncomplete {
+                from_height,
+                start_height,
+            });
+        }
+        let lower = Self::address_activity_key(address, from_height, 0);
+        let upper = Self::address_activity_key(address, u32::MAX, u32::MAX);
+        let range = (
+            std::ops::Bound::Included(&lower[..]),
+            std::ops::Bound::Included(&upper[..]),
+        );
+        let txids = address_activity
+            .range(rotxn, &range)?
+            .map(|res| res.map(|(_key, txid)| txid))
+            .collect::<Result<_, _>>()?;
+        Ok(txids)
+    }
+
+    /** Returns the txids of confirmed txs that spent or created BitAsset
+     *  outputs or control coins for the specified BitAsset, in the order
+     *  that they were confirmed. */
+    pub fn get_bitasset_txids(
+        &self,
+        rotxn: &RoTxn,
+        bitasset: &BitAssetId,
+    ) -> Result<Vec<Txid>, Error> {
+        let txids = self
+            .bitasset_activity
+            .get(rotxn, bitasset)?
+            .unwrap_or_default();
+        Ok(txids)
+    }
+
+    /** Returns the state of an AMM pool at the end of each block in the
+     *  specified (inclusive) height range in which the pool state changed,
+     *  ordered by height. */
+    pub fn get_amm_pool_history(
+        &self,
+        rotxn: &RoTxn,
+        amm_pair: AmmPair,
+        from_height: u32,
+        to_height: u32,
+    ) -> Result<Vec<(u32, AmmPoolState)>, Error> {
+        let Some(history) = self.amm_pool_history.get(rotxn, &amm_pair)? else {
+            return Ok(Vec::new());
+        };
+        let mut res = Vec::<(u32, AmmPoolState)>::new();
+        for txid_stamped in history.0.iter() {
+            let height = txid_stamped.height;
+            if height < from_height || height > to_height {
+                continue;
+            }
+            // Keep only the last state seen in each block
+            if let Some((last_height, last_state)) = res.last_mut()
+                && *last_height == height
+            {
+                *last_state = txid_stamped.data;
+            } else {
+                res.push((height, txid_stamped.data));
+            }
+        }
+        Ok(res)
+    }
+
+    /** Returns the state of an AMM pool as it was at the end of the block at
+     *  the specified height.
+     *  Returns `None` if the pool did not exist at the specified height. */
+    pub fn try_get_amm_pool_state_at_block_height(
+        &self,
+        rotxn: &RoTxn,
+        amm_pair: AmmPair,
+        height: u32,
+    ) -> Result<Option<AmmPoolState>, Error> {
+        let res =
+            self.amm_pool_history
+                .get(rotxn, &amm_pair)?
+                .and_then(|history| {
+                    history
+                        .at_block_height(height)
+                        .map(|txid_stamped| txid_stamped.data)
+                });
+        Ok(res)
+    }
+
+    /** Get a stream of the states of an AMM pool. Whenever the tip changes,
+     *  yields the pool state if it differs from the pool state when the
+     *  stream was created, or when a pool state was last yielded.
+     *  Tip changes that do not affect the pool are skipped. */
+    pub fn watch_amm_pool(
+        &self,
+        env: &heed::Env,
+        amm_pair: AmmPair,
+    ) -> Result<impl Stream<Item = Result<AmmPoolState, Error>>, Error> {
+        let mut amm_pool_state = {
+            let rotxn = env.read_txn()?;
+            self.amm_pools.get(&rotxn, &amm_pair)?
+        };
+        let env = env.clone();
+        let state = self.clone();
+        let stream = self.watch().filter_map(move |()| {
+            let res = env
+                .read_txn()
+                .and_then(|rotxn| state.amm_pools.get(&rotxn, &amm_pair));
+            let item = match res {
+                Ok(new_amm_pool_state)
+                    if new_amm_pool_state != amm_pool_state =>
+                {
+                    amm_pool_state = new_amm_pool_state;
+                    amm_pool_state.map(Ok)
+                }
+                Ok(_) => None,
+                Err(err) => Some(Err(err.into())),
+            };
+            future::ready(item)
+        });
+        Ok(stream)
+    }
+
+    /** Get a stream of the price of a Dutch auction. Whenever the tip
+     *  changes, yields the current price, if the auction has started.
+     *  The stream ends after yielding the price at the end block of the
+     *  auction, or if the auction does not exist or has ended. */
+    pub fn watch_dutch_auction_price(
+        &self,
+        env: &heed::Env,
+        auction_id: DutchAuctionId,
+    ) -> impl Stream<Item = Result<u64, Error>> {
+        let env = env.clone();
+        let state = self.clone();
+        // Auction state and height at the tip
+        let get_auction_state = move || {
+            let rotxn = env.read_txn()?;
+            let auction_state =
+                state.dutch_auctions.get(&rotxn, &auction_id)?;
+            let height = state.get_height(&rotxn)?;
+            Result::<_, Error>::Ok(
+                auction_state.map(|auction_state| (auction_state, height)),
+            )
+        };
+        self.watch()
+            .scan(false, move |ended, ()| {
+                // `None` ends the stream, `Some(None)` skips the tip change
+                let item = if *ended {
+                    None
+                } else {
+                    match get_auction_state() {
+                        Ok(Some((auction_state, height))) => {
+                            match auction_state.current_price(height) {
+                                Ok(price) => {
+                                    *ended =
+                                        height >= auction_state.end_block();
+                                    Some(Some(Ok(price)))
+                                }
+                                Err(
+                                    DutchAuctionBidError::AuctionNotStarted,
+                                ) => Some(None),
+                                Err(_) => None,
+                            }
+                        }
+                        Ok(None) => None,
+                        Err(err) => Some(Some(Err(err))),
+                    }
+                };
+                future::ready(item)
+            })
+            .filter_map(future::ready)
+    }
+
     /** The sequence number of the last registered BitAsset.
      * Returns `None` if no BitAssets have been registered. */
     pub fn last_bitasset_seq(
@@ -1173,6 +2439,84 @@ impl State {
         )
     }
 
+    /** Compute the BitAsset ID for a plaintext name, and whether the name
+     *  is available to register, ie. no BitAsset with that ID exists. */
+    pub fn preview_registration(
+        &self,
+        rotxn: &RoTxn,
+        plain_name: &str,
+    ) -> Result<(BitAssetId, bool), heed::Error> {
+        let bitasset_id = BitAssetId::from_plain_name(plain_name);
+        let available = self.bitassets.get(rotxn, &bitasset_id)?.is_none();
+        Ok((bitasset_id, available))
+    }
+
+    /** BitAssets for which the key is currently set as the signing or
+     *  encryption pubkey, in [`BitAssetId`] order.
+     *  This is a linear scan over all BitAssets. */
+    pub fn find_bitassets_by_key(
+        &self,
+        txn: &RoTxn,
+        key: &BitAssetKey,
+    ) -> Result<Vec<BitAssetId>, heed::Error> {
+        let mut res = Vec::new();
+        for item in self.bitassets.iter(txn)? {
+            let (bitasset_id, bitasset_data) = item?;
+            if key.matches(&bitasset_data.current()) {
+                res.push(bitasset_id);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Current total supply of a BitAsset, if it exists
+    pub fn try_get_bitasset_total_supply(
+        &self,
+        txn: &RoTxn,
+        bitasset: &BitAssetId,
+    ) -> Result<Option<u64>, heed::Error> {
+        let res = self
+            .bitassets
+            .get(txn, bitasset)?
+            .map(|bitasset_data| bitasset_data.total_supply());
+        Ok(res)
+    }
+
+    /** Market cap of a BitAsset in sats, implied by the price in the
+     *  BitAsset-Bitcoin AMM pool.
+     *  Computed as `total_supply * reserve_bitcoin / reserve_bitasset`,
+     *  rounded down, and saturating at `u64::MAX`.
+     *  Returns `None` if the BitAsset does not exist, or if there is no
+     *  BitAsset-Bitcoin pool with nonzero reserves. */
+    pub fn try_get_bitasset_market_cap(
+        &self,
+        txn: &RoTxn,
+        bitasset: &BitAssetId,
+    ) -> Result<Option<u64>, heed::Error> {
+        let Some(total_supply) =
+            self.try_get_bitasset_total_supply(txn, bitasset)?
+        else {
+            return Ok(None);
+        };
+        let amm_pair =
+            AmmPair::new(AssetId::BitAsset(*bitasset), AssetId::Bitcoin);
+        let Some(amm_pool_state) = self.amm_pools.get(txn, &amm_pair)? else {
+            return Ok(None);
+        };
+        let (reserve_bitcoin, reserve_bitasset) =
+            if amm_pair.asset0() == AssetId::Bitcoin {
+                (amm_pool_state.reserve0, amm_pool_state.reserve1)
+            } else {
+                (amm_pool_state.reserve1, amm_pool_state.reserve0)
+            };
+        if reserve_bitcoin == 0 || reserve_bitasset == 0 {
+            return Ok(None);
+        }
+        let market_cap = total_supply as u128 * reserve_bitcoin as u128
+            / reserve_bitasset as u128;
+        Ok(Some(u64::try_from(market_cap).unwrap_or(u64::MAX)))
+    }
+
     pub fn get_utxos(
         &self,
         txn: &RoTxn,
@@ -1185,6 +2529,72 @@ impl State {
         Ok(utxos)
     }
 
+    /// Count all UTXOs, without decoding them
+    pub fn count_utxos(&self, rotxn: &RoTxn) -> Result<u64, Error> {
+        let count = self.utxos.len(rotxn)?;
+        Ok(count)
+    }
+
+    /// Count UTXOs containing the specified asset, without decoding
+    /// outpoints
+    pub fn count_utxos_by_asset(
+        &self,
+        rotxn: &RoTxn,
+        asset: AssetId,
+    ) -> Result<u64, Error> {
+        let mut count = 0;
+        for item in self.utxos.remap_key_type::<DecodeIgnore>().iter(rotxn)? {
+            let ((), output) = item?;
+            if output
+                .asset_value()
+                .is_some_and(|(output_asset, _)| output_asset == asset)
+            {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /** Get a page of UTXOs, skipping the first `offset` UTXOs in the
+     *  specified sort order.
+     *  If `asset` is specified, only UTXOs containing that asset are
+     *  included. */
+    pub fn get_utxos_paginated(
+        &self,
+        rotxn: &RoTxn,
+        offset: usize,
+        limit: usize,
+        asset: Option<AssetId>,
+        sort: UtxoSort,
+    ) -> Result<Vec<(OutPoint, FilledOutput)>, Error> {
+        let utxos =
+            self.utxos.iter(rotxn)?.filter(|item| match (item, asset) {
+                (Ok((_, output)), Some(asset)) => output
+                    .asset_value()
+                    .is_some_and(|(output_asset, _)| output_asset == asset),
+                _ => true,
+            });
+        match sort {
+            UtxoSort::Outpoint => utxos
+                .skip(offset)
+                .take(limit)
+                .map(|item| item.map_err(Error::from))
+                .collect(),
+            UtxoSort::Value => {
+                let value = |output: &FilledOutput| {
+                    output.asset_value().map_or(0, |(_, value)| value)
+                };
+                let mut utxos: Vec<_> = utxos.collect::<Result<_, _>>()?;
+                utxos.sort_by(|(outpoint0, output0), (outpoint1, output1)| {
+                    value(output1)
+                        .cmp(&value(output0))
+                        .then_with(|| outpoint0.cmp(outpoint1))
+                });
+                Ok(utxos.into_iter().skip(offset).take(limit).collect())
+            }
+        }
+    }
+
     pub fn get_utxos_by_addresses(
         &self,
         txn: &RoTxn,
@@ -1277,22 +2687,12 @@ impl State {
         })
     }
 
-    fn collect_withdrawal_bundle(
+    /// Aggregate all pending withdrawal outputs by destination, ordered by
+    /// priority for inclusion in a bundle
+    fn aggregate_withdrawals(
         &self,
         txn: &RoTxn,
-        block_height: u32,
-    ) -> Result<Option<WithdrawalBundle>, Error> {
-        use bitcoin::blockdata::{opcodes, script};
-        // Weight of a bundle with 0 outputs.
-        const BUNDLE_0_WEIGHT: u64 = 504;
-        // Weight of a single output.
-        const OUTPUT_WEIGHT: u64 = 128;
-        // Turns out to be 3121.
-        const MAX_BUNDLE_OUTPUTS: usize =
-            ((bitcoin::policy::MAX_STANDARD_TX_WEIGHT as u64 - BUNDLE_0_WEIGHT)
-                / OUTPUT_WEIGHT) as usize;
-
-        // Aggregate all outputs by destination.
+    ) -> Result<Vec<AggregatedWithdrawal>, Error> {
         // destination -> (value, mainchain fee, spent_utxos)
         let mut address_to_aggregated_withdrawal = HashMap::<
             bitcoin::Address<bitcoin::address::NetworkUnchecked>,
@@ -1323,19 +2723,34 @@ impl State {
                 aggregated.spend_utxos.insert(outpoint, output);
             }
         }
-        if address_to_aggregated_withdrawal.is_empty() {
-            return Ok(None);
-        }
         let mut aggregated_withdrawals: Vec<_> =
             address_to_aggregated_withdrawal.into_values().collect();
         aggregated_withdrawals.sort_by_key(|a| std::cmp::Reverse(a.clone()));
+        Ok(aggregated_withdrawals)
+    }
+
+    /// Number of aggregated withdrawals, ordered by priority, that are
+    /// included in a bundle
+    fn n_bundle_withdrawals(n_aggregated_withdrawals: usize) -> usize {
+        std::cmp::min(n_aggregated_withdrawals, Self::MAX_BUNDLE_OUTPUTS + 1)
+    }
+
+    fn collect_withdrawal_bundle(
+        &self,
+        txn: &RoTxn,
+        block_height: u32,
+    ) -> Result<Option<WithdrawalBundle>, Error> {
+        use bitcoin::blockdata::{opcodes, script};
+        let aggregated_withdrawals = self.aggregate_withdrawals(txn)?;
+        if aggregated_withdrawals.is_empty() {
+            return Ok(None);
+        }
+        let n_bundle_withdrawals =
+            Self::n_bundle_withdrawals(aggregated_withdrawals.len());
         let mut fee = 0;
         let mut spend_utxos = BTreeMap::<OutPoint, FilledOutput>::new();
         let mut bundle_outputs = vec![];
-        for aggregated in &aggregated_withdrawals {
-            if bundle_outputs.len() > MAX_BUNDLE_OUTPUTS {
-                break;
-            }
+        for aggregated in &aggregated_withdrawals[..n_bundle_withdrawals] {
             let bundle_output = bitcoin::TxOut {
                 value: BitcoinAmount::from_sat(aggregated.value),
                 script_pubkey: aggregated
@@ -1421,6 +2836,56 @@ impl State {
         }))
     }
 
+    /// Estimate the effect of a withdrawal on the next bundle, given the
+    /// pending withdrawals
+    pub fn estimate_withdrawal(
+        &self,
+        rotxn: &RoTxn,
+        main_address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+        value: u64,
+        main_fee: u64,
+    ) -> Result<WithdrawalEstimate, Error> {
+        let mut aggregated_withdrawals = self.aggregate_withdrawals(rotxn)?;
+        let pending_bundle_outputs = aggregated_withdrawals.len();
+        // Withdrawals to the same destination are aggregated into one
+        // bundle output
+        let (aggregated, weight) = match aggregated_withdrawals
+            .iter()
+            .position(|aggregated| aggregated.main_address == main_address)
+        {
+            Some(idx) => {
+                let mut aggregated = aggregated_withdrawals.remove(idx);
+                aggregated.value += value;
+                aggregated.main_fee =
+                    std::cmp::max(aggregated.main_fee, main_fee);
+                (aggregated, 0)
+            }
+            None => {
+                let aggregated = AggregatedWithdrawal {
+                    spend_utxos: HashMap::new(),
+                    main_address,
+                    value,
+                    main_fee,
+                };
+                (aggregated, Self::BUNDLE_OUTPUT_WEIGHT)
+            }
+        };
+        aggregated_withdrawals.push(aggregated.clone());
+        aggregated_withdrawals.sort_by_key(|a| std::cmp::Reverse(a.clone()));
+        let n_bundle_withdrawals =
+            Self::n_bundle_withdrawals(aggregated_withdrawals.len());
+        let fits_in_bundle = aggregated_withdrawals[..n_bundle_withdrawals]
+            .contains(&aggregated);
+        Ok(WithdrawalEstimate {
+            weight,
+            bundle_weight: Self::BUNDLE_0_WEIGHT
+                + (n_bundle_withdrawals as u64 * Self::BUNDLE_OUTPUT_WEIGHT),
+            pending_bundle_outputs,
+            max_bundle_outputs: Self::MAX_BUNDLE_OUTPUTS,
+            fits_in_bundle,
+        })
+    }
+
     /// Get pending withdrawal bundle and block height
     pub fn get_pending_withdrawal_bundle(
         &self,
@@ -1429,6 +2894,98 @@ impl State {
         Ok(self.pending_withdrawal_bundle.get(txn, &UnitKey)?)
     }
 
+    /** List all withdrawal bundles, oldest first.
+     *  Confirmed and failed bundles are followed by the pending bundle, if
+     *  there is one. */
+    pub fn list_withdrawal_bundles(
+        &self,
+        rotxn: &RoTxn,
+    ) -> Result<Vec<WithdrawalBundleRecord>, Error> {
+        let record =
+            |bundle: WithdrawalBundle, status, height| WithdrawalBundleRecord {
+                m6id: bundle.compute_m6id(),
+                status,
+                height,
+                spent_outpoints: bundle.spend_utxos.into_keys().collect(),
+            };
+        let mut res: Vec<_> = self
+            .withdrawal_bundles
+            .iter(rotxn)?
+            .map(|item| {
+                let (height, (bundle, status)) = item?;
+                let status = match status {
+                    WithdrawalBundleStatus::Confirmed => {
+                        WithdrawalStatus::Confirmed
+                    }
+                    WithdrawalBundleStatus::Failed => WithdrawalStatus::Failed,
+                };
+                Ok::<_, Error>(record(bundle, status, height))
+            })
+            .collect::<Result<_, _>>()?;
+        if let Some((bundle, height)) =
+            self.pending_withdrawal_bundle.get(rotxn, &UnitKey)?
+        {
+            res.push(record(bundle, WithdrawalStatus::Submitted, height));
+        }
+        Ok(res)
+    }
+
+    /// Get the inpoint that spent the specified outpoint, if it was spent
+    pub fn get_spent_by(
+        &self,
+        rotxn: &RoTxn,
+        outpoint: &OutPoint,
+    ) -> Result<Option<InPoint>, Error> {
+        let inpoint = self
+            .stxos
+            .get(rotxn, outpoint)?
+            .map(|spent_output| spent_output.inpoint);
+        Ok(inpoint)
+    }
+
+    /** Get the latest withdrawal bundle that a withdrawal output was spent
+     *  into, and the status of the bundle.
+     *  If the latest bundle failed, the output was returned to the UTXO set.
+     *  Returns `None` if the output was never collected into a bundle. */
+    pub fn try_get_withdrawal_bundle_for_utxo(
+        &self,
+        rotxn: &RoTxn,
+        outpoint: &OutPoint,
+    ) -> Result<Option<WithdrawalBundleInfo>, Error> {
+        if let Some(SpentOutput {
+            inpoint: InPoint::Withdrawal { m6id },
+            ..
+        }) = self.stxos.get(rotxn, outpoint)?
+            && let Some((bundle, _)) =
+                self.pending_withdrawal_bundle.get(rotxn, &UnitKey)?
+            && bundle.compute_m6id() == m6id
+        {
+            let res = WithdrawalBundleInfo {
+                m6id,
+                status: WithdrawalStatus::Submitted,
+            };
+            return Ok(Some(res));
+        }
+        for item in self.withdrawal_bundles.rev_iter(rotxn)? {
+            let (_, (bundle, status)) = item?;
+            if !bundle.spend_utxos.contains_key(outpoint) {
+                continue;
+            }
+            let status = match status {
+                WithdrawalBundleStatus::Confirmed => {
+                    WithdrawalStatus::Confirmed
+                }
+                WithdrawalBundleStatus::Failed => WithdrawalStatus::Failed,
+            };
+            let res = WithdrawalBundleInfo {
+                m6id: bundle.compute_m6id(),
+                status,
+            };
+            return Ok(Some(res));
+        }
+        Ok(None)
+    }
+
     /// Check that
     /// * If the tx is a BitAsset reservation, then the number of bitasset
     ///   reservations in the outputs is exactly one more than the number of
@@ -1436,7 +2993,8 @@ impl State {
     /// * If the tx is a BitAsset
     ///   registration, then the number of bitasset reservations in the outputs
     ///   is exactly one less than the number of bitasset reservations in the
-    ///   inputs.
+    ///   inputs, and the commitment implied by the revealed nonce is the
+    ///   commitment of a spent reservation.
     /// * Otherwise, the number of bitasset reservations in the outputs
     ///   is exactly equal to the number of bitasset reservations in the inputs.
     pub fn validate_reservations(
@@ -1451,7 +3009,23 @@ impl State {
             }
         } else if tx.is_registration() {
             if n_reservation_inputs == n_reservation_outputs + 1 {
-                return Ok(());
+                let name_hash = tx.registration_name_hash().expect(
+                    "A BitAsset registration tx should have a name hash",
+                );
+                let implied_commitment =
+                    tx.implied_reservation_commitment().expect(
+                        "A BitAsset registration tx should have an implied commitment",
+                    );
+                if tx.spent_reservations().any(|(_, filled_output)| {
+                    filled_output.reservation_commitment()
+                        == Some(&implied_commitment)
+                }) {
+                    return Ok(());
+                }
+                return Err(Error::ReservationCommitmentMismatch {
+                    name_hash,
+                    implied_commitment,
+                });
             }
         } else if n_reservation_inputs == n_reservation_outputs {
             return Ok(());
@@ -1516,6 +3090,19 @@ impl State {
      *      the number of unique BitAsset inputs
      *    * The number of unique BitAsset inputs must be at most equal to the
      *      number of unique BitAsset outputs
+     *  * If the tx is an order placement, then
+     *    * The number of unique BitAsset outputs must be at most equal to the
+     *      number of unique BitAsset inputs
+     *    * The number of unique BitAsset inputs must be at most one more than
+     *      the number of unique BitAsset outputs.
+     *  * If the tx is an order fill, then
+     *    * The number of unique BitAsset outputs must be one less than,
+     *      one greater than, or equal to, the number of unique BitAsset inputs.
+     *  * If the tx is an order cancel, then
+     *    * The number of unique BitAsset outputs must be at most two more than
+     *      the number of unique BitAsset inputs
+     *    * The number of unique BitAsset inputs must be at most equal to the
+     *      number of unique BitAsset outputs
      * */
     pub fn validate_bitassets(
         &self,
@@ -1567,6 +3154,18 @@ impl State {
         {
             return Err(Error::InvalidDutchAuctionBid);
         };
+        if tx.is_atomic_swap_lock()
+            && (n_unique_bitasset_outputs > n_unique_bitasset_inputs
+                || n_unique_bitasset_inputs > n_unique_bitasset_outputs + 1)
+        {
+            return Err(Error::InvalidAtomicSwapLock);
+        };
+        if (tx.is_atomic_swap_claim() || tx.is_atomic_swap_refund())
+            && (n_unique_bitasset_inputs > n_unique_bitasset_outputs
+                || n_unique_bitasset_outputs > n_unique_bitasset_inputs + 1)
+        {
+            return Err(Error::InvalidAtomicSwapRelease);
+        };
         if tx.is_dutch_auction_create()
             && (n_unique_bitasset_inputs < 1
                 || n_unique_bitasset_outputs > n_unique_bitasset_inputs
@@ -1581,6 +3180,29 @@ impl State {
         {
             return Err(Error::InvalidDutchAuctionCollect);
         };
+        if tx.is_order_place()
+            && (n_unique_bitasset_outputs > n_unique_bitasset_inputs
+                || n_unique_bitasset_inputs > n_unique_bitasset_outputs + 1)
+        {
+            return Err(Error::InvalidOrderPlace);
+        };
+        if tx.is_order_fill()
+            && !{
+                let min_unique_bitasset_outputs =
+                    n_unique_bitasset_inputs.saturating_sub(1);
+                let max_unique_bitasset_outputs = n_unique_bitasset_inputs + 1;
+                (min_unique_bitasset_outputs..=max_unique_bitasset_outputs)
+                    .contains(&n_unique_bitasset_outputs)
+            }
+        {
+            return Err(Error::InvalidOrderFill);
+        };
+        if tx.is_order_cancel()
+            && (n_unique_bitasset_inputs > n_unique_bitasset_outputs
+                || n_unique_bitasset_outputs > n_unique_bitasset_inputs + 2)
+        {
+            return Err(Error::InvalidOrderCancel);
+        };
         if let Some(TxData::BitAssetRegistration {
             name_hash,
             initial_supply,
@@ -1649,15 +3271,151 @@ impl State {
         }
     }
 
+    /// Check that the AMM pool exists, if the tx is an AMM swap or burn.
+    /// Otherwise, the tx would only be rejected when connecting a block.
+    pub fn validate_amm_pools(
+        &self,
+        rotxn: &RoTxn,
+        tx: &FilledTransaction,
+    ) -> Result<(), Error> {
+        let amm_pair = if let Some(AmmSwap {
+            asset_spend,
+            asset_receive,
+            ..
+        }) = tx.amm_swap()
+        {
+            AmmPair::new(asset_spend, asset_receive)
+        } else if let Some(AmmBurn { asset0, asset1, .. }) = tx.amm_burn() {
+            AmmPair::new(asset0, asset1)
+        } else {
+            return Ok(());
+        };
+        if self.amm_pools.get(rotxn, &amm_pair)?.is_none() {
+            return Err(Error::MissingAmmPoolState {
+                asset0: amm_pair.asset0(),
+                asset1: amm_pair.asset1(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that the number of tx inputs and outputs is within the
+    /// configured limits
+    fn validate_tx_size(&self, tx: &FilledTransaction) -> Result<(), Error> {
+        let n_inputs = tx.transaction.inputs.len();
+        if n_inputs > self.tx_limits.max_inputs {
+            return Err(Error::TooManyInputs {
+                n_inputs,
+                max_inputs: self.tx_limits.max_inputs,
+            });
+        }
+        let n_outputs = tx.transaction.outputs.len();
+        if n_outputs > self.tx_limits.max_outputs {
+            return Err(Error::TooManyOutputs {
+                n_outputs,
+                max_outputs: self.tx_limits.max_outputs,
+            });
+        }
+        Ok(())
+    }
+
+    /** Select up to `max_txs` txs for inclusion in a block, in order of
+     *  fee rate, highest first.
+     *  Fee rate is the fee paid per byte of the tx's serialized size,
+     *  including authorizations. Txs with equal fee rates are ordered by
+     *  txid.
+     *  Each tx is validated against the current state. Txs that are invalid,
+     *  or that spend a UTXO already spent by a selected tx, are rejected.
+     *  Valid txs that are not selected because `max_txs` was reached, or
+     *  because selecting them would exceed
+     *  [`Self::MAX_BITASSET_UPDATES_PER_BLOCK`], are neither selected nor
+     *  rejected. */
+    pub fn select_transactions(
+        &self,
+        rotxn: &RoTxn,
+        transactions: Vec<AuthorizedTransaction>,
+        max_txs: usize,
+    ) -> Result<TxSelection, Error> {
+        let mut res = TxSelection::default();
+        // Valid txs, with their fees and serialized sizes
+        let mut candidates = Vec::with_capacity(transactions.len());
+        for transaction in transactions {
+            let txid = transaction.transaction.txid();
+            let fee = match self.validate_transaction(rotxn, &transaction) {
+                Ok(fee) => fee,
+                Err(err @ Error::Heed(_)) => return Err(err),
+                Err(_) => {
+                    res.rejected.push(txid);
+                    continue;
+                }
+            };
+            let size = bincode::serialized_size(&transaction)?;
+            candidates.push((txid, transaction, fee, size));
+        }
+        /* Compare fee rates without division, by comparing
+         * `fee_a * size_b` and `fee_b * size_a` */
+        candidates.sort_by(
+            |(txid_a, _, fee_a, size_a), (txid_b, _, fee_b, size_b)| {
+                let fee_rate_a = *fee_a as u128 * *size_b as u128;
+                let fee_rate_b = *fee_b as u128 * *size_a as u128;
+                fee_rate_b.cmp(&fee_rate_a).then(txid_a.cmp(txid_b))
+            },
+        );
+        let mut spent_utxos = HashSet::new();
+        let mut bitasset_updates = HashMap::<BitAssetId, usize>::new();
+        for (txid, transaction, fee, _) in candidates {
+            if res.txs.len() >= max_txs {
+                break;
+            }
+            let inputs = &transaction.transaction.inputs;
+            if inputs.iter().any(|input| spent_utxos.contains(input)) {
+                res.rejected.push(txid);
+                continue;
+            }
+            let filled_tx =
+                self.fill_authorized_transaction(rotxn, transaction)?;
+            if let Some(bitasset) = filled_tx.transaction.updated_bitasset() {
+                let n_updates = bitasset_updates.entry(bitasset).or_default();
+                if *n_updates >= Self::MAX_BITASSET_UPDATES_PER_BLOCK {
+                    continue;
+                }
+                *n_updates += 1;
+            }
+            spent_utxos.extend(
+                filled_tx.transaction.transaction.inputs.iter().copied(),
+            );
+            res.fees += fee;
+            res.txs.push(filled_tx);
+        }
+        Ok(res)
+    }
+
     /// Validates a filled transaction, and returns the fee
     pub fn validate_filled_transaction(
         &self,
         rotxn: &RoTxn,
         tx: &FilledTransaction,
     ) -> Result<u64, Error> {
-        let () = self.validate_reservations(tx)?;
-        let () = self.validate_bitassets(rotxn, tx)?;
-        tx.bitcoin_fee().ok_or(Error::NotEnoughValueIn)
+        let () = self.validate_tx_size(tx).map_err(|err| {
+            err.at_tx_validation_stage(TxValidationStage::Size)
+        })?;
+        let () = self.validate_reservations(tx).map_err(|err| {
+            err.at_tx_validation_stage(TxValidationStage::Reservations)
+        })?;
+        let () = self.validate_bitassets(rotxn, tx).map_err(|err| {
+            err.at_tx_validation_stage(TxValidationStage::BitAssets)
+        })?;
+        if !tx.lp_tokens_conserved() {
+            return Err(Error::LpTokensNotConserved
+                .at_tx_validation_stage(TxValidationStage::LpTokens));
+        }
+        let () = self.validate_amm_pools(rotxn, tx).map_err(|err| {
+            err.at_tx_validation_stage(TxValidationStage::AmmPools)
+        })?;
+        tx.bitcoin_fee().ok_or_else(|| {
+            Error::NotEnoughValueIn
+                .at_tx_validation_stage(TxValidationStage::Fee)
+        })
     }
 
     pub fn validate_transaction(
@@ -1665,31 +3423,43 @@ impl State {
         rotxn: &RoTxn,
         transaction: &AuthorizedTransaction,
     ) -> Result<u64, Error> {
-        let filled_transaction =
-            self.fill_transaction(rotxn, &transaction.transaction)?;
-        for (authorization, spent_utxo) in transaction
-            .authorizations
-            .iter()
-            .zip(filled_transaction.spent_utxos.iter())
+        let filled_transaction = self
+            .fill_transaction(rotxn, &transaction.transaction)
+            .map_err(|err| {
+                err.at_tx_validation_stage(TxValidationStage::Fill)
+            })?;
+        for (authorization, (input, spent_utxo)) in
+            transaction.authorizations.iter().zip(
+                transaction
+                    .transaction
+                    .inputs
+                    .iter()
+                    .zip(filled_transaction.spent_utxos.iter()),
+            )
         {
             if authorization.get_address() != spent_utxo.address {
-                return Err(Error::WrongPubKeyForAddress);
+                return Err(Error::WrongPubKeyForAddress { input: *input }
+                    .at_tx_validation_stage(TxValidationStage::Authorization));
             }
         }
         if Authorization::verify_transaction(transaction).is_err() {
-            return Err(Error::AuthorizationError);
+            return Err(Error::AuthorizationError
+                .at_tx_validation_stage(TxValidationStage::Authorization));
         }
         let fee =
             self.validate_filled_transaction(rotxn, &filled_transaction)?;
         Ok(fee)
     }
 
+    /// Validate a block, returning the total fees and the filled txs.
+    /// The filled txs can be passed to [`Self::connect_filled_block`], so that
+    /// they do not need to be filled again when connecting the block.
     pub fn validate_block(
         &self,
         rotxn: &RoTxn,
         header: &Header,
         body: &Body,
-    ) -> Result<u64, Error> {
+    ) -> Result<(u64, Vec<FilledTransaction>), Error> {
         let tip_hash = self.get_tip(rotxn)?;
         if header.prev_side_hash != tip_hash {
             let err = InvalidHeaderError::PrevSideHash {
@@ -1706,6 +3476,19 @@ impl State {
             };
             return Err(err);
         }
+        // A body with a duplicated last tx, or duplicated txs at the end of
+        // the body, can have the same merkle root as the original body
+        // (CVE-2012-2459)
+        let mut txids = HashSet::new();
+        for tx in &body.transactions {
+            let txid = tx.txid();
+            if !txids.insert(txid) {
+                return Err(Error::DuplicateTxid { txid });
+            }
+        }
+        if body.has_duplicate_merkle_nodes() {
+            return Err(Error::DuplicateMerkleNodes);
+        }
         let mut coinbase_value: u64 = 0;
         for output in &body.coinbase {
             coinbase_value += output.get_bitcoin_value();
@@ -1717,6 +3500,7 @@ impl State {
             .iter()
             .map(|t| self.fill_transaction(rotxn, t))
             .collect::<Result<_, _>>()?;
+        let mut bitasset_updates = HashMap::<BitAssetId, usize>::new();
         for filled_transaction in &filled_transactions {
             for input in &filled_transaction.transaction.inputs {
                 if spent_utxos.contains(input) {
@@ -1724,26 +3508,50 @@ impl State {
                 }
                 spent_utxos.insert(*input);
             }
+            if let Some(bitasset) = filled_transaction.updated_bitasset() {
+                let n_updates = bitasset_updates.entry(bitasset).or_default();
+                *n_updates += 1;
+                if *n_updates > Self::MAX_BITASSET_UPDATES_PER_BLOCK {
+                    return Err(Error::MultipleBitAssetUpdates {
+                        bitasset,
+                        max_updates: Self::MAX_BITASSET_UPDATES_PER_BLOCK,
+                    });
+                }
+            }
             total_fees +=
                 self.validate_filled_transaction(rotxn, filled_transaction)?;
         }
         if coinbase_value > total_fees {
             return Err(Error::NotEnoughFees);
         }
-        let spent_utxos = filled_transactions
-            .iter()
-            .flat_map(|t| t.spent_utxos.iter());
-        for (authorization, spent_utxo) in
+        let spent_utxos = filled_transactions.iter().flat_map(|t| {
+            t.transaction.inputs.iter().zip(t.spent_utxos.iter())
+        });
+        for (authorization, (input, spent_utxo)) in
             body.authorizations.iter().zip(spent_utxos)
         {
             if authorization.get_address() != spent_utxo.address {
-                return Err(Error::WrongPubKeyForAddress);
+                return Err(Error::WrongPubKeyForAddress { input: *input });
             }
         }
         if Authorization::verify_body(body).is_err() {
             return Err(Error::AuthorizationError);
         }
-        Ok(total_fees)
+        Ok((total_fees, filled_transactions))
+    }
+
+    /// Get mainchain deposit blocks, and the sidechain height at which each
+    /// was applied, in the order that they were applied
+    pub fn get_deposit_blocks(
+        &self,
+        rotxn: &RoTxn,
+    ) -> Result<Vec<(bitcoin::BlockHash, u32)>, Error> {
+        let deposit_blocks = self
+            .deposit_blocks
+            .iter(rotxn)?
+            .map(|item| item.map(|(_, deposit_block)| deposit_block))
+            .collect::<Result<_, _>>()?;
+        Ok(deposit_blocks)
     }
 
     pub fn get_last_deposit_block_hash(
@@ -1805,10 +3613,10 @@ impl State {
             {
                 for (outpoint, spend_output) in &bundle.spend_utxos {
                     self.utxos.delete(rwtxn, outpoint)?;
-                    let txid = bundle.transaction.txid();
+                    let m6id = bundle.compute_m6id();
                     let spent_output = SpentOutput {
                         output: spend_output.clone(),
-                        inpoint: InPoint::Withdrawal { txid },
+                        inpoint: InPoint::Withdrawal { m6id },
                     };
                     self.stxos.put(rwtxn, outpoint, &spent_output)?;
                 }
@@ -1875,7 +3683,7 @@ impl State {
                     {
                         let spent_output = SpentOutput {
                             output: output.clone(),
-                            inpoint: InPoint::Withdrawal { txid: *txid },
+                            inpoint: InPoint::Withdrawal { m6id: M6id(*txid) },
                         };
                         self.stxos.put(rwtxn, &outpoint, &spent_output)?;
                         if self.utxos.delete(rwtxn, &outpoint)? {
@@ -1922,13 +3730,172 @@ impl State {
                 return Err(Error::NoDepositBlock);
             };
         }
-        for deposit in two_way_peg_data.deposits.iter().rev() {
-            if let Ok(_address) = deposit.output.address.parse::<Address>() {
-                let outpoint = OutPoint::Deposit(deposit.outpoint);
-                if !self.utxos.delete(rwtxn, &outpoint)? {
-                    return Err(Error::NoUtxo { outpoint });
+        for deposit in two_way_peg_data.deposits.iter().rev() {
+            if let Ok(_address) = deposit.output.address.parse::<Address>() {
+                let outpoint = OutPoint::Deposit(deposit.outpoint);
+                if !self.utxos.delete(rwtxn, &outpoint)? {
+                    return Err(Error::NoUtxo { outpoint });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Addresses that a tx spends from or pays to
+    fn tx_addresses(filled_tx: &FilledTransaction) -> HashSet<Address> {
+        filled_tx
+            .spent_utxos
+            .iter()
+            .map(|spent_utxo| spent_utxo.address)
+            .chain(filled_tx.outputs().iter().map(|output| output.address))
+            .collect()
+    }
+
+    /// Key for the address activity index. Keys sort by address, then by
+    /// block height, and then by the position of the tx in the block.
+    fn address_activity_key(
+        address: &Address,
+        height: u32,
+        tx_idx: u32,
+    ) -> [u8; 28] {
+        let mut key = [0; 28];
+        key[..20].copy_from_slice(&address.0);
+        key[20..24].copy_from_slice(&height.to_be_bytes());
+        key[24..].copy_from_slice(&tx_idx.to_be_bytes());
+        key
+    }
+
+    /** Record a tx in the address activity index, if enabled.
+     *  `tx_idx` is the position of the tx in the block at `height`. */
+    fn put_address_activity(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+        height: u32,
+        tx_idx: u32,
+    ) -> Result<(), Error> {
+        let Some(address_activity) = self.address_activity else {
+            return Ok(());
+        };
+        let txid = filled_tx.txid();
+        for address in Self::tx_addresses(filled_tx) {
+            let key = Self::address_activity_key(&address, height, tx_idx);
+            address_activity.put(rwtxn, &key, &txid)?;
+        }
+        Ok(())
+    }
+
+    /** Remove a tx from the address activity index, if enabled.
+     *  `tx_idx` is the position of the tx in the block at `height`. */
+    fn delete_address_activity(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+        height: u32,
+        tx_idx: u32,
+    ) -> Result<(), Error> {
+        let Some(address_activity) = self.address_activity else {
+            return Ok(());
+        };
+        for address in Self::tx_addresses(filled_tx) {
+            let key = Self::address_activity_key(&address, height, tx_idx);
+            address_activity.delete(rwtxn, &key)?;
+        }
+        Ok(())
+    }
+
+    /// BitAssets for which a tx spends or creates outputs or control coins
+    fn tx_bitassets(
+        filled_tx: &FilledTransaction,
+    ) -> Result<HashSet<BitAssetId>, Error> {
+        let filled_outputs = filled_tx
+            .filled_outputs()
+            .ok_or(Error::FillTxOutputContentsFailed)?;
+        let bitassets = filled_tx
+            .spent_utxos
+            .iter()
+            .chain(filled_outputs.iter())
+            .filter_map(FilledOutput::get_bitasset)
+            .collect();
+        Ok(bitassets)
+    }
+
+    /// Record a tx in the BitAsset activity index
+    fn put_bitasset_activity(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+    ) -> Result<(), Error> {
+        let txid = filled_tx.txid();
+        for bitasset in Self::tx_bitassets(filled_tx)? {
+            let mut txids = self.get_bitasset_txids(rwtxn, &bitasset)?;
+            txids.push(txid);
+            self.bitasset_activity.put(rwtxn, &bitasset, &txids)?;
+        }
+        Ok(())
+    }
+
+    /// Remove a tx from the BitAsset activity index
+    fn delete_bitasset_activity(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+    ) -> Result<(), Error> {
+        let txid = filled_tx.txid();
+        for bitasset in Self::tx_bitassets(filled_tx)? {
+            let mut txids = self.get_bitasset_txids(rwtxn, &bitasset)?;
+            // Txs are reverted last-to-first
+            if txids.last() == Some(&txid) {
+                txids.pop();
+            }
+            if txids.is_empty() {
+                self.bitasset_activity.delete(rwtxn, &bitasset)?;
+            } else {
+                self.bitasset_activity.put(rwtxn, &bitasset, &txids)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Store the new state of an AMM pool, and record it in the pool history
+    fn put_amm_pool_state(
+        &self,
+        rwtxn: &mut RwTxn,
+        amm_pair: &AmmPair,
+        amm_pool_state: &AmmPoolState,
+        txid: Txid,
+        height: u32,
+    ) -> Result<(), Error> {
+        self.amm_pools.put(rwtxn, amm_pair, amm_pool_state)?;
+        let amm_pool_history =
+            match self.amm_pool_history.get(rwtxn, amm_pair)? {
+                Some(mut amm_pool_history) => {
+                    amm_pool_history.push(*amm_pool_state, txid, height);
+                    amm_pool_history
                 }
-            }
+                None => RollBack::new(*amm_pool_state, txid, height),
+            };
+        self.amm_pool_history
+            .put(rwtxn, amm_pair, &amm_pool_history)?;
+        Ok(())
+    }
+
+    /// Remove the most recent state from the AMM pool history
+    fn pop_amm_pool_history(
+        &self,
+        rwtxn: &mut RwTxn,
+        amm_pair: &AmmPair,
+    ) -> Result<(), Error> {
+        let Some(mut amm_pool_history) =
+            self.amm_pool_history.get(rwtxn, amm_pair)?
+        else {
+            return Ok(());
+        };
+        if amm_pool_history.pop().is_some() {
+            self.amm_pool_history
+                .put(rwtxn, amm_pair, &amm_pool_history)?;
+        } else {
+            self.amm_pool_history.delete(rwtxn, amm_pair)?;
         }
         Ok(())
     }
@@ -1938,6 +3905,7 @@ impl State {
         &self,
         rwtxn: &mut RwTxn,
         filled_tx: &FilledTransaction,
+        height: u32,
     ) -> Result<(), Error> {
         let AmmBurn {
             asset0,
@@ -1965,7 +3933,13 @@ impl State {
         if payout1 != amount1 {
             return Err(Error::InvalidAmmBurn);
         }
-        self.amm_pools.put(rwtxn, &amm_pair, &new_amm_pool_state)?;
+        let () = self.put_amm_pool_state(
+            rwtxn,
+            &amm_pair,
+            &new_amm_pool_state,
+            filled_tx.txid(),
+            height,
+        )?;
         Ok(())
     }
 
@@ -1992,6 +3966,7 @@ impl State {
         let prev_amm_pool_state =
             amm_pool_state.unburn(lp_token_burn, amount0, amount1)?;
         self.amm_pools.put(rwtxn, &amm_pair, &prev_amm_pool_state)?;
+        let () = self.pop_amm_pool_history(rwtxn, &amm_pair)?;
         Ok(())
     }
 
@@ -2000,6 +3975,7 @@ impl State {
         &self,
         rwtxn: &mut RwTxn,
         filled_tx: &FilledTransaction,
+        height: u32,
     ) -> Result<(), Error> {
         let AmmMint {
             asset0,
@@ -2012,19 +3988,40 @@ impl State {
             return Err(Error::InvalidAmmMint);
         }
         let amm_pair = AmmPair::new(asset0, asset1);
-        let amm_pool_state = self
-            .amm_pools
-            .get(rwtxn, &amm_pair)?
-            .unwrap_or_else(|| AmmPoolState::new(filled_tx.txid()));
+        let amm_pool_state = match self.amm_pools.get(rwtxn, &amm_pair)? {
+            Some(amm_pool_state) => amm_pool_state,
+            None => {
+                /* If pool creation is permissioned, the tx that creates the
+                 * pool must spend an output owned by a permitted address */
+                if let Some(amm_pool_creators) = &self.amm_pool_creators
+                    && !filled_tx
+                        .spent_utxos
+                        .iter()
+                        .any(|utxo| amm_pool_creators.contains(&utxo.address))
+                {
+                    return Err(Error::AmmPoolCreationNotPermitted {
+                        asset0: amm_pair.asset0(),
+                        asset1: amm_pair.asset1(),
+                    });
+                }
+                AmmPoolState::new(filled_tx.txid())
+            }
+        };
         let new_amm_pool_state = amm_pool_state.mint(amount0, amount1)?;
         let lp_tokens_minted = new_amm_pool_state
             .outstanding_lp_tokens
-            .checked_sub(lp_token_mint)
+            .checked_sub(amm_pool_state.outstanding_lp_tokens)
             .ok_or(Error::InvalidAmmMint)?;
         if lp_tokens_minted != lp_token_mint {
             do yeet Error::InvalidAmmMint;
         }
-        self.amm_pools.put(rwtxn, &amm_pair, &new_amm_pool_state)?;
+        let () = self.put_amm_pool_state(
+            rwtxn,
+            &amm_pair,
+            &new_amm_pool_state,
+            filled_tx.txid(),
+            height,
+        )?;
         Ok(())
     }
 
@@ -2054,10 +4051,12 @@ impl State {
             })?;
         if amm_pool_state.creation_txid == filled_tx.txid() {
             self.amm_pools.delete(rwtxn, &amm_pair)?;
+            let () = self.pop_amm_pool_history(rwtxn, &amm_pair)?;
         } else {
             let new_amm_pool_state =
                 amm_pool_state.revert_mint(amount0, amount1, lp_token_mint)?;
             self.amm_pools.put(rwtxn, &amm_pair, &new_amm_pool_state)?;
+            let () = self.pop_amm_pool_history(rwtxn, &amm_pair)?;
         }
         Ok(())
     }
@@ -2067,6 +4066,7 @@ impl State {
         &self,
         rwtxn: &mut RwTxn,
         filled_tx: &FilledTransaction,
+        height: u32,
     ) -> Result<(), Error> {
         let AmmSwap {
             asset_spend,
@@ -2098,7 +4098,62 @@ impl State {
         if amount_receive != amount_receive_after_fee {
             return Err(Error::InvalidAmmSwap);
         }
-        self.amm_pools.put(rwtxn, &amm_pair, &new_amm_pool_state)?;
+        /* Fees are retained in the pool, so the product of the reserves
+         * must never decrease as a result of a swap */
+        let reserve_product =
+            amm_pool_state.reserve0 as u128 * amm_pool_state.reserve1 as u128;
+        let new_reserve_product = new_amm_pool_state.reserve0 as u128
+            * new_amm_pool_state.reserve1 as u128;
+        if new_reserve_product < reserve_product {
+            return Err(Error::AmmPoolInvariant);
+        }
+        if let Some(max_price_impact_bps) = self.amm_max_price_impact_bps {
+            let (reserve_spend, reserve_receive) =
+                if asset_spend < asset_receive {
+                    (amm_pool_state.reserve0, amm_pool_state.reserve1)
+                } else {
+                    (amm_pool_state.reserve1, amm_pool_state.reserve0)
+                };
+            if AmmPoolState::price_impact_exceeds(
+                reserve_spend,
+                reserve_receive,
+                amount_spend,
+                amount_receive,
+                max_price_impact_bps,
+            ) {
+                return Err(Error::AmmPriceImpactExceeded {
+                    max_price_impact_bps,
+                });
+            }
+        }
+        // The protocol fee must be paid to the protocol fee address
+        let protocol_fee = self.amm_protocol_fee.fee(amount_spend);
+        if protocol_fee != 0 {
+            let fee_address = self.amm_protocol_fee.address;
+            let filled_outputs = filled_tx
+                .filled_outputs()
+                .ok_or(Error::FillTxOutputContentsFailed)?;
+            let fee_paid: u64 = filled_outputs
+                .iter()
+                .filter(|output| output.address == fee_address)
+                .filter_map(|output| output.asset_value())
+                .filter(|(output_asset, _)| *output_asset == asset_spend)
+                .map(|(_, value)| value)
+                .sum();
+            if fee_paid < protocol_fee {
+                return Err(Error::InsufficientAmmProtocolFee {
+                    address: fee_address,
+                    fee: protocol_fee,
+                });
+            }
+        }
+        let () = self.put_amm_pool_state(
+            rwtxn,
+            &amm_pair,
+            &new_amm_pool_state,
+            filled_tx.txid(),
+            height,
+        )?;
         Ok(())
     }
 
@@ -2124,6 +4179,126 @@ impl State {
             })?;
         let new_amm_pool_state = amm_pool_state.revert_swap(amm_swap)?;
         self.amm_pools.put(rwtxn, &amm_pair, &new_amm_pool_state)?;
+        let () = self.pop_amm_pool_history(rwtxn, &amm_pair)?;
+        Ok(())
+    }
+
+    // Apply atomic swap lock
+    fn apply_atomic_swap_lock(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+        atomic_swap_params: AtomicSwapParams,
+        height: u32,
+    ) -> Result<(), Error> {
+        let AtomicSwapParams {
+            asset,
+            amount,
+            recipient,
+            refund_address,
+            hash_lock,
+            timeout,
+        } = atomic_swap_params;
+        if amount == 0 {
+            do yeet AtomicSwapLockError::ZeroAmount;
+        };
+        if height >= timeout {
+            do yeet AtomicSwapLockError::Expired;
+        };
+        let txid = filled_tx.txid();
+        let atomic_swap_id = AtomicSwapId(txid);
+        let atomic_swap_state = AtomicSwapState {
+            asset,
+            amount,
+            recipient,
+            refund_address,
+            hash_lock,
+            timeout,
+            status: RollBack::new(AtomicSwapStatus::Locked, txid, height),
+        };
+        self.atomic_swaps
+            .put(rwtxn, &atomic_swap_id, &atomic_swap_state)?;
+        Ok(())
+    }
+
+    // Revert atomic swap lock
+    fn revert_atomic_swap_lock(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+    ) -> Result<(), Error> {
+        let atomic_swap_id = AtomicSwapId(filled_tx.txid());
+        if !self.atomic_swaps.delete(rwtxn, &atomic_swap_id)? {
+            return Err(Error::MissingAtomicSwap(atomic_swap_id));
+        };
+        Ok(())
+    }
+
+    // Apply atomic swap claim or refund
+    fn apply_atomic_swap_release(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+        height: u32,
+    ) -> Result<(), Error> {
+        let AtomicSwapRelease {
+            swap_id,
+            preimage,
+            asset,
+            amount,
+        } = filled_tx
+            .atomic_swap_release()
+            .ok_or(AtomicSwapReleaseError::InvalidTxData)?;
+        let swap_state = self
+            .atomic_swaps
+            .get(rwtxn, &swap_id)?
+            .ok_or(AtomicSwapReleaseError::MissingSwap)?;
+        if swap_state.asset != asset {
+            do yeet AtomicSwapReleaseError::IncorrectAsset
+        }
+        if swap_state.amount != amount {
+            do yeet AtomicSwapReleaseError::IncorrectAmount
+        }
+        let new_swap_state =
+            swap_state.release(filled_tx.txid(), preimage, height)?;
+        // The released asset must be sent to the release address
+        let release_address = swap_state.release_address(preimage.as_ref());
+        let filled_outputs = filled_tx
+            .filled_outputs()
+            .ok_or(Error::FillTxOutputContentsFailed)?;
+        let released_amount: u64 = filled_outputs
+            .iter()
+            .filter(|output| output.address == release_address)
+            .filter_map(|output| output.asset_value())
+            .filter(|(output_asset, _)| *output_asset == asset)
+            .map(|(_, value)| value)
+            .sum();
+        if released_amount < amount {
+            do yeet AtomicSwapReleaseError::InsufficientOutputs {
+                address: release_address,
+            }
+        }
+        self.atomic_swaps.put(rwtxn, &swap_id, &new_swap_state)?;
+        Ok(())
+    }
+
+    // Revert atomic swap claim or refund
+    fn revert_atomic_swap_release(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+    ) -> Result<(), Error> {
+        let AtomicSwapRelease { swap_id, .. } = filled_tx
+            .atomic_swap_release()
+            .ok_or(AtomicSwapReleaseError::InvalidTxData)?;
+        let txid = filled_tx.txid();
+        let mut swap_state = self
+            .atomic_swaps
+            .get(rwtxn, &swap_id)?
+            .ok_or(Error::RevertAtomicSwapRelease)?;
+        assert!(swap_state.status.pop().is_some_and(|ts| ts.txid == txid));
+        assert_eq!(swap_state.status.latest().data, AtomicSwapStatus::Locked);
+        self.atomic_swaps.put(rwtxn, &swap_id, &swap_state)?;
         Ok(())
     }
 
@@ -2305,25 +4480,18 @@ impl State {
         bitasset_updates: BitAssetDataUpdates,
         height: u32,
     ) -> Result<(), Error> {
-        /* The updated BitAsset is the BitAsset that corresponds to the last
-         * bitasset output, or equivalently, the BitAsset corresponding to the
-         * last BitAsset input */
         let updated_bitasset = filled_tx
-            .spent_bitassets()
-            .next_back()
-            .ok_or(Error::NoBitAssetsToUpdate)?
-            .1
-            .bitasset()
-            .expect("should only contain BitAsset outputs");
+            .updated_bitasset()
+            .ok_or(Error::NoBitAssetsToUpdate)?;
         let mut bitasset_data = self
             .bitassets
-            .get(rwtxn, updated_bitasset)?
+            .get(rwtxn, &updated_bitasset)?
             .ok_or(Error::MissingBitAsset {
-                bitasset: *updated_bitasset,
-            })?;
+            bitasset: updated_bitasset,
+        })?;
         bitasset_data.apply_updates(bitasset_updates, filled_tx.txid(), height);
         self.bitassets
-            .put(rwtxn, updated_bitasset, &bitasset_data)?;
+            .put(rwtxn, &updated_bitasset, &bitasset_data)?;
         Ok(())
     }
 
@@ -2335,29 +4503,22 @@ impl State {
         bitasset_updates: BitAssetDataUpdates,
         height: u32,
     ) -> Result<(), Error> {
-        /* The updated BitAsset is the BitAsset that corresponds to the last
-         * bitasset output, or equivalently, the BitAsset corresponding to the
-         * last BitAsset input */
         let updated_bitasset = filled_tx
-            .spent_bitassets()
-            .next_back()
-            .ok_or(Error::NoBitAssetsToUpdate)?
-            .1
-            .bitasset()
-            .expect("should only contain BitAsset outputs");
+            .updated_bitasset()
+            .ok_or(Error::NoBitAssetsToUpdate)?;
         let mut bitasset_data = self
             .bitassets
-            .get(rwtxn, updated_bitasset)?
+            .get(rwtxn, &updated_bitasset)?
             .ok_or(Error::MissingBitAsset {
-                bitasset: *updated_bitasset,
-            })?;
+            bitasset: updated_bitasset,
+        })?;
         bitasset_data.revert_updates(
             bitasset_updates,
             filled_tx.txid(),
             height,
         );
         self.bitassets
-            .put(rwtxn, updated_bitasset, &bitasset_data)?;
+            .put(rwtxn, &updated_bitasset, &bitasset_data)?;
         Ok(())
     }
 
@@ -2607,11 +4768,218 @@ impl State {
         Ok(())
     }
 
+    // Apply order placement
+    fn apply_order_place(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+        order_params: OrderParams,
+        height: u32,
+    ) -> Result<(), Error> {
+        let OrderParams {
+            sell_asset,
+            sell_amount,
+            buy_asset,
+            price,
+        } = order_params;
+        if sell_amount == 0 {
+            do yeet OrderPlaceError::ZeroAmount;
+        };
+        if price == 0 {
+            do yeet OrderPlaceError::ZeroPrice;
+        };
+        if sell_asset == buy_asset {
+            do yeet OrderPlaceError::SameAsset;
+        };
+        if sell_amount.checked_mul(price).is_none() {
+            do yeet OrderPlaceError::Overflow;
+        };
+        let txid = filled_tx.txid();
+        let order_id = OrderId(txid);
+        let order_state = OrderState {
+            sell_asset,
+            initial_sell_amount: sell_amount,
+            sell_amount_remaining: RollBack::new(sell_amount, txid, height),
+            buy_asset,
+            buy_amount_received: RollBack::new(0, txid, height),
+            price,
+        };
+        self.orders.put(rwtxn, &order_id, &order_state)?;
+        Ok(())
+    }
+
+    // Revert order placement
+    fn revert_order_place(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+    ) -> Result<(), Error> {
+        let order_id = OrderId(filled_tx.txid());
+        if !self.orders.delete(rwtxn, &order_id)? {
+            return Err(Error::MissingOrder(order_id));
+        };
+        Ok(())
+    }
+
+    // Apply order fill
+    fn apply_order_fill(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+        height: u32,
+    ) -> Result<(), Error> {
+        let OrderFill {
+            order_id,
+            asset_spend,
+            asset_receive,
+            amount_spend,
+            amount_receive,
+        } = filled_tx
+            .order_fill()
+            .ok_or(OrderFillError::InvalidTxData)?;
+        let order_state = self
+            .orders
+            .get(rwtxn, &order_id)?
+            .ok_or(OrderFillError::MissingOrder)?;
+        if asset_receive != order_state.sell_asset {
+            do yeet OrderFillError::IncorrectReceiveAsset
+        }
+        if asset_spend != order_state.buy_asset {
+            do yeet OrderFillError::IncorrectSpendAsset
+        }
+        let new_order_state =
+            order_state.fill(filled_tx.txid(), amount_receive, height)?;
+        if order_state.cost(amount_receive) != Some(amount_spend) {
+            do yeet OrderFillError::InvalidPrice
+        };
+        self.orders.put(rwtxn, &order_id, &new_order_state)?;
+        Ok(())
+    }
+
+    // Revert order fill
+    fn revert_order_fill(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+    ) -> Result<(), Error> {
+        let OrderFill { order_id, .. } = filled_tx
+            .order_fill()
+            .ok_or(OrderFillError::InvalidTxData)?;
+        let order_state = self
+            .orders
+            .get(rwtxn, &order_id)?
+            .ok_or(OrderFillError::MissingOrder)?;
+        let new_order_state = order_state.revert_fill(filled_tx.txid());
+        self.orders.put(rwtxn, &order_id, &new_order_state)?;
+        Ok(())
+    }
+
+    // Apply order cancel
+    fn apply_order_cancel(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+        height: u32,
+    ) -> Result<(), Error> {
+        let OrderCancel {
+            order_id,
+            asset_offered,
+            asset_receive,
+            amount_offered_remaining,
+            amount_received,
+        } = filled_tx
+            .order_cancel()
+            .ok_or(OrderCancelError::InvalidTxData)?;
+        let mut order_state = self
+            .orders
+            .get(rwtxn, &order_id)?
+            .ok_or(OrderCancelError::MissingOrder)?;
+        if order_state.sell_asset != asset_offered {
+            do yeet OrderCancelError::IncorrectOfferedAsset
+        }
+        if order_state.buy_asset != asset_receive {
+            do yeet OrderCancelError::IncorrectReceiveAsset
+        }
+        if amount_offered_remaining
+            != order_state.sell_amount_remaining.latest().data
+        {
+            do yeet OrderCancelError::IncorrectOfferedAssetAmount
+        }
+        if amount_received != order_state.buy_amount_received.latest().data {
+            do yeet OrderCancelError::IncorrectReceiveAssetAmount
+        }
+        let txid = filled_tx.txid();
+        order_state.sell_amount_remaining.push(0, txid, height);
+        order_state.buy_amount_received.push(0, txid, height);
+        self.orders.put(rwtxn, &order_id, &order_state)?;
+        Ok(())
+    }
+
+    // Revert order cancel
+    fn revert_order_cancel(
+        &self,
+        rwtxn: &mut RwTxn,
+        filled_tx: &FilledTransaction,
+    ) -> Result<(), Error> {
+        let OrderCancel {
+            order_id,
+            asset_offered: _,
+            asset_receive: _,
+            amount_offered_remaining,
+            amount_received,
+        } = filled_tx
+            .order_cancel()
+            .ok_or(OrderCancelError::InvalidTxData)?;
+        let txid = filled_tx.txid();
+        let mut order_state = self
+            .orders
+            .get(rwtxn, &order_id)?
+            .ok_or(Error::RevertOrderCancel)?;
+        assert!(order_state
+            .sell_amount_remaining
+            .pop()
+            .is_some_and(|ts| { ts.txid == txid && ts.data == 0 }));
+        assert_eq!(
+            order_state.sell_amount_remaining.latest().data,
+            amount_offered_remaining
+        );
+        assert!(order_state
+            .buy_amount_received
+            .pop()
+            .is_some_and(|ts| { ts.txid == txid && ts.data == 0 }));
+        assert_eq!(
+            order_state.buy_amount_received.latest().data,
+            amount_received
+        );
+        self.orders.put(rwtxn, &order_id, &order_state)?;
+        Ok(())
+    }
+
     pub fn connect_block(
         &self,
         rwtxn: &mut RwTxn,
         header: &Header,
         body: &Body,
+    ) -> Result<(), Error> {
+        let filled_txs = body
+            .transactions
+            .iter()
+            .map(|tx| self.fill_transaction(rwtxn, tx))
+            .collect::<Result<_, _>>()?;
+        self.connect_filled_block(rwtxn, header, body, filled_txs)
+    }
+
+    /** Connect a block, using the filled txs returned by
+     *  [`Self::validate_block`].
+     *  `filled_txs` MUST be the txs in `body`, filled against the current
+     *  state. Returns an error if a filled tx does not match the
+     *  corresponding tx in `body`, or spends a UTXO that does not exist. */
+    pub fn connect_filled_block(
+        &self,
+        rwtxn: &mut RwTxn,
+        header: &Header,
+        body: &Body,
+        filled_txs: Vec<FilledTransaction>,
     ) -> Result<(), Error> {
         let height = self.get_height(rwtxn)?;
         let tip_hash = self.get_tip(rwtxn)?;
@@ -2652,7 +5020,8 @@ impl State {
                 | OutputContent::BitAsset(_)
                 | OutputContent::BitAssetControl
                 | OutputContent::BitAssetReservation
-                | OutputContent::DutchAuctionReceipt => {
+                | OutputContent::DutchAuctionReceipt
+                | OutputContent::OrderReceipt => {
                     return Err(Error::BadCoinbaseOutputContent);
                 }
             };
@@ -2663,22 +5032,38 @@ impl State {
             };
             self.utxos.put(rwtxn, &outpoint, &filled_output)?;
         }
-        for transaction in &body.transactions {
-            let filled_tx = self.fill_transaction(rwtxn, transaction)?;
+        if filled_txs.len() != body.transactions.len() {
+            return Err(Error::FilledTxsMismatch {
+                expected: body.transactions.len(),
+                received: filled_txs.len(),
+            });
+        }
+        for (tx_idx, (transaction, filled_tx)) in
+            body.transactions.iter().zip(filled_txs).enumerate()
+        {
             let txid = filled_tx.txid();
-            for (vin, input) in filled_tx.inputs().iter().enumerate() {
-                let spent_output = self
-                    .utxos
-                    .get(rwtxn, input)?
-                    .ok_or(Error::NoUtxo { outpoint: *input })?;
+            if txid != transaction.txid() {
+                return Err(Error::FilledTxMismatch {
+                    expected: transaction.txid(),
+                    received: txid,
+                });
+            }
+            for (vin, (input, spent_output)) in filled_tx
+                .inputs()
+                .iter()
+                .zip(filled_tx.spent_utxos.iter())
+                .enumerate()
+            {
                 let spent_output = SpentOutput {
-                    output: spent_output,
+                    output: spent_output.clone(),
                     inpoint: InPoint::Regular {
                         txid,
                         vin: vin as u32,
                     },
                 };
-                self.utxos.delete(rwtxn, input)?;
+                if !self.utxos.delete(rwtxn, input)? {
+                    return Err(Error::NoUtxo { outpoint: *input });
+                }
                 self.stxos.put(rwtxn, input, &spent_output)?;
             }
             let filled_outputs = filled_tx
@@ -2691,16 +5076,23 @@ impl State {
                 };
                 self.utxos.put(rwtxn, &outpoint, filled_output)?;
             }
+            let () = self.put_address_activity(
+                rwtxn,
+                &filled_tx,
+                height,
+                tx_idx as u32,
+            )?;
+            let () = self.put_bitasset_activity(rwtxn, &filled_tx)?;
             match &transaction.data {
                 None => (),
                 Some(TxData::AmmBurn { .. }) => {
-                    self.apply_amm_burn(rwtxn, &filled_tx)?;
+                    self.apply_amm_burn(rwtxn, &filled_tx, height)?;
                 }
                 Some(TxData::AmmMint { .. }) => {
-                    self.apply_amm_mint(rwtxn, &filled_tx)?;
+                    self.apply_amm_mint(rwtxn, &filled_tx, height)?;
                 }
                 Some(TxData::AmmSwap { .. }) => {
-                    self.apply_amm_swap(rwtxn, &filled_tx)?;
+                    self.apply_amm_swap(rwtxn, &filled_tx, height)?;
                 }
                 Some(TxData::BitAssetReservation { commitment }) => {
                     self.bitasset_reservations.put(rwtxn, &txid, commitment)?;
@@ -2753,6 +5145,37 @@ impl State {
                         rwtxn, &filled_tx, height,
                     )?;
                 }
+                Some(TxData::AtomicSwapLock(atomic_swap_params)) => {
+                    let () = self.apply_atomic_swap_lock(
+                        rwtxn,
+                        &filled_tx,
+                        *atomic_swap_params,
+                        height,
+                    )?;
+                }
+                Some(
+                    TxData::AtomicSwapClaim { .. }
+                    | TxData::AtomicSwapRefund { .. },
+                ) => {
+                    let () = self
+                        .apply_atomic_swap_release(rwtxn, &filled_tx, height)?;
+                }
+                Some(TxData::OrderPlace(order_params)) => {
+                    let () = self.apply_order_place(
+                        rwtxn,
+                        &filled_tx,
+                        *order_params,
+                        height,
+                    )?;
+                }
+                Some(TxData::OrderFill { .. }) => {
+                    let () =
+                        self.apply_order_fill(rwtxn, &filled_tx, height)?;
+                }
+                Some(TxData::OrderCancel { .. }) => {
+                    let () =
+                        self.apply_order_cancel(rwtxn, &filled_tx, height)?;
+                }
             }
         }
         let block_hash = header.hash();
@@ -2786,10 +5209,19 @@ impl State {
         let height = self.get_height(rwtxn)?;
         // revert txs, last-to-first
         let mut filled_txs: Vec<FilledTransaction> = Vec::new();
+        let mut tx_idx = body.transactions.len();
         body.transactions.iter().rev().try_for_each(|tx| {
+            tx_idx -= 1;
             let txid = tx.txid();
             let filled_tx =
                 self.fill_transaction_from_stxos(rwtxn, tx.clone())?;
+            let () = self.delete_address_activity(
+                rwtxn,
+                &filled_tx,
+                height - 1,
+                tx_idx as u32,
+            )?;
+            let () = self.delete_bitasset_activity(rwtxn, &filled_tx)?;
             // revert transaction effects
             match &tx.data {
                 None => (),
@@ -2846,6 +5278,25 @@ impl State {
                     let () =
                         self.revert_dutch_auction_create(rwtxn, &filled_tx)?;
                 }
+                Some(TxData::AtomicSwapLock(_atomic_swap_params)) => {
+                    let () = self.revert_atomic_swap_lock(rwtxn, &filled_tx)?;
+                }
+                Some(
+                    TxData::AtomicSwapClaim { .. }
+                    | TxData::AtomicSwapRefund { .. },
+                ) => {
+                    let () =
+                        self.revert_atomic_swap_release(rwtxn, &filled_tx)?;
+                }
+                Some(TxData::OrderPlace(_order_params)) => {
+                    let () = self.revert_order_place(rwtxn, &filled_tx)?;
+                }
+                Some(TxData::OrderFill { .. }) => {
+                    let () = self.revert_order_fill(rwtxn, &filled_tx)?;
+                }
+                Some(TxData::OrderCancel { .. }) => {
+                    let () = self.revert_order_cancel(rwtxn, &filled_tx)?;
+                }
             }
             filled_txs.push(filled_tx);
             // delete UTXOs, last-to-first
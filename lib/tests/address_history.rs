@@ -0,0 +1,263 @@
+//! Check that the address activity index matches a brute-force scan of the
+//! txs in connected blocks, including after a block is disconnected, and
+//! that history from before the index was enabled is not returned.
+
+use std::collections::HashMap;
+
+use plain_bitassets::{
+    state::{self, State},
+    testutil::{self, TestKey, TestState},
+    types::{
+        Address, AuthorizedTransaction, Body, FilledOutputContent, OutPoint,
+        Output, OutputContent, Transaction, Txid,
+    },
+};
+
+const INITIAL_BALANCE: u64 = 10_000;
+
+/** Transfer `amount` of the BitAsset in `input`, which must be owned by
+ *  `sender` and have value `input_value`, with change to the sender.
+ *  The recipient output is at vout 0, and the change output is at vout 1. */
+fn transfer(
+    sender: &TestKey,
+    input: OutPoint,
+    input_value: u64,
+    recipient: Address,
+    amount: u64,
+) -> AuthorizedTransaction {
+    let tx = Transaction::new(
+        vec![input],
+        vec![
+            Output::new(recipient, OutputContent::BitAsset(amount)),
+            Output::new(
+                sender.address,
+                OutputContent::BitAsset(input_value - amount),
+            ),
+        ],
+    );
+    sender.authorize(tx).unwrap()
+}
+
+fn outpoint(tx: &AuthorizedTransaction, vout: u32) -> OutPoint {
+    OutPoint::Regular {
+        txid: tx.transaction.txid(),
+        vout,
+    }
+}
+
+/** Txids of txs that spend from or pay to each address, in the order that
+ *  they were confirmed, by scanning every tx in the connected blocks.
+ *  `owners` must contain the owner of every output spent in the blocks
+ *  that was not created in the blocks. */
+fn scan(
+    mut owners: HashMap<OutPoint, Address>,
+    blocks: &[Body],
+) -> HashMap<Address, Vec<Txid>> {
+    let mut history = HashMap::<Address, Vec<Txid>>::new();
+    for body in blocks {
+        owners.extend(
+            body.get_outputs()
+                .into_iter()
+                .map(|(outpoint, output)| (outpoint, output.address)),
+        );
+        for tx in &body.transactions {
+            let txid = tx.txid();
+            let addresses = tx
+                .inputs
+                .iter()
+                .map(|input| owners[input])
+                .chain(tx.outputs.iter().map(|output| output.address));
+            for address in addresses {
+                let txids = history.entry(address).or_default();
+                if txids.last() != Some(&txid) {
+                    txids.push(txid);
+                }
+            }
+        }
+    }
+    history
+}
+
+fn assert_history_matches_scan(
+    test_state: &TestState,
+    owners: &HashMap<OutPoint, Address>,
+    blocks: &[Body],
+    addresses: &[Address],
+) {
+    let expected = scan(owners.clone(), blocks);
+    let rotxn = test_state.env.read_txn().unwrap();
+    for address in addresses {
+        let history = test_state
+            .state
+            .get_address_history(&rotxn, address, 0)
+            .unwrap();
+        assert_eq!(
+            history,
+            expected.get(address).cloned().unwrap_or_default(),
+            "address history mismatch for {address}"
+        );
+    }
+}
+
+#[test]
+fn address_history_matches_scan() {
+    let key1 = TestKey::from_seed(1);
+    let key2 = TestKey::from_seed(2);
+    let key3 = TestKey::from_seed(3);
+    let addresses = [key1.address, key2.address, key3.address];
+    let genesis =
+        testutil::single_bitasset_genesis(&key1, "asset", INITIAL_BALANCE);
+    let test_state = TestState::with_address_activity_index(
+        "address_history_scan",
+        &genesis,
+    )
+    .unwrap();
+    let owners: HashMap<OutPoint, Address> = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        test_state
+            .state
+            .get_utxos(&rotxn)
+            .unwrap()
+            .into_iter()
+            .map(|(outpoint, output)| (outpoint, output.address))
+            .collect()
+    };
+    let genesis_utxo = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        test_state
+            .state
+            .get_utxos(&rotxn)
+            .unwrap()
+            .into_iter()
+            .find(|(_, output)| {
+                output.address == key1.address
+                    && matches!(
+                        output.content,
+                        FilledOutputContent::BitAsset(..)
+                    )
+            })
+            .unwrap()
+            .0
+    };
+    let mut blocks = Vec::new();
+    // Block 1: key1 pays key2
+    let tx1 =
+        transfer(&key1, genesis_utxo, INITIAL_BALANCE, key2.address, 3_000);
+    let (_, body) = test_state
+        .connect_block(vec![tx1.clone()], Vec::new())
+        .unwrap();
+    blocks.push(body);
+    assert_history_matches_scan(&test_state, &owners, &blocks, &addresses);
+    // Block 2: key2 and key1 both pay key3
+    let tx2 = transfer(&key2, outpoint(&tx1, 0), 3_000, key3.address, 1_000);
+    let tx3 = transfer(
+        &key1,
+        outpoint(&tx1, 1),
+        INITIAL_BALANCE - 3_000,
+        key3.address,
+        2_000,
+    );
+    let (_, body) = test_state
+        .connect_block(vec![tx2.clone(), tx3], Vec::new())
+        .unwrap();
+    blocks.push(body);
+    assert_history_matches_scan(&test_state, &owners, &blocks, &addresses);
+    // Block 3: key3 pays key1, and is then disconnected
+    let tx4 = transfer(&key3, outpoint(&tx2, 0), 1_000, key1.address, 500);
+    let (header, body) =
+        test_state.connect_block(vec![tx4], Vec::new()).unwrap();
+    blocks.push(body);
+    assert_history_matches_scan(&test_state, &owners, &blocks, &addresses);
+    let body = blocks.pop().unwrap();
+    let () = test_state.disconnect_block(&header, &body).unwrap();
+    assert_history_matches_scan(&test_state, &owners, &blocks, &addresses);
+}
+
+#[test]
+fn address_history_disabled() {
+    let key = TestKey::from_seed(1);
+    let genesis =
+        testutil::single_bitasset_genesis(&key, "asset", INITIAL_BALANCE);
+    let test_state =
+        TestState::new("address_history_disabled", &genesis).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    assert!(matches!(
+        test_state
+            .state
+            .get_address_history(&rotxn, &key.address, 0),
+        Err(state::Error::AddressActivityIndexDisabled)
+    ));
+}
+
+#[test]
+fn address_history_start_height() {
+    let key1 = TestKey::from_seed(1);
+    let key2 = TestKey::from_seed(2);
+    let genesis =
+        testutil::single_bitasset_genesis(&key1, "asset", INITIAL_BALANCE);
+    let mut test_state =
+        TestState::new("address_history_start_height", &genesis).unwrap();
+    let genesis_utxo = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        test_state
+            .state
+            .get_utxos(&rotxn)
+            .unwrap()
+            .into_iter()
+            .find(|(_, output)| {
+                matches!(output.content, FilledOutputContent::BitAsset(..))
+            })
+            .unwrap()
+            .0
+    };
+    // Block 0 is connected before the index is enabled
+    let tx0 =
+        transfer(&key1, genesis_utxo, INITIAL_BALANCE, key2.address, 3_000);
+    let _ = test_state
+        .connect_block(vec![tx0.clone()], Vec::new())
+        .unwrap();
+    test_state.state = State::new(&test_state.env, true, &genesis).unwrap();
+    let tx1 = transfer(&key2, outpoint(&tx0, 0), 3_000, key1.address, 1_000);
+    let _ = test_state
+        .connect_block(vec![tx1.clone()], Vec::new())
+        .unwrap();
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        match test_state
+            .state
+            .get_address_history(&rotxn, &key2.address, 0)
+        {
+            Err(state::Error::AddressActivityIndexIncomplete {
+                from_height: 0,
+                start_height: 1,
+            }) => (),
+            res => panic!("expected incomplete history, but got {res:?}"),
+        }
+        let history = test_state
+            .state
+            .get_address_history(&rotxn, &key2.address, 1)
+            .unwrap();
+        assert_eq!(history, vec![tx1.transaction.txid()]);
+    }
+    // Block 2 is connected while the index is disabled. Once re-enabled,
+    // the index starts again from block 3.
+    test_state.state = State::new(&test_state.env, false, &genesis).unwrap();
+    let tx2 = transfer(&key2, outpoint(&tx1, 1), 2_000, key1.address, 500);
+    let _ = test_state.connect_block(vec![tx2], Vec::new()).unwrap();
+    test_state.state = State::new(&test_state.env, true, &genesis).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    assert!(matches!(
+        test_state
+            .state
+            .get_address_history(&rotxn, &key2.address, 1),
+        Err(state::Error::AddressActivityIndexIncomplete {
+            from_height: 1,
+            start_height: 3,
+        })
+    ));
+    let history = test_state
+        .state
+        .get_address_history(&rotxn, &key2.address, 3)
+        .unwrap();
+    assert!(history.is_empty());
+}
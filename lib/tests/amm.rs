@@ -0,0 +1,1002 @@
+//! Check AMM pool state history, fee accounting, price impact limits,
+//! LP token transfers, impermanent loss, implied market caps, and pool
+//! subscriptions.
+
+use std::pin::pin;
+
+use futures::{FutureExt as _, Stream, StreamExt as _};
+use plain_bitassets::{
+    state::{
+        self, AmmPair, AmmPoolState, AmmProtocolFee, Genesis, GenesisBitAsset,
+        TxValidationStage,
+    },
+    testutil::{self, TestKey, TestState, TxBuilder},
+    types::{
+        AmmMint, AssetId, BitAssetData, BitAssetId, BitcoinOutputContent,
+        FilledOutput, FilledOutputContent, OutPoint, OutputContent, TxData,
+        Txid,
+    },
+};
+
+fn bitasset(plain_name: &str) -> AssetId {
+    AssetId::BitAsset(BitAssetId(blake3::hash(plain_name.as_bytes()).into()))
+}
+
+/// Total LP tokens for the pool owned by the key
+fn lp_token_balance(
+    test_state: &TestState,
+    key: &TestKey,
+    amm_pair: AmmPair,
+) -> u64 {
+    let rotxn = test_state.env.read_txn().unwrap();
+    test_state
+        .state
+        .get_utxos(&rotxn)
+        .unwrap()
+        .values()
+        .filter(|output| output.address == key.address)
+        .filter_map(|output| output.lp_token_amount())
+        .filter(|(asset0, asset1, _)| {
+            (*asset0, *asset1) == (amm_pair.asset0(), amm_pair.asset1())
+        })
+        .map(|(_, _, amount)| amount)
+        .sum()
+}
+
+#[test]
+fn amm_pool_state_at_height() {
+    let key = TestKey::from_seed(1);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        ..Default::default()
+    };
+    let test_state =
+        TestState::new("amm_pool_state_at_height", &genesis).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let (asset0, asset1) = (bitasset("asset0"), bitasset("asset1"));
+    let amm_pair = AmmPair::new(asset0, asset1);
+    // Empty block, before the pool is created
+    test_state.connect_txs(Vec::new()).unwrap();
+    let amm_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, asset0, asset1, 10_000, 10_000)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    let state_after_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.amm_pools.get(&rotxn, &amm_pair).unwrap().unwrap()
+    };
+    // Empty block, in which the pool state does not change
+    test_state.connect_txs(Vec::new()).unwrap();
+    let amm_swap = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder.amm_swap(&rotxn, asset0, asset1, 1_000).unwrap()
+    };
+    test_state.connect_txs(vec![amm_swap]).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    let state_after_swap =
+        state.amm_pools.get(&rotxn, &amm_pair).unwrap().unwrap();
+    assert_ne!(state_after_mint, state_after_swap);
+    let state_at_height = |height| {
+        state
+            .try_get_amm_pool_state_at_block_height(&rotxn, amm_pair, height)
+            .unwrap()
+    };
+    // Blocks are connected at heights 0 to 3
+    assert_eq!(state_at_height(0), None);
+    assert_eq!(state_at_height(1), Some(state_after_mint));
+    assert_eq!(state_at_height(2), Some(state_after_mint));
+    assert_eq!(state_at_height(3), Some(state_after_swap));
+    assert_eq!(state_at_height(100), Some(state_after_swap));
+    // Pools that never existed have no state at any height
+    let missing_pair = AmmPair::new(asset0, AssetId::Bitcoin);
+    assert_eq!(
+        state
+            .try_get_amm_pool_state_at_block_height(&rotxn, missing_pair, 3)
+            .unwrap(),
+        None
+    );
+}
+
+/// The pool history records the state at the end of each block in which
+/// the pool changed, and is rolled back when blocks are disconnected
+#[test]
+fn amm_pool_history() {
+    let key = TestKey::from_seed(1);
+    let other_key = TestKey::from_seed(2);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000), (other_key.address, 100_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        ..Default::default()
+    };
+    let test_state = TestState::new("amm_pool_history", &genesis).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let other_builder = TxBuilder::new(state, &other_key);
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    let pool_state = || {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.amm_pools.get(&rotxn, &amm_pair).unwrap().unwrap()
+    };
+    let history = |from_height, to_height| {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state
+            .get_amm_pool_history(&rotxn, amm_pair, from_height, to_height)
+            .unwrap()
+    };
+    // Height 0
+    let amm_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, asset0, asset1, 10_000, 10_000)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    let state_after_mint = pool_state();
+    // Height 1, in which the pool state does not change
+    test_state.connect_txs(Vec::new()).unwrap();
+    // Height 2
+    let amm_swap = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder.amm_swap(&rotxn, asset0, asset1, 1_000).unwrap()
+    };
+    test_state.connect_txs(vec![amm_swap]).unwrap();
+    let state_after_swap = pool_state();
+    // Height 3, with two swaps. The second swap is built against the state
+    // after the first swap, so the first swap is connected alone and then
+    // disconnected.
+    let first_swap = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        other_builder.amm_swap(&rotxn, asset1, asset0, 500).unwrap()
+    };
+    let header = test_state.connect_txs(vec![first_swap.clone()]).unwrap();
+    let second_swap = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder.amm_swap(&rotxn, asset0, asset1, 200).unwrap()
+    };
+    test_state
+        .disconnect_txs(&header, vec![first_swap.clone()])
+        .unwrap();
+    let block_txs = vec![first_swap, second_swap];
+    let header = test_state.connect_txs(block_txs.clone()).unwrap();
+    let state_after_block = pool_state();
+    assert_eq!(
+        history(0, 3),
+        vec![
+            (0, state_after_mint),
+            (2, state_after_swap),
+            (3, state_after_block),
+        ]
+    );
+    assert_eq!(history(1, 2), vec![(2, state_after_swap)]);
+    assert_eq!(history(4, 100), Vec::new());
+    // Pools that never existed have no history
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        let missing_pair = AmmPair::new(asset0, AssetId::Bitcoin);
+        assert_eq!(
+            state
+                .get_amm_pool_history(&rotxn, missing_pair, 0, 100)
+                .unwrap(),
+            Vec::new()
+        );
+    }
+    test_state.disconnect_txs(&header, block_txs).unwrap();
+    assert_eq!(
+        history(0, 100),
+        vec![(0, state_after_mint), (2, state_after_swap)]
+    );
+}
+
+/// Next item from a stream, if one is ready without waiting
+fn next_ready<S>(stream: &mut S) -> Option<S::Item>
+where
+    S: Stream + Unpin,
+{
+    stream.next().now_or_never().flatten()
+}
+
+#[test]
+fn amm_pool_subscription() {
+    let key = TestKey::from_seed(1);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![
+            genesis_bitasset("asset0"),
+            genesis_bitasset("asset1"),
+            genesis_bitasset("asset2"),
+        ],
+        ..Default::default()
+    };
+    let test_state = TestState::new("amm_pool_subscription", &genesis).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let (asset0, asset1, asset2) =
+        (bitasset("asset0"), bitasset("asset1"), bitasset("asset2"));
+    let amm_pair = AmmPair::new(asset0, asset1);
+    let amm_pool_state = || {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.amm_pools.get(&rotxn, &amm_pair).unwrap().unwrap()
+    };
+    // Swap in the other pool, which does not affect the subscribed pool
+    let other_pool_swap = || {
+        let amm_swap = {
+            let rotxn = test_state.env.read_txn().unwrap();
+            builder.amm_swap(&rotxn, asset0, asset2, 100).unwrap()
+        };
+        test_state.connect_txs(vec![amm_swap]).unwrap();
+    };
+    let other_pool_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, asset0, asset2, 10_000, 10_000)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![other_pool_mint]).unwrap();
+    let mut amm_pool_states =
+        pin!(state.watch_amm_pool(&test_state.env, amm_pair).unwrap());
+    // The subscribed pool does not exist yet
+    assert!(next_ready(&mut amm_pool_states).is_none());
+    other_pool_swap();
+    assert!(next_ready(&mut amm_pool_states).is_none());
+    let amm_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, asset0, asset1, 10_000, 10_000)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    assert_eq!(
+        next_ready(&mut amm_pool_states).unwrap().unwrap(),
+        amm_pool_state()
+    );
+    other_pool_swap();
+    assert!(next_ready(&mut amm_pool_states).is_none());
+    let amm_swap = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder.amm_swap(&rotxn, asset0, asset1, 1_000).unwrap()
+    };
+    test_state.connect_txs(vec![amm_swap]).unwrap();
+    assert_eq!(
+        next_ready(&mut amm_pool_states).unwrap().unwrap(),
+        amm_pool_state()
+    );
+    // Blocks that do not change any pool are skipped
+    test_state.connect_txs(Vec::new()).unwrap();
+    assert!(next_ready(&mut amm_pool_states).is_none());
+}
+
+#[test]
+fn amm_cumulative_fees() {
+    let key = TestKey::from_seed(1);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        ..Default::default()
+    };
+    let test_state = TestState::new("amm_cumulative_fees", &genesis).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    let amm_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, asset0, asset1, 10_000, 10_000)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    let pool_state = |test_state: &TestState| {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.amm_pools.get(&rotxn, &amm_pair).unwrap().unwrap()
+    };
+    let state_after_mint = pool_state(&test_state);
+    assert_eq!(state_after_mint.cumulative_fees0, 0);
+    assert_eq!(state_after_mint.cumulative_fees1, 0);
+    let amm_swap = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder.amm_swap(&rotxn, asset0, asset1, 1_000).unwrap()
+    };
+    test_state.connect_txs(vec![amm_swap]).unwrap();
+    // 1000 spent, of which 997 count towards the swap price, and 906
+    // received. The fee is only charged on the amount spent.
+    let state_after_swap = pool_state(&test_state);
+    assert_eq!(state_after_swap.reserve0, 11_000);
+    assert_eq!(state_after_swap.reserve1, 10_000 - 906);
+    assert_eq!(state_after_swap.cumulative_fees0, 3);
+    assert_eq!(state_after_swap.cumulative_fees1, 0);
+    let expected = state_after_swap.swap_asset1_for_asset0(500).unwrap();
+    let amm_swap = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder.amm_swap(&rotxn, asset1, asset0, 500).unwrap()
+    };
+    test_state.connect_txs(vec![amm_swap]).unwrap();
+    let state_after_second_swap = pool_state(&test_state);
+    assert_eq!(state_after_second_swap, expected);
+    // 500 spent, of which 498 count towards the swap price
+    assert_eq!(state_after_second_swap.cumulative_fees0, 3);
+    assert_eq!(state_after_second_swap.cumulative_fees1, 2);
+}
+
+/// The LP tokens claimed by a mint must equal the LP tokens minted, both
+/// when creating a pool and when adding to an existing pool
+#[test]
+fn amm_mint_lp_token_amount() {
+    let key = TestKey::from_seed(1);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        ..Default::default()
+    };
+    let test_state =
+        TestState::new("amm_mint_lp_token_amount", &genesis).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    let amm_mint = |amount0, amount1| {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, asset0, asset1, amount0, amount1)
+            .unwrap()
+    };
+    let outstanding_lp_tokens = || {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state
+            .amm_pools
+            .get(&rotxn, &amm_pair)
+            .unwrap()
+            .map(|pool_state| pool_state.outstanding_lp_tokens)
+    };
+    // Creating a pool mints the geometric mean of the amounts
+    test_state
+        .connect_txs(vec![amm_mint(10_000, 10_000)])
+        .unwrap();
+    assert_eq!(outstanding_lp_tokens(), Some(10_000));
+    // Claim half of the LP tokens that would be outstanding after the mint,
+    // rather than the LP tokens minted
+    let honest_mint = amm_mint(5_000, 5_000);
+    let mut tx = honest_mint.transaction.clone();
+    let Some(TxData::AmmMint { lp_token_mint, .. }) = &mut tx.data else {
+        panic!("expected AMM mint")
+    };
+    assert_eq!(*lp_token_mint, 5_000);
+    *lp_token_mint = 7_500;
+    // The LP tokens are the last output
+    tx.outputs.last_mut().unwrap().content = OutputContent::AmmLpToken(7_500);
+    let tx = key.authorize(tx).unwrap();
+    match test_state.connect_txs(vec![tx]) {
+        Err(testutil::Error::State(state::Error::InvalidAmmMint)) => (),
+        res => panic!("expected invalid AMM mint, but got {res:?}"),
+    }
+    assert_eq!(outstanding_lp_tokens(), Some(10_000));
+    test_state.connect_txs(vec![honest_mint]).unwrap();
+    assert_eq!(outstanding_lp_tokens(), Some(15_000));
+}
+
+/// A mint that spends the assets in the reverse of the canonical order for
+/// the pool deposits each amount to the reserve for the corresponding asset
+#[test]
+fn amm_mint_non_canonical_order() {
+    const AMOUNT0: u64 = 1_000;
+    const AMOUNT1: u64 = 3_000;
+    // Geometric mean of the amounts, rounded down
+    const LP_TOKEN_MINT: u64 = 1_732;
+    let key = TestKey::from_seed(1);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        ..Default::default()
+    };
+    let test_state =
+        TestState::new("amm_mint_non_canonical_order", &genesis).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    // Spend asset 1 first
+    let amm_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, asset1, asset0, AMOUNT1, AMOUNT0)
+            .unwrap()
+    };
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        let filled_tx = state
+            .fill_transaction(&rotxn, &amm_mint.transaction)
+            .unwrap();
+        assert_eq!(
+            filled_tx.amm_mint(),
+            Some(AmmMint {
+                asset0,
+                asset1,
+                amount0: AMOUNT0,
+                amount1: AMOUNT1,
+                lp_token_mint: LP_TOKEN_MINT,
+            })
+        );
+    }
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    let pool_state = state.amm_pools.get(&rotxn, &amm_pair).unwrap().unwrap();
+    assert_eq!(pool_state.reserve0, AMOUNT0);
+    assert_eq!(pool_state.reserve1, AMOUNT1);
+    assert_eq!(pool_state.outstanding_lp_tokens, LP_TOKEN_MINT);
+    drop(rotxn);
+    assert_eq!(lp_token_balance(&test_state, &key, amm_pair), LP_TOKEN_MINT);
+}
+
+/// A hand-crafted swap that receives more than the pool gives, and so
+/// would decrease the product of the pool reserves, is rejected
+#[test]
+fn amm_swap_invariant() {
+    let key = TestKey::from_seed(1);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        ..Default::default()
+    };
+    let test_state = TestState::new("amm_swap_invariant", &genesis).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    let amm_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, asset0, asset1, 10_000, 10_000)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    let pool_state = || {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.amm_pools.get(&rotxn, &amm_pair).unwrap().unwrap()
+    };
+    let state_before = pool_state();
+    let amm_swap = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder.amm_swap(&rotxn, asset0, asset1, 1_000).unwrap()
+    };
+    // Receive more than the pool gives, by enough to decrease the product
+    // of the reserves, despite the swap fee retained by the pool
+    let mut tx = amm_swap.transaction.clone();
+    let Some(TxData::AmmSwap { amount_receive, .. }) = &mut tx.data else {
+        panic!("expected AMM swap")
+    };
+    *amount_receive += 10;
+    let amount_receive = *amount_receive;
+    // The received asset is the last output
+    tx.outputs.last_mut().unwrap().content =
+        OutputContent::BitAsset(amount_receive);
+    let new_reserve1 = state_before.reserve1 - amount_receive;
+    assert!(
+        (state_before.reserve0 + 1_000) as u128 * new_reserve1 as u128
+            < state_before.reserve0 as u128 * state_before.reserve1 as u128
+    );
+    let tx = key.authorize(tx).unwrap();
+    match test_state.connect_txs(vec![tx]) {
+        Err(testutil::Error::State(
+            state::Error::InvalidAmmSwap | state::Error::AmmPoolInvariant,
+        )) => (),
+        res => panic!("expected invalid AMM swap, but got {res:?}"),
+    }
+    assert_eq!(pool_state(), state_before);
+    // The swap built against the pool state is valid, and does not decrease
+    // the product of the reserves
+    test_state.connect_txs(vec![amm_swap]).unwrap();
+    let state_after = pool_state();
+    assert!(
+        state_after.reserve0 as u128 * state_after.reserve1 as u128
+            >= state_before.reserve0 as u128 * state_before.reserve1 as u128
+    );
+}
+
+/// Amount of the spent asset that counts towards the swap price
+fn amount_after_fee(amount_spend: u64) -> u64 {
+    amount_spend
+        * (AmmPoolState::SWAP_FEE_DENOMINATOR
+            - AmmPoolState::SWAP_FEE_NUMERATOR)
+        / AmmPoolState::SWAP_FEE_DENOMINATOR
+}
+
+/// Swapping an amount, and then swapping the amount received back, loses
+/// only the fee charged once on each leg, and up to one unit per leg to
+/// rounding. The pool is deep enough that price impact is negligible.
+#[test]
+fn amm_swap_round_trip() {
+    const RESERVE: u64 = 1_000_000_000_000_000;
+    const AMOUNT_SPEND: u64 = 1_000_000;
+    let key = TestKey::from_seed(1);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, RESERVE)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        ..Default::default()
+    };
+    let test_state = TestState::new("amm_swap_round_trip", &genesis).unwrap();
+    let state = &test_state.state;
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let amm_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        TxBuilder::new(state, &key)
+            .amm_mint(
+                &rotxn,
+                amm_pair.asset0(),
+                amm_pair.asset1(),
+                RESERVE,
+                RESERVE,
+            )
+            .unwrap()
+    };
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    let pool = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.amm_pools.get(&rotxn, &amm_pair).unwrap().unwrap()
+    };
+    let after_swap0 = pool.swap_asset0_for_asset1(AMOUNT_SPEND).unwrap();
+    let amount_receive1 = pool.reserve1 - after_swap0.reserve1;
+    assert!(amount_receive1.abs_diff(amount_after_fee(AMOUNT_SPEND)) <= 1);
+    // Both swap directions are symmetric
+    let swap1_for_0 = pool.swap_asset1_for_asset0(AMOUNT_SPEND).unwrap();
+    assert_eq!(pool.reserve0 - swap1_for_0.reserve0, amount_receive1);
+    assert_eq!(swap1_for_0.cumulative_fees1, after_swap0.cumulative_fees0);
+    // Swap the amount received back
+    let after_swap1 =
+        after_swap0.swap_asset1_for_asset0(amount_receive1).unwrap();
+    let amount_receive0 = after_swap0.reserve0 - after_swap1.reserve0;
+    assert!(
+        amount_receive0
+            .abs_diff(amount_after_fee(amount_after_fee(AMOUNT_SPEND)))
+            <= 2
+    );
+    // The fees charged on each leg remain in the pool
+    assert_eq!(
+        after_swap1.cumulative_fees0,
+        AMOUNT_SPEND - amount_after_fee(AMOUNT_SPEND)
+    );
+    assert_eq!(
+        after_swap1.cumulative_fees1,
+        amount_receive1 - amount_after_fee(amount_receive1)
+    );
+    assert_eq!(
+        after_swap1.reserve0,
+        pool.reserve0 + AMOUNT_SPEND - amount_receive0
+    );
+    assert_eq!(after_swap1.reserve1, pool.reserve1);
+}
+
+#[test]
+fn price_impact_exceeds() {
+    // 1000 spent against equal reserves of 10_000, receiving 906, is a
+    // price impact of exactly 940 basis points
+    let exceeds = |max_price_impact_bps| {
+        AmmPoolState::price_impact_exceeds(
+            10_000,
+            10_000,
+            1_000,
+            906,
+            max_price_impact_bps,
+        )
+    };
+    assert!(exceeds(0));
+    assert!(exceeds(939));
+    assert!(!exceeds(940));
+    assert!(!exceeds(AmmPoolState::MAX_PRICE_IMPACT_BPS));
+    // No price impact, other than the swap fee
+    assert!(!AmmPoolState::price_impact_exceeds(
+        u64::MAX,
+        u64::MAX,
+        1_000,
+        997,
+        30
+    ));
+}
+
+#[test]
+fn amm_max_price_impact() {
+    let key = TestKey::from_seed(1);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000)],
+    };
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    // A swap with a price impact of 940 basis points is only valid on
+    // chains with a max price impact of at least 940 basis points
+    for max_price_impact_bps in [939, 940] {
+        let genesis = Genesis {
+            bitassets: vec![
+                genesis_bitasset("asset0"),
+                genesis_bitasset("asset1"),
+            ],
+            amm_max_price_impact_bps: Some(max_price_impact_bps),
+            ..Default::default()
+        };
+        let test_state = TestState::new(
+            &format!("amm_max_price_impact_{max_price_impact_bps}"),
+            &genesis,
+        )
+        .unwrap();
+        let state = &test_state.state;
+        let builder = TxBuilder::new(state, &key);
+        let amm_mint = {
+            let rotxn = test_state.env.read_txn().unwrap();
+            builder
+                .amm_mint(&rotxn, asset0, asset1, 10_000, 10_000)
+                .unwrap()
+        };
+        test_state.connect_txs(vec![amm_mint]).unwrap();
+        let amm_swap = {
+            let rotxn = test_state.env.read_txn().unwrap();
+            builder.amm_swap(&rotxn, asset0, asset1, 1_000).unwrap()
+        };
+        let res = test_state.connect_txs(vec![amm_swap]);
+        if max_price_impact_bps < 940 {
+            match res {
+                Err(testutil::Error::State(
+                    state::Error::AmmPriceImpactExceeded {
+                        max_price_impact_bps: 939,
+                    },
+                )) => (),
+                res => {
+                    panic!("expected price impact exceeded, but got {res:?}")
+                }
+            }
+        } else {
+            res.unwrap();
+            let rotxn = test_state.env.read_txn().unwrap();
+            let pool_state = state.amm_pools.get(&rotxn, &amm_pair).unwrap();
+            assert_eq!(pool_state.unwrap().reserve1, 10_000 - 906);
+        }
+    }
+}
+
+/// Swaps must pay the protocol fee set in the genesis config, if it is
+/// nonzero
+#[test]
+fn amm_protocol_fee() {
+    const AMOUNT_SPEND: u64 = 1_000;
+    let key = TestKey::from_seed(1);
+    let fee_key = TestKey::from_seed(2);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000)],
+    };
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    for fee_bps in [0, 100] {
+        let genesis = Genesis {
+            bitassets: vec![
+                genesis_bitasset("asset0"),
+                genesis_bitasset("asset1"),
+            ],
+            amm_protocol_fee: AmmProtocolFee {
+                fee_bps,
+                address: fee_key.address,
+            },
+            ..Default::default()
+        };
+        let test_state =
+            TestState::new(&format!("amm_protocol_fee_{fee_bps}"), &genesis)
+                .unwrap();
+        let state = &test_state.state;
+        let builder = TxBuilder::new(state, &key);
+        let amm_mint = {
+            let rotxn = test_state.env.read_txn().unwrap();
+            builder
+                .amm_mint(&rotxn, asset0, asset1, 10_000, 10_000)
+                .unwrap()
+        };
+        test_state.connect_txs(vec![amm_mint]).unwrap();
+        let amm_swap = {
+            let rotxn = test_state.env.read_txn().unwrap();
+            builder
+                .amm_swap(&rotxn, asset0, asset1, AMOUNT_SPEND)
+                .unwrap()
+        };
+        let protocol_fee = AMOUNT_SPEND * fee_bps as u64 / 10_000;
+        if protocol_fee != 0 {
+            // A swap that does not pay the protocol fee is invalid
+            let mut unpaid = amm_swap.transaction.clone();
+            unpaid
+                .outputs
+                .retain(|output| output.address != fee_key.address);
+            let unpaid = key.authorize(unpaid).unwrap();
+            match test_state.connect_txs(vec![unpaid]) {
+                Err(testutil::Error::State(
+                    state::Error::InsufficientAmmProtocolFee { address, fee },
+                )) if address == fee_key.address && fee == protocol_fee => (),
+                res => panic!("expected insufficient fee, but got {res:?}"),
+            }
+        }
+        test_state.connect_txs(vec![amm_swap]).unwrap();
+        let rotxn = test_state.env.read_txn().unwrap();
+        let fee_paid = test_state.balance(&rotxn, &fee_key, asset0).unwrap();
+        assert_eq!(fee_paid, protocol_fee);
+        // The protocol fee is not added to the pool reserves
+        let pool_state = state.amm_pools.get(&rotxn, &amm_pair).unwrap();
+        assert_eq!(pool_state.unwrap().reserve0, 10_000 + AMOUNT_SPEND);
+    }
+}
+
+/// If AMM pool creators are set in the genesis config, only they can create
+/// new pools, but anyone can provide liquidity to existing pools
+#[test]
+fn amm_pool_creators() {
+    let creator = TestKey::from_seed(1);
+    let other = TestKey::from_seed(2);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: creator.address,
+        balances: vec![(creator.address, 100_000), (other.address, 100_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        amm_pool_creators: vec![creator.address],
+        ..Default::default()
+    };
+    let test_state = TestState::new("amm_pool_creators", &genesis).unwrap();
+    let state = &test_state.state;
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    let amm_mint = |key: &TestKey| {
+        let rotxn = test_state.env.read_txn().unwrap();
+        TxBuilder::new(state, key)
+            .amm_mint(&rotxn, asset0, asset1, 10_000, 10_000)
+            .unwrap()
+    };
+    // Denied, since the pool does not exist
+    match test_state.connect_txs(vec![amm_mint(&other)]) {
+        Err(testutil::Error::State(
+            state::Error::AmmPoolCreationNotPermitted { .. },
+        )) => (),
+        res => panic!("expected pool creation denied, but got {res:?}"),
+    }
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        assert!(state.amm_pools.get(&rotxn, &amm_pair).unwrap().is_none());
+    }
+    // Allowed
+    test_state.connect_txs(vec![amm_mint(&creator)]).unwrap();
+    // Once the pool exists, anyone can provide liquidity
+    test_state.connect_txs(vec![amm_mint(&other)]).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    let pool_state = state.amm_pools.get(&rotxn, &amm_pair).unwrap();
+    assert_eq!(pool_state.unwrap().reserve0, 20_000);
+}
+
+/// Part of an LP position can be transferred, with change returned to the
+/// sender, but LP tokens can not be created by a transfer
+#[test]
+fn amm_lp_token_partial_transfer() {
+    let key = TestKey::from_seed(1);
+    let recipient = TestKey::from_seed(2);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        ..Default::default()
+    };
+    let test_state =
+        TestState::new("amm_lp_token_partial_transfer", &genesis).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    let amm_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, asset0, asset1, 10_000, 10_000)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    let lp_tokens = lp_token_balance(&test_state, &key, amm_pair);
+    assert_ne!(lp_tokens, 0);
+    let transfer_amount = lp_tokens / 3;
+    let transfer = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_lp_token_transfer(
+                &rotxn,
+                amm_pair,
+                recipient.address,
+                transfer_amount,
+            )
+            .unwrap()
+    };
+    // A transfer that outputs more LP tokens than it spends is invalid
+    let mut inflated = transfer.transaction.clone();
+    inflated.outputs[0].content = OutputContent::AmmLpToken(lp_tokens);
+    let inflated = key.authorize(inflated).unwrap();
+    match test_state.connect_txs(vec![inflated]) {
+        Err(testutil::Error::State(state::Error::TxValidation {
+            stage: TxValidationStage::LpTokens,
+            source,
+        })) if matches!(*source, state::Error::LpTokensNotConserved) => (),
+        res => panic!("expected LP tokens not conserved, but got {res:?}"),
+    }
+    test_state.connect_txs(vec![transfer]).unwrap();
+    assert_eq!(
+        lp_token_balance(&test_state, &key, amm_pair),
+        lp_tokens - transfer_amount
+    );
+    assert_eq!(
+        lp_token_balance(&test_state, &recipient, amm_pair),
+        transfer_amount
+    );
+    // Transfers do not change the pool state
+    let rotxn = test_state.env.read_txn().unwrap();
+    let pool_state = state.amm_pools.get(&rotxn, &amm_pair).unwrap().unwrap();
+    assert_eq!(pool_state.outstanding_lp_tokens, lp_tokens);
+}
+
+/// Impermanent loss for known changes in the ratio of the reserves
+#[test]
+fn amm_impermanent_loss() {
+    let key = TestKey::from_seed(1);
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        ..Default::default()
+    };
+    let test_state = TestState::new("amm_impermanent_loss", &genesis).unwrap();
+    let state = &test_state.state;
+    let amm_pair = AmmPair::new(bitasset("asset0"), bitasset("asset1"));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    let amm_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        TxBuilder::new(state, &key)
+            .amm_mint(&rotxn, asset0, asset1, 10_000, 10_000)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    let pool_state = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.amm_pools.get(&rotxn, &amm_pair).unwrap().unwrap()
+    };
+    // No loss if the price has not changed
+    assert_eq!(pool_state.impermanent_loss(10_000, 10_000), Some(0.));
+    assert_eq!(pool_state.impermanent_loss(1_000, 1_000), Some(0.));
+    // If the price changes by a factor of 4 in either direction, then the
+    // loss is `2 * sqrt(4) / (1 + 4) - 1 = -0.2`
+    let impermanent_loss = |reserve0, reserve1| {
+        let mut pool_state = pool_state;
+        pool_state.reserve0 = reserve0;
+        pool_state.reserve1 = reserve1;
+        pool_state.impermanent_loss(10_000, 10_000).unwrap()
+    };
+    assert!((impermanent_loss(5_000, 20_000) + 0.2).abs() < 1e-12);
+    assert!((impermanent_loss(20_000, 5_000) + 0.2).abs() < 1e-12);
+    // A factor of 9 gives `2 * 3 / 10 - 1 = -0.4`
+    assert!((impermanent_loss(10_000, 90_000) + 0.4).abs() < 1e-12);
+    // Undefined for empty reserves
+    assert_eq!(pool_state.impermanent_loss(0, 10_000), None);
+}
+
+#[test]
+fn bitasset_market_cap() {
+    let key = TestKey::from_seed(1);
+    let genesis = Genesis {
+        bitassets: vec![GenesisBitAsset {
+            plain_name: "asset0".to_owned(),
+            bitasset_data: BitAssetData::default(),
+            control_address: key.address,
+            balances: vec![(key.address, 100_000), (key.address, 50_000)],
+        }],
+        ..Default::default()
+    };
+    let test_state = TestState::new("bitasset_market_cap", &genesis).unwrap();
+    let state = &test_state.state;
+    let bitasset_id = genesis.bitassets[0].bitasset_id();
+    let market_cap = |bitasset_id| {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state
+            .try_get_bitasset_market_cap(&rotxn, &bitasset_id)
+            .unwrap()
+    };
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        assert_eq!(
+            state
+                .try_get_bitasset_total_supply(&rotxn, &bitasset_id)
+                .unwrap(),
+            Some(150_000)
+        );
+    }
+    // No BitAsset-Bitcoin pool
+    assert_eq!(market_cap(bitasset_id), None);
+    // Pool with 3 sats per unit of the BitAsset
+    {
+        let output = FilledOutput::new(
+            key.address,
+            FilledOutputContent::Bitcoin(BitcoinOutputContent(30_000)),
+        );
+        let outpoint = OutPoint::Regular {
+            txid: Txid([1; 32]),
+            vout: 0,
+        };
+        let mut rwtxn = test_state.env.write_txn().unwrap();
+        state.utxos.put(&mut rwtxn, &outpoint, &output).unwrap();
+        rwtxn.commit().unwrap();
+    }
+    let amm_pair =
+        AmmPair::new(AssetId::Bitcoin, AssetId::BitAsset(bitasset_id));
+    let (asset0, asset1) = (amm_pair.asset0(), amm_pair.asset1());
+    let amount = |asset| {
+        if asset == AssetId::Bitcoin {
+            30_000
+        } else {
+            10_000
+        }
+    };
+    let amm_mint = {
+        let builder = TxBuilder::new(state, &key);
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, asset0, asset1, amount(asset0), amount(asset1))
+            .unwrap()
+    };
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    assert_eq!(market_cap(bitasset_id), Some(450_000));
+    // BitAssets that do not exist have no market cap
+    assert_eq!(market_cap(BitAssetId([0; 32])), None);
+}
@@ -0,0 +1,197 @@
+//! Check that assets locked in atomic swaps can be claimed by revealing the
+//! preimage before the timeout, and refunded after the timeout.
+
+use plain_bitassets::{
+    state::{self, AtomicSwapReleaseError, AtomicSwapStatus},
+    testutil::{self, TestKey, TestState, TxBuilder},
+    types::{AssetId, AtomicSwapId, AtomicSwapParams, BitAssetId, Hash},
+};
+
+const INITIAL_BALANCE: u64 = 100_000;
+
+const LOCKED_AMOUNT: u64 = 1_000;
+
+const PREIMAGE: Hash = [0x42; 32];
+
+fn asset() -> AssetId {
+    AssetId::BitAsset(BitAssetId::from_plain_name("asset"))
+}
+
+/** Lock the asset owned by the sender in a swap to the recipient, which
+ *  times out `timeout_blocks` blocks after the lock is confirmed.
+ *  Returns the swap ID. */
+fn lock(
+    test_state: &TestState,
+    sender: &TestKey,
+    recipient: &TestKey,
+    timeout_blocks: u32,
+) -> AtomicSwapId {
+    let lock = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        let height = test_state.state.get_height(&rotxn).unwrap();
+        let params = AtomicSwapParams {
+            asset: asset(),
+            amount: LOCKED_AMOUNT,
+            recipient: recipient.address,
+            refund_address: sender.address,
+            hash_lock: blake3::hash(&PREIMAGE).into(),
+            timeout: height + timeout_blocks,
+        };
+        TxBuilder::new(&test_state.state, sender)
+            .atomic_swap_lock(&rotxn, params)
+            .unwrap()
+    };
+    let swap_id = AtomicSwapId(lock.transaction.txid());
+    test_state.connect_txs(vec![lock]).unwrap();
+    swap_id
+}
+
+fn swap_status(
+    test_state: &TestState,
+    swap_id: AtomicSwapId,
+) -> AtomicSwapStatus {
+    let rotxn = test_state.env.read_txn().unwrap();
+    test_state
+        .state
+        .atomic_swaps
+        .get(&rotxn, &swap_id)
+        .unwrap()
+        .unwrap()
+        .status
+        .latest()
+        .data
+}
+
+fn assert_release_error(
+    res: Result<impl std::fmt::Debug, testutil::Error>,
+    expected: fn(&AtomicSwapReleaseError) -> bool,
+) {
+    match res {
+        Err(testutil::Error::State(state::Error::AtomicSwapRelease(err)))
+            if expected(&err) => {}
+        res => panic!("unexpected result: {res:?}"),
+    }
+}
+
+#[test]
+fn atomic_swap_claim() {
+    let sender = TestKey::from_seed(1);
+    let recipient = TestKey::from_seed(2);
+    let test_state = TestState::new(
+        "atomic_swap_claim",
+        &testutil::single_bitasset_genesis(&sender, "asset", INITIAL_BALANCE),
+    )
+    .unwrap();
+    let state = &test_state.state;
+    let swap_id = lock(&test_state, &sender, &recipient, 10);
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        assert_eq!(
+            test_state.balance(&rotxn, &sender, asset()).unwrap(),
+            INITIAL_BALANCE - LOCKED_AMOUNT
+        );
+    }
+    assert_eq!(swap_status(&test_state, swap_id), AtomicSwapStatus::Locked);
+    let recipient_builder = TxBuilder::new(state, &recipient);
+    // Claiming with the wrong preimage fails
+    let bad_claim = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        recipient_builder
+            .atomic_swap_claim(&rotxn, swap_id, [0x43; 32])
+            .unwrap()
+    };
+    assert_release_error(test_state.connect_txs(vec![bad_claim]), |err| {
+        matches!(err, AtomicSwapReleaseError::InvalidPreimage)
+    });
+    // Refunding before the timeout fails
+    let refund = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        TxBuilder::new(state, &sender)
+            .atomic_swap_refund(&rotxn, swap_id)
+            .unwrap()
+    };
+    assert_release_error(test_state.connect_txs(vec![refund]), |err| {
+        matches!(err, AtomicSwapReleaseError::NotExpired)
+    });
+    let claim = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        recipient_builder
+            .atomic_swap_claim(&rotxn, swap_id, PREIMAGE)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![claim.clone()]).unwrap();
+    assert_eq!(
+        swap_status(&test_state, swap_id),
+        AtomicSwapStatus::Claimed { preimage: PREIMAGE }
+    );
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        assert_eq!(
+            test_state.balance(&rotxn, &recipient, asset()).unwrap(),
+            LOCKED_AMOUNT
+        );
+        assert_eq!(
+            test_state.balance(&rotxn, &sender, asset()).unwrap(),
+            INITIAL_BALANCE - LOCKED_AMOUNT
+        );
+    }
+    // The swap can only be claimed once
+    assert_release_error(test_state.connect_txs(vec![claim]), |err| {
+        matches!(err, AtomicSwapReleaseError::NotLocked)
+    });
+}
+
+#[test]
+fn atomic_swap_refund_after_timeout() {
+    let sender = TestKey::from_seed(1);
+    let recipient = TestKey::from_seed(2);
+    let test_state = TestState::new(
+        "atomic_swap_refund",
+        &testutil::single_bitasset_genesis(&sender, "asset", INITIAL_BALANCE),
+    )
+    .unwrap();
+    let state = &test_state.state;
+    let swap_id = lock(&test_state, &sender, &recipient, 3);
+    let timeout = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state
+            .atomic_swaps
+            .get(&rotxn, &swap_id)
+            .unwrap()
+            .unwrap()
+            .timeout
+    };
+    while {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.get_height(&rotxn).unwrap() < timeout
+    } {
+        test_state.connect_txs(Vec::new()).unwrap();
+    }
+    // The swap can no longer be claimed once it has timed out
+    let claim = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        TxBuilder::new(state, &recipient)
+            .atomic_swap_claim(&rotxn, swap_id, PREIMAGE)
+            .unwrap()
+    };
+    assert_release_error(test_state.connect_txs(vec![claim]), |err| {
+        matches!(err, AtomicSwapReleaseError::Expired)
+    });
+    let refund = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        TxBuilder::new(state, &sender)
+            .atomic_swap_refund(&rotxn, swap_id)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![refund]).unwrap();
+    assert_eq!(
+        swap_status(&test_state, swap_id),
+        AtomicSwapStatus::Refunded
+    );
+    let rotxn = test_state.env.read_txn().unwrap();
+    assert_eq!(
+        test_state.balance(&rotxn, &sender, asset()).unwrap(),
+        INITIAL_BALANCE
+    );
+    assert_eq!(test_state.balance(&rotxn, &recipient, asset()).unwrap(), 0);
+}
@@ -0,0 +1,54 @@
+//! Check that block authorizations are verified in batches, and that an
+//! invalid signature is identified if batch verification fails.
+
+use plain_bitassets::{
+    authorization::{self, get_address, Authorization, Error, SigningKey},
+    types::{
+        AuthorizedTransaction, Body, OutPoint, Transaction, Txid, Verify as _,
+    },
+};
+
+const NUM_TXS: u8 = 64;
+
+/// Body with one single-input tx for each signing key
+fn body() -> Body {
+    let txs = (0..NUM_TXS)
+        .map(|idx| {
+            let signing_key = SigningKey::from_bytes(&[idx + 1; 32]);
+            let address = get_address(&signing_key.verifying_key());
+            let tx = Transaction::new(
+                vec![OutPoint::Regular {
+                    txid: Txid([idx; 32]),
+                    vout: 0,
+                }],
+                Vec::new(),
+            );
+            authorization::authorize(&[(address, &signing_key)], tx).unwrap()
+        })
+        .collect::<Vec<AuthorizedTransaction>>();
+    Body::new(txs, Vec::new())
+}
+
+#[test]
+fn valid_signatures() {
+    let body = body();
+    assert_eq!(body.authorizations.len(), NUM_TXS as usize);
+    let () = Authorization::verify_body(&body).unwrap();
+}
+
+#[test]
+fn one_invalid_signature() {
+    const INVALID_IDX: usize = 37;
+    let mut body = body();
+    // Use a signature for a different tx, by the same key
+    let signing_key = SigningKey::from_bytes(&[INVALID_IDX as u8 + 1; 32]);
+    let other_tx = &body.transactions[INVALID_IDX + 1];
+    body.authorizations[INVALID_IDX].signature =
+        authorization::sign(&signing_key, other_tx).unwrap();
+    match Authorization::verify_body(&body) {
+        Err(Error::InvalidSignature { index, source: _ }) => {
+            assert_eq!(index, INVALID_IDX)
+        }
+        res => panic!("expected invalid signature error, but got {res:?}"),
+    }
+}
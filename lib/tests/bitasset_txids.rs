@@ -0,0 +1,80 @@
+//! Check that the BitAsset activity index records the txs that register,
+//! mint, and update a BitAsset, in order, and only for that BitAsset.
+
+use plain_bitassets::{
+    testutil::{self, TestKey, TestState, TxBuilder},
+    types::{
+        AuthorizedTransaction, BitAssetData, BitAssetDataUpdates, BitAssetId,
+        Txid, Update,
+    },
+};
+
+fn bitasset_txids(test_state: &TestState, bitasset: BitAssetId) -> Vec<Txid> {
+    let rotxn = test_state.env.read_txn().unwrap();
+    test_state
+        .state
+        .get_bitasset_txids(&rotxn, &bitasset)
+        .unwrap()
+}
+
+#[test]
+fn bitasset_txids_register_mint_update() {
+    let key = TestKey::from_seed(1);
+    let genesis = testutil::single_bitasset_genesis(&key, "other", 1_000);
+    let test_state = TestState::new("bitasset_txids", &genesis).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let bitasset = BitAssetId::from_plain_name("newtoken");
+    let other = BitAssetId::from_plain_name("other");
+    // Reservations do not refer to a BitAsset ID
+    let reservation = builder.bitasset_reservation("newtoken").unwrap();
+    test_state.connect_txs(vec![reservation]).unwrap();
+    assert_eq!(bitasset_txids(&test_state, bitasset), Vec::new());
+    let registration = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .bitasset_registration(
+                &rotxn,
+                "newtoken",
+                BitAssetData::default(),
+                1_000,
+            )
+            .unwrap()
+    };
+    let registration_txid = registration.transaction.txid();
+    test_state.connect_txs(vec![registration]).unwrap();
+    let mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder.bitasset_mint(&rotxn, bitasset, 500).unwrap()
+    };
+    let mint_txid = mint.transaction.txid();
+    test_state.connect_txs(vec![mint]).unwrap();
+    let update = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        let updates = BitAssetDataUpdates {
+            commitment: Update::Set([1; 32]),
+            ipv4_addr: Update::Retain,
+            ipv6_addr: Update::Retain,
+            encryption_pubkey: Update::Retain,
+            signing_pubkey: Update::Retain,
+        };
+        builder.bitasset_update(&rotxn, bitasset, updates).unwrap()
+    };
+    let update_txid = update.transaction.txid();
+    let update_txs: Vec<AuthorizedTransaction> = vec![update];
+    let update_header = test_state.connect_txs(update_txs.clone()).unwrap();
+    assert_eq!(
+        bitasset_txids(&test_state, bitasset),
+        vec![registration_txid, mint_txid, update_txid]
+    );
+    // Txs for other BitAssets are not recorded
+    assert_eq!(bitasset_txids(&test_state, other), Vec::new());
+    // Disconnecting the update removes it from the index
+    let () = test_state
+        .disconnect_txs(&update_header, update_txs)
+        .unwrap();
+    assert_eq!(
+        bitasset_txids(&test_state, bitasset),
+        vec![registration_txid, mint_txid]
+    );
+}
@@ -0,0 +1,102 @@
+//! Check that blocks with too many updates to a single BitAsset are
+//! rejected, and that such blocks are not built by tx selection.
+
+use plain_bitassets::{
+    state::{self, Genesis, GenesisBitAsset, State},
+    testutil::{self, TestKey, TestState, TxBuilder},
+    types::{
+        BitAssetData, BitAssetDataUpdates, BitAssetId, FilledOutput,
+        FilledOutputContent, OutPoint, Txid, Update,
+    },
+};
+
+/// Update that sets the BitAsset commitment
+fn set_commitment(commitment: [u8; 32]) -> BitAssetDataUpdates {
+    BitAssetDataUpdates {
+        commitment: Update::Set(commitment),
+        ipv4_addr: Update::Retain,
+        ipv6_addr: Update::Retain,
+        encryption_pubkey: Update::Retain,
+        signing_pubkey: Update::Retain,
+    }
+}
+
+#[test]
+fn too_many_bitasset_updates() {
+    assert_eq!(State::MAX_BITASSET_UPDATES_PER_BLOCK, 1);
+    let key = TestKey::from_seed(1);
+    let other_key = TestKey::from_seed(2);
+    let genesis = Genesis {
+        bitassets: vec![GenesisBitAsset {
+            plain_name: "asset".to_owned(),
+            bitasset_data: BitAssetData::default(),
+            control_address: key.address,
+            balances: vec![(key.address, 1_000)],
+        }],
+        ..Default::default()
+    };
+    let bitasset = BitAssetId::from_plain_name("asset");
+    let test_state =
+        TestState::new("too_many_bitasset_updates", &genesis).unwrap();
+    let state = &test_state.state;
+    // A second control coin for the same BitAsset, so that two updates can
+    // be valid in the same block
+    {
+        let outpoint = OutPoint::Regular {
+            txid: Txid([1; 32]),
+            vout: 0,
+        };
+        let output = FilledOutput::new(
+            other_key.address,
+            FilledOutputContent::BitAssetControl(bitasset),
+        );
+        let mut rwtxn = test_state.env.write_txn().unwrap();
+        state.utxos.put(&mut rwtxn, &outpoint, &output).unwrap();
+        rwtxn.commit().unwrap();
+    }
+    let updates = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        [(&key, [1; 32]), (&other_key, [2; 32])].map(|(key, commitment)| {
+            TxBuilder::new(state, key)
+                .bitasset_update(&rotxn, bitasset, set_commitment(commitment))
+                .unwrap()
+        })
+    };
+    // Each update is valid on its own
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        for update in &updates {
+            state.validate_transaction(&rotxn, update).unwrap();
+        }
+    }
+    // A block with both updates is rejected
+    match test_state.connect_txs(updates.to_vec()) {
+        Err(testutil::Error::State(
+            state::Error::MultipleBitAssetUpdates {
+                bitasset: rejected,
+                max_updates: 1,
+            },
+        )) => assert_eq!(rejected, bitasset),
+        res => panic!("expected too many BitAsset updates, but got {res:?}"),
+    }
+    // Only one update is selected, and the other is not rejected, since it
+    // can be included in a later block
+    let selection = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state
+            .select_transactions(&rotxn, updates.to_vec(), 10)
+            .unwrap()
+    };
+    assert_eq!(selection.txs.len(), 1);
+    assert!(selection.rejected.is_empty());
+    let selected = selection
+        .txs
+        .into_iter()
+        .map(|tx| tx.transaction.transaction.txid())
+        .collect::<Vec<_>>();
+    let selected = updates
+        .into_iter()
+        .filter(|update| selected.contains(&update.transaction.txid()))
+        .collect();
+    test_state.connect_txs(selected).unwrap();
+}
@@ -0,0 +1,69 @@
+//! Check that the BMM critical hash for a block is the block hash, and
+//! commits to every field of the header.
+
+use plain_bitassets::{
+    bip300301::bitcoin::{self, hashes::Hash as _},
+    types::{
+        Address, BitcoinOutputContent, BlockHash, Body, Header, Output,
+        OutputContent,
+    },
+};
+
+fn header() -> Header {
+    Header {
+        merkle_root: Body::new(Vec::new(), Vec::new()).compute_merkle_root(),
+        prev_side_hash: BlockHash([1; 32]),
+        prev_main_hash: bitcoin::BlockHash::from_byte_array([2; 32]),
+        timestamp: 1_700_000_000,
+    }
+}
+
+#[test]
+fn block_commitment_is_header_hash() {
+    let header = header();
+    let critical_hash = header.bmm_critical_hash();
+    assert_eq!(critical_hash.to_byte_array(), header.hash().0);
+    assert_eq!(critical_hash, bitcoin::BlockHash::from(header.hash()));
+    // Headers are sent to the RPC server as JSON
+    let header_json = serde_json::to_string(&header).unwrap();
+    let header: Header = serde_json::from_str(&header_json).unwrap();
+    assert_eq!(header.bmm_critical_hash(), critical_hash);
+}
+
+#[test]
+fn block_commitment_changes_with_header() {
+    let header = header();
+    let coinbase_output = Output::new(
+        Address([3; 20]),
+        OutputContent::Bitcoin(BitcoinOutputContent(1_000)),
+    );
+    let modified_headers = [
+        Header {
+            merkle_root: Body::new(Vec::new(), vec![coinbase_output])
+                .compute_merkle_root(),
+            ..header.clone()
+        },
+        Header {
+            prev_side_hash: BlockHash([4; 32]),
+            ..header.clone()
+        },
+        Header {
+            prev_main_hash: bitcoin::BlockHash::from_byte_array([5; 32]),
+            ..header.clone()
+        },
+        Header {
+            timestamp: header.timestamp + 1,
+            ..header.clone()
+        },
+    ];
+    for modified_header in modified_headers {
+        assert_ne!(
+            modified_header.bmm_critical_hash(),
+            header.bmm_critical_hash()
+        );
+        assert_eq!(
+            modified_header.bmm_critical_hash().to_byte_array(),
+            modified_header.hash().0
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Check that the outputs and inputs reported for a block match the UTXOs
+//! that are created and spent when the block is connected.
+
+use std::collections::HashMap;
+
+use plain_bitassets::{
+    testutil::{self, TestKey, TestState},
+    types::{
+        AssetId, BitAssetId, BitcoinOutputContent, Body, FilledOutput,
+        FilledOutputContent, Header, InPoint, OutPoint, Output, OutputContent,
+        Transaction, Txid,
+    },
+};
+
+const BITCOIN_VALUE: u64 = 10_000;
+
+const BITASSET_VALUE: u64 = 10_000;
+
+const FEE: u64 = 1_000;
+
+fn asset() -> AssetId {
+    AssetId::BitAsset(BitAssetId::from_plain_name("asset"))
+}
+
+fn utxos(test_state: &TestState) -> HashMap<OutPoint, FilledOutput> {
+    let rotxn = test_state.env.read_txn().unwrap();
+    test_state.state.get_utxos(&rotxn).unwrap()
+}
+
+/** Connect a block with a Bitcoin transfer, a BitAsset transfer, and a
+ *  coinbase output claiming the fee. Returns the UTXOs before the block
+ *  was connected, and the block. */
+fn connect_block(
+    test_state: &TestState,
+    key: &TestKey,
+) -> (HashMap<OutPoint, FilledOutput>, Header, Body) {
+    let recipient = TestKey::from_seed(2);
+    let miner = TestKey::from_seed(3);
+    let bitcoin_utxo = OutPoint::Regular {
+        txid: Txid([1; 32]),
+        vout: 0,
+    };
+    {
+        let output = FilledOutput {
+            address: key.address,
+            content: FilledOutputContent::Bitcoin(BitcoinOutputContent(
+                BITCOIN_VALUE,
+            )),
+            memo: Vec::new(),
+        };
+        let mut rwtxn = test_state.env.write_txn().unwrap();
+        test_state
+            .state
+            .utxos
+            .put(&mut rwtxn, &bitcoin_utxo, &output)
+            .unwrap();
+        rwtxn.commit().unwrap();
+    }
+    let utxos_before = utxos(test_state);
+    let (bitasset_utxo, _) = utxos_before
+        .iter()
+        .find(|(_, output)| {
+            output.address == key.address
+                && output
+                    .asset_value()
+                    .is_some_and(|(output_asset, _)| output_asset == asset())
+        })
+        .unwrap();
+    let bitcoin_output = |address, value| {
+        Output::new(address, OutputContent::Value(BitcoinOutputContent(value)))
+    };
+    let bitcoin_transfer = Transaction::new(
+        vec![bitcoin_utxo],
+        vec![
+            bitcoin_output(recipient.address, 4_000),
+            bitcoin_output(key.address, BITCOIN_VALUE - 4_000 - FEE),
+        ],
+    );
+    let bitasset_transfer = Transaction::new(
+        vec![*bitasset_utxo],
+        vec![
+            Output::new(recipient.address, OutputContent::BitAsset(3_000)),
+            Output::new(
+                key.address,
+                OutputContent::BitAsset(BITASSET_VALUE - 3_000),
+            ),
+        ],
+    );
+    let txs = vec![
+        key.authorize(bitcoin_transfer).unwrap(),
+        key.authorize(bitasset_transfer).unwrap(),
+    ];
+    let coinbase = vec![bitcoin_output(miner.address, FEE)];
+    let (header, body) = test_state.connect_block(txs, coinbase).unwrap();
+    (utxos_before, header, body)
+}
+
+#[test]
+fn block_outputs_match_connected_utxos() {
+    let key = TestKey::from_seed(1);
+    let test_state = TestState::new(
+        "block_io_outputs",
+        &testutil::single_bitasset_genesis(&key, "asset", BITASSET_VALUE),
+    )
+    .unwrap();
+    let (utxos_before, _header, body) = connect_block(&test_state, &key);
+    let utxos_after = utxos(&test_state);
+    let outputs = body.get_outputs();
+    let n_tx_outputs: usize =
+        body.transactions.iter().map(|tx| tx.outputs.len()).sum();
+    assert_eq!(outputs.len(), body.coinbase.len() + n_tx_outputs);
+    assert!(outputs
+        .keys()
+        .any(|outpoint| matches!(outpoint, OutPoint::Coinbase { .. })));
+    for (outpoint, output) in &outputs {
+        let utxo = utxos_after
+            .get(outpoint)
+            .unwrap_or_else(|| panic!("block output {outpoint} is not a UTXO"));
+        assert_eq!(Output::from(utxo.clone()), *output);
+    }
+    // UTXOs after = (UTXOs before - inputs) + outputs
+    let inputs = body.get_inputs();
+    let mut expected_utxos: HashMap<OutPoint, Output> = utxos_before
+        .into_iter()
+        .filter(|(outpoint, _)| !inputs.contains(outpoint))
+        .map(|(outpoint, output)| (outpoint, output.into()))
+        .collect();
+    expected_utxos.extend(outputs);
+    let utxos_after: HashMap<OutPoint, Output> = utxos_after
+        .into_iter()
+        .map(|(outpoint, output)| (outpoint, output.into()))
+        .collect();
+    assert_eq!(utxos_after, expected_utxos);
+}
+
+#[test]
+fn block_inputs_match_spent_utxos() {
+    let key = TestKey::from_seed(1);
+    let test_state = TestState::new(
+        "block_io_inputs",
+        &testutil::single_bitasset_genesis(&key, "asset", BITASSET_VALUE),
+    )
+    .unwrap();
+    let (utxos_before, header, body) = connect_block(&test_state, &key);
+    let inputs = body.get_inputs();
+    let expected_inputs: Vec<OutPoint> = body
+        .transactions
+        .iter()
+        .flat_map(|tx| tx.inputs.iter().copied())
+        .collect();
+    assert_eq!(inputs, expected_inputs);
+    let utxos_after = utxos(&test_state);
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        let mut inputs = inputs.iter();
+        for tx in &body.transactions {
+            let txid = tx.txid();
+            for vin in 0..tx.inputs.len() as u32 {
+                let input = inputs.next().unwrap();
+                let spent_utxo = utxos_before.get(input).unwrap_or_else(|| {
+                    panic!("block input {input} was not a UTXO")
+                });
+                assert!(!utxos_after.contains_key(input));
+                let stxo =
+                    test_state.state.stxos.get(&rotxn, input).unwrap().unwrap();
+                assert_eq!(stxo.output, *spent_utxo);
+                assert_eq!(stxo.inpoint, InPoint::Regular { txid, vin });
+            }
+        }
+        assert_eq!(inputs.next(), None);
+    }
+    let () = test_state.disconnect_block(&header, &body).unwrap();
+    assert_eq!(utxos(&test_state), utxos_before);
+    let rotxn = test_state.env.read_txn().unwrap();
+    for input in &body.get_inputs() {
+        assert!(test_state.state.stxos.get(&rotxn, input).unwrap().is_none());
+    }
+}
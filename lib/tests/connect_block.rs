@@ -0,0 +1,192 @@
+//! Check that connecting a block with the filled txs from validation
+//! produces the same state as filling the txs again, and that filled txs
+//! that do not match the block are rejected.
+
+use std::collections::HashMap;
+
+use plain_bitassets::{
+    bip300301::bitcoin::{self, hashes::Hash as _},
+    state::{Error, Genesis, GenesisBitAsset},
+    testutil::{TestKey, TestState, TxBuilder},
+    types::{
+        AssetId, AuthorizedTransaction, BitAssetData, BitAssetId, BlockHash,
+        Body, FilledOutput, Header, OutPoint, SpentOutput,
+    },
+};
+
+fn bitasset(plain_name: &str) -> AssetId {
+    AssetId::BitAsset(BitAssetId(blake3::hash(plain_name.as_bytes()).into()))
+}
+
+fn genesis(key: &TestKey) -> Genesis {
+    let genesis_bitasset = |plain_name: &str| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data: BitAssetData::default(),
+        control_address: key.address,
+        balances: vec![(key.address, 100_000), (key.address, 50_000)],
+    };
+    Genesis {
+        bitassets: vec![genesis_bitasset("asset0"), genesis_bitasset("asset1")],
+        ..Default::default()
+    }
+}
+
+/// Block containing txs that create and spend UTXOs
+fn block(test_state: &TestState, key: &TestKey) -> (Header, Body) {
+    let builder = TxBuilder::new(&test_state.state, key);
+    let rotxn = test_state.env.read_txn().unwrap();
+    let txs: Vec<AuthorizedTransaction> = vec![
+        builder
+            .amm_mint(
+                &rotxn,
+                bitasset("asset0"),
+                bitasset("asset1"),
+                1_000,
+                2_000,
+            )
+            .unwrap(),
+        builder.bitasset_reservation("asset2").unwrap(),
+    ];
+    let body = Body::new(txs, Vec::new());
+    let header = Header {
+        merkle_root: body.compute_merkle_root(),
+        prev_side_hash: test_state.state.get_tip(&rotxn).unwrap(),
+        prev_main_hash: bitcoin::BlockHash::all_zeros(),
+        timestamp: 0,
+    };
+    (header, body)
+}
+
+/// UTXOs, STXOs, and tip
+type StateSnapshot = (
+    HashMap<OutPoint, FilledOutput>,
+    Vec<(OutPoint, SpentOutput)>,
+    BlockHash,
+);
+
+fn snapshot(test_state: &TestState) -> StateSnapshot {
+    let rotxn = test_state.env.read_txn().unwrap();
+    let state = &test_state.state;
+    let utxos = state.get_utxos(&rotxn).unwrap();
+    let stxos = state
+        .stxos
+        .iter(&rotxn)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let tip = state.get_tip(&rotxn).unwrap();
+    (utxos, stxos, tip)
+}
+
+#[test]
+fn connect_filled_block() {
+    let key = TestKey::from_seed(1);
+    let genesis = genesis(&key);
+    let refill = TestState::new("connect_block_refill", &genesis).unwrap();
+    let filled = TestState::new("connect_block_filled", &genesis).unwrap();
+    let (header, body) = block(&refill, &key);
+    assert_eq!(block(&filled, &key).0, header);
+    // Fill txs again when connecting
+    {
+        let mut rwtxn = refill.env.write_txn().unwrap();
+        let _ = refill.state.validate_block(&rwtxn, &header, &body).unwrap();
+        let () = refill
+            .state
+            .connect_block(&mut rwtxn, &header, &body)
+            .unwrap();
+        rwtxn.commit().unwrap();
+    }
+    // Re-use filled txs from validation
+    {
+        let mut rwtxn = filled.env.write_txn().unwrap();
+        let (_fees, filled_txs) =
+            filled.state.validate_block(&rwtxn, &header, &body).unwrap();
+        assert_eq!(filled_txs.len(), body.transactions.len());
+        let () = filled
+            .state
+            .connect_filled_block(&mut rwtxn, &header, &body, filled_txs)
+            .unwrap();
+        rwtxn.commit().unwrap();
+    }
+    let refill_snapshot = snapshot(&refill);
+    assert_eq!(refill_snapshot.2, header.hash());
+    assert!(!refill_snapshot.1.is_empty());
+    assert_eq!(refill_snapshot, snapshot(&filled));
+}
+
+#[test]
+fn filled_txs_mismatch() {
+    let key = TestKey::from_seed(1);
+    let test_state =
+        TestState::new("connect_block_filled_txs_mismatch", &genesis(&key))
+            .unwrap();
+    let (header, body) = block(&test_state, &key);
+    let mut rwtxn = test_state.env.write_txn().unwrap();
+    let (_fees, mut filled_txs) = test_state
+        .state
+        .validate_block(&rwtxn, &header, &body)
+        .unwrap();
+    filled_txs.pop();
+    match test_state
+        .state
+        .connect_filled_block(&mut rwtxn, &header, &body, filled_txs)
+    {
+        Err(Error::FilledTxsMismatch { expected, received }) => {
+            assert_eq!((expected, received), (2, 1))
+        }
+        res => panic!("expected filled txs mismatch, but got {res:?}"),
+    }
+}
+
+#[test]
+fn filled_tx_mismatch() {
+    let key = TestKey::from_seed(1);
+    let test_state =
+        TestState::new("connect_block_filled_tx_mismatch", &genesis(&key))
+            .unwrap();
+    let (header, body) = block(&test_state, &key);
+    let mut rwtxn = test_state.env.write_txn().unwrap();
+    let (_fees, mut filled_txs) = test_state
+        .state
+        .validate_block(&rwtxn, &header, &body)
+        .unwrap();
+    filled_txs.reverse();
+    match test_state
+        .state
+        .connect_filled_block(&mut rwtxn, &header, &body, filled_txs)
+    {
+        Err(Error::FilledTxMismatch { expected, received }) => {
+            assert_eq!(expected, body.transactions[0].txid());
+            assert_eq!(received, body.transactions[1].txid());
+        }
+        res => panic!("expected filled tx mismatch, but got {res:?}"),
+    }
+}
+
+/// Connecting a block without validating it does not allow a UTXO to be
+/// spent twice
+#[test]
+fn double_spend_without_validation() {
+    let key = TestKey::from_seed(1);
+    let test_state =
+        TestState::new("connect_block_double_spend", &genesis(&key)).unwrap();
+    let (header, body) = {
+        let (_, body) = block(&test_state, &key);
+        let amm_mint = body.authorized_transactions()[0].clone();
+        let body = Body::new(vec![amm_mint.clone(), amm_mint], Vec::new());
+        let rotxn = test_state.env.read_txn().unwrap();
+        let header = Header {
+            merkle_root: body.compute_merkle_root(),
+            prev_side_hash: test_state.state.get_tip(&rotxn).unwrap(),
+            prev_main_hash: bitcoin::BlockHash::all_zeros(),
+            timestamp: 0,
+        };
+        (header, body)
+    };
+    let double_spent = body.transactions[0].inputs[0];
+    let mut rwtxn = test_state.env.write_txn().unwrap();
+    match test_state.state.connect_block(&mut rwtxn, &header, &body) {
+        Err(Error::NoUtxo { outpoint }) => assert_eq!(outpoint, double_spent),
+        res => panic!("expected missing UTXO, but got {res:?}"),
+    }
+}
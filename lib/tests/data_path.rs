@@ -0,0 +1,75 @@
+//! Check that data in the flat data directory layout used by previous
+//! versions is not moved into the wrong network's subdirectory.
+
+use std::path::PathBuf;
+
+use plain_bitassets::{
+    types::Network,
+    util::{network_data_path, NetworkDataPathError},
+};
+
+/// Temporary directory, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "plain_bitassets_data_path_{name}_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn legacy_data_is_not_migrated() {
+    let dir = TempDir::new("legacy");
+    let datadir = &dir.0;
+    let legacy_path = datadir.join("data.mdb");
+    std::fs::create_dir_all(&legacy_path).unwrap();
+    std::fs::write(legacy_path.join("data"), b"signet data").unwrap();
+    // Neither network can claim the legacy data
+    for network in [Network::Signet, Network::Regtest] {
+        match network_data_path(datadir, network, "data.mdb") {
+            Err(NetworkDataPathError::UnknownLegacyNetwork {
+                legacy_path: err_legacy_path,
+                path,
+                network: err_network,
+            }) => {
+                assert_eq!(err_legacy_path, legacy_path);
+                assert_eq!(
+                    path,
+                    datadir.join(network.data_dir_name()).join("data.mdb")
+                );
+                assert_eq!(err_network, network);
+            }
+            res => panic!("expected unknown legacy network, but got {res:?}"),
+        }
+    }
+    assert!(legacy_path.join("data").exists());
+    assert!(!datadir.join("signet").exists());
+    assert!(!datadir.join("regtest").exists());
+    // Once the data is moved manually, each network uses its own
+    // subdirectory
+    std::fs::create_dir_all(datadir.join("signet")).unwrap();
+    std::fs::rename(&legacy_path, datadir.join("signet").join("data.mdb"))
+        .unwrap();
+    let signet_path =
+        network_data_path(datadir, Network::Signet, "data.mdb").unwrap();
+    assert_eq!(
+        std::fs::read(signet_path.join("data")).unwrap(),
+        b"signet data"
+    );
+    let regtest_path =
+        network_data_path(datadir, Network::Regtest, "data.mdb").unwrap();
+    assert_eq!(regtest_path, datadir.join("regtest").join("data.mdb"));
+    assert!(!regtest_path.exists());
+}
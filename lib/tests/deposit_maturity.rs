@@ -0,0 +1,64 @@
+//! Check that deposits without enough mainchain confirmations are not
+//! spent by the wallet.
+
+use std::collections::HashMap;
+
+use plain_bitassets::{
+    bip300301::bitcoin::{self, hashes::Hash as _},
+    state::Genesis,
+    testutil::TestHarness,
+    types::{
+        BitcoinOutputContent, FilledOutput, FilledOutputContent, OutPoint,
+    },
+    wallet,
+};
+
+#[test]
+fn immature_deposit_not_spendable() {
+    const FUNDED: u64 = 1_000;
+    const DEPOSITED: u64 = 50_000;
+    let harness =
+        TestHarness::new("immature_deposit_not_spendable", &Genesis::default())
+            .unwrap();
+    let _ = harness.fund(FUNDED).unwrap();
+    let deposit = OutPoint::Deposit(bitcoin::OutPoint {
+        txid: bitcoin::Txid::all_zeros(),
+        vout: 0,
+    });
+    let output = FilledOutput::new(
+        harness.wallet.get_new_address().unwrap(),
+        FilledOutputContent::Bitcoin(BitcoinOutputContent(DEPOSITED)),
+    );
+    let () = harness
+        .wallet
+        .put_utxos(&HashMap::from([(deposit, output)]))
+        .unwrap();
+    let () = harness
+        .wallet
+        .put_immature_deposits(&HashMap::from([(deposit, 1)]))
+        .unwrap();
+    // The immature deposit is held, but not spendable
+    assert_eq!(
+        harness.wallet.get_bitcoin_balance().unwrap(),
+        FUNDED + DEPOSITED
+    );
+    assert_eq!(
+        harness.wallet.get_spendable_bitcoin_balance().unwrap(),
+        FUNDED
+    );
+    assert!(matches!(
+        harness.wallet.select_bitcoins(FUNDED + 1),
+        Err(wallet::Error::NotEnoughFunds)
+    ));
+    // Once mature, the deposit can be spent
+    let () = harness
+        .wallet
+        .put_immature_deposits(&HashMap::new())
+        .unwrap();
+    assert_eq!(
+        harness.wallet.get_spendable_bitcoin_balance().unwrap(),
+        FUNDED + DEPOSITED
+    );
+    let (total, selected) = harness.wallet.select_bitcoins(FUNDED + 1).unwrap();
+    assert!(total > FUNDED && selected.contains_key(&deposit));
+}
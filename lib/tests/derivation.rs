@@ -0,0 +1,123 @@
+//! Test vectors for wallet key and address derivation.
+//! If any of these fail, funds in existing wallets may be unrecoverable.
+
+use plain_bitassets::{authorization::get_address, wallet::Wallet};
+
+const MNEMONIC_0: &str = "abandon abandon abandon abandon abandon abandon \
+    abandon abandon abandon abandon abandon about";
+
+const MNEMONIC_1: &str =
+    "legal winner thank year wave sausage worth useful legal winner thank \
+    yellow";
+
+struct Vector {
+    mnemonic: &'static str,
+    passphrase: &'static str,
+    index: u32,
+    signing_key: &'static str,
+    verifying_key: &'static str,
+    address: &'static str,
+}
+
+const VECTORS: &[Vector] = &[
+    Vector {
+        mnemonic: MNEMONIC_0,
+        passphrase: "",
+        index: 0,
+        signing_key:
+            "46b5079ad5d931264ccf3768ffee7e9c22dfd754e766ff91de7e964d2febc621",
+        verifying_key:
+            "2cdb3f550ccf47bdf4ba3278738e60bf2c2b7da71663fd9ff0a80d5f327c7b42",
+        address: "Ew8daAHFz86aTfuqSMTHUs9SEpsfT63yL",
+    },
+    Vector {
+        mnemonic: MNEMONIC_0,
+        passphrase: "",
+        index: 1,
+        signing_key:
+            "0eedb940469fcd26b1d6745c2211002737f2429ba8dab7ea0accfa973d20b00b",
+        verifying_key:
+            "f7a6b78219c26ac7241c88c6a922020e61f94aa2f67802e5e52323908f887cde",
+        address: "5sAgqjJ79W5HiutCPGw7Xv2SYCiZg5KVX",
+    },
+    Vector {
+        mnemonic: MNEMONIC_0,
+        passphrase: "",
+        index: (1 << 31) - 1,
+        signing_key:
+            "22f5a1c5614d41a5c683f7ebc74a3adc684e0142ee8dde135158b5a5f4377bb2",
+        verifying_key:
+            "8d8ae75dcf7909199b1d740b10f999470788ff5bae1f38d779dbe63da0675aa3",
+        address: "9bQT7NsyAAhkqWWg6AFAfc4ha5thzksWD",
+    },
+    Vector {
+        mnemonic: MNEMONIC_0,
+        passphrase: "TREZOR",
+        index: 0,
+        signing_key:
+            "ccc12e293b7e465ba70e5eaf0c413a4b857abe4e790e6d8f905dbc0fd6c36283",
+        verifying_key:
+            "0db2fa5b0dcd9654211a04e832a3e1dd15d5fcef5de8577965e43451b37b994b",
+        address: "MaJDF4g76Z6UQnps7QjSUWqpN7dysiXcZ",
+    },
+    Vector {
+        mnemonic: MNEMONIC_1,
+        passphrase: "",
+        index: 0,
+        signing_key:
+            "5daaac0dc728f6ed06f18fd12d63b71afc4860eda4b289c834ffd2e05bb70f5a",
+        verifying_key:
+            "c94a20ed520b6871589f4e3bdf9023ebadf1c68cb6b648a360c7354118e57cc3",
+        address: "2f9dmz6WS9ZgiEDXqspd6S5BMQfgiwus9",
+    },
+    Vector {
+        mnemonic: MNEMONIC_1,
+        passphrase: "",
+        index: 1,
+        signing_key:
+            "13a398774a684360070f82fb8f5c6b5d6fd7f4ea8ab3d82ffe97c92f7b6f7c4f",
+        verifying_key:
+            "1be4f33f95359f4b5608afed45fb5fdfebef279c64df1459f21a844f90289f37",
+        address: "MHJnxaukXN8DZMnPDsXqv56wU7SXYDokY",
+    },
+    Vector {
+        mnemonic: MNEMONIC_1,
+        passphrase: "",
+        index: (1 << 31) - 1,
+        signing_key:
+            "b19bd866ffe7bdec047637649831365da2ccb080ef6f30adf9ab81da9e6c349a",
+        verifying_key:
+            "a6e3bc5b54b2a788c50aabd383246c63e414d4ddb428c1df350e30509aff13af",
+        address: "UZW7BTiifpkfzn43w7wVdB3xXRNtZ7ZV",
+    },
+];
+
+#[test]
+fn derivation_vectors() -> anyhow::Result<()> {
+    for vector in VECTORS {
+        let seed =
+            Wallet::seed_from_mnemonic(vector.mnemonic, vector.passphrase)?;
+        let signing_key = Wallet::derive_signing_key(&seed, vector.index)?;
+        let verifying_key = signing_key.verifying_key();
+        let address = get_address(&verifying_key);
+        assert_eq!(
+            hex::encode(signing_key.to_bytes()),
+            vector.signing_key,
+            "signing key mismatch for index {}",
+            vector.index
+        );
+        assert_eq!(
+            hex::encode(verifying_key.to_bytes()),
+            vector.verifying_key,
+            "verifying key mismatch for index {}",
+            vector.index
+        );
+        assert_eq!(
+            address.to_string(),
+            vector.address,
+            "address mismatch for index {}",
+            vector.index
+        );
+    }
+    Ok(())
+}
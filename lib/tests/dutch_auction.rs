@@ -0,0 +1,77 @@
+//! Check that the Dutch auction price stream yields non-increasing prices
+//! from the start of the auction, and ends at the end of the auction.
+
+use std::pin::pin;
+
+use futures::{FutureExt as _, StreamExt as _};
+use plain_bitassets::{
+    testutil::{self, TestKey, TestState, TxBuilder},
+    types::{AssetId, BitAssetId, DutchAuctionId, DutchAuctionParams},
+};
+
+const INITIAL_PRICE: u64 = 2_000;
+
+const FINAL_PRICE: u64 = 1_000;
+
+const DURATION: u32 = 5;
+
+#[test]
+fn dutch_auction_price_stream() {
+    let key = TestKey::from_seed(1);
+    let genesis = testutil::bitassets_genesis(&[
+        ("base", &key, 100_000),
+        ("quote", &key, 100_000),
+    ]);
+    let test_state =
+        TestState::new("dutch_auction_price_stream", &genesis).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let auction_create = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        let height = state.get_height(&rotxn).unwrap();
+        let params = DutchAuctionParams {
+            start_block: height + 3,
+            duration: DURATION,
+            base_asset: AssetId::BitAsset(BitAssetId::from_plain_name("base")),
+            base_amount: 1_000,
+            quote_asset: AssetId::BitAsset(BitAssetId::from_plain_name(
+                "quote",
+            )),
+            initial_price: INITIAL_PRICE,
+            final_price: FINAL_PRICE,
+        };
+        builder.dutch_auction_create(&rotxn, params).unwrap()
+    };
+    let auction_id = DutchAuctionId(auction_create.transaction.txid());
+    test_state.connect_txs(vec![auction_create]).unwrap();
+    let mut prices_stream =
+        pin!(state.watch_dutch_auction_price(&test_state.env, auction_id));
+    let mut prices = Vec::new();
+    let mut skipped = 0;
+    loop {
+        match prices_stream.next().now_or_never() {
+            Some(Some(price)) => prices.push(price.unwrap()),
+            // The stream ended
+            Some(None) => break,
+            // No price before the auction starts
+            None => {
+                assert!(prices.is_empty());
+                skipped += 1;
+            }
+        }
+        assert!(prices.len() + skipped <= DURATION as usize + 2);
+        test_state.connect_txs(Vec::new()).unwrap();
+    }
+    assert_eq!(skipped, 2);
+    // One price for each block of the auction
+    assert_eq!(prices.len(), DURATION as usize);
+    assert_eq!(prices.first(), Some(&INITIAL_PRICE));
+    assert_eq!(prices.last(), Some(&FINAL_PRICE));
+    assert!(
+        prices.windows(2).all(|window| window[0] >= window[1]),
+        "prices increased: {prices:?}"
+    );
+    // The stream stays closed
+    test_state.connect_txs(Vec::new()).unwrap();
+    assert!(prices_stream.next().now_or_never().unwrap().is_none());
+}
@@ -0,0 +1,92 @@
+//! Check that BitAssets can be found by their current signing or encryption
+//! pubkeys.
+
+use plain_bitassets::{
+    state::{Genesis, GenesisBitAsset},
+    testutil::{TestKey, TestState},
+    types::{BitAssetData, BitAssetId, BitAssetKey, EncryptionPubKey},
+};
+
+#[test]
+fn find_bitassets_by_key() {
+    let key = TestKey::from_seed(1);
+    let shared_signing_pubkey = key.signing_key.verifying_key();
+    let other_signing_pubkey =
+        TestKey::from_seed(2).signing_key.verifying_key();
+    let encryption_pubkey = EncryptionPubKey::from([3; 32]);
+    let genesis_bitasset = |plain_name: &str, bitasset_data| GenesisBitAsset {
+        plain_name: plain_name.to_owned(),
+        bitasset_data,
+        control_address: key.address,
+        balances: vec![(key.address, 1_000)],
+    };
+    let genesis = Genesis {
+        bitassets: vec![
+            genesis_bitasset(
+                "asset0",
+                BitAssetData {
+                    signing_pubkey: Some(shared_signing_pubkey),
+                    ..Default::default()
+                },
+            ),
+            genesis_bitasset(
+                "asset1",
+                BitAssetData {
+                    encryption_pubkey: Some(encryption_pubkey),
+                    signing_pubkey: Some(shared_signing_pubkey),
+                    ..Default::default()
+                },
+            ),
+            genesis_bitasset(
+                "asset2",
+                BitAssetData {
+                    encryption_pubkey: Some(encryption_pubkey),
+                    signing_pubkey: Some(other_signing_pubkey),
+                    ..Default::default()
+                },
+            ),
+            genesis_bitasset("asset3", BitAssetData::default()),
+        ],
+        ..Default::default()
+    };
+    let bitasset_ids: Vec<BitAssetId> = genesis
+        .bitassets
+        .iter()
+        .map(GenesisBitAsset::bitasset_id)
+        .collect();
+    let test_state = TestState::new("find_bitassets_by_key", &genesis).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    let find = |key| {
+        test_state
+            .state
+            .find_bitassets_by_key(&rotxn, &key)
+            .unwrap()
+    };
+    let sorted = |mut bitasset_ids: Vec<BitAssetId>| {
+        bitasset_ids.sort();
+        bitasset_ids
+    };
+    assert_eq!(
+        find(BitAssetKey::Signing(shared_signing_pubkey)),
+        sorted(vec![bitasset_ids[0], bitasset_ids[1]])
+    );
+    assert_eq!(
+        find(BitAssetKey::Signing(other_signing_pubkey)),
+        vec![bitasset_ids[2]]
+    );
+    assert_eq!(
+        find(BitAssetKey::Encryption(encryption_pubkey)),
+        sorted(vec![bitasset_ids[1], bitasset_ids[2]])
+    );
+    // Keys that are not set for any BitAsset
+    let unused_signing_pubkey =
+        TestKey::from_seed(4).signing_key.verifying_key();
+    assert_eq!(
+        find(BitAssetKey::Signing(unused_signing_pubkey)),
+        Vec::new()
+    );
+    assert_eq!(
+        find(BitAssetKey::Encryption(EncryptionPubKey::from([5; 32]))),
+        Vec::new()
+    );
+}
@@ -0,0 +1,175 @@
+//! Examples of building valid txs with the `test-util` fixture builders.
+
+use plain_bitassets::{
+    state::{AmmPair, Genesis},
+    testutil::{self, TestKey, TestState, TxBuilder},
+    types::{
+        AssetId, BitAssetData, BitAssetId, DutchAuctionId, DutchAuctionParams,
+    },
+};
+
+const INITIAL_BALANCE: u64 = 100_000;
+
+fn bitasset_id(plain_name: &str) -> BitAssetId {
+    BitAssetId(blake3::hash(plain_name.as_bytes()).into())
+}
+
+/// Genesis allocating two BitAssets, and their control coins, to the key
+fn genesis(key: &TestKey) -> Genesis {
+    testutil::bitassets_genesis(&[
+        ("base", key, INITIAL_BALANCE),
+        ("quote", key, INITIAL_BALANCE),
+    ])
+}
+
+#[test]
+fn bitasset_registration_and_mint() {
+    let key = TestKey::from_seed(1);
+    let test_state = TestState::new("registration", &genesis(&key)).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let reservation = builder.bitasset_reservation("newtoken").unwrap();
+    test_state.connect_txs(vec![reservation]).unwrap();
+    let registration = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .bitasset_registration(
+                &rotxn,
+                "newtoken",
+                BitAssetData::default(),
+                1_000,
+            )
+            .unwrap()
+    };
+    test_state.connect_txs(vec![registration]).unwrap();
+    let new_token = bitasset_id("newtoken");
+    let mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        assert!(state.bitassets.get(&rotxn, &new_token).unwrap().is_some());
+        builder.bitasset_mint(&rotxn, new_token, 500).unwrap()
+    };
+    test_state.connect_txs(vec![mint]).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    assert_eq!(
+        test_state
+            .balance(&rotxn, &key, AssetId::BitAsset(new_token))
+            .unwrap(),
+        1_500
+    );
+    assert_eq!(
+        test_state
+            .balance(&rotxn, &key, AssetId::BitAssetControl(new_token))
+            .unwrap(),
+        1
+    );
+}
+
+#[test]
+fn amm_mint_and_swap() {
+    let key = TestKey::from_seed(1);
+    let test_state = TestState::new("amm", &genesis(&key)).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let base = AssetId::BitAsset(bitasset_id("base"));
+    let quote = AssetId::BitAsset(bitasset_id("quote"));
+    let amm_mint = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .amm_mint(&rotxn, base, quote, 10_000, 10_000)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![amm_mint]).unwrap();
+    let amm_swap = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder.amm_swap(&rotxn, base, quote, 1_000).unwrap()
+    };
+    test_state.connect_txs(vec![amm_swap]).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    let amm_pool_state = state
+        .amm_pools
+        .get(&rotxn, &AmmPair::new(base, quote))
+        .unwrap()
+        .unwrap();
+    let (base_reserve, quote_reserve) = if base < quote {
+        (amm_pool_state.reserve0, amm_pool_state.reserve1)
+    } else {
+        (amm_pool_state.reserve1, amm_pool_state.reserve0)
+    };
+    assert_eq!(base_reserve, 11_000);
+    assert_eq!(
+        test_state.balance(&rotxn, &key, base).unwrap(),
+        INITIAL_BALANCE - base_reserve
+    );
+    assert_eq!(
+        test_state.balance(&rotxn, &key, quote).unwrap(),
+        INITIAL_BALANCE - quote_reserve
+    );
+}
+
+#[test]
+fn dutch_auction_create_and_bid() {
+    let key = TestKey::from_seed(1);
+    let test_state = TestState::new("dutch_auction", &genesis(&key)).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let base = AssetId::BitAsset(bitasset_id("base"));
+    let quote = AssetId::BitAsset(bitasset_id("quote"));
+    let auction_create = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        let height = state.get_height(&rotxn).unwrap();
+        let params = DutchAuctionParams {
+            start_block: height + 1,
+            duration: 10,
+            base_asset: base,
+            base_amount: 1_000,
+            quote_asset: quote,
+            initial_price: 2_000,
+            final_price: 1_000,
+        };
+        builder.dutch_auction_create(&rotxn, params).unwrap()
+    };
+    let auction_id = DutchAuctionId(auction_create.transaction.txid());
+    test_state.connect_txs(vec![auction_create]).unwrap();
+    let auction_bid = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder.dutch_auction_bid(&rotxn, auction_id, 200).unwrap()
+    };
+    test_state.connect_txs(vec![auction_bid]).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    // At the initial price, 200 units of the quote buy 100 of the base
+    let auction_state = state
+        .dutch_auctions
+        .get(&rotxn, &auction_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(auction_state.base_amount_remaining.latest().data, 900);
+    assert_eq!(
+        test_state.balance(&rotxn, &key, base).unwrap(),
+        INITIAL_BALANCE - 1_000 + 100
+    );
+    assert_eq!(
+        test_state.balance(&rotxn, &key, quote).unwrap(),
+        INITIAL_BALANCE - 200
+    );
+}
+
+#[test]
+fn fixtures_are_reproducible() {
+    let key = TestKey::from_seed(1);
+    let genesis = genesis(&key);
+    let base = AssetId::BitAsset(bitasset_id("base"));
+    let quote = AssetId::BitAsset(bitasset_id("quote"));
+    let txids: Vec<_> = ["reproducible_0", "reproducible_1"]
+        .into_iter()
+        .map(|name| {
+            let test_state = TestState::new(name, &genesis).unwrap();
+            let rotxn = test_state.env.read_txn().unwrap();
+            TxBuilder::new(&test_state.state, &key)
+                .amm_mint(&rotxn, base, quote, 1_000, 2_000)
+                .unwrap()
+                .transaction
+                .txid()
+        })
+        .collect();
+    assert_eq!(txids[0], txids[1]);
+}
@@ -0,0 +1,287 @@
+//! Check that genesis allocations are applied to the state of a new chain,
+//! and that the state cannot be reopened with a different genesis config,
+//! or from a data directory with an incompatible schema version.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use plain_bitassets::{
+    heed::{
+        self,
+        types::{SerdeBincode, Unit},
+    },
+    state::{
+        self, AmmPoolState, AmmProtocolFee, Genesis, GenesisBitAsset,
+        GenesisError, State,
+    },
+    types::{
+        Address, BitAssetData, BitAssetId, FilledOutput, FilledOutputContent,
+        OutPoint,
+    },
+};
+
+/// Temporary directory, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "plain_bitassets_genesis_{name}_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn open_env(&self) -> heed::Env {
+        unsafe {
+            heed::EnvOpenOptions::new()
+                .map_size(10 * 1024 * 1024) // 10MB
+                .max_dbs(State::NUM_DBS)
+                .open(&self.0)
+                .unwrap()
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn new_state(
+    env: &heed::Env,
+    genesis: &Genesis,
+) -> Result<State, state::Error> {
+    State::new(env, false, genesis)
+}
+
+fn genesis() -> Genesis {
+    Genesis {
+        bitassets: vec![
+            GenesisBitAsset {
+                plain_name: "genesistoken".to_owned(),
+                bitasset_data: BitAssetData {
+                    commitment: Some([0x01; 32]),
+                    ..Default::default()
+                },
+                control_address: Address::from([0x11; 20]),
+                balances: vec![
+                    (Address::from([0x12; 20]), 1_000),
+                    (Address::from([0x13; 20]), 2_000),
+                ],
+            },
+            GenesisBitAsset {
+                plain_name: "othergenesistoken".to_owned(),
+                bitasset_data: BitAssetData::default(),
+                control_address: Address::from([0x21; 20]),
+                balances: vec![(Address::from([0x22; 20]), 21_000_000)],
+            },
+        ],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn genesis_allocations() {
+    let dir = TempDir::new("allocations");
+    let env = dir.open_env();
+    let genesis = genesis();
+    let state = new_state(&env, &genesis).unwrap();
+    let rotxn = env.read_txn().unwrap();
+    // BitAssets are registered in the order that they are specified
+    let bitasset_ids: Vec<BitAssetId> = state
+        .bitasset_seq_to_bitasset
+        .iter(&rotxn)
+        .unwrap()
+        .map(|res| res.unwrap().1)
+        .collect();
+    let expected_bitasset_ids: Vec<BitAssetId> = genesis
+        .bitassets
+        .iter()
+        .map(|genesis_bitasset| {
+            BitAssetId(
+                blake3::hash(genesis_bitasset.plain_name.as_bytes()).into(),
+            )
+        })
+        .collect();
+    assert_eq!(bitasset_ids, expected_bitasset_ids);
+    for (genesis_bitasset, bitasset_id) in
+        genesis.bitassets.iter().zip(&bitasset_ids)
+    {
+        let bitasset_data = state
+            .get_current_bitasset_data(&rotxn, bitasset_id)
+            .unwrap();
+        assert_eq!(bitasset_data, genesis_bitasset.bitasset_data);
+    }
+    // UTXOs are the allocated balances and control coins
+    let utxos = state.get_utxos(&rotxn).unwrap();
+    let expected_outputs = [
+        (
+            Address::from([0x12; 20]),
+            FilledOutputContent::BitAsset(bitasset_ids[0], 1_000),
+        ),
+        (
+            Address::from([0x13; 20]),
+            FilledOutputContent::BitAsset(bitasset_ids[0], 2_000),
+        ),
+        (
+            Address::from([0x11; 20]),
+            FilledOutputContent::BitAssetControl(bitasset_ids[0]),
+        ),
+        (
+            Address::from([0x22; 20]),
+            FilledOutputContent::BitAsset(bitasset_ids[1], 21_000_000),
+        ),
+        (
+            Address::from([0x21; 20]),
+            FilledOutputContent::BitAssetControl(bitasset_ids[1]),
+        ),
+    ];
+    assert_eq!(utxos.len(), expected_outputs.len());
+    for (address, content) in expected_outputs {
+        assert!(
+            utxos
+                .values()
+                .any(|output| output.address == address
+                    && output.content == content),
+            "missing genesis output {content:?} for {address}"
+        );
+    }
+    let expected_utxos: HashMap<OutPoint, FilledOutput> =
+        genesis.outputs().into_iter().collect();
+    assert_eq!(utxos, expected_utxos);
+}
+
+#[test]
+fn genesis_applied_once() {
+    let dir = TempDir::new("applied_once");
+    let env = dir.open_env();
+    let genesis = genesis();
+    let _state = new_state(&env, &genesis).unwrap();
+    // Reopening the state must not allocate the genesis BitAssets again
+    let state = new_state(&env, &genesis).unwrap();
+    let rotxn = env.read_txn().unwrap();
+    assert_eq!(
+        state.bitasset_seq_to_bitasset.len(&rotxn).unwrap(),
+        genesis.bitassets.len() as u64
+    );
+    assert_eq!(state.get_utxos(&rotxn).unwrap().len(), 5);
+}
+
+#[test]
+fn genesis_mismatch() {
+    let dir = TempDir::new("mismatch");
+    let env = dir.open_env();
+    let genesis = genesis();
+    let _state = new_state(&env, &genesis).unwrap();
+    // Chain parameters cannot be changed once the state is created
+    let mut fee_changed = genesis.clone();
+    fee_changed.amm_protocol_fee.fee_bps = 30;
+    let mut pool_creators_changed = genesis.clone();
+    pool_creators_changed
+        .amm_pool_creators
+        .push(Address::from([0x31; 20]));
+    let mut price_impact_changed = genesis.clone();
+    price_impact_changed.amm_max_price_impact_bps = Some(500);
+    let mut tx_limits_changed = genesis.clone();
+    tx_limits_changed.tx_limits.max_inputs = 1;
+    for changed in [
+        fee_changed,
+        pool_creators_changed,
+        price_impact_changed,
+        tx_limits_changed,
+    ] {
+        assert!(matches!(
+            new_state(&env, &changed),
+            Err(state::Error::InvalidGenesis(GenesisError::Mismatch))
+        ));
+    }
+    // The original genesis config is still accepted
+    let _state = new_state(&env, &genesis).unwrap();
+}
+
+#[test]
+fn invalid_genesis() {
+    let dir = TempDir::new("invalid");
+    let env = dir.open_env();
+    let mut duplicate = genesis();
+    duplicate.bitassets.push(duplicate.bitassets[0].clone());
+    assert!(matches!(
+        duplicate.validate(),
+        Err(GenesisError::DuplicateBitAsset { .. })
+    ));
+    let mut zero_balance = genesis();
+    zero_balance.bitassets[0].balances[0].1 = 0;
+    assert!(matches!(
+        zero_balance.validate(),
+        Err(GenesisError::ZeroBalance { .. })
+    ));
+    let mut fee_too_high = genesis();
+    fee_too_high.amm_protocol_fee.fee_bps = AmmProtocolFee::MAX_FEE_BPS + 1;
+    assert!(matches!(
+        fee_too_high.validate(),
+        Err(GenesisError::AmmProtocolFeeTooHigh { .. })
+    ));
+    let mut price_impact_too_high = genesis();
+    price_impact_too_high.amm_max_price_impact_bps =
+        Some(AmmPoolState::MAX_PRICE_IMPACT_BPS + 1);
+    assert!(matches!(
+        price_impact_too_high.validate(),
+        Err(GenesisError::AmmMaxPriceImpactTooHigh { .. })
+    ));
+    let mut overflow = genesis();
+    overflow.bitassets[0].balances[0].1 = u64::MAX;
+    assert!(matches!(
+        overflow.validate(),
+        Err(GenesisError::SupplyOverflow { .. })
+    ));
+    // Invalid genesis allocations are rejected by the state
+    assert!(matches!(
+        new_state(&env, &overflow),
+        Err(state::Error::InvalidGenesis(
+            GenesisError::SupplyOverflow { .. }
+        ))
+    ));
+}
+
+#[test]
+fn schema_version_mismatch() {
+    let genesis = genesis();
+    // Data directory created before the schema version was recorded
+    let dir = TempDir::new("schema_version_unrecorded");
+    let env = dir.open_env();
+    {
+        let mut rwtxn = env.write_txn().unwrap();
+        let _tip: heed::Database<Unit, Unit> =
+            env.create_database(&mut rwtxn, Some("tip")).unwrap();
+        rwtxn.commit().unwrap();
+    }
+    assert!(matches!(
+        new_state(&env, &genesis),
+        Err(state::Error::SchemaVersionMismatch { stored: None, .. })
+    ));
+    // Data directory created with an older schema version
+    let dir = TempDir::new("schema_version_old");
+    let env = dir.open_env();
+    let _state = new_state(&env, &genesis).unwrap();
+    {
+        let mut rwtxn = env.write_txn().unwrap();
+        let schema_version: heed::Database<Unit, SerdeBincode<u32>> = env
+            .open_database(&rwtxn, Some("schema_version"))
+            .unwrap()
+            .unwrap();
+        schema_version
+            .put(&mut rwtxn, &(), &(State::SCHEMA_VERSION - 1))
+            .unwrap();
+        rwtxn.commit().unwrap();
+    }
+    match new_state(&env, &genesis).err() {
+        Some(state::Error::SchemaVersionMismatch { stored, expected }) => {
+            assert_eq!(stored, Some(State::SCHEMA_VERSION - 1));
+            assert_eq!(expected, State::SCHEMA_VERSION);
+        }
+        err => panic!("expected schema version mismatch, but got {err:?}"),
+    }
+}
@@ -0,0 +1,268 @@
+//! End-to-end flows with txs built by the wallet, using the in-process
+//! test harness.
+
+use std::borrow::Cow;
+
+use plain_bitassets::{
+    state::{AmmPair, Genesis},
+    testutil::TestHarness,
+    types::{
+        AssetId, BitAssetData, BitAssetDataUpdates, BitAssetId, Output,
+        OutputContent, ReservationPrep, Transaction, Update,
+    },
+};
+
+/// Reserve and register a BitAsset with the wallet
+fn register(harness: &TestHarness, plain_name: &str, initial_supply: u64) {
+    let mut tx = Transaction::default();
+    let _: ReservationPrep = harness
+        .wallet
+        .reserve_bitasset(&mut tx, plain_name)
+        .unwrap();
+    let _ = harness.submit(tx).unwrap();
+    let _ = harness.mine().unwrap();
+    let mut tx = Transaction::default();
+    let () = harness
+        .wallet
+        .register_bitasset(
+            &mut tx,
+            plain_name,
+            Cow::Owned(BitAssetData::default()),
+            initial_supply,
+        )
+        .unwrap();
+    let _ = harness.submit(tx).unwrap();
+    let _ = harness.mine().unwrap();
+}
+
+/// Total value of a BitAsset in the wallet's UTXOs
+fn wallet_balance(harness: &TestHarness, bitasset: BitAssetId) -> u64 {
+    harness
+        .wallet
+        .get_utxos()
+        .unwrap()
+        .values()
+        .filter_map(|output| output.bitasset_value())
+        .filter(|(output_bitasset, _)| *output_bitasset == bitasset)
+        .map(|(_, value)| value)
+        .sum()
+}
+
+/** Spend the wallet's control coin for a BitAsset in a tx, and send it
+ *  back to the wallet */
+fn pass_through_control(
+    harness: &TestHarness,
+    tx: &mut Transaction,
+    bitasset: BitAssetId,
+) {
+    let (control_outpoint, _) =
+        harness.wallet.select_bitasset_control(bitasset).unwrap();
+    tx.inputs.push(control_outpoint);
+    tx.outputs.push(Output::new(
+        harness.wallet.get_new_address().unwrap(),
+        OutputContent::BitAssetControl,
+    ));
+}
+
+#[test]
+fn register_bitasset_and_amm_mint() {
+    const INITIAL_SUPPLY: u64 = 10_000;
+    let harness =
+        TestHarness::new("register_bitasset_and_amm_mint", &Genesis::default())
+            .unwrap();
+    let _ = harness.fund(1_000_000).unwrap();
+    // Reserve
+    let mut tx = Transaction::default();
+    let _: ReservationPrep = harness
+        .wallet
+        .reserve_bitasset(&mut tx, "newtoken")
+        .unwrap();
+    let _ = harness.submit(tx).unwrap();
+    let _ = harness.mine().unwrap();
+    // Register
+    let mut tx = Transaction::default();
+    let () = harness
+        .wallet
+        .register_bitasset(
+            &mut tx,
+            "newtoken",
+            Cow::Owned(BitAssetData::default()),
+            INITIAL_SUPPLY,
+        )
+        .unwrap();
+    let _ = harness.submit(tx).unwrap();
+    let _ = harness.mine().unwrap();
+    let bitasset_id = BitAssetId::from_plain_name("newtoken");
+    let bitasset = AssetId::BitAsset(bitasset_id);
+    {
+        let rotxn = harness.env.read_txn().unwrap();
+        assert!(harness
+            .state
+            .try_get_current_bitasset_data(&rotxn, &bitasset_id)
+            .unwrap()
+            .is_some());
+    }
+    assert_eq!(
+        harness
+            .wallet
+            .get_utxos()
+            .unwrap()
+            .values()
+            .filter_map(|output| output.asset_value())
+            .filter(|(asset, _)| *asset == bitasset)
+            .map(|(_, value)| value)
+            .sum::<u64>(),
+        INITIAL_SUPPLY
+    );
+    // Mint an AMM position, creating the pool
+    let _ = harness
+        .amm_mint(AssetId::Bitcoin, bitasset, 300_000, 1_000)
+        .unwrap();
+    let _ = harness.mine().unwrap();
+    let rotxn = harness.env.read_txn().unwrap();
+    let amm_pair = AmmPair::new(AssetId::Bitcoin, bitasset);
+    let amm_pool_state = harness
+        .state
+        .amm_pools
+        .get(&rotxn, &amm_pair)
+        .unwrap()
+        .unwrap();
+    let reserves = if amm_pair.asset0() == AssetId::Bitcoin {
+        (amm_pool_state.reserve0, amm_pool_state.reserve1)
+    } else {
+        (amm_pool_state.reserve1, amm_pool_state.reserve0)
+    };
+    assert_eq!(reserves, (300_000, 1_000));
+    assert!(harness
+        .wallet
+        .get_utxos()
+        .unwrap()
+        .values()
+        .any(|output| output.is_lp_token()));
+}
+
+#[test]
+fn update_and_mint_target_selected_bitasset() {
+    const INITIAL_SUPPLY: u64 = 10_000;
+    const MINT_AMOUNT: u64 = 500;
+    let harness = TestHarness::new(
+        "update_and_mint_target_selected_bitasset",
+        &Genesis::default(),
+    )
+    .unwrap();
+    let _ = harness.fund(1_000_000).unwrap();
+    let plain_names = ["first", "second", "third"];
+    for plain_name in plain_names {
+        let () = register(&harness, plain_name, INITIAL_SUPPLY);
+    }
+    let [first, second, third] = plain_names.map(BitAssetId::from_plain_name);
+    // Update the second BitAsset, in a tx that also spends the control coin
+    // for the third BitAsset
+    let commitment = [1; 32];
+    let mut tx = Transaction::default();
+    let () = pass_through_control(&harness, &mut tx, third);
+    let () = harness
+        .wallet
+        .update_bitasset(
+            &mut tx,
+            second,
+            BitAssetDataUpdates {
+                commitment: Update::Set(commitment),
+                ipv4_addr: Update::Retain,
+                ipv6_addr: Update::Retain,
+                encryption_pubkey: Update::Retain,
+                signing_pubkey: Update::Retain,
+            },
+        )
+        .unwrap();
+    let _ = harness.submit(tx).unwrap();
+    let _ = harness.mine().unwrap();
+    {
+        let rotxn = harness.env.read_txn().unwrap();
+        for bitasset in [first, second, third] {
+            let bitasset_data = harness
+                .state
+                .get_current_bitasset_data(&rotxn, &bitasset)
+                .unwrap();
+            let expected_commitment =
+                (bitasset == second).then_some(commitment);
+            assert_eq!(bitasset_data.commitment, expected_commitment);
+        }
+    }
+    // Mint the first BitAsset, in a tx that also spends the control coin
+    // for the third BitAsset
+    let mut tx = Transaction::default();
+    let () = pass_through_control(&harness, &mut tx, third);
+    let () = harness
+        .wallet
+        .mint_bitasset(&mut tx, first, MINT_AMOUNT)
+        .unwrap();
+    let _ = harness.submit(tx).unwrap();
+    let _ = harness.mine().unwrap();
+    let rotxn = harness.env.read_txn().unwrap();
+    for bitasset in [first, second, third] {
+        let expected_supply = if bitasset == first {
+            INITIAL_SUPPLY + MINT_AMOUNT
+        } else {
+            INITIAL_SUPPLY
+        };
+        assert_eq!(
+            harness
+                .state
+                .try_get_bitasset_total_supply(&rotxn, &bitasset)
+                .unwrap(),
+            Some(expected_supply)
+        );
+        assert_eq!(wallet_balance(&harness, bitasset), expected_supply);
+    }
+}
+
+#[test]
+fn bulk_register_bitassets() {
+    const INITIAL_SUPPLY: u64 = 1_000;
+    let harness =
+        TestHarness::new("bulk_register_bitassets", &Genesis::default())
+            .unwrap();
+    let plain_names = ["alpha", "bravo", "charlie", "delta", "echo"];
+    let bulk_register = || {
+        plain_names
+            .iter()
+            .map(|plain_name| {
+                let mut tx = Transaction::default();
+                let registered = harness
+                    .wallet
+                    .register_or_reserve_bitasset(
+                        &mut tx,
+                        plain_name,
+                        Cow::Owned(BitAssetData::default()),
+                        INITIAL_SUPPLY,
+                    )
+                    .unwrap();
+                let _ = harness.submit(tx).unwrap();
+                registered
+            })
+            .collect::<Vec<bool>>()
+    };
+    // The first pass reserves each name
+    assert_eq!(bulk_register(), vec![false; plain_names.len()]);
+    let _ = harness.mine().unwrap();
+    // The second pass registers each name
+    assert_eq!(bulk_register(), vec![true; plain_names.len()]);
+    let _ = harness.mine().unwrap();
+    let rotxn = harness.env.read_txn().unwrap();
+    let mut bitassets: Vec<BitAssetId> = harness
+        .state
+        .bitassets
+        .iter(&rotxn)
+        .unwrap()
+        .map(|res| res.map(|(bitasset_id, _)| bitasset_id))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    bitassets.sort();
+    let mut expected = plain_names.map(BitAssetId::from_plain_name);
+    expected.sort();
+    assert_eq!(bitassets, expected);
+    for bitasset in expected {
+        assert_eq!(wallet_balance(&harness, bitasset), INITIAL_SUPPLY);
+    }
+}
@@ -0,0 +1,92 @@
+//! Check that header timestamps are validated against the median time past,
+//! and against the time of the mainchain block that the header extends.
+
+use plain_bitassets::{
+    archive::{
+        self, check_header_timestamp, InvalidTimestampError,
+        MAX_FUTURE_BLOCK_TIME, MEDIAN_TIME_PAST_WINDOW,
+    },
+    bip300301::bitcoin::{self, hashes::Hash as _},
+    net::PeerLimits,
+    state::Genesis,
+    testutil::TestNode,
+    types::{BlockHash, Body, Header},
+};
+
+const MAIN_BLOCK_TIME: u64 = 1_700_000_000;
+
+fn header(prev_side_hash: BlockHash, timestamp: u64) -> Header {
+    Header {
+        merkle_root: Body::new(Vec::new(), Vec::new()).compute_merkle_root(),
+        prev_side_hash,
+        prev_main_hash: bitcoin::BlockHash::all_zeros(),
+        timestamp,
+    }
+}
+
+#[test]
+fn timestamp_within_mainchain_time_bound() {
+    let timestamps = [
+        MAIN_BLOCK_TIME - 60,
+        MAIN_BLOCK_TIME,
+        MAIN_BLOCK_TIME + MAX_FUTURE_BLOCK_TIME,
+    ];
+    for timestamp in timestamps {
+        assert!(
+            check_header_timestamp(timestamp, None, Some(MAIN_BLOCK_TIME))
+                .is_ok()
+        );
+    }
+}
+
+#[test]
+fn future_dated_timestamp_is_rejected() {
+    let timestamp = MAIN_BLOCK_TIME + MAX_FUTURE_BLOCK_TIME + 1;
+    assert!(matches!(
+        check_header_timestamp(timestamp, None, Some(MAIN_BLOCK_TIME)),
+        Err(InvalidTimestampError::TooFarInFuture {
+            timestamp: rejected,
+            main_block_time: MAIN_BLOCK_TIME,
+        }) if rejected == timestamp
+    ));
+}
+
+#[tokio::test]
+async fn timestamp_must_exceed_median_time_past() {
+    let node = TestNode::new(
+        "header_timestamp_median_time_past",
+        &Genesis::default(),
+        PeerLimits::default(),
+    )
+    .unwrap();
+    let mut rwtxn = node.env.write_txn().unwrap();
+    let mut tip = BlockHash::default();
+    for idx in 0..MEDIAN_TIME_PAST_WINDOW as u64 {
+        let timestamp = MAIN_BLOCK_TIME + 100 * idx;
+        let header = header(tip, timestamp);
+        let () = node.archive.put_header(&mut rwtxn, &header).unwrap();
+        tip = header.hash();
+    }
+    let median_time_past = node
+        .archive
+        .get_median_time_past(&rwtxn, tip)
+        .unwrap()
+        .unwrap();
+    assert_eq!(median_time_past, MAIN_BLOCK_TIME + 500);
+    let stale_header = header(tip, median_time_past);
+    match node.archive.put_header(&mut rwtxn, &stale_header) {
+        Err(archive::Error::InvalidTimestamp(
+            InvalidTimestampError::NotAfterMedianTimePast { .. },
+        )) => (),
+        res => panic!("expected median time past error, got {res:?}"),
+    }
+    assert!(node
+        .archive
+        .try_get_header(&rwtxn, stale_header.hash())
+        .unwrap()
+        .is_none());
+    // Timestamps may be earlier than the parent's, if they are later than
+    // the median time past
+    let header = header(tip, median_time_past + 1);
+    let () = node.archive.put_header(&mut rwtxn, &header).unwrap();
+}
@@ -0,0 +1,329 @@
+//! Check that the mempool evicts txs with the lowest fee rates once its
+//! limits are exceeded, without evicting txs that other txs depend on, and
+//! that conflicting txs can replace mempool txs by paying a higher fee.
+
+use std::path::PathBuf;
+
+use plain_bitassets::{
+    heed,
+    mempool::{self, MemPool, MemPoolInfo, MemPoolLimits},
+    types::{
+        Address, AuthorizedTransaction, BitcoinOutputContent, OutPoint, Output,
+        OutputContent, Transaction, Txid,
+    },
+};
+
+/// Temporary directory, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "plain_bitassets_mempool_{name}_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+struct Setup {
+    env: heed::Env,
+    mempool: MemPool,
+    // Must be dropped after the env
+    _dir: TempDir,
+}
+
+impl Setup {
+    fn new(name: &str, limits: MemPoolLimits) -> Self {
+        let dir = TempDir::new(name);
+        let env = unsafe {
+            heed::EnvOpenOptions::new()
+                .map_size(10 * 1024 * 1024) // 10MB
+                .max_dbs(MemPool::NUM_DBS)
+                .open(&dir.0)
+                .unwrap()
+        };
+        let mempool = MemPool::new(&env, limits).unwrap();
+        Self {
+            env,
+            mempool,
+            _dir: dir,
+        }
+    }
+
+    /// Add a tx paying the specified fee, and return the evicted txids
+    fn put(&self, tx: &AuthorizedTransaction, fee: u64) -> Vec<Txid> {
+        self.try_put(tx, fee).unwrap()
+    }
+
+    /// Add a tx paying the specified fee, committing only if successful
+    fn try_put(
+        &self,
+        tx: &AuthorizedTransaction,
+        fee: u64,
+    ) -> Result<Vec<Txid>, mempool::Error> {
+        let mut rwtxn = self.env.write_txn().unwrap();
+        let evicted = self.mempool.put(&mut rwtxn, tx, fee, None)?;
+        rwtxn.commit().unwrap();
+        Ok(evicted)
+    }
+
+    fn contains(&self, tx: &AuthorizedTransaction) -> bool {
+        let rotxn = self.env.read_txn().unwrap();
+        self.mempool
+            .transactions
+            .get(&rotxn, &tx.transaction.txid())
+            .unwrap()
+            .is_some()
+    }
+
+    fn info(&self) -> MemPoolInfo {
+        let rotxn = self.env.read_txn().unwrap();
+        self.mempool.info(&rotxn).unwrap()
+    }
+}
+
+/// Unsigned tx spending the input, with a single output of the specified
+/// value. The mempool does not validate txs, so the input need not exist.
+fn tx_with_value(input: OutPoint, value: u64) -> AuthorizedTransaction {
+    let output = Output::new(
+        Address::BURN,
+        OutputContent::Value(BitcoinOutputContent(value)),
+    );
+    AuthorizedTransaction {
+        transaction: Transaction::new(vec![input], vec![output]),
+        authorizations: Vec::new(),
+    }
+}
+
+fn tx(input: OutPoint) -> AuthorizedTransaction {
+    tx_with_value(input, 1)
+}
+
+/// Tx spending an output that does not exist in the mempool
+fn independent_tx(seed: u8) -> AuthorizedTransaction {
+    tx(OutPoint::Regular {
+        txid: Txid([seed; 32]),
+        vout: 0,
+    })
+}
+
+/// Tx spending the output of the parent tx
+fn child_tx(parent: &AuthorizedTransaction) -> AuthorizedTransaction {
+    tx(OutPoint::Regular {
+        txid: parent.transaction.txid(),
+        vout: 0,
+    })
+}
+
+#[test]
+fn evict_lowest_fee_rate() {
+    let setup = Setup::new(
+        "evict_lowest_fee_rate",
+        MemPoolLimits {
+            max_txs: 2,
+            ..MemPoolLimits::default()
+        },
+    );
+    let high_fee_tx = independent_tx(1);
+    let low_fee_tx = independent_tx(2);
+    let mid_fee_tx = independent_tx(3);
+    assert_eq!(setup.put(&high_fee_tx, 10), Vec::new());
+    assert_eq!(setup.put(&low_fee_tx, 1), Vec::new());
+    assert_eq!(
+        setup.put(&mid_fee_tx, 5),
+        vec![low_fee_tx.transaction.txid()]
+    );
+    assert!(setup.contains(&high_fee_tx));
+    assert!(!setup.contains(&low_fee_tx));
+    assert!(setup.contains(&mid_fee_tx));
+    // A tx with a lower fee rate than every tx in a full mempool is evicted
+    // immediately
+    let zero_fee_tx = independent_tx(4);
+    assert_eq!(
+        setup.put(&zero_fee_tx, 0),
+        vec![zero_fee_tx.transaction.txid()]
+    );
+    assert!(!setup.contains(&zero_fee_tx));
+    assert_eq!(setup.info().num_txs, 2);
+}
+
+#[test]
+fn evict_by_size() {
+    let tx_size = bincode::serialized_size(&independent_tx(0)).unwrap();
+    let setup = Setup::new(
+        "evict_by_size",
+        MemPoolLimits {
+            max_bytes: 2 * tx_size,
+            ..MemPoolLimits::default()
+        },
+    );
+    let txs: Vec<_> = (1..=3).map(independent_tx).collect();
+    assert_eq!(setup.put(&txs[0], 3), Vec::new());
+    assert_eq!(setup.put(&txs[1], 1), Vec::new());
+    assert_eq!(
+        setup.info(),
+        MemPoolInfo {
+            num_txs: 2,
+            bytes: 2 * tx_size,
+            max_txs: MemPoolLimits::DEFAULT_MAX_TXS,
+            max_bytes: 2 * tx_size,
+        }
+    );
+    assert_eq!(setup.put(&txs[2], 2), vec![txs[1].transaction.txid()]);
+    assert_eq!(setup.info().bytes, 2 * tx_size);
+}
+
+/// A tx is not evicted while a tx that spends its outputs remains in the
+/// mempool, even if it has the lowest fee rate
+#[test]
+fn evict_children_before_parents() {
+    let setup = Setup::new(
+        "evict_children_before_parents",
+        MemPoolLimits {
+            max_txs: 2,
+            ..MemPoolLimits::default()
+        },
+    );
+    let parent_tx = independent_tx(1);
+    let child_tx = child_tx(&parent_tx);
+    let other_tx = independent_tx(2);
+    assert_eq!(setup.put(&parent_tx, 1), Vec::new());
+    assert_eq!(setup.put(&child_tx, 100), Vec::new());
+    assert_eq!(setup.put(&other_tx, 50), vec![other_tx.transaction.txid()]);
+    assert!(setup.contains(&parent_tx));
+    assert!(setup.contains(&child_tx));
+    // Once the child is evicted, the parent can be evicted
+    let high_fee_tx = independent_tx(3);
+    assert_eq!(
+        setup.put(&high_fee_tx, 200),
+        vec![child_tx.transaction.txid()]
+    );
+    assert!(setup.contains(&parent_tx));
+    let mid_fee_tx = independent_tx(4);
+    assert_eq!(
+        setup.put(&mid_fee_tx, 150),
+        vec![parent_tx.transaction.txid()]
+    );
+    assert!(setup.contains(&high_fee_tx));
+    assert!(setup.contains(&mid_fee_tx));
+}
+
+#[test]
+fn replace_by_higher_fee() {
+    let setup = Setup::new("replace_by_higher_fee", MemPoolLimits::default());
+    let input = OutPoint::Regular {
+        txid: Txid([1; 32]),
+        vout: 0,
+    };
+    let original_tx = tx_with_value(input, 10);
+    let child_tx = child_tx(&original_tx);
+    assert_eq!(setup.put(&original_tx, 5), Vec::new());
+    assert_eq!(setup.put(&child_tx, 1), Vec::new());
+    // Replacements must pay a strictly higher fee rate than the conflicting
+    // tx
+    for fee in [4, 5] {
+        let replacement_tx = tx_with_value(input, 20 - fee);
+        match setup.try_put(&replacement_tx, fee) {
+            Err(mempool::Error::InsufficientReplacementFeeRate {
+                txid,
+                conflicting_txid,
+            }) => {
+                assert_eq!(txid, replacement_tx.transaction.txid());
+                assert_eq!(conflicting_txid, original_tx.transaction.txid());
+            }
+            res => panic!("expected insufficient fee rate, but got {res:?}"),
+        }
+        assert!(!setup.contains(&replacement_tx));
+    }
+    // Replacements must pay a strictly higher fee than the replaced tx and
+    // its descendants
+    let replacement_tx = tx_with_value(input, 14);
+    match setup.try_put(&replacement_tx, 6) {
+        Err(mempool::Error::InsufficientReplacementFee {
+            txid,
+            fee,
+            replaced_fee,
+        }) => {
+            assert_eq!(txid, replacement_tx.transaction.txid());
+            assert_eq!((fee, replaced_fee), (6, 6));
+        }
+        res => panic!("expected insufficient fee, but got {res:?}"),
+    }
+    assert!(!setup.contains(&replacement_tx));
+    assert!(setup.contains(&original_tx));
+    assert!(setup.contains(&child_tx));
+    // The replaced tx and its descendants are removed
+    let replacement_tx = tx_with_value(input, 9);
+    assert_eq!(setup.put(&replacement_tx, 7), Vec::new());
+    assert!(setup.contains(&replacement_tx));
+    assert!(!setup.contains(&original_tx));
+    assert!(!setup.contains(&child_tx));
+    assert_eq!(setup.info().num_txs, 1);
+}
+
+/// A larger replacement that pays a higher total fee, but a lower fee rate,
+/// is rejected
+#[test]
+fn replace_by_lower_fee_rate() {
+    let setup =
+        Setup::new("replace_by_lower_fee_rate", MemPoolLimits::default());
+    let input = OutPoint::Regular {
+        txid: Txid([1; 32]),
+        vout: 0,
+    };
+    let original_tx = tx(input);
+    assert_eq!(setup.put(&original_tx, 5), Vec::new());
+    let mut replacement_tx = tx(input);
+    let output = replacement_tx.transaction.outputs[0].clone();
+    replacement_tx.transaction.outputs = vec![output; 20];
+    let original_size = bincode::serialized_size(&original_tx).unwrap();
+    let replacement_size = bincode::serialized_size(&replacement_tx).unwrap();
+    assert!(10 * original_size < 5 * replacement_size);
+    match setup.try_put(&replacement_tx, 10) {
+        Err(mempool::Error::InsufficientReplacementFeeRate {
+            conflicting_txid,
+            ..
+        }) => assert_eq!(conflicting_txid, original_tx.transaction.txid()),
+        res => panic!("expected insufficient fee rate, but got {res:?}"),
+    }
+    assert!(setup.contains(&original_tx));
+    assert!(!setup.contains(&replacement_tx));
+}
+
+/// The size of the mempool is tracked across deletes, and is unchanged when
+/// the mempool is reopened
+#[test]
+fn info_tracks_puts_and_deletes() {
+    let setup =
+        Setup::new("info_tracks_puts_and_deletes", MemPoolLimits::default());
+    let tx_size = bincode::serialized_size(&independent_tx(0)).unwrap();
+    let parent_tx = independent_tx(1);
+    let child_tx = child_tx(&parent_tx);
+    let other_tx = independent_tx(2);
+    for tx in [&parent_tx, &child_tx, &other_tx] {
+        assert_eq!(setup.put(tx, 1), Vec::new());
+    }
+    assert_eq!((setup.info().num_txs, setup.info().bytes), (3, 3 * tx_size));
+    // Deleting a tx also deletes its descendants
+    {
+        let mut rwtxn = setup.env.write_txn().unwrap();
+        setup
+            .mempool
+            .delete(&mut rwtxn, parent_tx.transaction.txid())
+            .unwrap();
+        rwtxn.commit().unwrap();
+    }
+    assert_eq!((setup.info().num_txs, setup.info().bytes), (1, tx_size));
+    let reopened = MemPool::new(&setup.env, MemPoolLimits::default()).unwrap();
+    let rotxn = setup.env.read_txn().unwrap();
+    assert_eq!(reopened.info(&rotxn).unwrap(), setup.info());
+}
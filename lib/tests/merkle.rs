@@ -0,0 +1,173 @@
+//! Check the construction of block body merkle roots, that merkle
+//! proofs for txs in a block body verify against the body's merkle root,
+//! and that bodies with duplicated leaves are rejected.
+
+use plain_bitassets::{
+    bip300301::bitcoin::{self, hashes::Hash as _},
+    state::{Error, Genesis},
+    testutil::TestState,
+    types::{
+        merkle, verify_merkle_proof, Address, BitcoinOutputContent, Body, Hash,
+        Header, MerkleRoot, Output, OutputContent, Transaction,
+    },
+};
+
+fn body(n_coinbase: u64, n_txs: u8) -> Body {
+    let coinbase = (0..n_coinbase)
+        .map(|value| {
+            Output::new(
+                Address([0x11; 20]),
+                OutputContent::Value(BitcoinOutputContent(value)),
+            )
+        })
+        .collect();
+    let transactions = (0..n_txs)
+        .map(|idx| Transaction {
+            memo: vec![idx],
+            ..Default::default()
+        })
+        .collect();
+    Body {
+        coinbase,
+        transactions,
+        authorizations: Vec::new(),
+    }
+}
+
+#[test]
+fn merkle_proof_round_trip() {
+    for (n_coinbase, n_txs) in [(0, 1), (0, 2), (1, 1), (2, 5), (3, 8)] {
+        let body = body(n_coinbase, n_txs);
+        let merkle_root = body.compute_merkle_root();
+        for tx in &body.transactions {
+            let txid = tx.txid();
+            let proof = body.merkle_proof(txid).unwrap();
+            assert!(verify_merkle_proof(merkle_root, txid, &proof));
+            // The proof does not verify for another tx
+            let other_txid = Transaction {
+                memo: b"other".to_vec(),
+                ..Default::default()
+            }
+            .txid();
+            assert!(!verify_merkle_proof(merkle_root, other_txid, &proof));
+            // The proof does not verify at another position in the tree
+            let mut moved = proof.clone();
+            moved.index ^= 1;
+            if (moved.index as u64) < n_coinbase + n_txs as u64 {
+                assert!(!verify_merkle_proof(merkle_root, txid, &moved));
+            }
+        }
+    }
+    // No proof for a tx that is not in the body
+    let body = body(1, 3);
+    let txid = Transaction::default().txid();
+    assert!(body.merkle_proof(txid).is_none());
+}
+
+#[test]
+fn merkle_root_commits_to_tx_order() {
+    let mut body = body(1, 4);
+    let merkle_root = body.compute_merkle_root();
+    body.transactions.swap(1, 2);
+    assert_ne!(body.compute_merkle_root(), merkle_root);
+    body.transactions.swap(1, 2);
+    assert_eq!(body.compute_merkle_root(), merkle_root);
+}
+
+#[test]
+fn single_tx_merkle_root() {
+    // An empty body has the all-zero root
+    assert_eq!(
+        body(0, 0).compute_merkle_root(),
+        MerkleRoot::from(Hash::default())
+    );
+    let body = body(0, 1);
+    let leaf = merkle::tx_leaf(body.transactions[0].txid());
+    assert_eq!(body.compute_merkle_root(), MerkleRoot::from(leaf));
+}
+
+/// Duplicating the last leaves of a tree with an odd number of nodes at
+/// some level does not change the merkle root (CVE-2012-2459), but the
+/// duplicates are detected
+#[test]
+fn duplicated_last_leaves() {
+    for (n_coinbase, n_txs) in [(3, 0), (0, 3), (1, 2), (0, 6)] {
+        let body = body(n_coinbase, n_txs);
+        assert!(!body.has_duplicate_merkle_nodes());
+        let mut mutated = body.clone();
+        if n_txs == 6 {
+            // Duplicate the last pair of leaves
+            mutated
+                .transactions
+                .extend_from_slice(&body.transactions[4..]);
+        } else if n_txs == 0 {
+            mutated.coinbase.push(body.coinbase.last().unwrap().clone());
+        } else {
+            mutated
+                .transactions
+                .push(body.transactions.last().unwrap().clone());
+        }
+        assert_eq!(mutated.compute_merkle_root(), body.compute_merkle_root());
+        assert!(mutated.has_duplicate_merkle_nodes());
+    }
+}
+
+/// Validate a body against a header with the specified merkle root
+fn validate(
+    test_state: &TestState,
+    merkle_root: MerkleRoot,
+    body: &Body,
+) -> Result<(u64, usize), Error> {
+    let rotxn = test_state.env.read_txn().unwrap();
+    let header = Header {
+        merkle_root,
+        prev_side_hash: test_state.state.get_tip(&rotxn).unwrap(),
+        prev_main_hash: bitcoin::BlockHash::all_zeros(),
+        timestamp: 0,
+    };
+    let (fees, filled_txs) =
+        test_state.state.validate_block(&rotxn, &header, body)?;
+    Ok((fees, filled_txs.len()))
+}
+
+#[test]
+fn duplicated_last_leaves_are_rejected() {
+    let test_state =
+        TestState::new("merkle_duplicated_last_leaves", &Genesis::default())
+            .unwrap();
+    // Duplicate coinbase outputs. Outputs have zero value, so that the
+    // body without duplicates is valid.
+    let coinbase_body = Body {
+        coinbase: (0..3)
+            .map(|idx| {
+                Output::new(
+                    Address([idx; 20]),
+                    OutputContent::Value(BitcoinOutputContent(0)),
+                )
+            })
+            .collect(),
+        transactions: Vec::new(),
+        authorizations: Vec::new(),
+    };
+    let merkle_root = coinbase_body.compute_merkle_root();
+    assert_eq!(
+        validate(&test_state, merkle_root, &coinbase_body).unwrap(),
+        (0, 0)
+    );
+    let mut mutated = coinbase_body.clone();
+    mutated.coinbase.push(coinbase_body.coinbase[2].clone());
+    match validate(&test_state, merkle_root, &mutated) {
+        Err(Error::DuplicateMerkleNodes) => (),
+        res => panic!("expected duplicate merkle nodes, but got {res:?}"),
+    }
+    // Duplicate txs
+    let tx_body = body(0, 3);
+    let mut mutated = tx_body.clone();
+    mutated.transactions.push(tx_body.transactions[2].clone());
+    match validate(&test_state, tx_body.compute_merkle_root(), &mutated) {
+        Err(Error::DuplicateTxid { txid }) => {
+            assert_eq!(txid, tx_body.transactions[2].txid())
+        }
+        res => panic!("expected duplicate txid, but got {res:?}"),
+    }
+}
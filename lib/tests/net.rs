@@ -0,0 +1,268 @@
+//! Check peer connection limits, connection direction, address gossip,
+//! and peer scoring.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::StreamExt as _;
+use plain_bitassets::{
+    net::{
+        self, ConnectionDirection, PeerConnectionInfo, PeerConnectionMessage,
+        PeerCounts, PeerLimits, PeerMisbehavior, INITIAL_PEER_SCORE,
+    },
+    state::Genesis,
+    testutil::TestNode,
+};
+
+const ACCEPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn test_node(name: &str, peer_limits: PeerLimits) -> TestNode {
+    TestNode::new(name, &Genesis::default(), peer_limits).unwrap()
+}
+
+/// Connect from `node` to `target`, and accept the connection at `target`
+async fn connect(node: &TestNode, target: &TestNode) {
+    let target_addr = target.net_addr().unwrap();
+    let () = node
+        .net
+        .connect_peer(node.env.clone(), target_addr)
+        .unwrap();
+    let () = tokio::time::timeout(
+        ACCEPT_TIMEOUT,
+        target.net.accept_incoming(target.env.clone()),
+    )
+    .await
+    .expect("timed out accepting connection")
+    .unwrap();
+}
+
+/// Addresses of the peers connected to the node
+fn peers(node: &TestNode) -> Vec<SocketAddr> {
+    node.net
+        .active_peers()
+        .into_iter()
+        .map(|(addr, _)| addr)
+        .collect()
+}
+
+#[tokio::test]
+async fn inbound_peer_limit() {
+    let limited = PeerLimits {
+        max_inbound: 1,
+        ..PeerLimits::default()
+    };
+    let node = test_node("inbound_peer_limit", limited);
+    let peer0 = test_node("inbound_peer_limit_peer0", PeerLimits::default());
+    let peer1 = test_node("inbound_peer_limit_peer1", PeerLimits::default());
+    let peer2 = test_node("inbound_peer_limit_peer2", PeerLimits::default());
+    connect(&peer0, &node).await;
+    let [peer0_addr] = peers(&node)[..] else {
+        panic!("expected exactly one peer")
+    };
+    // peer0 has not sent a new tip or tx, and is evicted
+    connect(&peer1, &node).await;
+    let [peer1_addr] = peers(&node)[..] else {
+        panic!("expected exactly one peer")
+    };
+    assert_ne!(peer0_addr, peer1_addr);
+    assert_eq!(
+        node.net.peer_counts(),
+        PeerCounts {
+            inbound: 1,
+            outbound: 0
+        }
+    );
+    // Useful peers are not evicted, so the new connection is refused
+    node.net.record_useful_peer(peer1_addr);
+    connect(&peer2, &node).await;
+    assert_eq!(peers(&node), vec![peer1_addr]);
+}
+
+#[tokio::test]
+async fn outbound_peer_limit() {
+    let limited = PeerLimits {
+        max_outbound: 1,
+        ..PeerLimits::default()
+    };
+    let node = test_node("outbound_peer_limit", limited);
+    let peer0 = test_node("outbound_peer_limit_peer0", PeerLimits::default());
+    let peer1 = test_node("outbound_peer_limit_peer1", PeerLimits::default());
+    connect(&node, &peer0).await;
+    let peer1_addr = peer1.net_addr().unwrap();
+    match node.net.connect_peer(node.env.clone(), peer1_addr) {
+        Err(net::Error::TooManyOutboundPeers { max: 1 }) => (),
+        res => panic!("expected outbound peer limit error, got {res:?}"),
+    }
+    assert_eq!(
+        node.net.peer_counts(),
+        PeerCounts {
+            inbound: 0,
+            outbound: 1
+        }
+    );
+}
+
+#[tokio::test]
+async fn connection_direction() {
+    let node = test_node("connection_direction", PeerLimits::default());
+    let peer = test_node("connection_direction_peer", PeerLimits::default());
+    connect(&node, &peer).await;
+    let peer_addr = peer.net_addr().unwrap();
+    assert_eq!(
+        node.net.active_peers(),
+        vec![(peer_addr, ConnectionDirection::Outbound)]
+    );
+    let [(_, direction)] = peer.net.active_peers()[..] else {
+        panic!("expected exactly one peer")
+    };
+    assert_eq!(direction, ConnectionDirection::Inbound);
+}
+
+/// Accept all incoming connections to the node, in a background task
+fn accept_all(node: &TestNode) {
+    let net = node.net.clone();
+    let env = node.env.clone();
+    tokio::spawn(async move {
+        while net.accept_incoming(env.clone()).await.is_ok() {}
+    });
+}
+
+/// Handle any connection and peer addrs info received by the node, as the
+/// net task would
+fn handle_peer_info(node: &mut TestNode) {
+    while let Ok(Some((addr, info))) = node.peer_info_rx.try_next() {
+        match info {
+            Some(PeerConnectionInfo::Connected) => {
+                let () =
+                    node.net.handle_peer_connected(&node.env, addr).unwrap();
+            }
+            Some(PeerConnectionInfo::Error(_)) => {
+                let () =
+                    node.net.forget_unreachable_peer(&node.env, addr).unwrap();
+                node.net.remove_active_peer(addr);
+            }
+            Some(PeerConnectionInfo::PeerAddrs { listen_addr, addrs }) => {
+                let () = node
+                    .net
+                    .handle_peer_addrs(&node.env, addr, listen_addr, addrs)
+                    .unwrap();
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Returns `true` if `addr` is in the node's address book
+fn knows_peer(node: &TestNode, addr: SocketAddr) -> bool {
+    let rotxn = node.env.read_txn().unwrap();
+    node.net.known_peers(&rotxn).unwrap().contains(&addr)
+}
+
+#[tokio::test]
+async fn peers_learn_addrs_from_common_peer() {
+    let mut hub = test_node("peer_addrs_hub", PeerLimits::default());
+    let mut peer0 = test_node("peer_addrs_peer0", PeerLimits::default());
+    let mut peer1 = test_node("peer_addrs_peer1", PeerLimits::default());
+    connect(&hub, &peer0).await;
+    connect(&hub, &peer1).await;
+    accept_all(&peer0);
+    accept_all(&peer1);
+    let hub_addr = hub.net_addr().unwrap();
+    let peer0_addr = peer0.net_addr().unwrap();
+    let peer1_addr = peer1.net_addr().unwrap();
+    // Once connected, the hub adds each peer to the address book, and
+    // relays its address to the other peer, which then connects to it
+    let () = tokio::time::timeout(ACCEPT_TIMEOUT, async {
+        while !(knows_peer(&peer0, peer1_addr)
+            && knows_peer(&peer1, peer0_addr))
+        {
+            handle_peer_info(&mut hub);
+            handle_peer_info(&mut peer0);
+            handle_peer_info(&mut peer1);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for peers to learn of each other");
+    assert!(knows_peer(&hub, peer0_addr));
+    assert!(knows_peer(&hub, peer1_addr));
+    // Each peer learns the address on which the hub accepts connections,
+    // but has not connected to it, so it is not added to the address book
+    assert!(!knows_peer(&peer0, hub_addr));
+    assert!(!knows_peer(&peer1, hub_addr));
+}
+
+#[tokio::test]
+async fn peer_addrs_flood_is_penalized() {
+    let node = test_node("peer_addrs_flood", PeerLimits::default());
+    let mut peer = test_node("peer_addrs_flood_peer", PeerLimits::default());
+    connect(&node, &peer).await;
+    let peer_addr = peer.net_addr().unwrap();
+    let addrs: Vec<_> = (1..=1000)
+        .map(|port| SocketAddr::new(Ipv4Addr::new(10, 0, 0, 1).into(), port))
+        .collect();
+    for _ in 0..3 {
+        let () = node
+            .net
+            .push_internal_message(
+                PeerConnectionMessage::PeerAddrs(addrs.clone()),
+                peer_addr,
+            )
+            .unwrap();
+    }
+    let () = tokio::time::timeout(ACCEPT_TIMEOUT, async {
+        loop {
+            match peer.peer_info_rx.next().await {
+                Some((
+                    _,
+                    Some(PeerConnectionInfo::Misbehavior(
+                        PeerMisbehavior::PeerAddrsFlood,
+                    )),
+                )) => break,
+                Some(_) => continue,
+                None => panic!("peer info stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for peer addrs flood to be detected");
+}
+
+#[tokio::test]
+async fn misbehaving_peer_is_banned() {
+    let node = test_node("misbehaving_peer", PeerLimits::default());
+    let peer0 = test_node("misbehaving_peer_peer0", PeerLimits::default());
+    let peer1 = test_node("misbehaving_peer_peer1", PeerLimits::default());
+    connect(&peer0, &node).await;
+    let [peer0_addr] = peers(&node)[..] else {
+        panic!("expected exactly one peer")
+    };
+    assert_eq!(node.net.peer_score(peer0_addr), INITIAL_PEER_SCORE);
+    // Each invalid block reduces the peer's score, until it is banned
+    let penalty = PeerMisbehavior::InvalidBlock.penalty();
+    let num_penalties = INITIAL_PEER_SCORE.div_ceil(penalty);
+    for idx in 1..num_penalties {
+        node.net
+            .penalize_peer(peer0_addr, PeerMisbehavior::InvalidBlock);
+        assert_eq!(
+            node.net.peer_score(peer0_addr),
+            INITIAL_PEER_SCORE - idx * penalty
+        );
+        assert_eq!(peers(&node), vec![peer0_addr]);
+    }
+    node.net
+        .penalize_peer(peer0_addr, PeerMisbehavior::InvalidBlock);
+    assert!(node.net.is_banned(peer0_addr));
+    assert!(peers(&node).is_empty());
+    /* Peers are banned by IP address. All test nodes listen on localhost,
+     * so connections to and from any of them are now refused. */
+    connect(&peer1, &node).await;
+    assert!(peers(&node).is_empty());
+    let peer1_addr = peer1.net_addr().unwrap();
+    match node.net.connect_peer(node.env.clone(), peer1_addr) {
+        Err(net::Error::PeerBanned(addr)) if addr == peer1_addr => (),
+        res => panic!("expected banned peer error, got {res:?}"),
+    }
+}
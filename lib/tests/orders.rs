@@ -0,0 +1,159 @@
+//! Check that limit orders can be partially filled and cancelled, and that
+//! fills and cancellations are reverted when their blocks are disconnected.
+
+use plain_bitassets::{
+    state::{self, Genesis, OrderFillError},
+    testutil::{self, TestKey, TestState, TxBuilder},
+    types::{AssetId, BitAssetId, OrderId, OrderParams},
+};
+
+const INITIAL_BALANCE: u64 = 100_000;
+
+const SELL_AMOUNT: u64 = 1_000;
+
+const PRICE: u64 = 2;
+
+/// Genesis allocating the base asset to the maker, and the quote asset to
+/// the taker
+fn genesis(maker: &TestKey, taker: &TestKey) -> Genesis {
+    testutil::bitassets_genesis(&[
+        ("base", maker, INITIAL_BALANCE),
+        ("quote", taker, INITIAL_BALANCE),
+    ])
+}
+
+fn base() -> AssetId {
+    AssetId::BitAsset(BitAssetId::from_plain_name("base"))
+}
+
+fn quote() -> AssetId {
+    AssetId::BitAsset(BitAssetId::from_plain_name("quote"))
+}
+
+/// Remaining sell amount and received buy amount of the order
+fn order_amounts(test_state: &TestState, order_id: OrderId) -> (u64, u64) {
+    let rotxn = test_state.env.read_txn().unwrap();
+    let order_state = test_state
+        .state
+        .orders
+        .get(&rotxn, &order_id)
+        .unwrap()
+        .unwrap();
+    (
+        order_state.sell_amount_remaining.latest().data,
+        order_state.buy_amount_received.latest().data,
+    )
+}
+
+/// Balances of the base and quote assets owned by the key
+fn balances(test_state: &TestState, key: &TestKey) -> (u64, u64) {
+    let rotxn = test_state.env.read_txn().unwrap();
+    (
+        test_state.balance(&rotxn, key, base()).unwrap(),
+        test_state.balance(&rotxn, key, quote()).unwrap(),
+    )
+}
+
+#[test]
+fn order_partial_fill_and_cancel() {
+    let maker = TestKey::from_seed(1);
+    let taker = TestKey::from_seed(2);
+    let test_state =
+        TestState::new("order_partial_fill", &genesis(&maker, &taker)).unwrap();
+    let state = &test_state.state;
+    let maker_builder = TxBuilder::new(state, &maker);
+    let taker_builder = TxBuilder::new(state, &taker);
+    let place = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        let params = OrderParams {
+            sell_asset: base(),
+            sell_amount: SELL_AMOUNT,
+            buy_asset: quote(),
+            price: PRICE,
+        };
+        maker_builder.order_place(&rotxn, params).unwrap()
+    };
+    let order_id = OrderId(place.transaction.txid());
+    test_state.connect_txs(vec![place]).unwrap();
+    assert_eq!(order_amounts(&test_state, order_id), (SELL_AMOUNT, 0));
+    assert_eq!(
+        balances(&test_state, &maker),
+        (INITIAL_BALANCE - SELL_AMOUNT, 0)
+    );
+    // Fills cannot exceed the remaining amount
+    let overfill = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        taker_builder
+            .order_fill(&rotxn, order_id, SELL_AMOUNT + 1)
+            .unwrap()
+    };
+    match test_state.connect_txs(vec![overfill]) {
+        Err(testutil::Error::State(state::Error::OrderFill(
+            OrderFillError::QuantityTooLarge,
+        ))) => (),
+        res => panic!("expected fill quantity too large, but got {res:?}"),
+    }
+    // Partial fill
+    let fill = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        taker_builder.order_fill(&rotxn, order_id, 300).unwrap()
+    };
+    let fill_header = test_state.connect_txs(vec![fill.clone()]).unwrap();
+    assert_eq!(
+        order_amounts(&test_state, order_id),
+        (SELL_AMOUNT - 300, 300 * PRICE)
+    );
+    assert_eq!(
+        balances(&test_state, &taker),
+        (300, INITIAL_BALANCE - 300 * PRICE)
+    );
+    // Reverting the fill restores the order and balances
+    test_state
+        .disconnect_txs(&fill_header, vec![fill.clone()])
+        .unwrap();
+    assert_eq!(order_amounts(&test_state, order_id), (SELL_AMOUNT, 0));
+    assert_eq!(balances(&test_state, &taker), (0, INITIAL_BALANCE));
+    test_state.connect_txs(vec![fill]).unwrap();
+    let second_fill = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        taker_builder.order_fill(&rotxn, order_id, 200).unwrap()
+    };
+    test_state.connect_txs(vec![second_fill]).unwrap();
+    assert_eq!(
+        order_amounts(&test_state, order_id),
+        (SELL_AMOUNT - 500, 500 * PRICE)
+    );
+    // Cancelling redeems the unfilled remainder and the proceeds
+    let cancel = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        maker_builder.order_cancel(&rotxn, order_id).unwrap()
+    };
+    let cancel_header = test_state.connect_txs(vec![cancel.clone()]).unwrap();
+    assert_eq!(order_amounts(&test_state, order_id), (0, 0));
+    assert_eq!(
+        balances(&test_state, &maker),
+        (INITIAL_BALANCE - 500, 500 * PRICE)
+    );
+    // The order can only be cancelled once, since the receipt is spent
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        assert!(matches!(
+            maker_builder.order_cancel(&rotxn, order_id),
+            Err(testutil::Error::MissingOrderReceipt(_))
+        ));
+    }
+    // Reverting the cancellation restores the order and the receipt
+    test_state
+        .disconnect_txs(&cancel_header, vec![cancel])
+        .unwrap();
+    assert_eq!(
+        order_amounts(&test_state, order_id),
+        (SELL_AMOUNT - 500, 500 * PRICE)
+    );
+    assert_eq!(
+        balances(&test_state, &maker),
+        (INITIAL_BALANCE - SELL_AMOUNT, 0)
+    );
+    let rotxn = test_state.env.read_txn().unwrap();
+    maker_builder.order_cancel(&rotxn, order_id).unwrap();
+}
@@ -0,0 +1,103 @@
+//! Check that reindexing the archive reproduces the tx inclusions that were
+//! indexed when the blocks were stored.
+
+use std::collections::{BTreeMap, HashMap};
+
+use plain_bitassets::{
+    bip300301::bitcoin::{self, hashes::Hash as _},
+    net::PeerLimits,
+    state::Genesis,
+    testutil::TestNode,
+    types::{
+        Authorized, AuthorizedTransaction, BlockHash, Body, Header,
+        Transaction, Txid,
+    },
+};
+
+/// Tx without inputs, distinguished by its memo
+fn tx(memo: u8) -> AuthorizedTransaction {
+    Authorized {
+        transaction: Transaction {
+            memo: vec![memo],
+            ..Default::default()
+        },
+        authorizations: Vec::new(),
+    }
+}
+
+/// Store a block with the specified txs. Returns the block hash.
+fn put_block(
+    node: &TestNode,
+    prev_side_hash: BlockHash,
+    timestamp: u64,
+    txs: Vec<AuthorizedTransaction>,
+) -> BlockHash {
+    let body = Body::new(txs, Vec::new());
+    let header = Header {
+        merkle_root: body.compute_merkle_root(),
+        prev_side_hash,
+        prev_main_hash: bitcoin::BlockHash::all_zeros(),
+        timestamp,
+    };
+    let block_hash = header.hash();
+    let mut rwtxn = node.env.write_txn().unwrap();
+    let () = node.archive.put_header(&mut rwtxn, &header).unwrap();
+    let () = node
+        .archive
+        .put_body(&mut rwtxn, block_hash, &body)
+        .unwrap();
+    rwtxn.commit().unwrap();
+    block_hash
+}
+
+fn tx_inclusions(
+    node: &TestNode,
+    txids: &[Txid],
+) -> HashMap<Txid, BTreeMap<BlockHash, u32>> {
+    let rotxn = node.env.read_txn().unwrap();
+    txids
+        .iter()
+        .map(|txid| {
+            let inclusions =
+                node.archive.get_tx_inclusions(&rotxn, *txid).unwrap();
+            (*txid, inclusions)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn reindex_reproduces_tx_inclusions() {
+    let node = TestNode::new(
+        "reindex_tx_inclusions",
+        &Genesis::default(),
+        PeerLimits::default(),
+    )
+    .unwrap();
+    let txids: Vec<Txid> =
+        (0..4).map(|memo| tx(memo).transaction.txid()).collect();
+    // Block 0 is the parent of two competing blocks, which both include
+    // tx 2, at different indices
+    let block0 = put_block(&node, BlockHash::default(), 1, vec![tx(0), tx(1)]);
+    let block1 = put_block(&node, block0, 2, vec![tx(2)]);
+    let block1_fork = put_block(&node, block0, 3, vec![tx(3), tx(2)]);
+    let expected = HashMap::from([
+        (txids[0], BTreeMap::from([(block0, 0)])),
+        (txids[1], BTreeMap::from([(block0, 1)])),
+        (txids[2], BTreeMap::from([(block1, 0), (block1_fork, 1)])),
+        (txids[3], BTreeMap::from([(block1_fork, 0)])),
+    ]);
+    assert_eq!(tx_inclusions(&node, &txids), expected);
+    {
+        let mut rwtxn = node.env.write_txn().unwrap();
+        let () = node.archive.reindex(&mut rwtxn).unwrap();
+        rwtxn.commit().unwrap();
+    }
+    assert_eq!(tx_inclusions(&node, &txids), expected);
+    // Reindexing is idempotent
+    {
+        let mut rwtxn = node.env.write_txn().unwrap();
+        let () = node.archive.reindex(&mut rwtxn).unwrap();
+        rwtxn.commit().unwrap();
+    }
+    assert_eq!(tx_inclusions(&node, &txids), expected);
+}
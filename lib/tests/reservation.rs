@@ -0,0 +1,147 @@
+//! Check that a BitAsset can be registered in two phases, by broadcasting
+//! the commitment from a reservation prep, and later revealing the nonce,
+//! and that registration previews report whether a name is available.
+
+use plain_bitassets::{
+    state::{self, Genesis, TxValidationStage},
+    testutil::{TestKey, TestState, TxBuilder},
+    types::{AssetId, BitAssetData, BitAssetId, Hash, ReservationPrep},
+};
+
+#[test]
+fn two_phase_registration() {
+    let key = TestKey::from_seed(1);
+    let test_state =
+        TestState::new("two_phase_registration", &Genesis::default()).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let prep = key.reservation_prep("newtoken");
+    assert!(prep.is_valid());
+    assert_eq!(prep.bitasset_id(), BitAssetId::from_plain_name("newtoken"));
+    // Phase 1: broadcast the commitment
+    let reservation = builder.bitasset_reservation("newtoken").unwrap();
+    assert_eq!(
+        reservation.transaction.reservation_commitment(),
+        Some(prep.commitment)
+    );
+    test_state.connect_txs(vec![reservation]).unwrap();
+    // The prep is retained by the client until registration
+    let prep: ReservationPrep =
+        serde_json::from_str(&serde_json::to_string(&prep).unwrap()).unwrap();
+    assert!(prep.is_valid());
+    // A nonce that does not open the commitment is detected
+    let bad_prep = ReservationPrep {
+        nonce: Hash::default(),
+        ..prep.clone()
+    };
+    assert!(!bad_prep.is_valid());
+    // Phase 2: reveal the nonce
+    let bitasset_data = BitAssetData {
+        commitment: Some([7; 32]),
+        ..Default::default()
+    };
+    let registration = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .complete_registration(&rotxn, &prep, bitasset_data.clone(), 1_000)
+            .unwrap()
+    };
+    test_state.connect_txs(vec![registration]).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    let bitasset_id = prep.bitasset_id();
+    assert_eq!(
+        state
+            .try_get_current_bitasset_data(&rotxn, &bitasset_id)
+            .unwrap(),
+        Some(bitasset_data)
+    );
+    let balance: u64 = state
+        .get_utxos(&rotxn)
+        .unwrap()
+        .values()
+        .filter_map(|output| output.asset_value())
+        .filter(|(asset, _)| *asset == AssetId::BitAsset(bitasset_id))
+        .map(|(_, value)| value)
+        .sum();
+    assert_eq!(balance, 1_000);
+}
+
+#[test]
+fn registration_nonce_mismatch() {
+    let key = TestKey::from_seed(1);
+    let test_state =
+        TestState::new("registration_nonce_mismatch", &Genesis::default())
+            .unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let prep = key.reservation_prep("newtoken");
+    let reservation = builder.bitasset_reservation("newtoken").unwrap();
+    test_state.connect_txs(vec![reservation]).unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    // Spends the reservation, but reveals a nonce that does not open its
+    // commitment
+    let bad_prep = ReservationPrep {
+        nonce: [1; 32],
+        ..prep.clone()
+    };
+    let bad_registration = builder
+        .complete_registration(&rotxn, &bad_prep, BitAssetData::default(), 0)
+        .unwrap();
+    match state.validate_transaction(&rotxn, &bad_registration) {
+        Err(state::Error::TxValidation {
+            stage: TxValidationStage::Reservations,
+            source,
+        }) => match *source {
+            state::Error::ReservationCommitmentMismatch {
+                name_hash,
+                implied_commitment,
+            } => {
+                assert_eq!(name_hash, prep.bitasset_id().0);
+                assert_ne!(implied_commitment, prep.commitment);
+            }
+            err => panic!("expected commitment mismatch, but got {err:?}"),
+        },
+        res => panic!("expected reservation error, but got {res:?}"),
+    }
+    let registration = builder
+        .complete_registration(&rotxn, &prep, BitAssetData::default(), 0)
+        .unwrap();
+    assert!(state.validate_transaction(&rotxn, &registration).is_ok());
+}
+
+#[test]
+fn preview_registration() {
+    let key = TestKey::from_seed(1);
+    let test_state =
+        TestState::new("preview_registration", &Genesis::default()).unwrap();
+    let state = &test_state.state;
+    let builder = TxBuilder::new(state, &key);
+    let preview = |plain_name: &str| {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.preview_registration(&rotxn, plain_name).unwrap()
+    };
+    let bitasset_id = BitAssetId::from_plain_name("newtoken");
+    assert_eq!(preview("newtoken"), (bitasset_id, true));
+    // A reservation does not make the name unavailable
+    let reservation = builder.bitasset_reservation("newtoken").unwrap();
+    test_state.connect_txs(vec![reservation]).unwrap();
+    assert_eq!(preview("newtoken"), (bitasset_id, true));
+    let registration = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        builder
+            .bitasset_registration(
+                &rotxn,
+                "newtoken",
+                BitAssetData::default(),
+                1_000,
+            )
+            .unwrap()
+    };
+    test_state.connect_txs(vec![registration]).unwrap();
+    assert_eq!(preview("newtoken"), (bitasset_id, false));
+    // Other names remain available
+    assert_eq!(
+        preview("othertoken"),
+        (BitAssetId::from_plain_name("othertoken"), true)
+    );
+}
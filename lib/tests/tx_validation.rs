@@ -0,0 +1,423 @@
+//! Check that rejected txs report the validation stage at which they were
+//! rejected.
+
+use std::path::PathBuf;
+
+use plain_bitassets::{
+    authorization::{self, get_address, SigningKey},
+    bip300301::bitcoin,
+    heed,
+    state::{self, AmmPair, Genesis, State, TxLimits, TxValidationStage},
+    types::{
+        Address, AssetId, AuthorizedTransaction, BitAssetId,
+        BitcoinOutputContent, FilledOutput, FilledOutputContent, OutPoint,
+        Output, OutputContent, Transaction, TxData, Txid,
+    },
+};
+
+/// Temporary directory, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "plain_bitassets_tx_validation_{name}_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+struct Setup {
+    env: heed::Env,
+    state: State,
+    signing_key: SigningKey,
+    address: Address,
+    /// Bitcoin UTXO owned by `address`
+    utxo: OutPoint,
+    // Must be dropped after the env
+    _dir: TempDir,
+}
+
+const UTXO_VALUE: u64 = 1_000;
+
+/// Tx limits used to check txs at and beyond the boundary counts
+const TX_LIMITS: TxLimits = TxLimits {
+    max_inputs: 2,
+    max_outputs: 3,
+};
+
+/// Genesis config with [`TX_LIMITS`]
+fn tx_limits_genesis() -> Genesis {
+    Genesis {
+        tx_limits: TX_LIMITS,
+        ..Default::default()
+    }
+}
+
+impl Setup {
+    fn new(name: &str) -> Self {
+        Self::with_genesis(name, &Genesis::default())
+    }
+
+    fn with_genesis(name: &str, genesis: &Genesis) -> Self {
+        let dir = TempDir::new(name);
+        let env = unsafe {
+            heed::EnvOpenOptions::new()
+                .map_size(10 * 1024 * 1024) // 10MB
+                .max_dbs(State::NUM_DBS)
+                .open(&dir.0)
+                .unwrap()
+        };
+        let state = State::new(&env, false, genesis).unwrap();
+        let signing_key = SigningKey::from_bytes(&[1; 32]);
+        let address = get_address(&signing_key.verifying_key());
+        let utxo = OutPoint::Regular {
+            txid: Txid([0; 32]),
+            vout: 0,
+        };
+        let setup = Self {
+            env,
+            state,
+            signing_key,
+            address,
+            utxo,
+            _dir: dir,
+        };
+        setup.put_utxo(
+            utxo,
+            FilledOutputContent::Bitcoin(BitcoinOutputContent(UTXO_VALUE)),
+        );
+        setup
+    }
+
+    /// Add `n` Bitcoin UTXOs owned by `address`, including `utxo`
+    fn bitcoin_utxos(&self, n: u32) -> Vec<OutPoint> {
+        (0..n)
+            .map(|vout| {
+                let outpoint = OutPoint::Regular {
+                    txid: Txid([0; 32]),
+                    vout,
+                };
+                self.put_utxo(
+                    outpoint,
+                    FilledOutputContent::Bitcoin(BitcoinOutputContent(
+                        UTXO_VALUE,
+                    )),
+                );
+                outpoint
+            })
+            .collect()
+    }
+
+    /// Add a UTXO owned by `address`
+    fn put_utxo(&self, outpoint: OutPoint, content: FilledOutputContent) {
+        let output = FilledOutput {
+            address: self.address,
+            content,
+            memo: Vec::new(),
+        };
+        let mut rwtxn = self.env.write_txn().unwrap();
+        self.state
+            .utxos
+            .put(&mut rwtxn, &outpoint, &output)
+            .unwrap();
+        rwtxn.commit().unwrap();
+    }
+
+    /// Sign each input of the tx with `signing_key`
+    fn authorize(&self, tx: Transaction) -> AuthorizedTransaction {
+        let addresses_signing_keys: Vec<_> = tx
+            .inputs
+            .iter()
+            .map(|_| (self.address, &self.signing_key))
+            .collect();
+        authorization::authorize(&addresses_signing_keys, tx).unwrap()
+    }
+
+    /// Sign the tx, and return the stage at which the tx was rejected
+    fn rejection_stage(&self, tx: Transaction) -> TxValidationStage {
+        let tx = self.authorize(tx);
+        let rotxn = self.env.read_txn().unwrap();
+        match self.state.validate_transaction(&rotxn, &tx) {
+            Err(state::Error::TxValidation { stage, source: _ }) => stage,
+            res => panic!("expected tx validation error, but got {res:?}"),
+        }
+    }
+
+    fn bitcoin_output(&self, value: u64) -> Output {
+        Output::new(
+            self.address,
+            OutputContent::Value(BitcoinOutputContent(value)),
+        )
+    }
+}
+
+#[test]
+fn valid_tx() {
+    let setup = Setup::new("valid_tx");
+    let tx = Transaction::new(
+        vec![setup.utxo],
+        vec![setup.bitcoin_output(UTXO_VALUE - 1)],
+    );
+    let tx =
+        authorization::authorize(&[(setup.address, &setup.signing_key)], tx)
+            .unwrap();
+    let rotxn = setup.env.read_txn().unwrap();
+    let fee = setup.state.validate_transaction(&rotxn, &tx).unwrap();
+    assert_eq!(fee, 1);
+}
+
+#[test]
+fn missing_utxo() {
+    let setup = Setup::new("missing_utxo");
+    let missing_utxo = OutPoint::Regular {
+        txid: Txid([1; 32]),
+        vout: 0,
+    };
+    let tx = Transaction::new(vec![missing_utxo], vec![]);
+    assert_eq!(setup.rejection_stage(tx), TxValidationStage::Fill);
+}
+
+#[test]
+fn wrong_signing_key() {
+    let setup = Setup::new("wrong_signing_key");
+    let tx = Transaction::new(vec![setup.utxo], vec![]);
+    let wrong_signing_key = SigningKey::from_bytes(&[2; 32]);
+    let rotxn = setup.env.read_txn().unwrap();
+    let wrong_address = get_address(&wrong_signing_key.verifying_key());
+    let tx =
+        authorization::authorize(&[(wrong_address, &wrong_signing_key)], tx)
+            .unwrap();
+    match setup.state.validate_transaction(&rotxn, &tx) {
+        Err(state::Error::TxValidation {
+            stage: TxValidationStage::Authorization,
+            source,
+        }) => match *source {
+            state::Error::WrongPubKeyForAddress { input } => {
+                assert_eq!(input, setup.utxo)
+            }
+            err => panic!("expected wrong pubkey error, but got {err:?}"),
+        },
+        res => panic!("expected authorization error, but got {res:?}"),
+    }
+}
+
+#[test]
+fn tx_at_size_limits() {
+    let setup = Setup::with_genesis("tx_at_size_limits", &tx_limits_genesis());
+    let inputs = setup.bitcoin_utxos(TX_LIMITS.max_inputs as u32);
+    let outputs = (0..TX_LIMITS.max_outputs)
+        .map(|_| setup.bitcoin_output(1))
+        .collect();
+    let tx = Transaction::new(inputs, outputs);
+    let addresses_signing_keys: Vec<_> = tx
+        .inputs
+        .iter()
+        .map(|_| (setup.address, &setup.signing_key))
+        .collect();
+    let tx = authorization::authorize(&addresses_signing_keys, tx).unwrap();
+    let rotxn = setup.env.read_txn().unwrap();
+    let fee = setup.state.validate_transaction(&rotxn, &tx).unwrap();
+    assert_eq!(
+        fee,
+        (TX_LIMITS.max_inputs as u64 * UTXO_VALUE)
+            - TX_LIMITS.max_outputs as u64
+    );
+}
+
+#[test]
+fn too_many_inputs() {
+    let setup = Setup::with_genesis("too_many_inputs", &tx_limits_genesis());
+    let inputs = setup.bitcoin_utxos(TX_LIMITS.max_inputs as u32 + 1);
+    let tx = Transaction::new(inputs, vec![setup.bitcoin_output(1)]);
+    assert_eq!(setup.rejection_stage(tx), TxValidationStage::Size);
+}
+
+#[test]
+fn too_many_outputs() {
+    let setup = Setup::with_genesis("too_many_outputs", &tx_limits_genesis());
+    let outputs = (0..=TX_LIMITS.max_outputs)
+        .map(|_| setup.bitcoin_output(1))
+        .collect();
+    let tx = Transaction::new(vec![setup.utxo], outputs);
+    assert_eq!(setup.rejection_stage(tx), TxValidationStage::Size);
+}
+
+#[test]
+fn unbalanced_reservations() {
+    let setup = Setup::new("unbalanced_reservations");
+    let tx = Transaction::new(
+        vec![setup.utxo],
+        vec![Output::new(
+            setup.address,
+            OutputContent::BitAssetReservation,
+        )],
+    );
+    assert_eq!(setup.rejection_stage(tx), TxValidationStage::Reservations);
+}
+
+#[test]
+fn unbalanced_bitasset_controls() {
+    let setup = Setup::new("unbalanced_bitasset_controls");
+    let tx = Transaction::new(
+        vec![setup.utxo],
+        vec![Output::new(setup.address, OutputContent::BitAssetControl)],
+    );
+    assert_eq!(setup.rejection_stage(tx), TxValidationStage::BitAssets);
+}
+
+#[test]
+fn lp_tokens_not_conserved() {
+    let setup = Setup::new("lp_tokens_not_conserved");
+    let tx = Transaction::new(
+        vec![setup.utxo],
+        vec![Output::new(setup.address, OutputContent::AmmLpToken(1))],
+    );
+    assert_eq!(setup.rejection_stage(tx), TxValidationStage::LpTokens);
+}
+
+#[test]
+fn not_enough_value_in() {
+    let setup = Setup::new("not_enough_value_in");
+    let main_address: bitcoin::Address<bitcoin::address::NetworkUnchecked> =
+        "1BoatSLRHtKNngkdXEeobR76b53LETtpyT".parse().unwrap();
+    let tx = Transaction::new(
+        vec![setup.utxo],
+        vec![Output::new(
+            setup.address,
+            OutputContent::Withdrawal {
+                value: UTXO_VALUE + 1,
+                main_fee: 0,
+                main_address,
+            },
+        )],
+    );
+    assert_eq!(setup.rejection_stage(tx), TxValidationStage::Fee);
+}
+
+#[test]
+fn amm_swap_without_pool() {
+    let setup = Setup::new("amm_swap_without_pool");
+    let bitasset_id = BitAssetId([1; 32]);
+    let bitasset_utxo = OutPoint::Regular {
+        txid: Txid([2; 32]),
+        vout: 0,
+    };
+    setup.put_utxo(
+        bitasset_utxo,
+        FilledOutputContent::BitAsset(bitasset_id, 100),
+    );
+    // Swap 50 BitAsset units for 10 sats, in a pool that does not exist
+    let mut tx = Transaction::new(
+        vec![bitasset_utxo, setup.utxo],
+        vec![
+            Output::new(setup.address, OutputContent::BitAsset(50)),
+            setup.bitcoin_output(UTXO_VALUE),
+        ],
+    );
+    tx.data = Some(TxData::AmmSwap {
+        amount_spent: 50,
+        amount_receive: 10,
+        pair_asset: AssetId::Bitcoin,
+    });
+    let addresses_signing_keys: Vec<_> = tx
+        .inputs
+        .iter()
+        .map(|_| (setup.address, &setup.signing_key))
+        .collect();
+    let tx = authorization::authorize(&addresses_signing_keys, tx).unwrap();
+    let rotxn = setup.env.read_txn().unwrap();
+    match setup.state.validate_transaction(&rotxn, &tx) {
+        Err(state::Error::TxValidation {
+            stage: TxValidationStage::AmmPools,
+            source,
+        }) => match *source {
+            state::Error::MissingAmmPoolState { asset0, asset1 } => {
+                let pair = AmmPair::new(
+                    AssetId::BitAsset(bitasset_id),
+                    AssetId::Bitcoin,
+                );
+                assert_eq!((asset0, asset1), (pair.asset0(), pair.asset1()))
+            }
+            err => panic!("expected missing AMM pool error, but got {err:?}"),
+        },
+        res => panic!("expected AMM pools error, but got {res:?}"),
+    }
+}
+
+/// If only one of two txs can be selected, the higher fee rate tx is selected
+#[test]
+fn select_higher_fee_rate() {
+    let setup = Setup::new("select_higher_fee_rate");
+    let utxos = setup.bitcoin_utxos(2);
+    let low_fee_tx = setup.authorize(Transaction::new(
+        vec![utxos[0]],
+        vec![setup.bitcoin_output(UTXO_VALUE - 1)],
+    ));
+    let high_fee_tx = setup.authorize(Transaction::new(
+        vec![utxos[1]],
+        vec![setup.bitcoin_output(UTXO_VALUE - 10)],
+    ));
+    let high_fee_txid = high_fee_tx.transaction.txid();
+    let rotxn = setup.env.read_txn().unwrap();
+    let selection = setup
+        .state
+        .select_transactions(&rotxn, vec![low_fee_tx, high_fee_tx], 1)
+        .unwrap();
+    let selected: Vec<_> = selection
+        .txs
+        .iter()
+        .map(|tx| tx.transaction.transaction.txid())
+        .collect();
+    assert_eq!(selected, vec![high_fee_txid]);
+    assert_eq!(selection.fees, 10);
+    assert!(selection.rejected.is_empty());
+}
+
+/** If two txs spend the same UTXO, the higher fee rate tx is selected, and
+ *  the other tx is rejected. Invalid txs are rejected. */
+#[test]
+fn select_rejects_double_spends() {
+    let setup = Setup::new("select_rejects_double_spends");
+    let low_fee_tx = setup.authorize(Transaction::new(
+        vec![setup.utxo],
+        vec![setup.bitcoin_output(UTXO_VALUE - 1)],
+    ));
+    let high_fee_tx = setup.authorize(Transaction::new(
+        vec![setup.utxo],
+        vec![setup.bitcoin_output(UTXO_VALUE - 10)],
+    ));
+    let invalid_tx = setup.authorize(Transaction::new(
+        vec![setup.utxo],
+        vec![setup.bitcoin_output(UTXO_VALUE + 1)],
+    ));
+    let low_fee_txid = low_fee_tx.transaction.txid();
+    let high_fee_txid = high_fee_tx.transaction.txid();
+    let invalid_txid = invalid_tx.transaction.txid();
+    let rotxn = setup.env.read_txn().unwrap();
+    let selection = setup
+        .state
+        .select_transactions(
+            &rotxn,
+            vec![invalid_tx, low_fee_tx, high_fee_tx],
+            10,
+        )
+        .unwrap();
+    let selected: Vec<_> = selection
+        .txs
+        .iter()
+        .map(|tx| tx.transaction.transaction.txid())
+        .collect();
+    assert_eq!(selected, vec![high_fee_txid]);
+    assert_eq!(selection.fees, 10);
+    assert_eq!(selection.rejected, vec![invalid_txid, low_fee_txid]);
+}
@@ -0,0 +1,39 @@
+//! Check that an encrypted wallet can only be unlocked for up to the
+//! maximum unlock timeout.
+
+use std::time::Duration;
+
+use plain_bitassets::{
+    state::Genesis,
+    testutil::TestHarness,
+    wallet::{self, Wallet},
+};
+
+const PASSPHRASE: &str = "passphrase";
+
+#[test]
+fn unlock_timeout_limit() {
+    let harness =
+        TestHarness::new("wallet_unlock_timeout_limit", &Genesis::default())
+            .unwrap();
+    let wallet = &harness.wallet;
+    let () = wallet.encrypt(PASSPHRASE).unwrap();
+    assert!(wallet.is_locked().unwrap());
+    // Timeouts that would overflow are rejected, rather than panicking
+    for timeout in [
+        Duration::from_secs(u64::MAX),
+        Wallet::MAX_UNLOCK_TIMEOUT + Duration::from_secs(1),
+    ] {
+        match wallet.unlock(PASSPHRASE, timeout) {
+            Err(wallet::Error::UnlockTimeoutTooLong { max }) => {
+                assert_eq!(max, Wallet::MAX_UNLOCK_TIMEOUT)
+            }
+            res => panic!("expected unlock timeout error, but got {res:?}"),
+        }
+        assert!(wallet.is_locked().unwrap());
+    }
+    let () = wallet
+        .unlock(PASSPHRASE, Wallet::MAX_UNLOCK_TIMEOUT)
+        .unwrap();
+    assert!(!wallet.is_locked().unwrap());
+}
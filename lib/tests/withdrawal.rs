@@ -0,0 +1,440 @@
+//! Check withdrawal estimates against pending withdrawals, withdrawal bundle
+//! lookups for withdrawal outputs, the encoding of outputs spent by
+//! withdrawal bundles, and the inpoints that spend outputs.
+
+use std::collections::BTreeMap;
+
+use plain_bitassets::{
+    bip300301::{bitcoin, TwoWayPegData, WithdrawalBundleStatus},
+    state::{Genesis, State},
+    testutil::{TestKey, TestState},
+    types::{
+        Address, BitcoinOutputContent, FilledOutput, FilledOutputContent,
+        InPoint, OutPoint, Output, OutputContent, SpentOutput, Transaction,
+        Txid, WithdrawalBundle, WithdrawalBundleInfo, WithdrawalBundleRecord,
+        WithdrawalStatus,
+    },
+    util::UnitKey,
+};
+
+fn main_address(
+    address: &str,
+) -> bitcoin::Address<bitcoin::address::NetworkUnchecked> {
+    address.parse().unwrap()
+}
+
+/// Add a pending withdrawal output
+fn put_withdrawal(
+    test_state: &TestState,
+    vout: u32,
+    main_address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+    value: u64,
+    main_fee: u64,
+) {
+    let outpoint = OutPoint::Regular {
+        txid: Txid([0; 32]),
+        vout,
+    };
+    let output = FilledOutput::new(
+        Address::BURN,
+        FilledOutputContent::BitcoinWithdrawal {
+            value,
+            main_fee,
+            main_address,
+        },
+    );
+    let mut rwtxn = test_state.env.write_txn().unwrap();
+    test_state
+        .state
+        .utxos
+        .put(&mut rwtxn, &outpoint, &output)
+        .unwrap();
+    rwtxn.commit().unwrap();
+}
+
+#[test]
+fn estimate_withdrawal() {
+    let test_state =
+        TestState::new("estimate_withdrawal", &Genesis::default()).unwrap();
+    let pending_address = main_address("1BoatSLRHtKNngkdXEeobR76b53LETtpyT");
+    let new_address = main_address("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa");
+    // No pending withdrawals
+    {
+        let rotxn = test_state.env.read_txn().unwrap();
+        let estimate = test_state
+            .state
+            .estimate_withdrawal(&rotxn, new_address.clone(), 10_000, 1_000)
+            .unwrap();
+        assert_eq!(estimate.weight, State::BUNDLE_OUTPUT_WEIGHT);
+        assert_eq!(
+            estimate.bundle_weight,
+            State::BUNDLE_0_WEIGHT + State::BUNDLE_OUTPUT_WEIGHT
+        );
+        assert_eq!(estimate.pending_bundle_outputs, 0);
+        assert_eq!(estimate.max_bundle_outputs, State::MAX_BUNDLE_OUTPUTS);
+        assert!(estimate.fits_in_bundle);
+    }
+    put_withdrawal(&test_state, 0, pending_address.clone(), 50_000, 2_000);
+    let rotxn = test_state.env.read_txn().unwrap();
+    // Withdrawals to the same address are aggregated into one output
+    let estimate = test_state
+        .state
+        .estimate_withdrawal(&rotxn, pending_address, 10_000, 1_000)
+        .unwrap();
+    assert_eq!(estimate.weight, 0);
+    assert_eq!(
+        estimate.bundle_weight,
+        State::BUNDLE_0_WEIGHT + State::BUNDLE_OUTPUT_WEIGHT
+    );
+    assert_eq!(estimate.pending_bundle_outputs, 1);
+    assert!(estimate.fits_in_bundle);
+    // Withdrawals to a new address add an output
+    let estimate = test_state
+        .state
+        .estimate_withdrawal(&rotxn, new_address, 10_000, 1_000)
+        .unwrap();
+    assert_eq!(estimate.weight, State::BUNDLE_OUTPUT_WEIGHT);
+    assert_eq!(
+        estimate.bundle_weight,
+        State::BUNDLE_0_WEIGHT + 2 * State::BUNDLE_OUTPUT_WEIGHT
+    );
+    assert_eq!(estimate.pending_bundle_outputs, 1);
+    assert!(estimate.fits_in_bundle);
+}
+
+/// Withdrawal bundle spending the specified outputs. Bundles with distinct
+/// lock times have distinct m6ids.
+fn withdrawal_bundle(
+    lock_time: u32,
+    spend_utxos: &[(OutPoint, FilledOutput)],
+) -> WithdrawalBundle {
+    WithdrawalBundle {
+        spend_utxos: spend_utxos.iter().cloned().collect::<BTreeMap<_, _>>(),
+        transaction: bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::from_consensus(lock_time),
+            input: Vec::new(),
+            output: Vec::new(),
+        },
+    }
+}
+
+/// Withdrawal outputs and bundles added by [`put_withdrawal_bundles`]
+struct WithdrawalBundles {
+    confirmed: (OutPoint, FilledOutput),
+    failed: (OutPoint, FilledOutput),
+    pending: (OutPoint, FilledOutput),
+    unbundled: (OutPoint, FilledOutput),
+    /// Confirmed at height 1
+    confirmed_bundle: WithdrawalBundle,
+    /// Failed at height 2
+    failed_bundle: WithdrawalBundle,
+    /// Pending since height 3
+    pending_bundle: WithdrawalBundle,
+}
+
+/// Add confirmed, failed, and pending withdrawal bundles, each spending one
+/// withdrawal output, and an unbundled withdrawal output
+fn put_withdrawal_bundles(test_state: &TestState) -> WithdrawalBundles {
+    let state = &test_state.state;
+    let withdrawal = |vout| {
+        let outpoint = OutPoint::Regular {
+            txid: Txid([0; 32]),
+            vout,
+        };
+        let output = FilledOutput::new(
+            Address::BURN,
+            FilledOutputContent::BitcoinWithdrawal {
+                value: 10_000,
+                main_fee: 1_000,
+                main_address: main_address(
+                    "1BoatSLRHtKNngkdXEeobR76b53LETtpyT",
+                ),
+            },
+        );
+        (outpoint, output)
+    };
+    let (confirmed, failed, pending, unbundled) =
+        (withdrawal(0), withdrawal(1), withdrawal(2), withdrawal(3));
+    let confirmed_bundle = withdrawal_bundle(0, &[confirmed.clone()]);
+    let failed_bundle = withdrawal_bundle(1, &[failed.clone()]);
+    let pending_bundle = withdrawal_bundle(2, &[pending.clone()]);
+    let mut rwtxn = test_state.env.write_txn().unwrap();
+    for (bundle, (outpoint, output)) in
+        [(&confirmed_bundle, &confirmed), (&pending_bundle, &pending)]
+    {
+        let spent_output = SpentOutput {
+            output: output.clone(),
+            inpoint: InPoint::Withdrawal {
+                m6id: bundle.compute_m6id(),
+            },
+        };
+        state
+            .stxos
+            .put(&mut rwtxn, outpoint, &spent_output)
+            .unwrap();
+    }
+    // Outputs from failed bundles are returned to the UTXO set
+    for (outpoint, output) in [&failed, &unbundled] {
+        state.utxos.put(&mut rwtxn, outpoint, output).unwrap();
+    }
+    state
+        .withdrawal_bundles
+        .put(
+            &mut rwtxn,
+            &1,
+            &(confirmed_bundle.clone(), WithdrawalBundleStatus::Confirmed),
+        )
+        .unwrap();
+    state
+        .withdrawal_bundles
+        .put(
+            &mut rwtxn,
+            &2,
+            &(failed_bundle.clone(), WithdrawalBundleStatus::Failed),
+        )
+        .unwrap();
+    state
+        .pending_withdrawal_bundle
+        .put(&mut rwtxn, &UnitKey, &(pending_bundle.clone(), 3))
+        .unwrap();
+    rwtxn.commit().unwrap();
+    WithdrawalBundles {
+        confirmed,
+        failed,
+        pending,
+        unbundled,
+        confirmed_bundle,
+        failed_bundle,
+        pending_bundle,
+    }
+}
+
+#[test]
+fn withdrawal_bundle_for_utxo() {
+    let test_state =
+        TestState::new("withdrawal_bundle_for_utxo", &Genesis::default())
+            .unwrap();
+    let state = &test_state.state;
+    let WithdrawalBundles {
+        confirmed,
+        failed,
+        pending,
+        unbundled,
+        confirmed_bundle,
+        failed_bundle,
+        pending_bundle,
+    } = put_withdrawal_bundles(&test_state);
+    let rotxn = test_state.env.read_txn().unwrap();
+    let bundle_for_utxo = |outpoint| {
+        state
+            .try_get_withdrawal_bundle_for_utxo(&rotxn, outpoint)
+            .unwrap()
+    };
+    let bundle_info = |bundle: &WithdrawalBundle, status| {
+        Some(WithdrawalBundleInfo {
+            m6id: bundle.compute_m6id(),
+            status,
+        })
+    };
+    assert_eq!(
+        bundle_for_utxo(&confirmed.0),
+        bundle_info(&confirmed_bundle, WithdrawalStatus::Confirmed)
+    );
+    assert_eq!(
+        bundle_for_utxo(&failed.0),
+        bundle_info(&failed_bundle, WithdrawalStatus::Failed)
+    );
+    assert_eq!(
+        bundle_for_utxo(&pending.0),
+        bundle_info(&pending_bundle, WithdrawalStatus::Submitted)
+    );
+    assert_eq!(bundle_for_utxo(&unbundled.0), None);
+}
+
+#[test]
+fn list_withdrawal_bundles() {
+    let test_state =
+        TestState::new("list_withdrawal_bundles", &Genesis::default()).unwrap();
+    let WithdrawalBundles {
+        confirmed,
+        failed,
+        pending,
+        unbundled: _,
+        confirmed_bundle,
+        failed_bundle,
+        pending_bundle,
+    } = put_withdrawal_bundles(&test_state);
+    let rotxn = test_state.env.read_txn().unwrap();
+    let bundles = test_state.state.list_withdrawal_bundles(&rotxn).unwrap();
+    let record = |bundle: &WithdrawalBundle, outpoint, status, height| {
+        WithdrawalBundleRecord {
+            m6id: bundle.compute_m6id(),
+            status,
+            height,
+            spent_outpoints: vec![outpoint],
+        }
+    };
+    assert_eq!(
+        bundles,
+        vec![
+            record(
+                &confirmed_bundle,
+                confirmed.0,
+                WithdrawalStatus::Confirmed,
+                1
+            ),
+            record(&failed_bundle, failed.0, WithdrawalStatus::Failed, 2),
+            record(&pending_bundle, pending.0, WithdrawalStatus::Submitted, 3),
+        ]
+    );
+}
+
+#[test]
+fn spent_output_withdrawal_inpoint_serde() {
+    let test_state = TestState::new(
+        "spent_output_withdrawal_inpoint_serde",
+        &Genesis::default(),
+    )
+    .unwrap();
+    let outpoint = OutPoint::Regular {
+        txid: Txid([0; 32]),
+        vout: 0,
+    };
+    let output = FilledOutput::new(
+        Address::BURN,
+        FilledOutputContent::BitcoinWithdrawal {
+            value: 10_000,
+            main_fee: 1_000,
+            main_address: main_address("1BoatSLRHtKNngkdXEeobR76b53LETtpyT"),
+        },
+    );
+    let bundle = withdrawal_bundle(0, &[(outpoint, output.clone())]);
+    let spent_output = SpentOutput {
+        output,
+        inpoint: InPoint::Withdrawal {
+            m6id: bundle.compute_m6id(),
+        },
+    };
+    // The m6id is encoded as the txid of the mainchain bundle tx
+    let json = serde_json::to_value(&spent_output).unwrap();
+    assert_eq!(
+        json["inpoint"]["Withdrawal"]["m6id"],
+        bundle.transaction.txid().to_string()
+    );
+    assert_eq!(
+        serde_json::from_value::<SpentOutput>(json).unwrap(),
+        spent_output
+    );
+    let bytes = bincode::serialize(&spent_output).unwrap();
+    assert_eq!(
+        bincode::deserialize::<SpentOutput>(&bytes).unwrap(),
+        spent_output
+    );
+    // Round trip through the STXO db
+    let mut rwtxn = test_state.env.write_txn().unwrap();
+    let () = test_state
+        .state
+        .stxos
+        .put(&mut rwtxn, &outpoint, &spent_output)
+        .unwrap();
+    rwtxn.commit().unwrap();
+    let rotxn = test_state.env.read_txn().unwrap();
+    assert_eq!(
+        test_state.state.stxos.get(&rotxn, &outpoint).unwrap(),
+        Some(spent_output)
+    );
+}
+
+#[test]
+fn spent_by_regular_and_withdrawal() {
+    let key = TestKey::from_seed(1);
+    let test_state =
+        TestState::new("spent_by_regular_and_withdrawal", &Genesis::default())
+            .unwrap();
+    let state = &test_state.state;
+    let regular = OutPoint::Regular {
+        txid: Txid([1; 32]),
+        vout: 0,
+    };
+    let withdrawal = OutPoint::Regular {
+        txid: Txid([2; 32]),
+        vout: 0,
+    };
+    {
+        let mut rwtxn = test_state.env.write_txn().unwrap();
+        let output = FilledOutput::new(
+            key.address,
+            FilledOutputContent::Bitcoin(BitcoinOutputContent(1_000)),
+        );
+        state.utxos.put(&mut rwtxn, &regular, &output).unwrap();
+        let output = FilledOutput::new(
+            Address::BURN,
+            FilledOutputContent::BitcoinWithdrawal {
+                value: 10_000,
+                main_fee: 1_000,
+                main_address: main_address(
+                    "1BoatSLRHtKNngkdXEeobR76b53LETtpyT",
+                ),
+            },
+        );
+        state.utxos.put(&mut rwtxn, &withdrawal, &output).unwrap();
+        rwtxn.commit().unwrap();
+    }
+    let spent_by = |outpoint| {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.get_spent_by(&rotxn, outpoint).unwrap()
+    };
+    assert_eq!(spent_by(&regular), None);
+    assert_eq!(spent_by(&withdrawal), None);
+    // Spent by a tx input
+    let tx = Transaction::new(
+        vec![regular],
+        vec![Output::new(
+            key.address,
+            OutputContent::Value(BitcoinOutputContent(1_000)),
+        )],
+    );
+    let txid = tx.txid();
+    test_state
+        .connect_txs(vec![key.authorize(tx).unwrap()])
+        .unwrap();
+    assert_eq!(spent_by(&regular), Some(InPoint::Regular { txid, vin: 0 }));
+    assert_eq!(spent_by(&withdrawal), None);
+    // Spent by a withdrawal bundle, which is only collected after the
+    // failure gap
+    let height = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state.get_height(&rotxn).unwrap()
+    };
+    for _ in height..=State::WITHDRAWAL_BUNDLE_FAILURE_GAP {
+        test_state.connect_txs(Vec::new()).unwrap();
+    }
+    {
+        let two_way_peg_data = TwoWayPegData {
+            deposits: Default::default(),
+            deposit_block_hash: None,
+            bundle_statuses: Default::default(),
+        };
+        let mut rwtxn = test_state.env.write_txn().unwrap();
+        let () = state
+            .connect_two_way_peg_data(&mut rwtxn, &two_way_peg_data)
+            .unwrap();
+        rwtxn.commit().unwrap();
+    }
+    let (bundle, _) = {
+        let rotxn = test_state.env.read_txn().unwrap();
+        state
+            .pending_withdrawal_bundle
+            .get(&rotxn, &UnitKey)
+            .unwrap()
+            .unwrap()
+    };
+    assert_eq!(
+        spent_by(&withdrawal),
+        Some(InPoint::Withdrawal {
+            m6id: bundle.compute_m6id()
+        })
+    );
+    assert_eq!(spent_by(&regular), Some(InPoint::Regular { txid, vin: 0 }));
+}
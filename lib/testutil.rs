@@ -0,0 +1,1077 @@
+//! Builders for valid transactions, networked test nodes, and an in-process
+//! harness with a wallet, for use in tests.
+//! Available with the `test-util` feature.
+//!
+//! Transactions are built against the current state, spending UTXOs owned by
+//! a single [`TestKey`]. UTXOs are selected in [`OutPoint`] order, so the
+//! same state and key always produce the same transactions.
+
+use std::{
+    collections::HashSet,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
+
+use bip300301::bitcoin::{self, hashes::Hash as _};
+use heed::{RoTxn, RwTxn};
+
+use crate::{
+    archive::{self, Archive},
+    authorization::{self, get_address, SigningKey},
+    mempool::{self, MemPool, MemPoolLimits},
+    net::{self, Net, PeerInfoRx, PeerLimits},
+    state::{self, AmmPair, AmmPoolState, State},
+    types::{
+        Address, AssetId, AtomicSwapId, AtomicSwapParams,
+        AuthorizedTransaction, BitAssetData, BitAssetDataUpdates, BitAssetId,
+        BitcoinOutputContent, Body, DutchAuctionId, DutchAuctionParams,
+        FilledOutput, FilledOutputContent, Hash, Header, Network, OrderId,
+        OrderParams, OutPoint, Output, OutputContent, ReservationPrep,
+        Transaction, TxData, Txid,
+    },
+    wallet::{self, Wallet},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("archive error")]
+    Archive(#[from] archive::Error),
+    #[error("authorization error")]
+    Authorization(#[from] authorization::Error),
+    #[error("heed error")]
+    Heed(#[from] heed::Error),
+    #[error("mempool error")]
+    MemPool(#[from] mempool::Error),
+    #[error("insufficient funds: {available} < {required} of {asset}")]
+    InsufficientFunds {
+        asset: AssetId,
+        available: u64,
+        required: u64,
+    },
+    #[error(
+        "insufficient LP tokens for {asset0}-{asset1}: {available} < \
+         {required}"
+    )]
+    InsufficientLpTokens {
+        asset0: AssetId,
+        asset1: AssetId,
+        available: u64,
+        required: u64,
+    },
+    #[error("missing AMM pool state for {asset0}-{asset1}")]
+    MissingAmmPool { asset0: AssetId, asset1: AssetId },
+    #[error("missing atomic swap {0}")]
+    MissingAtomicSwap(AtomicSwapId),
+    #[error("missing BitAsset control coin for {bitasset}")]
+    MissingBitAssetControl { bitasset: BitAssetId },
+    #[error("missing BitAsset reservation for `{plain_name}`")]
+    MissingBitAssetReservation { plain_name: String },
+    #[error("missing Dutch auction {0}")]
+    MissingDutchAuction(DutchAuctionId),
+    #[error("missing order {0}")]
+    MissingOrder(OrderId),
+    #[error("missing order receipt for {0}")]
+    MissingOrderReceipt(OrderId),
+    #[error("net error")]
+    Net(#[from] net::Error),
+    #[error("state error")]
+    State(#[from] state::Error),
+    #[error("wallet error")]
+    Wallet(#[from] wallet::Error),
+}
+
+/// A signing key and the address that it controls
+#[derive(Clone)]
+pub struct TestKey {
+    pub signing_key: SigningKey,
+    pub address: Address,
+}
+
+impl TestKey {
+    /// Deterministic key, derived from a seed byte
+    pub fn from_seed(seed: u8) -> Self {
+        let signing_key = SigningKey::from_bytes(&[seed; 32]);
+        let address = get_address(&signing_key.verifying_key());
+        Self {
+            signing_key,
+            address,
+        }
+    }
+
+    /// Commitment and nonce used to reserve and register a BitAsset
+    pub fn reservation_prep(&self, plain_name: &str) -> ReservationPrep {
+        ReservationPrep::new(self.signing_key.as_bytes(), plain_name)
+    }
+
+    /// Sign all inputs of a tx, which must all be owned by this key
+    pub fn authorize(
+        &self,
+        tx: Transaction,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let addresses_signing_keys: Vec<_> = tx
+            .inputs
+            .iter()
+            .map(|_| (self.address, &self.signing_key))
+            .collect();
+        let tx = authorization::authorize(&addresses_signing_keys, tx)?;
+        Ok(tx)
+    }
+}
+
+/// Temporary directory, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` must be unique among concurrently running tests
+    fn new(name: &str) -> Result<Self, Error> {
+        let dir = std::env::temp_dir().join(format!(
+            "plain_bitassets_testutil_{name}_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).map_err(heed::Error::Io)?;
+        Ok(Self(dir))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// State in a temporary directory, removed on drop
+pub struct TestState {
+    pub env: heed::Env,
+    pub state: State,
+    // Must be dropped after the env
+    _dir: TempDir,
+}
+
+impl TestState {
+    /// Create a new state in a temporary directory. `name` must be unique
+    /// among concurrently running tests.
+    pub fn new(name: &str, genesis: &state::Genesis) -> Result<Self, Error> {
+        Self::open(name, false, genesis)
+    }
+
+    /// As [`Self::new`], with the address activity index enabled
+    pub fn with_address_activity_index(
+        name: &str,
+        genesis: &state::Genesis,
+    ) -> Result<Self, Error> {
+        Self::open(name, true, genesis)
+    }
+
+    fn open(
+        name: &str,
+        index_address_activity: bool,
+        genesis: &state::Genesis,
+    ) -> Result<Self, Error> {
+        let dir = TempDir::new(name)?;
+        let env = unsafe {
+            heed::EnvOpenOptions::new()
+                .map_size(10 * 1024 * 1024) // 10MB
+                .max_dbs(State::NUM_DBS)
+                .open(&dir.0)?
+        };
+        let state = State::new(&env, index_address_activity, genesis)?;
+        Ok(Self {
+            env,
+            state,
+            _dir: dir,
+        })
+    }
+
+    /// Validate and connect a block containing the specified txs
+    pub fn connect_txs(
+        &self,
+        txs: Vec<AuthorizedTransaction>,
+    ) -> Result<Header, Error> {
+        let mut rwtxn = self.env.write_txn()?;
+        let header = connect_txs(&self.state, &mut rwtxn, txs)?;
+        rwtxn.commit()?;
+        Ok(header)
+    }
+
+    /** Validate and connect a block containing the specified txs and
+     *  coinbase outputs. Returns the header and body of the block. */
+    pub fn connect_block(
+        &self,
+        txs: Vec<AuthorizedTransaction>,
+        coinbase: Vec<Output>,
+    ) -> Result<(Header, Body), Error> {
+        let mut rwtxn = self.env.write_txn()?;
+        let res = connect_block(&self.state, &mut rwtxn, txs, coinbase)?;
+        rwtxn.commit()?;
+        Ok(res)
+    }
+
+    /// Disconnect the tip, which must be the specified block
+    pub fn disconnect_block(
+        &self,
+        header: &Header,
+        body: &Body,
+    ) -> Result<(), Error> {
+        let mut rwtxn = self.env.write_txn()?;
+        let () = self.state.disconnect_tip(&mut rwtxn, header, body)?;
+        rwtxn.commit()?;
+        Ok(())
+    }
+
+    /** Disconnect the tip, which must be the block with the specified header
+     *  that was connected by [`Self::connect_txs`] with the same txs */
+    pub fn disconnect_txs(
+        &self,
+        header: &Header,
+        txs: Vec<AuthorizedTransaction>,
+    ) -> Result<(), Error> {
+        self.disconnect_block(header, &Body::new(txs, Vec::new()))
+    }
+
+    /// Total value of the specified asset owned by the key
+    pub fn balance(
+        &self,
+        rotxn: &RoTxn,
+        key: &TestKey,
+        asset: AssetId,
+    ) -> Result<u64, Error> {
+        let addresses = HashSet::from([key.address]);
+        let balance = self
+            .state
+            .get_utxos_by_addresses(rotxn, &addresses)?
+            .values()
+            .filter_map(|output| output.asset_value())
+            .filter(|(output_asset, _)| *output_asset == asset)
+            .map(|(_, value)| value)
+            .sum();
+        Ok(balance)
+    }
+}
+
+/** Net, state, and archive in a temporary directory, removed on drop.
+ *  The net listens on an arbitrary free port on localhost.
+ *  Incoming connections are only accepted by calling
+ *  [`Net::accept_incoming`], and peer connection info is not handled
+ *  unless received from `peer_info_rx`. */
+pub struct TestNode {
+    pub env: heed::Env,
+    pub archive: Archive,
+    pub state: State,
+    pub net: Net,
+    pub peer_info_rx: PeerInfoRx,
+    // Must be dropped after the env
+    _dir: TempDir,
+}
+
+impl TestNode {
+    /// Must be called from within a tokio runtime.
+    /// `name` must be unique among concurrently running tests.
+    pub fn new(
+        name: &str,
+        genesis: &state::Genesis,
+        peer_limits: PeerLimits,
+    ) -> Result<Self, Error> {
+        let dir = TempDir::new(name)?;
+        let env = unsafe {
+            heed::EnvOpenOptions::new()
+                .map_size(10 * 1024 * 1024) // 10MB
+                .max_dbs(State::NUM_DBS + Archive::NUM_DBS + Net::NUM_DBS)
+                .open(&dir.0)?
+        };
+        let state = State::new(&env, false, genesis)?;
+        let archive = Archive::new(&env)?;
+        let bind_addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+        let (net, peer_info_rx) = Net::new(
+            &env,
+            archive.clone(),
+            Network::Regtest,
+            state.clone(),
+            bind_addr,
+            peer_limits,
+        )?;
+        Ok(Self {
+            env,
+            archive,
+            state,
+            net,
+            peer_info_rx,
+            _dir: dir,
+        })
+    }
+
+    /// Address on which the net accepts incoming connections
+    pub fn net_addr(&self) -> Result<SocketAddr, Error> {
+        let addr = self.net.server.local_addr().map_err(net::Error::from)?;
+        Ok(addr)
+    }
+}
+
+/** State, mempool, and a seeded wallet, in a temporary directory, removed
+ *  on drop, for end-to-end tests of txs built by the wallet.
+ *  There is no mainchain. Bitcoin is sent to the wallet with
+ *  [`Self::fund`], and blocks are mined with [`Self::mine`], which connects
+ *  the mempool txs without BMM. */
+pub struct TestHarness {
+    pub env: heed::Env,
+    pub state: State,
+    pub mempool: MemPool,
+    pub wallet: Wallet,
+    // Must be dropped after the env and wallet
+    _dir: TempDir,
+}
+
+impl TestHarness {
+    /// `name` must be unique among concurrently running tests.
+    pub fn new(name: &str, genesis: &state::Genesis) -> Result<Self, Error> {
+        let dir = TempDir::new(name)?;
+        let node_dir = dir.0.join("node");
+        std::fs::create_dir_all(&node_dir).map_err(heed::Error::Io)?;
+        let env = unsafe {
+            heed::EnvOpenOptions::new()
+                .map_size(10 * 1024 * 1024) // 10MB
+                .max_dbs(State::NUM_DBS + MemPool::NUM_DBS)
+                .open(&node_dir)?
+        };
+        let state = State::new(&env, false, genesis)?;
+        let mempool = MemPool::new(&env, MemPoolLimits::default())?;
+        let wallet = Wallet::new(&dir.0.join("wallet"))?;
+        let () = wallet.set_seed(&[0; 64])?;
+        Ok(Self {
+            env,
+            state,
+            mempool,
+            wallet,
+            _dir: dir,
+        })
+    }
+
+    /// Update the wallet's UTXOs from the state
+    fn sync_wallet(&self) -> Result<(), Error> {
+        let rotxn = self.env.read_txn()?;
+        let addresses = self.wallet.get_addresses()?;
+        let utxos = self.state.get_utxos_by_addresses(&rotxn, &addresses)?;
+        let mut spent = Vec::new();
+        for outpoint in self.wallet.get_utxos()?.into_keys() {
+            if let Some(spent_output) =
+                self.state.stxos.get(&rotxn, &outpoint)?
+            {
+                spent.push((outpoint, spent_output.inpoint));
+            }
+        }
+        let () = self.wallet.put_utxos(&utxos)?;
+        let () = self.wallet.spend_utxos(&spent)?;
+        Ok(())
+    }
+
+    /** Send Bitcoin to a new wallet address, by inserting a UTXO directly
+     *  into the state, as a stand-in for a mainchain deposit. */
+    pub fn fund(&self, value: u64) -> Result<OutPoint, Error> {
+        let address = self.wallet.get_new_address()?;
+        // Each funding output is sent to a new address, so the outpoint is
+        // unique
+        let outpoint = OutPoint::Regular {
+            txid: Txid(blake3::hash(&address.0).into()),
+            vout: 0,
+        };
+        let output = FilledOutput::new(
+            address,
+            FilledOutputContent::Bitcoin(BitcoinOutputContent(value)),
+        );
+        let mut rwtxn = self.env.write_txn()?;
+        self.state.utxos.put(&mut rwtxn, &outpoint, &output)?;
+        rwtxn.commit()?;
+        let () = self.sync_wallet()?;
+        Ok(outpoint)
+    }
+
+    /// Sign a tx with the wallet, validate it, and add it to the mempool
+    pub fn submit(&self, tx: Transaction) -> Result<Txid, Error> {
+        let tx = self.wallet.authorize(tx)?;
+        let txid = tx.transaction.txid();
+        let mut rwtxn = self.env.write_txn()?;
+        let fee = self.state.validate_transaction(&rwtxn, &tx)?;
+        let _evicted: Vec<Txid> =
+            self.mempool.put(&mut rwtxn, &tx, fee, None)?;
+        rwtxn.commit()?;
+        Ok(txid)
+    }
+
+    /** Provide liquidity to an AMM pool from the wallet, creating the pool
+     *  if it does not exist, and submit the tx. The LP token amount is
+     *  computed from the current pool state. */
+    pub fn amm_mint(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        amount0: u64,
+        amount1: u64,
+    ) -> Result<Txid, Error> {
+        let amm_pair = AmmPair::new(asset0, asset1);
+        let amm_pool_state = {
+            let rotxn = self.env.read_txn()?;
+            self.state
+                .amm_pools
+                .get(&rotxn, &amm_pair)?
+                .unwrap_or_else(|| AmmPoolState::new(Txid::default()))
+        };
+        // Pool amounts are in the canonical order for the pair
+        let new_amm_pool_state = if asset0 == amm_pair.asset0() {
+            amm_pool_state.mint(amount0, amount1)?
+        } else {
+            amm_pool_state.mint(amount1, amount0)?
+        };
+        let lp_token_mint = new_amm_pool_state.outstanding_lp_tokens
+            - amm_pool_state.outstanding_lp_tokens;
+        let mut tx = Transaction::default();
+        let () = self.wallet.amm_mint(
+            &mut tx,
+            asset0,
+            asset1,
+            amount0,
+            amount1,
+            lp_token_mint,
+        )?;
+        self.submit(tx)
+    }
+
+    /** Connect a block containing all mempool txs, remove them from the
+     *  mempool, and update the wallet. Returns the header of the connected
+     *  block.
+     *  Mempool txs must not spend outputs of other mempool txs, so submit
+     *  dependent txs in separate blocks. */
+    pub fn mine(&self) -> Result<Header, Error> {
+        let mut rwtxn = self.env.write_txn()?;
+        let txs = self.mempool.take_all(&rwtxn)?;
+        let txids: Vec<Txid> =
+            txs.iter().map(|tx| tx.transaction.txid()).collect();
+        let header = connect_txs(&self.state, &mut rwtxn, txs)?;
+        for txid in txids {
+            let () = self.mempool.delete(&mut rwtxn, txid)?;
+        }
+        rwtxn.commit()?;
+        let () = self.sync_wallet()?;
+        Ok(header)
+    }
+}
+
+/** Genesis config with the specified BitAssets, each specified by plain
+ *  name, the key that controls it, and the balance of the BitAsset that is
+ *  allocated to the same key */
+pub fn bitassets_genesis(
+    bitassets: &[(&str, &TestKey, u64)],
+) -> state::Genesis {
+    let bitassets = bitassets
+        .iter()
+        .map(|(plain_name, key, balance)| state::GenesisBitAsset {
+            plain_name: (*plain_name).to_owned(),
+            bitasset_data: BitAssetData::default(),
+            control_address: key.address,
+            balances: vec![(key.address, *balance)],
+        })
+        .collect();
+    state::Genesis {
+        bitassets,
+        ..Default::default()
+    }
+}
+
+/// Genesis config with a single BitAsset, as in [`bitassets_genesis`]
+pub fn single_bitasset_genesis(
+    key: &TestKey,
+    plain_name: &str,
+    balance: u64,
+) -> state::Genesis {
+    bitassets_genesis(&[(plain_name, key, balance)])
+}
+
+/** Validate and connect a block containing the specified txs and coinbase
+ *  outputs. Returns the header and body of the connected block.
+ *  The header timestamp is not checked, since the header is not stored in
+ *  an archive, so it is always zero. */
+pub fn connect_block(
+    state: &State,
+    rwtxn: &mut RwTxn,
+    txs: Vec<AuthorizedTransaction>,
+    coinbase: Vec<Output>,
+) -> Result<(Header, Body), Error> {
+    let body = Body::new(txs, coinbase);
+    let header = Header {
+        merkle_root: body.compute_merkle_root(),
+        prev_side_hash: state.get_tip(rwtxn)?,
+        prev_main_hash: bitcoin::BlockHash::all_zeros(),
+        timestamp: 0,
+    };
+    let (_fees, filled_txs) = state.validate_block(rwtxn, &header, &body)?;
+    let () = state.connect_filled_block(rwtxn, &header, &body, filled_txs)?;
+    Ok((header, body))
+}
+
+/** Validate and connect a block containing the specified txs, with an empty
+ *  coinbase. Returns the header of the connected block. */
+pub fn connect_txs(
+    state: &State,
+    rwtxn: &mut RwTxn,
+    txs: Vec<AuthorizedTransaction>,
+) -> Result<Header, Error> {
+    let (header, _body) = connect_block(state, rwtxn, txs, Vec::new())?;
+    Ok(header)
+}
+
+fn asset_output_content(asset: AssetId, amount: u64) -> OutputContent {
+    match asset {
+        AssetId::Bitcoin => OutputContent::Value(BitcoinOutputContent(amount)),
+        AssetId::BitAsset(_) => OutputContent::BitAsset(amount),
+        AssetId::BitAssetControl(_) => OutputContent::BitAssetControl,
+    }
+}
+
+/** Builds valid txs against the current state, spending UTXOs owned by a
+ *  single key. All outputs, including change, are sent to the same key.
+ *  Txs are not applied to the state; use [`connect_txs`] to apply them
+ *  before building txs that depend on them. */
+pub struct TxBuilder<'a> {
+    state: &'a State,
+    key: &'a TestKey,
+}
+
+impl<'a> TxBuilder<'a> {
+    pub fn new(state: &'a State, key: &'a TestKey) -> Self {
+        Self { state, key }
+    }
+
+    /// UTXOs owned by the key, in outpoint order
+    fn utxos(
+        &self,
+        rotxn: &RoTxn,
+    ) -> Result<Vec<(OutPoint, FilledOutput)>, Error> {
+        let addresses = HashSet::from([self.key.address]);
+        let mut utxos: Vec<_> = self
+            .state
+            .get_utxos_by_addresses(rotxn, &addresses)?
+            .into_iter()
+            .collect();
+        utxos.sort_by_key(|(outpoint, _)| *outpoint);
+        Ok(utxos)
+    }
+
+    /** Select UTXOs of the specified asset, in outpoint order, with total
+     *  value of at least `amount`. Returns the total value and the selected
+     *  outpoints. */
+    fn select_asset_utxos(
+        &self,
+        rotxn: &RoTxn,
+        asset: AssetId,
+        amount: u64,
+    ) -> Result<(u64, Vec<OutPoint>), Error> {
+        let mut total: u64 = 0;
+        let mut selected = Vec::new();
+        for (outpoint, output) in self.utxos(rotxn)? {
+            if total >= amount {
+                break;
+            }
+            if let Some((output_asset, value)) = output.asset_value()
+                && output_asset == asset
+            {
+                total += value;
+                selected.push(outpoint);
+            }
+        }
+        if total < amount {
+            return Err(Error::InsufficientFunds {
+                asset,
+                available: total,
+                required: amount,
+            });
+        }
+        Ok((total, selected))
+    }
+
+    fn output(&self, content: OutputContent) -> Output {
+        Output::new(self.key.address, content)
+    }
+
+    /// Change output for the specified asset, if the change is nonzero
+    fn change_output(&self, asset: AssetId, change: u64) -> Option<Output> {
+        (change != 0).then(|| self.output(asset_output_content(asset, change)))
+    }
+
+    /// Reserve a BitAsset name
+    pub fn bitasset_reservation(
+        &self,
+        plain_name: &str,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let prep = self.key.reservation_prep(plain_name);
+        let mut tx = Transaction::new(
+            Vec::new(),
+            vec![self.output(OutputContent::BitAssetReservation)],
+        );
+        tx.data = Some(prep.reservation_tx_data());
+        self.key.authorize(tx)
+    }
+
+    /** Register a BitAsset, spending a reservation created by
+     *  [`Self::bitasset_reservation`]. */
+    pub fn bitasset_registration(
+        &self,
+        rotxn: &RoTxn,
+        plain_name: &str,
+        bitasset_data: BitAssetData,
+        initial_supply: u64,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let prep = self.key.reservation_prep(plain_name);
+        self.complete_registration(rotxn, &prep, bitasset_data, initial_supply)
+    }
+
+    /** Register a BitAsset, spending the reservation with the commitment
+     *  from the prep, and revealing the nonce from the prep. The nonce is
+     *  not checked against the commitment. */
+    pub fn complete_registration(
+        &self,
+        rotxn: &RoTxn,
+        prep: &ReservationPrep,
+        bitasset_data: BitAssetData,
+        initial_supply: u64,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let reservation_outpoint = self
+            .utxos(rotxn)?
+            .into_iter()
+            .find_map(|(outpoint, output)| {
+                (output.reservation_commitment() == Some(&prep.commitment))
+                    .then_some(outpoint)
+            })
+            .ok_or_else(|| Error::MissingBitAssetReservation {
+                plain_name: prep.plain_name.clone(),
+            })?;
+        let mut tx = Transaction::new(vec![reservation_outpoint], Vec::new());
+        if initial_supply != 0 {
+            tx.outputs
+                .push(self.output(OutputContent::BitAsset(initial_supply)));
+        }
+        tx.outputs.push(self.output(OutputContent::BitAssetControl));
+        tx.data =
+            Some(prep.registration_tx_data(bitasset_data, initial_supply));
+        self.key.authorize(tx)
+    }
+
+    /** Mint BitAssets. The minted BitAssets are carried by a spent BitAsset
+     *  UTXO, so the key must own at least one unit of the BitAsset, as well
+     *  as the control coin. */
+    pub fn bitasset_mint(
+        &self,
+        rotxn: &RoTxn,
+        bitasset: BitAssetId,
+        mint_amount: u64,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let (carry_value, carry_utxos) =
+            self.select_asset_utxos(rotxn, AssetId::BitAsset(bitasset), 1)?;
+        let control_outpoint = self
+            .select_asset_utxos(rotxn, AssetId::BitAssetControl(bitasset), 1)
+            .map_err(|_| Error::MissingBitAssetControl { bitasset })?
+            .1[0];
+        // The control coin must be the last control coin input
+        let mut tx = Transaction::new(carry_utxos, Vec::new());
+        tx.inputs.push(control_outpoint);
+        tx.outputs.push(self.output(OutputContent::BitAssetControl));
+        tx.outputs.push(
+            self.output(OutputContent::BitAsset(carry_value + mint_amount)),
+        );
+        tx.data = Some(TxData::BitAssetMint(mint_amount));
+        self.key.authorize(tx)
+    }
+
+    /// Update the data associated with a BitAsset, spending the control coin
+    pub fn bitasset_update(
+        &self,
+        rotxn: &RoTxn,
+        bitasset: BitAssetId,
+        bitasset_updates: BitAssetDataUpdates,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let control_outpoint = self
+            .select_asset_utxos(rotxn, AssetId::BitAssetControl(bitasset), 1)
+            .map_err(|_| Error::MissingBitAssetControl { bitasset })?
+            .1[0];
+        let mut tx = Transaction::new(
+            vec![control_outpoint],
+            vec![self.output(OutputContent::BitAssetControl)],
+        );
+        tx.data = Some(TxData::BitAssetUpdate(Box::new(bitasset_updates)));
+        self.key.authorize(tx)
+    }
+
+    /** Provide liquidity to an AMM pool, creating the pool if it does not
+     *  exist. The LP token amount is computed from the current pool state. */
+    pub fn amm_mint(
+        &self,
+        rotxn: &RoTxn,
+        asset0: AssetId,
+        asset1: AssetId,
+        amount0: u64,
+        amount1: u64,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let amm_pair = AmmPair::new(asset0, asset1);
+        let amm_pool_state = self
+            .state
+            .amm_pools
+            .get(rotxn, &amm_pair)?
+            .unwrap_or_else(|| AmmPoolState::new(Txid::default()));
+        // Pool amounts are in the canonical order for the pair
+        let new_amm_pool_state = if asset0 == amm_pair.asset0() {
+            amm_pool_state.mint(amount0, amount1)?
+        } else {
+            amm_pool_state.mint(amount1, amount0)?
+        };
+        let lp_token_mint = new_amm_pool_state.outstanding_lp_tokens
+            - amm_pool_state.outstanding_lp_tokens;
+        let (input_amount0, asset0_utxos) =
+            self.select_asset_utxos(rotxn, asset0, amount0)?;
+        let (input_amount1, asset1_utxos) =
+            self.select_asset_utxos(rotxn, asset1, amount1)?;
+        // The first two unique assets in the inputs must be `asset0` and
+        // `asset1`
+        let mut tx = Transaction::new(asset0_utxos, Vec::new());
+        tx.inputs.extend(asset1_utxos);
+        tx.outputs
+            .extend(self.change_output(asset0, input_amount0 - amount0));
+        tx.outputs
+            .extend(self.change_output(asset1, input_amount1 - amount1));
+        tx.outputs
+            .push(self.output(OutputContent::AmmLpToken(lp_token_mint)));
+        tx.data = Some(TxData::AmmMint {
+            amount0,
+            amount1,
+            lp_token_mint,
+        });
+        self.key.authorize(tx)
+    }
+
+    /** Transfer LP tokens for an AMM pool to the destination address.
+     *  LP token UTXOs are selected in outpoint order. */
+    pub fn amm_lp_token_transfer(
+        &self,
+        rotxn: &RoTxn,
+        amm_pair: AmmPair,
+        dest: Address,
+        amount: u64,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let mut total: u64 = 0;
+        let mut selected = Vec::new();
+        for (outpoint, output) in self.utxos(rotxn)? {
+            if total >= amount {
+                break;
+            }
+            if let Some((asset0, asset1, lp_token_amount)) =
+                output.lp_token_amount()
+                && asset0 == amm_pair.asset0()
+                && asset1 == amm_pair.asset1()
+            {
+                total += lp_token_amount;
+                selected.push(outpoint);
+            }
+        }
+        if total < amount {
+            return Err(Error::InsufficientLpTokens {
+                asset0: amm_pair.asset0(),
+                asset1: amm_pair.asset1(),
+                available: total,
+                required: amount,
+            });
+        }
+        let mut tx = Transaction::new(
+            selected,
+            vec![Output::new(dest, OutputContent::AmmLpToken(amount))],
+        );
+        if total != amount {
+            tx.outputs
+                .push(self.output(OutputContent::AmmLpToken(total - amount)));
+        }
+        self.key.authorize(tx)
+    }
+
+    /** Swap against an AMM pool. The amount received and the protocol fee
+     *  are computed from the current pool state. */
+    pub fn amm_swap(
+        &self,
+        rotxn: &RoTxn,
+        asset_spend: AssetId,
+        asset_receive: AssetId,
+        amount_spend: u64,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let amm_pair = AmmPair::new(asset_spend, asset_receive);
+        let amm_pool_state =
+            self.state.amm_pools.get(rotxn, &amm_pair)?.ok_or(
+                Error::MissingAmmPool {
+                    asset0: amm_pair.asset0(),
+                    asset1: amm_pair.asset1(),
+                },
+            )?;
+        let amount_receive = if asset_spend < asset_receive {
+            let new_amm_pool_state =
+                amm_pool_state.swap_asset0_for_asset1(amount_spend)?;
+            amm_pool_state.reserve1 - new_amm_pool_state.reserve1
+        } else {
+            let new_amm_pool_state =
+                amm_pool_state.swap_asset1_for_asset0(amount_spend)?;
+            amm_pool_state.reserve0 - new_amm_pool_state.reserve0
+        };
+        let protocol_fee = self.state.amm_protocol_fee.fee(amount_spend);
+        let (input_amount, spend_utxos) = self.select_asset_utxos(
+            rotxn,
+            asset_spend,
+            amount_spend + protocol_fee,
+        )?;
+        let mut tx = Transaction::new(spend_utxos, Vec::new());
+        tx.outputs.extend(self.change_output(
+            asset_spend,
+            input_amount - amount_spend - protocol_fee,
+        ));
+        if protocol_fee != 0 {
+            tx.outputs.push(Output::new(
+                self.state.amm_protocol_fee.address,
+                asset_output_content(asset_spend, protocol_fee),
+            ));
+        }
+        tx.outputs.push(
+            self.output(asset_output_content(asset_receive, amount_receive)),
+        );
+        tx.data = Some(TxData::AmmSwap {
+            amount_spent: amount_spend,
+            amount_receive,
+            pair_asset: asset_receive,
+        });
+        self.key.authorize(tx)
+    }
+
+    /// Lock an asset in an atomic swap. The swap ID is the txid.
+    pub fn atomic_swap_lock(
+        &self,
+        rotxn: &RoTxn,
+        atomic_swap_params: AtomicSwapParams,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let AtomicSwapParams { asset, amount, .. } = atomic_swap_params;
+        let (input_amount, utxos) =
+            self.select_asset_utxos(rotxn, asset, amount)?;
+        let mut tx = Transaction::new(utxos, Vec::new());
+        tx.outputs
+            .extend(self.change_output(asset, input_amount - amount));
+        tx.data = Some(TxData::AtomicSwapLock(atomic_swap_params));
+        self.key.authorize(tx)
+    }
+
+    /** Claim the asset locked in an atomic swap, by revealing the preimage.
+     *  The key must be the recipient of the swap. */
+    pub fn atomic_swap_claim(
+        &self,
+        rotxn: &RoTxn,
+        swap_id: AtomicSwapId,
+        preimage: Hash,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let swap_state = self
+            .state
+            .atomic_swaps
+            .get(rotxn, &swap_id)?
+            .ok_or(Error::MissingAtomicSwap(swap_id))?;
+        let mut tx = Transaction::new(
+            Vec::new(),
+            vec![self.output(asset_output_content(
+                swap_state.asset,
+                swap_state.amount,
+            ))],
+        );
+        tx.data = Some(TxData::AtomicSwapClaim {
+            swap_id,
+            preimage,
+            asset: swap_state.asset,
+            amount: swap_state.amount,
+        });
+        self.key.authorize(tx)
+    }
+
+    /** Refund the asset locked in an atomic swap, after the timeout.
+     *  The key must be the refund address of the swap. */
+    pub fn atomic_swap_refund(
+        &self,
+        rotxn: &RoTxn,
+        swap_id: AtomicSwapId,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let swap_state = self
+            .state
+            .atomic_swaps
+            .get(rotxn, &swap_id)?
+            .ok_or(Error::MissingAtomicSwap(swap_id))?;
+        let mut tx = Transaction::new(
+            Vec::new(),
+            vec![self.output(asset_output_content(
+                swap_state.asset,
+                swap_state.amount,
+            ))],
+        );
+        tx.data = Some(TxData::AtomicSwapRefund {
+            swap_id,
+            asset: swap_state.asset,
+            amount: swap_state.amount,
+        });
+        self.key.authorize(tx)
+    }
+
+    /// Place a limit order. The order ID is the txid.
+    pub fn order_place(
+        &self,
+        rotxn: &RoTxn,
+        order_params: OrderParams,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let OrderParams {
+            sell_asset,
+            sell_amount,
+            ..
+        } = order_params;
+        let (input_amount, sell_utxos) =
+            self.select_asset_utxos(rotxn, sell_asset, sell_amount)?;
+        let mut tx = Transaction::new(sell_utxos, Vec::new());
+        tx.outputs
+            .extend(self.change_output(sell_asset, input_amount - sell_amount));
+        tx.outputs.push(self.output(OutputContent::OrderReceipt));
+        tx.data = Some(TxData::OrderPlace(order_params));
+        self.key.authorize(tx)
+    }
+
+    /** Fill the specified quantity of a limit order. The cost is computed
+     *  from the order price. */
+    pub fn order_fill(
+        &self,
+        rotxn: &RoTxn,
+        order_id: OrderId,
+        quantity: u64,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let order_state = self
+            .state
+            .orders
+            .get(rotxn, &order_id)?
+            .ok_or(Error::MissingOrder(order_id))?;
+        let cost = order_state.cost(quantity).unwrap_or(u64::MAX);
+        let (input_amount, buy_utxos) =
+            self.select_asset_utxos(rotxn, order_state.buy_asset, cost)?;
+        let mut tx = Transaction::new(buy_utxos, Vec::new());
+        tx.outputs.push(
+            self.output(asset_output_content(order_state.sell_asset, quantity)),
+        );
+        tx.outputs.extend(
+            self.change_output(order_state.buy_asset, input_amount - cost),
+        );
+        tx.data = Some(TxData::OrderFill {
+            order_id,
+            receive_asset: order_state.sell_asset,
+            quantity,
+            spend_asset: order_state.buy_asset,
+            spend_amount: cost,
+        });
+        self.key.authorize(tx)
+    }
+
+    /** Cancel a limit order, redeeming the unfilled remainder and proceeds.
+     *  The key must own the order receipt. */
+    pub fn order_cancel(
+        &self,
+        rotxn: &RoTxn,
+        order_id: OrderId,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let order_state = self
+            .state
+            .orders
+            .get(rotxn, &order_id)?
+            .ok_or(Error::MissingOrder(order_id))?;
+        let receipt_outpoint = self
+            .utxos(rotxn)?
+            .into_iter()
+            .find_map(|(outpoint, output)| {
+                (output.order_receipt() == Some(order_id)).then_some(outpoint)
+            })
+            .ok_or(Error::MissingOrderReceipt(order_id))?;
+        let amount_offered_remaining =
+            order_state.sell_amount_remaining.latest().data;
+        let amount_received = order_state.buy_amount_received.latest().data;
+        let mut tx = Transaction::new(vec![receipt_outpoint], Vec::new());
+        tx.outputs.extend(
+            self.change_output(
+                order_state.sell_asset,
+                amount_offered_remaining,
+            ),
+        );
+        tx.outputs
+            .extend(self.change_output(order_state.buy_asset, amount_received));
+        tx.data = Some(TxData::OrderCancel {
+            asset_offered: order_state.sell_asset,
+            asset_receive: order_state.buy_asset,
+            amount_offered_remaining,
+            amount_received,
+        });
+        self.key.authorize(tx)
+    }
+
+    /// Create a Dutch auction. The auction ID is the txid.
+    pub fn dutch_auction_create(
+        &self,
+        rotxn: &RoTxn,
+        dutch_auction_params: DutchAuctionParams,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let DutchAuctionParams {
+            base_asset,
+            base_amount,
+            ..
+        } = dutch_auction_params;
+        let (input_amount, base_utxos) =
+            self.select_asset_utxos(rotxn, base_asset, base_amount)?;
+        let mut tx = Transaction::new(base_utxos, Vec::new());
+        tx.outputs
+            .extend(self.change_output(base_asset, input_amount - base_amount));
+        tx.outputs
+            .push(self.output(OutputContent::DutchAuctionReceipt));
+        tx.data = Some(TxData::DutchAuctionCreate(dutch_auction_params));
+        self.key.authorize(tx)
+    }
+
+    /** Bid on a Dutch auction, in the next block. The quantity received is
+     *  computed from the current auction state. */
+    pub fn dutch_auction_bid(
+        &self,
+        rotxn: &RoTxn,
+        auction_id: DutchAuctionId,
+        bid_size: u64,
+    ) -> Result<AuthorizedTransaction, Error> {
+        let auction_state = self
+            .state
+            .dutch_auctions
+            .get(rotxn, &auction_id)?
+            .ok_or(Error::MissingDutchAuction(auction_id))?;
+        // The bid is applied at the height of the next block
+        let height = self.state.get_height(rotxn)?;
+        let new_auction_state =
+            auction_state.bid(Txid::default(), bid_size, height)?;
+        let receive_quantity =
+            auction_state.base_amount_remaining.latest().data
+                - new_auction_state.base_amount_remaining.latest().data;
+        let (input_amount, quote_utxos) = self.select_asset_utxos(
+            rotxn,
+            auction_state.quote_asset,
+            bid_size,
+        )?;
+        let mut tx = Transaction::new(quote_utxos, Vec::new());
+        tx.outputs.push(self.output(asset_output_content(
+            auction_state.base_asset,
+            receive_quantity,
+        )));
+        tx.outputs.extend(
+            self.change_output(
+                auction_state.quote_asset,
+                input_amount - bid_size,
+            ),
+        );
+        tx.data = Some(TxData::DutchAuctionBid {
+            auction_id,
+            receive_asset: auction_state.base_asset,
+            quantity: receive_quantity,
+            bid_size,
+        });
+        self.key.authorize(tx)
+    }
+}
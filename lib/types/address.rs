@@ -16,6 +16,10 @@ pub enum AddressParseError {
 }
 
 impl Address {
+    /// Address to which burned funds are sent.
+    /// No known key hashes to this address, so outputs to it are unspendable.
+    pub const BURN: Self = Self([0; 20]);
+
     pub fn to_base58(self) -> String {
         bs58::encode(self.0)
             .with_alphabet(bs58::Alphabet::BITCOIN)
@@ -272,6 +272,13 @@ impl utoipa::ToSchema<'static> for Txid {
 #[repr(transparent)]
 pub struct BitAssetId(#[serde(with = "serde_hexstr_human_readable")] pub Hash);
 
+impl BitAssetId {
+    /// Compute the BitAsset ID (name hash) for a plaintext name
+    pub fn from_plain_name(plain_name: &str) -> Self {
+        Self(blake3::hash(plain_name.as_bytes()).into())
+    }
+}
+
 impl FromHex for BitAssetId {
     type Error = <Hash as FromHex>::Error;
 
@@ -280,6 +287,13 @@ impl FromHex for BitAssetId {
     }
 }
 
+impl FromStr for BitAssetId {
+    type Err = <Self as FromHex>::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
 impl utoipa::PartialSchema for BitAssetId {
     fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
         let obj = utoipa::openapi::Object::with_type(
@@ -379,6 +393,63 @@ impl utoipa::ToSchema<'static> for AssetId {
     }
 }
 
+/// Unique identifier for each atomic swap
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Eq,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Serialize,
+)]
+#[repr(transparent)]
+#[serde(transparent)]
+pub struct AtomicSwapId(pub Txid);
+
+impl std::fmt::Display for AtomicSwapId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromHex for AtomicSwapId {
+    type Error = <Hash as FromHex>::Error;
+
+    fn from_hex<T: AsRef<[u8]>>(hex: T) -> Result<Self, Self::Error> {
+        Txid::from_hex(hex).map(Self)
+    }
+}
+
+impl FromStr for AtomicSwapId {
+    type Err = <Self as FromHex>::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+impl utoipa::PartialSchema for AtomicSwapId {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        let obj = utoipa::openapi::Object::with_type(
+            utoipa::openapi::SchemaType::String,
+        );
+        utoipa::openapi::RefOr::T(utoipa::openapi::Schema::Object(obj))
+    }
+}
+
+impl utoipa::ToSchema<'static> for AtomicSwapId {
+    fn schema() -> (
+        &'static str,
+        utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+    ) {
+        ("AtomicSwapId", <Self as utoipa::PartialSchema>::schema())
+    }
+}
+
 /// Unique identifier for each Dutch auction
 #[derive(
     BorshDeserialize,
@@ -435,3 +506,60 @@ impl utoipa::ToSchema<'static> for DutchAuctionId {
         ("DutchAuctionId", <Self as utoipa::PartialSchema>::schema())
     }
 }
+
+/// Unique identifier for each order
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Eq,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Serialize,
+)]
+#[repr(transparent)]
+#[serde(transparent)]
+pub struct OrderId(pub Txid);
+
+impl std::fmt::Display for OrderId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromHex for OrderId {
+    type Error = <Hash as FromHex>::Error;
+
+    fn from_hex<T: AsRef<[u8]>>(hex: T) -> Result<Self, Self::Error> {
+        Txid::from_hex(hex).map(Self)
+    }
+}
+
+impl FromStr for OrderId {
+    type Err = <Self as FromHex>::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+impl utoipa::PartialSchema for OrderId {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        let obj = utoipa::openapi::Object::with_type(
+            utoipa::openapi::SchemaType::String,
+        );
+        utoipa::openapi::RefOr::T(utoipa::openapi::Schema::Object(obj))
+    }
+}
+
+impl utoipa::ToSchema<'static> for OrderId {
+    fn schema() -> (
+        &'static str,
+        utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+    ) {
+        ("OrderId", <Self as utoipa::PartialSchema>::schema())
+    }
+}
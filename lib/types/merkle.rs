@@ -0,0 +1,139 @@
+//! Binary merkle tree over the coinbase outputs and txids in a block body.
+//!
+//! Leaves are the coinbase outputs, in order, followed by the txids of the
+//! transactions, in order. Leaf and interior node hashes are domain
+//! separated by a one-byte prefix. At each level with an odd number of
+//! nodes, the last node is paired with itself, so bodies in which any node
+//! is equal to its sibling are rejected (see [`has_duplicate_nodes`]). The
+//! root of a single leaf is the leaf hash, and the root of an empty tree is
+//! the all-zero hash.
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    hashes::{self, Hash, MerkleRoot, Txid},
+    Output,
+};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Leaf hash for a coinbase output
+pub fn coinbase_leaf(output: &Output) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hashes::update(&mut hasher, output);
+    hasher.finalize().into()
+}
+
+/// Leaf hash for a transaction
+pub fn tx_leaf(txid: Txid) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(&txid.0);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Compute the next level up of the tree
+fn parent_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| node_hash(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+/// Compute the merkle root of the specified leaves
+pub fn root(leaves: Vec<Hash>) -> MerkleRoot {
+    let mut level = leaves;
+    if level.is_empty() {
+        return Hash::default().into();
+    }
+    while level.len() > 1 {
+        level = parent_level(&level);
+    }
+    level[0].into()
+}
+
+/** Returns `true` if any node in the tree is equal to its sibling.
+ *  Since the last node at each level with an odd number of nodes is paired
+ *  with itself, a list of leaves in which the last leaf, or the last
+ *  leaves, have been duplicated can have the same root as the original list
+ *  (CVE-2012-2459). As in Bitcoin, such trees are detected by duplicate
+ *  siblings. */
+pub fn has_duplicate_nodes(leaves: Vec<Hash>) -> bool {
+    let mut level = leaves;
+    while level.len() > 1 {
+        if level.chunks_exact(2).any(|pair| pair[0] == pair[1]) {
+            return true;
+        }
+        level = parent_level(&level);
+    }
+    false
+}
+
+/// Proof that a leaf is included in a merkle tree
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MerkleProof {
+    /// Index of the leaf in the tree
+    pub index: u32,
+    /// Sibling hashes, from the leaf level up to the level below the root
+    pub siblings: Vec<Hash>,
+}
+
+impl MerkleProof {
+    /// Compute a proof for the leaf at the specified index.
+    /// Returns `None` if the index is out of bounds.
+    pub fn new(leaves: Vec<Hash>, index: u32) -> Option<Self> {
+        if index as usize >= leaves.len() {
+            return None;
+        }
+        let mut siblings = Vec::new();
+        let mut level = leaves;
+        let mut idx = index as usize;
+        while level.len() > 1 {
+            let sibling = level.get(idx ^ 1).unwrap_or(&level[idx]);
+            siblings.push(*sibling);
+            level = parent_level(&level);
+            idx /= 2;
+        }
+        Some(Self { index, siblings })
+    }
+
+    /// Compute the merkle root implied by the proof, for the specified leaf
+    pub fn compute_root(&self, leaf: Hash) -> MerkleRoot {
+        let mut idx = self.index;
+        let hash = self.siblings.iter().fold(leaf, |hash, sibling| {
+            let parent = if idx % 2 == 0 {
+                node_hash(&hash, sibling)
+            } else {
+                node_hash(sibling, &hash)
+            };
+            idx /= 2;
+            parent
+        });
+        hash.into()
+    }
+}
+
+/// Verify that a proof shows the inclusion of the specified tx in a block
+/// body with the specified merkle root
+pub fn verify_merkle_proof(
+    merkle_root: MerkleRoot,
+    txid: Txid,
+    proof: &MerkleProof,
+) -> bool {
+    // The index must be fully consumed by the siblings
+    let index_consumed = u32::try_from(proof.siblings.len())
+        .ok()
+        .and_then(|depth| proof.index.checked_shr(depth))
+        .is_none_or(|rest| rest == 0);
+    index_consumed && proof.compute_root(tx_leaf(txid)) == merkle_root
+}
@@ -12,27 +12,32 @@ use thiserror::Error;
 use utoipa::ToSchema;
 
 pub use crate::authorization::Authorization;
+use crate::authorization::VerifyingKey;
 
 mod address;
 pub mod constants;
 pub mod hashes;
+pub mod merkle;
 pub mod output;
 mod transaction;
 
 pub use address::*;
 pub use hashes::{
-    AssetId, BitAssetId, BlockHash, DutchAuctionId, Hash, MerkleRoot, Txid,
+    AssetId, AtomicSwapId, BitAssetId, BlockHash, DutchAuctionId, Hash,
+    MerkleRoot, OrderId, Txid,
 };
+pub use merkle::{verify_merkle_proof, MerkleProof};
 pub use output::{
     AssetOutput, AssetOutputContent, BitcoinOutput, BitcoinOutputContent,
     FilledContent as FilledOutputContent, FilledOutput, Output, OutputContent,
     Pointed as PointedOutput, SpentOutput,
 };
 pub use transaction::{
-    AmmBurn, AmmMint, AmmSwap, Authorized, AuthorizedTransaction, BitAssetData,
-    BitAssetDataUpdates, DutchAuctionBid, DutchAuctionCollect,
-    DutchAuctionParams, FilledTransaction, InPoint, OutPoint, Transaction,
-    TxData, TxInputs, Update,
+    AmmBurn, AmmMint, AmmSwap, AtomicSwapParams, AtomicSwapRelease, Authorized,
+    AuthorizedTransaction, BitAssetData, BitAssetDataUpdates, DutchAuctionBid,
+    DutchAuctionCollect, DutchAuctionParams, FilledTransaction, InPoint,
+    OrderCancel, OrderFill, OrderParams, OutPoint, ParseOutPointError,
+    Transaction, TxData, TxInputs, Update,
 };
 
 /// (de)serialize as Display/FromStr for human-readable forms like json,
@@ -229,12 +234,45 @@ pub struct Header {
     pub prev_side_hash: BlockHash,
     #[borsh(serialize_with = "borsh_serialize_bitcoin_block_hash")]
     pub prev_main_hash: bitcoin::BlockHash,
+    /// Block time, in seconds since the Unix epoch
+    pub timestamp: u64,
 }
 
 impl Header {
     pub fn hash(&self) -> BlockHash {
         hashes::hash(self).into()
     }
+
+    /** The BMM critical hash that must be committed to on the mainchain for
+     *  this block. This is the block hash, encoded as a mainchain block
+     *  hash. */
+    pub fn bmm_critical_hash(&self) -> bitcoin::BlockHash {
+        bitcoin::BlockHash::from_byte_array(self.hash().into())
+    }
+}
+
+/// Identifier for a withdrawal bundle, equal to the txid of the bundle's
+/// mainchain transaction
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Serialize,
+)]
+#[repr(transparent)]
+#[serde(transparent)]
+pub struct M6id(pub bitcoin::Txid);
+
+impl std::fmt::Display for M6id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
@@ -249,6 +287,141 @@ pub struct WithdrawalBundle {
     pub transaction: bitcoin::Transaction,
 }
 
+impl WithdrawalBundle {
+    pub fn compute_m6id(&self) -> M6id {
+        M6id(self.transaction.txid())
+    }
+}
+
+/// Status of a withdrawal bundle on the mainchain
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawalStatus {
+    /// The bundle is pending, and has not yet confirmed or failed
+    Submitted,
+    /// The bundle has been confirmed on the mainchain
+    Confirmed,
+    /// The bundle failed, and the withdrawal outputs were returned to the
+    /// UTXO set
+    Failed,
+}
+
+/// Withdrawal bundle that a withdrawal output was spent into
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ToSchema,
+)]
+pub struct WithdrawalBundleInfo {
+    #[schema(value_type = String)]
+    pub m6id: M6id,
+    pub status: WithdrawalStatus,
+}
+
+/// A withdrawal bundle, with its latest status
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, ToSchema)]
+pub struct WithdrawalBundleRecord {
+    #[schema(value_type = String)]
+    pub m6id: M6id,
+    pub status: WithdrawalStatus,
+    /// Sidechain block height at which the bundle was submitted, if it is
+    /// pending, or at which it was confirmed or failed
+    pub height: u32,
+    /// Withdrawal outputs spent by the bundle
+    pub spent_outpoints: Vec<OutPoint>,
+}
+
+/// Pubkey that can be set in BitAsset data
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ToSchema,
+)]
+pub enum BitAssetKey {
+    /// Pubkey used for encryption
+    Encryption(#[schema(value_type = String)] EncryptionPubKey),
+    /// Pubkey used for signing messages
+    Signing(#[schema(value_type = String)] VerifyingKey),
+}
+
+impl BitAssetKey {
+    /// Returns `true` if the key is set as the corresponding pubkey in the
+    /// BitAsset data
+    pub fn matches(&self, bitasset_data: &BitAssetData) -> bool {
+        match self {
+            Self::Encryption(encryption_pubkey) => {
+                bitasset_data.encryption_pubkey.as_ref()
+                    == Some(encryption_pubkey)
+            }
+            Self::Signing(signing_pubkey) => {
+                bitasset_data.signing_pubkey.as_ref() == Some(signing_pubkey)
+            }
+        }
+    }
+}
+
+/** Data for the two phases of a BitAsset registration.
+ * The commitment is broadcast in a reservation tx.
+ * The nonce must be retained, and is revealed in the registration tx that
+ * spends the reservation. */
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, ToSchema)]
+pub struct ReservationPrep {
+    pub plain_name: String,
+    /// `hmac(nonce, name_hash)`
+    #[serde(with = "serde_hexstr_human_readable")]
+    #[schema(value_type = String)]
+    pub commitment: Hash,
+    /// `hmac(secret, name_hash)`
+    #[serde(with = "serde_hexstr_human_readable")]
+    #[schema(value_type = String)]
+    pub nonce: Hash,
+}
+
+impl ReservationPrep {
+    /// Derive the nonce and commitment for a name from a secret
+    pub fn new(secret: &[u8; 32], plain_name: &str) -> Self {
+        let name_hash = BitAssetId::from_plain_name(plain_name).0;
+        let nonce = blake3::keyed_hash(secret, &name_hash).into();
+        let commitment = blake3::keyed_hash(&nonce, &name_hash).into();
+        Self {
+            plain_name: plain_name.to_owned(),
+            commitment,
+            nonce,
+        }
+    }
+
+    pub fn bitasset_id(&self) -> BitAssetId {
+        BitAssetId::from_plain_name(&self.plain_name)
+    }
+
+    /// Returns `true` if the nonce opens the commitment for the name
+    pub fn is_valid(&self) -> bool {
+        let name_hash = self.bitasset_id().0;
+        // `blake3::Hash` comparison is constant time
+        blake3::keyed_hash(&self.nonce, &name_hash)
+            == blake3::Hash::from(self.commitment)
+    }
+
+    /// Tx data for the reservation tx
+    pub fn reservation_tx_data(&self) -> TxData {
+        TxData::BitAssetReservation {
+            commitment: self.commitment,
+        }
+    }
+
+    /// Tx data for the registration tx, revealing the nonce
+    pub fn registration_tx_data(
+        &self,
+        bitasset_data: BitAssetData,
+        initial_supply: u64,
+    ) -> TxData {
+        TxData::BitAssetRegistration {
+            name_hash: self.bitasset_id().0,
+            revealed_nonce: self.nonce,
+            bitasset_data: Box::new(bitasset_data),
+            initial_supply,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct TwoWayPegData {
     pub deposits: HashMap<OutPoint, Output>,
@@ -305,9 +478,36 @@ impl Body {
             .collect()
     }
 
+    /// Merkle tree leaves for the coinbase outputs, followed by the txs
+    fn merkle_leaves(&self) -> Vec<Hash> {
+        let coinbase_leaves = self.coinbase.iter().map(merkle::coinbase_leaf);
+        let tx_leaves = self
+            .transactions
+            .iter()
+            .map(|tx| merkle::tx_leaf(tx.txid()));
+        coinbase_leaves.chain(tx_leaves).collect()
+    }
+
+    /// Root of the merkle tree over the coinbase outputs and txids.
+    /// See [`merkle`] for how the tree is constructed.
     pub fn compute_merkle_root(&self) -> MerkleRoot {
-        // FIXME: Compute actual merkle root instead of just a hash.
-        hashes::hash(&(&self.coinbase, &self.transactions)).into()
+        merkle::root(self.merkle_leaves())
+    }
+
+    /// Returns `true` if any node in the merkle tree is equal to its
+    /// sibling. See [`merkle::has_duplicate_nodes`].
+    pub fn has_duplicate_merkle_nodes(&self) -> bool {
+        merkle::has_duplicate_nodes(self.merkle_leaves())
+    }
+
+    /** Compute a proof of inclusion for the specified tx, against the
+     *  merkle root of the body.
+     *  Returns `None` if the tx is not in the body. */
+    pub fn merkle_proof(&self, txid: Txid) -> Option<MerkleProof> {
+        let tx_idx =
+            self.transactions.iter().position(|tx| tx.txid() == txid)?;
+        let index = (self.coinbase.len() + tx_idx) as u32;
+        MerkleProof::new(self.merkle_leaves(), index)
     }
 
     pub fn get_inputs(&self) -> Vec<OutPoint> {
@@ -354,6 +554,24 @@ pub struct Block {
     pub height: u32,
 }
 
+/// A block connected to or disconnected from the tip
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub enum BlockEvent {
+    Connected(Block),
+    Disconnected(Block),
+}
+
+/// Blocks disconnected from and connected to the tip in a reorg
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, ToSchema)]
+pub struct Reorg {
+    /// Disconnected blocks, starting from the old tip
+    pub disconnected: Vec<BlockHash>,
+    /// Connected blocks, ending with the new tip
+    pub connected: Vec<BlockHash>,
+    /// Last block that remains connected
+    pub common_ancestor: BlockHash,
+}
+
 /*
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DisconnectData {
@@ -402,6 +620,29 @@ pub struct TxIn {
     pub idx: u32,
 }
 
+/// Status of a transaction, relative to the current tip
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TxStatus {
+    /// The tx is not known to this node
+    Unknown,
+    /// The tx is in the mempool
+    InMempool,
+    /// The tx is included in a block in the current chain
+    Confirmed {
+        /// Height of the block that includes the tx
+        height: u32,
+        /// Number of blocks in the current chain after the block that
+        /// includes the tx
+        confirmations: u32,
+    },
+    /// The tx was included in blocks that are no longer in the current
+    /// chain, and is not in the mempool
+    Failed,
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum BmmResult {
     Verified,
@@ -444,6 +685,17 @@ pub enum Network {
     Regtest,
 }
 
+impl Network {
+    /// Name of the data directory subdirectory in which data for this
+    /// network is stored
+    pub fn data_dir_name(self) -> &'static str {
+        match self {
+            Self::Signet => "signet",
+            Self::Regtest => "regtest",
+        }
+    }
+}
+
 pub mod open_api_schemas {
     pub use super::output::open_api_schemas::*;
     pub use super::transaction::open_api_schemas::*;
@@ -6,7 +6,7 @@ use utoipa::ToSchema;
 use super::{
     serde_display_fromstr_human_readable, serde_hexstr_human_readable, Address,
     AssetId, BitAssetId, DutchAuctionId, GetBitcoinValue, Hash, InPoint,
-    OutPoint, Txid,
+    OrderId, OutPoint, Txid,
 };
 
 #[derive(
@@ -82,6 +82,8 @@ pub enum OutputContent {
         #[borsh(serialize_with = "borsh_serialize_bitcoin_address")]
         main_address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
     },
+    /// Receipt used to cancel an order and redeem its proceeds
+    OrderReceipt,
 }
 
 impl OutputContent {
@@ -187,7 +189,8 @@ impl GetBitcoinValue for OutputContent {
             | Self::BitAsset(_)
             | Self::BitAssetControl
             | Self::BitAssetReservation
-            | Self::DutchAuctionReceipt => 0,
+            | Self::DutchAuctionReceipt
+            | Self::OrderReceipt => 0,
             Self::Value(value) => value.0,
             Self::Withdrawal { value, .. } => *value,
         }
@@ -216,6 +219,8 @@ pub enum FilledContent {
     BitAssetReservation(Txid, Hash),
     /// Auction ID
     DutchAuctionReceipt(DutchAuctionId),
+    /// Order ID
+    OrderReceipt(OrderId),
 }
 
 impl FilledContent {
@@ -284,6 +289,15 @@ impl FilledContent {
         }
     }
 
+    /** Returns the order ID, if the filled output content corresponds
+     *  to an order receipt output. */
+    pub fn order_receipt(&self) -> Option<OrderId> {
+        match self {
+            Self::OrderReceipt(order_id) => Some(*order_id),
+            _ => None,
+        }
+    }
+
     /// `true` if the output content corresponds to a BitAsset
     pub fn is_bitasset(&self) -> bool {
         matches!(self, Self::BitAsset(_, _))
@@ -309,6 +323,11 @@ impl FilledContent {
         matches!(self, Self::AmmLpToken { .. })
     }
 
+    /// `true` if the output content corresponds to an order receipt
+    pub fn is_order_receipt(&self) -> bool {
+        matches!(self, Self::OrderReceipt(_))
+    }
+
     /// `true` if the output content corresponds to a reservation
     pub fn is_reservation(&self) -> bool {
         matches!(self, Self::BitAssetReservation { .. })
@@ -363,6 +382,7 @@ impl From<FilledContent> for OutputContent {
             FilledContent::DutchAuctionReceipt(_) => {
                 OutputContent::DutchAuctionReceipt
             }
+            FilledContent::OrderReceipt(_) => OutputContent::OrderReceipt,
         }
     }
 }
@@ -521,6 +541,12 @@ impl FilledOutput {
         self.content.lp_token_amount()
     }
 
+    /** Returns the order ID, if the filled output content corresponds
+     *  to an order receipt output. */
+    pub fn order_receipt(&self) -> Option<OrderId> {
+        self.content.order_receipt()
+    }
+
     /// Accessor for content
     pub fn content(&self) -> &FilledContent {
         &self.content
@@ -551,6 +577,11 @@ impl FilledOutput {
         self.content.is_lp_token()
     }
 
+    /// `true` if the output content corresponds to an order receipt
+    pub fn is_order_receipt(&self) -> bool {
+        self.content.is_order_receipt()
+    }
+
     /// True if the output content corresponds to a reservation
     pub fn is_reservation(&self) -> bool {
         self.content.is_reservation()
@@ -19,11 +19,12 @@ use utoipa::{
 use super::{
     address::Address,
     hashes::{
-        self, AssetId, BitAssetId, DutchAuctionId, Hash, MerkleRoot, Txid,
+        self, AssetId, AtomicSwapId, BitAssetId, DutchAuctionId, Hash,
+        MerkleRoot, OrderId, Txid,
     },
     output::FilledContent,
     serde_hexstr_human_readable, AssetOutput, EncryptionPubKey, FilledOutput,
-    GetAddress, GetBitcoinValue, Output, OutputContent,
+    GetAddress, GetBitcoinValue, M6id, Output, OutputContent,
 };
 use crate::authorization::{Authorization, VerifyingKey};
 
@@ -78,7 +79,9 @@ impl PartialSchema for OutPoint {
 }
 
 /// Reference to a tx input.
-#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize, ToSchema,
+)]
 pub enum InPoint {
     /// Transaction input
     Regular {
@@ -88,7 +91,8 @@ pub enum InPoint {
     },
     // Created by mainchain withdrawals
     Withdrawal {
-        txid: bitcoin::Txid,
+        #[schema(value_type = String)]
+        m6id: M6id,
     },
 }
 
@@ -298,6 +302,42 @@ pub struct DutchAuctionParams {
     pub final_price: u64,
 }
 
+/// Parameters of an atomic swap
+#[derive(
+    BorshSerialize, Clone, Copy, Debug, Deserialize, Serialize, ToSchema,
+)]
+pub struct AtomicSwapParams {
+    /// The asset to be locked
+    pub asset: AssetId,
+    /// The amount of the asset to be locked
+    pub amount: u64,
+    /// Address that can claim the locked asset by revealing the preimage
+    pub recipient: Address,
+    /// Address to refund the locked asset to, after the timeout
+    pub refund_address: Address,
+    /// BLAKE3 hash of the preimage that must be revealed to claim
+    #[serde(with = "serde_hexstr_human_readable")]
+    pub hash_lock: Hash,
+    /// Block height from which the swap can no longer be claimed,
+    /// and can be refunded
+    pub timeout: u32,
+}
+
+/// Parameters of a limit order
+#[derive(
+    BorshSerialize, Clone, Copy, Debug, Deserialize, Serialize, ToSchema,
+)]
+pub struct OrderParams {
+    /// The asset to be sold
+    pub sell_asset: AssetId,
+    /// The amount of the sell asset offered
+    pub sell_amount: u64,
+    /// The asset to be received in exchange
+    pub buy_asset: AssetId,
+    /// Price, in units of the buy asset per unit of the sell asset
+    pub price: u64,
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(BorshSerialize, Clone, Debug, Deserialize, Serialize, ToSchema)]
 #[schema(as = TxData)]
@@ -369,6 +409,52 @@ pub enum TransactionData {
         /// Amount of the received quote asset
         amount_received: u64,
     },
+    /// Lock an asset in an atomic swap
+    AtomicSwapLock(AtomicSwapParams),
+    /// Claim the asset locked in an atomic swap, by revealing the preimage
+    AtomicSwapClaim {
+        swap_id: AtomicSwapId,
+        /// Preimage of the hash lock
+        #[serde(with = "serde_hexstr_human_readable")]
+        preimage: Hash,
+        /// Locked asset
+        asset: AssetId,
+        /// Locked amount
+        amount: u64,
+    },
+    /// Refund the asset locked in an atomic swap, after the timeout
+    AtomicSwapRefund {
+        swap_id: AtomicSwapId,
+        /// Locked asset
+        asset: AssetId,
+        /// Locked amount
+        amount: u64,
+    },
+    /// Place a limit order
+    OrderPlace(OrderParams),
+    /// Fill some or all of a limit order
+    OrderFill {
+        order_id: OrderId,
+        /// Asset to receive from the order
+        receive_asset: AssetId,
+        /// Quantity of the sell asset to purchase from the order
+        quantity: u64,
+        /// Asset to pay with
+        spend_asset: AssetId,
+        /// Total amount paid, in terms of the buy asset
+        spend_amount: u64,
+    },
+    /// Cancel a limit order, redeeming the unfilled remainder and proceeds
+    OrderCancel {
+        /// Sell asset
+        asset_offered: AssetId,
+        /// Buy asset
+        asset_receive: AssetId,
+        /// Amount of the sell asset remaining
+        amount_offered_remaining: u64,
+        /// Amount of the buy asset received
+        amount_received: u64,
+    },
 }
 
 pub type TxData = TransactionData;
@@ -480,6 +566,42 @@ pub struct DutchAuctionCollect {
     pub amount_received: u64,
 }
 
+/// Struct describing an atomic swap claim or refund
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AtomicSwapRelease {
+    pub swap_id: AtomicSwapId,
+    /// Preimage of the hash lock, if the swap is claimed.
+    /// `None` if the swap is refunded.
+    pub preimage: Option<Hash>,
+    pub asset: AssetId,
+    /// Amount of the locked asset released
+    pub amount: u64,
+}
+
+/// Struct describing an order fill
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OrderFill {
+    pub order_id: OrderId,
+    pub asset_spend: AssetId,
+    pub asset_receive: AssetId,
+    /// Amount of spend asset spent
+    pub amount_spend: u64,
+    //// Amount of receive asset received
+    pub amount_receive: u64,
+}
+
+/// Struct describing an order cancellation
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OrderCancel {
+    pub order_id: OrderId,
+    pub asset_offered: AssetId,
+    pub asset_receive: AssetId,
+    /// Amount of offered asset remaining
+    pub amount_offered_remaining: u64,
+    //// Amount of receive asset received
+    pub amount_received: u64,
+}
+
 impl std::fmt::Display for OutPoint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -494,6 +616,48 @@ impl std::fmt::Display for OutPoint {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum ParseOutPointError {
+    #[error(transparent)]
+    BitcoinTxid(#[from] bitcoin::hashes::hex::HexToArrayError),
+    #[error(transparent)]
+    FromHex(#[from] hex::FromHexError),
+    #[error(
+        "expected `<regular|coinbase|deposit> <id> <vout>`, but got `{0}`"
+    )]
+    InvalidFormat(String),
+    #[error(transparent)]
+    Vout(#[from] std::num::ParseIntError),
+}
+
+/// Parses the format used by the [`Display`](std::fmt::Display) impl
+impl std::str::FromStr for OutPoint {
+    type Err = ParseOutPointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [kind, id, vout] = s.split_whitespace().collect::<Vec<_>>()[..]
+        else {
+            return Err(ParseOutPointError::InvalidFormat(s.to_owned()));
+        };
+        let vout: u32 = vout.parse()?;
+        match kind {
+            "regular" => {
+                let txid = <Txid as hex::FromHex>::from_hex(id)?;
+                Ok(Self::Regular { txid, vout })
+            }
+            "coinbase" => {
+                let merkle_root = <Hash as hex::FromHex>::from_hex(id)?.into();
+                Ok(Self::Coinbase { merkle_root, vout })
+            }
+            "deposit" => {
+                let txid = id.parse()?;
+                Ok(Self::Deposit(bitcoin::OutPoint { txid, vout }))
+            }
+            _ => Err(ParseOutPointError::InvalidFormat(s.to_owned())),
+        }
+    }
+}
+
 impl TxData {
     /// `true` if the tx data corresponds to an AMM burn
     pub fn is_amm_burn(&self) -> bool {
@@ -510,6 +674,21 @@ impl TxData {
         matches!(self, Self::AmmSwap { .. })
     }
 
+    /// `true` if the tx data corresponds to an atomic swap claim
+    pub fn is_atomic_swap_claim(&self) -> bool {
+        matches!(self, Self::AtomicSwapClaim { .. })
+    }
+
+    /// `true` if the tx data corresponds to an atomic swap lock
+    pub fn is_atomic_swap_lock(&self) -> bool {
+        matches!(self, Self::AtomicSwapLock(_))
+    }
+
+    /// `true` if the tx data corresponds to an atomic swap refund
+    pub fn is_atomic_swap_refund(&self) -> bool {
+        matches!(self, Self::AtomicSwapRefund { .. })
+    }
+
     /// `true` if the tx data corresponds to a Dutch auction bid
     pub fn is_dutch_auction_bid(&self) -> bool {
         matches!(self, Self::DutchAuctionBid { .. })
@@ -525,6 +704,21 @@ impl TxData {
         matches!(self, Self::DutchAuctionCollect { .. })
     }
 
+    /// `true` if the tx data corresponds to an order cancellation
+    pub fn is_order_cancel(&self) -> bool {
+        matches!(self, Self::OrderCancel { .. })
+    }
+
+    /// `true` if the tx data corresponds to an order fill
+    pub fn is_order_fill(&self) -> bool {
+        matches!(self, Self::OrderFill { .. })
+    }
+
+    /// `true` if the tx data corresponds to an order placement
+    pub fn is_order_place(&self) -> bool {
+        matches!(self, Self::OrderPlace(_))
+    }
+
     /// `true` if the tx data corresponds to a reservation
     pub fn is_registration(&self) -> bool {
         matches!(self, Self::BitAssetRegistration { .. })
@@ -598,6 +792,30 @@ impl Transaction {
         }
     }
 
+    /// `true` if the tx data corresponds to an atomic swap claim
+    pub fn is_atomic_swap_claim(&self) -> bool {
+        match &self.data {
+            Some(tx_data) => tx_data.is_atomic_swap_claim(),
+            None => false,
+        }
+    }
+
+    /// `true` if the tx data corresponds to an atomic swap lock
+    pub fn is_atomic_swap_lock(&self) -> bool {
+        match &self.data {
+            Some(tx_data) => tx_data.is_atomic_swap_lock(),
+            None => false,
+        }
+    }
+
+    /// `true` if the tx data corresponds to an atomic swap refund
+    pub fn is_atomic_swap_refund(&self) -> bool {
+        match &self.data {
+            Some(tx_data) => tx_data.is_atomic_swap_refund(),
+            None => false,
+        }
+    }
+
     /// `true` if the tx data corresponds to a Dutch auction bid
     pub fn is_dutch_auction_bid(&self) -> bool {
         match &self.data {
@@ -622,6 +840,30 @@ impl Transaction {
         }
     }
 
+    /// `true` if the tx data corresponds to an order cancellation
+    pub fn is_order_cancel(&self) -> bool {
+        match &self.data {
+            Some(tx_data) => tx_data.is_order_cancel(),
+            None => false,
+        }
+    }
+
+    /// `true` if the tx data corresponds to an order fill
+    pub fn is_order_fill(&self) -> bool {
+        match &self.data {
+            Some(tx_data) => tx_data.is_order_fill(),
+            None => false,
+        }
+    }
+
+    /// `true` if the tx data corresponds to an order placement
+    pub fn is_order_place(&self) -> bool {
+        match &self.data {
+            Some(tx_data) => tx_data.is_order_place(),
+            None => false,
+        }
+    }
+
     /// `true` if the tx data corresponds to a BitAsset registration
     pub fn is_registration(&self) -> bool {
         match &self.data {
@@ -742,6 +984,21 @@ impl FilledTransaction {
         self.transaction.is_amm_swap()
     }
 
+    /// `true` if the tx data corresponds to an atomic swap claim
+    pub fn is_atomic_swap_claim(&self) -> bool {
+        self.transaction.is_atomic_swap_claim()
+    }
+
+    /// `true` if the tx data corresponds to an atomic swap lock
+    pub fn is_atomic_swap_lock(&self) -> bool {
+        self.transaction.is_atomic_swap_lock()
+    }
+
+    /// `true` if the tx data corresponds to an atomic swap refund
+    pub fn is_atomic_swap_refund(&self) -> bool {
+        self.transaction.is_atomic_swap_refund()
+    }
+
     /// `true` if the tx data corresponds to a Dutch auction bid
     pub fn is_dutch_auction_bid(&self) -> bool {
         self.transaction.is_dutch_auction_bid()
@@ -757,6 +1014,21 @@ impl FilledTransaction {
         self.transaction.is_dutch_auction_collect()
     }
 
+    /// `true` if the tx data corresponds to an order cancellation
+    pub fn is_order_cancel(&self) -> bool {
+        self.transaction.is_order_cancel()
+    }
+
+    /// `true` if the tx data corresponds to an order fill
+    pub fn is_order_fill(&self) -> bool {
+        self.transaction.is_order_fill()
+    }
+
+    /// `true` if the tx data corresponds to an order placement
+    pub fn is_order_place(&self) -> bool {
+        self.transaction.is_order_place()
+    }
+
     /// `true` if the tx data corresponds to a BitAsset registration
     pub fn is_registration(&self) -> bool {
         self.transaction.is_registration()
@@ -782,6 +1054,17 @@ impl FilledTransaction {
         &self.transaction.outputs
     }
 
+    /** If the tx is a BitAsset update, returns the updated BitAsset.
+     *  The updated BitAsset is the BitAsset that corresponds to the last
+     *  BitAsset control coin input. */
+    pub fn updated_bitasset(&self) -> Option<BitAssetId> {
+        if !self.is_update() {
+            return None;
+        }
+        let (_, control_output) = self.spent_bitasset_controls().next_back()?;
+        control_output.get_bitasset()
+    }
+
     /** If the tx is an AMM burn, returns the LP token's
      *  corresponding [`AmmBurn`]. */
     pub fn amm_burn(&self) -> Option<AmmBurn> {
@@ -814,12 +1097,19 @@ impl FilledTransaction {
                 lp_token_mint,
             }) => match self.unique_spent_assets().get(0..=1) {
                 Some([(first_asset, _), (second_asset, _)]) => {
-                    let mut assets = [first_asset, second_asset];
-                    assets.sort();
-                    let [asset0, asset1] = assets;
+                    /* `amount0` and `amount1` correspond to the first and
+                     * second unique spent assets. If the spent assets are not
+                     * in the canonical order for the AMM pair, then the
+                     * amounts must be swapped along with the assets. */
+                    let (asset0, amount0, asset1, amount1) =
+                        if first_asset <= second_asset {
+                            (*first_asset, amount0, *second_asset, amount1)
+                        } else {
+                            (*second_asset, amount1, *first_asset, amount0)
+                        };
                     Some(AmmMint {
-                        asset0: *asset0,
-                        asset1: *asset1,
+                        asset0,
+                        asset1,
                         amount0,
                         amount1,
                         lp_token_mint,
@@ -851,6 +1141,46 @@ impl FilledTransaction {
         }
     }
 
+    /** If the tx is an atomic swap lock,
+     *  returns the corresponding [`AtomicSwapParams`]. */
+    pub fn atomic_swap_lock(&self) -> Option<AtomicSwapParams> {
+        match self.transaction.data {
+            Some(TransactionData::AtomicSwapLock(atomic_swap_params)) => {
+                Some(atomic_swap_params)
+            }
+            _ => None,
+        }
+    }
+
+    /** If the tx is an atomic swap claim or refund,
+     *  returns the corresponding [`AtomicSwapRelease`]. */
+    pub fn atomic_swap_release(&self) -> Option<AtomicSwapRelease> {
+        match self.transaction.data {
+            Some(TransactionData::AtomicSwapClaim {
+                swap_id,
+                preimage,
+                asset,
+                amount,
+            }) => Some(AtomicSwapRelease {
+                swap_id,
+                preimage: Some(preimage),
+                asset,
+                amount,
+            }),
+            Some(TransactionData::AtomicSwapRefund {
+                swap_id,
+                asset,
+                amount,
+            }) => Some(AtomicSwapRelease {
+                swap_id,
+                preimage: None,
+                asset,
+                amount,
+            }),
+            _ => None,
+        }
+    }
+
     /** If the tx is a valid BitAsset mint,
      *  returns the BitAsset ID and mint amount */
     pub fn bitasset_mint(&self) -> Option<(BitAssetId, u64)> {
@@ -928,6 +1258,63 @@ impl FilledTransaction {
         }
     }
 
+    /** If the tx is an order cancellation,
+     *  returns the corresponding [`OrderCancel`]. */
+    pub fn order_cancel(&self) -> Option<OrderCancel> {
+        match self.transaction.data {
+            Some(TransactionData::OrderCancel {
+                asset_offered,
+                asset_receive,
+                amount_offered_remaining,
+                amount_received,
+            }) => {
+                let mut spent_order_receipts = self.spent_order_receipts();
+                let order_id =
+                    spent_order_receipts.next()?.1.order_receipt()?;
+                Some(OrderCancel {
+                    order_id,
+                    asset_offered,
+                    asset_receive,
+                    amount_offered_remaining,
+                    amount_received,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /** If the tx is an order fill,
+     *  returns the corresponding [`OrderFill`]. */
+    pub fn order_fill(&self) -> Option<OrderFill> {
+        match self.transaction.data {
+            Some(TransactionData::OrderFill {
+                order_id,
+                receive_asset,
+                quantity,
+                spend_asset,
+                spend_amount,
+            }) => Some(OrderFill {
+                order_id,
+                asset_spend: spend_asset,
+                asset_receive: receive_asset,
+                amount_spend: spend_amount,
+                amount_receive: quantity,
+            }),
+            _ => None,
+        }
+    }
+
+    /** If the tx is an order placement,
+     *  returns the corresponding [`OrderParams`]. */
+    pub fn order_place(&self) -> Option<OrderParams> {
+        match self.transaction.data {
+            Some(TransactionData::OrderPlace(order_params)) => {
+                Some(order_params)
+            }
+            _ => None,
+        }
+    }
+
     /// If the tx is a BitAsset registration, returns the registered name hash
     pub fn registration_name_hash(&self) -> Option<Hash> {
         self.transaction.registration_name_hash()
@@ -1069,6 +1456,14 @@ impl FilledTransaction {
         })
     }
 
+    /// Return an iterator over spent order receipts
+    pub fn spent_order_receipts(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (&OutPoint, &FilledOutput)> {
+        self.spent_inputs()
+            .filter(|(_, filled_output)| filled_output.is_order_receipt())
+    }
+
     /// Return an iterator over spent AMM LP tokens
     pub fn spent_lp_tokens(
         &self,
@@ -1100,6 +1495,36 @@ impl FilledTransaction {
             .collect()
     }
 
+    /** Returns `true` if the LP token outputs exactly account for the LP
+     *  tokens that must appear in the outputs, for every AMM pool.
+     *  LP tokens can only be created by an AMM mint and destroyed by an
+     *  AMM burn. */
+    pub fn lp_tokens_conserved(&self) -> bool {
+        let Some(filled_outputs) = self.filled_outputs() else {
+            return false;
+        };
+        // Combined output amount for each LP token
+        let mut output_amounts = HashMap::<(AssetId, AssetId), u64>::new();
+        for (asset0, asset1, amount) in filled_outputs
+            .iter()
+            .filter_map(FilledOutput::lp_token_amount)
+        {
+            let combined_amount =
+                output_amounts.entry((asset0, asset1)).or_default();
+            let Some(new_amount) = combined_amount.checked_add(amount) else {
+                return false;
+            };
+            *combined_amount = new_amount;
+        }
+        self.output_lp_token_total_amounts().all(
+            |(asset0, asset1, total_amount)| {
+                let output_amount =
+                    output_amounts.remove(&(asset0, asset1)).unwrap_or(0);
+                total_amount == Some(output_amount)
+            },
+        ) && output_amounts.is_empty()
+    }
+
     /** Returns an iterator over total value for each asset that must
      *  appear in the outputs, in order.
      *  The total output value can possibly over/underflow in a transaction,
@@ -1178,6 +1603,14 @@ impl FilledTransaction {
             self.dutch_auction_create().map(|auction_params| {
                 (auction_params.base_asset, auction_params.base_amount)
             });
+        let mut atomic_swap_lock_spend =
+            self.atomic_swap_lock().map(|atomic_swap_params| {
+                (atomic_swap_params.asset, atomic_swap_params.amount)
+            });
+        let mut atomic_swap_release_receive =
+            self.atomic_swap_release().map(|atomic_swap_release| {
+                (atomic_swap_release.asset, atomic_swap_release.amount)
+            });
         let (mut dutch_auction_collect0, mut dutch_auction_collect1) =
             match self.dutch_auction_collect() {
                 Some(DutchAuctionCollect {
@@ -1192,6 +1625,36 @@ impl FilledTransaction {
                 ),
                 None => (None, None),
             };
+        let mut order_place_spend = self.order_place().map(|order_params| {
+            (order_params.sell_asset, order_params.sell_amount)
+        });
+        let (mut order_fill_spend, mut order_fill_receive) =
+            match self.order_fill() {
+                Some(OrderFill {
+                    order_id: _,
+                    asset_spend,
+                    asset_receive,
+                    amount_spend,
+                    amount_receive,
+                }) => (
+                    Some((asset_spend, amount_spend)),
+                    Some((asset_receive, amount_receive)),
+                ),
+                None => (None, None),
+            };
+        let (mut order_cancel0, mut order_cancel1) = match self.order_cancel() {
+            Some(OrderCancel {
+                order_id: _,
+                asset_offered,
+                asset_receive,
+                amount_offered_remaining,
+                amount_received,
+            }) => (
+                Some((asset_offered, amount_offered_remaining)),
+                Some((asset_receive, amount_received)),
+            ),
+            None => (None, None),
+        };
         self.unique_spent_assets()
             .into_iter()
             .map(move |(asset, total_value)| {
@@ -1262,6 +1725,48 @@ impl FilledTransaction {
                 {
                     dutch_auction_collect1 = None;
                     total_value.checked_add(receive_amount)
+                } else if let Some((spend_asset, spend_amount)) =
+                    atomic_swap_lock_spend
+                    && spend_asset == asset
+                {
+                    atomic_swap_lock_spend = None;
+                    total_value.checked_sub(spend_amount)
+                } else if let Some((receive_asset, receive_amount)) =
+                    atomic_swap_release_receive
+                    && receive_asset == asset
+                {
+                    atomic_swap_release_receive = None;
+                    total_value.checked_add(receive_amount)
+                } else if let Some((spend_asset, spend_amount)) =
+                    order_place_spend
+                    && spend_asset == asset
+                {
+                    order_place_spend = None;
+                    total_value.checked_sub(spend_amount)
+                } else if let Some((receive_asset, receive_amount)) =
+                    order_fill_receive
+                    && receive_asset == asset
+                {
+                    order_fill_receive = None;
+                    total_value.checked_add(receive_amount)
+                } else if let Some((spend_asset, spend_amount)) =
+                    order_fill_spend
+                    && spend_asset == asset
+                {
+                    order_fill_spend = None;
+                    total_value.checked_sub(spend_amount)
+                } else if let Some((receive_asset, receive_amount)) =
+                    order_cancel0
+                    && receive_asset == asset
+                {
+                    order_cancel0 = None;
+                    total_value.checked_add(receive_amount)
+                } else if let Some((receive_asset, receive_amount)) =
+                    order_cancel1
+                    && receive_asset == asset
+                {
+                    order_cancel1 = None;
+                    total_value.checked_add(receive_amount)
                 } else {
                     Some(total_value)
                 };
@@ -1312,6 +1817,32 @@ impl FilledTransaction {
                     (receive_asset, Some(receive_amount))
                 },
             ))
+            .chain(atomic_swap_lock_spend.map(|(spend_asset, _)|
+                    /* If the BitAssets are not already accounted for,
+                    * indicate an underflow */
+                    (spend_asset, None)))
+            .chain(atomic_swap_release_receive.map(
+                |(receive_asset, receive_amount)| {
+                    (receive_asset, Some(receive_amount))
+                },
+            ))
+            .chain(order_place_spend.map(|(spend_asset, _)|
+                    /* If the BitAssets are not already accounted for,
+                    * indicate an underflow */
+                    (spend_asset, None)))
+            .chain(order_fill_receive.map(|(receive_asset, receive_amount)| {
+                (receive_asset, Some(receive_amount))
+            }))
+            .chain(order_fill_spend.map(|(spend_asset, _)|
+                    /* If the BitAssets are not already accounted for,
+                    * indicate an underflow */
+                    (spend_asset, None)))
+            .chain(order_cancel0.map(|(receive_asset, receive_amount)| {
+                (receive_asset, Some(receive_amount))
+            }))
+            .chain(order_cancel1.map(|(receive_asset, receive_amount)| {
+                (receive_asset, Some(receive_amount))
+            }))
             .chain(new_bitasset_value.map(|(bitasset, total_value)| {
                 (AssetId::BitAsset(bitasset), Some(total_value))
             }))
@@ -1449,6 +1980,32 @@ impl FilledTransaction {
             .chain(new_dutch_auction_receipt_content)
     }
 
+    /// Compute the filled content for order receipt outputs.
+    // WARNING: do not expose DoubleEndedIterator.
+    fn filled_order_receipts(
+        &self,
+    ) -> impl Iterator<Item = FilledContent> + '_ {
+        /* If this tx is an order placement, this is the content of the
+         * output corresponding to the newly created order receipt,
+         * which is the last order receipt output. */
+        let new_order_receipt_content = if self.is_order_place() {
+            let order_id = OrderId(self.txid());
+            Some(FilledContent::OrderReceipt(order_id))
+        } else {
+            None
+        };
+        let mut spent_order_receipts = self.spent_order_receipts();
+        /* If this tx is an order cancellation,
+        the first order receipt is burned */
+        if self.is_order_cancel() {
+            let _ = spent_order_receipts.next();
+        }
+        spent_order_receipts
+            .map(|(_, filled_output)| filled_output.content())
+            .cloned()
+            .chain(new_order_receipt_content)
+    }
+
     /// compute the filled content for BitAsset reservation outputs
     /// WARNING: do not expose DoubleEndedIterator.
     fn filled_reservation_output_content(
@@ -1502,6 +2059,7 @@ impl FilledTransaction {
             self.filled_bitasset_control_output_content();
         let mut filled_dutch_auction_receipts =
             self.filled_dutch_auction_receipts();
+        let mut filled_order_receipts = self.filled_order_receipts();
         let mut filled_reservation_output_content =
             self.filled_reservation_output_content();
         self.outputs()
@@ -1578,6 +2136,9 @@ impl FilledTransaction {
                         main_fee,
                         main_address,
                     },
+                    OutputContent::OrderReceipt => {
+                        filled_order_receipts.next()?
+                    }
                 };
                 Some(FilledOutput {
                     address: output.address,
@@ -1,11 +1,15 @@
 //! Utility and convenience types and functions
 
+use std::path::{Path, PathBuf};
+
 use futures::Stream;
-use heed::{Database, DefaultComparator, RoTxn, RwTxn};
+use heed::{types::DecodeIgnore, Database, DefaultComparator, RoTxn, RwTxn};
 use serde::{Deserialize, Serialize};
 use tokio::sync::watch;
 use tokio_stream::wrappers::WatchStream;
 
+use crate::types::Network;
+
 /// Watchable types
 pub mod watchable {
     use futures::Stream;
@@ -73,6 +77,21 @@ impl<KC, DC, C> WatchableDb<KC, DC, C> {
         self.db.clear(rwtxn).inspect(|()| self.broadcast_modified())
     }
 
+    /// Returns true if the key exists, without decoding the value
+    pub fn contains_key<'a>(
+        &self,
+        rotxn: &RoTxn<'_>,
+        key: &'a KC::EItem,
+    ) -> heed::Result<bool>
+    where
+        KC: heed::BytesEncode<'a>,
+    {
+        self.db
+            .remap_data_type::<DecodeIgnore>()
+            .get(rotxn, key)
+            .map(|res| res.is_some())
+    }
+
     pub fn delete<'a>(
         &self,
         rwtxn: &mut RwTxn<'_>,
@@ -210,3 +229,50 @@ impl EnvExt for heed::Env {
             .map(WatchableDb::from)
     }
 }
+
+/// Error returned by [`network_data_path`]
+#[derive(Debug, thiserror::Error)]
+pub enum NetworkDataPathError {
+    #[error("io error")]
+    Io(#[from] std::io::Error),
+    #[error(
+        "found {} in the flat data directory layout used by previous \
+         versions, but the network that it belongs to is unknown. Move it \
+         into the `signet` or `regtest` subdirectory of the data directory, \
+         according to the network that it was created for (eg. to {} if it \
+         was created for {}), and restart.",
+        .legacy_path.display(),
+        .path.display(),
+        .network.data_dir_name()
+    )]
+    UnknownLegacyNetwork {
+        legacy_path: PathBuf,
+        path: PathBuf,
+        network: Network,
+    },
+}
+
+/** Returns the path of a file or directory in the network-specific
+ *  subdirectory of the data directory, eg. `<DATADIR>/signet/data.mdb`.
+ *  Previous versions stored data directly in the data directory, without
+ *  recording the network that it was created for. Such data can not be
+ *  migrated safely, since it could belong to a different network than the
+ *  one being opened. If the path does not exist, but exists directly in the
+ *  data directory, an error is returned, and the data must be moved into
+ *  the subdirectory for its network manually. */
+pub fn network_data_path(
+    datadir: &Path,
+    network: Network,
+    name: &str,
+) -> Result<PathBuf, NetworkDataPathError> {
+    let path = datadir.join(network.data_dir_name()).join(name);
+    let legacy_path = datadir.join(name);
+    if !path.try_exists()? && legacy_path.try_exists()? {
+        return Err(NetworkDataPathError::UnknownLegacyNetwork {
+            legacy_path,
+            path,
+            network,
+        });
+    }
+    Ok(path)
+}
@@ -2,25 +2,35 @@ use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
     path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+use argon2::Argon2;
 use bip300301::bitcoin;
 use byteorder::{BigEndian, ByteOrder};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305,
+};
 use ed25519_dalek_bip32::{ChildIndex, DerivationPath, ExtendedSigningKey};
 use futures::{Stream, StreamExt};
 use heed::{
-    types::{Bytes, SerdeBincode, Str, U8},
+    types::{Bytes, SerdeBincode, Str, Unit, U8},
     RoTxn,
 };
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 use tokio_stream::{wrappers::WatchStream, StreamMap};
 
 use crate::{
     authorization::{self, get_address, Authorization},
     types::{
-        Address, AssetId, AuthorizedTransaction, BitAssetData, BitAssetId,
+        Address, AssetId, AtomicSwapId, AtomicSwapParams,
+        AuthorizedTransaction, BitAssetData, BitAssetDataUpdates, BitAssetId,
         BitcoinOutputContent, DutchAuctionId, DutchAuctionParams, FilledOutput,
-        GetBitcoinValue, Hash, InPoint, OutPoint, Output, OutputContent,
-        SpentOutput, Transaction, TxData,
+        GetBitcoinValue, Hash, InPoint, OrderId, OrderParams, OutPoint, Output,
+        OutputContent, ReservationPrep, SpentOutput, Transaction, TxData,
     },
     util::{EnvExt, Watchable, WatchableDb},
 };
@@ -31,16 +41,30 @@ pub enum Error {
     AddressDoesNotExist { address: crate::types::Address },
     #[error("authorization error")]
     Authorization(#[from] crate::authorization::Error),
+    #[error("bincode error")]
+    Bincode(#[from] bincode::Error),
     #[error("bip32 error")]
     Bip32(#[from] ed25519_dalek_bip32::Error),
     #[error("heed error")]
     Heed(#[from] heed::Error),
+    #[error("incorrect wallet passphrase")]
+    IncorrectPassphrase,
+    #[error(
+        "nonce does not open the reservation commitment for `{plain_name}`"
+    )]
+    InvalidReservationPrep { plain_name: String },
     #[error("io error")]
     Io(#[from] std::io::Error),
+    #[error("key derivation error: {0}")]
+    KeyDerivation(argon2::Error),
     #[error("no index for address {address}")]
     NoIndex { address: Address },
-    #[error("wallet doesn't have a seed")]
-    NoSeed,
+    #[error("wallet seed was not set from a mnemonic seed phrase")]
+    NoMnemonic,
+    #[error(
+        "wallet doesn't have a seed; set one with `set_seed_from_mnemonic`"
+    )]
+    NoWalletSeed,
     #[error("could not find bitasset reservation for `{plain_name}`")]
     NoBitassetReservation { plain_name: String },
     #[error("not enough funds")]
@@ -51,15 +75,98 @@ pub enum Error {
     ParseMnemonic(#[source] anyhow::Error),
     #[error("seed has already been set")]
     SeedAlreadyExists,
+    #[error("wallet is already encrypted")]
+    WalletAlreadyEncrypted,
+    #[error("unlock timeout exceeds the maximum of {}s", max.as_secs())]
+    UnlockTimeoutTooLong { max: Duration },
+    #[error("wallet is locked; unlock it with `unlock_wallet`")]
+    WalletLocked,
+    #[error("wallet is not encrypted")]
+    WalletNotEncrypted,
+}
+
+/// Wallet seed and mnemonic seed phrase (if the seed was set from a
+/// mnemonic), encrypted with a key derived from a passphrase
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct EncryptedSeed {
+    /// Argon2 salt
+    salt: [u8; 16],
+    /// ChaCha20-Poly1305 nonce
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+}
+
+impl EncryptedSeed {
+    fn derive_key(
+        passphrase: &str,
+        salt: &[u8; 16],
+    ) -> Result<chacha20poly1305::Key, Error> {
+        let mut key = chacha20poly1305::Key::default();
+        let () = Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(Error::KeyDerivation)?;
+        Ok(key)
+    }
+
+    fn encrypt(
+        seed: &[u8; 64],
+        mnemonic: Option<&str>,
+        passphrase: &str,
+    ) -> Result<Self, Error> {
+        let plaintext = bincode::serialize(&(seed.as_slice(), mnemonic))?;
+        let mut salt = [0; 16];
+        OsRng.fill_bytes(&mut salt);
+        let key = Self::derive_key(passphrase, &salt)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = ChaCha20Poly1305::new(&key)
+            .encrypt(&nonce, plaintext.as_slice())
+            .expect("encrypting a seed should not fail");
+        Ok(Self {
+            salt,
+            nonce: nonce.into(),
+            ciphertext,
+        })
+    }
+
+    /// Returns the seed, and the mnemonic if the seed was set from a
+    /// mnemonic
+    fn decrypt(
+        &self,
+        passphrase: &str,
+    ) -> Result<([u8; 64], Option<String>), Error> {
+        let key = Self::derive_key(passphrase, &self.salt)?;
+        let plaintext = ChaCha20Poly1305::new(&key)
+            .decrypt(&self.nonce.into(), self.ciphertext.as_slice())
+            .map_err(|_| Error::IncorrectPassphrase)?;
+        let (seed, mnemonic): (Vec<u8>, Option<String>) =
+            bincode::deserialize(&plaintext)?;
+        let seed = seed.try_into().map_err(|_| Error::IncorrectPassphrase)?;
+        Ok((seed, mnemonic))
+    }
+}
+
+/// Decrypted seed for an encrypted wallet
+struct UnlockedSeed {
+    seed: [u8; 64],
+    mnemonic: Option<String>,
+    /// The wallet is automatically locked at this time
+    expires_at: Instant,
 }
 
 #[derive(Clone)]
 pub struct Wallet {
     env: heed::Env,
     // Seed is always [u8; 64], but due to serde not implementing serialize
-    // for [T; 64], use heed's `Bytes`
-    // TODO: Don't store the seed in plaintext.
+    // for [T; 64], use heed's `Bytes`.
+    // Only set if the wallet is not encrypted.
     seed: WatchableDb<U8, Bytes>,
+    /// Mnemonic seed phrase, if the seed was set from a mnemonic.
+    /// Only set if the wallet is not encrypted.
+    mnemonic: WatchableDb<U8, Str>,
+    /// Only set if the wallet is encrypted
+    encrypted_seed: WatchableDb<U8, SerdeBincode<EncryptedSeed>>,
+    /// Decrypted seed, if the wallet is encrypted and unlocked
+    unlocked_seed: Arc<RwLock<Option<UnlockedSeed>>>,
     /// Map each address to it's index
     address_to_index: WatchableDb<SerdeBincode<Address>, SerdeBincode<[u8; 4]>>,
     /// Map each address index to an address
@@ -76,10 +183,21 @@ pub struct Wallet {
     bitasset_reservations: WatchableDb<SerdeBincode<[u8; 32]>, Str>,
     /// Associates BitAssets with plaintext names
     known_bitassets: WatchableDb<SerdeBincode<BitAssetId>, Str>,
+    /// UTXOs that are reserved for manual coin control, and are never
+    /// selected automatically
+    reserved_utxos: WatchableDb<SerdeBincode<OutPoint>, Unit>,
+    /// Deposit UTXOs that do not yet have enough mainchain confirmations to
+    /// be spent, with the number of confirmations of each
+    immature_deposits: WatchableDb<SerdeBincode<OutPoint>, SerdeBincode<u32>>,
 }
 
 impl Wallet {
-    pub const NUM_DBS: u32 = 9;
+    /// Default duration for which an encrypted wallet remains unlocked
+    pub const DEFAULT_UNLOCK_TIMEOUT: Duration = Duration::from_secs(300);
+    /// Maximum duration for which an encrypted wallet can be unlocked
+    pub const MAX_UNLOCK_TIMEOUT: Duration =
+        Duration::from_secs(7 * 24 * 60 * 60);
+    pub const NUM_DBS: u32 = 13;
 
     pub fn new(path: &Path) -> Result<Self, Error> {
         std::fs::create_dir_all(path)?;
@@ -91,6 +209,9 @@ impl Wallet {
         };
         let mut rwtxn = env.write_txn()?;
         let seed_db = env.create_watchable_db(&mut rwtxn, "seed")?;
+        let mnemonic = env.create_watchable_db(&mut rwtxn, "mnemonic")?;
+        let encrypted_seed =
+            env.create_watchable_db(&mut rwtxn, "encrypted_seed")?;
         let address_to_index =
             env.create_watchable_db(&mut rwtxn, "address_to_index")?;
         let index_to_address =
@@ -105,10 +226,17 @@ impl Wallet {
             env.create_watchable_db(&mut rwtxn, "bitasset_reservations")?;
         let known_bitassets =
             env.create_watchable_db(&mut rwtxn, "known_bitassets")?;
+        let reserved_utxos =
+            env.create_watchable_db(&mut rwtxn, "reserved_utxos")?;
+        let immature_deposits =
+            env.create_watchable_db(&mut rwtxn, "immature_deposits")?;
         rwtxn.commit()?;
         Ok(Self {
             env,
             seed: seed_db,
+            mnemonic,
+            encrypted_seed,
+            unlocked_seed: Arc::new(RwLock::new(None)),
             address_to_index,
             index_to_address,
             unconfirmed_utxos,
@@ -117,15 +245,80 @@ impl Wallet {
             spent_unconfirmed_utxos,
             bitasset_reservations,
             known_bitassets,
+            reserved_utxos,
+            immature_deposits,
         })
     }
 
-    fn get_signing_key(
-        &self,
-        rotxn: &RoTxn,
+    /// Apply a function to the unlocked seed of an encrypted wallet.
+    /// Returns an error if the wallet is locked.
+    fn with_unlocked_seed<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&UnlockedSeed) -> T,
+    {
+        let mut unlocked_seed_write = self.unlocked_seed.write();
+        if let Some(unlocked_seed) = &*unlocked_seed_write
+            && unlocked_seed.expires_at > Instant::now()
+        {
+            return Ok(f(unlocked_seed));
+        }
+        // Lock the wallet if the unlock has expired
+        *unlocked_seed_write = None;
+        Err(Error::WalletLocked)
+    }
+
+    /// Get the seed, which requires that the wallet is unlocked if it is
+    /// encrypted
+    fn get_seed(&self, rotxn: &RoTxn) -> Result<[u8; 64], Error> {
+        if let Some(seed) = self.seed.try_get(rotxn, &0)? {
+            return Ok(seed.try_into().unwrap());
+        }
+        if self.encrypted_seed.try_get(rotxn, &0)?.is_none() {
+            return Err(Error::NoWalletSeed);
+        }
+        self.with_unlocked_seed(|unlocked_seed| unlocked_seed.seed)
+    }
+
+    /// Get the mnemonic seed phrase, which requires that the wallet is
+    /// unlocked if it is encrypted.
+    /// Returns an error if the seed was not set from a mnemonic.
+    pub fn get_mnemonic(&self) -> Result<String, Error> {
+        let rotxn = self.env.read_txn()?;
+        if self.seed.try_get(&rotxn, &0)?.is_some() {
+            return self
+                .mnemonic
+                .try_get(&rotxn, &0)?
+                .map(str::to_owned)
+                .ok_or(Error::NoMnemonic);
+        }
+        if self.encrypted_seed.try_get(&rotxn, &0)?.is_none() {
+            return Err(Error::NoWalletSeed);
+        }
+        self.with_unlocked_seed(|unlocked_seed| unlocked_seed.mnemonic.clone())?
+            .ok_or(Error::NoMnemonic)
+    }
+
+    /// Derive the seed for a mnemonic seed phrase and BIP39 passphrase.
+    /// The passphrase should be empty if the mnemonic is not
+    /// passphrase-protected.
+    pub fn seed_from_mnemonic(
+        mnemonic: &str,
+        passphrase: &str,
+    ) -> Result<[u8; 64], Error> {
+        let mnemonic =
+            bip39::Mnemonic::from_phrase(mnemonic, bip39::Language::English)
+                .map_err(Error::ParseMnemonic)?;
+        let seed = bip39::Seed::new(&mnemonic, passphrase);
+        Ok(seed.as_bytes().try_into().unwrap())
+    }
+
+    /// Derive the signing key with the specified index from a seed, using
+    /// the derivation path `m/1'/0'/0'/{index}'`.
+    /// Changing this breaks recovery of existing wallets.
+    pub fn derive_signing_key(
+        seed: &[u8; 64],
         index: u32,
     ) -> Result<ed25519_dalek::SigningKey, Error> {
-        let seed = self.seed.try_get(rotxn, &0)?.ok_or(Error::NoSeed)?;
         let xpriv = ExtendedSigningKey::from_seed(seed)?;
         let derivation_path = DerivationPath::new([
             ChildIndex::Hardened(1),
@@ -137,6 +330,15 @@ impl Wallet {
         Ok(xsigning_key.signing_key)
     }
 
+    fn get_signing_key(
+        &self,
+        rotxn: &RoTxn,
+        index: u32,
+    ) -> Result<ed25519_dalek::SigningKey, Error> {
+        let seed = self.get_seed(rotxn)?;
+        Self::derive_signing_key(&seed, index)
+    }
+
     // get the signing key that corresponds to the provided address
     fn get_signing_key_for_addr(
         &self,
@@ -171,10 +373,21 @@ impl Wallet {
         Ok(address)
     }
 
-    /// Overwrite the seed, or set it if it does not already exist.
-    pub fn overwrite_seed(&self, seed: &[u8; 64]) -> Result<(), Error> {
+    /// Overwrite the seed and mnemonic, or set them if they do not already
+    /// exist. The new seed is not encrypted.
+    fn overwrite_seed_and_mnemonic(
+        &self,
+        seed: &[u8; 64],
+        mnemonic: Option<&str>,
+    ) -> Result<(), Error> {
         let mut rwtxn = self.env.write_txn()?;
         self.seed.put(&mut rwtxn, &0, seed)?;
+        if let Some(mnemonic) = mnemonic {
+            self.mnemonic.put(&mut rwtxn, &0, mnemonic)?;
+        } else {
+            self.mnemonic.delete(&mut rwtxn, &0)?;
+        }
+        self.encrypted_seed.delete(&mut rwtxn, &0)?;
         self.address_to_index.clear(&mut rwtxn)?;
         self.index_to_address.clear(&mut rwtxn)?;
         self.unconfirmed_utxos.clear(&mut rwtxn)?;
@@ -182,13 +395,105 @@ impl Wallet {
         self.stxos.clear(&mut rwtxn)?;
         self.spent_unconfirmed_utxos.clear(&mut rwtxn)?;
         self.bitasset_reservations.clear(&mut rwtxn)?;
+        self.immature_deposits.clear(&mut rwtxn)?;
         rwtxn.commit()?;
+        *self.unlocked_seed.write() = None;
         Ok(())
     }
 
+    /// Overwrite the seed, or set it if it does not already exist.
+    /// The new seed is not encrypted.
+    pub fn overwrite_seed(&self, seed: &[u8; 64]) -> Result<(), Error> {
+        self.overwrite_seed_and_mnemonic(seed, None)
+    }
+
     pub fn has_seed(&self) -> Result<bool, Error> {
         let rotxn = self.env.read_txn()?;
-        Ok(self.seed.try_get(&rotxn, &0)?.is_some())
+        Ok(self.seed.try_get(&rotxn, &0)?.is_some()
+            || self.encrypted_seed.try_get(&rotxn, &0)?.is_some())
+    }
+
+    pub fn is_encrypted(&self) -> Result<bool, Error> {
+        let rotxn = self.env.read_txn()?;
+        Ok(self.encrypted_seed.try_get(&rotxn, &0)?.is_some())
+    }
+
+    /// Returns `true` if the wallet is encrypted, and not unlocked
+    pub fn is_locked(&self) -> Result<bool, Error> {
+        if !self.is_encrypted()? {
+            return Ok(false);
+        }
+        let unlocked =
+            self.unlocked_seed
+                .read()
+                .as_ref()
+                .is_some_and(|unlocked_seed| {
+                    unlocked_seed.expires_at > Instant::now()
+                });
+        Ok(!unlocked)
+    }
+
+    /// Encrypt the seed with a passphrase. Once encrypted, the wallet must
+    /// be unlocked in order to sign txs or generate addresses.
+    pub fn encrypt(&self, passphrase: &str) -> Result<(), Error> {
+        let mut rwtxn = self.env.write_txn()?;
+        if self.encrypted_seed.try_get(&rwtxn, &0)?.is_some() {
+            return Err(Error::WalletAlreadyEncrypted);
+        }
+        let seed: [u8; 64] = self
+            .seed
+            .try_get(&rwtxn, &0)?
+            .ok_or(Error::NoWalletSeed)?
+            .try_into()
+            .unwrap();
+        let mnemonic = self.mnemonic.try_get(&rwtxn, &0)?;
+        let encrypted_seed =
+            EncryptedSeed::encrypt(&seed, mnemonic, passphrase)?;
+        self.encrypted_seed.put(&mut rwtxn, &0, &encrypted_seed)?;
+        self.seed.delete(&mut rwtxn, &0)?;
+        self.mnemonic.delete(&mut rwtxn, &0)?;
+        rwtxn.commit()?;
+        Ok(())
+    }
+
+    /// Unlock an encrypted wallet. The wallet is automatically locked again
+    /// after the specified timeout, which must not exceed
+    /// [`Self::MAX_UNLOCK_TIMEOUT`].
+    pub fn unlock(
+        &self,
+        passphrase: &str,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        let timeout_too_long = Error::UnlockTimeoutTooLong {
+            max: Self::MAX_UNLOCK_TIMEOUT,
+        };
+        if timeout > Self::MAX_UNLOCK_TIMEOUT {
+            return Err(timeout_too_long);
+        }
+        let expires_at = Instant::now()
+            .checked_add(timeout)
+            .ok_or(timeout_too_long)?;
+        let rotxn = self.env.read_txn()?;
+        let encrypted_seed = self
+            .encrypted_seed
+            .try_get(&rotxn, &0)?
+            .ok_or(Error::WalletNotEncrypted)?;
+        let (seed, mnemonic) = encrypted_seed.decrypt(passphrase)?;
+        *self.unlocked_seed.write() = Some(UnlockedSeed {
+            seed,
+            mnemonic,
+            expires_at,
+        });
+        Ok(())
+    }
+
+    /// Lock an encrypted wallet
+    pub fn lock(&self) -> Result<(), Error> {
+        if !self.is_encrypted()? {
+            return Err(Error::WalletNotEncrypted);
+        }
+        *self.unlocked_seed.write() = None;
+        Ok(())
     }
 
     /// Set the seed, if it does not already exist
@@ -200,15 +505,22 @@ impl Wallet {
         }
     }
 
-    /// Set the seed from a mnemonic seed phrase,
-    /// if the seed does not already exist
-    pub fn set_seed_from_mnemonic(&self, mnemonic: &str) -> Result<(), Error> {
+    /// Set the seed from a mnemonic seed phrase and BIP39 passphrase,
+    /// if the seed does not already exist.
+    /// The passphrase should be empty if the mnemonic is not
+    /// passphrase-protected.
+    pub fn set_seed_from_mnemonic(
+        &self,
+        mnemonic: &str,
+        passphrase: &str,
+    ) -> Result<(), Error> {
+        let seed = Self::seed_from_mnemonic(mnemonic, passphrase)?;
+        if self.has_seed()? {
+            return Err(Error::SeedAlreadyExists);
+        }
         let mnemonic =
-            bip39::Mnemonic::from_phrase(mnemonic, bip39::Language::English)
-                .map_err(Error::ParseMnemonic)?;
-        let seed = bip39::Seed::new(&mnemonic, "");
-        let seed_bytes: [u8; 64] = seed.as_bytes().try_into().unwrap();
-        self.set_seed(&seed_bytes)
+            mnemonic.split_whitespace().collect::<Vec<_>>().join(" ");
+        self.overwrite_seed_and_mnemonic(&seed, Some(&mnemonic))
     }
 
     /// Create a transaction with a fee only.
@@ -279,14 +591,82 @@ impl Wallet {
         Ok(Transaction::new(inputs, outputs))
     }
 
+    /** Create a transfer that may send several assets (eg. Bitcoin and
+     *  BitAssets) in a single tx. Each output is specified by recipient,
+     *  asset, and amount. Change for each asset is sent to this wallet. */
+    pub fn create_multi_asset_transfer(
+        &self,
+        outputs: &[(Address, AssetId, u64)],
+        fee: u64,
+    ) -> Result<Transaction, Error> {
+        fn output_content(asset: AssetId, amount: u64) -> OutputContent {
+            match asset {
+                AssetId::Bitcoin => {
+                    OutputContent::Value(BitcoinOutputContent(amount))
+                }
+                AssetId::BitAsset(_) => OutputContent::BitAsset(amount),
+                AssetId::BitAssetControl(_) => OutputContent::BitAssetControl,
+            }
+        }
+        // Total amount to send for each asset, in order of first appearance
+        let mut asset_totals = Vec::<(AssetId, u64)>::new();
+        for (_, asset, amount) in outputs {
+            match asset_totals.iter_mut().find(|(a, _)| a == asset) {
+                Some((_, total)) => {
+                    *total = total
+                        .checked_add(*amount)
+                        .ok_or(Error::NotEnoughFunds)?
+                }
+                None => asset_totals.push((*asset, *amount)),
+            }
+        }
+        // Bitcoin is always spent, to pay the fee
+        if !asset_totals
+            .iter()
+            .any(|(asset, _)| *asset == AssetId::Bitcoin)
+        {
+            asset_totals.push((AssetId::Bitcoin, 0));
+        }
+        /* Filled BitAsset outputs are assigned to the unique spent BitAssets
+         * in order, so the inputs and outputs for each asset are added
+         * together. */
+        let mut tx = Transaction::default();
+        for (asset, total) in asset_totals {
+            let select_amount = if asset == AssetId::Bitcoin {
+                total.checked_add(fee).ok_or(Error::NotEnoughFunds)?
+            } else {
+                total
+            };
+            let (input_amount, utxos) =
+                self.select_asset_utxos(asset, select_amount)?;
+            tx.inputs.extend(utxos.into_keys());
+            tx.outputs.extend(
+                outputs.iter().filter(|(_, a, _)| *a == asset).map(
+                    |(address, asset, amount)| {
+                        Output::new(*address, output_content(*asset, *amount))
+                    },
+                ),
+            );
+            let change = input_amount - select_amount;
+            if change != 0 {
+                tx.outputs.push(Output::new(
+                    self.get_new_address()?,
+                    output_content(asset, change),
+                ));
+            }
+        }
+        Ok(tx)
+    }
+
     /// given a regular transaction, add a bitasset reservation.
     /// given a bitasset reservation tx, change the reserved name.
     /// panics if the tx is not regular or a bitasset reservation tx.
+    /// returns the commitment and nonce used for the reservation.
     pub fn reserve_bitasset(
         &self,
         tx: &mut Transaction,
         plain_name: &str,
-    ) -> Result<(), Error> {
+    ) -> Result<ReservationPrep, Error> {
         assert!(
             tx.is_regular() || tx.is_reservation(),
             "this function only accepts a regular or bitasset reservation tx"
@@ -316,20 +696,22 @@ impl Wallet {
         let rotxn = self.env.read_txn()?;
         let reservation_signing_key =
             self.get_signing_key_for_addr(&rotxn, &reservation_addr)?;
-        let name_hash: Hash = blake3::hash(plain_name.as_bytes()).into();
-        let bitasset_id = BitAssetId(name_hash);
-        // hmac(secret, name_hash)
-        let nonce =
-            blake3::keyed_hash(reservation_signing_key.as_bytes(), &name_hash)
-                .into();
-        // hmac(nonce, name_hash)
-        let commitment = blake3::keyed_hash(&nonce, &name_hash).into();
+        let prep = ReservationPrep::new(
+            reservation_signing_key.as_bytes(),
+            plain_name,
+        );
         // store reservation data
         let mut rwtxn = self.env.write_txn()?;
-        self.bitasset_reservations
-            .put(&mut rwtxn, &commitment, plain_name)?;
-        self.known_bitassets
-            .put(&mut rwtxn, &bitasset_id, plain_name)?;
+        self.bitasset_reservations.put(
+            &mut rwtxn,
+            &prep.commitment,
+            plain_name,
+        )?;
+        self.known_bitassets.put(
+            &mut rwtxn,
+            &prep.bitasset_id(),
+            plain_name,
+        )?;
         rwtxn.commit()?;
         // if the tx is regular, add a reservation output
         if tx.is_regular() {
@@ -339,8 +721,50 @@ impl Wallet {
             );
             tx.outputs.push(reservation_output);
         };
-        tx.data = Some(TxData::BitAssetReservation { commitment });
-        Ok(())
+        tx.data = Some(prep.reservation_tx_data());
+        Ok(prep)
+    }
+
+    /// Address for registration outputs.
+    /// If the last output is owned by this wallet, then use
+    /// the address associated with the last output
+    fn registration_addr(&self, tx: &Transaction) -> Result<Address, Error> {
+        if let Some(last_output) = tx.outputs.last() {
+            let last_output_addr = last_output.address;
+            let rotxn = self.env.read_txn()?;
+            if self
+                .address_to_index
+                .try_get(&rotxn, &last_output_addr)?
+                .is_some()
+            {
+                return Ok(last_output_addr);
+            }
+        }
+        self.get_new_address()
+    }
+
+    /// Spend the reservation utxo, and add registration outputs and data
+    fn push_registration(
+        tx: &mut Transaction,
+        registration_addr: Address,
+        reservation_outpoint: OutPoint,
+        prep: &ReservationPrep,
+        bitasset_data: BitAssetData,
+        initial_supply: u64,
+    ) {
+        tx.inputs.push(reservation_outpoint);
+        if initial_supply != 0 {
+            let mint_output = Output::new(
+                registration_addr,
+                OutputContent::BitAsset(initial_supply),
+            );
+            tx.outputs.push(mint_output);
+        };
+        let control_coin_output =
+            Output::new(registration_addr, OutputContent::BitAssetControl);
+        tx.outputs.push(control_coin_output);
+        tx.data =
+            Some(prep.registration_tx_data(bitasset_data, initial_supply));
     }
 
     /// given a regular transaction, add a bitasset registration.
@@ -356,30 +780,17 @@ impl Wallet {
     ) -> Result<(), Error> {
         assert!(tx.is_regular(), "this function only accepts a regular tx");
         // address for the registration output
-        let registration_addr =
-            // if the last output is owned by this wallet, then use
-            // the address associated with the last output
-            if let Some(last_output) = tx.outputs.last() {
-                let last_output_addr = last_output.address;
-                let rotxn = self.env.read_txn()?;
-                if self.address_to_index.try_get(&rotxn, &last_output_addr)?.is_some() {
-                    last_output_addr
-                } else {
-                    self.get_new_address()?
-                }
-            } else {
-                self.get_new_address()?
-            };
-        let name_hash: Hash = blake3::hash(plain_name.as_bytes()).into();
-        let bitasset_id = BitAssetId(name_hash);
+        let registration_addr = self.registration_addr(tx)?;
+        let bitasset_id = BitAssetId::from_plain_name(plain_name);
         /* Search for reservation utxo by the following procedure:
         For each reservation:
         * Get the corresponding signing key
         * Compute a reservation commitment for the bitasset to be registered
         * If the computed commitment is the same as the reservation commitment,
           then use this utxo. Otherwise, continue */
-        // outpoint and nonce, if found
-        let mut reservation_outpoint_nonce: Option<(OutPoint, Hash)> = None;
+        // outpoint and prep, if found
+        let mut reservation_outpoint_prep: Option<(OutPoint, ReservationPrep)> =
+            None;
         for (outpoint, filled_output) in self.get_utxos()?.into_iter() {
             if let Some(reservation_commitment) =
                 filled_output.reservation_commitment()
@@ -389,18 +800,16 @@ impl Wallet {
                 let rotxn = self.env.read_txn()?;
                 let reservation_signing_key =
                     self.get_signing_key_for_addr(&rotxn, &reservation_addr)?;
-                // hmac(secret, name_hash)
-                let nonce = blake3::keyed_hash(
+                let prep = ReservationPrep::new(
                     reservation_signing_key.as_bytes(),
-                    &name_hash,
-                )
-                .into();
-                // hmac(nonce, name_hash)
-                let commitment = blake3::keyed_hash(&nonce, &name_hash);
+                    plain_name,
+                );
                 // WARNING: This comparison MUST be done in constant time.
                 // `blake3::Hash` handles this; DO NOT compare as byte arrays
-                if commitment == *reservation_commitment {
-                    reservation_outpoint_nonce = Some((outpoint, nonce));
+                if blake3::Hash::from(prep.commitment)
+                    == *reservation_commitment
+                {
+                    reservation_outpoint_prep = Some((outpoint, prep));
                     break;
                 }
             }
@@ -410,27 +819,211 @@ impl Wallet {
         self.known_bitassets
             .put(&mut rwtxn, &bitasset_id, plain_name)?;
         rwtxn.commit()?;
-        let (reservation_outpoint, nonce) = reservation_outpoint_nonce
+        let (reservation_outpoint, prep) = reservation_outpoint_prep
             .ok_or_else(|| Error::NoBitassetReservation {
                 plain_name: plain_name.to_owned(),
             })?;
-        tx.inputs.push(reservation_outpoint);
-        if initial_supply != 0 {
-            let mint_output = Output::new(
-                registration_addr,
-                OutputContent::BitAsset(initial_supply),
-            );
-            tx.outputs.push(mint_output);
-        };
-        let control_coin_output =
-            Output::new(registration_addr, OutputContent::BitAssetControl);
-        tx.outputs.push(control_coin_output);
-        tx.data = Some(TxData::BitAssetRegistration {
-            name_hash,
-            revealed_nonce: nonce,
-            bitasset_data: Box::new(bitasset_data.into_owned()),
+        Self::push_registration(
+            tx,
+            registration_addr,
+            reservation_outpoint,
+            &prep,
+            bitasset_data.into_owned(),
             initial_supply,
-        });
+        );
+        Ok(())
+    }
+
+    /// given a regular transaction, add a bitasset registration if there is
+    /// a reservation for the plaintext name, or a reservation otherwise.
+    /// panics if the tx is not regular.
+    /// returns `true` if a registration was added.
+    pub fn register_or_reserve_bitasset(
+        &self,
+        tx: &mut Transaction,
+        plain_name: &str,
+        bitasset_data: Cow<BitAssetData>,
+        initial_supply: u64,
+    ) -> Result<bool, Error> {
+        match self.register_bitasset(
+            tx,
+            plain_name,
+            bitasset_data,
+            initial_supply,
+        ) {
+            Ok(()) => Ok(true),
+            Err(Error::NoBitassetReservation { .. }) => {
+                let _: ReservationPrep =
+                    self.reserve_bitasset(tx, plain_name)?;
+                Ok(false)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// given a regular transaction, add a bitasset registration that
+    /// reveals the nonce from a reservation prep.
+    /// panics if the tx is not regular.
+    /// returns an error if the nonce does not open the commitment, or if
+    /// there is no reservation utxo with the commitment.
+    pub fn complete_registration(
+        &self,
+        tx: &mut Transaction,
+        prep: &ReservationPrep,
+        bitasset_data: BitAssetData,
+        initial_supply: u64,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        if !prep.is_valid() {
+            return Err(Error::InvalidReservationPrep {
+                plain_name: prep.plain_name.clone(),
+            });
+        }
+        let commitment = blake3::Hash::from(prep.commitment);
+        let reservation_outpoint = self
+            .get_utxos()?
+            .into_iter()
+            .find_map(|(outpoint, filled_output)| {
+                // WARNING: This comparison MUST be done in constant time.
+                // `blake3::Hash` handles this; DO NOT compare as byte arrays
+                filled_output
+                    .reservation_commitment()
+                    .is_some_and(|reservation_commitment| {
+                        commitment == *reservation_commitment
+                    })
+                    .then_some(outpoint)
+            })
+            .ok_or_else(|| Error::NoBitassetReservation {
+                plain_name: prep.plain_name.clone(),
+            })?;
+        let registration_addr = self.registration_addr(tx)?;
+        let mut rwtxn = self.env.write_txn()?;
+        self.known_bitassets.put(
+            &mut rwtxn,
+            &prep.bitasset_id(),
+            &prep.plain_name,
+        )?;
+        rwtxn.commit()?;
+        Self::push_registration(
+            tx,
+            registration_addr,
+            reservation_outpoint,
+            prep,
+            bitasset_data,
+            initial_supply,
+        );
+        Ok(())
+    }
+
+    /** Spend the control coin for the specified BitAsset as the last
+     *  BitAsset control coin input, and return it to this wallet as the last
+     *  BitAsset control coin output.
+     *  BitAsset updates and mints apply to the BitAsset that corresponds to
+     *  the last BitAsset control coin input. */
+    fn spend_bitasset_control_last(
+        &self,
+        tx: &mut Transaction,
+        bitasset: BitAssetId,
+    ) -> Result<(), Error> {
+        let (control_outpoint, _) = self.select_bitasset_control(bitasset)?;
+        tx.inputs.retain(|outpoint| *outpoint != control_outpoint);
+        tx.inputs.push(control_outpoint);
+        let control_coin_output = Output::new(
+            self.get_new_address()?,
+            OutputContent::BitAssetControl,
+        );
+        tx.outputs.push(control_coin_output);
+        Ok(())
+    }
+
+    /// Given a regular transaction, add a BitAsset data update.
+    pub fn update_bitasset(
+        &self,
+        tx: &mut Transaction,
+        bitasset: BitAssetId,
+        bitasset_updates: BitAssetDataUpdates,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        let () = self.spend_bitasset_control_last(tx, bitasset)?;
+        tx.data = Some(TxData::BitAssetUpdate(Box::new(bitasset_updates)));
+        Ok(())
+    }
+
+    /** Given a regular transaction, add a BitAsset mint.
+     *  The minted BitAssets are sent to this wallet, together with the
+     *  BitAssets in a UTXO that is spent to carry the mint. */
+    pub fn mint_bitasset(
+        &self,
+        tx: &mut Transaction,
+        bitasset: BitAssetId,
+        mint_amount: u64,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        // The minted BitAssets must be carried by a spent BitAsset UTXO
+        let (carry_value, carry_utxos) =
+            self.select_bitasset_utxos(bitasset, 1)?;
+        tx.inputs.extend(carry_utxos.keys());
+        let () = self.spend_bitasset_control_last(tx, bitasset)?;
+        let mint_output = Output::new(
+            self.get_new_address()?,
+            OutputContent::BitAsset(carry_value + mint_amount),
+        );
+        tx.outputs.push(mint_output);
+        tx.data = Some(TxData::BitAssetMint(mint_amount));
+        Ok(())
+    }
+
+    /** Given a regular transaction, send a BitAsset control coin and
+     *  BitAsset units to separate addresses.
+     *  Any remaining units in the spent BitAsset UTXOs are sent to this
+     *  wallet. */
+    pub fn split_bitasset_control(
+        &self,
+        tx: &mut Transaction,
+        bitasset: BitAssetId,
+        control_dest: Address,
+        units_dest: Address,
+        units_amount: u64,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        let (control_outpoint, _) = self.select_bitasset_control(bitasset)?;
+        let (input_units, units_utxos) =
+            self.select_bitasset_utxos(bitasset, units_amount)?;
+        let units_change = input_units - units_amount;
+        let units_change_output = if units_change != 0 {
+            Some(Output::new(
+                self.get_new_address()?,
+                OutputContent::BitAsset(units_change),
+            ))
+        } else {
+            None
+        };
+        tx.inputs.extend(units_utxos.keys());
+        tx.inputs.push(control_outpoint);
+        tx.outputs.push(Output::new(
+            units_dest,
+            OutputContent::BitAsset(units_amount),
+        ));
+        tx.outputs.extend(units_change_output);
+        tx.outputs
+            .push(Output::new(control_dest, OutputContent::BitAssetControl));
+        Ok(())
+    }
+
+    /** Given a regular transaction, send a BitAsset control coin to the
+     *  specified address, transferring authority to mint and update the
+     *  BitAsset. */
+    pub fn transfer_bitasset_control(
+        &self,
+        tx: &mut Transaction,
+        bitasset: BitAssetId,
+        dest: Address,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        let (control_outpoint, _) = self.select_bitasset_control(bitasset)?;
+        tx.inputs.push(control_outpoint);
+        tx.outputs
+            .push(Output::new(dest, OutputContent::BitAssetControl));
         Ok(())
     }
 
@@ -442,7 +1035,10 @@ impl Wallet {
         let mut bitcoin_utxos = Vec::<(_, Output)>::new();
         for item in self.utxos.iter(&txn)? {
             let (outpoint, output) = item?;
-            if output.is_bitcoin() {
+            if output.is_bitcoin()
+                && !self.reserved_utxos.contains_key(&txn, &outpoint)?
+                && !self.immature_deposits.contains_key(&txn, &outpoint)?
+            {
                 bitcoin_utxos.push((outpoint, output.into()));
             }
         }
@@ -451,7 +1047,9 @@ impl Wallet {
         let mut unconfirmed_bitcoin_utxos = Vec::new();
         for item in self.unconfirmed_utxos.iter(&txn)? {
             let (outpoint, output) = item?;
-            if output.is_bitcoin() {
+            if output.is_bitcoin()
+                && !self.reserved_utxos.contains_key(&txn, &outpoint)?
+            {
                 unconfirmed_bitcoin_utxos.push((outpoint, output));
             }
         }
@@ -491,6 +1089,7 @@ impl Wallet {
             let (outpoint, output) = item?;
             if let Some(output_bitasset) = output.bitasset()
                 && bitasset == *output_bitasset
+                && !self.reserved_utxos.contains_key(&txn, &outpoint)?
             {
                 bitasset_utxos.push((outpoint, output));
             }
@@ -529,8 +1128,9 @@ impl Wallet {
         let mut bitasset_utxo = None;
         for item in self.utxos.iter(&txn)? {
             let (outpoint, output) = item?;
-            if let Some(output_bitasset) = output.bitasset()
-                && bitasset == *output_bitasset
+            if output.is_bitasset_control()
+                && output.get_bitasset() == Some(bitasset)
+                && !self.reserved_utxos.contains_key(&txn, &outpoint)?
             {
                 bitasset_utxo = Some((outpoint, output.into()));
                 break;
@@ -575,6 +1175,7 @@ impl Wallet {
                 output.lp_token_amount()
                 && pool_asset0 == asset0
                 && pool_asset1 == asset1
+                && !self.reserved_utxos.contains_key(&txn, &outpoint)?
             {
                 amm_lp_token_utxos.push((outpoint, output));
             }
@@ -618,6 +1219,25 @@ impl Wallet {
         receipt_utxo.ok_or(Error::NotEnoughFunds)
     }
 
+    // Select order receipt utxo for the specified order
+    pub fn select_order_receipt(
+        &self,
+        order_id: OrderId,
+    ) -> Result<(OutPoint, FilledOutput), Error> {
+        let txn = self.env.read_txn()?;
+        let mut receipt_utxo = None;
+        for item in self.utxos.iter(&txn)? {
+            let (outpoint, output) = item?;
+            if let Some(output_order_id) = output.order_receipt()
+                && order_id == output_order_id
+            {
+                receipt_utxo = Some((outpoint, output));
+                break;
+            }
+        }
+        receipt_utxo.ok_or(Error::NotEnoughFunds)
+    }
+
     /// Given a regular transaction, add an AMM mint.
     pub fn amm_mint(
         &self,
@@ -768,7 +1388,10 @@ impl Wallet {
         Ok(())
     }
 
-    // Given a regular transaction, add an AMM swap.
+    /** Given a regular transaction, add an AMM swap.
+     *  The protocol fee, if nonzero, is paid in the spend asset
+     *  to the protocol fee address. */
+    #[allow(clippy::too_many_arguments)]
     pub fn amm_swap(
         &self,
         tx: &mut Transaction,
@@ -776,13 +1399,15 @@ impl Wallet {
         asset_receive: AssetId,
         amount_spend: u64,
         amount_receive: u64,
+        protocol_fee: u64,
+        protocol_fee_address: Address,
     ) -> Result<(), Error> {
         assert!(tx.is_regular(), "this function only accepts a regular tx");
         // Address for receiving `asset_receive`
         let receive_addr = self.get_new_address()?;
         let (input_amount_spend, spend_utxos) =
-            self.select_asset_utxos(asset_spend, amount_spend)?;
-        let amount_change = input_amount_spend - amount_spend;
+            self.select_asset_utxos(asset_spend, amount_spend + protocol_fee)?;
+        let amount_change = input_amount_spend - amount_spend - protocol_fee;
         let change_output = if amount_change != 0 {
             let address = self.get_new_address()?;
             let content = match asset_spend {
@@ -800,6 +1425,22 @@ impl Wallet {
         } else {
             None
         };
+        let protocol_fee_output = if protocol_fee != 0 {
+            let content = match asset_spend {
+                AssetId::Bitcoin => {
+                    OutputContent::Value(BitcoinOutputContent(protocol_fee))
+                }
+                AssetId::BitAsset(_) => OutputContent::BitAsset(protocol_fee),
+                AssetId::BitAssetControl(_) => OutputContent::BitAssetControl,
+            };
+            Some(Output {
+                address: protocol_fee_address,
+                memo: Vec::new(),
+                content,
+            })
+        } else {
+            None
+        };
         let receive_output = Output {
             address: receive_addr,
             memo: Vec::new(),
@@ -815,6 +1456,7 @@ impl Wallet {
         tx.inputs.extend(spend_utxos.keys());
         tx.inputs.rotate_right(spend_utxos.len());
         tx.outputs.extend(change_output);
+        tx.outputs.extend(protocol_fee_output);
         tx.outputs.push(receive_output);
         tx.data = Some(TxData::AmmSwap {
             amount_spent: amount_spend,
@@ -824,6 +1466,117 @@ impl Wallet {
         Ok(())
     }
 
+    /// Given a regular transaction, add a transfer of AMM LP tokens.
+    pub fn amm_lp_token_transfer(
+        &self,
+        tx: &mut Transaction,
+        asset0: AssetId,
+        asset1: AssetId,
+        dest: Address,
+        amount: u64,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        let (input_lp_token_amount, lp_token_utxos) =
+            self.select_amm_lp_tokens(asset0, asset1, amount)?;
+        let lp_token_change_amount = input_lp_token_amount - amount;
+        let lp_token_change_output = if lp_token_change_amount != 0 {
+            let address = self.get_new_address()?;
+            Some(Output {
+                address,
+                content: OutputContent::AmmLpToken(lp_token_change_amount),
+                memo: Vec::new(),
+            })
+        } else {
+            None
+        };
+        let lp_token_output = Output {
+            address: dest,
+            content: OutputContent::AmmLpToken(amount),
+            memo: Vec::new(),
+        };
+        tx.inputs.extend(lp_token_utxos.keys());
+        tx.outputs.push(lp_token_output);
+        tx.outputs.extend(lp_token_change_output);
+        Ok(())
+    }
+
+    /// Given a regular transaction, create an atomic swap lock tx
+    pub fn atomic_swap_lock(
+        &self,
+        tx: &mut Transaction,
+        atomic_swap_params: AtomicSwapParams,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        let (input_amount, utxos) = self.select_asset_utxos(
+            atomic_swap_params.asset,
+            atomic_swap_params.amount,
+        )?;
+        let change_amount = input_amount - atomic_swap_params.amount;
+        let change_output = if change_amount != 0 {
+            let address = self.get_new_address()?;
+            let content = match atomic_swap_params.asset {
+                AssetId::Bitcoin => {
+                    OutputContent::Value(BitcoinOutputContent(change_amount))
+                }
+                AssetId::BitAsset(_) => OutputContent::BitAsset(change_amount),
+                AssetId::BitAssetControl(_) => OutputContent::BitAssetControl,
+            };
+            Some(Output {
+                address,
+                memo: Vec::new(),
+                content,
+            })
+        } else {
+            None
+        };
+        tx.inputs.extend(utxos.keys());
+        tx.outputs.extend(change_output);
+        tx.data = Some(TxData::AtomicSwapLock(atomic_swap_params));
+        Ok(())
+    }
+
+    /** Given a regular transaction, create an atomic swap claim tx
+     *  (if the preimage is provided), or an atomic swap refund tx
+     *  (if the preimage is not provided).
+     *  The released asset is sent to `address`. */
+    pub fn atomic_swap_release(
+        &self,
+        tx: &mut Transaction,
+        swap_id: AtomicSwapId,
+        preimage: Option<Hash>,
+        asset: AssetId,
+        amount: u64,
+        address: Address,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        let release_output = Output {
+            address,
+            memo: Vec::new(),
+            content: match asset {
+                AssetId::Bitcoin => {
+                    OutputContent::Value(BitcoinOutputContent(amount))
+                }
+                AssetId::BitAsset(_) => OutputContent::BitAsset(amount),
+                AssetId::BitAssetControl(_) => OutputContent::BitAssetControl,
+            },
+        };
+        tx.outputs.push(release_output);
+        tx.data = Some(match preimage {
+            Some(preimage) => TxData::AtomicSwapClaim {
+                swap_id,
+                preimage,
+                asset,
+                amount,
+            },
+            None => TxData::AtomicSwapRefund {
+                swap_id,
+                asset,
+                amount,
+            },
+        });
+        Ok(())
+    }
+
     /// Given a regular transaction, create a dutch auction tx
     pub fn dutch_auction_create(
         &self,
@@ -991,12 +1744,175 @@ impl Wallet {
         Ok(())
     }
 
+    /// Given a regular transaction, create an order placement tx
+    pub fn order_place(
+        &self,
+        tx: &mut Transaction,
+        order_params: OrderParams,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        let (input_sell_amount, sell_utxos) = self.select_asset_utxos(
+            order_params.sell_asset,
+            order_params.sell_amount,
+        )?;
+        let change_amount = input_sell_amount - order_params.sell_amount;
+        let change_output = if change_amount != 0 {
+            let address = self.get_new_address()?;
+            let content = match order_params.sell_asset {
+                AssetId::Bitcoin => {
+                    OutputContent::Value(BitcoinOutputContent(change_amount))
+                }
+                AssetId::BitAsset(_) => OutputContent::BitAsset(change_amount),
+                AssetId::BitAssetControl(_) => OutputContent::BitAssetControl,
+            };
+            Some(Output {
+                address,
+                memo: Vec::new(),
+                content,
+            })
+        } else {
+            None
+        };
+        let order_receipt = Output {
+            address: self.get_new_address()?,
+            memo: Vec::new(),
+            content: OutputContent::OrderReceipt,
+        };
+        tx.inputs.extend(sell_utxos.keys());
+        tx.outputs.extend(change_output);
+        tx.outputs.push(order_receipt);
+        tx.data = Some(TxData::OrderPlace(order_params));
+        Ok(())
+    }
+
+    /// Given a regular transaction, create an order fill tx
+    pub fn order_fill(
+        &self,
+        tx: &mut Transaction,
+        order_id: OrderId,
+        sell_asset: AssetId,
+        buy_asset: AssetId,
+        quantity: u64,
+        cost: u64,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        let (input_buy_amount, buy_utxos) =
+            self.select_asset_utxos(buy_asset, cost)?;
+        let change_amount = input_buy_amount - cost;
+        let change_output = if change_amount != 0 {
+            let address = self.get_new_address()?;
+            let content = match buy_asset {
+                AssetId::Bitcoin => {
+                    OutputContent::Value(BitcoinOutputContent(change_amount))
+                }
+                AssetId::BitAsset(_) => OutputContent::BitAsset(change_amount),
+                AssetId::BitAssetControl(_) => OutputContent::BitAssetControl,
+            };
+            Some(Output {
+                address,
+                memo: Vec::new(),
+                content,
+            })
+        } else {
+            None
+        };
+        let sell_output = {
+            let content = match sell_asset {
+                AssetId::Bitcoin => {
+                    OutputContent::Value(BitcoinOutputContent(quantity))
+                }
+                AssetId::BitAsset(_) => OutputContent::BitAsset(quantity),
+                AssetId::BitAssetControl(_) => OutputContent::BitAssetControl,
+            };
+            Output {
+                address: self.get_new_address()?,
+                memo: Vec::new(),
+                content,
+            }
+        };
+        tx.inputs.extend(buy_utxos.keys());
+        tx.outputs.push(sell_output);
+        tx.outputs.extend(change_output);
+        tx.data = Some(TxData::OrderFill {
+            order_id,
+            receive_asset: sell_asset,
+            quantity,
+            spend_asset: buy_asset,
+            spend_amount: cost,
+        });
+        Ok(())
+    }
+
+    /// Given a regular transaction, create an order cancel tx
+    pub fn order_cancel(
+        &self,
+        tx: &mut Transaction,
+        order_id: OrderId,
+        sell_asset: AssetId,
+        buy_asset: AssetId,
+        amount_sell: u64,
+        amount_buy: u64,
+    ) -> Result<(), Error> {
+        assert!(tx.is_regular(), "this function only accepts a regular tx");
+        let (order_receipt_input, _) = self.select_order_receipt(order_id)?;
+        let sell_output = if amount_sell != 0 {
+            let address = self.get_new_address()?;
+            let content = match sell_asset {
+                AssetId::Bitcoin => {
+                    OutputContent::Value(BitcoinOutputContent(amount_sell))
+                }
+                AssetId::BitAsset(_) => OutputContent::BitAsset(amount_sell),
+                AssetId::BitAssetControl(_) => OutputContent::BitAssetControl,
+            };
+            Some(Output {
+                address,
+                memo: Vec::new(),
+                content,
+            })
+        } else {
+            None
+        };
+        let buy_output = if amount_buy != 0 {
+            let address = self.get_new_address()?;
+            let content = match buy_asset {
+                AssetId::Bitcoin => {
+                    OutputContent::Value(BitcoinOutputContent(amount_buy))
+                }
+                AssetId::BitAsset(_) => OutputContent::BitAsset(amount_buy),
+                AssetId::BitAssetControl(_) => OutputContent::BitAssetControl,
+            };
+            Some(Output {
+                address,
+                memo: Vec::new(),
+                content,
+            })
+        } else {
+            None
+        };
+
+        /* The order receipt must occur before any other order receipts
+        in the inputs. */
+        tx.inputs.push(order_receipt_input);
+        tx.inputs.rotate_right(1);
+
+        tx.outputs.extend(sell_output);
+        tx.outputs.extend(buy_output);
+        tx.data = Some(TxData::OrderCancel {
+            asset_offered: sell_asset,
+            asset_receive: buy_asset,
+            amount_offered_remaining: amount_sell,
+            amount_received: amount_buy,
+        });
+        Ok(())
+    }
+
     pub fn spend_utxos(
         &self,
         spent: &[(OutPoint, InPoint)],
     ) -> Result<(), Error> {
         let mut txn = self.env.write_txn()?;
         for (outpoint, inpoint) in spent {
+            self.reserved_utxos.delete(&mut txn, outpoint)?;
             if let Some(output) = self.utxos.try_get(&txn, outpoint)? {
                 self.utxos.delete(&mut txn, outpoint)?;
                 let spent_output = SpentOutput {
@@ -1059,6 +1975,22 @@ impl Wallet {
         Ok(balance)
     }
 
+    /// Bitcoin balance that can be spent, excluding withdrawal outputs and
+    /// immature deposits
+    pub fn get_spendable_bitcoin_balance(&self) -> Result<u64, Error> {
+        let mut balance: u64 = 0;
+        let txn = self.env.read_txn()?;
+        for item in self.utxos.iter(&txn)? {
+            let (outpoint, utxo) = item?;
+            if utxo.is_bitcoin()
+                && !self.immature_deposits.contains_key(&txn, &outpoint)?
+            {
+                balance += utxo.get_bitcoin_value();
+            }
+        }
+        Ok(balance)
+    }
+
     /// gets the plaintext name associated with a bitasset reservation
     /// commitment, if it is known by the wallet.
     pub fn get_bitasset_reservation_plaintext(
@@ -1091,6 +2023,64 @@ impl Wallet {
         Ok(utxos)
     }
 
+    /// Get UTXOs that are reserved for manual coin control
+    pub fn get_reserved_utxos(&self) -> Result<HashSet<OutPoint>, Error> {
+        let txn = self.env.read_txn()?;
+        let mut reserved_utxos = HashSet::new();
+        for item in self.reserved_utxos.iter(&txn)? {
+            let (outpoint, ()) = item?;
+            reserved_utxos.insert(outpoint);
+        }
+        Ok(reserved_utxos)
+    }
+
+    /// Reserve a UTXO for manual coin control, so that it is never selected
+    /// automatically when creating transactions
+    pub fn reserve_utxo(&self, outpoint: &OutPoint) -> Result<(), Error> {
+        let mut txn = self.env.write_txn()?;
+        self.reserved_utxos.put(&mut txn, outpoint, &())?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Release a reserved UTXO, so that it may be selected automatically
+    /// when creating transactions
+    pub fn unreserve_utxo(&self, outpoint: &OutPoint) -> Result<(), Error> {
+        let mut txn = self.env.write_txn()?;
+        self.reserved_utxos.delete(&mut txn, outpoint)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Get deposit UTXOs that do not yet have enough mainchain
+    /// confirmations to be spent, with the number of confirmations of each
+    pub fn get_immature_deposits(
+        &self,
+    ) -> Result<HashMap<OutPoint, u32>, Error> {
+        let txn = self.env.read_txn()?;
+        let mut immature_deposits = HashMap::new();
+        for item in self.immature_deposits.iter(&txn)? {
+            let (outpoint, confirmations) = item?;
+            immature_deposits.insert(outpoint, confirmations);
+        }
+        Ok(immature_deposits)
+    }
+
+    /// Replace the set of immature deposits
+    pub fn put_immature_deposits(
+        &self,
+        immature_deposits: &HashMap<OutPoint, u32>,
+    ) -> Result<(), Error> {
+        let mut txn = self.env.write_txn()?;
+        self.immature_deposits.clear(&mut txn)?;
+        for (outpoint, confirmations) in immature_deposits {
+            self.immature_deposits
+                .put(&mut txn, outpoint, confirmations)?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
     pub fn get_unconfirmed_utxos(
         &self,
     ) -> Result<HashMap<OutPoint, Output>, Error> {
@@ -1209,6 +2199,9 @@ impl Watchable<()> for Wallet {
         let Self {
             env: _,
             seed,
+            mnemonic,
+            encrypted_seed,
+            unlocked_seed: _,
             address_to_index,
             index_to_address,
             utxos,
@@ -1217,9 +2210,13 @@ impl Watchable<()> for Wallet {
             spent_unconfirmed_utxos,
             bitasset_reservations,
             known_bitassets,
+            reserved_utxos,
+            immature_deposits,
         } = self;
         let watchables = [
             seed.watch(),
+            mnemonic.watch(),
+            encrypted_seed.watch(),
             address_to_index.watch(),
             index_to_address.watch(),
             utxos.watch(),
@@ -1228,6 +2225,8 @@ impl Watchable<()> for Wallet {
             spent_unconfirmed_utxos.watch(),
             bitasset_reservations.watch(),
             known_bitassets.watch(),
+            reserved_utxos.watch(),
+            immature_deposits.watch(),
         ];
         let streams = StreamMap::from_iter(
             watchables.into_iter().map(WatchStream::new).enumerate(),
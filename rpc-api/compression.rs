@@ -0,0 +1,294 @@
+//! Compression for RPC responses, negotiated via `Accept-Encoding`
+
+use std::{
+    io::{Read as _, Write as _},
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use hyper::{
+    header::{
+        HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING,
+        CONTENT_LENGTH, VARY,
+    },
+    Body, Request, Response, StatusCode,
+};
+use tower::{Layer, Service};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Supported content encodings, in order of preference
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    const ALL: [Self; 2] = [Self::Gzip, Self::Deflate];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    /// Value of the `Accept-Encoding` header sent by clients that support
+    /// all encodings
+    pub fn accept_header_value() -> HeaderValue {
+        HeaderValue::from_static("gzip, deflate")
+    }
+
+    /// Choose the preferred encoding accepted by the client, if any
+    pub fn negotiate(headers: &HeaderMap) -> Option<Self> {
+        let accepted: Vec<&str> = headers
+            .get_all(ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|coding| {
+                let mut params = coding.split(';').map(str::trim);
+                let coding = params.next()?;
+                // Codings with `q=0` are explicitly not accepted
+                let rejected = params.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .is_some_and(|q| q == 0.0)
+                });
+                (!rejected).then_some(coding)
+            })
+            .collect();
+        Self::ALL.into_iter().find(|encoding| {
+            accepted
+                .iter()
+                .any(|coding| coding.eq_ignore_ascii_case(encoding.as_str()))
+        })
+    }
+
+    /// Encoding specified by the `Content-Encoding` header, if any.
+    /// Returns `Err` if the encoding is not supported.
+    pub fn from_content_encoding(
+        headers: &HeaderMap,
+    ) -> Result<Option<Self>, BoxError> {
+        let Some(value) = headers.get(CONTENT_ENCODING) else {
+            return Ok(None);
+        };
+        let value = value.to_str()?.trim();
+        if value.eq_ignore_ascii_case("identity") {
+            return Ok(None);
+        }
+        Self::ALL
+            .into_iter()
+            .find(|encoding| value.eq_ignore_ascii_case(encoding.as_str()))
+            .map(Some)
+            .ok_or_else(|| {
+                format!("unsupported content encoding `{value}`").into()
+            })
+    }
+
+    pub fn encode(self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    Vec::new(),
+                    flate2::Compression::default(),
+                );
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            Self::Deflate => {
+                let mut encoder = flate2::write::ZlibEncoder::new(
+                    Vec::new(),
+                    flate2::Compression::default(),
+                );
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// Decode bytes, failing if the decoded length exceeds `max_len`
+    pub fn decode(
+        self,
+        bytes: &[u8],
+        max_len: usize,
+    ) -> std::io::Result<Vec<u8>> {
+        let decoder: Box<dyn std::io::Read> = match self {
+            Self::Gzip => Box::new(flate2::read::GzDecoder::new(bytes)),
+            Self::Deflate => Box::new(flate2::read::ZlibDecoder::new(bytes)),
+        };
+        let mut decoded = Vec::new();
+        // Read at most one byte more than the limit, to detect bodies that
+        // exceed it without decoding them in full
+        decoder.take(max_len as u64 + 1).read_to_end(&mut decoded)?;
+        if decoded.len() > max_len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("decoded body exceeds the limit of {max_len} bytes"),
+            ));
+        }
+        Ok(decoded)
+    }
+
+    /// Map a body, applying `f` to the complete body once it is received
+    fn map_body<F>(body: Body, f: F) -> Body
+    where
+        F: FnOnce(&[u8]) -> std::io::Result<Vec<u8>> + Send + 'static,
+    {
+        Body::wrap_stream(futures::stream::once(async move {
+            let bytes = hyper::body::to_bytes(body).await?;
+            f(&bytes).map_err(BoxError::from)
+        }))
+    }
+}
+
+/// Server middleware that compresses response bodies, if the client
+/// accepts a supported encoding
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompressionLayer;
+
+impl<S> Layer<S> for CompressionLayer {
+    type Service = Compression<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Compression { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Compression<S> {
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for Compression<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let encoding = Encoding::negotiate(req.headers());
+        let res_fut = self.inner.call(req);
+        Box::pin(async move {
+            let mut res = res_fut.await?;
+            // Websocket upgrades and already-encoded responses are left
+            // unchanged
+            if res.status() == StatusCode::SWITCHING_PROTOCOLS
+                || res.headers().contains_key(CONTENT_ENCODING)
+            {
+                return Ok(res);
+            }
+            res.headers_mut()
+                .append(VARY, HeaderValue::from_static("accept-encoding"));
+            let Some(encoding) = encoding else {
+                return Ok(res);
+            };
+            let (mut parts, body) = res.into_parts();
+            parts.headers.remove(CONTENT_LENGTH);
+            parts.headers.insert(
+                CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.as_str()),
+            );
+            let body =
+                Encoding::map_body(body, move |bytes| encoding.encode(bytes));
+            Ok(Response::from_parts(parts, body))
+        })
+    }
+}
+
+/// Client middleware that requests compressed responses, and decompresses
+/// response bodies.
+/// Decompressed bodies larger than the maximum response size are rejected.
+#[derive(Clone, Copy, Debug)]
+pub struct DecompressionLayer {
+    max_response_size: usize,
+}
+
+impl DecompressionLayer {
+    /// Same as the default maximum response size for jsonrpsee clients
+    pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 10 * 1024 * 1024;
+
+    pub fn new(max_response_size: usize) -> Self {
+        Self { max_response_size }
+    }
+}
+
+impl Default for DecompressionLayer {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_RESPONSE_SIZE)
+    }
+}
+
+impl<S> Layer<S> for DecompressionLayer {
+    type Service = Decompression<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Decompression {
+            inner,
+            max_response_size: self.max_response_size,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Decompression<S> {
+    inner: S,
+    max_response_size: usize,
+}
+
+impl<S> Service<Request<Body>> for Decompression<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        req.headers_mut()
+            .insert(ACCEPT_ENCODING, Encoding::accept_header_value());
+        let res_fut = self.inner.call(req);
+        let max_response_size = self.max_response_size;
+        Box::pin(async move {
+            let res = res_fut.await?;
+            let (mut parts, body) = res.into_parts();
+            let body = match Encoding::from_content_encoding(&parts.headers) {
+                Ok(None) => body,
+                Ok(Some(encoding)) => {
+                    parts.headers.remove(CONTENT_ENCODING);
+                    parts.headers.remove(CONTENT_LENGTH);
+                    Encoding::map_body(body, move |bytes| {
+                        encoding.decode(bytes, max_response_size)
+                    })
+                }
+                // Surface the error when the body is read
+                Err(err) => Body::wrap_stream(futures::stream::once(
+                    futures::future::err::<Vec<u8>, _>(err),
+                )),
+            };
+            Ok(Response::from_parts(parts, body))
+        })
+    }
+}
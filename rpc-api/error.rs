@@ -0,0 +1,182 @@
+//! Structured RPC errors.
+//!
+//! Errors returned by RPC methods use a stable numeric code from
+//! [`ErrorCode`], and include an [`ErrorData`] object as the error data, so
+//! that clients can distinguish errors without parsing error messages.
+
+use plain_bitassets::{
+    archive, mempool, node,
+    state::{self, TxValidationStage},
+    wallet,
+};
+use serde::{Deserialize, Serialize};
+
+/// Stable error codes returned by RPC methods.
+/// Codes MUST NOT be changed or re-used once assigned.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[repr(i32)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// An error that is not otherwise classified
+    Other = -1,
+    /// The wallet does not have a seed
+    NoWalletSeed = -2,
+    /// The wallet is encrypted and locked
+    WalletLocked = -3,
+    /// A tx was rejected by the mempool
+    TxRejected = -4,
+    /// Not enough funds to construct or validate a tx
+    InsufficientFunds = -5,
+    /// The BitAsset is already registered
+    BitAssetAlreadyRegistered = -6,
+    /// A UTXO is spent by another tx
+    UtxoDoubleSpent = -7,
+    /// A UTXO does not exist
+    UtxoNotFound = -8,
+    /// A requested item (block, tx, pool, auction, etc.) does not exist
+    NotFound = -9,
+    /// An AMM pool does not have enough liquidity
+    InsufficientLiquidity = -10,
+    /// An AMM swap would receive less than the specified minimum
+    SlippageExceeded = -11,
+    /// A tx has invalid or missing authorizations
+    InvalidAuthorization = -12,
+    /// A tx expired before it could be included in a block
+    TxExpired = -13,
+    /// A bundle exceeds the maximum weight
+    BundleTooHeavy = -14,
+    /// The wallet passphrase is incorrect
+    IncorrectPassphrase = -15,
+    /// The wallet seed has already been set
+    WalletSeedAlreadyExists = -16,
+    /// A withdrawal has already been collected into a withdrawal bundle
+    WithdrawalInBundle = -17,
+    /// An AMM swap exceeds the maximum price impact
+    PriceImpactExceeded = -18,
+}
+
+impl ErrorCode {
+    pub const fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+impl From<&state::Error> for ErrorCode {
+    fn from(err: &state::Error) -> Self {
+        use state::Error;
+        match err {
+            Error::AmmPriceImpactExceeded { .. } => Self::PriceImpactExceeded,
+            Error::AmmSwapBelowMinReceive { .. } => Self::SlippageExceeded,
+            Error::AuthorizationError
+            | Error::SignatureError(_)
+            | Error::WrongPubKeyForAddress { .. } => Self::InvalidAuthorization,
+            Error::BitAssetAlreadyRegistered { .. } => {
+                Self::BitAssetAlreadyRegistered
+            }
+            Error::BundleTooHeavy { .. } => Self::BundleTooHeavy,
+            Error::InsufficientLiquidity => Self::InsufficientLiquidity,
+            Error::MissingAmmPoolState { .. }
+            | Error::MissingAtomicSwap(_)
+            | Error::MissingBitAsset { .. }
+            | Error::MissingBitAssetData { .. }
+            | Error::MissingDutchAuction(_)
+            | Error::MissingOrder(_)
+            | Error::MissingReservation { .. } => Self::NotFound,
+            Error::NoUtxo { .. } => Self::UtxoNotFound,
+            Error::NotEnoughValueIn => Self::InsufficientFunds,
+            Error::TxValidation { .. } => Self::TxRejected,
+            Error::UtxoDoubleSpent => Self::UtxoDoubleSpent,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl From<&mempool::Error> for ErrorCode {
+    fn from(err: &mempool::Error) -> Self {
+        match err {
+            mempool::Error::FeeRateTooLow { .. }
+            | mempool::Error::InsufficientReplacementFee { .. }
+            | mempool::Error::InsufficientReplacementFeeRate { .. } => {
+                Self::TxRejected
+            }
+            mempool::Error::MissingTransaction(_) => Self::NotFound,
+            mempool::Error::UtxoDoubleSpent => Self::UtxoDoubleSpent,
+            mempool::Error::Bincode(_) | mempool::Error::Heed(_) => Self::Other,
+        }
+    }
+}
+
+impl From<&node::Error> for ErrorCode {
+    fn from(err: &node::Error) -> Self {
+        match err {
+            node::Error::Archive(
+                archive::Error::NoBlock(_)
+                | archive::Error::NoBlockHash(_)
+                | archive::Error::NoBody(_)
+                | archive::Error::NoHeader(_)
+                | archive::Error::NoTx(_),
+            ) => Self::NotFound,
+            node::Error::MemPool(err) => err.into(),
+            node::Error::State(err) => err.into(),
+            node::Error::TxExpired { .. } => Self::TxExpired,
+            node::Error::WithdrawalInBundle { .. } => Self::WithdrawalInBundle,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl From<&wallet::Error> for ErrorCode {
+    fn from(err: &wallet::Error) -> Self {
+        use wallet::Error;
+        match err {
+            Error::IncorrectPassphrase => Self::IncorrectPassphrase,
+            Error::NoBitassetReservation { .. } => Self::NotFound,
+            Error::NoUtxo => Self::UtxoNotFound,
+            Error::NoWalletSeed => Self::NoWalletSeed,
+            Error::NotEnoughFunds => Self::InsufficientFunds,
+            Error::SeedAlreadyExists => Self::WalletSeedAlreadyExists,
+            Error::WalletLocked => Self::WalletLocked,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Error data returned with RPC errors
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ErrorData {
+    /// Machine-readable name of the error code
+    pub kind: ErrorCode,
+    /// For [`ErrorCode::TxRejected`], the validation stage at which the tx
+    /// was rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage: Option<TxValidationStage>,
+    /// For [`ErrorCode::TxRejected`], the reason that the tx was rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<ErrorCode>,
+}
+
+impl ErrorData {
+    pub fn new(kind: ErrorCode) -> Self {
+        Self {
+            kind,
+            stage: None,
+            reason: None,
+        }
+    }
+}
+
+impl From<&node::Error> for ErrorData {
+    fn from(err: &node::Error) -> Self {
+        match err {
+            node::Error::State(state::Error::TxValidation {
+                stage,
+                source,
+            }) => Self {
+                kind: ErrorCode::TxRejected,
+                stage: Some(*stage),
+                reason: Some(source.as_ref().into()),
+            },
+            err => Self::new(err.into()),
+        }
+    }
+}
@@ -1,20 +1,33 @@
 //! RPC API
 
-use std::{marker::PhantomData, net::SocketAddr};
+pub mod compression;
+pub mod error;
+
+use std::{collections::BTreeMap, marker::PhantomData, net::SocketAddr};
 
 use bip300301::bitcoin;
 use fraction::Fraction;
-use jsonrpsee::{core::RpcResult, proc_macros::rpc};
+use jsonrpsee::{
+    core::{RpcResult, SubscriptionResult},
+    proc_macros::rpc,
+};
 use l2l_openapi::open_api;
 
 use plain_bitassets::{
-    state::{AmmPoolState, BitAssetSeqId, DutchAuctionState},
+    mempool::MemPoolInfo,
+    net::ConnectionDirection,
+    state::{
+        AmmPoolState, AtomicSwapState, BitAssetSeqId, DutchAuctionState,
+        OrderState, UtxoSort, WithdrawalEstimate,
+    },
     types::{
-        open_api_schemas, Address, AssetId, Authorization, BitAssetData,
-        BitAssetDataUpdates, BitAssetId, Block, BlockHash, Body,
-        DutchAuctionId, DutchAuctionParams, FilledOutputContent, Header,
-        MerkleRoot, OutPoint, Output, PointedOutput, Transaction, TxData, TxIn,
-        Txid,
+        open_api_schemas, Address, AssetId, AtomicSwapId, Authorization,
+        AuthorizedTransaction, BitAssetData, BitAssetDataUpdates, BitAssetId,
+        BitAssetKey, Block, BlockEvent, BlockHash, Body, DutchAuctionId,
+        DutchAuctionParams, FilledOutputContent, Header, InPoint, MerkleRoot,
+        OrderId, OutPoint, Output, PointedOutput, Reorg, ReservationPrep,
+        Transaction, TxData, TxIn, TxStatus, Txid, WithdrawalBundleInfo,
+        WithdrawalBundleRecord, WithdrawalStatus,
     },
 };
 use serde::{Deserialize, Serialize};
@@ -173,6 +186,21 @@ impl ToSchema<'static> for OpenApiSchema {
     }
 }
 
+struct OpenRpcSchema;
+
+impl PartialSchema for OpenRpcSchema {
+    fn schema() -> RefOr<Schema> {
+        let obj = utoipa::openapi::Object::new();
+        RefOr::T(Schema::Object(obj))
+    }
+}
+
+impl ToSchema<'static> for OpenRpcSchema {
+    fn schema() -> (&'static str, RefOr<Schema>) {
+        ("OpenRpcSchema", <Self as PartialSchema>::schema())
+    }
+}
+
 struct SocketAddrSchema;
 
 impl PartialSchema for SocketAddrSchema {
@@ -239,6 +267,175 @@ impl ToSchema<'static> for VerifyingKeySchema {
     }
 }
 
+/// A BitAsset to register
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct BitAssetRegistration {
+    pub plain_name: String,
+    pub initial_supply: u64,
+    pub bitasset_data: BitAssetData,
+}
+
+/// Estimated result of an AMM swap
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct AmmSwapEstimate {
+    /// Amount of the received asset
+    pub amount_receive: u64,
+    /// Relative difference between the current pool price and the
+    /// effective price of the swap
+    #[schema(value_type = FractionSchema)]
+    pub price_impact: Fraction,
+}
+
+/// Confirmed and unconfirmed subtotals of a wallet balance
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, ToSchema,
+)]
+pub struct Balance {
+    /// Amount held in confirmed UTXOs
+    pub confirmed: u64,
+    /// Amount held in UTXOs created by txs in the mempool
+    pub unconfirmed: u64,
+}
+
+/// LP token balance for an AMM pool
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct LpTokenBalance {
+    pub asset0: AssetId,
+    pub asset1: AssetId,
+    pub balance: Balance,
+}
+
+/// Balances of all assets held by the wallet
+#[derive(Clone, Debug, Default, Deserialize, Serialize, ToSchema)]
+pub struct AssetBalances {
+    /// Bitcoin (in sats), BitAsset, and BitAsset control coin balances
+    pub assets: BTreeMap<AssetId, Balance>,
+    /// LP token balances, ordered by pooled asset pair
+    pub lp_tokens: Vec<LpTokenBalance>,
+    /// Number of receipts held for each Dutch auction
+    pub dutch_auction_receipts: BTreeMap<DutchAuctionId, Balance>,
+}
+
+/// An output of a multi-asset transfer
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct TransferOutput {
+    pub address: Address,
+    pub asset: AssetId,
+    pub amount: u64,
+}
+
+pub use error::{ErrorCode, ErrorData};
+
+/// Error code returned by wallet-dependent RPC methods if the wallet does
+/// not have a seed
+pub const NO_WALLET_SEED_ERROR_CODE: i32 = ErrorCode::NoWalletSeed.code();
+
+/// Error code returned by wallet-dependent RPC methods if the wallet is
+/// encrypted and locked
+pub const WALLET_LOCKED_ERROR_CODE: i32 = ErrorCode::WalletLocked.code();
+
+/// Error code returned by RPC methods that submit a tx, if the tx is
+/// rejected by the mempool. The error data includes the validation stage at
+/// which the tx was rejected, and the reason for rejection.
+pub const TX_REJECTED_ERROR_CODE: i32 = ErrorCode::TxRejected.code();
+
+/// Outcome of a single diagnostic check
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Eq,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Serialize,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub status: DiagnosticStatus,
+    /// Human-readable details
+    pub message: String,
+}
+
+/// Report produced by the `run_diagnostics` RPC
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct Diagnostics {
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+impl Diagnostics {
+    /// The worst status of any check
+    pub fn status(&self) -> DiagnosticStatus {
+        self.checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(DiagnosticStatus::Pass)
+    }
+}
+
+/// Record of a mutating RPC call
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct RpcAuditLogEntry {
+    /// Unix timestamp (seconds) at which the call completed
+    pub timestamp: u64,
+    pub method: String,
+    /// Hex-encoded BLAKE3 digest of the JSON-encoded params.
+    /// Sensitive params (eg. mnemonics) are redacted before hashing.
+    pub params_digest: String,
+    /// JSON-encoded result (eg. the txid of the sent tx), if successful
+    pub result: Option<String>,
+    /// Error message, if unsuccessful
+    pub error: Option<String>,
+}
+
+/// A mainchain block whose deposits were applied to the sidechain
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct DepositBlock {
+    #[schema(value_type = BitcoinBlockHashSchema)]
+    pub main_block_hash: bitcoin::BlockHash,
+    /// Sidechain height at which the deposits were applied
+    pub sidechain_height: u32,
+}
+
+/// Information about the node's peer connections, returned by the
+/// `get_node_info` RPC
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct NodeInfo {
+    /// Number of connections accepted from peers
+    pub num_inbound_peers: usize,
+    /// Number of connections made to peers
+    pub num_outbound_peers: usize,
+    /// Max number of connections accepted from peers
+    pub max_inbound_peers: usize,
+    /// Max number of connections made to peers
+    pub max_outbound_peers: usize,
+    /// Currently connected peers
+    pub peers: Vec<Peer>,
+}
+
+/// A connected peer
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct Peer {
+    #[schema(value_type = SocketAddrSchema)]
+    pub address: SocketAddr,
+    /// Whether the connection was accepted from, or made to, the peer
+    pub direction: ConnectionDirection,
+    /// Score from 0 to 100, reduced by protocol violations.
+    /// Peers are disconnected and banned once their score reaches 0.
+    pub score: u32,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
 pub struct TxInfo {
     pub confirmations: Option<u32>,
@@ -251,12 +448,16 @@ pub struct TxInfo {
     open_api_schemas::UpdateIpv4Addr, open_api_schemas::UpdateIpv6Addr,
     open_api_schemas::UpdateEncryptionPubKey,
     open_api_schemas::UpdateVerifyingKey,
-    Address, AssetId, Authorization, BitAssetData, BitAssetDataUpdates,
+    Address, AssetId, AtomicSwapId, Authorization, Balance, BitAssetData,
+    BitAssetDataUpdates, BitAssetKey,
     BitcoinBlockHashSchema, BitcoinOutPointSchema,
-    BlockHash, Body, DutchAuctionId, DutchAuctionParams, EncryptionPubKeySchema,
-    HashSchema, Header, Ipv4AddrSchema, Ipv6AddrSchema, MerkleRoot, OutPoint,
-    Output, Transaction, TxData, Txid, TxIn,
-    VerifyingKeySchema
+    BlockHash, Body, ConnectionDirection, DutchAuctionId, DutchAuctionParams,
+    EncryptionPubKeySchema,
+    HashSchema, Header, InPoint, Ipv4AddrSchema, Ipv6AddrSchema,
+    LpTokenBalance, MemPoolInfo, MerkleRoot, OrderId, OutPoint, Output,
+    ReservationPrep, Transaction, TxData,
+    Txid, TxIn, TxStatus, UtxoSort, VerifyingKeySchema, WithdrawalBundleInfo,
+    WithdrawalBundleRecord, WithdrawalStatus
 ])]
 #[rpc(client, server)]
 pub trait Rpc {
@@ -279,15 +480,78 @@ pub trait Rpc {
         amount1: u64,
     ) -> RpcResult<Txid>;
 
-    /// Returns the amount of `asset_receive` to receive
+    /// List all AMM pools, with the assets in each pair in ascending order
+    #[open_api_method(output_schema(
+        PartialSchema = "Vec<Tuple3Schema<AssetId, AssetId, AmmPoolState>>"
+    ))]
+    #[method(name = "amm_pools")]
+    async fn amm_pools(
+        &self,
+    ) -> RpcResult<Vec<(AssetId, AssetId, AmmPoolState)>>;
+
+    /// Returns the amount of `asset_receive` to receive.
+    /// If `min_amount_receive` is set, the swap is not submitted if the
+    /// amount to receive is less than `min_amount_receive`.
+    /// If `expiry_height` is set, the swap tx is dropped from the mempool if
+    /// it is not included in a block at or below that height.
     #[method(name = "amm_swap")]
     async fn amm_swap(
         &self,
         asset_spend: AssetId,
         asset_receive: AssetId,
         amount_spend: u64,
+        min_amount_receive: Option<u64>,
+        expiry_height: Option<u32>,
+    ) -> RpcResult<u64>;
+
+    /// Estimate the amount of `asset_receive` that would be received by an
+    /// AMM swap against the current pool state, and the price impact of the
+    /// swap, without building or submitting a tx.
+    /// The AMM protocol fee is paid in addition to `amount_spend`.
+    #[method(name = "amm_swap_estimate")]
+    async fn amm_swap_estimate(
+        &self,
+        asset_spend: AssetId,
+        asset_receive: AssetId,
+        amount_spend: u64,
+    ) -> RpcResult<AmmSwapEstimate>;
+
+    /// Swap against an AMM pool, receiving exactly `amount_receive` of
+    /// `asset_receive`, or slightly more due to rounding.
+    /// Returns the amount of `asset_spend` spent, which is the minimum
+    /// amount required to receive `amount_receive`.
+    /// The AMM protocol fee is paid in addition to the amount spent.
+    #[method(name = "amm_swap_exact_output")]
+    async fn amm_swap_exact_output(
+        &self,
+        asset_spend: AssetId,
+        asset_receive: AssetId,
+        amount_receive: u64,
     ) -> RpcResult<u64>;
 
+    /// Get the balances of all assets held by the wallet, with confirmed and
+    /// unconfirmed subtotals
+    #[method(name = "asset_balances")]
+    async fn asset_balances(&self) -> RpcResult<AssetBalances>;
+
+    /// List all atomic swaps
+    #[open_api_method(output_schema(
+        ToSchema = "TupleSchema<AtomicSwapId, AtomicSwapState>"
+    ))]
+    #[method(name = "atomic_swaps")]
+    async fn atomic_swaps(
+        &self,
+    ) -> RpcResult<Vec<(AtomicSwapId, AtomicSwapState)>>;
+
+    /// Mine blocks automatically, for local development on regtest.
+    /// If `interval_secs` is set, a block is mined at that interval.
+    /// Otherwise, a block is mined whenever the mempool is non-empty.
+    /// The mainchain block that includes each BMM request is generated
+    /// immediately, as with `mine` when `instant` is set.
+    #[open_api_method(output_schema(ToSchema))]
+    #[method(name = "auto_mine")]
+    async fn auto_mine(&self, interval_secs: Option<u64>) -> RpcResult<()>;
+
     /// List all BitAssets
     #[open_api_method(output_schema(
         PartialSchema = "Vec<Tuple3Schema<BitAssetSeqId, BitAssetId, BitAssetData>>"
@@ -301,6 +565,49 @@ pub trait Rpc {
     #[method(name = "bitcoin_balance")]
     async fn bitcoin_balance(&self) -> RpcResult<u64>;
 
+    /// Build, sign, and send a tx that may transfer several assets, eg.
+    /// Bitcoin and BitAssets, to several recipients. Change for each asset
+    /// is sent to the wallet.
+    #[method(name = "build_transfer")]
+    async fn build_transfer(
+        &self,
+        outputs: Vec<TransferOutput>,
+        fee: u64,
+    ) -> RpcResult<Txid>;
+
+    /// Cancel an order, redeeming the unfilled remainder of the sell asset
+    /// and any proceeds in the buy asset
+    #[method(name = "cancel_order")]
+    async fn cancel_order(&self, order_id: OrderId) -> RpcResult<Txid>;
+
+    /// Cancel a withdrawal that has not yet been collected into a withdrawal
+    /// bundle, by spending the withdrawal output to a new wallet address
+    #[method(name = "cancel_withdrawal")]
+    async fn cancel_withdrawal(
+        &self,
+        outpoint: OutPoint,
+        fee_sats: u64,
+    ) -> RpcResult<Txid>;
+
+    /// Claim the asset locked in an atomic swap, by revealing the
+    /// hex-encoded preimage
+    #[method(name = "claim_atomic_swap")]
+    async fn claim_atomic_swap(
+        &self,
+        swap_id: AtomicSwapId,
+        preimage: String,
+    ) -> RpcResult<Txid>;
+
+    /// Register a BitAsset by revealing the nonce from a reservation prep,
+    /// once the reservation tx from `prepare_reservation` is confirmed
+    #[method(name = "complete_registration")]
+    async fn complete_registration(
+        &self,
+        prep: ReservationPrep,
+        initial_supply: u64,
+        bitasset_data: BitAssetData,
+    ) -> RpcResult<Txid>;
+
     /// Connect to a peer
     #[open_api_method(output_schema(ToSchema))]
     #[method(name = "connect_peer")]
@@ -310,12 +617,54 @@ pub trait Rpc {
         addr: SocketAddr,
     ) -> RpcResult<()>;
 
-    /// Returns the amount of the base asset to receive
+    /// Count all UTXOs.
+    /// This is much cheaper than counting the result of `list_utxos`.
+    #[open_api_method(output_schema(ToSchema))]
+    #[method(name = "count_utxos")]
+    async fn count_utxos(&self) -> RpcResult<u64>;
+
+    /// Count UTXOs containing the specified asset
+    #[open_api_method(output_schema(ToSchema))]
+    #[method(name = "count_utxos_by_asset")]
+    async fn count_utxos_by_asset(&self, asset_id: AssetId) -> RpcResult<u64>;
+
+    /// Lock an asset in an atomic swap, claimable by the recipient by
+    /// revealing the preimage of the hex-encoded BLAKE3 hash lock,
+    /// or refundable from the timeout block height onwards
+    #[method(name = "create_atomic_swap")]
+    async fn create_atomic_swap(
+        &self,
+        asset: AssetId,
+        amount: u64,
+        recipient: Address,
+        hash_lock: String,
+        timeout: u32,
+    ) -> RpcResult<AtomicSwapId>;
+
+    /// Reserve and register a BitAsset with the specified initial supply,
+    /// in one step.
+    /// If the wallet already has a confirmed reservation for the BitAsset,
+    /// the BitAsset is registered immediately. Otherwise, a reservation tx
+    /// is sent, and the BitAsset is registered once the reservation is
+    /// confirmed.
+    /// Returns the txid of the registration tx.
+    #[method(name = "create_token")]
+    async fn create_token(
+        &self,
+        plain_name: String,
+        initial_supply: u64,
+        bitasset_data: BitAssetData,
+    ) -> RpcResult<Txid>;
+
+    /// Returns the amount of the base asset to receive.
+    /// If `expiry_height` is set, the bid tx is dropped from the mempool if
+    /// it is not included in a block at or below that height.
     #[method(name = "dutch_auction_bid")]
     async fn dutch_auction_bid(
         &self,
         dutch_auction_id: DutchAuctionId,
         bid_size: u64,
+        expiry_height: Option<u32>,
     ) -> RpcResult<u64>;
 
     /// Create a dutch auction
@@ -343,6 +692,56 @@ pub trait Rpc {
         &self,
     ) -> RpcResult<Vec<(DutchAuctionId, DutchAuctionState)>>;
 
+    /// Encrypt the wallet seed with a passphrase. Once encrypted, the wallet
+    /// must be unlocked with `unlock_wallet` in order to sign txs or
+    /// generate addresses.
+    #[open_api_method(output_schema(ToSchema))]
+    #[method(name = "encrypt_wallet")]
+    async fn encrypt_wallet(&self, passphrase: String) -> RpcResult<()>;
+
+    /// Estimate the impermanent loss of an LP position in the specified
+    /// pool that was entered at the specified reserves, relative to holding
+    /// the deposited assets
+    #[open_api_method(output_schema(ToSchema = "FractionSchema"))]
+    #[method(name = "estimate_impermanent_loss")]
+    async fn estimate_impermanent_loss(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        entry_reserve0: u64,
+        entry_reserve1: u64,
+    ) -> RpcResult<Fraction>;
+
+    /// Estimate the effect of a withdrawal on the next withdrawal bundle,
+    /// given the pending withdrawals
+    #[method(name = "estimate_withdrawal")]
+    async fn estimate_withdrawal(
+        &self,
+        #[open_api_method_arg(schema(PartialSchema = "BitcoinAddrSchema"))]
+        mainchain_address: bitcoin::Address<
+            bitcoin::address::NetworkUnchecked,
+        >,
+        amount_sats: u64,
+        mainchain_fee_sats: u64,
+    ) -> RpcResult<WithdrawalEstimate>;
+
+    /// Fill an order, purchasing the specified amount of the sell asset
+    /// at the order price
+    #[method(name = "fill_order")]
+    async fn fill_order(
+        &self,
+        order_id: OrderId,
+        amount: u64,
+    ) -> RpcResult<Txid>;
+
+    /// Find BitAssets for which the specified key is currently set as the
+    /// signing or encryption pubkey
+    #[method(name = "find_bitassets_by_key")]
+    async fn find_bitassets_by_key(
+        &self,
+        key: BitAssetKey,
+    ) -> RpcResult<Vec<BitAssetId>>;
+
     /// Format a deposit address
     #[method(name = "format_deposit_address")]
     async fn format_deposit_address(
@@ -350,10 +749,39 @@ pub trait Rpc {
         address: Address,
     ) -> RpcResult<String>;
 
-    /// Generate a mnemonic seed phrase
+    /// Generate a mnemonic seed phrase.
+    /// The generated mnemonic may optionally be protected with a BIP39
+    /// passphrase when it is set with `set_seed_from_mnemonic`. The same
+    /// passphrase is then required to restore the wallet from the mnemonic.
     #[method(name = "generate_mnemonic")]
     async fn generate_mnemonic(&self) -> RpcResult<String>;
 
+    /// Get the txids of txs that spent from or paid to the specified
+    /// address, confirmed in blocks at or above `from_height` (default 0),
+    /// in the order that they were confirmed.
+    /// Requires the address activity index to have been enabled before the
+    /// block at `from_height` was connected.
+    #[method(name = "get_address_history")]
+    async fn get_address_history(
+        &self,
+        address: Address,
+        from_height: Option<u32>,
+    ) -> RpcResult<Vec<Txid>>;
+
+    /// Get the state of the specified AMM pool at the end of each block in
+    /// the specified height range in which the pool state changed
+    #[open_api_method(output_schema(
+        PartialSchema = "Vec<TupleSchema<u32, AmmPoolState>>"
+    ))]
+    #[method(name = "get_amm_pool_history")]
+    async fn get_amm_pool_history(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        from_height: u32,
+        to_height: u32,
+    ) -> RpcResult<Vec<(u32, AmmPoolState)>>;
+
     /// Get the state of the specified AMM pool
     #[open_api_method(output_schema(ToSchema))]
     #[method(name = "get_amm_pool_state")]
@@ -363,6 +791,17 @@ pub trait Rpc {
         asset1: AssetId,
     ) -> RpcResult<AmmPoolState>;
 
+    /// Get the state of the specified AMM pool at the end of the block at
+    /// the specified height.
+    /// Returns `None` if the pool did not exist at that height.
+    #[method(name = "get_amm_pool_state_at_height")]
+    async fn get_amm_pool_state_at_height(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        height: u32,
+    ) -> RpcResult<Option<AmmPoolState>>;
+
     /// Get the current price for the specified pair
     #[open_api_method(output_schema(PartialSchema = "Option<FractionSchema>"))]
     #[method(name = "get_amm_price")]
@@ -372,15 +811,151 @@ pub trait Rpc {
         quote: AssetId,
     ) -> RpcResult<Option<Fraction>>;
 
+    /// Get the relative difference between the current price for the
+    /// specified pair, and the effective price of a swap spending
+    /// `amount_spend` of `base` to receive `quote`.
+    /// Returns `null` if the pool does not exist or is empty.
+    #[open_api_method(output_schema(PartialSchema = "Option<FractionSchema>"))]
+    #[method(name = "get_amm_price_impact")]
+    async fn get_amm_price_impact(
+        &self,
+        base: AssetId,
+        quote: AssetId,
+        amount_spend: u64,
+    ) -> RpcResult<Option<Fraction>>;
+
+    /// Get the Bitcoin balance of an address in sats at the end of the block
+    /// at the specified height, excluding withdrawal outputs.
+    /// Heights more than 10,000 blocks below the tip are rejected.
+    #[method(name = "get_balance_at_height")]
+    async fn get_balance_at_height(
+        &self,
+        address: Address,
+        height: u32,
+    ) -> RpcResult<u64>;
+
+    /// Get the market cap of a BitAsset in sats, implied by the price in the
+    /// BitAsset-Bitcoin AMM pool.
+    /// Returns `null` if the BitAsset does not exist, or if the pool does not
+    /// exist or is empty.
+    #[method(name = "get_bitasset_market_cap")]
+    async fn get_bitasset_market_cap(
+        &self,
+        bitasset_id: BitAssetId,
+    ) -> RpcResult<Option<u64>>;
+
+    /// Get the txids of confirmed txs that spent or created BitAsset outputs
+    /// or control coins for the specified BitAsset, in the order that they
+    /// were confirmed
+    #[method(name = "get_bitasset_txids")]
+    async fn get_bitasset_txids(
+        &self,
+        bitasset_id: BitAssetId,
+    ) -> RpcResult<Vec<Txid>>;
+
     /// Get block data
     #[open_api_method(output_schema(ToSchema))]
     #[method(name = "get_block")]
     async fn get_block(&self, block_hash: BlockHash) -> RpcResult<Block>;
 
+    /** Get the BMM critical hash that must be committed to on the mainchain
+     *  for a block with the specified header.
+     *  This is the block hash (`Header::hash`), encoded as a mainchain block
+     *  hash. BMM requests commit to the critical hash and to the last
+     *  4 bytes of the header's `prev_main_hash`, via the mainchain's
+     *  `createbmmcriticaldatatx` RPC. */
+    #[open_api_method(output_schema(ToSchema = "BitcoinBlockHashSchema"))]
+    #[method(name = "get_block_commitment")]
+    async fn get_block_commitment(
+        &self,
+        header: Header,
+    ) -> RpcResult<bitcoin::BlockHash>;
+
+    /// Get the outpoints spent by the specified block, in the order that
+    /// they are spent
+    #[method(name = "get_block_inputs")]
+    async fn get_block_inputs(
+        &self,
+        block_hash: BlockHash,
+    ) -> RpcResult<Vec<OutPoint>>;
+
+    /// Get the outputs created by the specified block, ordered by outpoint
+    #[method(name = "get_block_outputs")]
+    async fn get_block_outputs(
+        &self,
+        block_hash: BlockHash,
+    ) -> RpcResult<Vec<PointedOutput>>;
+
+    /// Get the local wall-clock time (unix seconds) at which the specified
+    /// block was first received by this node, if the block is known
+    #[method(name = "get_block_receive_time")]
+    async fn get_block_receive_time(
+        &self,
+        block_hash: BlockHash,
+    ) -> RpcResult<Option<u64>>;
+
+    /// Get the authorized txs in the specified block, in block order, each
+    /// paired with its txid
+    #[method(name = "get_block_transactions")]
+    async fn get_block_transactions(
+        &self,
+        block_hash: BlockHash,
+    ) -> RpcResult<Vec<(Txid, AuthorizedTransaction)>>;
+
+    /// Get mainchain deposit blocks, in the order that they were applied
+    #[method(name = "get_deposit_blocks")]
+    async fn get_deposit_blocks(&self) -> RpcResult<Vec<DepositBlock>>;
+
+    /// Get up to `count` headers in the current chain, in ascending height
+    /// order, starting with the header at `start_height`, eg. for light
+    /// clients to verify `prev_side_hash` linkage without fetching bodies.
+    /// At most 2000 headers are returned. Fewer headers are returned if the
+    /// tip is reached. The first block is at height 1.
+    #[method(name = "get_headers")]
+    async fn get_headers(
+        &self,
+        start_height: u32,
+        count: u32,
+    ) -> RpcResult<Vec<Header>>;
+
+    /// Get the identifier for the LP token of the AMM pool for the specified
+    /// pair. LP tokens are identified by the pair of pooled assets in
+    /// canonical order, as in the `AmmLpToken` filled output content, so the
+    /// result does not depend on the order of the specified assets.
+    #[open_api_method(output_schema(
+        ToSchema = "TupleSchema<AssetId, AssetId>"
+    ))]
+    #[method(name = "get_lp_token_id")]
+    async fn get_lp_token_id(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+    ) -> RpcResult<(AssetId, AssetId)>;
+
     /// Get a new address
     #[method(name = "get_new_address")]
     async fn get_new_address(&self) -> RpcResult<Address>;
 
+    /// Get the number of connected peers, and the configured peer limits
+    #[method(name = "get_node_info")]
+    async fn get_node_info(&self) -> RpcResult<NodeInfo>;
+
+    /// Get the most recent mutating RPC calls, most recent first
+    #[method(name = "get_rpc_audit_log")]
+    async fn get_rpc_audit_log(
+        &self,
+        limit: usize,
+    ) -> RpcResult<Vec<RpcAuditLogEntry>>;
+
+    /// Get the input that spent the specified outpoint, if it has been
+    /// spent in the current chain. The outpoint may have been spent by a
+    /// regular tx, or by a withdrawal bundle.
+    #[method(name = "get_spent_by")]
+    async fn get_spent_by(
+        &self,
+        outpoint: OutPoint,
+    ) -> RpcResult<Option<InPoint>>;
+
     /// Get transaction by txid
     #[method(name = "get_transaction")]
     async fn get_transaction(
@@ -395,21 +970,61 @@ pub trait Rpc {
         txid: Txid,
     ) -> RpcResult<Option<TxInfo>>;
 
+    /// Get the status of a transaction, relative to the current tip
+    #[method(name = "get_transaction_status")]
+    async fn get_transaction_status(&self, txid: Txid) -> RpcResult<TxStatus>;
+
+    /// Get several transactions from the archive or mempool, in the same
+    /// order as the requested txids.
+    /// At most 1000 txids may be requested in a single call.
+    #[method(name = "get_transactions_batch")]
+    async fn get_transactions_batch(
+        &self,
+        txids: Vec<Txid>,
+    ) -> RpcResult<Vec<Option<Transaction>>>;
+
     /// Get wallet addresses, sorted by base58 encoding
     #[method(name = "get_wallet_addresses")]
     async fn get_wallet_addresses(&self) -> RpcResult<Vec<Address>>;
 
-    /// Get wallet UTXOs
+    /// Get wallet UTXOs, ordered by outpoint
     #[method(name = "get_wallet_utxos")]
     async fn get_wallet_utxos(
         &self,
     ) -> RpcResult<Vec<PointedOutput<FilledOutputContent>>>;
 
+    /// Get the latest withdrawal bundle that a withdrawal output was spent
+    /// into, and the status of the bundle.
+    /// Returns null if the output was never collected into a bundle.
+    #[method(name = "get_withdrawal_bundle_for_utxo")]
+    async fn get_withdrawal_bundle_for_utxo(
+        &self,
+        outpoint: OutPoint,
+    ) -> RpcResult<Option<WithdrawalBundleInfo>>;
+
     /// Get the current block count
     #[method(name = "getblockcount")]
     async fn getblockcount(&self) -> RpcResult<u32>;
 
-    /// List all UTXOs
+    /// List the wallet's deposits that do not yet have enough mainchain
+    /// confirmations to be spent, with the number of confirmations
+    /// remaining until each can be spent, ordered by outpoint
+    #[open_api_method(output_schema(ToSchema = "TupleSchema<OutPoint, u32>"))]
+    #[method(name = "list_maturing_deposits")]
+    async fn list_maturing_deposits(&self) -> RpcResult<Vec<(OutPoint, u32)>>;
+
+    /// List all orders
+    #[open_api_method(output_schema(
+        ToSchema = "TupleSchema<OrderId, OrderState>"
+    ))]
+    #[method(name = "list_orders")]
+    async fn list_orders(&self) -> RpcResult<Vec<(OrderId, OrderState)>>;
+
+    /// List connected peers, ordered by address
+    #[method(name = "list_peers")]
+    async fn list_peers(&self) -> RpcResult<Vec<Peer>>;
+
+    /// List all UTXOs, ordered by outpoint
     #[open_api_method(output_schema(
         PartialSchema = "Vec<open_api_schemas::PointedFilledOutput>"
     ))]
@@ -418,50 +1033,207 @@ pub trait Rpc {
         &self,
     ) -> RpcResult<Vec<PointedOutput<FilledOutputContent>>>;
 
-    /// Attempt to mine a sidechain block
+    /// List all UTXOs holding the specified asset, ordered by outpoint.
+    /// Bitcoin UTXOs are listed for [`AssetId::Bitcoin`].
+    #[open_api_method(output_schema(
+        PartialSchema = "Vec<open_api_schemas::PointedFilledOutput>"
+    ))]
+    #[method(name = "list_utxos_by_asset")]
+    async fn list_utxos_by_asset(
+        &self,
+        asset_id: AssetId,
+    ) -> RpcResult<Vec<PointedOutput<FilledOutputContent>>>;
+
+    /// List a page of UTXOs, skipping the first `offset` UTXOs in the
+    /// specified sort order.
+    /// If `asset` is specified, only UTXOs containing that asset are listed.
+    #[open_api_method(output_schema(
+        PartialSchema = "Vec<open_api_schemas::PointedFilledOutput>"
+    ))]
+    #[method(name = "list_utxos_paginated")]
+    async fn list_utxos_paginated(
+        &self,
+        offset: usize,
+        limit: usize,
+        asset: Option<AssetId>,
+        sort: UtxoSort,
+    ) -> RpcResult<Vec<PointedOutput<FilledOutputContent>>>;
+
+    /// List all withdrawal bundles, oldest first, with their latest status
+    /// and the withdrawal outputs that they spend.
+    /// The pending bundle, if there is one, is listed last.
+    #[method(name = "list_withdrawal_bundles")]
+    async fn list_withdrawal_bundles(
+        &self,
+    ) -> RpcResult<Vec<WithdrawalBundleRecord>>;
+
+    /// Lock an encrypted wallet
+    #[open_api_method(output_schema(ToSchema))]
+    #[method(name = "lock_wallet")]
+    async fn lock_wallet(&self) -> RpcResult<()>;
+
+    /// Get the current size and capacity of the mempool
+    #[open_api_method(output_schema(ToSchema))]
+    #[method(name = "mempool_info")]
+    async fn mempool_info(&self) -> RpcResult<MemPoolInfo>;
+
+    /// Attempt to mine a sidechain block.
+    /// If `instant` is `true`, the mainchain block that includes the BMM
+    /// request is generated immediately, rather than waiting for it to be
+    /// mined externally. This is only supported on regtest.
     #[open_api_method(output_schema(ToSchema))]
     #[method(name = "mine")]
-    async fn mine(&self, fee: Option<u64>) -> RpcResult<()>;
+    async fn mine(
+        &self,
+        fee: Option<u64>,
+        instant: Option<bool>,
+    ) -> RpcResult<()>;
+
+    /// Mint units of a BitAsset. The wallet must hold the control coin
+    /// for the BitAsset, and at least one unit of the BitAsset.
+    #[method(name = "mint_bitasset")]
+    async fn mint_bitasset(
+        &self,
+        bitasset_id: BitAssetId,
+        amount: u64,
+        fee: u64,
+    ) -> RpcResult<Txid>;
 
     /*
     #[method(name = "my_unconfirmed_stxos")]
     async fn my_unconfirmed_stxos(&self) -> RpcResult<Vec<InPoint>>;
     */
 
-    /// List unconfirmed owned UTXOs
+    /// List unconfirmed owned UTXOs, ordered by outpoint
     #[method(name = "my_unconfirmed_utxos")]
     async fn my_unconfirmed_utxos(&self) -> RpcResult<Vec<PointedOutput>>;
 
-    /// List owned UTXOs
+    /// List owned UTXOs, ordered by outpoint
     #[method(name = "my_utxos")]
     async fn my_utxos(
         &self,
     ) -> RpcResult<Vec<PointedOutput<FilledOutputContent>>>;
 
-    /// Get OpenRPC schema
+    /// Get OpenAPI schema
     #[open_api_method(output_schema(PartialSchema = "OpenApiSchema"))]
     #[method(name = "openapi_schema")]
     async fn openapi_schema(&self) -> RpcResult<utoipa::openapi::OpenApi>;
 
+    /// Get OpenRPC schema
+    #[open_api_method(output_schema(PartialSchema = "OpenRpcSchema"))]
+    #[method(name = "openrpc_schema")]
+    async fn openrpc_schema(&self) -> RpcResult<serde_json::Value>;
+
+    /// Place a limit order, offering `amount` of `sell_asset` at `price`
+    /// units of `buy_asset` per unit of `sell_asset`
+    #[method(name = "place_order")]
+    async fn place_order(
+        &self,
+        sell_asset: AssetId,
+        buy_asset: AssetId,
+        amount: u64,
+        price: u64,
+    ) -> RpcResult<OrderId>;
+
+    /// Reserve a BitAsset, returning the commitment broadcast in the
+    /// reservation tx, and the nonce to retain. The nonce is revealed when
+    /// registering with `complete_registration`.
+    #[method(name = "prepare_reservation")]
+    async fn prepare_reservation(
+        &self,
+        plain_name: String,
+    ) -> RpcResult<ReservationPrep>;
+
+    /// Compute the BitAsset ID for the specified plaintext name, and whether
+    /// the name is available to register
+    #[open_api_method(output_schema(
+        ToSchema = "TupleSchema<BitAssetId, bool>"
+    ))]
+    #[method(name = "preview_registration")]
+    async fn preview_registration(
+        &self,
+        plain_name: String,
+    ) -> RpcResult<(BitAssetId, bool)>;
+
+    /// Refund the asset locked in an atomic swap, after the timeout
+    #[method(name = "refund_atomic_swap")]
+    async fn refund_atomic_swap(
+        &self,
+        swap_id: AtomicSwapId,
+    ) -> RpcResult<Txid>;
+
+    /// Register multiple BitAssets, eg. to seed a test environment.
+    /// For each BitAsset with a confirmed reservation in the wallet, a
+    /// registration tx is sent. For each BitAsset without one, a reservation
+    /// tx is sent instead, so that calling this again once the reservations
+    /// are confirmed will register the remaining BitAssets.
+    /// Returns the txids of the sent txs.
+    #[method(name = "register_bitassets_bulk")]
+    async fn register_bitassets_bulk(
+        &self,
+        registrations: Vec<BitAssetRegistration>,
+    ) -> RpcResult<Vec<Txid>>;
+
+    /// Rebuild derived indices (tx inclusions and BMM results) from the
+    /// archived blocks, without re-validating the UTXO set
+    #[open_api_method(output_schema(ToSchema))]
+    #[method(name = "reindex")]
+    async fn reindex(&self) -> RpcResult<()>;
+
     /// Reserve a BitAsset
     #[method(name = "reserve_bitasset")]
     async fn reserve_bitasset(&self, plain_name: String) -> RpcResult<Txid>;
 
-    /// Set the wallet seed from a mnemonic seed phrase
+    /// Reveal the mnemonic seed phrase for the wallet. The wallet must be
+    /// unlocked if it is encrypted. Rate-limited to one call per minute.
+    #[method(name = "reveal_mnemonic")]
+    async fn reveal_mnemonic(&self) -> RpcResult<String>;
+
+    /// Run health checks on the node, returning a report with the outcome
+    /// of each check
+    #[method(name = "run_diagnostics")]
+    async fn run_diagnostics(&self) -> RpcResult<Diagnostics>;
+
+    /// Set the wallet seed from a mnemonic seed phrase, and optional BIP39
+    /// passphrase
     #[open_api_method(output_schema(ToSchema))]
     #[method(name = "set_seed_from_mnemonic")]
-    async fn set_seed_from_mnemonic(&self, mnemonic: String) -> RpcResult<()>;
+    async fn set_seed_from_mnemonic(
+        &self,
+        mnemonic: String,
+        passphrase: Option<String>,
+    ) -> RpcResult<()>;
 
     /// Get total sidechain wealth
     #[open_api_method(output_schema(ToSchema = "BitcoinAmountSchema"))]
     #[method(name = "sidechain_wealth")]
     async fn sidechain_wealth(&self) -> RpcResult<bitcoin::Amount>;
 
+    /// Send the control coin for a BitAsset, and BitAsset units, to separate
+    /// addresses. Any remaining units in the spent UTXOs are sent to a new
+    /// wallet address.
+    #[method(name = "split_bitasset_control")]
+    async fn split_bitasset_control(
+        &self,
+        bitasset_id: BitAssetId,
+        control_dest: Address,
+        units_dest: Address,
+        units_amount: u64,
+        fee: u64,
+    ) -> RpcResult<Txid>;
+
     /// Stop the node
     #[method(name = "stop")]
     async fn stop(&self);
 
-    /// Transfer funds to the specified address
+    /// Stop mining blocks automatically, if `auto_mine` is enabled
+    #[open_api_method(output_schema(ToSchema))]
+    #[method(name = "stop_auto_mine")]
+    async fn stop_auto_mine(&self) -> RpcResult<()>;
+
+    /// Transfer funds to the specified address.
+    /// If `expiry_height` is set, the tx is dropped from the mempool if it
+    /// is not included in a block at or below that height.
     #[method(name = "transfer")]
     async fn transfer(
         &self,
@@ -469,8 +1241,52 @@ pub trait Rpc {
         value: u64,
         fee: u64,
         memo: Option<String>,
+        expiry_height: Option<u32>,
+    ) -> RpcResult<Txid>;
+
+    /// Transfer the control coin for a BitAsset to the specified address.
+    /// The holder of the control coin can mint and update the BitAsset.
+    #[method(name = "transfer_bitasset_control")]
+    async fn transfer_bitasset_control(
+        &self,
+        bitasset_id: BitAssetId,
+        dest: Address,
+        fee: u64,
     ) -> RpcResult<Txid>;
 
+    /// Transfer AMM LP tokens for the specified pair to the specified address
+    #[method(name = "transfer_lp_token")]
+    async fn transfer_lp_token(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+        dest: Address,
+        amount: u64,
+        fee: u64,
+    ) -> RpcResult<Txid>;
+
+    /// Unlock an encrypted wallet. The wallet is automatically locked again
+    /// after `timeout_secs` seconds (default 300, max 604800, ie. one week).
+    #[open_api_method(output_schema(ToSchema))]
+    #[method(name = "unlock_wallet")]
+    async fn unlock_wallet(
+        &self,
+        passphrase: String,
+        timeout_secs: Option<u64>,
+    ) -> RpcResult<()>;
+
+    /// Wait for the block count to exceed `current_height`, and return the
+    /// new block count. Returns immediately if the block count already
+    /// exceeds `current_height`.
+    /// If the block count does not increase within `timeout_secs` seconds
+    /// (default 30, max 60), the current block count is returned.
+    #[method(name = "wait_for_block")]
+    async fn wait_for_block(
+        &self,
+        current_height: u32,
+        timeout_secs: Option<u64>,
+    ) -> RpcResult<u32>;
+
     /// Initiate a withdrawal to the specified mainchain address
     #[method(name = "withdraw")]
     async fn withdraw(
@@ -484,3 +1300,142 @@ pub trait Rpc {
         mainchain_fee_sats: u64,
     ) -> RpcResult<Txid>;
 }
+
+/// Subscriptions require a persistent connection (eg. WebSocket), so they are
+/// declared separately from [`Rpc`], in order that [`RpcClient`] can be used
+/// with HTTP clients.
+#[rpc(client, server)]
+pub trait RpcSubscriptions {
+    /// Subscribe to the state of the AMM pool for the specified pair.
+    /// The new pool state is emitted after each block that changes it.
+    #[subscription(
+        name = "subscribe_amm_pool",
+        unsubscribe = "unsubscribe_amm_pool",
+        item = AmmPoolState
+    )]
+    async fn subscribe_amm_pool(
+        &self,
+        asset0: AssetId,
+        asset1: AssetId,
+    ) -> SubscriptionResult;
+
+    /// Subscribe to the price of the specified Dutch auction.
+    /// The current price is emitted after each new block, until the auction
+    /// ends, at which point the subscription is closed.
+    #[subscription(
+        name = "subscribe_auction",
+        unsubscribe = "unsubscribe_auction",
+        item = u64
+    )]
+    async fn subscribe_auction(
+        &self,
+        auction_id: DutchAuctionId,
+    ) -> SubscriptionResult;
+
+    /// Subscribe to blocks connected to and disconnected from the tip,
+    /// including block bodies, eg. for external indexers.
+    /// Events are emitted in the order that blocks are connected and
+    /// disconnected. During a reorg, each disconnected block is emitted,
+    /// starting from the old tip, followed by each connected block, ending
+    /// with the new tip.
+    /// If the subscriber falls too far behind, the subscription is closed
+    /// with an error, rather than skipping events.
+    #[subscription(
+        name = "subscribe_block_events",
+        unsubscribe = "unsubscribe_block_events",
+        item = BlockEvent
+    )]
+    async fn subscribe_block_events(&self) -> SubscriptionResult;
+
+    /// Subscribe to reorgs that disconnect blocks from the tip, eg. so that
+    /// wallets can re-scan only the affected blocks.
+    /// Blocks that extend the tip without disconnecting any blocks are not
+    /// emitted.
+    /// If the subscriber falls too far behind, the subscription is closed
+    /// with an error, rather than skipping reorgs.
+    #[subscription(
+        name = "subscribe_reorgs",
+        unsubscribe = "unsubscribe_reorgs",
+        item = Reorg
+    )]
+    async fn subscribe_reorgs(&self) -> SubscriptionResult;
+}
+
+/// OpenRPC specification version of the generated OpenRPC document
+const OPENRPC_VERSION: &str = "1.3.2";
+
+/** Generate an OpenRPC document for the RPC API, from the same method
+ *  metadata as the OpenAPI document.
+ *  Each RPC method is represented in the OpenAPI document as a path named
+ *  after the method, with the method params as properties of the request
+ *  body, and the method result as the response body. */
+pub fn openrpc_schema() -> serde_json::Value {
+    use serde_json::{json, Value};
+    let openapi = serde_json::to_value(<RpcDoc as utoipa::OpenApi>::openapi())
+        .expect("OpenAPI document should serialize to JSON");
+    // Use an empty schema (accepts any value) if a schema is missing
+    let schema_or_any = |schema: &Value| {
+        if schema.is_null() {
+            json!({})
+        } else {
+            schema.clone()
+        }
+    };
+    let mut methods = Vec::new();
+    if let Some(paths) = openapi["paths"].as_object() {
+        for (path, path_item) in paths {
+            let Some(operations) = path_item.as_object() else {
+                continue;
+            };
+            let name = path.trim_start_matches('/');
+            for operation in operations.values() {
+                let params_schema = &operation["requestBody"]["content"]
+                    ["application/json"]["schema"];
+                let required_params: Vec<&str> = params_schema["required"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .collect();
+                let params: Vec<Value> = params_schema["properties"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(param_name, param_schema)| {
+                        json!({
+                            "name": param_name,
+                            "required": required_params
+                                .contains(&param_name.as_str()),
+                            "schema": param_schema,
+                        })
+                    })
+                    .collect();
+                let result_schema = &operation["responses"]["200"]["content"]
+                    ["application/json"]["schema"];
+                let mut method = json!({
+                    "name": name,
+                    "params": params,
+                    "paramStructure": "by-name",
+                    "result": {
+                        "name": format!("{name}_result"),
+                        "schema": schema_or_any(result_schema),
+                    },
+                });
+                for field in ["summary", "description"] {
+                    if let Some(text) = operation[field].as_str() {
+                        method[field] = Value::from(text);
+                    }
+                }
+                methods.push(method);
+            }
+        }
+    }
+    json!({
+        "openrpc": OPENRPC_VERSION,
+        "info": openapi["info"],
+        "methods": methods,
+        "components": {
+            "schemas": schema_or_any(&openapi["components"]["schemas"]),
+        },
+    })
+}
@@ -0,0 +1,78 @@
+//! Check the structure of the generated OpenRPC document.
+
+use std::collections::HashSet;
+
+use plain_bitassets_app_rpc_api::openrpc_schema;
+use serde_json::Value;
+
+/// Collect the targets of all `$ref`s in a JSON value
+fn collect_refs<'a>(value: &'a Value, refs: &mut HashSet<&'a str>) {
+    match value {
+        Value::Array(items) => {
+            items.iter().for_each(|item| collect_refs(item, refs))
+        }
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                match field.as_str() {
+                    Some(target) if key == "$ref" => {
+                        refs.insert(target);
+                    }
+                    _ => collect_refs(field, refs),
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+#[test]
+fn openrpc_document_structure() {
+    let doc = openrpc_schema();
+    assert_eq!(doc["openrpc"], "1.3.2");
+    assert!(doc["info"]["title"].is_string());
+    assert!(doc["info"]["version"].is_string());
+    let methods = doc["methods"].as_array().unwrap();
+    assert!(!methods.is_empty());
+    let mut names = HashSet::new();
+    for method in methods {
+        let name = method["name"].as_str().unwrap();
+        assert!(!name.is_empty() && !name.starts_with('/'), "{name}");
+        assert!(names.insert(name), "duplicate method {name}");
+        assert_eq!(method["paramStructure"], "by-name", "{name}");
+        let mut param_names = HashSet::new();
+        for param in method["params"].as_array().unwrap() {
+            let param_name = param["name"].as_str().unwrap();
+            assert!(param_names.insert(param_name), "{name}: {param_name}");
+            assert!(param["required"].is_boolean(), "{name}: {param_name}");
+            assert!(param["schema"].is_object(), "{name}: {param_name}");
+        }
+        assert_eq!(method["result"]["name"], format!("{name}_result"));
+        assert!(method["result"]["schema"].is_object(), "{name}");
+    }
+    for name in ["get_block", "openapi_schema", "openrpc_schema"] {
+        assert!(names.contains(name), "missing method {name}");
+    }
+    let get_block = methods
+        .iter()
+        .find(|method| method["name"] == "get_block")
+        .unwrap();
+    let params = get_block["params"].as_array().unwrap();
+    assert_eq!(params.len(), 1);
+    assert_eq!(params[0]["name"], "block_hash");
+    assert_eq!(params[0]["required"], true);
+    // All schema references resolve to component schemas
+    let schemas = doc["components"]["schemas"].as_object().unwrap();
+    let mut refs = HashSet::new();
+    collect_refs(&doc["methods"], &mut refs);
+    collect_refs(&doc["components"], &mut refs);
+    assert!(!refs.is_empty());
+    for target in refs {
+        let schema_name = target
+            .strip_prefix("#/components/schemas/")
+            .unwrap_or_else(|| panic!("unexpected reference {target}"));
+        assert!(
+            schemas.contains_key(schema_name),
+            "unresolved reference {target}"
+        );
+    }
+}